@@ -1,9 +1,17 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use bytestring::ByteString;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use rutin::{frame::Resp3, server::Handler};
+use rutin::{
+    frame::Resp3,
+    server::Handler,
+    shared::{
+        db::{Db, ObjectInner},
+        Shared,
+    },
+    Key,
+};
 use smallvec::{smallvec, SmallVec};
 
 fn gen_get_cmd(key: &'static str) -> Resp3 {
@@ -21,6 +29,34 @@ fn gen_set_cmd(key: &'static str, value: &'static str) -> Resp3 {
     ])
 }
 
+fn gen_expire_cmd(key: &'static str, seconds: &'static str, opt: Option<&'static str>) -> Resp3 {
+    let mut args = vec![
+        Resp3::<Bytes, ByteString>::new_blob_string("EXPIRE".into()),
+        Resp3::new_blob_string(key.into()),
+        Resp3::new_blob_string(seconds.into()),
+    ];
+    if let Some(opt) = opt {
+        args.push(Resp3::new_blob_string(opt.into()));
+    }
+    Resp3::new_array(args)
+}
+
+fn gen_subscribe_cmd(channel: &'static str) -> Resp3 {
+    Resp3::new_array(vec![
+        Resp3::<Bytes, ByteString>::new_blob_string("SUBSCRIBE".into()),
+        Resp3::new_blob_string(channel.into()),
+    ])
+}
+
+fn gen_publish_cmd(channel: &'static str, msg: &'static str) -> Resp3 {
+    Resp3::new_array(vec![
+        Resp3::<Bytes, ByteString>::new_blob_string("PUBLISH".into()),
+        Resp3::new_blob_string(channel.into()),
+        Resp3::new_blob_string(msg.into()),
+    ])
+}
+
+#[allow(dead_code)]
 fn bench_vec(c: &mut Criterion) {
     let mut group = c.benchmark_group("dispatch");
 
@@ -63,5 +99,207 @@ fn bench_dispatch(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_dispatch);
+fn bench_multi_get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_get");
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let db = Db::default();
+    let keys: Vec<Key> = (0..100).map(|i| Key::from(format!("key{i}"))).collect();
+    rt.block_on(async {
+        for key in keys.iter() {
+            db.insert_object(key.clone(), ObjectInner::new_str("value", None))
+                .await;
+        }
+    });
+
+    group.bench_function("multi_get", |b| {
+        b.to_async(&rt).iter(|| async {
+            black_box(
+                db.multi_get(black_box(&keys), |obj| obj.on_str().unwrap().to_bytes())
+                    .await,
+            );
+        });
+    });
+
+    group.bench_function("sequential_get", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut res = Vec::with_capacity(keys.len());
+            for key in black_box(&keys) {
+                let mut value = None;
+                let _ = db
+                    .visit_object(key, |obj| {
+                        value = Some(obj.on_str()?.to_bytes());
+                        Ok(())
+                    })
+                    .await;
+                res.push(value);
+            }
+            black_box(res);
+        });
+    });
+
+    group.finish();
+}
+
+// 向订阅了同一频道的多个客户端发布消息，直到所有客户端都收到全部消息
+fn bench_publish_fanout(c: &mut Criterion) {
+    const SUBSCRIBERS: usize = 16;
+
+    c.bench_function("publish_fanout", |b| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        b.to_async(rt).iter_custom(|iters| async move {
+            let shared = Shared::default();
+
+            let mut readers = Vec::with_capacity(SUBSCRIBERS);
+            for _ in 0..SUBSCRIBERS {
+                let (mut handler, mut client) = Handler::new_memory_with(shared.clone());
+                tokio::spawn(async move { handler.run().await });
+
+                client
+                    .write_frame(&gen_subscribe_cmd("channel"))
+                    .await
+                    .unwrap();
+                client.read_frame().await.unwrap().unwrap();
+
+                readers.push(tokio::spawn(async move {
+                    for _ in 0..iters {
+                        client.read_frame().await.unwrap().unwrap();
+                    }
+                }));
+            }
+
+            let (mut publisher, _client) = Handler::with_shared(shared);
+            let start = Instant::now();
+            for _ in 0..iters {
+                publisher
+                    .dispatch(gen_publish_cmd(black_box("channel"), black_box("message")))
+                    .await
+                    .unwrap();
+            }
+            for reader in readers {
+                reader.await.unwrap();
+            }
+            start.elapsed()
+        })
+    });
+}
+
+// 比较共享整数范围内的值(以Str::Int内联存储)与相同长度的非整数值的SET开销
+fn bench_set_int(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_int");
+
+    for (name, value) in [("int", "1234"), ("raw", "abcd")] {
+        group.bench_function(name, |b| {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            b.to_async(rt).iter_custom(|iters| async move {
+                let (mut handler, _client) = Handler::new_fake();
+                let start = Instant::now();
+                for _ in 0..iters {
+                    handler
+                        .dispatch(gen_set_cmd(black_box("key"), black_box(value)))
+                        .await
+                        .unwrap()
+                        .unwrap();
+                }
+                start.elapsed()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// 多个连接并发地对同一个键执行EXPIRE。不需要更新的EXPIRE GT只需获取读锁，而不带选项的
+// EXPIRE每次都需要获取写锁
+fn bench_conditional_expire(c: &mut Criterion) {
+    const CLIENTS: usize = 8;
+
+    let mut group = c.benchmark_group("conditional_expire");
+
+    for (name, opt) in [("gt_noop", Some("GT")), ("unconditional", None)] {
+        group.bench_function(name, |b| {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            b.to_async(rt).iter_custom(|iters| async move {
+                let shared = Shared::default();
+                // 原有的过期时间大于新的过期时间，因此EXPIRE GT不需要更新
+                shared
+                    .db()
+                    .insert_object(
+                        Key::from("key"),
+                        ObjectInner::new_str(
+                            "value",
+                            Some(tokio::time::Instant::now() + Duration::from_secs(3600)),
+                        ),
+                    )
+                    .await;
+
+                let start = Instant::now();
+                let tasks: Vec<_> = (0..CLIENTS)
+                    .map(|_| {
+                        let (mut handler, _client) = Handler::with_shared(shared.clone());
+                        tokio::spawn(async move {
+                            for _ in 0..iters {
+                                handler
+                                    .dispatch(gen_expire_cmd(
+                                        black_box("key"),
+                                        black_box("10"),
+                                        opt,
+                                    ))
+                                    .await
+                                    .unwrap();
+                            }
+                        })
+                    })
+                    .collect();
+                for task in tasks {
+                    task.await.unwrap();
+                }
+                start.elapsed()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// 通过内存管道一次性发送一批GET命令，直到收到所有回复。回复在批处理结束时才一起写出
+fn bench_pipeline(c: &mut Criterion) {
+    const PIPELINE: usize = 64;
+
+    c.bench_function("pipeline", |b| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        b.to_async(rt).iter_custom(|iters| async move {
+            let (mut handler, mut client) = Handler::new_memory();
+            tokio::spawn(async move { handler.run().await });
+
+            let mut pipeline = BytesMut::new();
+            for _ in 0..PIPELINE {
+                gen_get_cmd("key").encode_buf(&mut pipeline);
+            }
+
+            let start = Instant::now();
+            for _ in 0..iters {
+                client.write_all(black_box(&pipeline)).await.unwrap();
+                for _ in 0..PIPELINE {
+                    black_box(client.read_frame().await.unwrap().unwrap());
+                }
+            }
+            start.elapsed()
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_vec,
+    bench_dispatch,
+    bench_multi_get,
+    bench_publish_fanout,
+    bench_set_int,
+    bench_conditional_expire,
+    bench_pipeline
+);
 criterion_main!(benches);
@@ -5,7 +5,7 @@ use crate::{
     connection::AsyncStream,
     frame::Resp3,
     server::Handler,
-    CmdFlag,
+    CmdFlag, Int,
 };
 use bytes::Bytes;
 use bytestring::ByteString;
@@ -24,6 +24,7 @@ impl CmdExecutor for AclCat {
     const NAME: &'static str = "ACLCAT";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = ACLCAT_FLAG;
+    const ARITY: Int = -2;
 
     #[instrument(level = "debug", skip(_handler), ret, err)]
     async fn execute(
@@ -77,6 +78,7 @@ impl CmdExecutor for AclDelUser {
     const NAME: &'static str = "ACLDELUSER";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = ACLDELUSER_FLAG;
+    const ARITY: Int = -3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -115,11 +117,13 @@ impl CmdExecutor for AclDelUser {
 ///
 /// RESET代表清空
 ///
-/// ```
+/// ```text
 /// ACL SETUSER <name> [enable | disable]  [PWD <password>] [ALLOWCMD <cmd>,...]
 /// [DENYCMD <cmd>,...] [ALLOWCAT <category>,...] [DENYCAT <category>,...] [DENYRKEY <readable key>,...]
-/// [DENYWKEY <writeable key>,...] [DENYCHANNEL <channel>,...]
+/// [DENYWKEY <writeable key>,...] [DENYCHANNEL <channel>,...] [clearselectors] [(<selector rules>) ...]
 /// ```
+///
+/// selector是以括号包围的备选权限集合，例如`(+GET ~cache:*)`，规则见[`AccessControl::parse_selector`]
 #[derive(Debug)]
 pub struct AclSetUser {
     pub name: Bytes,
@@ -130,6 +134,7 @@ impl CmdExecutor for AclSetUser {
     const NAME: &'static str = "ACLSETUSER";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = ACLSETUSER_FLAG;
+    const ARITY: Int = -3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -180,13 +185,30 @@ impl CmdExecutor for AclSetUser {
             match b.as_ref() {
                 b"enable" => aci.enable = Some(true),
                 b"disable" => aci.enable = Some(false),
-                b"PWD" => aci.password = Some(args.next().unwrap()),
+                b"PWD" => aci.password = Some(args.next().ok_or(Err::Syntax)?),
+                b"clearselectors" => aci
+                    .selectors
+                    .get_or_insert_with(Vec::new)
+                    .push(Bytes::from_static(b"RESET")),
+                // selector可能被拆分为多个参数，例如`(+GET`与`~cache:*)`
+                [b'(', ..] => {
+                    let mut selector = b.to_vec();
+                    while !selector.ends_with(b")") {
+                        selector.push(b' ');
+                        selector.extend_from_slice(&args.next().ok_or(Err::Syntax)?);
+                    }
+
+                    aci.selectors
+                        .get_or_insert_with(Vec::new)
+                        .push(Bytes::copy_from_slice(&selector[1..selector.len() - 1]));
+                }
                 // collect 从ALLOWCMD开始直到某个cmd的末尾不带','则结束
                 b"ALLOWCMD" => {
                     let mut allow_commands = Vec::with_capacity(10);
                     for mut b in args.by_ref() {
                         if b.last().is_some_and(|b| *b == b',') {
-                            allow_commands.push(b.split_off(b.len() - 1));
+                            b.truncate(b.len() - 1);
+                            allow_commands.push(b);
                         } else {
                             allow_commands.push(b);
                             break;
@@ -198,7 +220,8 @@ impl CmdExecutor for AclSetUser {
                     let mut deny_commands = Vec::with_capacity(10);
                     for mut b in args.by_ref() {
                         if b.last().is_some_and(|b| *b == b',') {
-                            deny_commands.push(b.split_off(b.len() - 1));
+                            b.truncate(b.len() - 1);
+                            deny_commands.push(b);
                         } else {
                             deny_commands.push(b);
                             break;
@@ -210,7 +233,8 @@ impl CmdExecutor for AclSetUser {
                     let mut allow_categories = Vec::with_capacity(10);
                     for mut b in args.by_ref() {
                         if b.last().is_some_and(|b| *b == b',') {
-                            allow_categories.push(b.split_off(b.len() - 1));
+                            b.truncate(b.len() - 1);
+                            allow_categories.push(b);
                         } else {
                             allow_categories.push(b);
                             break;
@@ -222,7 +246,8 @@ impl CmdExecutor for AclSetUser {
                     let mut deny_categories = Vec::with_capacity(10);
                     for mut b in args.by_ref() {
                         if b.last().is_some_and(|b| *b == b',') {
-                            deny_categories.push(b.split_off(b.len() - 1));
+                            b.truncate(b.len() - 1);
+                            deny_categories.push(b);
                         } else {
                             deny_categories.push(b);
                             break;
@@ -296,6 +321,7 @@ impl CmdExecutor for AclUsers {
     const NAME: &'static str = "ACLUSERS";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = ACLUSERS_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -325,6 +351,7 @@ impl CmdExecutor for AclWhoAmI {
     const NAME: &'static str = "ACLWHOAMI";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = ACLWHOAMI_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -339,6 +366,90 @@ impl CmdExecutor for AclWhoAmI {
     }
 }
 
+/// # Reply:
+///
+/// Simple string reply: OK. The command may fail with an error if no ACL file is configured or the file could not be written.
+///
+/// # Tips:
+///
+/// 将所有用户的规则写入security.aclfile，文件中的每一行都可以作为ACL SETUSER的参数
+#[derive(Debug)]
+pub struct AclSave;
+
+impl CmdExecutor for AclSave {
+    const NAME: &'static str = "ACLSAVE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = ACLSAVE_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let security = &handler.shared.conf().security;
+        if security.aclfile.is_none() {
+            return Err("ERR This instance is not configured to use an ACL file".into());
+        }
+
+        security
+            .save_acl()
+            .map_err(|e| format!("ERR There was an error trying to save the ACLs: {e}"))?;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(AclSave)
+    }
+}
+
+/// # Reply:
+///
+/// Simple string reply: OK on success. The command may fail with an error if no ACL file is configured or the file contains invalid rules.
+///
+/// # Tips:
+///
+/// 使用security.aclfile中的规则替换内存中的所有用户，文件有误时不修改任何用户
+#[derive(Debug)]
+pub struct AclLoad;
+
+impl CmdExecutor for AclLoad {
+    const NAME: &'static str = "ACLLOAD";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = ACLLOAD_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let security = &handler.shared.conf().security;
+        if security.aclfile.is_none() {
+            return Err("ERR This instance is not configured to use an ACL file".into());
+        }
+
+        security
+            .load_acl()
+            .map_err(|e| format!("ERR Error loading ACLs: {e}"))?;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(AclLoad)
+    }
+}
+
 #[tokio::test]
 async fn cmd_acl_tests() {
     crate::util::test_init();
@@ -513,3 +624,262 @@ async fn cmd_acl_tests() {
         &vec![Resp3::new_blob_string("default_ac".into()),]
     );
 }
+
+#[tokio::test]
+async fn acl_save_load_test() {
+    use crate::{
+        cmd::dispatch,
+        conf::{Conf, SecurityConf},
+        shared::{db::Db, Shared},
+        util::cmd,
+    };
+    use std::sync::Arc;
+
+    crate::util::test_init();
+
+    let ok = Some(Resp3::new_simple_string("OK".into()));
+
+    // 未配置aclfile时返回错误
+    let (mut handler, _) = Handler::new_fake();
+    let res = dispatch(cmd(&["ACL", "SAVE"]), &mut handler)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(res.try_simple_error().is_some());
+
+    let aclfile = std::env::temp_dir().join("rutin_acl_save_load_test.acl");
+    let conf = Conf {
+        security: SecurityConf {
+            aclfile: Some(aclfile.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let shared = Shared::new(Arc::new(Db::default()), Arc::new(conf), Default::default());
+    let (mut handler, _) = Handler::new_fake_with(shared, None, None);
+
+    let setuser = [
+        cmd(&[
+            "ACL",
+            "SETUSER",
+            "reader",
+            "enable",
+            "PWD",
+            "pass word",
+            "ALLOWCAT",
+            "read,",
+            "string",
+            "DENYCMD",
+            "keys",
+            "DENYRKEY",
+            r"secret\d+,",
+            "private",
+        ]),
+        cmd(&[
+            "ACL",
+            "SETUSER",
+            "writer",
+            "enable",
+            "ALLOWCMD",
+            "ALL",
+            "DENYCAT",
+            "hash",
+            "DENYCHANNEL",
+            "admin*",
+        ]),
+        cmd(&["ACL", "SETUSER", "default_ac", "DENYCMD", "del"]),
+    ];
+    for c in setuser {
+        assert_eq!(dispatch(c, &mut handler).await.unwrap(), ok);
+    }
+
+    let conf = handler.shared.conf().clone();
+    let security = &conf.security;
+    let acl = security.acl.as_ref().unwrap();
+    let snapshot = |name: &str| {
+        let ac = acl.get(&Bytes::copy_from_slice(name.as_bytes())).unwrap();
+        AccessControl::clone(&ac)
+    };
+    let (reader, writer) = (snapshot("reader"), snapshot("writer"));
+    let default_ac = security.default_ac.load_full();
+
+    assert_eq!(
+        dispatch(cmd(&["ACL", "SAVE"]), &mut handler).await.unwrap(),
+        ok
+    );
+
+    // 清空后重新加载
+    acl.clear();
+    security
+        .default_ac
+        .store(Arc::new(AccessControl::new_strict()));
+    assert_eq!(
+        dispatch(cmd(&["ACL", "LOAD"]), &mut handler).await.unwrap(),
+        ok
+    );
+
+    let assert_same = |a: &AccessControl, b: &AccessControl| {
+        assert_eq!(a.enable, b.enable);
+        assert_eq!(a.password, b.password);
+        for i in 0..CmdFlag::BITS {
+            let flag = 1 << i;
+            if flag_to_cmd_names(flag).is_ok() {
+                assert_eq!(
+                    a.is_forbidden_cmd(flag),
+                    b.is_forbidden_cmd(flag),
+                    "bit {i}"
+                );
+            }
+        }
+        let patterns = |set: &Option<regex::bytes::RegexSet>| {
+            set.as_ref().map(|set| {
+                let mut p = set.patterns().to_vec();
+                p.sort();
+                p
+            })
+        };
+        assert_eq!(
+            patterns(&a.deny_read_key_patterns),
+            patterns(&b.deny_read_key_patterns)
+        );
+        assert_eq!(
+            patterns(&a.deny_write_key_patterns),
+            patterns(&b.deny_write_key_patterns)
+        );
+        assert_eq!(
+            patterns(&a.deny_channel_patterns),
+            patterns(&b.deny_channel_patterns)
+        );
+    };
+    assert_same(&snapshot("reader"), &reader);
+    assert_same(&snapshot("writer"), &writer);
+    assert_same(&security.default_ac.load(), &default_ac);
+
+    let reader = snapshot("reader");
+    assert!(reader.is_pwd_correct(&"pass word".into()));
+    assert!(!reader.is_forbidden_cmd(Get::FLAG));
+    assert!(!reader.is_forbidden_cmd(Set::FLAG));
+    assert!(reader.is_forbidden_cmd(Keys::FLAG));
+    assert!(reader.is_forbidden_cmd(HSet::FLAG));
+    assert!(reader.is_forbidden_key(b"secret1", CmdType::Read));
+    let writer = snapshot("writer");
+    assert!(writer.is_forbidden_cmd(HGet::FLAG));
+    assert!(!writer.is_forbidden_cmd(Set::FLAG));
+    assert!(writer.is_forbidden_channel(b"admin1"));
+
+    // 文件有误时返回错误，且不修改任何用户
+    std::fs::write(
+        &aclfile,
+        "user someone enable\nuser broken ALLOWCAT nosuchcat\n",
+    )
+    .unwrap();
+    let res = dispatch(cmd(&["ACL", "LOAD"]), &mut handler)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(res.try_simple_error().is_some());
+    assert!(acl.get(&"someone".into()).is_none());
+    assert!(acl.get(&"reader".into()).is_some());
+
+    std::fs::remove_file(&aclfile).unwrap();
+}
+
+#[tokio::test]
+async fn acl_selector_test() {
+    use crate::{cmd::dispatch, util::cmd};
+    use std::sync::Arc;
+
+    crate::util::test_init();
+
+    let ok = Some(Resp3::new_simple_string("OK".into()));
+
+    let (mut handler, _) = Handler::new_fake();
+    for c in [
+        cmd(&["SET", "cache:1", "v1"]),
+        cmd(&["SET", "other", "v2"]),
+        // 基本规则只允许SET，selector允许GET以cache:开头的键。selector可以被拆分为多个参数
+        cmd(&[
+            "ACL",
+            "SETUSER",
+            "cache_reader",
+            "enable",
+            "ALLOWCMD",
+            "set",
+            "(+GET",
+            "~cache:*)",
+        ]),
+    ] {
+        assert_eq!(dispatch(c, &mut handler).await.unwrap(), ok);
+    }
+
+    let user = handler
+        .shared
+        .conf()
+        .security
+        .acl
+        .as_ref()
+        .unwrap()
+        .get(&"cache_reader".into())
+        .map(|ac| AccessControl::clone(&ac))
+        .unwrap();
+    assert_eq!(user.selectors.len(), 1);
+    handler.context.ac = Arc::new(user.clone());
+
+    let is_noperm = |res: Option<Resp3>| {
+        res.unwrap()
+            .try_simple_error()
+            .is_some_and(|e| e.starts_with("NOPERM"))
+    };
+
+    assert_eq!(
+        dispatch(cmd(&["GET", "cache:1"]), &mut handler)
+            .await
+            .unwrap(),
+        Some(Resp3::new_blob_string("v1".into()))
+    );
+    assert!(is_noperm(
+        dispatch(cmd(&["GET", "other"]), &mut handler)
+            .await
+            .unwrap()
+    ));
+    // 基本规则允许SET任意键
+    assert_eq!(
+        dispatch(cmd(&["SET", "other", "v3"]), &mut handler)
+            .await
+            .unwrap(),
+        ok
+    );
+    assert!(is_noperm(
+        dispatch(cmd(&["HGET", "cache:1", "f"]), &mut handler)
+            .await
+            .unwrap()
+    ));
+
+    // selector在ACL SAVE的规则中以括号包围，解析后得到相同的权限
+    let rules = user.to_rules();
+    assert!(rules.contains(&Bytes::from("(+GET ~cache:*)")));
+    let mut args = vec![Bytes::from("cache_reader")];
+    args.extend(rules);
+    let AclSetUser { aci, .. } =
+        AclSetUser::parse(&mut args.into(), &AccessControl::new_loose()).unwrap();
+    let restored = AccessControl::try_from(aci).unwrap();
+    assert_eq!(restored.to_rules(), user.to_rules());
+
+    // clearselectors清空所有selector
+    handler.context.ac = Arc::new(AccessControl::new_loose());
+    assert_eq!(
+        dispatch(
+            cmd(&["ACL", "SETUSER", "cache_reader", "clearselectors"]),
+            &mut handler
+        )
+        .await
+        .unwrap(),
+        ok
+    );
+    let acl = handler.shared.conf().security.acl.as_ref().unwrap();
+    assert!(acl
+        .get(&"cache_reader".into())
+        .unwrap()
+        .selectors
+        .is_empty());
+}
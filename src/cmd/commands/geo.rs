@@ -0,0 +1,806 @@
+// GeoAdd
+// GeoDist
+// GeoPos
+// GeoSearch
+
+use super::*;
+use crate::{
+    cmd::{CmdError, CmdExecutor, CmdType, CmdUnparsed, Err},
+    conf::AccessControl,
+    connection::AsyncStream,
+    frame::Resp3,
+    server::Handler,
+    shared::db::{ObjValueType, ZSet, ZSetElem},
+    util::{self, atof, atoi},
+    CmdFlag, Int, Key,
+};
+use bytes::Bytes;
+use tracing::instrument;
+
+// 经纬度各使用26位，交错编码后得到52位的geohash，可以被f64精确表示，
+// 因此直接作为有序集合的分数
+const GEO_STEP: u32 = 26;
+const GEO_LON_MIN: f64 = -180.0;
+const GEO_LON_MAX: f64 = 180.0;
+// EPSG:3785限制的纬度范围
+const GEO_LAT_MIN: f64 = -85.05112878;
+const GEO_LAT_MAX: f64 = 85.05112878;
+// 与Redis保持一致
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+
+/// 将一个或多个地理位置(经度、纬度、成员)加入到有序集合中，分数为位置的geohash。
+/// NX表示只添加新成员，XX表示只更新已存在的成员，CH表示返回值包括被更新的成员
+/// # Reply:
+///
+/// **Integer reply:** When used without optional arguments, the number of elements
+/// added to the sorted set (excluding score updates). If the CH option is specified,
+/// the number of elements that were changed (added or updated).
+#[derive(Debug)]
+pub struct GeoAdd {
+    pub key: Key,
+    pub nx: bool,
+    pub xx: bool,
+    pub ch: bool,
+    pub points: Vec<(f64, f64, Bytes)>, // (longitude, latitude, member)
+}
+
+impl CmdExecutor for GeoAdd {
+    const NAME: &'static str = "GEOADD";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = GEOADD_FLAG;
+    const ARITY: Int = -5;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let (mut added, mut updated) = (0, 0);
+
+        let f = |zset: &mut ZSet| {
+            for (lon, lat, member) in self.points {
+                let score = geohash_encode(lon, lat) as f64;
+                match zset.score(&member) {
+                    Some(old) => {
+                        if self.nx || old == score {
+                            continue;
+                        }
+                        zset.remove_member(&member);
+                        zset.insert(ZSetElem::new(score, member));
+                        updated += 1;
+                    }
+                    None => {
+                        if self.xx {
+                            continue;
+                        }
+                        zset.insert(ZSetElem::new(score, member));
+                        added += 1;
+                    }
+                }
+            }
+        };
+
+        let db = handler.shared.db();
+        if self.xx {
+            // 只更新已存在的成员时，不创建新的键
+            match db
+                .update_object(&self.key, |obj| {
+                    f(obj.on_zset_mut()?);
+                    Ok(())
+                })
+                .await
+            {
+                Ok(()) | Err(CmdError::Null) => {}
+                Err(e) => return Err(e),
+            }
+        } else {
+            db.update_or_create_object(&self.key, ObjValueType::ZSet, |obj| {
+                f(obj.on_zset_mut()?);
+                Ok(())
+            })
+            .await?;
+        }
+
+        let count = if self.ch { added + updated } else { added };
+        Ok(Some(Resp3::new_integer(count)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let (mut nx, mut xx, mut ch) = (false, false, false);
+        let mut args = args.peekable();
+        while let Some(opt) = args.peek() {
+            if opt.eq_ignore_ascii_case(b"NX") {
+                nx = true;
+            } else if opt.eq_ignore_ascii_case(b"XX") {
+                xx = true;
+            } else if opt.eq_ignore_ascii_case(b"CH") {
+                ch = true;
+            } else {
+                break;
+            }
+            args.next();
+        }
+        if nx && xx {
+            return Err("ERR XX and NX options at the same time are not compatible".into());
+        }
+
+        let args: Vec<Bytes> = args.collect();
+        if args.is_empty() || !args.len().is_multiple_of(3) {
+            return Err(Err::Syntax.into());
+        }
+
+        let points = args
+            .chunks_exact(3)
+            .map(|chunk| {
+                let (lon, lat) = parse_lon_lat(&chunk[0], &chunk[1])?;
+                Ok((lon, lat, chunk[2].clone()))
+            })
+            .collect::<Result<_, CmdError>>()?;
+
+        Ok(GeoAdd {
+            key,
+            nx,
+            xx,
+            ch,
+            points,
+        })
+    }
+}
+
+/// 返回两个成员之间的距离，默认单位为米
+/// # Reply:
+///
+/// **Null reply:** one or both of the elements are missing.
+/// **Bulk string reply:** distance as a double (represented as a string) in the specified units.
+#[derive(Debug)]
+pub struct GeoDist {
+    pub key: Key,
+    pub member1: Bytes,
+    pub member2: Bytes,
+    pub unit: f64,
+}
+
+impl CmdExecutor for GeoDist {
+    const NAME: &'static str = "GEODIST";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = GEODIST_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut res = None;
+
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let zset = obj.on_zset()?;
+                let (Some(hash1), Some(hash2)) =
+                    (zset.score(&self.member1), zset.score(&self.member2))
+                else {
+                    return Ok(());
+                };
+
+                let (lon1, lat1) = geohash_decode(hash1 as u64);
+                let (lon2, lat2) = geohash_decode(hash2 as u64);
+                res = Some(geo_distance(lon1, lat1, lon2, lat2));
+                Ok(())
+            })
+            .await?;
+
+        let dist = res.ok_or(CmdError::Null)?;
+        Ok(Some(Resp3::new_blob_string(format_distance(
+            dist / self.unit,
+        ))))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 3 && args.len() != 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let member1 = args.next().unwrap();
+        let member2 = args.next().unwrap();
+        let unit = match args.next() {
+            Some(unit) => parse_unit(&unit)?,
+            None => 1.0,
+        };
+
+        Ok(GeoDist {
+            key,
+            member1,
+            member2,
+            unit,
+        })
+    }
+}
+
+/// 返回成员的经纬度。经纬度由geohash解码得到，因此与添加时的值存在细微误差
+/// # Reply:
+///
+/// **Array reply:** An array where each element is a two elements array representing
+/// longitude and latitude (x,y) of each member name passed as argument to the command.
+/// Non-existing elements are reported as Null reply elements of the array.
+#[derive(Debug)]
+pub struct GeoPos {
+    pub key: Key,
+    pub members: Vec<Bytes>,
+}
+
+impl CmdExecutor for GeoPos {
+    const NAME: &'static str = "GEOPOS";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = GEOPOS_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut res = vec![Resp3::Null; self.members.len()];
+
+        let visited = handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let zset = obj.on_zset()?;
+                for (i, member) in self.members.iter().enumerate() {
+                    if let Some(hash) = zset.score(member) {
+                        let (lon, lat) = geohash_decode(hash as u64);
+                        res[i] = coord_reply(lon, lat);
+                    }
+                }
+                Ok(())
+            })
+            .await;
+        match visited {
+            Ok(()) | Err(CmdError::Null) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Some(Resp3::new_array(res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(GeoPos {
+            key,
+            members: args.collect(),
+        })
+    }
+}
+
+/// 返回以某个成员或经纬度为中心，在给定半径的圆形或给定宽高的矩形范围内的成员。
+/// 查询时会遍历有序集合中的所有成员
+/// # Reply:
+///
+/// **Array reply:**
+/// - If no WITH* option is specified, an array of matched member names
+/// - If WITHCOORD, WITHDIST, or WITHHASH options are specified, the command returns
+///   an array of arrays, where each sub-array represents a single item:
+///     - The distance from the center as a floating point number, in the same unit
+///       specified in the radius.
+///     - The Geohash integer.
+///     - The coordinates as a two items x,y array (longitude,latitude).
+#[derive(Debug)]
+pub struct GeoSearch {
+    pub key: Key,
+    pub from: GeoSearchFrom,
+    pub by: GeoSearchBy,
+    pub unit: f64,
+    pub asc: Option<bool>,
+    pub count: Option<usize>,
+    pub any: bool,
+    pub with_coord: bool,
+    pub with_dist: bool,
+    pub with_hash: bool,
+}
+
+#[derive(Debug)]
+pub enum GeoSearchFrom {
+    Member(Bytes),
+    LonLat(f64, f64),
+}
+
+/// 距离均以米为单位
+#[derive(Debug)]
+pub enum GeoSearchBy {
+    Radius(f64),
+    Box(f64, f64), // (width, height)
+}
+
+impl CmdExecutor for GeoSearch {
+    const NAME: &'static str = "GEOSEARCH";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = GEOSEARCH_FLAG;
+    const ARITY: Int = -7;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        // (member, distance, geohash, (longitude, latitude))
+        let mut matched: Vec<(Bytes, f64, u64, (f64, f64))> = vec![];
+
+        let visited = handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let zset = obj.on_zset()?;
+
+                let (lon, lat) = match &self.from {
+                    GeoSearchFrom::LonLat(lon, lat) => (*lon, *lat),
+                    GeoSearchFrom::Member(member) => {
+                        let hash = zset
+                            .score(member)
+                            .ok_or("ERR could not decode requested zset member")?;
+                        geohash_decode(hash as u64)
+                    }
+                };
+
+                for elem in zset.iter() {
+                    // ANY表示找到足够数量的成员后立即返回
+                    if self.any && self.count.is_some_and(|count| matched.len() >= count) {
+                        break;
+                    }
+
+                    let hash = elem.score() as u64;
+                    let (x, y) = geohash_decode(hash);
+                    let dist = match self.by {
+                        GeoSearchBy::Radius(radius) => {
+                            let dist = geo_distance(lon, lat, x, y);
+                            if dist > radius {
+                                continue;
+                            }
+                            dist
+                        }
+                        GeoSearchBy::Box(width, height) => {
+                            let lat_dist =
+                                EARTH_RADIUS_IN_METERS * (y.to_radians() - lat.to_radians()).abs();
+                            if lat_dist > height / 2.0 || geo_distance(x, y, lon, y) > width / 2.0 {
+                                continue;
+                            }
+                            geo_distance(lon, lat, x, y)
+                        }
+                    };
+
+                    matched.push((elem.member().clone(), dist, hash, (x, y)));
+                }
+
+                Ok(())
+            })
+            .await;
+        match visited {
+            Ok(()) | Err(CmdError::Null) => {}
+            Err(e) => return Err(e),
+        }
+
+        // 指定COUNT但未指定ANY时，返回距离最近的成员
+        let asc = match self.asc {
+            None if self.count.is_some() && !self.any => Some(true),
+            asc => asc,
+        };
+        if let Some(asc) = asc {
+            matched.sort_by(|a, b| a.1.total_cmp(&b.1));
+            if !asc {
+                matched.reverse();
+            }
+        }
+        if let Some(count) = self.count {
+            matched.truncate(count);
+        }
+
+        let res = matched
+            .into_iter()
+            .map(|(member, dist, hash, (lon, lat))| {
+                if !self.with_coord && !self.with_dist && !self.with_hash {
+                    return Resp3::new_blob_string(member);
+                }
+
+                let mut item = vec![Resp3::new_blob_string(member)];
+                if self.with_dist {
+                    item.push(Resp3::new_blob_string(format_distance(dist / self.unit)));
+                }
+                if self.with_hash {
+                    item.push(Resp3::new_integer(hash as Int));
+                }
+                if self.with_coord {
+                    item.push(coord_reply(lon, lat));
+                }
+                Resp3::new_array(item)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Some(Resp3::new_array(res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 6 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let mut from = None;
+        let mut by = None;
+        let mut unit = 1.0;
+        let mut asc = None;
+        let mut count = None;
+        let mut any = false;
+        let (mut with_coord, mut with_dist, mut with_hash) = (false, false, false);
+
+        while let Some(opt) = args.next() {
+            let mut buf = [0; 10];
+            let len = util::uppercase(&opt, &mut buf).map_err(|_| Err::Syntax)?;
+
+            match &buf[..len] {
+                b"FROMMEMBER" | b"FROMLONLAT" => {
+                    if from.is_some() {
+                        return Err("ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH".into());
+                    }
+                    from = Some(if &buf[..len] == b"FROMMEMBER" {
+                        GeoSearchFrom::Member(args.next().ok_or(Err::Syntax)?)
+                    } else {
+                        let lon = args.next().ok_or(Err::Syntax)?;
+                        let lat = args.next().ok_or(Err::Syntax)?;
+                        let (lon, lat) = parse_lon_lat(&lon, &lat)?;
+                        GeoSearchFrom::LonLat(lon, lat)
+                    });
+                }
+                b"BYRADIUS" | b"BYBOX" => {
+                    if by.is_some() {
+                        return Err(
+                            "ERR exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH"
+                                .into(),
+                        );
+                    }
+                    let shape = if &buf[..len] == b"BYRADIUS" {
+                        GeoSearchBy::Radius(next_f64(args)?)
+                    } else {
+                        GeoSearchBy::Box(next_f64(args)?, next_f64(args)?)
+                    };
+                    unit = parse_unit(&args.next().ok_or(Err::Syntax)?)?;
+                    by = Some(match shape {
+                        GeoSearchBy::Radius(r) if r >= 0.0 => GeoSearchBy::Radius(r * unit),
+                        GeoSearchBy::Box(w, h) if w >= 0.0 && h >= 0.0 => {
+                            GeoSearchBy::Box(w * unit, h * unit)
+                        }
+                        _ => return Err("ERR radius cannot be negative".into()),
+                    });
+                }
+                b"ASC" => asc = Some(true),
+                b"DESC" => asc = Some(false),
+                b"COUNT" => {
+                    let n =
+                        atoi::<i64>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+                    if n <= 0 {
+                        return Err("ERR COUNT must be > 0".into());
+                    }
+                    count = Some(n as usize);
+                }
+                b"ANY" => any = true,
+                b"WITHCOORD" => with_coord = true,
+                b"WITHDIST" => with_dist = true,
+                b"WITHHASH" => with_hash = true,
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        let from = from
+            .ok_or("ERR exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH")?;
+        let by =
+            by.ok_or("ERR exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH")?;
+        if any && count.is_none() {
+            return Err("ERR the ANY argument requires COUNT argument".into());
+        }
+
+        Ok(GeoSearch {
+            key,
+            from,
+            by,
+            unit,
+            asc,
+            count,
+            any,
+            with_coord,
+            with_dist,
+            with_hash,
+        })
+    }
+}
+
+fn next_f64(args: &mut CmdUnparsed) -> Result<f64, CmdError> {
+    atof(&args.next().ok_or(Err::Syntax)?).map_err(|_| "ERR value is not a valid float".into())
+}
+
+fn parse_lon_lat(lon: &[u8], lat: &[u8]) -> Result<(f64, f64), CmdError> {
+    let err = || -> CmdError { "ERR value is not a valid float".into() };
+    let lon = atof(lon).map_err(|_| err())?;
+    let lat = atof(lat).map_err(|_| err())?;
+
+    if !(GEO_LON_MIN..=GEO_LON_MAX).contains(&lon) || !(GEO_LAT_MIN..=GEO_LAT_MAX).contains(&lat) {
+        return Err(format!("ERR invalid longitude,latitude pair {lon:.6},{lat:.6}").into());
+    }
+
+    Ok((lon, lat))
+}
+
+/// 返回单位对应的米数
+fn parse_unit(unit: &[u8]) -> Result<f64, CmdError> {
+    if unit.eq_ignore_ascii_case(b"M") {
+        Ok(1.0)
+    } else if unit.eq_ignore_ascii_case(b"KM") {
+        Ok(1000.0)
+    } else if unit.eq_ignore_ascii_case(b"FT") {
+        Ok(0.3048)
+    } else if unit.eq_ignore_ascii_case(b"MI") {
+        Ok(1609.34)
+    } else {
+        Err("ERR unsupported unit provided. please use M, KM, FT, MI".into())
+    }
+}
+
+fn format_distance(dist: f64) -> Bytes {
+    format!("{dist:.4}").into()
+}
+
+fn coord_reply(lon: f64, lat: f64) -> Resp3 {
+    Resp3::new_array(vec![
+        Resp3::new_blob_string(lon.to_string().into()),
+        Resp3::new_blob_string(lat.to_string().into()),
+    ])
+}
+
+/// 纬度位于偶数位，经度位于奇数位
+fn geohash_encode(lon: f64, lat: f64) -> u64 {
+    let max_offset = (1_u64 << GEO_STEP) - 1;
+    let offset = |v: f64, min: f64, max: f64| {
+        (((v - min) / (max - min) * (1_u64 << GEO_STEP) as f64) as u64).min(max_offset)
+    };
+    let lat_offset = offset(lat, GEO_LAT_MIN, GEO_LAT_MAX);
+    let lon_offset = offset(lon, GEO_LON_MIN, GEO_LON_MAX);
+
+    let mut hash = 0;
+    for i in 0..GEO_STEP {
+        hash |= ((lat_offset >> i) & 1) << (2 * i);
+        hash |= ((lon_offset >> i) & 1) << (2 * i + 1);
+    }
+    hash
+}
+
+/// 返回geohash所表示区域的中心点(经度, 纬度)
+fn geohash_decode(hash: u64) -> (f64, f64) {
+    let (mut lat_offset, mut lon_offset) = (0_u64, 0_u64);
+    for i in 0..GEO_STEP {
+        lat_offset |= ((hash >> (2 * i)) & 1) << i;
+        lon_offset |= ((hash >> (2 * i + 1)) & 1) << i;
+    }
+
+    let scale = (1_u64 << GEO_STEP) as f64;
+    let center = |offset: u64, min: f64, max: f64| {
+        let lo = min + offset as f64 / scale * (max - min);
+        let hi = min + (offset + 1) as f64 / scale * (max - min);
+        ((lo + hi) / 2.0).clamp(min, max)
+    };
+
+    (
+        center(lon_offset, GEO_LON_MIN, GEO_LON_MAX),
+        center(lat_offset, GEO_LAT_MIN, GEO_LAT_MAX),
+    )
+}
+
+/// 使用haversine公式计算两点间的球面距离(米)
+fn geo_distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2 - lat1) / 2.0).sin();
+    let v = ((lon2 - lon1).to_radians() / 2.0).sin();
+    2.0 * EARTH_RADIUS_IN_METERS * (u * u + lat1.cos() * lat2.cos() * v * v).sqrt().asin()
+}
+
+#[cfg(test)]
+mod cmd_geo_tests {
+    use super::*;
+    use crate::util::test_init;
+
+    fn parse_f64(res: &Resp3) -> f64 {
+        atof(res.try_blob().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn geo_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let geoadd = GeoAdd::parse(
+            &mut [
+                "Sicily",
+                "13.361389",
+                "38.115556",
+                "Palermo",
+                "15.087269",
+                "37.502669",
+                "Catania",
+            ]
+            .as_ref()
+            .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            geoadd.execute(&mut handler).await.unwrap(),
+            Some(Resp3::new_integer(2))
+        );
+
+        // 已知两地距离约为166274.1516米
+        let geodist = GeoDist::parse(
+            &mut ["Sicily", "Palermo", "Catania"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let dist = parse_f64(&geodist.execute(&mut handler).await.unwrap().unwrap());
+        assert!((dist - 166274.1516).abs() < 1.0, "{dist}");
+
+        let geodist = GeoDist::parse(
+            &mut ["Sicily", "Palermo", "Catania", "km"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let dist = parse_f64(&geodist.execute(&mut handler).await.unwrap().unwrap());
+        assert!((dist - 166.2742).abs() < 0.001, "{dist}");
+
+        // 成员不存在
+        let geodist = GeoDist::parse(
+            &mut ["Sicily", "Palermo", "Rome"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert!(matches!(
+            geodist.execute(&mut handler).await,
+            Err(CmdError::Null)
+        ));
+
+        let geopos = GeoPos::parse(
+            &mut ["Sicily", "Palermo", "Rome"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = geopos.execute(&mut handler).await.unwrap().unwrap();
+        let res = res.try_array().unwrap();
+        let pos = res[0].try_array().unwrap();
+        assert!((parse_f64(&pos[0]) - 13.361389).abs() < 1e-5);
+        assert!((parse_f64(&pos[1]) - 38.115556).abs() < 1e-5);
+        assert_eq!(res[1], Resp3::Null);
+
+        // XX不添加新成员，CH计入被更新的成员
+        let geoadd = GeoAdd::parse(
+            &mut [
+                "Sicily", "XX", "CH", "13.5", "38.1", "Palermo", "12.0", "37.0", "Trapani",
+            ]
+            .as_ref()
+            .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            geoadd.execute(&mut handler).await.unwrap(),
+            Some(Resp3::new_integer(1))
+        );
+
+        let geosearch = |args: &[&str]| {
+            GeoSearch::parse(&mut args.into(), &AccessControl::new_loose()).unwrap()
+        };
+
+        assert_eq!(
+            geosearch(&[
+                "Sicily",
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "200",
+                "km",
+                "ASC"
+            ])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap(),
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("Catania".into()),
+                Resp3::new_blob_string("Palermo".into()),
+            ])
+        );
+        assert_eq!(
+            geosearch(&["Sicily", "FROMLONLAT", "15", "37", "BYRADIUS", "100", "km",])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_array(vec![Resp3::new_blob_string("Catania".into())])
+        );
+
+        // COUNT未指定ANY时返回最近的成员
+        let res = geosearch(&[
+            "Sicily",
+            "FROMMEMBER",
+            "Palermo",
+            "BYBOX",
+            "400",
+            "400",
+            "km",
+            "COUNT",
+            "1",
+            "WITHDIST",
+            "WITHHASH",
+        ])
+        .execute(&mut handler)
+        .await
+        .unwrap()
+        .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 1);
+        let item = res[0].try_array().unwrap();
+        assert_eq!(item[0], Resp3::new_blob_string("Palermo".into()));
+        assert_eq!(parse_f64(&item[1]), 0.0);
+        assert!(item[2].try_integer().is_some());
+
+        // 键不存在
+        assert_eq!(
+            geosearch(&["nil", "FROMLONLAT", "15", "37", "BYRADIUS", "1", "m"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_array(vec![])
+        );
+
+        // 非法参数
+        for args in [
+            ["Sicily", "FROMMEMBER", "Palermo", "BYRADIUS", "1", "yard"].as_ref(),
+            ["Sicily", "FROMLONLAT", "200", "0", "BYRADIUS", "1", "m"].as_ref(),
+            ["Sicily", "BYRADIUS", "1", "m", "ANY", "WITHDIST"].as_ref(),
+            ["Sicily", "FROMMEMBER", "a", "BYRADIUS", "1", "m", "ANY"].as_ref(),
+        ] {
+            assert!(GeoSearch::parse(&mut args.into(), &AccessControl::new_loose()).is_err());
+        }
+        assert!(GeoAdd::parse(
+            &mut ["Sicily", "181", "0", "x"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+    }
+}
@@ -1,6 +1,10 @@
 // HDel
 // HExists
 // HGet
+// HGetAll
+// HGetDel
+// HGetEx
+// HRandField
 // HSet
 
 use super::*;
@@ -11,9 +15,12 @@ use crate::{
     frame::Resp3,
     server::Handler,
     shared::db::ObjValueType::Hash,
-    CmdFlag, Key,
+    util::{atoi, random_indices},
+    CmdFlag, Int, Key,
 };
+use ahash::AHashMap;
 use bytes::Bytes;
+use std::sync::atomic::Ordering;
 use tracing::instrument;
 
 /// **Integer reply:** The number of fields that were removed from the hash, excluding any specified but non-existing fields.
@@ -27,6 +34,8 @@ impl CmdExecutor for HDel {
     const NAME: &'static str = "HDEL";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = HDEL_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -82,6 +91,8 @@ impl CmdExecutor for HExists {
     const NAME: &'static str = "HEXISTS";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = HEXISTS_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -133,6 +144,8 @@ impl CmdExecutor for HGet {
     const NAME: &'static str = "HGET";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = HGET_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -172,6 +185,383 @@ impl CmdExecutor for HGet {
     }
 }
 
+/// 返回哈希表的所有字段和值。字段数量较多时，字段和值被逐个编码到写缓冲区，不会先构造
+/// 完整的回复
+/// # Reply:
+///
+/// **Map reply:** a map of fields and their values stored in the hash, or an empty list when key does not exist.
+#[derive(Debug)]
+pub struct HGetAll {
+    pub key: Key,
+}
+
+impl CmdExecutor for HGetAll {
+    const NAME: &'static str = "HGETALL";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = HGETALL_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut res = None;
+        // 流式回复需要等待IO，不能在持有锁时写出，因此先复制字段和值(只增加引用计数)
+        let mut streaming = None;
+
+        let db = handler.shared.db().clone();
+        let visit = db
+            .visit_object(&self.key, |obj| {
+                let hash = obj.on_hash()?;
+                let pairs = hash.iter().map(|(f, v)| (f.clone(), v.clone()));
+
+                if handler.should_stream_reply(hash.len()) {
+                    streaming = Some(pairs.collect::<Vec<_>>());
+                } else {
+                    res = Some(Resp3::new_map(
+                        pairs
+                            .map(|(f, v)| (Resp3::new_blob_string(f), Resp3::new_blob_string(v)))
+                            .collect::<AHashMap<_, _>>(),
+                    ));
+                }
+
+                Ok(())
+            })
+            .await;
+
+        match visit {
+            Ok(()) => {}
+            // 键不存在时返回空map
+            Err(CmdError::Null) => return Ok(Some(Resp3::new_map(AHashMap::new()))),
+            Err(e) => return Err(e),
+        }
+
+        if let Some(pairs) = streaming {
+            handler
+                .write_streaming_map(
+                    pairs.len(),
+                    pairs
+                        .into_iter()
+                        .map(|(f, v)| (Resp3::new_blob_string(f), Resp3::new_blob_string(v))),
+                )
+                .await?;
+        }
+
+        Ok(res)
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(HGetAll { key })
+    }
+}
+
+/// # Reply:
+///
+/// **Array reply:** a list of deleted fields and their values or nil for fields that do not exist.
+///
+/// # Desc:
+///
+/// HGETDEL key FIELDS numfields field [field ...]
+///
+/// 获取并删除指定的字段，字段全部被删除后键也会被删除
+#[derive(Debug)]
+pub struct HGetDel {
+    pub key: Key,
+    pub fields: Vec<Key>,
+}
+
+impl CmdExecutor for HGetDel {
+    const NAME: &'static str = "HGETDEL";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = HGETDEL_FLAG;
+    const ARITY: Int = -5;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut entry = handler.shared.db().get_object_entry_mut(self.key).await;
+        if !entry.is_object_existed() {
+            return Ok(Some(Resp3::new_array(vec![Resp3::Null; self.fields.len()])));
+        }
+
+        let mut values = Vec::with_capacity(self.fields.len());
+        let mut is_empty = false;
+        entry.update_object_value(|obj| {
+            let hash = obj.on_hash_mut()?;
+            for field in &self.fields {
+                values.push(
+                    hash.remove(field)
+                        .map_or(Resp3::Null, Resp3::new_blob_string),
+                );
+            }
+            is_empty = hash.is_empty();
+
+            Ok(())
+        })?;
+
+        // 字段全部被删除，则删除键
+        if is_empty {
+            entry.remove_object();
+        }
+
+        Ok(Some(Resp3::new_array(values)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(HGetDel {
+            key,
+            fields: parse_fields(args)?,
+        })
+    }
+}
+
+/// # Reply:
+///
+/// **Array reply:** a list of values associated with the given fields, in the same order as they are requested.
+///
+/// # Desc:
+///
+/// HGETEX key [PERSIST] FIELDS numfields field [field ...]
+///
+/// 获取指定字段的值。哈希字段不支持单独设置过期时间，因此EX、PX、EXAT、PXAT选项不在
+/// 支持范围内，会返回错误；PERSIST选项仅为兼容而保留(字段本身没有过期时间，不会产生
+/// 任何效果)
+#[derive(Debug)]
+pub struct HGetEx {
+    pub key: Key,
+    pub fields: Vec<Key>,
+}
+
+impl CmdExecutor for HGetEx {
+    const NAME: &'static str = "HGETEX";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = HGETEX_FLAG;
+    const ARITY: Int = -5;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut values = vec![Resp3::Null; self.fields.len()];
+
+        let res = handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let hash = obj.on_hash()?;
+                for (value, field) in values.iter_mut().zip(self.fields.iter()) {
+                    if let Some(v) = hash.get(field) {
+                        *value = Resp3::new_blob_string(v);
+                    }
+                }
+
+                Ok(())
+            })
+            .await;
+
+        match res {
+            Ok(()) | Err(CmdError::Null) => Ok(Some(Resp3::new_array(values))),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let opt = args.next().unwrap().to_ascii_uppercase();
+        match opt.as_slice() {
+            b"FIELDS" => {}
+            b"PERSIST" => {
+                let fields = args.next().ok_or(Err::Syntax)?;
+                if !fields.eq_ignore_ascii_case(b"FIELDS") {
+                    return Err(Err::Syntax.into());
+                }
+            }
+            b"EX" | b"PX" | b"EXAT" | b"PXAT" => {
+                return Err(
+                    "ERR HGETEX does not support hash field expiration, only PERSIST is accepted"
+                        .into(),
+                )
+            }
+            _ => return Err(Err::Syntax.into()),
+        }
+
+        let numfields = args.next().ok_or(Err::Syntax)?;
+        Ok(HGetEx {
+            key,
+            fields: parse_numfields(numfields, args)?,
+        })
+    }
+}
+
+/// 解析`FIELDS numfields field [field ...]`
+fn parse_fields(args: &mut CmdUnparsed) -> Result<Vec<Key>, CmdError> {
+    let fields = args.next().unwrap();
+    if !fields.eq_ignore_ascii_case(b"FIELDS") {
+        return Err(Err::Syntax.into());
+    }
+
+    let numfields = args.next().ok_or(Err::Syntax)?;
+    parse_numfields(numfields, args)
+}
+
+fn parse_numfields(numfields: Bytes, args: &mut CmdUnparsed) -> Result<Vec<Key>, CmdError> {
+    let numfields = atoi::<usize>(&numfields)?;
+    if numfields == 0 {
+        return Err("ERR numfields should be greater than 0".into());
+    }
+
+    let fields: Vec<Key> = args.collect();
+    if fields.len() != numfields {
+        return Err("ERR the numfields parameter must match the number of arguments".into());
+    }
+
+    Ok(fields)
+}
+
+/// # Reply:
+///
+/// **Bulk string reply:** without the additional count argument, the command returns a randomly selected field, or nil when key doesn't exist.
+/// **Array reply:** if the additional count argument is passed, the command returns an array of fields, or an empty array when key doesn't exist.
+/// **Array reply:** if the WITHVALUES modifier is used, the reply is a list of fields and their values from the hash. In RESP3 each field and its value are returned as a two-element array.
+///
+/// # Desc:
+///
+/// HRANDFIELD key [count [WITHVALUES]]
+///
+/// count为正数时返回不重复的字段，count为负数时返回|count|个可能重复的字段
+#[derive(Debug)]
+pub struct HRandField {
+    pub key: Key,
+    pub count: Option<Int>,
+    pub with_values: bool,
+}
+
+impl CmdExecutor for HRandField {
+    const NAME: &'static str = "HRANDFIELD";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = HRANDFIELD_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut picked = vec![];
+        let visit = handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let hash = obj.on_hash()?;
+                let pairs: Vec<_> = hash.iter().collect();
+                picked = random_indices(pairs.len(), self.count.unwrap_or(1))
+                    .into_iter()
+                    .map(|i| (pairs[i].0.clone(), pairs[i].1.clone()))
+                    .collect();
+                Ok(())
+            })
+            .await;
+
+        match visit {
+            Ok(()) => {}
+            // 键不存在时，不带count返回nil，带count返回空数组
+            Err(CmdError::Null) if self.count.is_some() => {
+                return Ok(Some(Resp3::new_array(vec![])))
+            }
+            Err(e) => return Err(e),
+        }
+
+        let reply = match self.count {
+            None => picked
+                .pop()
+                .map_or(Resp3::Null, |(f, _)| Resp3::new_blob_string(f)),
+            Some(_) if self.with_values => handler.new_pairs_reply(
+                picked
+                    .into_iter()
+                    .map(|(f, v)| (Resp3::new_blob_string(f), Resp3::new_blob_string(v))),
+            ),
+            Some(_) => Resp3::new_array(
+                picked
+                    .into_iter()
+                    .map(|(f, _)| Resp3::new_blob_string(f))
+                    .collect::<Vec<_>>(),
+            ),
+        };
+
+        Ok(Some(reply))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (key, count, with_values) = parse_rand_args(args, b"WITHVALUES")?;
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(HRandField {
+            key,
+            count,
+            with_values,
+        })
+    }
+}
+
+/// 解析`key [count [with]]`形式的参数，with为WITHVALUES或WITHSCORES
+pub(super) fn parse_rand_args(
+    args: &mut CmdUnparsed,
+    with: &[u8],
+) -> Result<(Key, Option<Int>, bool), CmdError> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(Err::WrongArgNum.into());
+    }
+
+    let key = args.next().unwrap();
+    let count = match args.next() {
+        Some(count) => Some(atoi::<Int>(&count)?),
+        None => None,
+    };
+    let with = match args.next() {
+        Some(arg) if arg.eq_ignore_ascii_case(with) => true,
+        Some(_) => return Err(Err::Syntax.into()),
+        None => false,
+    };
+
+    Ok((key, count, with))
+}
+
 /// **Integer reply:** the number of fields that were added.
 #[derive(Debug)]
 pub struct HSet {
@@ -183,6 +573,8 @@ impl CmdExecutor for HSet {
     const NAME: &'static str = "HSET";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = HSET_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -191,6 +583,12 @@ impl CmdExecutor for HSet {
     ) -> Result<Option<Resp3>, CmdError> {
         let mut count = 0;
 
+        let memory_conf = &handler.shared.conf().memory;
+        let max_entries = memory_conf
+            .hash_max_listpack_entries
+            .load(Ordering::Relaxed);
+        let max_value = memory_conf.hash_max_listpack_value.load(Ordering::Relaxed);
+
         handler
             .shared
             .db()
@@ -200,6 +598,7 @@ impl CmdExecutor for HSet {
                     hash.insert(field, value);
                     count += 1;
                 }
+                hash.convert_if_exceed(max_entries, max_value);
 
                 Ok(())
             })
@@ -232,7 +631,8 @@ impl CmdExecutor for HSet {
 #[cfg(test)]
 mod cmd_hash_tests {
     use super::*;
-    use crate::util::test_init;
+    use crate::util::{cmd, test_init};
+    use ahash::AHashSet;
 
     #[tokio::test]
     async fn hdel_test() {
@@ -273,6 +673,195 @@ mod cmd_hash_tests {
         );
     }
 
+    #[tokio::test]
+    async fn encoding_change_event_test() {
+        use crate::{
+            conf::{Conf, MemoryConf},
+            shared::{db::Db, Shared},
+        };
+        use std::sync::Arc;
+
+        test_init();
+
+        let conf = Conf {
+            memory: MemoryConf {
+                notify_encoding_change: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        conf.memory
+            .hash_max_listpack_entries
+            .store(2, Ordering::Relaxed);
+        let shared = Shared::new(Arc::new(Db::default()), Arc::new(conf), Default::default());
+        let (mut handler, _) = Handler::with_shared(shared.clone());
+        let (mut subscriber, _subscriber_client) = Handler::with_shared(shared);
+
+        subscriber
+            .dispatch(cmd(&["SUBSCRIBE", "__keyevent@0__:encoding-change"]))
+            .await
+            .unwrap();
+
+        // 元素数量不超过阈值时编码不变
+        handler
+            .dispatch(cmd(&["HSET", "hash", "f1", "v1", "f2", "v2"]))
+            .await
+            .unwrap();
+        assert!(subscriber.bg_task_channel.try_recv_from_bg_task().is_none());
+
+        // 超过阈值后由ziplist转换为hashtable
+        handler
+            .dispatch(cmd(&["HSET", "hash", "f3", "v3"]))
+            .await
+            .unwrap();
+        let msg = subscriber.bg_task_channel.try_recv_from_bg_task().unwrap();
+        assert_eq!(
+            msg.try_push().unwrap().to_vec(),
+            vec![
+                Resp3::new_blob_string("message".into()),
+                Resp3::new_blob_string("__keyevent@0__:encoding-change".into()),
+                Resp3::new_blob_string("hash".into()),
+            ]
+        );
+
+        // 编码已经是hashtable，之后的修改不再发布事件
+        handler
+            .dispatch(cmd(&["HSET", "hash", "f4", "v4"]))
+            .await
+            .unwrap();
+        assert!(subscriber.bg_task_channel.try_recv_from_bg_task().is_none());
+    }
+
+    #[tokio::test]
+    async fn hgetall_test() {
+        test_init();
+        let (mut handler, mut client) = Handler::new_fake();
+
+        let hset = HSet::parse(
+            &mut ["small", "field1", "value1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        hset.execute(&mut handler).await.unwrap();
+
+        let hgetall =
+            HGetAll::parse(&mut ["small"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            hgetall.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_map(AHashMap::from([(
+                Resp3::new_blob_string("field1".into()),
+                Resp3::new_blob_string("value1".into()),
+            )]))
+        );
+
+        // 字段较多时逐个写入连接，RESP2下map被展开为数组
+        let fields: Vec<String> = (0..2000).map(|i| format!("field{i}")).collect();
+        let mut args = vec!["big"];
+        for f in &fields {
+            args.extend([f.as_str(), f.as_str()]);
+        }
+        let hset = HSet::parse(&mut args.as_slice().into(), &AccessControl::new_loose()).unwrap();
+        hset.execute(&mut handler).await.unwrap();
+
+        let hgetall =
+            HGetAll::parse(&mut ["big"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        handler.context.direct_reply = true;
+        assert!(hgetall.execute(&mut handler).await.unwrap().is_none());
+        handler.conn.write_buffered().await.unwrap();
+
+        let res = client.read_frame().await.unwrap().unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 4000);
+        for pair in res.chunks(2) {
+            assert_eq!(pair[0], pair[1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn hgetdel_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let hset = HSet::parse(
+            &mut [
+                "key", "field1", "value1", "field2", "value2", "field3", "value3",
+            ]
+            .as_ref()
+            .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        hset.execute(&mut handler).await.unwrap();
+
+        let hgetdel = HGetDel::parse(
+            &mut ["key", "FIELDS", "2", "field1", "field_nil"]
+                .as_ref()
+                .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            hgetdel.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_array(vec![Resp3::new_blob_string("value1".into()), Resp3::Null])
+        );
+
+        // 只删除指定的字段
+        let hgetex = HGetEx::parse(
+            &mut ["key", "FIELDS", "3", "field1", "field2", "field3"]
+                .as_ref()
+                .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            hgetex.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_array(vec![
+                Resp3::Null,
+                Resp3::new_blob_string("value2".into()),
+                Resp3::new_blob_string("value3".into()),
+            ])
+        );
+
+        // 字段全部被删除后，键也被删除
+        let hgetdel = HGetDel::parse(
+            &mut ["key", "fields", "2", "field2", "field3"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        hgetdel.execute(&mut handler).await.unwrap();
+        assert!(!handler.shared.db().contains_object(&Key::from("key")).await);
+
+        let hgetdel = HGetDel::parse(
+            &mut ["key", "FIELDS", "1", "field1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            hgetdel.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_array(vec![Resp3::Null])
+        );
+
+        // numfields与字段数量不一致
+        assert!(HGetDel::parse(
+            &mut ["key", "FIELDS", "2", "field1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .is_err());
+        // 不支持设置字段的过期时间
+        for opt in ["EX", "PX", "EXAT", "PXAT"] {
+            assert!(HGetEx::parse(
+                &mut ["key", opt, "10", "FIELDS", "1", "field1"].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .is_err());
+        }
+        assert!(HGetEx::parse(
+            &mut ["key", "PERSIST", "FIELDS", "1", "field1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .is_ok());
+    }
+
     #[tokio::test]
     async fn hexists_test() {
         test_init();
@@ -383,4 +972,193 @@ mod cmd_hash_tests {
             Resp3::new_blob_string("value2".into())
         );
     }
+
+    #[tokio::test]
+    async fn hash_encoding_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn encoding(handler: &mut Handler<impl AsyncStream>) -> Resp3 {
+            ObjectEncoding::parse(&mut ["key"].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        let config_set = ConfigSet::parse(
+            &mut ["hash-max-listpack-entries", "2"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        config_set.execute(&mut handler).await.unwrap();
+
+        let hset = HSet::parse(
+            &mut ["key", "field1", "value1", "field2", "value2"]
+                .as_ref()
+                .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        hset.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler).await.try_blob().unwrap(),
+            b"ziplist".as_ref()
+        );
+
+        // 元素数量超过阈值后转换为hashtable编码
+        let hset = HSet::parse(
+            &mut ["key", "field3", "value3"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        hset.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler).await.try_blob().unwrap(),
+            b"hashtable".as_ref()
+        );
+
+        let hget = HGet::parse(
+            &mut ["key", "field1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            hget.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_blob_string("value1".into())
+        );
+
+        // value长度超过阈值后转换为hashtable编码
+        let config_set = ConfigSet::parse(
+            &mut ["hash_max_listpack_value", "4"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        config_set.execute(&mut handler).await.unwrap();
+
+        let hset = HSet::parse(
+            &mut ["key2", "f", "value"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        hset.execute(&mut handler).await.unwrap();
+        let encoding =
+            ObjectEncoding::parse(&mut ["key2"].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(encoding.try_blob().unwrap(), b"hashtable".as_ref());
+
+        // 未知的参数
+        assert!(ConfigSet::parse(
+            &mut ["unknown-param", "1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn hrandfield_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        HSet::parse(
+            &mut ["key", "f1", "v1", "f2", "v2", "f3", "v3"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap()
+        .execute(&mut handler)
+        .await
+        .unwrap();
+
+        let hrandfield = |args: &[&str]| {
+            HRandField::parse(&mut args.into(), &AccessControl::new_loose()).unwrap()
+        };
+        // 字段fN对应的值为vN
+        let assert_paired = |field: &Resp3, value: &Resp3| {
+            let field = field.try_blob().unwrap();
+            let value = value.try_blob().unwrap();
+            assert!([&b"f1"[..], b"f2", b"f3"].contains(&field.as_ref()));
+            assert_eq!(field[1..], value[1..]);
+        };
+
+        // 不带count时返回单个字段
+        let res = hrandfield(&["key"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!([&b"f1"[..], b"f2", b"f3"].contains(&res.try_blob().unwrap().as_ref()));
+
+        // count为正数时返回不重复的字段，数量不超过字段数量
+        let res = hrandfield(&["key", "10"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let fields: AHashSet<_> = res.try_array().unwrap().iter().collect();
+        assert_eq!(fields.len(), 3);
+
+        // RESP2: [f1, v1, f2, v2, ...]，count为负数时字段可能重复
+        let res = hrandfield(&["key", "-5", "WITHVALUES"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 10);
+        for pair in res.chunks(2) {
+            assert_paired(&pair[0], &pair[1]);
+        }
+
+        // RESP3: [[f1, v1], [f2, v2], ...]
+        handler.context.protocol_version = 3;
+        let res = hrandfield(&["key", "-5", "WITHVALUES"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 5);
+        for pair in res {
+            let pair = pair.try_array().unwrap();
+            assert_eq!(pair.len(), 2);
+            assert_paired(&pair[0], &pair[1]);
+        }
+
+        let res = hrandfield(&["key", "2", "WITHVALUES"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 2);
+        assert_ne!(res[0], res[1]);
+
+        // count为0
+        let res = hrandfield(&["key", "0"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, Resp3::new_array(vec![]));
+
+        // 键不存在
+        let res = hrandfield(&["nil"]).execute(&mut handler).await;
+        assert!(matches!(res, Err(CmdError::Null)));
+        let res = hrandfield(&["nil", "3"])
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        assert_eq!(res, Some(Resp3::new_array(vec![])));
+
+        assert!(HRandField::parse(
+            &mut ["key", "1", "WITHSCORES"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+    }
 }
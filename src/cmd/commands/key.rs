@@ -5,23 +5,20 @@ use crate::{
         CmdExecutor, CmdType, CmdUnparsed,
     },
     conf::AccessControl,
-    connection::AsyncStream,
+    connection::{AsyncStream, Connection},
     frame::Resp3,
-    persist::rdb::{
-        encode_hash_value, encode_list_value, encode_set_value, encode_str_value, encode_zset_value,
-    },
+    persist::rdb::{decode_dump_payload, encode_dump_payload},
     server::Handler,
-    shared::db::ObjValueType,
-    util::{atoi, epoch},
+    shared::db::{Atc, Db, ExpireReason, ObjValue, ObjectInner},
+    util::{atoi, epoch, get_uppercase, unix_millis},
     CmdFlag, Id, Int, Key,
 };
 use bytes::{Bytes, BytesMut};
-use rayon::prelude::*;
-use std::time::Duration;
+use std::{sync::atomic::Ordering, time::Duration};
 use tokio::time::Instant;
 use tracing::instrument;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Opt {
     NX, // 要求键无过期时间
     XX, // 要求键有过期时间
@@ -43,6 +40,59 @@ impl TryFrom<&[u8]> for Opt {
     }
 }
 
+// 根据选项判断是否需要将过期时间ex更新为new_ex
+fn should_update_expire(opt: Option<Opt>, ex: Option<Instant>, new_ex: Instant) -> bool {
+    match opt {
+        Some(Opt::NX) => ex.is_none(),
+        Some(Opt::XX) => ex.is_some(),
+        Some(Opt::GT) => ex.is_some_and(|ex| new_ex > ex),
+        Some(Opt::LT) => ex.is_some_and(|ex| new_ex < ex),
+        None => true,
+    }
+}
+
+// 带有选项时先在读锁下判断是否需要更新过期时间，不需要更新时(常见的no-op情况)无需获取写锁，
+// 从而减少与其它命令的竞争。返回false时命令无需继续执行
+async fn may_update_expire(db: &Db, key: &Key, opt: Option<Opt>, new_ex: Instant) -> bool {
+    opt.is_none()
+        || db
+            .check_object(key, |obj| should_update_expire(opt, obj.expire(), new_ex))
+            .await
+}
+
+/// 返回当前数据库的 key 的数量。
+/// # Reply:
+///
+/// **Integer reply:** the number of keys in the currently-selected database.
+#[derive(Debug)]
+pub struct DbSize;
+
+impl CmdExecutor for DbSize {
+    const NAME: &'static str = "DBSIZE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DBSIZE_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        // 空对象以及已过期的对象不计入
+        let size = handler.shared.db().len_nonexpired();
+
+        Ok(Some(Resp3::new_integer(size as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DbSize)
+    }
+}
+
 /// 该命令用于在 key 存在时删除 key。
 /// # Reply:
 ///
@@ -54,18 +104,32 @@ pub struct Del {
 
 impl CmdExecutor for Del {
     const NAME: &'static str = "DEL";
-    const TYPE: CmdType = CmdType::Other;
+    const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = DEL_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, -1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
+        let db = handler.shared.db();
+
         let mut count = 0;
         for key in self.keys {
-            if handler.shared.db().remove_object(&key).await.is_some() {
+            let entry = db.get_object_entry_mut(key).await;
+            // 只计入确实存在的对象。已过期的对象会被顺带移除，而空对象只存储事件，无需移除
+            if entry.is_object_existed() {
+                if let Some((_, obj)) = entry.remove_object() {
+                    db.lazy_free().free(obj, ExpireReason::Deleted);
+                }
                 count += 1;
+            } else if entry.is_object_expired() {
+                if let Some((_, obj)) = entry.remove_object() {
+                    db.lazy_free().free(obj, ExpireReason::Expired);
+                    db.record_expired_key(false);
+                }
             }
         }
 
@@ -100,6 +164,8 @@ impl CmdExecutor for Dump {
     const NAME: &'static str = "DUMP";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = DUMP_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -111,14 +177,7 @@ impl CmdExecutor for Dump {
             .shared
             .db()
             .visit_object(&self.key, |obj| {
-                match obj.typ() {
-                    ObjValueType::Str => encode_str_value(&mut buf, obj.on_str()?.clone()),
-                    ObjValueType::List => encode_list_value(&mut buf, obj.on_list()?.clone()),
-                    ObjValueType::Set => encode_set_value(&mut buf, obj.on_set()?.clone()),
-                    ObjValueType::Hash => encode_hash_value(&mut buf, obj.on_hash()?.clone()),
-                    ObjValueType::ZSet => encode_zset_value(&mut buf, obj.on_zset()?.clone()),
-                }
-
+                encode_dump_payload(&mut buf, obj.value().clone());
                 Ok(())
             })
             .await?;
@@ -153,19 +212,29 @@ impl CmdExecutor for Exists {
     const NAME: &'static str = "EXISTS";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = EXISTS_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, -1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        for key in self.keys {
-            if !handler.shared.db().contains_object(&key).await {
-                return Err(0.into());
+        // 每个参数单独计数，重复的键会被重复计数
+        let mut count = 0;
+        for key in &self.keys {
+            let exists = handler.shared.db().contains_object(key).await;
+            handler.shared.db().record_keyspace_lookup(exists);
+            if exists {
+                count += 1;
             }
         }
 
-        Ok(Some(Resp3::new_integer(1)))
+        if count == 0 {
+            return Err(0.into());
+        }
+
+        Ok(Some(Resp3::new_integer(count)))
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
@@ -196,70 +265,49 @@ pub struct Expire {
 
 impl CmdExecutor for Expire {
     const NAME: &'static str = "EXPIRE";
-    const TYPE: CmdType = CmdType::Other;
+    const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = EXPIRE_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let mut res = None;
-
-        let new_ex = Instant::now() + self.seconds;
-        handler
+        let new_ex = handler
             .shared
-            .db()
-            .update_object(&self.key, |obj| {
-                let ex = obj.expire();
-                match self.opt {
-                    Some(Opt::NX) => {
-                        if ex.is_none() {
-                            obj.set_expire(Some(new_ex))?;
-                            res = Some(Resp3::new_integer(1));
-                            return Ok(());
-                        }
-                    }
-                    Some(Opt::XX) => {
-                        if ex.is_some() {
-                            obj.set_expire(Some(new_ex))?;
-                            res = Some(Resp3::new_integer(1));
-                            return Ok(());
-                        }
-                    }
-                    Some(Opt::GT) => {
-                        if let Some(ex) = ex {
-                            if new_ex > ex {
-                                obj.set_expire(Some(new_ex))?;
-
-                                res = Some(Resp3::new_integer(1));
-                                return Ok(());
-                            }
-                        }
-                    }
-                    Some(Opt::LT) => {
-                        if let Some(ex) = ex {
-                            if new_ex < ex {
-                                obj.set_expire(Some(new_ex))?;
-
-                                res = Some(Resp3::new_integer(1));
-                                return Ok(());
-                            }
-                        }
-                    }
-                    None => {
-                        obj.set_expire(Some(new_ex))?;
+            .conf()
+            .server
+            .jitter_expire(Instant::now() + self.seconds);
 
-                        res = Some(Resp3::new_integer(1));
-                        return Ok(());
-                    }
-                }
+        let db = handler.shared.db();
+        if !may_update_expire(db, &self.key, self.opt, new_ex).await {
+            return Err(0.into());
+        }
 
-                Err(CmdError::from(0))
-            })
-            .await?;
+        let mut entry = db.get_object_entry_mut(self.key).await;
 
-        Ok(res)
+        // 键不存在
+        let ex = match entry.value() {
+            Some(obj) => obj.expire(),
+            None => return Err(0.into()),
+        };
+
+        // 获取写锁之前对象可能已被修改，需要重新判断
+        if !should_update_expire(self.opt, ex, new_ex) {
+            return Err(0.into());
+        }
+
+        // 同时更新Db中的过期记录
+        let key = entry.key().clone();
+        entry.update_object_expire(Some(new_ex))?;
+
+        // 相对过期时间(且带有抖动)改写为绝对过期时间后再传播
+        handler.context.wcmd_rewrite =
+            Some(vec![Bytes::from_static(b"PEXPIREAT"), key, unix_millis(new_ex)].into());
+
+        Ok(Some(Resp3::new_integer(1)))
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
@@ -295,65 +343,38 @@ pub struct ExpireAt {
 
 impl CmdExecutor for ExpireAt {
     const NAME: &'static str = "EXPIREAT";
-    const TYPE: CmdType = CmdType::Other;
+    const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = EXPIREAT_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let mut res = None;
-        handler
-            .shared
-            .db()
-            .update_object(&self.key, |obj| {
-                let ex = obj.expire();
-                match self.opt {
-                    Some(Opt::NX) => {
-                        if ex.is_none() {
-                            obj.set_expire(Some(self.timestamp))?;
-                            res = Some(Resp3::new_integer(1));
-                            return Ok(());
-                        }
-                    }
-                    Some(Opt::XX) => {
-                        if ex.is_some() {
-                            obj.set_expire(Some(self.timestamp))?;
-                            res = Some(Resp3::new_integer(1));
-                            return Ok(());
-                        }
-                    }
-                    Some(Opt::GT) => {
-                        if let Some(ex) = ex {
-                            if self.timestamp > ex {
-                                obj.set_expire(Some(self.timestamp))?;
-                                res = Some(Resp3::new_integer(1));
-                                return Ok(());
-                            }
-                        }
-                    }
-                    Some(Opt::LT) => {
-                        if let Some(ex) = ex {
-                            if self.timestamp < ex {
-                                obj.set_expire(Some(self.timestamp))?;
-                                res = Some(Resp3::new_integer(1));
-                                return Ok(());
-                            }
-                        }
-                    }
-                    None => {
-                        obj.set_expire(Some(self.timestamp))?;
-                        res = Some(Resp3::new_integer(1));
-                        return Ok(());
-                    }
-                }
+        let db = handler.shared.db();
+        if !may_update_expire(db, &self.key, self.opt, self.timestamp).await {
+            return Err(0.into());
+        }
 
-                Err(CmdError::from(0))
-            })
-            .await?;
+        let mut entry = db.get_object_entry_mut(self.key).await;
+
+        // 键不存在
+        let ex = match entry.value() {
+            Some(obj) => obj.expire(),
+            None => return Err(0.into()),
+        };
 
-        Ok(res)
+        // 获取写锁之前对象可能已被修改，需要重新判断
+        if !should_update_expire(self.opt, ex, self.timestamp) {
+            return Err(0.into());
+        }
+
+        // 同时更新Db中的过期记录
+        entry.update_object_expire(Some(self.timestamp))?;
+
+        Ok(Some(Resp3::new_integer(1)))
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
@@ -385,6 +406,91 @@ impl CmdExecutor for ExpireAt {
     }
 }
 
+/// # Reply:
+///
+/// **Integer reply:** 0 if the timeout was not set; for example, the key doesn't exist, or the operation was skipped because of the provided arguments.
+/// **Integer reply:** 1 if the timeout was set.
+///
+/// # Desc:
+///
+/// 与EXPIREAT相同，但时间戳以毫秒计。时间戳已经过去时会直接删除键
+#[derive(Debug)]
+pub struct PExpireAt {
+    key: Key,
+    timestamp: Instant,
+    opt: Option<Opt>,
+}
+
+impl CmdExecutor for PExpireAt {
+    const NAME: &'static str = "PEXPIREAT";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = PEXPIREAT_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let db = handler.shared.db();
+        if !may_update_expire(db, &self.key, self.opt, self.timestamp).await {
+            return Err(0.into());
+        }
+
+        let mut entry = db.get_object_entry_mut(self.key).await;
+
+        // 键不存在
+        let ex = match entry.value() {
+            Some(obj) => obj.expire(),
+            None => return Err(0.into()),
+        };
+
+        // 获取写锁之前对象可能已被修改，需要重新判断
+        if !should_update_expire(self.opt, ex, self.timestamp) {
+            return Err(0.into());
+        }
+
+        if self.timestamp <= Instant::now() {
+            let key = entry.key().clone();
+            entry.remove_object();
+            handler.context.wcmd_rewrite = Some(vec![Bytes::from_static(b"DEL"), key].into());
+
+            return Ok(Some(Resp3::new_integer(1)));
+        }
+
+        // 同时更新Db中的过期记录
+        entry.update_object_expire(Some(self.timestamp))?;
+
+        Ok(Some(Resp3::new_integer(1)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 2 && args.len() != 3 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let timestamp = atoi::<u64>(&args.next().unwrap())?;
+        let timestamp = epoch() + Duration::from_millis(timestamp);
+
+        let opt = match args.next() {
+            Some(b) => Some(Opt::try_from(b.as_ref())?),
+            None => None,
+        };
+
+        Ok(PExpireAt {
+            key,
+            timestamp,
+            opt,
+        })
+    }
+}
+
 /// # Reply:
 ///
 /// **Integer reply:** the expiration Unix timestamp in seconds.
@@ -399,6 +505,8 @@ impl CmdExecutor for ExpireTime {
     const NAME: &'static str = "EXPIRETIME";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = EXPIRETIME_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -414,7 +522,7 @@ impl CmdExecutor for ExpireTime {
                 Ok(())
             })
             .await
-            .map_err(|_| CmdError::from(-1))?; // 键不存在
+            .map_err(|_| CmdError::from(-2))?; // 键不存在
 
         if let Some(ex) = ex {
             Ok(Some(Resp3::new_integer(
@@ -422,7 +530,7 @@ impl CmdExecutor for ExpireTime {
             )))
         } else {
             // 无过期时间
-            Err((-2).into())
+            Err((-1).into())
         }
     }
 
@@ -443,6 +551,8 @@ impl CmdExecutor for ExpireTime {
 /// # Reply:
 ///
 /// **Array reply:** a list of keys matching pattern.
+///
+/// 匹配的键较多时，先通过快照得到数量并写入数组头部，再将键逐个编码到写缓冲区
 #[derive(Debug)]
 pub struct Keys {
     pub pattern: Bytes,
@@ -453,6 +563,7 @@ impl CmdExecutor for Keys {
     const NAME: &'static str = "KEYS";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = KEYS_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -464,30 +575,28 @@ impl CmdExecutor for Keys {
         )
         .map_err(|_| "ERR invalid pattern is given")?;
 
-        let matched_keys = tokio::task::block_in_place(|| {
-            let db = handler.shared.db();
-            let matched_keys = if db.size() > (1024 << 32) {
-                db.entries()
-                    .par_iter()
-                    .filter_map(|entry| {
-                        re.is_match(entry.key())
-                            .then(|| Resp3::new_blob_string(entry.key().clone()))
-                    })
-                    .collect::<Vec<Resp3>>()
-            } else {
-                db.entries()
-                    .iter()
-                    .filter_map(|entry| {
-                        re.is_match(entry.key())
-                            .then(|| Resp3::new_blob_string(entry.key().clone()))
-                    })
-                    .collect::<Vec<Resp3>>()
-            };
+        let deadline = handler.context.deadline;
+        let matched_keys =
+            tokio::task::block_in_place(|| handler.shared.db().snapshot_keys(&re, deadline))
+                .ok_or(Err::Timeout)?;
+
+        if !handler.should_stream_reply(matched_keys.len()) {
+            return Ok(Some(Resp3::new_array(
+                matched_keys
+                    .into_iter()
+                    .map(Resp3::new_blob_string)
+                    .collect::<Vec<_>>(),
+            )));
+        }
 
-            matched_keys
-        });
+        handler
+            .write_streaming_array(
+                matched_keys.len(),
+                matched_keys.into_iter().map(Resp3::new_blob_string),
+            )
+            .await?;
 
-        Ok(Some(Resp3::new_array(matched_keys)))
+        Ok(None)
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
@@ -504,6 +613,209 @@ impl CmdExecutor for Keys {
     }
 }
 
+/// 将键原子地转移到另一个实例。键以DUMP的格式序列化后，通过RESTORE写入目标实例，
+/// 成功后删除本地的键(指定COPY时保留)。迁移期间被修改的键不会被删除。只有一个数据库，
+/// 因此destination-db只能为0
+/// # Reply:
+///
+/// **Simple string reply:** OK on success.
+/// **Simple string reply:** NOKEY when no keys were found in the source instance.
+#[derive(Debug)]
+pub struct Migrate {
+    host: String,
+    port: u16,
+    keys: Vec<Key>,
+    timeout: Duration,
+    copy: bool,
+    replace: bool,
+}
+
+impl CmdExecutor for Migrate {
+    const NAME: &'static str = "MIGRATE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = MIGRATE_FLAG;
+    const ARITY: Int = -6;
+    const KEY_RANGE: (Int, Int, Int) = (3, 3, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let db = handler.shared.db().clone();
+
+        // 序列化所有存在的键，并计算剩余的过期时间(0表示永不过期)。需要删除本地键时，
+        // 同时保存对象的快照，删除前据此确认对象在迁移期间没有被修改
+        let mut restores = Vec::with_capacity(self.keys.len());
+        for key in self.keys {
+            let Some(entry) = db.get_object_entry(&key).await else {
+                continue;
+            };
+            let obj = entry.inner_unchecked();
+
+            let ttl = obj.expire().map_or(0, |ex| {
+                ex.saturating_duration_since(Instant::now())
+                    .as_millis()
+                    .max(1) as u64
+            });
+            let mut payload = BytesMut::with_capacity(1024);
+            encode_dump_payload(&mut payload, obj.value().clone());
+            let snapshot = (!self.copy).then(|| (obj.value().clone(), obj.expire()));
+            drop(entry);
+
+            restores.push((key, ttl, payload.freeze(), snapshot));
+        }
+
+        if restores.is_empty() {
+            return Ok(Some(Resp3::new_simple_string("NOKEY".into())));
+        }
+
+        let stream = tokio::time::timeout(
+            self.timeout,
+            tokio::net::TcpStream::connect((self.host.as_str(), self.port)),
+        )
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .ok_or("IOERR error or timeout connecting to the client")?;
+        let mut conn = Connection::new(stream, 0);
+
+        // 以流水线的方式发送所有RESTORE命令
+        for (key, ttl, payload, _) in &restores {
+            let mut restore: Vec<Resp3> = vec![
+                Resp3::new_blob_string(Bytes::from_static(b"RESTORE")),
+                Resp3::new_blob_string(key.clone()),
+                Resp3::new_blob_string(itoa::Buffer::new().format(*ttl).to_owned().into()),
+                Resp3::new_blob_string(payload.clone()),
+            ];
+            if self.replace {
+                restore.push(Resp3::new_blob_string(Bytes::from_static(b"REPLACE")));
+            }
+            conn.buffer_frame(&Resp3::new_array(restore), true);
+        }
+        tokio::time::timeout(self.timeout, conn.write_buffered())
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .ok_or("IOERR error or timeout writing to target instance")?;
+
+        let mut migrated = vec![Bytes::from_static(b"DEL")];
+        let mut target_err = None;
+        let mut io_err = false;
+        for (key, _, _, snapshot) in restores {
+            let reply = match tokio::time::timeout(self.timeout, conn.read_frame()).await {
+                Ok(Ok(Some(reply))) => reply,
+                // 部分键可能已经迁移成功，这些键仍然需要从本地删除
+                _ => {
+                    io_err = true;
+                    break;
+                }
+            };
+
+            if let Resp3::SimpleError { inner, .. } = reply {
+                target_err = Some(inner);
+                continue;
+            }
+
+            if let Some((value, expire)) = snapshot {
+                let entry = db.get_object_entry_mut(key.clone()).await;
+                // 对象在迁移期间被修改(或者被删除)时保留本地的键，目标实例中为修改前的对象
+                if !entry
+                    .value()
+                    .is_some_and(|obj| obj.expire() == expire && *obj.value() == value)
+                {
+                    continue;
+                }
+
+                if let Some((_, obj)) = entry.remove_object() {
+                    db.lazy_free().free(obj, ExpireReason::Deleted);
+                }
+                migrated.push(key);
+            }
+        }
+
+        // 不传播MIGRATE本身(否则replica会再次迁移)，只传播本地键的删除
+        if migrated.len() > 1 {
+            handler
+                .shared
+                .wcmd_propagator()
+                .clone()
+                .may_propagate(migrated.into(), handler)
+                .await;
+        }
+
+        if io_err {
+            return Err("IOERR error or timeout reading to target instance".into());
+        }
+        if let Some(e) = target_err {
+            return Err(format!("ERR Target instance replied with error: {e}").into());
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 5 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let host = String::from_utf8(args.next().unwrap().to_vec()).map_err(|_| Err::Syntax)?;
+        let port = atoi::<u16>(&args.next().unwrap())?;
+        let key = args.next().unwrap();
+        if atoi::<i64>(&args.next().unwrap())? != 0 {
+            return Err("ERR DB index is out of range".into());
+        }
+        // 超时时间不大于0时，使用默认的1秒
+        let timeout = match atoi::<i64>(&args.next().unwrap())? {
+            t if t <= 0 => Duration::from_secs(1),
+            t => Duration::from_millis(t as u64),
+        };
+
+        let mut migrate = Migrate {
+            host,
+            port,
+            keys: vec![],
+            timeout,
+            copy: false,
+            replace: false,
+        };
+
+        while let Some(opt) = args.next() {
+            let mut buf = [0; 8];
+            let opt = get_uppercase(&opt, &mut buf).map_err(|_| Err::Syntax)?;
+            match opt {
+                b"COPY" => migrate.copy = true,
+                b"REPLACE" => migrate.replace = true,
+                b"KEYS" => {
+                    if !key.is_empty() {
+                        return Err("ERR When using MIGRATE KEYS option, the key argument must \
+                                    be set to the empty string"
+                            .into());
+                    }
+                    migrate.keys.extend(args.by_ref());
+                }
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        if migrate.keys.is_empty() {
+            if key.is_empty() {
+                return Err(Err::Syntax.into());
+            }
+            migrate.keys.push(key);
+        }
+
+        // 迁移需要读取键，并且会删除本地的键
+        if ac.is_forbidden_keys(&migrate.keys, CmdType::Read)
+            || ac.is_forbidden_keys(&migrate.keys, CmdType::Write)
+        {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(migrate)
+    }
+}
+
 /// # Reply:
 ///
 /// **Array reply:** a list of keys matching pattern.
@@ -518,13 +830,14 @@ impl CmdExecutor for NBKeys {
     const NAME: &'static str = "NBKEYS";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = NBKEYS_FLAG;
+    const ARITY: Int = 3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let re = regex::Regex::new(&String::from_utf8_lossy(&self.pattern))
+        let re = regex::bytes::Regex::new(&String::from_utf8_lossy(&self.pattern))
             .map_err(|_| "ERR invalid pattern is given")?;
 
         let shared = handler.shared.clone();
@@ -538,32 +851,14 @@ impl CmdExecutor for NBKeys {
         };
 
         tokio::task::block_in_place(move || {
-            let db = shared.db();
-
-            let matched_keys = if db.size() > (1024 << 32) {
-                // 并行
-                db.entries()
-                    .par_iter()
-                    .filter_map(|entry| {
-                        std::str::from_utf8(entry.key()).ok().and_then(|key| {
-                            re.is_match(key)
-                                .then(|| Resp3::new_blob_string(entry.key().clone()))
-                        })
-                    })
-                    .collect::<Vec<Resp3>>()
-            } else {
-                db.entries()
-                    .iter()
-                    .filter_map(|entry| {
-                        std::str::from_utf8(entry.key()).ok().and_then(|key| {
-                            re.is_match(key)
-                                .then(|| Resp3::new_blob_string(entry.key().clone()))
-                        })
-                    })
-                    .collect::<Vec<Resp3>>()
-            };
-
-            let _ = bg_sender.send(Resp3::new_array(matched_keys));
+            let matched_keys = shared.db().snapshot_keys(&re, None).unwrap_or_default();
+
+            let _ = bg_sender.send(Resp3::new_array(
+                matched_keys
+                    .into_iter()
+                    .map(Resp3::new_blob_string)
+                    .collect::<Vec<_>>(),
+            ));
         });
 
         Ok(None)
@@ -598,26 +893,27 @@ pub struct Persist {
 
 impl CmdExecutor for Persist {
     const NAME: &'static str = "PERSIST";
-    const TYPE: CmdType = CmdType::Other;
+    const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = PERSIST_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        handler
-            .shared
-            .db()
-            .update_object(&self.key, |obj| {
-                if obj.expire().is_none() {
-                    return Err(0.into());
-                }
+        let mut entry = handler.shared.db().get_object_entry_mut(self.key).await;
 
-                obj.set_expire(None)?;
-                Ok(())
-            })
-            .await
+        // 键不存在或者键没有设置过期时间
+        match entry.value() {
+            Some(obj) if obj.expire().is_some() => {}
+            _ => return Err(0.into()),
+        }
+
+        // 同时移除Db中的过期记录，避免定期删除时将其删除
+        entry
+            .update_object_expire(None)
             .map_err(|_| CmdError::from(0))?;
 
         Ok(Some(Resp3::new_integer(1)))
@@ -652,6 +948,8 @@ impl CmdExecutor for Pttl {
     const NAME: &'static str = "PTTL";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = PTTL_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -692,6 +990,156 @@ impl CmdExecutor for Pttl {
     }
 }
 
+/// 反序列化DUMP命令返回的值，并将其存储到给定的 key 中。IDLETIME和FREQ用于初始化
+/// 对象的访问信息，使得导入的数据在淘汰时保持原有的冷热程度
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct Restore {
+    key: Key,
+    // 为0时表示永不过期
+    ttl: u64,
+    payload: Bytes,
+    replace: bool,
+    abs_ttl: bool,
+    idle_time: Option<u32>,
+    freq: Option<u32>,
+}
+
+impl CmdExecutor for Restore {
+    const NAME: &'static str = "RESTORE";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = RESTORE_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let value = decode_dump_payload(&mut BytesMut::from(self.payload.as_ref()))
+            .map_err(|_| "ERR DUMP payload version or checksum are wrong")?;
+
+        let expire = match self.ttl {
+            0 => None,
+            ttl if self.abs_ttl => Some(epoch() + Duration::from_millis(ttl)),
+            ttl => Some(Instant::now() + Duration::from_millis(ttl)),
+        };
+
+        let entry = handler
+            .shared
+            .db()
+            .get_object_entry_mut(self.key.clone())
+            .await;
+        if entry.is_object_existed() && !self.replace {
+            return Err(Err::BusyKey.into());
+        }
+
+        // 绝对过期时间已经过去，相当于写入后马上过期
+        if expire.is_some_and(|ex| ex <= Instant::now()) {
+            if entry.is_object_existed() {
+                entry.remove_object();
+            }
+            handler.context.wcmd_rewrite = Some(vec![Bytes::from_static(b"DEL"), self.key].into());
+
+            return Ok(Some(Resp3::new_simple_string("OK".into())));
+        }
+
+        let mut obj = ObjectInner::new(value, expire);
+        if let Some(idle_time) = self.idle_time {
+            obj.set_atc(Atc::with_idle_time(idle_time));
+        } else if let Some(freq) = self.freq {
+            obj.set_atc(Atc::with_access_count(freq));
+        }
+        entry.insert_object(obj);
+
+        // 相对过期时间改写为绝对过期时间后再传播
+        if let (Some(ex), false) = (expire, self.abs_ttl) {
+            let mut wcmd = vec![
+                Bytes::from_static(b"RESTORE"),
+                self.key,
+                unix_millis(ex),
+                self.payload,
+                Bytes::from_static(b"ABSTTL"),
+            ];
+            if self.replace {
+                wcmd.push(Bytes::from_static(b"REPLACE"));
+            }
+            if let Some(idle_time) = self.idle_time {
+                wcmd.extend([
+                    Bytes::from_static(b"IDLETIME"),
+                    itoa::Buffer::new().format(idle_time).to_owned().into(),
+                ]);
+            }
+            if let Some(freq) = self.freq {
+                wcmd.extend([
+                    Bytes::from_static(b"FREQ"),
+                    itoa::Buffer::new().format(freq).to_owned().into(),
+                ]);
+            }
+            handler.context.wcmd_rewrite = Some(wcmd.into());
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 3 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let ttl = atoi::<i64>(&args.next().unwrap())?;
+        if ttl < 0 {
+            return Err("ERR Invalid TTL value, must be >= 0".into());
+        }
+        let payload = args.next().unwrap();
+
+        let mut restore = Restore {
+            key,
+            ttl: ttl as u64,
+            payload,
+            replace: false,
+            abs_ttl: false,
+            idle_time: None,
+            freq: None,
+        };
+
+        while let Some(opt) = args.next() {
+            let mut buf = [0; 8];
+            let opt = get_uppercase(&opt, &mut buf).map_err(|_| Err::Syntax)?;
+            match opt {
+                b"REPLACE" => restore.replace = true,
+                b"ABSTTL" => restore.abs_ttl = true,
+                // IDLETIME与FREQ互斥
+                b"IDLETIME" if restore.freq.is_none() => {
+                    let idle_time = atoi::<i64>(&args.next().ok_or(Err::Syntax)?)?;
+                    if idle_time < 0 {
+                        return Err("ERR Invalid IDLETIME value, must be >= 0".into());
+                    }
+                    restore.idle_time = Some(idle_time.min(u32::MAX as i64) as u32);
+                }
+                b"FREQ" if restore.idle_time.is_none() => {
+                    let freq = atoi::<i64>(&args.next().ok_or(Err::Syntax)?)?;
+                    if !(0..=255).contains(&freq) {
+                        return Err("ERR Invalid FREQ value, must be >= 0 and <= 255".into());
+                    }
+                    restore.freq = Some(freq as u32);
+                }
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        Ok(restore)
+    }
+}
+
 /// 以秒为单位，返回给定 key 的剩余生存时间(TTL, time to live)。
 /// # Reply:
 ///
@@ -707,6 +1155,8 @@ impl CmdExecutor for Ttl {
     const NAME: &'static str = "TTL";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = TTL_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -758,24 +1208,31 @@ pub struct Type {
 
 impl CmdExecutor for Type {
     const NAME: &'static str = "TYPE";
-    const TYPE: CmdType = CmdType::Other;
+    const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = TYPE_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let mut typ = "";
+        let mut typ = "none";
 
-        handler
+        // 键不存在、已过期或者为空对象时返回none
+        match handler
             .shared
             .db()
             .visit_object(&self.key, |obj| {
                 typ = obj.type_str();
                 Ok(())
             })
-            .await?;
+            .await
+        {
+            Ok(()) | Err(CmdError::Null) => {}
+            Err(e) => return Err(e),
+        }
 
         Ok(Some(Resp3::new_simple_string(typ.into())))
     }
@@ -794,17 +1251,283 @@ impl CmdExecutor for Type {
     }
 }
 
+/// 返回 key 所储存的值的内部编码。
+/// # Reply:
+///
+/// **Bulk string reply:** the encoding of the object.
+/// **Null reply:** if the key doesn't exist.
+#[derive(Debug)]
+pub struct ObjectEncoding {
+    pub key: Key,
+}
+
+impl CmdExecutor for ObjectEncoding {
+    const NAME: &'static str = "OBJECTENCODING";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = OBJECT_ENCODING_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut encoding = "";
+
+        let memory_conf = &handler.shared.conf().memory;
+        let max_entries = memory_conf
+            .list_max_listpack_entries
+            .load(Ordering::Relaxed);
+        let packed_threshold = memory_conf.list_packed_threshold.load(Ordering::Relaxed);
+
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                encoding = match obj.value() {
+                    // List的编码取决于元素的数量和长度
+                    ObjValue::List(list) => list.encoding(max_entries, packed_threshold),
+                    _ => obj.encoding(),
+                };
+                Ok(())
+            })
+            .await?;
+
+        Ok(Some(Resp3::new_blob_string(encoding.into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(ObjectEncoding { key })
+    }
+}
+
+/// 返回 key 所储存的对象的访问频率。
+/// # Reply:
+///
+/// **Integer reply:** the counter's value.
+/// **Null reply:** if the key doesn't exist.
+#[derive(Debug)]
+pub struct ObjectFreq {
+    pub key: Key,
+}
+
+impl CmdExecutor for ObjectFreq {
+    const NAME: &'static str = "OBJECTFREQ";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = OBJECT_FREQ_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut freq = 0;
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                freq = obj.atc().access_count();
+                Ok(())
+            })
+            .await?;
+
+        Ok(Some(Resp3::new_integer(freq as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(ObjectFreq { key })
+    }
+}
+
+/// 返回 key 所储存的对象自最近一次访问以来的空闲时间，以秒为单位。
+/// # Reply:
+///
+/// **Integer reply:** the idle time in seconds.
+/// **Null reply:** if the key doesn't exist.
+#[derive(Debug)]
+pub struct ObjectIdleTime {
+    pub key: Key,
+}
+
+impl CmdExecutor for ObjectIdleTime {
+    const NAME: &'static str = "OBJECTIDLETIME";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = OBJECT_IDLETIME_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut idle_time = 0;
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                idle_time = obj.atc().idle_time();
+                Ok(())
+            })
+            .await?;
+
+        Ok(Some(Resp3::new_integer(idle_time as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(ObjectIdleTime { key })
+    }
+}
+
+/// 返回 key 所储存的对象的引用计数。共享整数返回2147483647，其余对象返回1
+/// # Reply:
+///
+/// **Integer reply:** the number of references.
+/// **Null reply:** if the key doesn't exist.
+#[derive(Debug)]
+pub struct ObjectRefCount {
+    pub key: Key,
+}
+
+impl CmdExecutor for ObjectRefCount {
+    const NAME: &'static str = "OBJECTREFCOUNT";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = OBJECT_REFCOUNT_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut refcount = 0;
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                refcount = obj.refcount();
+                Ok(())
+            })
+            .await?;
+
+        Ok(Some(Resp3::new_integer(refcount)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(ObjectRefCount { key })
+    }
+}
+
 #[cfg(test)]
 mod cmd_key_tests {
     use super::*;
     use crate::{
-        shared::db::{Hash, List, ObjectInner, Set, Str, ZSet},
-        util::epoch,
+        frame::RESP3Decoder,
+        shared::db::{Hash, List, Object, ObjectInner, Set, Str, ZSet, SHARED_REFCOUNT},
+        util::{cmd, epoch},
     };
+    use ahash::AHashSet;
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
 
     // 允许的时间误差
     const ALLOWED_DELTA: u64 = 3;
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dbsize_test() {
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object(Key::from("key1"), ObjectInner::new_str("value1", None))
+            .await;
+
+        // 监听不存在的键，这会创建一个只存储事件的空对象
+        let (tx, _rx) = flume::unbounded();
+        db.add_may_update_event(Key::from("key_null"), tx).await;
+
+        let dbsize =
+            DbSize::parse(&mut CmdUnparsed::default(), &AccessControl::new_loose()).unwrap();
+        let result = dbsize.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(1));
+
+        // case: 已过期但尚未被移除的对象不计入
+        db.insert_object(
+            Key::from("key_expired"),
+            ObjectInner::new_str("value", Some(Instant::now() + Duration::from_millis(10))),
+        )
+        .await;
+        let dbsize =
+            DbSize::parse(&mut CmdUnparsed::default(), &AccessControl::new_loose()).unwrap();
+        let result = dbsize.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(2));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let dbsize =
+            DbSize::parse(&mut CmdUnparsed::default(), &AccessControl::new_loose()).unwrap();
+        let result = dbsize.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(1));
+
+        // case: 空对象对EXISTS不可见
+        let exists = Exists::parse(
+            &mut CmdUnparsed::from(["key_null"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = exists.execute(&mut handler).await.unwrap_err();
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
+
+        // case: 空对象对KEYS不可见
+        let keys = Keys::parse(
+            &mut CmdUnparsed::from([".*"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = keys.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(
+            result,
+            Resp3::new_array(vec![Resp3::new_blob_string("key1".into())])
+        );
+    }
+
     #[tokio::test]
     async fn del_test() {
         let (mut handler, _) = Handler::new_fake();
@@ -830,8 +1553,108 @@ mod cmd_key_tests {
             &AccessControl::new_loose(),
         )
         .unwrap();
-        let result = del.execute(&mut handler).await.unwrap().unwrap();
-        assert_eq!(result, Resp3::new_integer(0));
+        let result = del.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(0));
+
+        // case: 只计入确实被删除的键，并通知追踪该键的客户端
+        db.insert_object(Key::from("key2"), ObjectInner::new_str("value2", None))
+            .await;
+        let (track_tx, track_rx) = flume::unbounded();
+        db.add_track_event(Key::from("key2"), track_tx).await;
+
+        let del = Del::parse(
+            &mut CmdUnparsed::from(["key2", "key_nil"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = del.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(1));
+        assert_eq!(
+            track_rx.try_recv().unwrap(),
+            Resp3::new_push(vec![
+                Resp3::new_blob_string("invalidate".into()),
+                Resp3::new_array(vec![Resp3::new_blob_string("key2".into())]),
+            ])
+        );
+
+        // case: 空对象不计入，且其中的事件不会被移除
+        let (key_tx, key_rx) = flume::unbounded();
+        db.add_may_update_event(Key::from("key3"), key_tx).await;
+        let del = Del::parse(
+            &mut CmdUnparsed::from(["key3"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = del.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(0));
+
+        db.insert_object(Key::from("key3"), ObjectInner::new_str("value3", None))
+            .await;
+        assert_eq!(key_rx.try_recv().unwrap(), Key::from("key3"));
+    }
+
+    #[tokio::test]
+    async fn del_lazy_free_test() {
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+        db.lazy_free().set_lazy_user_del(true);
+
+        let members: Vec<Bytes> = (0..500_000)
+            .map(|i| Bytes::from(format!("member{i}")))
+            .collect();
+        db.insert_object(
+            Key::from("big"),
+            ObjectInner::new_set(Set::HashSet(members.into_iter().collect()), None),
+        )
+        .await;
+        db.insert_object(Key::from("small"), ObjectInner::new_str("value", None))
+            .await;
+
+        let del = Del::parse(
+            &mut ["big", "small"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = del.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(res, Resp3::new_integer(2));
+        assert_eq!(db.size(), 0);
+
+        // 大对象最终在后台被释放
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while db.lazy_free().pending() != 0 {
+            assert!(Instant::now() < deadline);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // 小对象以及未开启对应开关时直接释放
+        let small = Object::from(ObjectInner::new_str("value", None));
+        assert!(!db.lazy_free().free(small, ExpireReason::Deleted));
+        let big = Object::from(ObjectInner::new_list(
+            List::LinkedList((0..100).map(|i| Bytes::from(i.to_string())).collect()),
+            None,
+        ));
+        assert!(!db.lazy_free().free(big.clone(), ExpireReason::Expired));
+        assert!(db.lazy_free().free(big.clone(), ExpireReason::Deleted));
+        assert!(!db.lazy_free().free_overwritten(big.clone()));
+
+        // 被覆盖的大对象也在后台释放
+        db.lazy_free().set_lazy_server_del(true);
+        assert!(db.lazy_free().free_overwritten(big));
+        let members: Vec<Bytes> = (0..500_000)
+            .map(|i| Bytes::from(format!("member{i}")))
+            .collect();
+        db.insert_object(
+            Key::from("big"),
+            ObjectInner::new_set(Set::HashSet(members.into_iter().collect()), None),
+        )
+        .await;
+        db.insert_object(Key::from("big"), ObjectInner::new_str("value", None))
+            .await;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while db.lazy_free().pending() != 0 {
+            assert!(Instant::now() < deadline);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
     }
 
     #[tokio::test]
@@ -859,7 +1682,31 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = exists.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
+
+        // case: 重复的键会被重复计数，不存在的键不计入
+        let exists = Exists::parse(
+            &mut CmdUnparsed::from(["key1", "key1", "key_nil"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = exists.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(result, Resp3::new_integer(2));
+
+        // case: 已过期的键不计入
+        db.insert_object(
+            Key::from("key_expired"),
+            ObjectInner::new_str("value", Some(Instant::now() + Duration::from_millis(10))),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let exists = Exists::parse(
+            &mut CmdUnparsed::from(["key_expired", "key_expired"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = exists.execute(&mut handler).await.unwrap_err();
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
     }
 
     #[tokio::test]
@@ -900,7 +1747,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         db.insert_object(
             Key::from("key_with_ex"),
@@ -922,7 +1769,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire = Expire::parse(
             &mut CmdUnparsed::from(["key_without_ex", "10", "NX"].as_ref()),
@@ -953,7 +1800,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire = Expire::parse(
             &mut CmdUnparsed::from(["key_without_ex", "10", "NX"].as_ref()),
@@ -984,7 +1831,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire = Expire::parse(
             &mut CmdUnparsed::from(["key_with_ex", "20", "GT"].as_ref()),
@@ -1015,7 +1862,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire = Expire::parse(
             &mut CmdUnparsed::from(["key_with_ex", "5", "LT"].as_ref()),
@@ -1026,6 +1873,76 @@ mod cmd_key_tests {
         assert_eq!(result, Resp3::new_integer(1));
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expire_gt_concurrent_test() {
+        const BASE: u64 = 1893427200; // 2030-01-01 00:00:00
+
+        let (handler, _) = Handler::new_fake();
+        let shared = handler.shared.clone();
+        let db = shared.db().clone();
+
+        db.insert_object(
+            Key::from("key"),
+            ObjectInner::new_str("value", Some(epoch() + Duration::from_secs(BASE))),
+        )
+        .await;
+
+        let expire_at = |ts: u64, opt: &str| {
+            ExpireAt::parse(
+                &mut CmdUnparsed::from(["key", ts.to_string().as_str(), opt].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+        };
+        // epoch()每次计算的结果可能有纳秒级的误差
+        async fn assert_expire(db: &Db, secs: u64) {
+            let ex = db
+                .get_object_entry(&"key".into())
+                .await
+                .unwrap()
+                .inner_unchecked()
+                .expire()
+                .unwrap();
+            let expected = epoch() + Duration::from_secs(secs);
+            let delta = ex.max(expected) - ex.min(expected);
+            assert!(delta < Duration::from_millis(100));
+        }
+
+        // 多个连接并发执行EXPIREAT GT，最终的过期时间为其中的最大值
+        let mut tasks = Vec::new();
+        for i in 0..8 {
+            let (mut handler, _) = Handler::with_shared(shared.clone());
+            tasks.push(tokio::spawn(async move {
+                for j in 0..100 {
+                    let _ = expire_at(BASE + j * 8 + i, "GT")
+                        .execute(&mut handler)
+                        .await;
+                }
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let max = BASE + 99 * 8 + 7;
+        assert_expire(&db, max).await;
+
+        // 不需要更新时返回0，且过期时间保持不变
+        let (mut handler, _) = Handler::with_shared(shared.clone());
+        for (ts, opt) in [(BASE, "GT"), (BASE + 1000, "LT"), (BASE + 1000, "NX")] {
+            let res = expire_at(ts, opt).execute(&mut handler).await.unwrap_err();
+            assert!(matches!(res, CmdError::ErrorCode { code } if code == 0));
+            assert_expire(&db, max).await;
+        }
+
+        // 需要更新时依然生效
+        let res = expire_at(BASE + 1000, "GT")
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        assert_eq!(res, Some(Resp3::new_integer(1)));
+        assert_expire(&db, BASE + 1000).await;
+    }
+
     #[tokio::test]
     async fn expire_at_test() {
         let (mut handler, _) = Handler::new_fake();
@@ -1070,7 +1987,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_at.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         db.insert_object(
             Key::from("key_with_ex"),
@@ -1093,7 +2010,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_at.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire_at = ExpireAt::parse(
             &mut CmdUnparsed::from(["key_without_ex", "1893427200", "NX"].as_ref()),
@@ -1124,7 +2041,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_at.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire_at = ExpireAt::parse(
             &mut CmdUnparsed::from(["key_without_ex", "1893427200", "NX"].as_ref()),
@@ -1155,7 +2072,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_at.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire_at = ExpireAt::parse(
             &mut CmdUnparsed::from(["key_with_ex", "1893427201", "GT"].as_ref()),
@@ -1186,7 +2103,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_at.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         let expire_at = ExpireAt::parse(
             &mut CmdUnparsed::from(["key_with_ex", "1893427000", "LT"].as_ref()),
@@ -1225,7 +2142,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_time.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == -1);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
 
         // case: 键不存在
         let expire_time = ExpireTime::parse(
@@ -1234,7 +2151,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = expire_time.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == -2);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -2));
 
         // case: 键存在且有过期时间
         let expire_time = ExpireTime::parse(
@@ -1317,6 +2234,83 @@ mod cmd_key_tests {
             .unwrap()
             .to_vec();
         assert!(result.contains(&Resp3::new_blob_string("key1".into())));
+
+        // 已过期的键不会被返回
+        db.insert_object(
+            Key::from("key_expired"),
+            ObjectInner::new_str("value", Some(Instant::now() + Duration::from_millis(10))),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let keys = Keys::parse(
+            &mut CmdUnparsed::from(["key.*"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = keys
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap()
+            .try_array()
+            .unwrap()
+            .to_vec();
+        assert_eq!(result.len(), 4);
+        assert!(!result.contains(&Resp3::new_blob_string("key_expired".into())));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keys_streaming_test() {
+        let (mut handler, mut client) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        for i in 0..50_000 {
+            db.insert_object(
+                Key::from(format!("key{i}")),
+                ObjectInner::new_str("v", None),
+            )
+            .await;
+        }
+
+        // 匹配的键较多时不返回完整的回复，而是逐个写入连接
+        let keys = Keys::parse(
+            &mut CmdUnparsed::from([".*"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        handler.context.direct_reply = true;
+        assert!(keys.execute(&mut handler).await.unwrap().is_none());
+
+        // 编码期间写缓冲区达到阈值时已经写出了一部分回复
+        let mut buf = BytesMut::new();
+        let n = tokio::time::timeout(Duration::from_secs(1), client.read_buf(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(n > 0);
+
+        handler.conn.write_buffered().await.unwrap();
+        let res = loop {
+            if let Some(res) = RESP3Decoder::default().decode(&mut buf.clone()).unwrap() {
+                break res;
+            }
+            client.read_buf(&mut buf).await.unwrap();
+        };
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 50_000);
+        let keys: AHashSet<_> = res.iter().map(|k| k.try_blob().unwrap().clone()).collect();
+        assert_eq!(keys.len(), 50_000);
+        assert!(keys.contains(&Bytes::from("key0")) && keys.contains(&Bytes::from("key49999")));
+
+        // 匹配的键较少时仍返回完整的回复
+        let keys = Keys::parse(
+            &mut CmdUnparsed::from(["^key4999.$"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = keys.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(res.try_array().unwrap().len(), 10);
     }
 
     #[tokio::test]
@@ -1344,6 +2338,8 @@ mod cmd_key_tests {
             &AccessControl::new_loose(),
         )
         .unwrap();
+        let (tx, rx) = flume::unbounded();
+        db.add_may_update_event("key_with_ex".into(), tx).await;
         let result = persist.execute(&mut handler).await.unwrap().unwrap();
         assert_eq!(result, Resp3::new_integer(1));
         assert!(db
@@ -1353,6 +2349,20 @@ mod cmd_key_tests {
             .inner_unchecked()
             .expire()
             .is_none());
+        // 过期记录被移除，且触发了MayUpdate事件
+        assert!(!db
+            .entry_expire_records()
+            .iter()
+            .any(|r| r.key().1.as_ref() == b"key_with_ex"));
+        assert_eq!(rx.try_recv().unwrap().as_ref(), b"key_with_ex");
+
+        let ttl = Ttl::parse(
+            &mut CmdUnparsed::from(["key_with_ex"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = ttl.execute(&mut handler).await.unwrap_err();
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
 
         // case: 键存在，没有过期时间
         let persist = Persist::parse(
@@ -1361,7 +2371,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = persist.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
 
         // case: 键不存在
         let persist = Persist::parse(
@@ -1370,7 +2380,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = persist.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == 0);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == 0));
     }
 
     #[tokio::test]
@@ -1402,7 +2412,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = pttl.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == -1);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
 
         // case: 键不存在
         let pttl = Pttl::parse(
@@ -1411,7 +2421,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = pttl.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == -2);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -2));
 
         // case: 键存在且有过期时间
         let pttl = Pttl::parse(
@@ -1458,7 +2468,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = ttl.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == -1);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
 
         // case: 键不存在
         let ttl = Ttl::parse(
@@ -1467,7 +2477,7 @@ mod cmd_key_tests {
         )
         .unwrap();
         let result = ttl.execute(&mut handler).await.unwrap_err();
-        matches!(result, CmdError::ErrorCode { code } if code == -2);
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -2));
 
         // case: 键存在且有过期时间
         let ttl = Ttl::parse(
@@ -1485,6 +2495,239 @@ mod cmd_key_tests {
         assert!(dur.as_secs() - result < ALLOWED_DELTA);
     }
 
+    #[tokio::test]
+    async fn never_expire_test() {
+        let (mut handler, _) = Handler::new_fake();
+
+        let set = super::super::Set::parse(
+            &mut CmdUnparsed::from(["key", "value"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        set.execute(&mut handler).await.unwrap();
+
+        // 没有设置过期时间的键，TTL，PTTL和EXPIRETIME都返回-1
+        let ttl = Ttl::parse(
+            &mut CmdUnparsed::from(["key"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = ttl.execute(&mut handler).await.unwrap_err();
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
+
+        let pttl = Pttl::parse(
+            &mut CmdUnparsed::from(["key"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = pttl.execute(&mut handler).await.unwrap_err();
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
+
+        let expire_time = ExpireTime::parse(
+            &mut CmdUnparsed::from(["key"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let result = expire_time.execute(&mut handler).await.unwrap_err();
+        assert!(matches!(result, CmdError::ErrorCode { code } if code == -1));
+
+        // 延长过期时间后，旧的过期记录不应该导致键被提前删除
+        let expire = Expire::parse(
+            &mut CmdUnparsed::from(["key", "1"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        expire.execute(&mut handler).await.unwrap();
+        let expire = Expire::parse(
+            &mut CmdUnparsed::from(["key", "100"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        expire.execute(&mut handler).await.unwrap();
+
+        let db = handler.shared.db();
+        assert_eq!(db.entry_expire_records().len(), 1);
+        let removed = db
+            .remove_expired_objects(Instant::now() + Duration::from_secs(2))
+            .await;
+        assert_eq!(removed, 0);
+        assert!(db.contains_object(&"key".into()).await);
+    }
+
+    #[tokio::test]
+    async fn restore_test() {
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object(
+            Key::from("src"),
+            ObjectInner::new_list(List::LinkedList(["a", "b"].map(Bytes::from).into()), None),
+        )
+        .await;
+
+        let payload = Dump::parse(&mut ["src"].as_ref().into(), &AccessControl::new_loose())
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap()
+            .try_blob()
+            .unwrap()
+            .clone();
+
+        let restore = |args: &[&str]| {
+            let mut args: Vec<Bytes> = args
+                .iter()
+                .map(|a| Bytes::copy_from_slice(a.as_bytes()))
+                .collect();
+            args.insert(2, payload.clone());
+            Restore::parse(&mut args.into(), &AccessControl::new_loose())
+        };
+        let object_freq = |key: &str| {
+            ObjectFreq::parse(&mut [key].as_ref().into(), &AccessControl::new_loose()).unwrap()
+        };
+
+        // FREQ用于初始化对象的访问频率
+        restore(&["dst", "0", "FREQ", "50"])
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_object_entry(&"dst".into())
+                .await
+                .unwrap()
+                .inner()
+                .unwrap()
+                .value(),
+            db.get_object_entry(&"src".into())
+                .await
+                .unwrap()
+                .inner()
+                .unwrap()
+                .value()
+        );
+        assert_eq!(
+            object_freq("dst")
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(50)
+        );
+
+        // 键已存在且未指定REPLACE
+        assert!(restore(&["dst", "0"])
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .is_err());
+
+        // IDLETIME用于初始化对象的访问时间
+        restore(&["dst", "10000", "REPLACE", "IDLETIME", "100"])
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        let idle_time =
+            ObjectIdleTime::parse(&mut ["dst"].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_integer()
+                .unwrap();
+        assert!((100..100 + ALLOWED_DELTA as Int).contains(&idle_time));
+        assert!(db
+            .get_object_entry(&"dst".into())
+            .await
+            .unwrap()
+            .inner()
+            .unwrap()
+            .expire()
+            .is_some());
+
+        // IDLETIME与FREQ互斥
+        assert!(restore(&["dst", "0", "IDLETIME", "1", "FREQ", "1"]).is_err());
+        assert!(restore(&["dst", "0", "FREQ", "256"]).is_err());
+
+        // 校验和错误
+        let mut bad = payload.to_vec();
+        bad[0] ^= 0xff;
+        let restore = Restore::parse(
+            &mut vec![Bytes::from("bad"), Bytes::from("0"), Bytes::from(bad)].into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert!(restore.execute(&mut handler).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn object_refcount_test() {
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object(Key::from("key1"), ObjectInner::new_str("value1", None))
+            .await;
+        db.insert_object(Key::from("key2"), ObjectInner::new_str("5", None))
+            .await;
+        db.insert_object(Key::from("key3"), ObjectInner::new_str("10000", None))
+            .await;
+        db.insert_object(Key::from("key4"), ObjectInner::new_str("-1", None))
+            .await;
+
+        let mut refcount = async |key: &str| {
+            ObjectRefCount::parse(
+                &mut CmdUnparsed::from([key].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap()
+            .try_integer()
+            .unwrap()
+        };
+
+        // case: 普通对象
+        assert_eq!(refcount("key1").await, 1);
+        // case: 共享整数
+        assert_eq!(refcount("key2").await, SHARED_REFCOUNT);
+        // case: 超出共享整数范围
+        assert_eq!(refcount("key3").await, 1);
+        assert_eq!(refcount("key4").await, 1);
+
+        let encoding = ObjectEncoding::parse(
+            &mut CmdUnparsed::from(["key2"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap()
+        .execute(&mut handler)
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(encoding.try_blob().unwrap().as_ref(), b"int");
+
+        // case: 键不存在
+        assert!(ObjectRefCount::parse(
+            &mut CmdUnparsed::from(["nil"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap()
+        .execute(&mut handler)
+        .await
+        .is_err());
+
+        // case: 参数数量错误
+        assert!(ObjectRefCount::parse(
+            &mut CmdUnparsed::from(["key1", "key2"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .is_err());
+    }
+
     #[tokio::test]
     async fn type_test() {
         let (mut handler, _) = Handler::new_fake();
@@ -1591,5 +2834,215 @@ mod cmd_key_tests {
             .unwrap()
             .to_string();
         assert_eq!(result, "zset");
+
+        // case: 键不存在、已过期或者为空对象
+        db.insert_object(
+            Key::from("key_expired"),
+            ObjectInner::new_str("value", Some(Instant::now() + Duration::from_millis(10))),
+        )
+        .await;
+        let (tx, _rx) = flume::unbounded();
+        db.add_may_update_event(Key::from("key_null"), tx).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        for key in ["key_nil", "key_expired", "key_null"] {
+            let typ = Type::parse(
+                &mut CmdUnparsed::from([key].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap();
+            let result = typ
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_simple_string()
+                .unwrap()
+                .to_string();
+            assert_eq!(result, "none", "{key}");
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_test() {
+        use crate::shared::Shared;
+        use tokio::net::TcpListener;
+
+        // 目标实例
+        let target = Shared::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        tokio::spawn({
+            let target = target.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut handler = Handler::new(target.clone(), stream);
+                    tokio::spawn(async move { handler.run().await });
+                }
+            }
+        });
+
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+        let expire = Instant::now() + Duration::from_secs(100);
+        db.insert_object(Key::from("key1"), ObjectInner::new_str("v1", Some(expire)))
+            .await;
+        db.insert_object(Key::from("key2"), ObjectInner::new_str("v2", None))
+            .await;
+
+        let ok = Some(Resp3::new_simple_string("OK".into()));
+
+        // 迁移后删除本地的键，并保留过期时间
+        let res = handler
+            .dispatch(cmd(&["MIGRATE", "127.0.0.1", &port, "key1", "0", "1000"]))
+            .await
+            .unwrap();
+        assert_eq!(res, ok);
+        assert!(!db.contains_object(&Key::from("key1")).await);
+        let entry = target
+            .db()
+            .get_object_entry(&Key::from("key1"))
+            .await
+            .unwrap();
+        let obj = entry.inner_unchecked();
+        assert_eq!(obj.value(), &Str::from("v1").into());
+        let diff = expire.duration_since(obj.expire().unwrap());
+        assert!(diff < Duration::from_millis(ALLOWED_DELTA * 10));
+        drop(entry);
+
+        // COPY保留本地的键，不存在的键被忽略
+        let res = handler
+            .dispatch(cmd(&[
+                "MIGRATE",
+                "127.0.0.1",
+                &port,
+                "",
+                "0",
+                "1000",
+                "COPY",
+                "KEYS",
+                "key2",
+                "key_nil",
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(res, ok);
+        assert!(db.contains_object(&Key::from("key2")).await);
+        assert!(target.db().contains_object(&Key::from("key2")).await);
+
+        // 目标实例中已存在的键需要REPLACE
+        let res = handler
+            .dispatch(cmd(&["MIGRATE", "127.0.0.1", &port, "key2", "0", "1000"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res
+            .try_simple_error()
+            .unwrap()
+            .starts_with("ERR Target instance replied with error: BUSYKEY"));
+        assert!(db.contains_object(&Key::from("key2")).await);
+        let res = handler
+            .dispatch(cmd(&[
+                "MIGRATE",
+                "127.0.0.1",
+                &port,
+                "key2",
+                "0",
+                "1000",
+                "REPLACE",
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(res, ok);
+        assert!(!db.contains_object(&Key::from("key2")).await);
+
+        let res = handler
+            .dispatch(cmd(&[
+                "MIGRATE",
+                "127.0.0.1",
+                &port,
+                "key_nil",
+                "0",
+                "1000",
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(res, Some(Resp3::new_simple_string("NOKEY".into())));
+
+        // 无法连接到目标实例时，本地的键保持不变
+        let closed_port = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port().to_string()
+        };
+        db.insert_object(Key::from("key3"), ObjectInner::new_str("v3", None))
+            .await;
+        let res = handler
+            .dispatch(cmd(&[
+                "MIGRATE",
+                "127.0.0.1",
+                &closed_port,
+                "key3",
+                "0",
+                "100",
+            ]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().unwrap().starts_with("IOERR"));
+        assert!(db.contains_object(&Key::from("key3")).await);
+
+        // 语法错误
+        for args in [
+            ["MIGRATE", "127.0.0.1", &port, "key3", "1", "1000", "COPY"],
+            ["MIGRATE", "127.0.0.1", &port, "key3", "0", "1000", "KEYS"],
+            ["MIGRATE", "127.0.0.1", &port, "", "0", "1000", "COPY"],
+        ] {
+            let res = handler.dispatch(cmd(&args)).await.unwrap().unwrap();
+            assert!(res.is_simple_error(), "{args:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn migrate_modified_test() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+            sync::oneshot,
+        };
+
+        // 目标实例收到RESTORE后，等待本地的键被修改才回复
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let (received_tx, received_rx) = oneshot::channel();
+        let (modified_tx, modified_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            received_tx.send(()).unwrap();
+            modified_rx.await.unwrap();
+            stream.write_all(b"+OK\r\n").await.unwrap();
+        });
+
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+        db.insert_object(Key::from("key1"), ObjectInner::new_str("v1", None))
+            .await;
+
+        let modify = async {
+            received_rx.await.unwrap();
+            db.insert_object(Key::from("key1"), ObjectInner::new_str("v2", None))
+                .await;
+            modified_tx.send(()).unwrap();
+        };
+        let (res, _) = tokio::join!(
+            handler.dispatch(cmd(&["MIGRATE", "127.0.0.1", &port, "key1", "0", "1000"])),
+            modify
+        );
+        assert_eq!(res.unwrap(), Some(Resp3::new_simple_string("OK".into())));
+
+        // 迁移期间被修改的键不会被删除
+        let entry = db.get_object_entry(&Key::from("key1")).await.unwrap();
+        assert_eq!(entry.inner_unchecked().value(), &Str::from("v2").into());
     }
 }
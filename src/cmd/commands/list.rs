@@ -6,13 +6,11 @@ use crate::{
     frame::Resp3,
     server::Handler,
     shared::{db::ObjValueType, Shared},
-    util::atoi,
+    util::{atoi, timeout_to_deadline},
     Id, Int, Key,
 };
 use bytes::Bytes;
 use flume::{Receiver, Sender};
-use std::time::Duration;
-use tokio::time::Instant;
 use tracing::{instrument, trace};
 
 /// # Reply:
@@ -32,6 +30,8 @@ impl CmdExecutor for BLMove {
     const NAME: &'static str = "BLMOVE";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = BLMOVE_FLAG;
+    const ARITY: Int = 6;
+    const KEY_RANGE: (Int, Int, Int) = (1, 2, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -40,6 +40,10 @@ impl CmdExecutor for BLMove {
     ) -> Result<Option<Resp3>, CmdError> {
         let db = handler.shared.db();
 
+        // 目标键的类型错误时不应弹出源列表中的元素
+        db.check_object_type(&self.destination, ObjValueType::List)
+            .await?;
+
         let mut elem = None;
         let update_res = db
             .update_object(&self.source, |obj| {
@@ -86,13 +90,20 @@ impl CmdExecutor for BLMove {
         db.add_may_update_event(self.destination, key_tx.clone())
             .await;
 
-        let deadline = if self.timeout == 0 {
-            None
-        } else {
-            Some(Instant::now() + Duration::from_secs(self.timeout))
-        };
+        let deadline = timeout_to_deadline(self.timeout);
 
-        let res = pop_timeout_at(&handler.shared, key_tx, key_rx, deadline).await?;
+        // 阻塞期间不能阻止过期键的DEL命令的传播，因此先传播已缓存的写命令并释放hold
+        let shared = handler.shared.clone();
+        shared.wcmd_propagator().propagate_buffered(handler).await;
+
+        let res = shared
+            .block_until(
+                deadline,
+                pop_blocking(&shared, &mut handler.context.wcmd_held, key_tx, key_rx),
+            )
+            .await
+            // 超时或服务器关闭时返回Null
+            .unwrap_or(Ok(Resp3::Null))?;
 
         Ok(Some(res))
     }
@@ -115,9 +126,9 @@ impl CmdExecutor for BLMove {
         Ok(BLMove {
             source,
             destination,
-            wherefrom: Where::try_from(args.next_back().unwrap().as_ref())?,
-            whereto: Where::try_from(args.next_back().unwrap().as_ref())?,
-            timeout: atoi::<u64>(args.next_back().unwrap().as_ref())?,
+            wherefrom: Where::try_from(args.next().unwrap().as_ref())?,
+            whereto: Where::try_from(args.next().unwrap().as_ref())?,
+            timeout: atoi::<u64>(args.next().unwrap().as_ref())?,
         })
     }
 }
@@ -136,6 +147,8 @@ impl CmdExecutor for BLPop {
     const NAME: &'static str = "BLPOP";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = BLPOP_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, -2, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -163,13 +176,20 @@ impl CmdExecutor for BLPop {
             db.add_may_update_event(key.clone(), key_tx.clone()).await;
         }
 
-        let deadline = if self.timeout == 0 {
-            None
-        } else {
-            Some(Instant::now() + Duration::from_secs(self.timeout))
-        };
+        let deadline = timeout_to_deadline(self.timeout);
 
-        let res = pop_timeout_at(&handler.shared, key_tx, key_rx, deadline).await?;
+        // 阻塞期间不能阻止过期键的DEL命令的传播，因此先传播已缓存的写命令并释放hold
+        let shared = handler.shared.clone();
+        shared.wcmd_propagator().propagate_buffered(handler).await;
+
+        let res = shared
+            .block_until(
+                deadline,
+                pop_blocking(&shared, &mut handler.context.wcmd_held, key_tx, key_rx),
+            )
+            .await
+            // 超时或服务器关闭时返回Null
+            .unwrap_or(Ok(Resp3::Null))?;
 
         Ok(Some(res))
     }
@@ -208,6 +228,8 @@ impl CmdExecutor for LPos {
     const NAME: &'static str = "LPOS";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = LPOS_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -331,6 +353,72 @@ impl CmdExecutor for LPos {
     }
 }
 
+/// 返回列表中下标为index的元素。index可以为负数，-1表示最后一个元素
+/// # Reply:
+///
+/// **Null reply:** when index is out of range.
+/// **Bulk string reply:** the requested element.
+#[derive(Debug)]
+pub struct LIndex {
+    key: Key,
+    index: Int,
+}
+
+impl CmdExecutor for LIndex {
+    const NAME: &'static str = "LINDEX";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = LINDEX_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut res = None;
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let list = obj.on_list()?;
+
+                let len = list.len() as Int;
+                let index = if self.index < 0 {
+                    len + self.index
+                } else {
+                    self.index
+                };
+
+                res = if (0..len).contains(&index) {
+                    list.get(index as usize).map(Resp3::new_blob_string)
+                } else {
+                    Some(Resp3::Null)
+                };
+
+                Ok(())
+            })
+            .await?;
+
+        Ok(res)
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let index = atoi::<Int>(&args.next().unwrap())?;
+
+        Ok(LIndex { key, index })
+    }
+}
+
 /// **Integer reply:** the length of the list.
 #[derive(Debug)]
 pub struct LLen {
@@ -341,6 +429,8 @@ impl CmdExecutor for LLen {
     const NAME: &'static str = "LLEN";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = LLEN_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -392,6 +482,8 @@ impl CmdExecutor for LPop {
     const NAME: &'static str = "LPOP";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = LPOP_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -468,6 +560,8 @@ impl CmdExecutor for LPush {
     const NAME: &'static str = "LPUSH";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = LPUSH_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -526,6 +620,8 @@ impl CmdExecutor for NBLPop {
     const NAME: &'static str = "NBLPOP";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = NBLPOP_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, -3, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -553,11 +649,7 @@ impl CmdExecutor for NBLPop {
             shared.db().add_may_update_event(key, key_tx.clone()).await;
         }
 
-        let deadline = if self.timeout == 0 {
-            None
-        } else {
-            Some(Instant::now() + Duration::from_secs(self.timeout))
-        };
+        let deadline = timeout_to_deadline(self.timeout);
 
         let shared = handler.shared.clone();
         let bg_sender = if self.redirect != 0 {
@@ -570,10 +662,19 @@ impl CmdExecutor for NBLPop {
         };
 
         tokio::spawn(async move {
-            let res = match pop_timeout_at(&shared, key_tx, key_rx, deadline).await {
-                Ok(res) => res,
-                Err(e) => e.try_into().unwrap(),
+            let mut held = false;
+            let res = match shared
+                .block_until(deadline, pop_blocking(&shared, &mut held, key_tx, key_rx))
+                .await
+            {
+                Some(Ok(res)) => res,
+                Some(Err(e)) => e.try_into().unwrap(),
+                None => Resp3::Null,
             };
+            // 后台任务没有缓存的写命令需要等待传播，弹出后立即释放
+            if held {
+                shared.wcmd_propagator().release_wcmd();
+            }
             let _ = bg_sender.send(res);
         });
 
@@ -664,84 +765,57 @@ async fn first_round<S: AsRef<str> + PartialEq>(
     Ok(None)
 }
 
-async fn pop_timeout_at(
+// 等待监听的键被更新，并尝试从中弹出元素，直到成功弹出为止。超时由调用者控制。held为
+// handler的`wcmd_held`，只在弹出元素时hold，见[`Propagator::hold_wcmd`]
+async fn pop_blocking(
     shared: &Shared,
+    held: &mut bool,
     key_tx: Sender<Key>,
     key_rx: Receiver<Key>,
-    deadline: Option<Instant>,
 ) -> Result<Resp3, CmdError> {
     let db = shared.db();
     let mut res = None;
 
-    trace!("listening for list keys..., deadline: {deadline:?}");
+    trace!("listening for list keys...");
     loop {
-        // 存在超时时间
-        if let Some(dl) = deadline {
-            match tokio::time::timeout_at(dl, key_rx.recv_async()).await {
-                Ok(Ok(key)) => {
-                    let update_res = db
-                        .update_object(&key, |obj| {
-                            let list = obj.on_list_mut()?;
-
-                            if let Some(value) = list.pop_front() {
-                                res = Some(Resp3::new_array(vec![
-                                    Resp3::new_blob_string(key.clone()),
-                                    Resp3::new_blob_string(value),
-                                ]));
-                            }
-
-                            Ok(())
-                        })
-                        .await;
-
-                    if let Some(res) = res {
-                        // 如果next确实成功了，则退出循环
-                        break Ok(res);
-                    }
+        let Ok(key) = key_rx.recv_async().await else {
+            continue;
+        };
+        // 被唤醒时服务器可能处于暂停状态，等待暂停结束后才弹出元素
+        shared.wait_unpaused().await;
 
-                    // 如果next失败了，则重新加入事件
-                    db.add_may_update_event(key.clone(), key_tx.clone()).await;
+        shared.wcmd_propagator().hold_wcmd(held);
+        let update_res = db
+            .update_object(&key, |obj| {
+                let list = obj.on_list_mut()?;
 
-                    // 忽略空键的错误
-                    if !matches!(update_res, Err(CmdError::Null)) {
-                        update_res?;
-                    }
+                if let Some(value) = list.pop_front() {
+                    res = Some(Resp3::new_array(vec![
+                        Resp3::new_blob_string(key.clone()),
+                        Resp3::new_blob_string(value),
+                    ]));
                 }
-                // 超时
-                Err(_) => break Ok(Resp3::Null),
-                _ => continue,
-            }
-        }
-
-        // 不存在超时时间
-        if let Ok(key) = key_rx.recv_async().await {
-            let update_res = shared
-                .db()
-                .update_object(&key, |obj| {
-                    let list = obj.on_list_mut()?;
 
-                    if let Some(value) = list.pop_front() {
-                        res = Some(Resp3::new_array(vec![
-                            Resp3::new_blob_string(key.clone()),
-                            Resp3::new_blob_string(value),
-                        ]));
-                    }
+                Ok(())
+            })
+            .await;
 
-                    Ok(())
-                })
-                .await;
+        if let Some(res) = res {
+            // 如果next确实成功了，则退出循环
+            break Ok(res);
+        }
 
-            if let Some(res) = res {
-                // 如果next确实成功了，则退出循环
-                break Ok(res);
-            }
+        // 没有弹出元素，释放hold，继续等待
+        if std::mem::take(held) {
+            shared.wcmd_propagator().release_wcmd();
+        }
 
-            db.add_may_update_event(key.clone(), key_tx.clone()).await;
+        // 如果next失败了，则重新加入事件
+        db.add_may_update_event(key.clone(), key_tx.clone()).await;
 
-            // 忽略空键的错误
-            if !matches!(update_res, Err(CmdError::Null)) {
-                update_res?;
-            }
+        // 忽略空键的错误
+        if !matches!(update_res, Err(CmdError::Null)) {
+            update_res?;
         }
     }
 }
@@ -749,8 +823,55 @@ async fn pop_timeout_at(
 #[cfg(test)]
 mod cmd_list_tests {
     use super::*;
-    use crate::{cmd::Ping, util::test_init};
-    use tokio::time::sleep;
+    use crate::{cmd::Ping, shared::db::ObjectInner, util::test_init};
+    use std::time::Duration;
+    use tokio::time::{sleep, Instant};
+
+    #[tokio::test]
+    async fn list_encoding_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn encoding(handler: &mut Handler<impl AsyncStream>) -> Resp3 {
+            ObjectEncoding::parse(&mut ["list"].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        let lpush = LPush::parse(
+            &mut ["list", "a", "b"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        lpush.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler).await,
+            Resp3::new_blob_string("listpack".into())
+        );
+
+        let debug = DebugQuicklistPackedThreshold::parse(
+            &mut ["100"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        debug.execute(&mut handler).await.unwrap();
+
+        // 元素的长度超过阈值后转换为quicklist
+        let large = "x".repeat(200);
+        let lpush = LPush::parse(
+            &mut ["list", large.as_str()].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        lpush.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler).await,
+            Resp3::new_blob_string("quicklist".into())
+        );
+    }
 
     #[tokio::test]
     async fn llen_test() {
@@ -802,6 +923,55 @@ mod cmd_list_tests {
         );
     }
 
+    #[tokio::test]
+    async fn lindex_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let lindex = |index: &'static str| {
+            LIndex::parse(
+                &mut CmdUnparsed::from(["list", index].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+        };
+
+        // 键不存在
+        assert!(matches!(
+            lindex("0").execute(&mut handler).await.unwrap_err(),
+            CmdError::Null
+        ));
+
+        // list: 0 1 2
+        let lpush = LPush::parse(
+            &mut CmdUnparsed::from(["list", "2", "1", "0"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        lpush.execute(&mut handler).await.unwrap();
+
+        for (index, expected) in [("0", "0"), ("2", "2"), ("-1", "2"), ("-3", "0")] {
+            assert_eq!(
+                lindex(index).execute(&mut handler).await.unwrap(),
+                Some(Resp3::new_blob_string(expected.into()))
+            );
+        }
+
+        // 越界
+        for index in ["3", "-4", "100"] {
+            assert_eq!(
+                lindex(index).execute(&mut handler).await.unwrap(),
+                Some(Resp3::Null)
+            );
+        }
+
+        assert!(LIndex::parse(
+            &mut CmdUnparsed::from(["list", "a"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .is_err());
+    }
+
     #[tokio::test]
     async fn push_pop_test() {
         test_init();
@@ -863,6 +1033,53 @@ mod cmd_list_tests {
         );
     }
 
+    #[tokio::test]
+    async fn wrong_type_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object("str".into(), ObjectInner::new_str("hello", None))
+            .await;
+
+        // 键的类型错误时返回WRONGTYPE，且不修改键
+        let lpush = LPush::parse(
+            &mut ["str", "a", "b"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let err = lpush.execute(&mut handler).await.unwrap_err();
+        assert!(err.to_string().starts_with("WRONGTYPE"), "{err}");
+        db.visit_object(&"str".into(), |obj| {
+            assert_eq!(obj.on_str()?.to_vec(), b"hello");
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        // 目标键的类型错误时不弹出源列表中的元素
+        let lpush = LPush::parse(
+            &mut ["list", "a"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        lpush.execute(&mut handler).await.unwrap();
+
+        let blmove = BLMove::parse(
+            &mut ["list", "str", "LEFT", "LEFT", "0"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let err = blmove.execute(&mut handler).await.unwrap_err();
+        assert!(err.to_string().starts_with("WRONGTYPE"), "{err}");
+        db.visit_object(&"list".into(), |obj| {
+            assert_eq!(obj.on_list()?.len(), 1);
+            Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
     #[tokio::test]
     async fn blpop_test() {
         test_init();
@@ -995,6 +1212,106 @@ mod cmd_list_tests {
         );
     }
 
+    #[tokio::test]
+    async fn block_until_shutdown_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+        let shared = handler.shared.clone();
+
+        let blocked = tokio::spawn(async move {
+            let blpop = BLPop::parse(
+                &mut CmdUnparsed::from(["never_pushed", "0"].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap();
+            blpop.execute(&mut handler).await.unwrap().unwrap()
+        });
+
+        sleep(Duration::from_millis(100)).await;
+        let now = Instant::now();
+        shared.shutdown().trigger_shutdown(()).unwrap();
+
+        // 服务器关闭时，阻塞的命令应当立即返回
+        let res = tokio::time::timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, Resp3::Null);
+        assert!(now.elapsed() < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn blpop_pause_test() {
+        test_init();
+
+        let (handler, _) = Handler::new_fake();
+        let shared = handler.shared.clone();
+        let (mut pusher, _) = Handler::with_shared(shared.clone());
+
+        let blpop = |mut handler: Handler<_>| {
+            tokio::spawn(async move {
+                let blpop = BLPop::parse(
+                    &mut CmdUnparsed::from(["list", "0"].as_ref()),
+                    &AccessControl::new_loose(),
+                )
+                .unwrap();
+                let res = blpop.execute(&mut handler).await.unwrap().unwrap();
+                (handler, res)
+            })
+        };
+        let pair = |value: &str| {
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("list".into()),
+                Resp3::new_blob_string(value.to_string().into()),
+            ])
+        };
+
+        // 暂停之前阻塞的BLPOP，在暂停结束后键被推入元素时正常返回
+        let blocked = blpop(handler);
+        sleep(Duration::from_millis(50)).await;
+        let deadline = Instant::now() + Duration::from_millis(200);
+        shared.pause_until(deadline);
+
+        let lpush = Resp3::new_array(vec![
+            Resp3::new_blob_string("LPUSH".into()),
+            Resp3::new_blob_string("list".into()),
+            Resp3::new_blob_string("v1".into()),
+        ]);
+        // LPUSH需要等待暂停结束
+        pusher.dispatch(lpush).await.unwrap();
+        assert!(Instant::now() >= deadline);
+
+        let (handler, res) = tokio::time::timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, pair("v1"));
+
+        // 暂停期间绕过暂停推入元素，BLPOP直到暂停被解除才返回
+        let blocked = blpop(handler);
+        sleep(Duration::from_millis(50)).await;
+        shared.pause_until(Instant::now() + Duration::from_secs(10));
+
+        LPush::parse(
+            &mut CmdUnparsed::from(["list", "v2"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap()
+        .execute(&mut pusher)
+        .await
+        .unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert!(!blocked.is_finished());
+
+        shared.unpause();
+        let (_, res) = tokio::time::timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, pair("v2"));
+    }
+
     #[tokio::test]
     async fn nblpop_test() {
         test_init();
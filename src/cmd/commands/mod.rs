@@ -1,4 +1,5 @@
 mod acl;
+mod geo;
 mod hash;
 mod key;
 mod list;
@@ -6,21 +7,31 @@ mod other;
 mod pub_sub;
 mod script;
 mod set;
+mod sort;
 mod str;
+mod stream;
+mod transaction;
+mod zset;
 
 pub use acl::*;
+pub use geo::*;
 pub use hash::*;
 pub use key::*;
 pub use list::*;
 pub use other::*;
 pub use pub_sub::*;
 pub use script::*;
+pub use set::*;
+pub use sort::*;
 pub use str::*;
+pub use stream::*;
+pub use transaction::*;
+pub use zset::*;
 
 use crate::CmdFlag;
 
 pub const ALL_CMD_FLAG: CmdFlag = CmdFlag::MAX;
-pub const NO_CMD_FLAG: CmdFlag = CmdFlag::MIN | AUTH_FLAG; // 允许AUTH命令
+pub const NO_CMD_FLAG: CmdFlag = AUTH_FLAG; // 允许AUTH命令
 
 pub(super) const ECHO_FLAG: CmdFlag = 1;
 pub(super) const PING_FLAG: CmdFlag = 1 << 1;
@@ -85,3 +96,122 @@ pub(super) const ACLDELUSER_FLAG: CmdFlag = 1 << 52;
 pub(super) const ACLSETUSER_FLAG: CmdFlag = 1 << 53;
 pub(super) const ACLWHOAMI_FLAG: CmdFlag = 1 << 54;
 pub(super) const ACLUSERS_FLAG: CmdFlag = 1 << 55;
+pub(super) const DBSIZE_FLAG: CmdFlag = 1 << 56;
+pub(super) const COMMAND_INFO_FLAG: CmdFlag = 1 << 57;
+pub(super) const INFO_FLAG: CmdFlag = 1 << 58;
+pub(super) const DEBUG_CHANGE_REPL_ID_FLAG: CmdFlag = 1 << 59;
+pub(super) const OBJECT_ENCODING_FLAG: CmdFlag = 1 << 60;
+pub(super) const CONFIG_SET_FLAG: CmdFlag = 1 << 61;
+pub(super) const COMMAND_DOCS_FLAG: CmdFlag = 1 << 62;
+pub(super) const PSETEX_FLAG: CmdFlag = 1 << 63;
+pub(super) const HGETDEL_FLAG: CmdFlag = 1 << 64;
+pub(super) const HGETEX_FLAG: CmdFlag = 1 << 65;
+pub(super) const PEXPIREAT_FLAG: CmdFlag = 1 << 66;
+pub(super) const SINTERCARD_FLAG: CmdFlag = 1 << 67;
+pub(super) const ZINTERCARD_FLAG: CmdFlag = 1 << 68;
+pub(super) const HELLO_FLAG: CmdFlag = 1 << 69;
+pub(super) const DEBUG_SET_ACTIVE_EXPIRE_FLAG: CmdFlag = 1 << 70;
+pub(super) const SMEMBERS_FLAG: CmdFlag = 1 << 71;
+pub(super) const HGETALL_FLAG: CmdFlag = 1 << 72;
+pub(super) const DEBUG_QUICKLIST_PACKED_THRESHOLD_FLAG: CmdFlag = 1 << 73;
+pub(super) const RESTORE_FLAG: CmdFlag = 1 << 74;
+pub(super) const OBJECT_FREQ_FLAG: CmdFlag = 1 << 75;
+pub(super) const OBJECT_IDLETIME_FLAG: CmdFlag = 1 << 76;
+pub(super) const DEBUG_STRINGMATCH_LEN_FLAG: CmdFlag = 1 << 77;
+pub(super) const SADD_FLAG: CmdFlag = 1 << 78;
+pub(super) const TIME_FLAG: CmdFlag = 1 << 79;
+pub(super) const DEBUG_OBJECT_FLAG: CmdFlag = 1 << 80;
+pub(super) const MULTI_FLAG: CmdFlag = 1 << 81;
+pub(super) const EXEC_FLAG: CmdFlag = 1 << 82;
+pub(super) const DISCARD_FLAG: CmdFlag = 1 << 83;
+pub(super) const CLIENT_ID_FLAG: CmdFlag = 1 << 84;
+pub(super) const DEBUG_SLEEP_FLAG: CmdFlag = 1 << 85;
+pub(super) const DEBUG_SLEEP_BLOCK_FLAG: CmdFlag = 1 << 86;
+pub(super) const REPLICAOF_FLAG: CmdFlag = 1 << 87;
+pub(super) const DEBUG_LOAD_FILE_FLAG: CmdFlag = 1 << 88;
+pub(super) const CLIENT_INFO_FLAG: CmdFlag = 1 << 89;
+pub(super) const LINDEX_FLAG: CmdFlag = 1 << 90;
+pub(super) const DEBUG_PANIC_FLAG: CmdFlag = 1 << 91;
+pub(super) const DEBUG_OOM_FLAG: CmdFlag = 1 << 92;
+pub(super) const SORT_FLAG: CmdFlag = 1 << 93;
+pub(super) const SORT_RO_FLAG: CmdFlag = 1 << 94;
+pub(super) const LOLWUT_FLAG: CmdFlag = 1 << 95;
+pub(super) const CLIENT_KILL_FLAG: CmdFlag = 1 << 96;
+pub(super) const BITFIELD_FLAG: CmdFlag = 1 << 97;
+pub(super) const GEOADD_FLAG: CmdFlag = 1 << 98;
+pub(super) const GEODIST_FLAG: CmdFlag = 1 << 99;
+pub(super) const GEOPOS_FLAG: CmdFlag = 1 << 100;
+pub(super) const GEOSEARCH_FLAG: CmdFlag = 1 << 101;
+pub(super) const XADD_FLAG: CmdFlag = 1 << 102;
+pub(super) const XLEN_FLAG: CmdFlag = 1 << 103;
+pub(super) const XRANGE_FLAG: CmdFlag = 1 << 104;
+pub(super) const XREVRANGE_FLAG: CmdFlag = 1 << 105;
+pub(super) const XREAD_FLAG: CmdFlag = 1 << 106;
+pub(super) const XGROUP_CREATE_FLAG: CmdFlag = 1 << 107;
+pub(super) const XGROUP_DESTROY_FLAG: CmdFlag = 1 << 108;
+pub(super) const XGROUP_CREATECONSUMER_FLAG: CmdFlag = 1 << 109;
+pub(super) const XREADGROUP_FLAG: CmdFlag = 1 << 110;
+pub(super) const XACK_FLAG: CmdFlag = 1 << 111;
+pub(super) const XPENDING_FLAG: CmdFlag = 1 << 112;
+pub(super) const XCLAIM_FLAG: CmdFlag = 1 << 113;
+pub(super) const MEMORY_DOCTOR_FLAG: CmdFlag = 1 << 114;
+pub(super) const MEMORY_STATS_FLAG: CmdFlag = 1 << 115;
+pub(super) const OBJECT_REFCOUNT_FLAG: CmdFlag = 1 << 116;
+pub(super) const CONFIG_REWRITE_FLAG: CmdFlag = 1 << 117;
+pub(super) const CONFIG_RESETSTAT_FLAG: CmdFlag = 1 << 118;
+pub(super) const HRANDFIELD_FLAG: CmdFlag = 1 << 119;
+pub(super) const ZRANDMEMBER_FLAG: CmdFlag = 1 << 120;
+pub(super) const DEBUG_RELOAD_FLAG: CmdFlag = 1 << 121;
+pub(super) const DEBUG_FLUSHALL_FLAG: CmdFlag = 1 << 122;
+pub(super) const WATCH_FLAG: CmdFlag = 1 << 123;
+pub(super) const UNWATCH_FLAG: CmdFlag = 1 << 124;
+pub(super) const ACLSAVE_FLAG: CmdFlag = 1 << 125;
+pub(super) const ACLLOAD_FLAG: CmdFlag = 1 << 126;
+pub(super) const MIGRATE_FLAG: CmdFlag = 1 << 127;
+
+// 不受command_timeout_ms限制的命令：阻塞命令和MIGRATE有自己的超时时间；EXEC中的命令会
+// 分别计时，中断EXEC会破坏事务的原子性。脚本在其它线程中执行，中断EVAL并不会中断脚本；
+// DEBUG RELOAD等命令被中断时数据库可能已被清空却未加载完毕。写命令也不会被强制中断
+// (见CmdExecutor::apply)
+pub(super) const NO_TIMEOUT_FLAG: CmdFlag = BLPOP_FLAG
+    | NBLPOP_FLAG
+    | BLMOVE_FLAG
+    | XREAD_FLAG
+    | XREADGROUP_FLAG
+    | SUBSCRIBE_FLAG
+    | MIGRATE_FLAG
+    | EXEC_FLAG
+    | EVAL_FLAG
+    | EVALNAME_FLAG
+    | DEBUG_RELOAD_FLAG
+    | DEBUG_LOAD_FILE_FLAG
+    | DEBUG_FLUSHALL_FLAG;
+
+// 与写命令一样不会被强制中断，只由命令自己检查截止时间的命令：SORT只有带STORE时才修改数据
+pub(super) const SELF_TIMEOUT_FLAG: CmdFlag = SORT_FLAG;
+
+// 可能增加内存占用的命令，使用的内存超过maxmemory时拒绝执行
+pub(super) const DENY_OOM_FLAG: CmdFlag = APPEND_FLAG
+    | DECR_FLAG
+    | DECRBY_FLAG
+    | GETSET_FLAG
+    | INCR_FLAG
+    | INCRBY_FLAG
+    | MSET_FLAG
+    | MSETNX_FLAG
+    | SET_FLAG
+    | SETEX_FLAG
+    | PSETEX_FLAG
+    | SETNX_FLAG
+    | BITFIELD_FLAG
+    | LPUSH_FLAG
+    | BLMOVE_FLAG
+    | HSET_FLAG
+    | SADD_FLAG
+    | SORT_FLAG
+    | RESTORE_FLAG
+    | GEOADD_FLAG
+    | XADD_FLAG
+    | XGROUP_CREATE_FLAG
+    | XGROUP_CREATECONSUMER_FLAG
+    | XREADGROUP_FLAG;
@@ -1,22 +1,35 @@
 use super::*;
 use crate::{
     cmd::{
+        cmd_info,
         error::{CmdError, Err},
-        CmdExecutor, CmdType, CmdUnparsed,
+        CmdExecutor, CmdInfo, CmdType, CmdUnparsed, CMD_INFOS,
     },
-    conf::AccessControl,
+    conf::{parse_memory_size, AccessControl, DEFAULT_USER},
     connection::AsyncStream,
     frame::Resp3,
-    persist::rdb::Rdb,
+    persist::{
+        bulk::{bulk_load, BulkFormat},
+        rdb::{encode_object_value, Rdb},
+    },
     server::Handler,
-    util, CmdFlag, Id,
+    shared::db::ObjValue,
+    util::{self, gen_run_id},
+    CmdFlag, Id, Int, Key,
+};
+use ahash::AHashMap;
+use bytes::{Bytes, BytesMut};
+use std::{
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
 };
-use bytes::Bytes;
+use tokio::time::Instant;
 use tracing::instrument;
 
-/// # Reply:
-///
-/// **Array reply**: a nested list of command details. The order of the commands in the array is random.
+// # Reply:
+//
+// **Array reply**: a nested list of command details. The order of the commands in the array is random.
 // #[derive(Debug)]
 // pub struct _Command;
 //
@@ -35,6 +48,101 @@ use tracing::instrument;
 //     }
 // }
 
+/// # Reply:
+///
+/// **Array reply:** a nested list of command details. 每个命令的详情包含10个字段：
+/// name, arity, flags, first key, last key, key step, acl categories, tips, key
+/// specs, subcommands。未知的命令返回Null
+#[derive(Debug)]
+pub struct CommandInfo {
+    cmd_names: Vec<Bytes>,
+}
+
+impl CmdExecutor for CommandInfo {
+    const NAME: &'static str = "COMMANDINFO";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = COMMAND_INFO_FLAG;
+    const ARITY: Int = -2;
+
+    #[instrument(level = "debug", skip(_handler), ret, err)]
+    async fn execute(
+        self,
+        _handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        // 未指定命令时，返回所有命令的详情
+        if self.cmd_names.is_empty() {
+            let res: Vec<_> = CMD_INFOS
+                .iter()
+                .filter(|info| !info.name.contains('|'))
+                .map(CmdInfo::to_resp3)
+                .collect();
+
+            return Ok(Some(Resp3::new_array(res)));
+        }
+
+        let res: Vec<_> = self
+            .cmd_names
+            .iter()
+            .map(|name| cmd_info(name).map_or(Resp3::Null, CmdInfo::to_resp3))
+            .collect();
+
+        Ok(Some(Resp3::new_array(res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        Ok(CommandInfo {
+            cmd_names: args.collect(),
+        })
+    }
+}
+
+/// # Reply:
+///
+/// **Map reply:** a map where each key is a command name, and each value is the
+/// documentary information: summary, since, group, arguments。未知的命令会被忽略
+#[derive(Debug)]
+pub struct CommandDocs {
+    cmd_names: Vec<Bytes>,
+}
+
+impl CmdExecutor for CommandDocs {
+    const NAME: &'static str = "COMMANDDOCS";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = COMMAND_DOCS_FLAG;
+    const ARITY: Int = -2;
+
+    #[instrument(level = "debug", skip(_handler), ret, err)]
+    async fn execute(
+        self,
+        _handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let to_entry = |info: &'static CmdInfo| {
+            (
+                Resp3::new_blob_string(Bytes::from_static(info.name.as_bytes())),
+                info.to_doc_resp3(),
+            )
+        };
+
+        // 未指定命令时，返回所有命令的文档
+        let res: AHashMap<_, _> = if self.cmd_names.is_empty() {
+            CMD_INFOS.iter().map(to_entry).collect()
+        } else {
+            self.cmd_names
+                .iter()
+                .filter_map(|name| cmd_info(name).map(to_entry))
+                .collect()
+        };
+
+        Ok(Some(Resp3::new_map(res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        Ok(CommandDocs {
+            cmd_names: args.collect(),
+        })
+    }
+}
+
 /// # Reply:
 ///
 /// **Simple string reply**: PONG when no argument is provided.
@@ -48,6 +156,7 @@ impl CmdExecutor for Ping {
     const NAME: &'static str = "PING";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = PING_FLAG;
+    const ARITY: Int = -1;
 
     #[instrument(level = "debug", skip(_handler), ret, err)]
     async fn execute(
@@ -86,6 +195,7 @@ impl CmdExecutor for Echo {
     const NAME: &'static str = "ECHO";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = ECHO_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(_handler), ret, err)]
     async fn execute(
@@ -105,142 +215,173 @@ impl CmdExecutor for Echo {
         })
     }
 }
-//
-// 该命令用于获取Redis服务器的各种信息和统计数值
-// *1\r\n$4\r\ninfo\r\n
-// *2\r\n$4\r\ninfo\r\n$11\r\nreplication\r\n
-// pub struct Info {
-//     pub sections: Section,
-// }
-//
-// #[allow(dead_code)]
-// pub enum Section {
-//     Array(Vec<Section>),
-//     // all: Return all sections (excluding module generated ones)
-//     All,
-//     // default: Return only the default set of sections
-//     Default,
-//     // everything: Includes all and modules
-//     Everything,
-//     // server: General information about the Redis server
-//     Server,
-//     // clients: Client connections section
-//     Clients,
-//     // memory: Memory consumption related information
-//     Memory,
-//     // persistence: RDB and AOF related information
-//     Persistence,
-//     // stats: General statistics
-//     Stats,
-//     // replication: Master/replica replication information
-//     Replication,
-//     // cpu: CPU consumption statistics
-//     Cpu,
-//     // commandstats: Redis command statistics
-//     CommandStats,
-//     // latencystats: Redis command latency percentile distribution statistics
-//     LatencyStats,
-//     // sentinel: Redis Sentinel section (only applicable to Sentinel instances)
-//     Sentinel,
-//     // cluster: Redis Cluster section
-//     Cluster,
-//     // modules: Modules section
-//     Modules,
-//     // keyspace: Database related statistics
-//     Keyspace,
-//     // errorstats: Redis error statistics
-//     ErrorStats,
-// }
-// impl TryFrom<Bytes> for Section {
-//     type Error = Error;
-//
-//     fn try_from(bulk: Bytes) -> Result<Self, Self::Error> {
-//         let value = bulk.to_ascii_uppercase();
-//         match value.as_slice() {
-//             b"REPLICATION" => Ok(Section::Replication),
-//             // TODO:
-//             _ => Err(anyhow!("Incomplete")),
-//         }
-//     }
-// }
-// impl TryFrom<Vec<Bytes>> for Section {
-//     type Error = Error;
-//
-//     fn try_from(bulks: Vec<Bytes>) -> Result<Self, Self::Error> {
-//         let mut sections = Vec::with_capacity(cmd_frame.array_len()?);
-//         for section in bulks {
-//             sections.push(section.try_into()?);
-//         }
-//         Ok(Section::Array(sections))
-//     }
-// }
 
-// impl Info {
-// #[instrument(level = "debug", skip(handler), ret, err)]
-//     pub async fn execute(&self, _db: &Db) -> ResultCmd
-//         debug!("executing command 'INFO'");
-//
-//         match self.sections {
-//             Section::Replication => {
-//                 let res = if CONFIG.replication.replicaof.is_none() {
-//                     format!(
-//                         "role:master\r\nmaster_replid:{}\r\nmaster_repl_offset:{}\r\n",
-//                         CONFIG.server.run_id,
-//                         OFFSET.load(std::sync::atomic::Ordering::SeqCst)
-//                     )
-//                 } else {
-//                     format!(
-//                         "role:slave\r\nmaster_replid:{}\r\nmaster_repl_offset:{}\r\n",
-//                         CONFIG.server.run_id,
-//                         OFFSET.load(std::sync::atomic::Ordering::SeqCst)
-//                     )
-//                 };
-//                 Ok(Some(RESP3::Bulk(res.into())))
-//             }
-//             // TODO:
-//             _ => Err(anyhow!("Incomplete")),
-//         }
-//     }
-// }
+/// 返回服务器的当前时间
+/// # Reply:
+///
+/// **Array reply:** specifically, a two-element array consisting of the Unix timestamp
+/// in seconds and the microseconds already elapsed in the current second.
+#[derive(Debug)]
+pub struct Time;
 
-// 该命令用于在后台异步保存当前数据库的数据到磁盘
+impl CmdExecutor for Time {
+    const NAME: &'static str = "TIME";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = TIME_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(_handler), ret, err)]
+    async fn execute(
+        self,
+        _handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        Ok(Some(Resp3::new_array(vec![
+            Resp3::new_blob_string(itoa::Buffer::new().format(now.as_secs()).to_owned().into()),
+            Resp3::new_blob_string(
+                itoa::Buffer::new()
+                    .format(now.subsec_micros())
+                    .to_owned()
+                    .into(),
+            ),
+        ])))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(Time)
+    }
+}
+
+/// 返回一段字符画以及服务器的版本。VERSION参数只用于兼容Redis，不影响输出
 /// # Reply:
 ///
-/// **Simple string reply:** Background saving started.
-/// **Simple string reply:** Background saving scheduled.
+/// **Bulk string reply:** a string containing generative computer art and the server version.
+/// **Verbatim string reply:** the same text with format `txt` when using RESP3.
 #[derive(Debug)]
-pub struct BgSave;
+pub struct Lolwut;
 
-impl CmdExecutor for BgSave {
-    const NAME: &'static str = "BGSAVE";
+impl CmdExecutor for Lolwut {
+    const NAME: &'static str = "LOLWUT";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = LOLWUT_FLAG;
+    const ARITY: Int = -1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        Ok(Some(handler.new_text_reply(format!(
+            "{LOLWUT_ART}rutin ver. {}\n",
+            env!("CARGO_PKG_VERSION")
+        ))))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        match args.len() {
+            0 => {}
+            2 => {
+                if !args.next().unwrap().eq_ignore_ascii_case(b"VERSION") {
+                    return Err(Err::Syntax.into());
+                }
+                util::atoi::<u64>(&args.next().unwrap()).map_err(|_| Err::A2IParse)?;
+            }
+            _ => return Err(Err::Syntax.into()),
+        }
+
+        Ok(Lolwut)
+    }
+}
+
+const LOLWUT_ART: &str = r"             _   _
+ _ __ _   _| |_(_)_ __
+| '__| | | | __| | '_ \
+| |  | |_| | |_| | | | |
+|_|   \__,_|\__|_|_| |_|
+
+";
+
+/// 返回一段可读的内存诊断报告，检查峰值内存、maxmemory以及键的平均大小
+/// # Reply:
+///
+/// **Bulk string reply:** a memory problems report.
+/// **Verbatim string reply:** the same text with format `txt` when using RESP3.
+#[derive(Debug)]
+pub struct MemoryDoctor;
+
+impl CmdExecutor for MemoryDoctor {
+    const NAME: &'static str = "MEMORYDOCTOR";
     const TYPE: CmdType = CmdType::Other;
-    const FLAG: CmdFlag = BGSAVE_FLAG;
+    const FLAG: CmdFlag = MEMORY_DOCTOR_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let rdb_conf = &handler.shared.conf().rdb;
-        let shared = &handler.shared;
+        // 占用的内存低于该值时不进行诊断
+        const EMPTY_THRESHOLD: usize = 5 * 1024 * 1024;
 
-        let mut rdb = if let Some(rdb) = rdb_conf {
-            Rdb::new(shared, rdb.file_path.clone(), rdb.enable_checksum)
+        let (allocated, peak) = util::allocated_memory();
+        let keys = handler.shared.db().len_nonexpired();
+        let dataset = tokio::task::block_in_place(|| handler.shared.db().dataset_bytes());
+        let max_memory = handler
+            .shared
+            .conf()
+            .memory
+            .max_memory
+            .load(Ordering::Relaxed) as usize;
+
+        if allocated < EMPTY_THRESHOLD && keys == 0 {
+            return Ok(Some(handler.new_text_reply(
+                "This instance is empty or is using very little memory, the memory doctor \
+                 can't diagnose it in these conditions.\n",
+            )));
+        }
+
+        let mut issues = vec![];
+        if peak > allocated / 2 * 3 {
+            issues.push(format!(
+                " * Peak memory: in the past this instance used more than 150% of the memory \
+                 it is currently using (peak {peak} bytes, allocated {allocated} bytes). The \
+                 allocator is normally not able to release memory after a peak.\n"
+            ));
+        }
+        if max_memory != 0 && allocated > max_memory {
+            issues.push(format!(
+                " * Max memory: the allocated memory ({allocated} bytes) exceeds maxmemory \
+                 ({max_memory} bytes).\n"
+            ));
+        }
+        if keys != 0 && dataset / keys > 1024 * 1024 {
+            issues.push(format!(
+                " * Big keys: the average size of a key is {} bytes, consider splitting big \
+                 keys into smaller ones.\n",
+                dataset / keys
+            ));
+        }
+
+        let report = if issues.is_empty() {
+            format!(
+                "No memory issue was found. allocated: {allocated} bytes, peak: {peak} bytes, \
+                 dataset: {dataset} bytes, keys: {keys}.\n"
+            )
         } else {
-            Rdb::new(shared, "./dump.rdb".into(), false)
+            format!(
+                "The memory doctor detected the following issues:\n\n{}",
+                issues.concat()
+            )
         };
-        // let mut rdb = RDB::new(shared, rdb_conf.unwrap_or("").file_path.clone(), rdb_conf.enable_checksum);
-        tokio::spawn(async move {
-            if let Err(e) = rdb.save().await {
-                tracing::error!("save rdb error: {:?}", e);
-            } else {
-                tracing::info!("save rdb success");
-            }
-        });
 
-        Ok(Some(Resp3::new_simple_string(
-            "Background saving started".into(),
-        )))
+        Ok(Some(handler.new_text_reply(report)))
     }
 
     fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
@@ -248,152 +389,1558 @@ impl CmdExecutor for BgSave {
             return Err(Err::WrongArgNum.into());
         }
 
-        Ok(BgSave)
+        Ok(MemoryDoctor)
     }
 }
 
-// pub struct BgRewriteAof;
-
+/// 返回内存相关的统计数值：peak.allocated和total.allocated为全局分配器提交的内存的
+/// 峰值和当前值；dataset.bytes为键和值占用的字节数的估计值；keys.count为键的数量
+/// # Reply:
+///
+/// **Map reply:** memory usage details. (RESP2下为数组)
 #[derive(Debug)]
-pub struct Auth {
-    pub username: Bytes,
-    pub password: Bytes,
-}
+pub struct MemoryStats;
 
-impl CmdExecutor for Auth {
-    const NAME: &'static str = "AUTH";
+impl CmdExecutor for MemoryStats {
+    const NAME: &'static str = "MEMORYSTATS";
     const TYPE: CmdType = CmdType::Other;
-    const FLAG: CmdFlag = AUTH_FLAG;
+    const FLAG: CmdFlag = MEMORY_STATS_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        if let Some(acl) = handler.shared.conf().security.acl.as_ref() {
-            if let Some(ac) = acl.get(&self.username) {
-                if !ac.is_pwd_correct(&self.password) {
-                    Err("ERR invalid password".into())
-                } else {
-                    // 设置客户端的权限
-                    handler.context.ac = std::sync::Arc::new(ac.clone());
-                    Ok(Some(Resp3::new_simple_string("OK".into())))
-                }
-            } else {
-                Err("ERR invalid username".into())
-            }
-        } else {
-            // 没有设置ACL
-            Ok(Some(Resp3::new_simple_string("OK".into())))
-        }
+        let (allocated, peak) = util::allocated_memory();
+        let keys = handler.shared.db().len_nonexpired();
+        let dataset = tokio::task::block_in_place(|| handler.shared.db().dataset_bytes());
+
+        let stats = [
+            ("peak.allocated", peak),
+            ("total.allocated", allocated),
+            ("dataset.bytes", dataset),
+            ("keys.count", keys),
+        ];
+
+        Ok(Some(Resp3::new_map(
+            stats
+                .into_iter()
+                .map(|(name, value)| {
+                    (
+                        Resp3::new_simple_string(name.into()),
+                        Resp3::new_integer(value as Int),
+                    )
+                })
+                .collect::<AHashMap<_, _>>(),
+        )))
     }
 
     fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
-        if args.len() != 1 && args.len() != 2 {
+        if !args.is_empty() {
             return Err(Err::WrongArgNum.into());
         }
 
-        Ok(Auth {
-            username: args.next().unwrap(),
-            password: args.next().unwrap_or_default(),
-        })
+        Ok(MemoryStats)
     }
 }
 
-/// # Desc:
-///
-/// 执行该命令后，会开启客户端缓存追踪。每次执行读命令时，客户端都可能缓存该键
-/// 值对， 因此需要对客户端读过的键值对加入 监听事件ObjectEvent::Write，当该键
-/// 值对被修改时服务端需要发送invalidation messages给客户端。取消追踪并不意味
-/// 着客户端一定不会收到invalidation messages，它仅仅保证对于新的读命令，不再监
-/// 听其键值对
-///
+/// 该命令用于获取服务器的各种信息和统计数值
 /// # Reply:
 ///
-/// **Simple string reply:** OK if the connection was successfully put in tracking
-/// mode or if the tracking mode was successfully disabled. Otherwise, an error is
-/// returned.
+/// **Bulk string reply:** a map of info fields, one field per line in the form of
+/// `<field>:<value>`. Also contains section header lines starting with `#` and blank lines.
+/// **Verbatim string reply:** the same text with format `txt` when using RESP3.
 #[derive(Debug)]
-pub struct ClientTracking {
-    switch_on: bool,
-    // 将该客户端缓存失效的消息重定向
-    redirect: Option<Id>,
+pub struct Info {
+    sections: Vec<Bytes>,
 }
 
-impl CmdExecutor for ClientTracking {
-    const NAME: &'static str = "TRACKING";
+impl CmdExecutor for Info {
+    const NAME: &'static str = "INFO";
     const TYPE: CmdType = CmdType::Other;
-    const FLAG: CmdFlag = CLIENT_TRACKING_FLAG;
+    const FLAG: CmdFlag = INFO_FLAG;
+    const ARITY: Int = -1;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        if !self.switch_on {
-            // 关闭追踪后并不意味着之前的追踪事件会被删除，只是不再添加新的追踪事件
-            handler.context.client_track = None;
-            return Ok(Some(Resp3::new_simple_string("OK".into())));
-        }
+        let conf = handler.shared.conf();
 
-        if let Some(redirect) = self.redirect {
-            let redirect_bg_sender = handler
-                .shared
-                .db()
-                .get_client_bg_sender(redirect)
-                .ok_or("ERR the client ID you want redirect to does not exist")?;
-            handler.context.client_track = Some(redirect_bg_sender);
-        } else {
-            handler.context.client_track = Some(handler.bg_task_channel.new_sender());
+        // 未指定section时，返回所有section
+        let all = self.sections.is_empty()
+            || self.sections.iter().any(|s| {
+                [b"all".as_ref(), b"default", b"everything"]
+                    .iter()
+                    .any(|all| s.eq_ignore_ascii_case(all))
+            });
+        let contains = |section: &str| {
+            all || self
+                .sections
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(section.as_bytes()))
+        };
+
+        let mut res = String::with_capacity(256);
+
+        if contains("server") {
+            res.push_str(&format!(
+                "# Server\r\nprocess_id:{}\r\nrun_id:{}\r\ntcp_port:{}\r\n",
+                std::process::id(),
+                conf.server.run_id,
+                conf.server.port,
+            ));
         }
 
-        Ok(Some(Resp3::new_simple_string("OK".into())))
-    }
+        if contains("stats") {
+            if !res.is_empty() {
+                res.push_str("\r\n");
+            }
 
-    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
-        if args.len() > 2 {
-            return Err(Err::WrongArgNum.into());
+            let db = handler.shared.db();
+            res.push_str(&format!(
+                "# Stats\r\nkeyspace_hits:{}\r\nkeyspace_misses:{}\r\nexpired_keys:{}\r\n\
+                 expired_keys_lazy:{}\r\nexpired_keys_active:{}\r\n",
+                db.keyspace_hits(),
+                db.keyspace_misses(),
+                db.expired_keys_lazy() + db.expired_keys_active(),
+                db.expired_keys_lazy(),
+                db.expired_keys_active(),
+            ));
         }
 
-        let mut switch = [0; 3];
-        let bulk = args.next().unwrap();
-        let len = bulk.len();
-        switch[..len].clone_from_slice(bulk.as_ref());
-        switch[..len].make_ascii_uppercase();
+        if contains("replication") {
+            if !res.is_empty() {
+                res.push_str("\r\n");
+            }
 
-        let switch = match &switch[..len] {
-            b"ON" => true,
-            b"OFF" => false,
-            _ => return Err("ERR invalid switch is given")?,
-        };
+            let replica_conf = &conf.replica;
+            let offset = replica_conf.offset.load();
+            res.push_str("# Replication\r\n");
+            match replica_conf.replicaof.load().as_deref() {
+                None => {
+                    let replicas = handler.shared.wcmd_propagator().replica_states();
+                    res.push_str(&format!(
+                        "role:master\r\nconnected_slaves:{}\r\n",
+                        replicas.len()
+                    ));
+                    for (i, replica) in replicas.iter().enumerate() {
+                        res.push_str(&format!(
+                            "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
+                            i,
+                            replica.addr.ip(),
+                            replica.addr.port(),
+                            replica.ack_offset,
+                            replica.last_ack.elapsed().as_secs(),
+                        ));
+                    }
+                }
+                Some(master_addr) => {
+                    let (host, port) = master_addr.rsplit_once(':').unwrap_or((master_addr, ""));
+                    let link_status = if replica_conf.master_link_up.load() {
+                        "up"
+                    } else {
+                        "down"
+                    };
+                    res.push_str(&format!(
+                        "role:slave\r\nmaster_host:{host}\r\nmaster_port:{port}\r\nmaster_link_status:{link_status}\r\nslave_repl_offset:{offset}\r\n",
+                    ));
+                }
+            }
+            res.push_str(&format!(
+                "master_replid:{}\r\nmaster_repl_offset:{}\r\n",
+                replica_conf.master_replid.load(),
+                offset,
+            ));
+        }
 
-        let redirect = if let Some(redirect) = args.next() {
-            Some(util::atoi::<Id>(&redirect)?)
-        } else {
-            None
-        };
+        Ok(Some(handler.new_text_reply(res)))
+    }
 
-        Ok(ClientTracking {
-            switch_on: switch,
-            redirect,
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        Ok(Info {
+            sections: args.collect(),
         })
     }
 }
 
-#[cfg(test)]
-mod cmd_other_tests {
-    use std::sync::Arc;
+/// 修改服务器的复制设置。REPLICAOF NO ONE会将从服务器提升为主服务器：保留当前的
+/// 数据，停止接收主服务器的命令并生成新的replid，之后由该服务器自行删除过期键
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct ReplicaOf {
+    // None代表NO ONE
+    master_addr: Option<String>,
+}
 
-    use super::*;
-    use crate::{
-        conf::{AccessControl, Acl, Conf},
-        shared::Shared,
-        util::test_init,
-    };
+impl CmdExecutor for ReplicaOf {
+    const NAME: &'static str = "REPLICAOF";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = REPLICAOF_FLAG;
+    const ARITY: Int = 3;
 
-    #[tokio::test]
-    async fn auth_test() {
-        test_init();
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let replica_conf = &handler.shared.conf().replica;
+
+        match self.master_addr {
+            Some(master_addr) => {
+                if replica_conf
+                    .replicaof
+                    .load()
+                    .as_deref()
+                    .is_some_and(|addr| *addr == master_addr)
+                {
+                    return Ok(Some(Resp3::new_simple_string(
+                        "OK Already connected to specified master".into(),
+                    )));
+                }
+
+                replica_conf.replicaof.store(Some(Arc::new(master_addr)));
+                replica_conf.master_link_up.store(false);
+                handler.shared.db().set_replica_mode(true);
+            }
+            None => {
+                // 已经是主服务器
+                if replica_conf.replicaof.swap(None).is_none() {
+                    return Ok(Some(Resp3::new_simple_string("OK".into())));
+                }
+                replica_conf.master_link_up.store(false);
+                handler.shared.db().set_replica_mode(false);
+
+                // 提升为主服务器，其它从服务器只能通过全量同步与其保持一致
+                replica_conf.master_replid.store(Arc::new(gen_run_id()));
+            }
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let host = args.next().unwrap();
+        let port = args.next().unwrap();
+        if host.eq_ignore_ascii_case(b"NO") && port.eq_ignore_ascii_case(b"ONE") {
+            return Ok(ReplicaOf { master_addr: None });
+        }
+
+        let host = String::from_utf8(host.to_vec()).map_err(|_| Err::Syntax)?;
+        let port = util::atoi::<u16>(&port).map_err(|_| "ERR Invalid master port")?;
+
+        Ok(ReplicaOf {
+            master_addr: Some(format!("{host}:{port}")),
+        })
+    }
+}
+
+/// 生成新的replid，从服务器之后只能进行全量同步
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugChangeReplId;
+
+impl CmdExecutor for DebugChangeReplId {
+    const NAME: &'static str = "DEBUGCHANGE-REPL-ID";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_CHANGE_REPL_ID_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        handler
+            .shared
+            .conf()
+            .replica
+            .master_replid
+            .store(Arc::new(gen_run_id()));
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugChangeReplId)
+    }
+}
+
+/// 开启或关闭定期删除过期键。关闭后过期键只会在被访问时删除
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugSetActiveExpire {
+    enable: bool,
+}
+
+impl CmdExecutor for DebugSetActiveExpire {
+    const NAME: &'static str = "DEBUGSET-ACTIVE-EXPIRE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_SET_ACTIVE_EXPIRE_FLAG;
+    const ARITY: Int = 3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        handler.shared.set_active_expire(self.enable);
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let enable = match args.next().unwrap().as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => return Err(Err::Syntax.into()),
+        };
+
+        Ok(DebugSetActiveExpire { enable })
+    }
+}
+
+/// 设置List元素单独成为一个节点的长度阈值，用于测试List的编码
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugQuicklistPackedThreshold {
+    threshold: usize,
+}
+
+impl CmdExecutor for DebugQuicklistPackedThreshold {
+    const NAME: &'static str = "DEBUGQUICKLIST-PACKED-THRESHOLD";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_QUICKLIST_PACKED_THRESHOLD_FLAG;
+    const ARITY: Int = 3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        handler
+            .shared
+            .conf()
+            .memory
+            .list_packed_threshold
+            .store(self.threshold, Ordering::Relaxed);
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let threshold = util::atoi::<usize>(&args.next().unwrap()).map_err(|_| Err::A2IParse)?;
+        if threshold == 0 {
+            return Err("ERR threshold should be greater than 0".into());
+        }
+
+        Ok(DebugQuicklistPackedThreshold { threshold })
+    }
+}
+
+/// 使用glob风格的模式匹配字符串，用于验证模式匹配的行为是否与Redis一致
+/// # Reply:
+///
+/// **Integer reply:** 1 if the string matches the pattern, 0 otherwise.
+#[derive(Debug)]
+pub struct DebugStringMatchLen {
+    pattern: Bytes,
+    string: Bytes,
+}
+
+impl CmdExecutor for DebugStringMatchLen {
+    const NAME: &'static str = "DEBUGSTRINGMATCH-LEN";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_STRINGMATCH_LEN_FLAG;
+    const ARITY: Int = 4;
+
+    #[instrument(level = "debug", skip(_handler), ret, err)]
+    async fn execute(
+        self,
+        _handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let matched = util::glob_match(&self.pattern, &self.string, false);
+
+        Ok(Some(Resp3::new_integer(matched as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugStringMatchLen {
+            pattern: args.next().unwrap(),
+            string: args.next().unwrap(),
+        })
+    }
+}
+
+/// 返回对象的调试信息，其中serializedlength为对象值按照RDB格式编码后的长度。使用
+/// quicklist编码的List还会返回ql_nodes。
+///
+/// `DEBUG OBJECT ENCODING-FORCE key encoding`强制转换对象的编码(Hash: hashtable, ziplist;
+/// Set: hashtable, intset)，用于测试
+/// # Reply:
+///
+/// **Simple string reply:** the debugging information of the object.
+/// **Simple string reply:** `OK` if the encoding was forced.
+#[derive(Debug)]
+pub struct DebugObject {
+    key: Key,
+    force_encoding: Option<Bytes>,
+}
+
+impl CmdExecutor for DebugObject {
+    const NAME: &'static str = "DEBUGOBJECT";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_OBJECT_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if let Some(encoding) = self.force_encoding {
+            handler
+                .shared
+                .db()
+                .update_object(&self.key, |obj| {
+                    if !obj.force_encoding(&encoding) {
+                        return Err("ERR unsupported encoding conversion".into());
+                    }
+                    Ok(())
+                })
+                .await?;
+
+            return Ok(Some(Resp3::new_simple_string("OK".into())));
+        }
+
+        let memory_conf = &handler.shared.conf().memory;
+        let max_entries = memory_conf
+            .list_max_listpack_entries
+            .load(Ordering::Relaxed);
+        let packed_threshold = memory_conf.list_packed_threshold.load(Ordering::Relaxed);
+
+        let mut info = String::new();
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let encoding = match obj.value() {
+                    ObjValue::List(list) => list.encoding(max_entries, packed_threshold),
+                    _ => obj.encoding(),
+                };
+
+                // 只计算编码后的长度，不进行持久化
+                let mut buf = BytesMut::new();
+                encode_object_value(&mut buf, obj.value().clone());
+
+                let atc = obj.atc();
+                info = format!(
+                    "Value at:{:p} refcount:1 encoding:{} serializedlength:{} lru:{} lru_seconds_idle:{}",
+                    obj,
+                    encoding,
+                    buf.len(),
+                    atc.access_time(),
+                    atc.idle_time(),
+                );
+                if let (ObjValue::List(list), "quicklist") = (obj.value(), encoding) {
+                    info.push_str(&format!(
+                        " ql_nodes:{}",
+                        list.ql_nodes(max_entries, packed_threshold)
+                    ));
+                }
+
+                Ok(())
+            })
+            .await?;
+
+        Ok(Some(Resp3::new_simple_string(info.into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let force_encoding = match args.len() {
+            1 => false,
+            3 => true,
+            _ => return Err(Err::WrongArgNum.into()),
+        };
+
+        if force_encoding && !args.next().unwrap().eq_ignore_ascii_case(b"ENCODING-FORCE") {
+            return Err(Err::Syntax.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(DebugObject {
+            key,
+            force_encoding: args
+                .next()
+                .map(|encoding| encoding.to_ascii_lowercase().into()),
+        })
+    }
+}
+
+/// 使当前连接休眠指定的秒数(可以是小数)，其它连接不受影响
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugSleep {
+    duration: Duration,
+}
+
+impl CmdExecutor for DebugSleep {
+    const NAME: &'static str = "DEBUGSLEEP";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_SLEEP_FLAG;
+    const ARITY: Int = 3;
+
+    #[instrument(level = "debug", skip(_handler), ret, err)]
+    async fn execute(
+        self,
+        _handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        tokio::time::sleep(self.duration).await;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugSleep {
+            duration: parse_sleep_secs(&args.next().unwrap())?,
+        })
+    }
+}
+
+/// 暂停所有连接的命令执行指定的秒数(可以是小数)，模拟单线程的Redis执行DEBUG SLEEP时
+/// 整个服务器被阻塞的情况
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugSleepBlock {
+    duration: Duration,
+}
+
+impl CmdExecutor for DebugSleepBlock {
+    const NAME: &'static str = "DEBUGSLEEP-BLOCK";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_SLEEP_BLOCK_FLAG;
+    const ARITY: Int = 3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let deadline = Instant::now() + self.duration;
+        handler.shared.pause_until(deadline);
+        tokio::time::sleep_until(deadline).await;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugSleepBlock {
+            duration: parse_sleep_secs(&args.next().unwrap())?,
+        })
+    }
+}
+
+/// 从文件批量导入键值对作为字符串对象，用于预热缓存。format为TSV或BINARY，可通过
+/// EX seconds为导入的键设置过期时间。导入的键不会传播给replica和AOF
+/// # Reply:
+///
+/// **Integer reply:** the number of keys loaded.
+#[derive(Debug)]
+pub struct DebugLoadFile {
+    path: String,
+    format: BulkFormat,
+    ttl: Option<Duration>,
+}
+
+impl CmdExecutor for DebugLoadFile {
+    const NAME: &'static str = "DEBUGLOAD-FILE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_LOAD_FILE_FLAG;
+    const ARITY: Int = -4;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let count = bulk_load(handler.shared.db(), &self.path, self.format, self.ttl)
+            .await
+            .map_err(|e| format!("ERR failed to load file: {e}"))?;
+
+        Ok(Some(Resp3::new_integer(count as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 2 && args.len() != 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let path = String::from_utf8(args.next().unwrap().to_vec()).map_err(|_| Err::Syntax)?;
+        let format = BulkFormat::try_from(args.next().unwrap().as_ref())
+            .map_err(|_| "ERR format should be TSV or BINARY")?;
+
+        let ttl = match args.next() {
+            Some(ex) if ex.eq_ignore_ascii_case(b"EX") => {
+                let secs = util::atoi::<u64>(&args.next().unwrap())?;
+                if secs == 0 {
+                    return Err("ERR invalid expire time".into());
+                }
+                Some(Duration::from_secs(secs))
+            }
+            Some(_) => return Err(Err::Syntax.into()),
+            None => None,
+        };
+
+        Ok(DebugLoadFile { path, format, ttl })
+    }
+}
+
+/// 使当前连接的任务panic，用于测试单个连接崩溃不会影响服务器和其它连接。只有在配置中
+/// 开启enable_debug_crash时才可用
+/// # Reply:
+///
+/// 连接被断开，不会返回回复
+#[derive(Debug)]
+pub struct DebugPanic;
+
+impl CmdExecutor for DebugPanic {
+    const NAME: &'static str = "DEBUGPANIC";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_PANIC_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if !handler.shared.conf().server.enable_debug_crash {
+            return Err(DEBUG_CRASH_DISABLED.into());
+        }
+
+        panic!("DEBUG PANIC");
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugPanic)
+    }
+}
+
+/// 模拟内存分配失败。真正的内存分配失败(handle_alloc_error)会直接终止整个进程，因此这里
+/// 通过try_reserve请求一块无法满足的内存，并将分配失败转换为panic，只有当前连接的任务会
+/// 终止。只有在配置中开启enable_debug_crash时才可用
+/// # Reply:
+///
+/// 连接被断开，不会返回回复
+#[derive(Debug)]
+pub struct DebugOom;
+
+impl CmdExecutor for DebugOom {
+    const NAME: &'static str = "DEBUGOOM";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_OOM_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if !handler.shared.conf().server.enable_debug_crash {
+            return Err(DEBUG_CRASH_DISABLED.into());
+        }
+
+        // 请求的容量没有超过isize::MAX，但超过了地址空间，分配器必定返回失败
+        let mut buf = Vec::<u8>::new();
+        match buf.try_reserve_exact(isize::MAX as usize) {
+            Err(e) => panic!("memory allocation failed: {e}"),
+            Ok(()) => unreachable!(),
+        }
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugOom)
+    }
+}
+
+/// 清空数据库后从RDB文件重新加载数据，用于测试持久化与恢复。默认先将当前数据保存到
+/// RDB文件；指定NOSAVE时不保存，直接从磁盘上已有的文件加载，内存中未保存的修改会被
+/// 丢弃。文件不存在时数据库为空。开启AOF时不可用
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugReload {
+    nosave: bool,
+}
+
+impl CmdExecutor for DebugReload {
+    const NAME: &'static str = "DEBUGRELOAD";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_RELOAD_FLAG;
+    const ARITY: Int = -2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let shared = &handler.shared;
+        let conf = shared.conf();
+        if conf.aof.is_some() {
+            return Err("ERR DEBUG RELOAD is not supported when AOF is enabled".into());
+        }
+
+        let mut rdb = if let Some(rdb) = &conf.rdb {
+            Rdb::new(shared, rdb.file_path.clone(), rdb.enable_checksum)
+        } else {
+            Rdb::new(shared, "./dump.rdb".into(), false)
+        };
+
+        if !self.nosave {
+            rdb.save()
+                .await
+                .map_err(|e| format!("ERR failed to save RDB file: {e}"))?;
+        }
+
+        shared.db().clear().await;
+
+        if let Err(e) = rdb.load().await {
+            // 文件不存在时相当于加载了一个空的数据集
+            let not_found = e
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound);
+            if !not_found {
+                return Err(format!("ERR failed to load RDB file: {e}").into());
+            }
+            tracing::warn!("RDB file not found, dataset is empty after reload");
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        let nosave = match args.next() {
+            Some(opt) if opt.eq_ignore_ascii_case(b"NOSAVE") => true,
+            Some(_) => return Err(Err::Syntax.into()),
+            None => false,
+        };
+
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugReload { nosave })
+    }
+}
+
+/// 清空数据库中的所有键。不会保存到磁盘，也不会传播给replica和AOF
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct DebugFlushAll;
+
+impl CmdExecutor for DebugFlushAll {
+    const NAME: &'static str = "DEBUGFLUSHALL";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DEBUG_FLUSHALL_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        handler.shared.db().clear().await;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(DebugFlushAll)
+    }
+}
+
+const DEBUG_CRASH_DISABLED: &str =
+    "ERR DEBUG PANIC and DEBUG OOM are disabled, set enable_debug_crash to enable them";
+
+fn parse_sleep_secs(secs: &[u8]) -> Result<Duration, CmdError> {
+    std::str::from_utf8(secs)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| "ERR value is not a valid float".into())
+}
+
+/// 在运行时修改配置。目前只支持修改对象编码转换的阈值(修改后只对新建或修改的对象生效)
+/// 以及requirepass(设置为空字符串时default用户不再需要密码)
+/// # Reply:
+///
+/// **Simple string reply:** OK when the configuration was set properly.
+#[derive(Debug)]
+pub struct ConfigSet {
+    pub params: Vec<(Bytes, Bytes)>,
+}
+
+impl ConfigSet {
+    const PARAMS: [&'static str; 7] = [
+        "maxmemory",
+        "max-memory",
+        "hash-max-listpack-entries",
+        "hash-max-listpack-value",
+        "list-max-listpack-entries",
+        "set-max-intset-entries",
+        "requirepass",
+    ];
+}
+
+impl CmdExecutor for ConfigSet {
+    const NAME: &'static str = "CONFIGSET";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CONFIG_SET_FLAG;
+    const ARITY: Int = -4;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let conf = handler.shared.conf();
+        let memory_conf = &conf.memory;
+        for (param, value) in self.params {
+            match param.as_ref() {
+                b"requirepass" => {
+                    conf.security.set_requirepass(value);
+                    continue;
+                }
+                b"maxmemory" | b"max-memory" => {
+                    let max_memory =
+                        parse_memory_size(&value).map_err(|e| max_memory_err(&value, e))?;
+                    memory_conf.max_memory.store(max_memory, Ordering::Relaxed);
+                    continue;
+                }
+                _ => {}
+            }
+
+            // 数值已在解析时检查过
+            let value = util::atoi::<usize>(&value).map_err(|_| Err::A2IParse)?;
+            match param.as_ref() {
+                b"hash-max-listpack-entries" => memory_conf
+                    .hash_max_listpack_entries
+                    .store(value, Ordering::Relaxed),
+                b"hash-max-listpack-value" => memory_conf
+                    .hash_max_listpack_value
+                    .store(value, Ordering::Relaxed),
+                b"list-max-listpack-entries" => memory_conf
+                    .list_max_listpack_entries
+                    .store(value, Ordering::Relaxed),
+                b"set-max-intset-entries" => memory_conf
+                    .set_max_intset_entries
+                    .store(value, Ordering::Relaxed),
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.is_empty() || args.len() % 2 == 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let mut params = Vec::with_capacity(args.len() / 2);
+        while let (Some(param), Some(value)) = (args.next(), args.next()) {
+            // 参数名大小写不敏感，且允许使用配置文件中的下划线形式
+            let param: Bytes = param
+                .iter()
+                .map(|b| {
+                    if *b == b'_' {
+                        b'-'
+                    } else {
+                        b.to_ascii_lowercase()
+                    }
+                })
+                .collect();
+            if !Self::PARAMS.iter().any(|p| p.as_bytes() == param) {
+                return Err(format!(
+                    "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                    String::from_utf8_lossy(&param)
+                )
+                .into());
+            }
+
+            match param.as_ref() {
+                b"requirepass" => {}
+                b"maxmemory" | b"max-memory" => {
+                    parse_memory_size(&value).map_err(|e| max_memory_err(&value, e))?;
+                }
+                _ => {
+                    util::atoi::<usize>(&value).map_err(|_| Err::A2IParse)?;
+                }
+            }
+            params.push((param, value));
+        }
+
+        Ok(ConfigSet { params })
+    }
+}
+
+fn max_memory_err(value: &[u8], reason: String) -> CmdError {
+    format!(
+        "ERR Invalid argument '{}' for CONFIG SET 'maxmemory' - {reason}",
+        String::from_utf8_lossy(value)
+    )
+    .into()
+}
+
+/// 将CONFIG SET修改的配置写回启动时加载的配置文件，使其在重启后依然生效
+/// # Reply:
+///
+/// **Simple string reply:** OK when the configuration was rewritten properly. Otherwise
+/// an error is returned.
+#[derive(Debug)]
+pub struct ConfigRewrite;
+
+impl CmdExecutor for ConfigRewrite {
+    const NAME: &'static str = "CONFIGREWRITE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CONFIG_REWRITE_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let conf = handler.shared.conf();
+        if conf.config_file.is_none() {
+            return Err("ERR The server is running without a config file".into());
+        }
+
+        conf.rewrite()
+            .map_err(|e| format!("ERR Rewriting config file: {e}"))?;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(ConfigRewrite)
+    }
+}
+
+/// 清零INFO stats中的累计统计，不影响连接以及运行时间等信息
+/// # Reply:
+///
+/// **Simple string reply:** always OK.
+#[derive(Debug)]
+pub struct ConfigResetStat;
+
+impl CmdExecutor for ConfigResetStat {
+    const NAME: &'static str = "CONFIGRESETSTAT";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CONFIG_RESETSTAT_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        handler.shared.db().reset_stats();
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(ConfigResetStat)
+    }
+}
+
+// 该命令用于在后台异步保存当前数据库的数据到磁盘
+/// # Reply:
+///
+/// **Simple string reply:** Background saving started.
+/// **Simple string reply:** Background saving scheduled.
+#[derive(Debug)]
+pub struct BgSave;
+
+impl CmdExecutor for BgSave {
+    const NAME: &'static str = "BGSAVE";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = BGSAVE_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let rdb_conf = &handler.shared.conf().rdb;
+        let shared = &handler.shared;
+
+        let mut rdb = if let Some(rdb) = rdb_conf {
+            Rdb::new(shared, rdb.file_path.clone(), rdb.enable_checksum)
+        } else {
+            Rdb::new(shared, "./dump.rdb".into(), false)
+        };
+        // let mut rdb = RDB::new(shared, rdb_conf.unwrap_or("").file_path.clone(), rdb_conf.enable_checksum);
+        tokio::spawn(async move {
+            if let Err(e) = rdb.save().await {
+                tracing::error!("save rdb error: {:?}", e);
+            } else {
+                tracing::info!("save rdb success");
+            }
+        });
+
+        Ok(Some(Resp3::new_simple_string(
+            "Background saving started".into(),
+        )))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(BgSave)
+    }
+}
+
+// pub struct BgRewriteAof;
+
+#[derive(Debug)]
+pub struct Auth {
+    pub username: Bytes,
+    pub password: Bytes,
+}
+
+impl CmdExecutor for Auth {
+    const NAME: &'static str = "AUTH";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = AUTH_FLAG;
+    const ARITY: Int = -2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        // default用户的密码由requirepass设置
+        if self.username.eq_ignore_ascii_case(b"default") || self.username == DEFAULT_USER {
+            let ac = handler.shared.conf().security.default_ac.load_full();
+            if !ac.is_pwd_correct(&self.password) {
+                return Err(Err::WrongPass.into());
+            }
+
+            handler.context.user = DEFAULT_USER;
+            handler.context.ac = ac;
+            handler.context.authenticated = true;
+            return Ok(Some(Resp3::new_simple_string("OK".into())));
+        }
+
+        if let Some(acl) = handler.shared.conf().security.acl.as_ref() {
+            if let Some(ac) = acl.get(&self.username) {
+                if !ac.is_pwd_correct(&self.password) {
+                    Err(Err::WrongPass.into())
+                } else {
+                    // 设置客户端的权限
+                    handler.context.ac = std::sync::Arc::new(ac.clone());
+                    handler.context.authenticated = true;
+                    Ok(Some(Resp3::new_simple_string("OK".into())))
+                }
+            } else {
+                Err(Err::WrongPass.into())
+            }
+        } else {
+            // 没有设置ACL
+            handler.context.authenticated = true;
+            Ok(Some(Resp3::new_simple_string("OK".into())))
+        }
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        // AUTH <password> 以default用户的身份认证
+        match args.len() {
+            1 => Ok(Auth {
+                username: DEFAULT_USER,
+                password: args.next().unwrap(),
+            }),
+            2 => Ok(Auth {
+                username: args.next().unwrap(),
+                password: args.next().unwrap(),
+            }),
+            _ => Err(Err::WrongArgNum.into()),
+        }
+    }
+}
+
+/// # Desc:
+///
+/// 切换连接使用的协议版本，并可选地进行认证。客户端未发送HELLO时使用RESP2。未认证的
+/// 客户端必须在HELLO中通过AUTH认证，否则返回NOAUTH错误
+///
+/// # Reply:
+///
+/// **Map reply:** a list of server properties. **Simple error reply:** if the
+/// protover requested does not exist.
+#[derive(Debug)]
+pub struct Hello {
+    pub protover: Option<u8>,
+    pub auth: Option<Auth>,
+}
+
+impl CmdExecutor for Hello {
+    const NAME: &'static str = "HELLO";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = HELLO_FLAG;
+    const ARITY: Int = -1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if let Some(auth) = self.auth {
+            auth.execute(handler).await?;
+        }
+
+        // 未认证的客户端只能通过带有AUTH的HELLO认证，不能直接执行HELLO
+        if !handler.context.authenticated {
+            return Err(Err::NoAuth.into());
+        }
+
+        // 只有HELLO执行成功时才切换协议版本
+        if let Some(protover) = self.protover {
+            handler.context.protocol_version = protover;
+        }
+
+        let role = if handler.shared.conf().replica.replicaof.load().is_none() {
+            "master"
+        } else {
+            "replica"
+        };
+
+        let props: AHashMap<Resp3, Resp3> = [
+            (
+                Resp3::new_blob_string("server".into()),
+                Resp3::new_blob_string("rutin".into()),
+            ),
+            (
+                Resp3::new_blob_string("version".into()),
+                Resp3::new_blob_string(env!("CARGO_PKG_VERSION").into()),
+            ),
+            (
+                Resp3::new_blob_string("proto".into()),
+                Resp3::new_integer(handler.context.protocol_version as Int),
+            ),
+            (
+                Resp3::new_blob_string("id".into()),
+                Resp3::new_integer(handler.context.client_id as Int),
+            ),
+            (
+                Resp3::new_blob_string("mode".into()),
+                Resp3::new_blob_string("standalone".into()),
+            ),
+            (
+                Resp3::new_blob_string("role".into()),
+                Resp3::new_blob_string(role.into()),
+            ),
+            (
+                Resp3::new_blob_string("modules".into()),
+                Resp3::new_array(vec![]),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        Ok(Some(Resp3::new_map(props)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        let Some(protover) = args.next() else {
+            return Ok(Hello {
+                protover: None,
+                auth: None,
+            });
+        };
+
+        let protover = match util::atoi::<Int>(&protover) {
+            Ok(2) => 2,
+            Ok(3) => 3,
+            Ok(_) => return Err(Err::NoProto.into()),
+            Err(_) => return Err("ERR Protocol version is not an integer or out of range".into()),
+        };
+
+        let auth = match args.next() {
+            None => None,
+            Some(opt) if opt.eq_ignore_ascii_case(b"AUTH") => Some(Auth {
+                username: args.next().ok_or(Err::Syntax)?,
+                password: args.next().ok_or(Err::Syntax)?,
+            }),
+            Some(_) => return Err(Err::Syntax.into()),
+        };
+
+        if !args.is_empty() {
+            return Err(Err::Syntax.into());
+        }
+
+        Ok(Hello {
+            protover: Some(protover),
+            auth,
+        })
+    }
+}
+
+/// 返回当前连接的客户端ID。客户端ID在连接建立时分配，单调递增且不会重复使用
+/// # Reply:
+///
+/// **Integer reply:** the ID of the client.
+#[derive(Debug)]
+pub struct ClientId;
+
+impl CmdExecutor for ClientId {
+    const NAME: &'static str = "CLIENTID";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CLIENT_ID_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        Ok(Some(Resp3::new_integer(handler.context.client_id as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(ClientId)
+    }
+}
+
+/// 返回当前连接的信息，其中tot-cmds为执行过的命令数，tot-net-in和tot-net-out为连接
+/// 读取和写出的总字节数(不包括本次回复)
+/// # Reply:
+///
+/// **Bulk string reply:** a unique string for the current client.
+/// **Verbatim string reply:** the same text with format `txt` when using RESP3.
+#[derive(Debug)]
+pub struct ClientInfo;
+
+impl CmdExecutor for ClientInfo {
+    const NAME: &'static str = "CLIENTINFO";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CLIENT_INFO_FLAG;
+    const ARITY: Int = 2;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let context = &handler.context;
+        let info = format!(
+            "id={} user={} resp={} sub={} multi={} tot-cmds={} tot-net-in={} tot-net-out={}\n",
+            context.client_id,
+            String::from_utf8_lossy(&context.user),
+            context.protocol_version,
+            context.subscribed_channels.as_ref().map_or(0, Vec::len),
+            context
+                .transaction
+                .as_ref()
+                .map_or(-1, |tx| tx.queued.len() as i64),
+            context.cmd_count,
+            handler.conn.net_in(),
+            handler.conn.net_out(),
+        );
+
+        Ok(Some(handler.new_text_reply(info)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(ClientInfo)
+    }
+}
+
+/// 断开指定的连接。旧的格式`CLIENT KILL ip:port`只能指定地址；新的格式可以组合多个
+/// 过滤条件，所有条件都满足的连接才会被断开，默认不断开当前连接(SKIPME yes)
+/// # Reply:
+///
+/// **Simple string reply:** `OK` when called in 3 argument format and the connection has been closed.
+/// **Integer reply:** when called in filter/value format, the number of clients killed.
+#[derive(Debug)]
+pub struct ClientKill {
+    addr: Option<SocketAddr>,
+    id: Option<Id>,
+    skip_me: bool,
+    old_format: bool,
+}
+
+impl CmdExecutor for ClientKill {
+    const NAME: &'static str = "CLIENTKILL";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CLIENT_KILL_FLAG;
+    const ARITY: Int = -3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let db = handler.shared.db();
+
+        // 通过地址索引查找连接，无需遍历所有连接
+        let target = match (self.addr, self.id) {
+            (Some(addr), id) => db
+                .get_client_id_by_addr(&addr)
+                .filter(|target| id.is_none_or(|id| id == *target)),
+            (None, id) => id,
+        }
+        .filter(|target| !(self.skip_me && *target == handler.context.client_id))
+        .and_then(|target| db.get_client_bg_sender(target));
+
+        if let Some(bg_sender) = &target {
+            bg_sender.kill();
+        }
+
+        if self.old_format {
+            if target.is_none() {
+                return Err("ERR No such client".into());
+            }
+            Ok(Some(Resp3::new_simple_string("OK".into())))
+        } else {
+            Ok(Some(Resp3::new_integer(target.is_some() as Int)))
+        }
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        let parse_addr = |addr: &[u8]| {
+            std::str::from_utf8(addr)
+                .ok()
+                .and_then(|addr| addr.parse::<SocketAddr>().ok())
+                .ok_or(Err::Syntax)
+        };
+
+        if args.len() == 1 {
+            return Ok(ClientKill {
+                addr: Some(parse_addr(&args.next().unwrap())?),
+                id: None,
+                skip_me: false,
+                old_format: true,
+            });
+        }
+
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(Err::Syntax.into());
+        }
+
+        let mut kill = ClientKill {
+            addr: None,
+            id: None,
+            skip_me: true,
+            old_format: false,
+        };
+        while let (Some(filter), Some(value)) = (args.next(), args.next()) {
+            let mut buf = [0; 6];
+            let len = util::uppercase(&filter, &mut buf).map_err(|_| Err::Syntax)?;
+
+            match &buf[..len] {
+                b"ADDR" => kill.addr = Some(parse_addr(&value)?),
+                b"ID" => kill.id = Some(util::atoi::<Id>(&value).map_err(|_| Err::A2IParse)?),
+                b"SKIPME" => {
+                    kill.skip_me = if value.eq_ignore_ascii_case(b"YES") {
+                        true
+                    } else if value.eq_ignore_ascii_case(b"NO") {
+                        false
+                    } else {
+                        return Err(Err::Syntax.into());
+                    }
+                }
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        if kill.addr.is_none() && kill.id.is_none() {
+            return Err(Err::Syntax.into());
+        }
+
+        Ok(kill)
+    }
+}
+
+/// # Desc:
+///
+/// 执行该命令后，会开启客户端缓存追踪。每次执行读命令时，客户端都可能缓存该键
+/// 值对， 因此需要对客户端读过的键值对加入 监听事件ObjectEvent::Write，当该键
+/// 值对被修改时服务端需要发送invalidation messages给客户端。取消追踪并不意味
+/// 着客户端一定不会收到invalidation messages，它仅仅保证对于新的读命令，不再监
+/// 听其键值对
+///
+/// # Reply:
+///
+/// **Simple string reply:** OK if the connection was successfully put in tracking
+/// mode or if the tracking mode was successfully disabled. Otherwise, an error is
+/// returned.
+#[derive(Debug)]
+pub struct ClientTracking {
+    switch_on: bool,
+    // 将该客户端缓存失效的消息重定向
+    redirect: Option<Id>,
+}
+
+impl CmdExecutor for ClientTracking {
+    const NAME: &'static str = "CLIENTTRACKING";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = CLIENT_TRACKING_FLAG;
+    const ARITY: Int = -3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if !self.switch_on {
+            // 关闭追踪后并不意味着之前的追踪事件会被删除，只是不再添加新的追踪事件
+            handler.context.client_track = None;
+            return Ok(Some(Resp3::new_simple_string("OK".into())));
+        }
+
+        if let Some(redirect) = self.redirect {
+            let redirect_bg_sender = handler
+                .shared
+                .db()
+                .get_client_bg_sender(redirect)
+                .ok_or("ERR the client ID you want redirect to does not exist")?;
+            handler.context.client_track = Some(redirect_bg_sender);
+        } else {
+            handler.context.client_track = Some(handler.bg_task_channel.new_sender());
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() > 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let mut switch = [0; 3];
+        let bulk = args.next().unwrap();
+        let len = bulk.len();
+        switch[..len].clone_from_slice(bulk.as_ref());
+        switch[..len].make_ascii_uppercase();
+
+        let switch = match &switch[..len] {
+            b"ON" => true,
+            b"OFF" => false,
+            _ => return Err("ERR invalid switch is given")?,
+        };
+
+        let redirect = if let Some(redirect) = args.next() {
+            Some(util::atoi::<Id>(&redirect)?)
+        } else {
+            None
+        };
+
+        Ok(ClientTracking {
+            switch_on: switch,
+            redirect,
+        })
+    }
+}
+
+#[cfg(test)]
+mod cmd_other_tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        cmd::{flag_to_cmd_names, registry},
+        conf::{AccessControl, Acl, Conf},
+        server::RESERVE_MAX_ID,
+        shared::Shared,
+        util::{cmd, test_init},
+    };
+
+    #[tokio::test]
+    async fn auth_test() {
+        test_init();
 
         let username = "admin";
         let password = "123456";
@@ -409,61 +1956,1442 @@ mod cmd_other_tests {
         );
 
         let conf = Conf {
-            security: crate::conf::SecurityConf {
-                acl: Some(acl),
+            security: crate::conf::SecurityConf {
+                acl: Some(acl),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let shared = Shared::new(Default::default(), Arc::new(conf), Default::default());
+        let (mut handler, _) = Handler::new_fake_with(shared, None, None);
+
+        let auth = Auth::parse(
+            &mut CmdUnparsed::from([username, "1234567"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = auth.execute(&mut handler).await;
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "WRONGPASS invalid username-password pair or user is disabled."
+        );
+
+        let auth = Auth::parse(
+            &mut CmdUnparsed::from(["admin1", password].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = auth.execute(&mut handler).await;
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "WRONGPASS invalid username-password pair or user is disabled."
+        );
+
+        let auth = Auth::parse(
+            &mut CmdUnparsed::from([username, password].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        auth.execute(&mut handler).await.unwrap();
+        assert_eq!(handler.context.ac.cmd_flag(), cmd_flag);
+    }
+
+    #[tokio::test]
+    async fn requirepass_test() {
+        use crate::connection::FakeStream;
+
+        test_init();
+
+        let conf = Conf::default();
+        conf.security.set_requirepass(Bytes::from_static(b"secret"));
+        let shared = Shared::new(Default::default(), Arc::new(conf), Default::default());
+        let (tx, rx) = flume::unbounded();
+        let mut handler = Handler::new(shared.clone(), FakeStream::new(tx, rx));
+        assert!(!handler.context.authenticated);
+
+        // 未认证时只能执行AUTH和带有AUTH的HELLO
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert!(res.try_simple_error().unwrap().starts_with("NOAUTH"));
+
+        let res = handler
+            .dispatch(cmd(&["AUTH", "wrong"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().unwrap().starts_with("WRONGPASS"));
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert!(res.try_simple_error().unwrap().starts_with("NOAUTH"));
+
+        // 不带AUTH或者AUTH失败的HELLO被拒绝，也不会切换协议版本
+        for args in [
+            &["HELLO", "3"][..],
+            &["HELLO"],
+            &["HELLO", "3", "AUTH", "default", "wrong"],
+        ] {
+            let res = handler.dispatch(cmd(args)).await.unwrap().unwrap();
+            assert!(res.try_simple_error().is_some());
+            assert!(!handler.context.authenticated);
+        }
+        assert_eq!(handler.context.protocol_version, 2);
+
+        let res = handler
+            .dispatch(cmd(&["HELLO", "3", "AUTH", "default", "secret"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_map().is_some());
+        assert!(handler.context.authenticated);
+        assert_eq!(handler.context.protocol_version, 3);
+
+        let res = handler
+            .dispatch(cmd(&["AUTH", "default", "secret"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert!(res.is_null());
+
+        // 移除密码后，新连接无需认证
+        let res = handler
+            .dispatch(cmd(&["CONFIG", "SET", "requirepass", ""]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        let (tx, rx) = flume::unbounded();
+        let mut handler = Handler::new(shared, FakeStream::new(tx, rx));
+        assert!(handler.context.authenticated);
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert!(res.is_null());
+    }
+
+    #[tokio::test]
+    async fn client_id_test() {
+        test_init();
+
+        let shared = Shared::default();
+        let (mut handler1, _) = Handler::with_shared(shared.clone());
+        let (mut handler2, _) = Handler::with_shared(shared);
+
+        async fn client_id(handler: &mut Handler<impl AsyncStream>) -> Int {
+            ClientId::parse(&mut CmdUnparsed::default(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_integer()
+                .unwrap()
+        }
+
+        let id1 = client_id(&mut handler1).await;
+        let id2 = client_id(&mut handler2).await;
+        assert!(id1 > RESERVE_MAX_ID as Int);
+        assert!(id2 > id1);
+        // 同一连接的ID保持不变
+        assert_eq!(client_id(&mut handler1).await, id1);
+
+        assert!(
+            ClientId::parse(&mut ["arg"].as_ref().into(), &AccessControl::new_loose()).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn client_tracking_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+
+        let tracking = ClientTracking::parse(
+            &mut CmdUnparsed::from(["ON"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        tracking.execute(&mut handler).await.unwrap();
+        assert!(handler.context.client_track.is_some());
+
+        let tracking = ClientTracking::parse(
+            &mut CmdUnparsed::from(["OFF"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        tracking.execute(&mut handler).await.unwrap();
+        assert!(handler.context.client_track.is_none());
+    }
+
+    #[tokio::test]
+    async fn command_info_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+
+        let command_info = CommandInfo::parse(
+            &mut CmdUnparsed::from(["set", "not_exist"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = command_info.execute(&mut handler).await.unwrap().unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 2);
+
+        let set_info = res[0].try_array().unwrap();
+        assert_eq!(set_info.len(), 10);
+        assert_eq!(set_info[0], Resp3::new_blob_string("set".into()));
+        assert_eq!(set_info[1], Resp3::new_integer(-3));
+        assert!(set_info[2]
+            .try_set()
+            .unwrap()
+            .contains(&Resp3::new_simple_string("write".into())));
+        assert_eq!(set_info[3], Resp3::new_integer(1));
+        assert_eq!(set_info[4], Resp3::new_integer(1));
+        assert_eq!(set_info[5], Resp3::new_integer(1));
+        assert!(set_info[6]
+            .try_set()
+            .unwrap()
+            .contains(&Resp3::new_simple_string("@string".into())));
+
+        // 未知的命令返回Null
+        assert_eq!(res[1], Resp3::Null);
+    }
+
+    #[tokio::test]
+    async fn command_docs_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+
+        let command_docs = CommandDocs::parse(
+            &mut CmdUnparsed::from(["get", "not_exist"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = command_docs.execute(&mut handler).await.unwrap().unwrap();
+        let res = res.try_map().unwrap();
+        // 未知的命令被忽略
+        assert_eq!(res.len(), 1);
+
+        let get_doc = res
+            .get(&Resp3::new_blob_string("get".into()))
+            .unwrap()
+            .try_map()
+            .unwrap();
+        assert_eq!(
+            get_doc.get(&Resp3::new_blob_string("group".into())),
+            Some(&Resp3::new_blob_string("string".into()))
+        );
+        assert!(get_doc.contains_key(&Resp3::new_blob_string("summary".into())));
+        assert!(get_doc.contains_key(&Resp3::new_blob_string("since".into())));
+        assert_eq!(
+            get_doc
+                .get(&Resp3::new_blob_string("arguments".into()))
+                .unwrap()
+                .try_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // 每个命令都应当有文档
+        for info in CMD_INFOS.iter() {
+            assert!(info.doc().is_some(), "{} has no doc", info.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn registry_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+        // 禁止执行所有命令，能够路由到的命令会返回NOPERM而不会真正执行
+        handler.context.ac = Arc::new(AccessControl {
+            cmd_flag: 0,
+            ..AccessControl::new_strict()
+        });
+
+        let dispatch_name = |name: &str| {
+            Resp3::new_array(
+                name.split('|')
+                    .map(|s| Resp3::new_blob_string(s.to_uppercase().into()))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let no_perm = Resp3::new_simple_error(Err::NoPermission.to_string().into());
+
+        let mut all_flag = 0;
+        for (name, flag, cat_flag, _) in registry() {
+            // 每个注册的命令都能通过dispatch访问
+            let res = handler.dispatch(dispatch_name(name)).await.unwrap();
+            assert_eq!(res, Some(no_perm.clone()), "{name} is not dispatched");
+
+            assert!(flag.is_power_of_two(), "{name} has invalid flag");
+            assert_eq!(all_flag & flag, 0, "{name} has duplicated flag");
+            all_flag |= flag;
+
+            assert_eq!(
+                flag_to_cmd_names(flag).unwrap(),
+                vec![name.replace('|', "").to_uppercase()]
+            );
+            assert_ne!(cat_flag, 0, "{name} has no category");
+        }
+
+        // 反之，flag表中的每个命令都在registry中
+        for i in 0..CmdFlag::BITS {
+            if let Ok(names) = flag_to_cmd_names(1 << i) {
+                assert_ne!(all_flag & (1 << i), 0, "{names:?} is not registered");
+            }
+        }
+
+        let res = handler.dispatch(dispatch_name("not_exist")).await.unwrap();
+        assert_eq!(
+            res,
+            Some(Resp3::new_simple_error(Err::UnknownCmd.to_string().into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn info_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+
+        let info_field = |info: &str, field: &str| {
+            info.lines()
+                .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+                .map(ToString::to_string)
+                .unwrap()
+        };
+
+        let info = Info::parse(
+            &mut CmdUnparsed::from(["server"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = info.execute(&mut handler).await.unwrap().unwrap();
+        let res = String::from_utf8(res.try_blob().unwrap().to_vec()).unwrap();
+        assert!(res.starts_with("# Server"));
+        assert!(!res.contains("# Replication"));
+        let run_id = info_field(&res, "run_id");
+        assert_eq!(run_id.len(), 40);
+        assert!(run_id.chars().all(|c| c.is_ascii_hexdigit()));
+
+        let replication = || {
+            Info::parse(
+                &mut CmdUnparsed::from(["replication"].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+        };
+        let res = replication().execute(&mut handler).await.unwrap().unwrap();
+        let res = String::from_utf8(res.try_blob().unwrap().to_vec()).unwrap();
+        assert_eq!(info_field(&res, "role"), "master");
+        let old_replid = info_field(&res, "master_replid");
+        assert_eq!(old_replid.len(), 40);
+
+        let change_repl_id =
+            DebugChangeReplId::parse(&mut CmdUnparsed::default(), &AccessControl::new_loose())
+                .unwrap();
+        let res = change_repl_id.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(res, Resp3::new_simple_string("OK".into()));
+
+        let res = replication().execute(&mut handler).await.unwrap().unwrap();
+        let res = String::from_utf8(res.try_blob().unwrap().to_vec()).unwrap();
+        let new_replid = info_field(&res, "master_replid");
+        assert_eq!(new_replid.len(), 40);
+        assert_ne!(old_replid, new_replid);
+
+        // RESP3下返回格式为txt的Verbatim String
+        handler.context.protocol_version = 3;
+        let res = replication().execute(&mut handler).await.unwrap().unwrap();
+        let (format, data) = res.try_verbatim_string().unwrap();
+        assert_eq!(format, b"txt");
+        assert!(data.starts_with(b"# Replication"));
+    }
+
+    #[tokio::test]
+    async fn info_replication_replicas_test() {
+        test_init();
+
+        let info_replication =
+            |res: Resp3| String::from_utf8(res.try_blob().unwrap().to_vec()).unwrap();
+        let info_field = |info: &str, field: &str| {
+            info.lines()
+                .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+                .map(ToString::to_string)
+                .unwrap()
+        };
+
+        // master列出已连接的replica
+        let shared = Shared::new(
+            Arc::new(crate::shared::db::Db::default()),
+            Default::default(),
+            Default::default(),
+        );
+        let (mut master, _) = Handler::with_shared(shared);
+        let propagator = master.shared.wcmd_propagator().clone();
+        let replica_addr = "10.0.0.2:6380".parse().unwrap();
+        let _replica_rx = propagator.new_receiver(replica_addr).unwrap();
+        propagator.ack_replica(&replica_addr, 42);
+
+        let res = master
+            .dispatch(cmd(&["INFO", "replication"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = info_replication(res);
+        assert_eq!(info_field(&res, "role"), "master");
+        assert_eq!(info_field(&res, "connected_slaves"), "1");
+        assert_eq!(
+            info_field(&res, "slave0"),
+            "ip=10.0.0.2,port=6380,state=online,offset=42,lag=0"
+        );
+
+        propagator.delete_receiver(&replica_addr).unwrap();
+        let res = master
+            .dispatch(cmd(&["INFO", "replication"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = info_replication(res);
+        assert_eq!(info_field(&res, "connected_slaves"), "0");
+        assert!(!res.contains("slave0"));
+
+        // replica报告master的地址以及连接状态
+        let (mut replica, _) = Handler::new_fake();
+        replica
+            .dispatch(cmd(&["REPLICAOF", "127.0.0.1", "6379"]))
+            .await
+            .unwrap();
+        let res = replica
+            .dispatch(cmd(&["INFO", "replication"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = info_replication(res);
+        assert_eq!(info_field(&res, "role"), "slave");
+        assert_eq!(info_field(&res, "master_host"), "127.0.0.1");
+        assert_eq!(info_field(&res, "master_port"), "6379");
+        assert_eq!(info_field(&res, "master_link_status"), "down");
+        assert_eq!(info_field(&res, "slave_repl_offset"), "0");
+
+        let set = cmd(&["SET", "key", "value"]);
+        let len = set.encode().len();
+        replica.apply_from_master(set).await.unwrap();
+        let res = replica
+            .dispatch(cmd(&["INFO", "replication"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = info_replication(res);
+        assert_eq!(info_field(&res, "master_link_status"), "up");
+        assert_eq!(info_field(&res, "slave_repl_offset"), len.to_string());
+        assert_eq!(info_field(&res, "master_repl_offset"), len.to_string());
+    }
+
+    #[tokio::test]
+    async fn info_keyspace_stats_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn stats(handler: &mut Handler<impl AsyncStream>) -> (u64, u64) {
+            let res = Info::parse(
+                &mut CmdUnparsed::from(["stats"].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+            .execute(handler)
+            .await
+            .unwrap()
+            .unwrap();
+            let res = String::from_utf8(res.try_blob().unwrap().to_vec()).unwrap();
+            let field = |field: &str| {
+                res.lines()
+                    .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+                    .unwrap()
+                    .parse::<u64>()
+                    .unwrap()
+            };
+            (field("keyspace_hits"), field("keyspace_misses"))
+        }
+
+        assert_eq!(stats(&mut handler).await, (0, 0));
+
+        // 写命令不计入
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+        assert_eq!(stats(&mut handler).await, (0, 0));
+
+        handler.dispatch(cmd(&["GET", "missing"])).await.unwrap();
+        assert_eq!(stats(&mut handler).await, (0, 1));
+        handler.dispatch(cmd(&["GET", "k"])).await.unwrap();
+        assert_eq!(stats(&mut handler).await, (1, 1));
+
+        // 多键命令的每个键单独计数
+        handler
+            .dispatch(cmd(&["MGET", "k", "missing", "k"]))
+            .await
+            .unwrap();
+        assert_eq!(stats(&mut handler).await, (3, 2));
+        handler
+            .dispatch(cmd(&["EXISTS", "k", "missing"]))
+            .await
+            .unwrap();
+        assert_eq!(stats(&mut handler).await, (4, 3));
+
+        // CONFIG RESETSTAT清零统计
+        let res = handler
+            .dispatch(cmd(&["CONFIG", "RESETSTAT"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        assert_eq!(stats(&mut handler).await, (0, 0));
+        handler.dispatch(cmd(&["GET", "k"])).await.unwrap();
+        assert_eq!(stats(&mut handler).await, (1, 0));
+    }
+
+    #[tokio::test]
+    async fn replicaof_no_one_test() {
+        test_init();
+
+        let (mut handler, _) = Handler::new_fake();
+        let shared = handler.shared.clone();
+        let replica_conf = &shared.conf().replica;
+
+        let role = |res: Resp3| {
+            String::from_utf8(res.try_blob().unwrap().to_vec())
+                .unwrap()
+                .lines()
+                .find_map(|line| line.strip_prefix("role:").map(|r| r.trim().to_string()))
+                .unwrap()
+        };
+
+        let res = handler
+            .dispatch(cmd(&["REPLICAOF", "127.0.0.1", "6379"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        assert_eq!(
+            replica_conf.replicaof.load().as_deref().unwrap(),
+            "127.0.0.1:6379"
+        );
+        let res = handler
+            .dispatch(cmd(&["INFO", "replication"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(role(res), "slave");
+
+        // 来自master的命令
+        handler
+            .apply_from_master(cmd(&["SET", "key", "value"]))
+            .await
+            .unwrap();
+        // replica不会自行删除过期键
+        let expire = Instant::now() + Duration::from_millis(10);
+        shared
+            .db()
+            .insert_object(
+                "expired".into(),
+                crate::shared::db::ObjectInner::new_str("v", Some(expire)),
+            )
+            .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(shared.active_expire_cycle().await, 0);
+        // 访问已过期的键时视为键不存在，但不删除键
+        let res = handler.dispatch(cmd(&["GET", "expired"])).await.unwrap();
+        assert_eq!(res, Some(Resp3::Null));
+        assert!(shared.db().entries().contains_key(b"expired".as_slice()));
+
+        let old_replid = replica_conf.master_replid.load_full();
+        let offset = replica_conf.offset.load();
+
+        let res = handler
+            .dispatch(cmd(&["REPLICAOF", "no", "one"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+
+        // 保留数据和复制偏移量，但replid改变
+        assert!(replica_conf.replicaof.load().is_none());
+        assert_ne!(replica_conf.master_replid.load_full(), old_replid);
+        assert_eq!(replica_conf.offset.load(), offset);
+        let res = handler
+            .dispatch(cmd(&["GET", "key"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"value");
+        let res = handler
+            .dispatch(cmd(&["INFO", "replication"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(role(res), "master");
+
+        // 提升后自行删除过期键，并接受写命令
+        assert_eq!(shared.active_expire_cycle().await, 1);
+        let res = handler
+            .dispatch(cmd(&["SET", "key", "new"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+
+        let res = handler
+            .dispatch(cmd(&["REPLICAOF", "127.0.0.1", "port"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), "ERR Invalid master port");
+    }
+
+    #[tokio::test]
+    async fn time_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let res = Time::parse(&mut CmdUnparsed::default(), &AccessControl::new_loose())
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 2);
+        let secs = util::atoi::<u64>(res[0].try_blob().unwrap()).unwrap();
+        let micros = util::atoi::<u64>(res[1].try_blob().unwrap()).unwrap();
+        assert!(micros < 1_000_000);
+        assert!(now.as_secs().abs_diff(secs) <= 1);
+
+        assert!(Time::parse(&mut ["arg"].as_ref().into(), &AccessControl::new_loose()).is_err());
+    }
+
+    #[tokio::test]
+    async fn client_kill_test() {
+        test_init();
+
+        let shared = Shared::default();
+        let (mut victim, _victim_conn) = Handler::with_shared(shared.clone());
+        let (mut killer, _) = Handler::with_shared(shared.clone());
+
+        let victim_addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        victim.set_peer_addr(victim_addr);
+        assert_eq!(
+            shared.db().get_client_id_by_addr(&victim_addr),
+            Some(victim.context.client_id)
+        );
+        let victim_task = tokio::spawn(async move { victim.run().await });
+
+        // 通过地址断开连接
+        let res = killer
+            .dispatch(cmd(&["CLIENT", "KILL", "ADDR", "127.0.0.1:50000"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, Resp3::new_integer(1));
+        tokio::time::timeout(Duration::from_secs(1), victim_task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        // 连接断开后，地址索引被清理
+        assert_eq!(shared.db().get_client_id_by_addr(&victim_addr), None);
+        let res = killer
+            .dispatch(cmd(&["CLIENT", "KILL", "ADDR", "127.0.0.1:50000"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, Resp3::new_integer(0));
+        let res = killer
+            .dispatch(cmd(&["CLIENT", "KILL", "127.0.0.1:50000"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), "ERR No such client");
+
+        // 默认不断开当前连接
+        killer.set_peer_addr("127.0.0.1:50001".parse().unwrap());
+        let res = killer
+            .dispatch(cmd(&["CLIENT", "KILL", "ADDR", "127.0.0.1:50001"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, Resp3::new_integer(0));
+
+        assert!(ClientKill::parse(
+            &mut ["ADDR", "not-an-addr"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn lolwut_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let version = format!("rutin ver. {}", env!("CARGO_PKG_VERSION"));
+        for args in [&[][..], &["VERSION", "5"][..]] {
+            let res = Lolwut::parse(&mut args.into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap();
+            let res = std::str::from_utf8(res.try_blob().unwrap()).unwrap();
+            assert!(res.contains(&version));
+        }
+
+        assert!(Lolwut::parse(
+            &mut ["VERSION", "abc"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+        assert!(Lolwut::parse(&mut ["arg"].as_ref().into(), &AccessControl::new_loose()).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn memory_stats_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        for i in 0..10 {
+            handler
+                .dispatch(cmd(&["SET", &format!("key{i}"), "value"]))
+                .await
+                .unwrap();
+        }
+
+        let db_size = handler.dispatch(cmd(&["DBSIZE"])).await.unwrap().unwrap();
+        let stats = handler
+            .dispatch(cmd(&["MEMORY", "STATS"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let stats = stats.try_map().unwrap();
+        let stat = |name: &'static str| stats.get(&Resp3::new_simple_string(name.into()));
+
+        assert_eq!(stat("keys.count"), Some(&db_size));
+        assert_eq!(stat("keys.count"), Some(&Resp3::new_integer(10)));
+        // 10个4字节的"keyN"和5字节的"value"
+        assert_eq!(stat("dataset.bytes"), Some(&Resp3::new_integer(90)));
+        let allocated = stat("total.allocated").unwrap().try_integer().unwrap();
+        let peak = stat("peak.allocated").unwrap().try_integer().unwrap();
+        assert!(allocated > 0 && peak >= allocated);
+
+        let report = handler
+            .dispatch(cmd(&["MEMORY", "DOCTOR"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!report.try_blob().unwrap().is_empty());
+        assert!(handler
+            .dispatch(cmd(&["MEMORY", "STATS", "arg"]))
+            .await
+            .unwrap()
+            .unwrap()
+            .is_simple_error());
+    }
+
+    #[tokio::test]
+    async fn debug_panic_disabled_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        for sub_cmd in ["PANIC", "OOM"] {
+            let res = handler
+                .dispatch(cmd(&["DEBUG", sub_cmd]))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(res.try_simple_error().unwrap(), DEBUG_CRASH_DISABLED);
+        }
+    }
+
+    #[tokio::test]
+    async fn debug_sleep_test() {
+        test_init();
+
+        let shared = Shared::default();
+        let (mut handler1, _) = Handler::with_shared(shared.clone());
+        let (mut handler2, _) = Handler::with_shared(shared);
+
+        // DEBUG SLEEP只阻塞当前连接
+        let (_, elapsed) =
+            tokio::join!(handler1.dispatch(cmd(&["DEBUG", "SLEEP", "0.2"])), async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                let now = Instant::now();
+                handler2.dispatch(cmd(&["PING"])).await.unwrap();
+                now.elapsed()
+            });
+        assert!(elapsed < Duration::from_millis(100));
+
+        // DEBUG SLEEP-BLOCK期间，其它连接的命令被延迟执行
+        let (res, elapsed) = tokio::join!(
+            handler1.dispatch(cmd(&["DEBUG", "SLEEP-BLOCK", "0.2"])),
+            async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                let now = Instant::now();
+                handler2.dispatch(cmd(&["PING"])).await.unwrap();
+                now.elapsed()
+            }
+        );
+        assert_eq!(res.unwrap().unwrap().try_simple_string().unwrap(), "OK");
+        assert!(elapsed >= Duration::from_millis(150));
+
+        // 暂停结束后不再阻塞
+        let now = Instant::now();
+        handler2.dispatch(cmd(&["PING"])).await.unwrap();
+        assert!(now.elapsed() < Duration::from_millis(100));
+
+        assert!(
+            DebugSleep::parse(&mut ["abc"].as_ref().into(), &AccessControl::new_loose()).is_err()
+        );
+        assert!(
+            DebugSleep::parse(&mut ["-1"].as_ref().into(), &AccessControl::new_loose()).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn command_timeout_test() {
+        use crate::{
+            conf::ServerConf,
+            shared::db::{Db, ObjectInner},
+        };
+        use async_shutdown::ShutdownManager;
+
+        test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                command_timeout_ms: 50,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            ShutdownManager::new(),
+        );
+        let (mut handler, _) = Handler::with_shared(shared);
+
+        // 超时的命令被中断
+        let now = Instant::now();
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "SLEEP", "1"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR command execution timed out"
+        );
+        assert!(now.elapsed() < Duration::from_millis(500));
+
+        // 未超时的命令正常执行
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "SLEEP", "0"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+
+        // 同步执行的遍历不会被tokio::time::timeout中断，需要主动检查截止时间
+        let db = handler.shared.db();
+        for i in 0..10 {
+            db.insert_object(
+                Key::from(format!("key{i}")),
+                ObjectInner::new_str("v", None),
+            )
+            .await;
+        }
+        let re = regex::bytes::Regex::new("key.*").unwrap();
+        assert!(db.snapshot_keys(&re, Some(Instant::now())).is_none());
+        assert_eq!(db.snapshot_keys(&re, None).unwrap().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn no_timeout_test() {
+        use crate::{
+            conf::{RdbConf, ServerConf},
+            shared::db::{Db, ObjectInner},
+        };
+        use async_shutdown::ShutdownManager;
+
+        test_init();
+
+        let path =
+            std::env::temp_dir().join(format!("rutin_timeout_{}.rdb", rand::random::<u64>()));
+        let conf = Conf {
+            server: ServerConf {
+                command_timeout_ms: 1,
                 ..Default::default()
             },
+            rdb: Some(RdbConf {
+                file_path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            }),
+            aof: None,
             ..Default::default()
         };
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            ShutdownManager::new(),
+        );
+        let (mut handler, _) = Handler::with_shared(shared);
 
-        let shared = Shared::new(Default::default(), Arc::new(conf), Default::default());
-        let (mut handler, _) = Handler::new_fake_with(shared, None, None);
+        // 脚本执行时间超过截止时间，但EVAL不会被中断，脚本的修改与回复一致
+        let res = handler
+            .dispatch(cmd(&[
+                "EVAL",
+                "local i = 0 while i < 3000000 do i = i + 1 end return redis.call('set', KEYS[1], 'v')",
+                "1",
+                "key",
+            ]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        assert!(handler.shared.db().contains_object(&Key::from("key")).await);
 
-        let auth = Auth::parse(
-            &mut CmdUnparsed::from([username, "1234567"].as_ref()),
-            &AccessControl::new_loose(),
-        )
-        .unwrap();
-        let res = auth.execute(&mut handler).await;
-        assert_eq!(res.unwrap_err().to_string(), "ERR invalid password");
+        // DEBUG RELOAD不会在清空数据库之后、加载完毕之前被中断
+        let db = handler.shared.db();
+        for i in 0..20000 {
+            db.insert_object(
+                Key::from(format!("key{i}")),
+                ObjectInner::new_str("v", None),
+            )
+            .await;
+        }
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "RELOAD"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        assert_eq!(handler.shared.db().size(), 20001);
 
-        let auth = Auth::parse(
-            &mut CmdUnparsed::from(["admin1", password].as_ref()),
-            &AccessControl::new_loose(),
-        )
-        .unwrap();
-        let res = auth.execute(&mut handler).await;
-        assert_eq!(res.unwrap_err().to_string(), "ERR invalid username");
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        let auth = Auth::parse(
-            &mut CmdUnparsed::from([username, password].as_ref()),
-            &AccessControl::new_loose(),
-        )
-        .unwrap();
-        auth.execute(&mut handler).await.unwrap();
-        assert_eq!(handler.context.ac.cmd_flag(), cmd_flag);
+    #[tokio::test]
+    async fn max_memory_oom_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let oom = "OOM command not allowed when used memory > 'maxmemory'.";
+
+        let value = "x".repeat(1024);
+        for i in 0..100 {
+            handler
+                .dispatch(cmd(&["SET", &format!("key{i}"), &value]))
+                .await
+                .unwrap();
+        }
+
+        // 已使用的内存必然超过1字节
+        handler
+            .dispatch(cmd(&["CONFIG", "SET", "maxmemory", "1"]))
+            .await
+            .unwrap();
+
+        // 写命令返回OOM错误
+        let res = handler
+            .dispatch(cmd(&["SET", "key", "value"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), oom);
+        let res = handler
+            .dispatch(cmd(&["INCR", "counter"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), oom);
+
+        // 读命令和删除命令不受影响
+        let res = handler
+            .dispatch(cmd(&["GET", "key0"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), value.as_bytes());
+        let res = handler
+            .dispatch(cmd(&["DEL", "key0"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 1);
+
+        // 事务中的写命令排队失败，EXEC放弃整个事务
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        let res = handler
+            .dispatch(cmd(&["SET", "key", "value"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), oom);
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        assert!(res.try_simple_error().unwrap().starts_with("EXECABORT"));
+
+        // 取消限制后恢复正常
+        handler
+            .dispatch(cmd(&["CONFIG", "SET", "maxmemory", "0"]))
+            .await
+            .unwrap();
+        let res = handler
+            .dispatch(cmd(&["SET", "key", "value"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
     }
 
     #[tokio::test]
-    async fn client_tracking_test() {
+    async fn debug_object_encoding_force_test() {
         test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let ok = Resp3::new_simple_string("OK".into());
 
+        handler
+            .dispatch(cmd(&["HSET", "hash", "f1", "v1"]))
+            .await
+            .unwrap();
+        let encoding = handler
+            .dispatch(cmd(&["OBJECT", "ENCODING", "hash"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(encoding.try_blob().unwrap(), b"ziplist".as_ref());
+
+        // 强制将较小的Hash转换为hashtable
+        let res = handler
+            .dispatch(cmd(&[
+                "DEBUG",
+                "OBJECT",
+                "ENCODING-FORCE",
+                "hash",
+                "HASHTABLE",
+            ]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res, ok);
+        let encoding = handler
+            .dispatch(cmd(&["OBJECT", "ENCODING", "hash"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(encoding.try_blob().unwrap(), b"hashtable".as_ref());
+        let value = handler
+            .dispatch(cmd(&["HGET", "hash", "f1"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(value.try_blob().unwrap(), b"v1".as_ref());
+
+        // 元素不全是整数的Set不能转换为intset
+        handler
+            .dispatch(cmd(&["SADD", "set", "1", "a"]))
+            .await
+            .unwrap();
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "OBJECT", "ENCODING-FORCE", "set", "intset"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR unsupported encoding conversion"
+        );
+
+        // 键不存在
+        let res = handler
+            .dispatch(cmd(&[
+                "DEBUG",
+                "OBJECT",
+                "ENCODING-FORCE",
+                "nil",
+                "hashtable",
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(res, Some(Resp3::Null));
+    }
+
+    #[tokio::test]
+    async fn debug_object_test() {
+        use crate::shared::db::ObjectInner;
+
+        test_init();
         let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
 
-        let tracking = ClientTracking::parse(
-            &mut CmdUnparsed::from(["ON"].as_ref()),
-            &AccessControl::new_loose(),
+        // 返回调试信息中field的值
+        async fn field(
+            handler: &mut Handler<impl AsyncStream>,
+            key: &str,
+            name: &str,
+        ) -> Option<usize> {
+            let res = DebugObject::parse(&mut [key].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap();
+            let info = res.try_simple_string().unwrap().to_string();
+            info.split(' ')
+                .find_map(|kv| kv.strip_prefix(name)?.strip_prefix(':'))
+                .map(|v| v.parse().unwrap())
+        }
+
+        db.insert_object(
+            "small".into(),
+            ObjectInner::new_str(Bytes::from("a".repeat(10)), None),
         )
-        .unwrap();
-        tracking.execute(&mut handler).await.unwrap();
-        assert!(handler.context.client_track.is_some());
+        .await;
+        db.insert_object(
+            "large".into(),
+            ObjectInner::new_str(Bytes::from("a".repeat(1000)), None),
+        )
+        .await;
 
-        let tracking = ClientTracking::parse(
-            &mut CmdUnparsed::from(["OFF"].as_ref()),
-            &AccessControl::new_loose(),
+        let small = field(&mut handler, "small", "serializedlength")
+            .await
+            .unwrap();
+        let large = field(&mut handler, "large", "serializedlength")
+            .await
+            .unwrap();
+        assert!(small > 0);
+        assert!(large > small);
+        assert!(field(&mut handler, "small", "ql_nodes").await.is_none());
+
+        // 使用quicklist编码的List返回节点数量
+        let list: std::collections::VecDeque<Bytes> =
+            (0..300).map(|i| Bytes::from(i.to_string())).collect();
+        db.insert_object("list".into(), ObjectInner::new_list(list, None))
+            .await;
+        assert_eq!(field(&mut handler, "list", "ql_nodes").await, Some(3));
+
+        assert!(
+            DebugObject::parse(&mut ["nil"].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn debug_set_active_expire_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let shared = handler.shared.clone();
+        let db = shared.db().clone();
+
+        handler
+            .dispatch(cmd(&["DEBUG", "SET-ACTIVE-EXPIRE", "0"]))
+            .await
+            .unwrap();
+        assert!(!shared.is_active_expire_enabled());
+
+        let expire = tokio::time::Instant::now() + std::time::Duration::from_millis(10);
+        db.insert_object(
+            "key".into(),
+            crate::shared::db::ObjectInner::new_str("value", Some(expire)),
         )
-        .unwrap();
-        tracking.execute(&mut handler).await.unwrap();
-        assert!(handler.context.client_track.is_none());
+        .await;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // 关闭定期删除后，过期键仍然保留在Db中
+        assert_eq!(shared.active_expire_cycle().await, 0);
+        assert_eq!(db.size(), 1);
+
+        // 访问时惰性删除
+        assert_eq!(
+            handler.dispatch(cmd(&["GET", "key"])).await.unwrap(),
+            Some(Resp3::Null)
+        );
+        assert_eq!(db.size(), 0);
+
+        handler
+            .dispatch(cmd(&["DEBUG", "SET-ACTIVE-EXPIRE", "1"]))
+            .await
+            .unwrap();
+        assert!(shared.is_active_expire_enabled());
+    }
+    #[tokio::test]
+    async fn debug_load_file_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        let dir = std::env::temp_dir();
+        let tsv_path = dir.join(format!("rutin_load_{}.tsv", rand::random::<u64>()));
+        std::fs::write(&tsv_path, "k1\tv1\r\nk2\tv\t2\n\nk3\t\n").unwrap();
+        let tsv_path = tsv_path.to_str().unwrap().to_string();
+
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "LOAD-FILE", &tsv_path, "tsv"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 3);
+        let res = handler
+            .dispatch(cmd(&["GET", "k1"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"v1");
+        // 只以第一个\t分隔键和值
+        let res = handler
+            .dispatch(cmd(&["GET", "k2"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"v\t2");
+        let res = handler
+            .dispatch(cmd(&["GET", "k3"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"");
+        let res = handler
+            .dispatch(cmd(&["TTL", "k1"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), -1);
+
+        let bin_path = dir.join(format!("rutin_load_{}.bin", rand::random::<u64>()));
+        let mut buf = Vec::new();
+        for (k, v) in [("b1", "value1"), ("b2", "")] {
+            buf.extend((k.len() as u32).to_be_bytes());
+            buf.extend(k.as_bytes());
+            buf.extend((v.len() as u32).to_be_bytes());
+            buf.extend(v.as_bytes());
+        }
+        std::fs::write(&bin_path, &buf).unwrap();
+        let bin_path = bin_path.to_str().unwrap().to_string();
+
+        let res = handler
+            .dispatch(cmd(&[
+                "DEBUG",
+                "LOAD-FILE",
+                &bin_path,
+                "BINARY",
+                "EX",
+                "100",
+            ]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 2);
+        let res = handler
+            .dispatch(cmd(&["GET", "b1"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"value1");
+        let res = handler
+            .dispatch(cmd(&["TTL", "b2"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_integer().unwrap() > 0);
+        assert_eq!(db.len_nonexpired(), 5);
+
+        // 截断的记录
+        std::fs::write(&bin_path, &buf[..buf.len() - 3]).unwrap();
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "LOAD-FILE", &bin_path, "binary"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().is_some());
+
+        // 长度超过文件大小时不会按长度分配内存
+        let mut corrupted = u32::MAX.to_be_bytes().to_vec();
+        corrupted.extend(b"key");
+        std::fs::write(&bin_path, &corrupted).unwrap();
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "LOAD-FILE", &bin_path, "binary"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR failed to load file: unexpected end of file: truncated record"
+        );
+
+        // 错误中的行号包括空行
+        std::fs::write(&tsv_path, "k1\tv1\n\n\nbad\n").unwrap();
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "LOAD-FILE", &tsv_path, "tsv"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR failed to load file: invalid TSV line 4: missing tab"
+        );
+
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "LOAD-FILE", &tsv_path, "csv"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR format should be TSV or BINARY"
+        );
+
+        std::fs::remove_file(tsv_path).ok();
+        std::fs::remove_file(bin_path).ok();
+    }
+
+    #[tokio::test]
+    async fn debug_reload_test() {
+        use crate::{conf::RdbConf, shared::db::Db};
+        use async_shutdown::ShutdownManager;
+
+        test_init();
+
+        let path = std::env::temp_dir().join(format!("rutin_reload_{}.rdb", rand::random::<u64>()));
+        let conf = Conf {
+            rdb: Some(RdbConf {
+                file_path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            }),
+            aof: None,
+            ..Default::default()
+        };
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            ShutdownManager::new(),
+        );
+        let (mut handler, _) = Handler::with_shared(shared);
+
+        // 文件不存在时，重新加载后数据库为空
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "RELOAD", "NOSAVE"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        assert_eq!(handler.shared.db().size(), 0);
+
+        // 不带NOSAVE时先保存再加载，数据保持不变
+        handler.dispatch(cmd(&["SET", "k1", "v1"])).await.unwrap();
+        handler.dispatch(cmd(&["SET", "k2", "v2"])).await.unwrap();
+        handler
+            .dispatch(cmd(&["DEBUG", "RELOAD"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = handler
+            .dispatch(cmd(&["GET", "k1"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"v1");
+
+        // 未保存的修改被丢弃，恢复为已保存的数据
+        handler.dispatch(cmd(&["SET", "k1", "new"])).await.unwrap();
+        handler.dispatch(cmd(&["DEL", "k2"])).await.unwrap();
+        handler.dispatch(cmd(&["SET", "k3", "v3"])).await.unwrap();
+        handler
+            .dispatch(cmd(&["DEBUG", "RELOAD", "NOSAVE"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = handler
+            .dispatch(cmd(&["MGET", "k1", "k2", "k3"]))
+            .await
+            .unwrap()
+            .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res[0].try_blob().unwrap().as_ref(), b"v1");
+        assert_eq!(res[1].try_blob().unwrap().as_ref(), b"v2");
+        assert!(res[2].is_null());
+
+        // DEBUG FLUSHALL只清空内存，不影响磁盘上的文件
+        handler
+            .dispatch(cmd(&["DEBUG", "FLUSHALL"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(handler.shared.db().size(), 0);
+        handler
+            .dispatch(cmd(&["DEBUG", "RELOAD", "NOSAVE"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(handler.shared.db().size(), 2);
+
+        let res = handler
+            .dispatch(cmd(&["DEBUG", "RELOAD", "SAVE"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn debug_stringmatch_len_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let long_pattern = "*?".repeat(50) + "b";
+        let long_string = "a".repeat(300);
+
+        let vectors = [
+            ("*hello*", "ahellob", 1),
+            ("h?llo", "hello", 1),
+            ("h?llo", "hllo", 0),
+            ("h*llo", "hllo", 1),
+            ("h*llo", "heeeello", 1),
+            ("h[ae]llo", "hallo", 1),
+            ("h[ae]llo", "hillo", 0),
+            ("h[^e]llo", "hallo", 1),
+            ("h[^e]llo", "hello", 0),
+            ("h[a-b]llo", "hbllo", 1),
+            // 范围的起止顺序可以颠倒
+            ("h[b-a]llo", "hallo", 1),
+            ("h[\\]]llo", "h]llo", 1),
+            ("h\\*llo", "h*llo", 1),
+            ("h\\*llo", "hello", 0),
+            ("a*", "", 0),
+            ("a**", "a", 1),
+            ("Hello", "hello", 0),
+            // 嵌套的*不会导致指数级的回溯
+            (long_pattern.as_str(), long_string.as_str(), 0),
+        ];
+        for (pattern, string, expected) in vectors {
+            assert_eq!(
+                handler
+                    .dispatch(cmd(&["DEBUG", "STRINGMATCH-LEN", pattern, string]))
+                    .await
+                    .unwrap(),
+                Some(Resp3::new_integer(expected)),
+                "pattern: {pattern}, string: {string}"
+            );
+        }
     }
 }
@@ -4,7 +4,7 @@ use crate::{
         error::{CmdError, Err},
         CmdExecutor, CmdType, CmdUnparsed,
     },
-    conf::AccessControl,
+    conf::{AccessControl, ClientClass},
     connection::AsyncStream,
     frame::Resp3,
     server::Handler,
@@ -29,6 +29,7 @@ impl CmdExecutor for Publish {
     const NAME: &'static str = "PUBLISH";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = PUBLISH_FLAG;
+    const ARITY: Int = 3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -43,27 +44,35 @@ impl CmdExecutor for Publish {
             .ok_or(CmdError::from(0))?;
 
         let mut count = 0;
-        // 理论上一定会发送成功，因为Db中保存的发布者与订阅者是一一对应的
+        let mut failed = Vec::new();
         for listener in listeners {
             let res = listener
-                .send_async(Resp3::new_array(vec![
+                .send_async(Resp3::new_push(vec![
                     Resp3::new_blob_string("message".into()),
                     Resp3::new_blob_string(self.topic.clone()),
                     Resp3::new_blob_string(self.msg.clone()),
                 ]))
                 .await;
 
-            // 如果发送失败，证明订阅者已经关闭连接，此时应该从Db中移除该订阅者
+            // 发送失败说明订阅者已经断开连接(或者输出缓冲区超出限制即将被断开)
             if res.is_err() {
-                handler
-                    .shared
-                    .db()
-                    .remove_channel_listener(&self.topic, &listener);
+                failed.push(listener);
             } else {
                 count += 1;
             }
         }
 
+        // 订阅者断开连接时不会主动退订，因此在发送失败时一次性移除该频道中所有失效的
+        // 订阅者，避免其无限增长
+        if !failed.is_empty() {
+            handler
+                .shared
+                .db()
+                .retain_channel_listeners(&self.topic, |l| {
+                    !l.is_disconnected() && !failed.iter().any(|f| f.same_channel(l))
+                });
+        }
+
         Ok(Some(Resp3::new_integer(count)))
     }
 
@@ -84,6 +93,12 @@ impl CmdExecutor for Publish {
     }
 }
 
+/// # Reply:
+///
+/// When successful, this command doesn't return anything. Instead, for each channel,
+/// one message with the first element being the string subscribe is pushed as a
+/// confirmation that the command succeeded. The third element is the number of
+/// channels the client is currently subscribed to.
 #[derive(Debug)]
 pub struct Subscribe {
     topics: Vec<Key>,
@@ -93,30 +108,35 @@ impl CmdExecutor for Subscribe {
     const NAME: &'static str = "SUBSCRIBE";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = SUBSCRIBE_FLAG;
+    const ARITY: Int = -2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        use snafu::Location;
-
         let Handler {
             shared,
-            conn,
             context,
             bg_task_channel,
             ..
         } = handler;
 
-        let subscribed_channels = if context.subscribed_channels.is_none() {
-            // subscribed_channels为None，表明从未订阅过频道。创建一个Vec存储订阅的频道的名称
-            context.subscribed_channels = Some(Vec::with_capacity(8));
-            context.subscribed_channels.as_mut().unwrap()
-        } else {
-            context.subscribed_channels.as_mut().unwrap()
-        };
+        // subscribed_channels为None，表明从未订阅过频道。创建一个Vec存储订阅的频道的名称
+        let subscribed_channels = context
+            .subscribed_channels
+            .get_or_insert_with(|| Vec::with_capacity(8));
+
+        // 订阅者使用pubsub类型的输出缓冲区限制
+        bg_task_channel.set_output_buffer_limit(
+            shared
+                .conf()
+                .server
+                .client_output_buffer_limit
+                .get(ClientClass::PubSub),
+        );
 
+        let mut confirmations = Vec::with_capacity(self.topics.len());
         for topic in self.topics {
             if !subscribed_channels.contains(&topic) {
                 // 没有订阅过，则将该频道加入订阅列表
@@ -126,18 +146,15 @@ impl CmdExecutor for Subscribe {
                     .add_channel_listener(topic.clone(), bg_task_channel.new_sender());
             }
 
-            conn.write_frame::<Bytes, String>(&Resp3::new_array(vec![
-                Resp3::new_blob_string("subscribe".into()),
-                Resp3::new_blob_string(topic),
-                Resp3::new_integer(subscribed_channels.len() as Int), // 当前客户端订阅的频道数
-            ]))
-            .await
-            .map_err(|e| CmdError::ServerErr {
-                source: e.into(),
-                loc: location!(),
-            })?;
+            // 当前客户端订阅的频道数
+            confirmations.push(confirmation(
+                "subscribe",
+                Some(topic),
+                subscribed_channels.len(),
+            ));
         }
 
+        write_confirmations(handler, confirmations).await?;
         Ok(None)
     }
 
@@ -155,6 +172,7 @@ impl CmdExecutor for Subscribe {
     }
 }
 
+/// 取消订阅指定的频道，未指定频道时取消订阅所有频道
 /// # Reply:
 ///
 /// When successful, this command doesn't return anything. Instead, for each channel,
@@ -169,69 +187,67 @@ impl CmdExecutor for Unsubscribe {
     const NAME: &'static str = "UNSUBSCRIBE";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = UNSUBSCRIBE_FLAG;
+    const ARITY: Int = -1;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        use snafu::Location;
-
         let Handler {
             shared,
-            conn,
             context,
             bg_task_channel,
             ..
         } = handler;
 
-        let subscribed_channels = if let Some(sub) = &mut context.subscribed_channels {
-            sub
+        let subscribed_channels = context.subscribed_channels.get_or_insert_with(Vec::new);
+
+        // 未指定频道时取消订阅所有频道
+        let topics = if self.topics.is_empty() {
+            subscribed_channels.clone()
         } else {
-            for topic in self.topics {
-                conn.write_frame::<Bytes, String>(&Resp3::new_array(vec![
-                    Resp3::new_blob_string("unsubscribe".into()),
-                    Resp3::new_blob_string(topic),
-                    Resp3::new_integer(0),
-                ]))
-                .await
-                .map_err(|e| CmdError::ServerErr {
-                    source: e.into(),
-                    loc: location!(),
-                })?;
-            }
-            return Ok(None);
+            self.topics
         };
 
-        for topic in self.topics {
+        let mut confirmations = Vec::with_capacity(topics.len().max(1));
+        // 没有订阅任何频道时，也需要回复一条确认消息
+        if topics.is_empty() {
+            confirmations.push(confirmation("unsubscribe", None, 0));
+        }
+
+        for topic in topics {
             // 订阅了该频道，需要从订阅列表移除，并且移除Db中的监听器
             if let Some(i) = subscribed_channels.iter().position(|t| *t == topic) {
-                subscribed_channels.swap_remove(i);
+                subscribed_channels.remove(i);
                 shared
                     .db()
                     .remove_channel_listener(&topic, bg_task_channel.get_sender());
             }
 
-            conn.write_frame::<Bytes, String>(&Resp3::new_array(vec![
-                Resp3::new_blob_string("unsubscribe".into()),
-                Resp3::new_blob_string(topic),
-                Resp3::new_integer(subscribed_channels.len() as Int),
-            ]))
-            .await
-            .map_err(|e| CmdError::ServerErr {
-                source: e.into(),
-                loc: location!(),
-            })?;
+            confirmations.push(confirmation(
+                "unsubscribe",
+                Some(topic),
+                subscribed_channels.len(),
+            ));
+        }
+
+        // 不再订阅任何频道时，恢复为normal类型的输出缓冲区限制
+        if subscribed_channels.is_empty() {
+            bg_task_channel.set_output_buffer_limit(
+                shared
+                    .conf()
+                    .server
+                    .client_output_buffer_limit
+                    .get(ClientClass::Normal),
+            );
         }
 
+        write_confirmations(handler, confirmations).await?;
         Ok(None)
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
-        if args.is_empty() {
-            return Err(Err::WrongArgNum.into());
-        }
-
         let topics: Vec<_> = args.collect();
         if ac.is_forbidden_channels(&topics) {
             return Err(Err::NoPermission.into());
@@ -241,16 +257,47 @@ impl CmdExecutor for Unsubscribe {
     }
 }
 
+/// 订阅和取消订阅的确认消息，topic为None时表示没有订阅任何频道
+fn confirmation(kind: &'static str, topic: Option<Key>, count: usize) -> Resp3 {
+    Resp3::new_push(vec![
+        Resp3::new_blob_string(kind.into()),
+        topic.map_or(Resp3::Null, Resp3::new_blob_string),
+        Resp3::new_integer(count as Int),
+    ])
+}
+
+// 将所有确认消息编码到写缓冲区后一次写出。RESP3下为Push，RESP2下为数组
+async fn write_confirmations(
+    handler: &mut Handler<impl AsyncStream>,
+    confirmations: Vec<Resp3>,
+) -> Result<(), CmdError> {
+    use snafu::Location;
+
+    let resp3 = handler.context.protocol_version == 3;
+    for frame in &confirmations {
+        handler.conn.buffer_frame(frame, resp3);
+    }
+
+    handler
+        .conn
+        .write_buffered()
+        .await
+        .map_err(|e| CmdError::ServerErr {
+            source: e.into(),
+            loc: location!(),
+        })
+}
+
 #[cfg(test)]
 mod cmd_pub_sub_tests {
     use super::*;
-    use crate::util::test_init;
+    use crate::util::{cmd, test_init};
 
     #[tokio::test]
     async fn sub_pub_unsub_test() {
         test_init();
 
-        let (mut handler, _) = Handler::new_fake();
+        let (mut handler, _client) = Handler::new_fake();
 
         // 订阅channel1和channel2
         let subscribe = Subscribe::parse(
@@ -314,7 +361,7 @@ mod cmd_pub_sub_tests {
             .bg_task_channel
             .recv_from_bg_task()
             .await
-            .try_array()
+            .try_push()
             .unwrap()
             .to_vec();
         assert_eq!(
@@ -346,7 +393,7 @@ mod cmd_pub_sub_tests {
             .bg_task_channel
             .recv_from_bg_task()
             .await
-            .try_array()
+            .try_push()
             .unwrap()
             .to_vec();
         assert_eq!(
@@ -387,4 +434,202 @@ mod cmd_pub_sub_tests {
             handler.context.subscribed_channels.as_ref().unwrap().len()
         );
     }
+
+    #[tokio::test]
+    async fn publish_prune_dead_subscriber_test() {
+        test_init();
+
+        let (mut publisher, _) = Handler::new_fake();
+        let shared = publisher.shared.clone();
+        let subscribe = || {
+            Subscribe::parse(
+                &mut CmdUnparsed::from(["channel"].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+        };
+        let publish = || {
+            Publish::parse(
+                &mut CmdUnparsed::from(["channel", "msg"].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+        };
+
+        let (mut alive, _alive_client) = Handler::with_shared(shared.clone());
+        subscribe().execute(&mut alive).await.unwrap();
+        let (mut dead1, _dead1_client) = Handler::with_shared(shared.clone());
+        subscribe().execute(&mut dead1).await.unwrap();
+        let (mut dead2, _dead2_client) = Handler::with_shared(shared.clone());
+        subscribe().execute(&mut dead2).await.unwrap();
+        assert_eq!(
+            shared
+                .db()
+                .get_channel_all_listener(b"channel")
+                .unwrap()
+                .len(),
+            3
+        );
+
+        // 订阅者断开连接后没有退订，下一次发布时移除
+        drop(dead1);
+        drop(dead2);
+        let res = publish().execute(&mut publisher).await.unwrap().unwrap();
+        assert_eq!(res.try_integer().unwrap(), 1);
+        let listeners = shared.db().get_channel_all_listener(b"channel").unwrap();
+        assert_eq!(listeners.len(), 1);
+        assert!(listeners[0].same_channel(alive.bg_task_channel.get_sender()));
+
+        // 最后一个订阅者被移除后，频道也被删除
+        drop(alive);
+        let res = publish().execute(&mut publisher).await.unwrap().unwrap();
+        assert_eq!(res.try_integer().unwrap(), 0);
+        assert!(shared.db().get_channel_all_listener(b"channel").is_none());
+    }
+
+    #[tokio::test]
+    async fn subscribe_confirmation_test() {
+        test_init();
+
+        let (mut handler, mut client) = Handler::new_memory();
+
+        let expect = |kind: &'static str, topic: Option<&'static str>, count: Int| {
+            vec![
+                Resp3::new_blob_string(kind.into()),
+                topic.map_or(Resp3::Null, |t| Resp3::new_blob_string(t.into())),
+                Resp3::new_integer(count),
+            ]
+        };
+
+        // RESP2下每个频道对应一个数组，订阅数量递增
+        assert!(handler
+            .dispatch(cmd(&["SUBSCRIBE", "c1", "c2", "c3"]))
+            .await
+            .unwrap()
+            .is_none());
+        for (i, topic) in ["c1", "c2", "c3"].into_iter().enumerate() {
+            assert_eq!(
+                client.read_frame().await.unwrap().unwrap(),
+                Resp3::new_array(expect("subscribe", Some(topic), i as Int + 1))
+            );
+        }
+
+        // 重复订阅不增加数量
+        handler.dispatch(cmd(&["SUBSCRIBE", "c2"])).await.unwrap();
+        assert_eq!(
+            client.read_frame().await.unwrap().unwrap(),
+            Resp3::new_array(expect("subscribe", Some("c2"), 3))
+        );
+
+        // RESP3下为Push，不指定频道时取消订阅所有频道，订阅数量递减
+        handler.context.protocol_version = 3;
+        handler.dispatch(cmd(&["UNSUBSCRIBE"])).await.unwrap();
+        for (i, topic) in ["c1", "c2", "c3"].into_iter().enumerate() {
+            assert_eq!(
+                client.read_frame().await.unwrap().unwrap(),
+                Resp3::new_push(expect("unsubscribe", Some(topic), 2 - i as Int))
+            );
+        }
+        assert!(handler
+            .shared
+            .db()
+            .get_channel_all_listener(b"c1")
+            .is_none());
+
+        // 没有订阅任何频道
+        handler.dispatch(cmd(&["UNSUBSCRIBE"])).await.unwrap();
+        assert_eq!(
+            client.read_frame().await.unwrap().unwrap(),
+            Resp3::new_push(expect("unsubscribe", None, 0))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn output_buffer_limit_test() {
+        use crate::{
+            conf::{ClientOutputBufferLimit, Conf, OutputBufferLimit, ServerConf},
+            connection::Connection,
+            shared::{db::Db, Shared},
+        };
+        use async_shutdown::ShutdownManager;
+        use std::sync::Arc;
+        use tokio::net::{TcpListener, TcpStream};
+
+        test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                client_output_buffer_limit: ClientOutputBufferLimit {
+                    pubsub: OutputBufferLimit {
+                        hard_limit: 64 * 1024,
+                        soft_limit: 0,
+                        soft_seconds: 0,
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            rdb: None,
+            aof: None,
+            ..Default::default()
+        };
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            ShutdownManager::new(),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut subscriber = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        let stream = listener.accept().await.unwrap().0;
+        let mut handler = Handler::new(shared.clone(), stream);
+        let subscriber_handle = tokio::spawn(async move { handler.run().await });
+
+        subscriber
+            .write_frame(&Resp3::<_, String>::new_array(vec![
+                Resp3::new_blob_string("SUBSCRIBE".as_bytes()),
+                Resp3::new_blob_string("channel".as_bytes()),
+            ]))
+            .await
+            .unwrap();
+        subscriber.read_frame().await.unwrap().unwrap();
+
+        // 订阅者不再读取消息，输出缓冲区超出限制后，订阅者会被断开连接
+        let (mut publisher, _) = Handler::with_shared(shared);
+        let msg = "x".repeat(4096);
+        let mut disconnected = false;
+        for _ in 0..100_000 {
+            let publish = Publish::parse(
+                &mut CmdUnparsed::from(["channel", msg.as_str()].as_ref()),
+                &AccessControl::new_loose(),
+            )
+            .unwrap();
+            let res = publish
+                .execute(&mut publisher)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_integer()
+                .unwrap();
+
+            if res == 0 {
+                disconnected = true;
+                break;
+            }
+        }
+        assert!(disconnected);
+
+        let err = subscriber_handle.await.unwrap().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("client output buffer limit reached"));
+
+        // 订阅者已从频道中移除
+        assert!(publisher
+            .shared
+            .db()
+            .get_channel_all_listener(b"channel")
+            .is_none());
+    }
 }
@@ -6,6 +6,7 @@ use crate::{
     frame::Resp3,
     server::Handler,
     util::atoi,
+    Int,
 };
 use bytes::Bytes;
 use snafu::ResultExt;
@@ -22,6 +23,7 @@ impl CmdExecutor for Eval {
     const NAME: &'static str = "EVAL";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = EVAL_FLAG;
+    const ARITY: Int = -3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -66,6 +68,7 @@ impl CmdExecutor for EvalName {
     const NAME: &'static str = "EVALNAME";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = EVALNAME_FLAG;
+    const ARITY: Int = -3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -107,6 +110,7 @@ impl CmdExecutor for ScriptExists {
     const NAME: &'static str = "SCRIPTEXISTS";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = SCRIPT_EXISTS_FLAG;
+    const ARITY: Int = -3;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -143,6 +147,7 @@ impl CmdExecutor for ScriptFlush {
     const NAME: &'static str = "SCRIPTFLUSH";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = SCRIPT_FLUSH_FLAG;
+    const ARITY: Int = 2;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -173,6 +178,7 @@ impl CmdExecutor for ScriptRegister {
     const NAME: &'static str = "SCRIPTREGISTER";
     const TYPE: CmdType = CmdType::Other;
     const FLAG: CmdFlag = SCRIPT_REGISTER_FLAG;
+    const ARITY: Int = 4;
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -205,6 +211,7 @@ mod cmd_script_tests {
     use super::*;
 
     #[tokio::test]
+    #[allow(clippy::approx_constant)]
     async fn eval_test() {
         let (mut handler, _) = Handler::new_fake();
 
@@ -235,6 +242,34 @@ mod cmd_script_tests {
         .unwrap();
         let res = eval.execute(&mut handler).await.unwrap().unwrap();
         assert_eq!(res, Resp3::new_blob_string("value".into()));
+
+        let eval = Eval::parse(
+            &mut ["return {big_number='99999999999999999999'}", "0"]
+                .as_ref()
+                .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = eval.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(
+            res,
+            Resp3::new_big_number("99999999999999999999".parse().unwrap())
+        );
+
+        let eval = Eval::parse(
+            &mut ["return {double=3.14}", "0"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let res = eval.execute(&mut handler).await.unwrap().unwrap();
+        assert_eq!(res, Resp3::new_double(3.14));
+
+        let eval = Eval::parse(
+            &mut ["return {big_number='not a number'}", "0"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert!(eval.execute(&mut handler).await.is_err());
     }
 
     #[tokio::test]
@@ -0,0 +1,504 @@
+// SAdd
+// SInterCard
+// SMembers
+
+use super::*;
+use crate::{
+    cmd::{CmdError, CmdExecutor, CmdType, CmdUnparsed, Err},
+    conf::AccessControl,
+    connection::AsyncStream,
+    frame::Resp3,
+    server::Handler,
+    shared::db::{ObjValueType, ObjectInner},
+    util::atoi,
+    CmdFlag, Int, Key,
+};
+use ahash::AHashSet;
+use bytes::Bytes;
+use std::sync::atomic::Ordering;
+use tracing::instrument;
+
+/// 将一个或多个成员加入到集合中，已经存在于集合的成员将被忽略。
+/// # Reply:
+///
+/// **Integer reply:** the number of elements that were added to the set, not
+/// including all the elements already present in the set.
+#[derive(Debug)]
+pub struct SAdd {
+    pub key: Key,
+    pub members: Vec<Bytes>,
+}
+
+impl CmdExecutor for SAdd {
+    const NAME: &'static str = "SADD";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = SADD_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut count = 0;
+
+        let max_entries = handler
+            .shared
+            .conf()
+            .memory
+            .set_max_intset_entries
+            .load(Ordering::Relaxed);
+
+        handler
+            .shared
+            .db()
+            .update_or_create_object(&self.key, ObjValueType::Set, |obj| {
+                let set = obj.on_set_mut()?;
+                for member in self.members {
+                    if set.insert(member) {
+                        count += 1;
+                    }
+                }
+                set.convert_if_exceed(max_entries);
+
+                Ok(())
+            })
+            .await?;
+
+        Ok(Some(Resp3::new_integer(count)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(SAdd {
+            key,
+            members: args.collect(),
+        })
+    }
+}
+
+/// 返回多个集合交集的成员数量。LIMIT不为0时，交集的成员数量达到LIMIT后停止计算
+/// # Reply:
+///
+/// **Integer reply:** the number of elements in the resulting intersection.
+#[derive(Debug)]
+pub struct SInterCard {
+    pub keys: Vec<Key>,
+    pub limit: usize,
+}
+
+impl CmdExecutor for SInterCard {
+    const NAME: &'static str = "SINTERCARD";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = SINTERCARD_FLAG;
+    const ARITY: Int = -3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let card = inter_card(handler, &self.keys, self.limit, |obj| {
+            Ok(obj.on_set()?.iter().collect())
+        })
+        .await?;
+
+        Ok(Some(Resp3::new_integer(card as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (keys, limit) = parse_inter_card(args)?;
+        if ac.is_forbidden_keys(&keys, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(SInterCard { keys, limit })
+    }
+}
+
+/// 返回集合的所有成员。成员数量较多时，成员被逐个编码到写缓冲区，不会先构造完整的回复
+/// # Reply:
+///
+/// **Array reply:** all members of the set.
+#[derive(Debug)]
+pub struct SMembers {
+    pub key: Key,
+}
+
+impl CmdExecutor for SMembers {
+    const NAME: &'static str = "SMEMBERS";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = SMEMBERS_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut res = None;
+        // 流式回复需要等待IO，不能在持有锁时写出，因此先复制元素(只增加引用计数)
+        let mut streaming = None;
+
+        let db = handler.shared.db().clone();
+        let visit = db
+            .visit_object(&self.key, |obj| {
+                let set = obj.on_set()?;
+
+                if handler.should_stream_reply(set.len()) {
+                    streaming = Some(set.iter().collect::<Vec<_>>());
+                } else {
+                    res = Some(Resp3::new_array(
+                        set.iter().map(Resp3::new_blob_string).collect::<Vec<_>>(),
+                    ));
+                }
+
+                Ok(())
+            })
+            .await;
+
+        match visit {
+            Ok(()) => {}
+            // 键不存在时返回空数组
+            Err(CmdError::Null) => return Ok(Some(Resp3::new_array(vec![]))),
+            Err(e) => return Err(e),
+        }
+
+        if let Some(members) = streaming {
+            handler
+                .write_streaming_array(
+                    members.len(),
+                    members.into_iter().map(Resp3::new_blob_string),
+                )
+                .await?;
+        }
+
+        Ok(res)
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(SMembers { key })
+    }
+}
+
+/// 计算多个集合交集的成员数量，limit为0时表示不限制。members将对象转换为成员集合，
+/// 任一键不存在时交集为空
+pub(super) async fn inter_card(
+    handler: &mut Handler<impl AsyncStream>,
+    keys: &[Key],
+    limit: usize,
+    members: impl FnMut(&ObjectInner) -> Result<AHashSet<Bytes>, CmdError>,
+) -> Result<usize, CmdError> {
+    let mut sets = Vec::with_capacity(keys.len());
+    let mut missing = false;
+    for set in handler.shared.db().multi_get(keys, members).await {
+        match set {
+            Some(set) => sets.push(set?),
+            None => missing = true,
+        }
+    }
+    if missing {
+        return Ok(0);
+    }
+
+    // 以最小的集合为基准，检查其成员是否存在于其它所有集合中
+    sets.sort_unstable_by_key(|set| set.len());
+    let Some((smallest, others)) = sets.split_first() else {
+        return Ok(0);
+    };
+
+    let mut card = 0;
+    for member in smallest {
+        if others.iter().all(|set| set.contains(member)) {
+            card += 1;
+            if card == limit {
+                break;
+            }
+        }
+    }
+
+    Ok(card)
+}
+
+/// 解析`numkeys key [key ...] [LIMIT limit]`
+pub(super) fn parse_inter_card(args: &mut CmdUnparsed) -> Result<(Vec<Key>, usize), CmdError> {
+    if args.len() < 2 {
+        return Err(Err::WrongArgNum.into());
+    }
+
+    let numkeys = atoi::<usize>(&args.next().unwrap())?;
+    if numkeys == 0 {
+        return Err("ERR numkeys should be greater than 0".into());
+    }
+
+    let keys: Vec<Key> = args.by_ref().take(numkeys).collect();
+    if keys.len() != numkeys {
+        return Err("ERR Number of keys can't be greater than number of args".into());
+    }
+
+    let limit = match args.next() {
+        None => 0,
+        Some(opt) if opt.eq_ignore_ascii_case(b"LIMIT") => {
+            let limit = atoi::<i64>(&args.next().ok_or(Err::Syntax)?)?;
+            if limit < 0 {
+                return Err("ERR LIMIT can't be negative".into());
+            }
+            limit as usize
+        }
+        Some(_) => return Err(Err::Syntax.into()),
+    };
+
+    if !args.is_empty() {
+        return Err(Err::Syntax.into());
+    }
+
+    Ok((keys, limit))
+}
+
+#[cfg(test)]
+mod cmd_set_tests {
+    use super::*;
+    use crate::{shared::db::Set, util::test_init};
+
+    #[tokio::test]
+    async fn sadd_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn sadd(handler: &mut Handler<impl AsyncStream>, args: &[&str]) -> Resp3 {
+            SAdd::parse(&mut args.into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        async fn encoding(handler: &mut Handler<impl AsyncStream>, key: &str) -> Resp3 {
+            ObjectEncoding::parse(&mut [key].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        // 元素均为整数时使用intset编码
+        assert_eq!(
+            sadd(&mut handler, &["set", "3", "1", "2", "1"]).await,
+            Resp3::new_integer(3)
+        );
+        assert_eq!(
+            encoding(&mut handler, "set").await,
+            Resp3::new_blob_string("intset".into())
+        );
+
+        // 非规范形式的整数不能存储在intset中
+        assert_eq!(
+            sadd(&mut handler, &["set", "01"]).await,
+            Resp3::new_integer(1)
+        );
+        assert_eq!(
+            encoding(&mut handler, "set").await,
+            Resp3::new_blob_string("hashtable".into())
+        );
+        assert_eq!(
+            sadd(&mut handler, &["set", "1", "a"]).await,
+            Resp3::new_integer(1)
+        );
+
+        let db = handler.shared.db().clone();
+        let mut members = AHashSet::new();
+        db.visit_object(&"set".into(), |obj| {
+            members = obj.on_set()?.iter().collect();
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(members, ["1", "2", "3", "01", "a"].map(Bytes::from).into());
+
+        // 元素数量超过set-max-intset-entries时转换为hashtable编码
+        ConfigSet::parse(
+            &mut ["set-max-intset-entries", "2"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap()
+        .execute(&mut handler)
+        .await
+        .unwrap();
+        sadd(&mut handler, &["set2", "1", "2"]).await;
+        assert_eq!(
+            encoding(&mut handler, "set2").await,
+            Resp3::new_blob_string("intset".into())
+        );
+        sadd(&mut handler, &["set2", "3"]).await;
+        assert_eq!(
+            encoding(&mut handler, "set2").await,
+            Resp3::new_blob_string("hashtable".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn sintercard_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        let set = |members: &[&'static str]| {
+            ObjectInner::new_set(
+                Set::HashSet(members.iter().map(|m| Bytes::from(*m)).collect()),
+                None,
+            )
+        };
+        db.insert_object(Key::from("set1"), set(&["a", "b", "c", "d"]))
+            .await;
+        db.insert_object(Key::from("set2"), set(&["b", "c", "d", "e"]))
+            .await;
+        db.insert_object(Key::from("set3"), set(&["x", "y"])).await;
+
+        let sintercard = |args: &[&str]| {
+            SInterCard::parse(&mut args.into(), &AccessControl::new_loose()).unwrap()
+        };
+
+        assert_eq!(
+            sintercard(&["2", "set1", "set2"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(3)
+        );
+
+        // 达到LIMIT后停止计算
+        assert_eq!(
+            sintercard(&["2", "set1", "set2", "LIMIT", "2"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(2)
+        );
+
+        // 交集为空
+        assert_eq!(
+            sintercard(&["2", "set1", "set3"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(0)
+        );
+
+        // 键不存在时交集为空
+        assert_eq!(
+            sintercard(&["2", "set1", "set_nil"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(0)
+        );
+
+        assert!(SInterCard::parse(
+            &mut ["3", "set1", "set2"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+        assert!(SInterCard::parse(
+            &mut ["2", "set1", "set2", "LIMIT", "-1"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn smembers_test() {
+        test_init();
+        let (mut handler, mut client) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        let members: AHashSet<Bytes> = (0..100_000)
+            .map(|i| Bytes::from(format!("member{i}")))
+            .collect();
+        db.insert_object(
+            Key::from("big"),
+            ObjectInner::new_set(Set::HashSet(members.clone()), None),
+        )
+        .await;
+        db.insert_object(
+            Key::from("small"),
+            ObjectInner::new_set(Set::HashSet([Bytes::from("a")].into()), None),
+        )
+        .await;
+
+        let smembers =
+            |key: &str| SMembers::parse(&mut [key].as_ref().into(), &AccessControl::new_loose());
+
+        // 元素较少时直接返回完整的回复
+        assert_eq!(
+            smembers("small")
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_array(vec![Resp3::new_blob_string("a".into())])
+        );
+        assert_eq!(
+            smembers("set_nil")
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_array(vec![])
+        );
+
+        // 回复不直接写出到连接时(例如在EXEC中)，即使元素较多也返回完整的回复
+        let res = smembers("big")
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_array().unwrap().len(), 100_000);
+
+        // 元素较多时逐个写入连接，不返回回复
+        handler.context.direct_reply = true;
+        assert!(smembers("big")
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .is_none());
+        handler.conn.write_buffered().await.unwrap();
+
+        let res = client.read_frame().await.unwrap().unwrap();
+        let res: AHashSet<Bytes> = res
+            .try_array()
+            .unwrap()
+            .iter()
+            .map(|m| m.try_blob().unwrap().clone())
+            .collect();
+        assert_eq!(res, members);
+    }
+}
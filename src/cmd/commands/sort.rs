@@ -0,0 +1,689 @@
+// Sort
+// SortRo
+
+use super::*;
+use crate::{
+    cmd::{check_deadline, CmdError, CmdExecutor, CmdType, CmdUnparsed, Err},
+    conf::AccessControl,
+    connection::AsyncStream,
+    frame::Resp3,
+    server::Handler,
+    shared::db::{Db, ObjValue, ObjectInner},
+    util::{self, atoi},
+    Int, Key,
+};
+use bytes::Bytes;
+use std::cmp::Ordering;
+use tokio::time::Instant;
+use tracing::instrument;
+
+/// 对列表，集合或者有序集合的元素进行排序。默认将元素作为浮点数进行比较，ALPHA时按
+/// 字节序比较。BY pattern使用外部键的值作为排序权重(pattern中的第一个`*`会被替换为元
+/// 素，`->field`表示取哈希的字段)，BY nosort则不进行排序。GET pattern返回外部键的值，
+/// `#`表示元素本身。只有STORE时才是写命令，此时以原命令传播
+/// # Reply:
+///
+/// **Array reply:** without passing the STORE option, the command returns a list of sorted elements.
+/// **Integer reply:** when the STORE option is specified, the command returns the number of sorted elements in the destination list.
+#[derive(Debug)]
+pub struct Sort {
+    opts: SortOptions,
+    store: Option<Key>,
+    // 带有STORE时需要传播的原命令
+    wcmd: Vec<Bytes>,
+}
+
+impl CmdExecutor for Sort {
+    const NAME: &'static str = "SORT";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = SORT_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let db = handler.shared.db();
+        let values = self.opts.sort(db, handler.context.deadline).await?;
+
+        let Some(dest) = self.store else {
+            return Ok(Some(values_to_resp3(values)));
+        };
+
+        // SORT的类型为读命令，带有STORE时需要自己hold以及传播(见CmdExecutor::apply)
+        let from_master = handler.context.from_master;
+        if !from_master {
+            handler
+                .shared
+                .wcmd_propagator()
+                .hold_wcmd(&mut handler.context.wcmd_held);
+        }
+
+        let len = values.len();
+        if values.is_empty() {
+            db.remove_object(&dest).await;
+        } else {
+            // 不存在的值以空字符串保存
+            let list: Vec<Bytes> = values.into_iter().map(Option::unwrap_or_default).collect();
+            db.insert_object(dest, ObjectInner::new_list(list, None))
+                .await;
+        }
+
+        if !from_master {
+            handler
+                .shared
+                .wcmd_propagator()
+                .clone()
+                .may_propagate(self.wcmd.into(), handler)
+                .await;
+        }
+
+        Ok(Some(Resp3::new_integer(len as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let wcmd: Vec<Bytes> = std::iter::once(Bytes::from_static(b"SORT"))
+            .chain(args.clone())
+            .collect();
+
+        let (opts, store) = SortOptions::parse(args, ac)?;
+        let Some(dest) = &store else {
+            return Ok(Sort {
+                opts,
+                store,
+                wcmd: Vec::new(),
+            });
+        };
+
+        if ac.is_forbidden_key(dest, CmdType::Write) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(Sort { opts, store, wcmd })
+    }
+}
+
+/// SORT的只读版本，不支持STORE选项
+/// # Reply:
+///
+/// **Array reply:** a list of sorted elements.
+#[derive(Debug)]
+pub struct SortRo {
+    opts: SortOptions,
+}
+
+impl CmdExecutor for SortRo {
+    const NAME: &'static str = "SORT_RO";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = SORT_RO_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let values = self
+            .opts
+            .sort(handler.shared.db(), handler.context.deadline)
+            .await?;
+
+        Ok(Some(values_to_resp3(values)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (opts, store) = SortOptions::parse(args, ac)?;
+        if store.is_some() {
+            return Err(Err::Syntax.into());
+        }
+
+        Ok(SortRo { opts })
+    }
+}
+
+#[derive(Debug)]
+struct SortOptions {
+    key: Key,
+    by: Option<Bytes>,
+    // 不进行排序，BY的pattern中不包含`*`时也不进行排序
+    nosort: bool,
+    limit: Option<(Int, Int)>, // (offset, count)
+    gets: Vec<Bytes>,
+    desc: bool,
+    alpha: bool,
+}
+
+impl SortOptions {
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<(Self, Option<Key>), CmdError> {
+        if args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, CmdType::Read) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let mut opts = SortOptions {
+            key,
+            by: None,
+            nosort: false,
+            limit: None,
+            gets: Vec::new(),
+            desc: false,
+            alpha: false,
+        };
+        let mut store = None;
+
+        while let Some(arg) = args.next() {
+            let mut buf = [0; 8];
+            let len = util::uppercase(&arg, &mut buf).map_err(|_| Err::Syntax)?;
+
+            match &buf[..len] {
+                b"ASC" => opts.desc = false,
+                b"DESC" => opts.desc = true,
+                b"ALPHA" => opts.alpha = true,
+                b"BY" => {
+                    let pattern = args.next().ok_or(Err::Syntax)?;
+                    opts.nosort = !pattern.contains(&b'*');
+                    opts.by = Some(pattern);
+                }
+                b"LIMIT" => {
+                    let offset = atoi::<Int>(&args.next().ok_or(Err::Syntax)?)?;
+                    let count = atoi::<Int>(&args.next().ok_or(Err::Syntax)?)?;
+                    opts.limit = Some((offset, count));
+                }
+                b"GET" => opts.gets.push(args.next().ok_or(Err::Syntax)?),
+                b"STORE" => store = Some(args.next().ok_or(Err::Syntax)?),
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        // BY和GET读取的键由元素决定，无法在解析时检查，因此要求能够读取所有键
+        let derived = (opts.by.is_some() && !opts.nosort)
+            || opts.gets.iter().any(|pattern| pattern.as_ref() != b"#");
+        if derived && !ac.can_read_all_keys() {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok((opts, store))
+    }
+
+    /// 返回排序后的结果，不存在的值为None。超过deadline时返回超时错误，此时尚未修改
+    /// 任何数据
+    async fn sort(
+        &self,
+        db: &Db,
+        deadline: Option<Instant>,
+    ) -> Result<Vec<Option<Bytes>>, CmdError> {
+        let mut elems = Vec::new();
+        match db
+            .visit_object(&self.key, |obj| {
+                match obj.value() {
+                    ObjValue::List(list) => {
+                        elems.extend((0..list.len()).filter_map(|i| list.get(i)));
+                    }
+                    ObjValue::Set(set) => elems.extend(set.iter()),
+                    ObjValue::ZSet(zset) => elems.extend(zset.members().cloned()),
+                    _ => return Err(Err::WrongType.into()),
+                }
+
+                Ok(())
+            })
+            .await
+        {
+            Ok(()) | Err(CmdError::Null) => {}
+            Err(e) => return Err(e),
+        }
+
+        if !self.nosort {
+            let mut weighted = Vec::with_capacity(elems.len());
+            for elem in elems {
+                check_deadline(deadline)?;
+                let weight = match &self.by {
+                    Some(pattern) => lookup(db, pattern, &elem).await,
+                    None => Some(elem.clone()),
+                };
+                weighted.push((weight, elem));
+            }
+
+            check_deadline(deadline)?;
+            elems = if self.alpha {
+                // 不存在的权重排在最前面
+                weighted.sort_by(|(w1, e1), (w2, e2)| w1.cmp(w2).then_with(|| e1.cmp(e2)));
+                weighted.into_iter().map(|(_, e)| e).collect()
+            } else {
+                let mut scored = Vec::with_capacity(weighted.len());
+                for (weight, elem) in weighted {
+                    let score = match weight {
+                        Some(w) => std::str::from_utf8(&w)
+                            .ok()
+                            .and_then(|w| w.trim().parse::<f64>().ok())
+                            .filter(|score| !score.is_nan())
+                            .ok_or("ERR One or more scores can't be converted into double")?,
+                        // 不存在的权重视为0
+                        None => 0.0,
+                    };
+                    scored.push((score, elem));
+                }
+                scored.sort_by(|(s1, e1), (s2, e2)| {
+                    s1.partial_cmp(s2)
+                        .unwrap_or(Ordering::Equal)
+                        .then_with(|| e1.cmp(e2))
+                });
+                scored.into_iter().map(|(_, e)| e).collect()
+            };
+
+            if self.desc {
+                elems.reverse();
+            }
+        }
+
+        if let Some((offset, count)) = self.limit {
+            let offset = (offset.max(0) as usize).min(elems.len());
+            let count = if count < 0 {
+                elems.len() - offset
+            } else {
+                (count as usize).min(elems.len() - offset)
+            };
+            elems = elems.drain(offset..offset + count).collect();
+        }
+
+        if self.gets.is_empty() {
+            return Ok(elems.into_iter().map(Some).collect());
+        }
+
+        let mut values = Vec::with_capacity(elems.len() * self.gets.len());
+        for elem in &elems {
+            check_deadline(deadline)?;
+            for pattern in &self.gets {
+                if pattern.as_ref() == b"#" {
+                    values.push(Some(elem.clone()));
+                } else {
+                    values.push(lookup(db, pattern, elem).await);
+                }
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// 将pattern中的第一个`*`替换为elem作为键，获取字符串的值。如果pattern中包含`->field`，
+/// 则获取哈希的字段值。键不存在或者类型不符时返回None
+async fn lookup(db: &Db, pattern: &[u8], elem: &[u8]) -> Option<Bytes> {
+    let star = pattern.iter().position(|&b| b == b'*')?;
+
+    // `->`必须出现在`*`之后，且字段名不能为空
+    let (key_pattern, field) = match pattern[star..].windows(2).rposition(|w| w == b"->") {
+        Some(i) if star + i + 2 < pattern.len() => {
+            (&pattern[..star + i], Some(&pattern[star + i + 2..]))
+        }
+        _ => (pattern, None),
+    };
+
+    let mut key = Vec::with_capacity(key_pattern.len() + elem.len());
+    key.extend_from_slice(&key_pattern[..star]);
+    key.extend_from_slice(elem);
+    key.extend_from_slice(&key_pattern[star + 1..]);
+    let key = Key::from(key);
+
+    let mut res = None;
+    db.visit_object(&key, |obj| {
+        res = match field {
+            Some(field) => obj.on_hash()?.get(&Key::copy_from_slice(field)),
+            None => Some(obj.on_str()?.to_bytes()),
+        };
+
+        Ok(())
+    })
+    .await
+    .ok()?;
+
+    res
+}
+
+fn values_to_resp3(values: Vec<Option<Bytes>>) -> Resp3 {
+    Resp3::new_array(
+        values
+            .into_iter()
+            .map(|v| v.map_or(Resp3::Null, Resp3::new_blob_string))
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[cfg(test)]
+mod cmd_sort_tests {
+    use super::*;
+    use crate::util::{cmd, test_init};
+
+    fn blobs(res: Resp3) -> Vec<String> {
+        res.try_array()
+            .unwrap()
+            .iter()
+            .map(|v| match v.try_blob() {
+                Some(b) => String::from_utf8(b.to_vec()).unwrap(),
+                None => "nil".to_string(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sort_numeric_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        // list: 3 10 1 2.5
+        handler
+            .dispatch(cmd(&["LPUSH", "list", "2.5", "1", "10", "3"]))
+            .await
+            .unwrap();
+
+        let res = handler
+            .dispatch(cmd(&["SORT", "list"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blobs(res), ["1", "2.5", "3", "10"]);
+
+        let res = handler
+            .dispatch(cmd(&["SORT", "list", "DESC", "LIMIT", "1", "2"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blobs(res), ["3", "2.5"]);
+
+        // 使用外部键作为权重，并获取外部键以及哈希字段的值
+        for (elem, weight) in [("1", "30"), ("2.5", "10"), ("3", "20"), ("10", "0")] {
+            handler
+                .dispatch(cmd(&["SET", &format!("w_{elem}"), weight]))
+                .await
+                .unwrap();
+        }
+        let res = handler
+            .dispatch(cmd(&[
+                "SORT", "list", "BY", "w_*", "GET", "#", "GET", "w_*",
+            ]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blobs(res), ["10", "0", "2.5", "10", "3", "20", "1", "30"]);
+
+        // BY nosort不进行排序，保持列表的顺序
+        let res = handler
+            .dispatch(cmd(&["SORT", "list", "BY", "nosort"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blobs(res), ["3", "10", "1", "2.5"]);
+
+        let res = handler
+            .dispatch(cmd(&["LPUSH", "bad", "a", "1"]))
+            .await
+            .unwrap();
+        assert!(res.is_some());
+        let res = handler
+            .dispatch(cmd(&["SORT", "bad"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR One or more scores can't be converted into double"
+        );
+    }
+
+    #[tokio::test]
+    async fn sort_alpha_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        handler
+            .dispatch(cmd(&["SADD", "set", "banana", "apple", "cherry"]))
+            .await
+            .unwrap();
+
+        let res = handler
+            .dispatch(cmd(&["SORT", "set", "ALPHA"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blobs(res), ["apple", "banana", "cherry"]);
+
+        let res = handler
+            .dispatch(cmd(&["SORT_RO", "set", "ALPHA", "DESC"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blobs(res), ["cherry", "banana", "apple"]);
+
+        // 哈希字段作为权重
+        handler
+            .dispatch(cmd(&["HSET", "h_apple", "rank", "c"]))
+            .await
+            .unwrap();
+        handler
+            .dispatch(cmd(&["HSET", "h_banana", "rank", "a"]))
+            .await
+            .unwrap();
+        let res = handler
+            .dispatch(cmd(&[
+                "SORT",
+                "set",
+                "BY",
+                "h_*->rank",
+                "ALPHA",
+                "GET",
+                "h_*->rank",
+            ]))
+            .await
+            .unwrap()
+            .unwrap();
+        // cherry没有权重，排在最前面
+        assert_eq!(blobs(res), ["nil", "a", "c"]);
+
+        let res = handler
+            .dispatch(cmd(&["SORT_RO", "set", "STORE", "dest"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().is_some());
+
+        // 键不存在时返回空数组
+        let res = handler
+            .dispatch(cmd(&["SORT", "nokey"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sort_store_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        handler
+            .shared
+            .db()
+            .insert_object(
+                "zset".into(),
+                ObjectInner::new_zset([(1.0, "c"), (2.0, "a"), (3.0, "b")], None),
+            )
+            .await;
+
+        let res = handler
+            .dispatch(cmd(&["SORT", "zset", "ALPHA", "STORE", "dest"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 3);
+
+        let res = handler
+            .dispatch(cmd(&["LINDEX", "dest", "0"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"a");
+        let res = handler
+            .dispatch(cmd(&["LLEN", "dest"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 3);
+
+        // 结果为空时删除目标键
+        let res = handler
+            .dispatch(cmd(&["SORT", "nokey", "STORE", "dest"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 0);
+        let res = handler
+            .dispatch(cmd(&["EXISTS", "dest"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_integer().unwrap(), 0);
+
+        // 超过截止时间时在写入目标键之前中断
+        let sort = Sort::parse(
+            &mut ["zset", "ALPHA", "STORE", "dest"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        let db = handler.shared.db();
+        let err: Resp3 = sort
+            .opts
+            .sort(db, Some(Instant::now()))
+            .await
+            .unwrap_err()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            err.try_simple_error().unwrap(),
+            "ERR command execution timed out"
+        );
+        assert_eq!(sort.opts.sort(db, None).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn sort_acl_test() {
+        use regex::bytes::RegexSet;
+        use std::sync::Arc;
+
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        handler
+            .dispatch(cmd(&["LPUSH", "list", "1", "2"]))
+            .await
+            .unwrap();
+
+        let is_denied = |res: Option<Resp3>| {
+            res.unwrap()
+                .try_simple_error()
+                .is_some_and(|e| e.starts_with("NOPERM"))
+        };
+
+        // 源键作为读取的键检查，STORE的目标键作为写入的键检查
+        handler.context.ac = Arc::new(AccessControl {
+            deny_write_key_patterns: Some(RegexSet::new(["list", "dest"]).unwrap()),
+            ..AccessControl::new_loose()
+        });
+        let res = handler.dispatch(cmd(&["SORT", "list"])).await.unwrap();
+        assert!(!is_denied(res));
+        let res = handler
+            .dispatch(cmd(&["SORT", "list", "STORE", "dest"]))
+            .await
+            .unwrap();
+        assert!(is_denied(res));
+
+        // 不能读取所有键时，拒绝需要读取外部键的BY和GET
+        handler.context.ac = Arc::new(AccessControl {
+            deny_read_key_patterns: Some(RegexSet::new(["secret"]).unwrap()),
+            ..AccessControl::new_loose()
+        });
+        for args in [
+            &["SORT", "list", "BY", "w_*"][..],
+            &["SORT", "list", "GET", "w_*"],
+            &["SORT_RO", "list", "BY", "nosort", "GET", "#", "GET", "w_*"],
+        ] {
+            let res = handler.dispatch(cmd(args)).await.unwrap();
+            assert!(is_denied(res));
+        }
+        let res = handler
+            .dispatch(cmd(&["SORT", "list", "BY", "nosort", "GET", "#"]))
+            .await
+            .unwrap();
+        assert!(!is_denied(res));
+
+        // 只能访问部分键的selector同样被拒绝，能访问所有键的selector则允许
+        let mut ac = AccessControl {
+            selectors: vec![AccessControl::parse_selector(b"allcommands ~list ~w_*").unwrap()],
+            ..AccessControl::new_strict()
+        };
+        handler.context.ac = Arc::new(ac.clone());
+        let res = handler
+            .dispatch(cmd(&["SORT", "list", "BY", "w_*"]))
+            .await
+            .unwrap();
+        assert!(is_denied(res));
+        ac.selectors
+            .push(AccessControl::parse_selector(b"allcommands ~*").unwrap());
+        handler.context.ac = Arc::new(ac);
+        let res = handler
+            .dispatch(cmd(&["SORT", "list", "BY", "w_*"]))
+            .await
+            .unwrap();
+        assert!(!is_denied(res));
+    }
+
+    #[tokio::test]
+    async fn sort_propagate_test() {
+        use crate::{
+            frame::RESP3Decoder,
+            shared::{db::Db, Shared},
+        };
+        use async_shutdown::ShutdownManager;
+        use std::sync::Arc;
+        use tokio_util::codec::Decoder;
+
+        test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+        let (mut handler, _) = Handler::with_shared(master.clone());
+
+        handler
+            .dispatch(cmd(&["LPUSH", "list", "1", "2"]))
+            .await
+            .unwrap();
+        replica_rx.recv().await.unwrap();
+
+        // 不带STORE的SORT不会被传播
+        handler.dispatch(cmd(&["SORT", "list"])).await.unwrap();
+        assert!(replica_rx.try_recv().unwrap().is_none());
+
+        handler
+            .dispatch(cmd(&["SORT", "list", "DESC", "STORE", "dest"]))
+            .await
+            .unwrap();
+        assert!(!handler.context.wcmd_held);
+        let mut buf = replica_rx.recv().await.unwrap();
+        assert_eq!(
+            RESP3Decoder::default().decode(&mut buf).unwrap().unwrap(),
+            cmd(&["SORT", "list", "DESC", "STORE", "dest"])
+        );
+    }
+}
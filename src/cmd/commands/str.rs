@@ -9,7 +9,7 @@ use crate::{
     frame::Resp3,
     server::Handler,
     shared::db::{ObjValueType, ObjectInner},
-    util::{atoi, epoch},
+    util::{self, atoi, epoch, unix_millis},
     Int, Key,
 };
 use bytes::Bytes;
@@ -31,6 +31,8 @@ impl CmdExecutor for Append {
     const NAME: &'static str = "APPEND";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = APPEND_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -71,6 +73,292 @@ impl CmdExecutor for Append {
     }
 }
 
+/// 将字符串视为位数组，读写其中任意宽度(有符号i1~i64，无符号u1~u63)、任意偏移量的整数。
+/// 偏移量以#开头时，表示类型宽度的倍数。OVERFLOW指定其后的SET和INCRBY溢出时的行为，
+/// 默认为WRAP
+/// # Reply:
+///
+/// **Array reply:** each entry being the corresponding result of the sub-command given at
+/// the same position. OVERFLOW subcommands don't count as generating a reply.
+/// **Null reply:** for the SET and INCRBY sub-commands when OVERFLOW FAIL is given and
+/// overflow is detected.
+#[derive(Debug)]
+pub struct BitField {
+    key: Key,
+    ops: Vec<BitFieldOp>,
+}
+
+impl CmdExecutor for BitField {
+    const NAME: &'static str = "BITFIELD";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = BITFIELD_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut res = vec![];
+
+        let write = self
+            .ops
+            .iter()
+            .any(|op| matches!(op, BitFieldOp::Set(..) | BitFieldOp::IncrBy(..)));
+        if write {
+            handler
+                .shared
+                .db()
+                .update_or_create_object(&self.key, ObjValueType::Str, |obj| {
+                    let str = obj.on_str_mut()?;
+
+                    let mut bits = str.to_vec();
+                    res = bitfield(&mut bits, &self.ops);
+                    str.set(bits.into());
+                    Ok(())
+                })
+                .await?;
+        } else {
+            // 只读时不创建键，键不存在时视为空字符串
+            let visited = handler
+                .shared
+                .db()
+                .visit_object(&self.key, |obj| {
+                    res = bitfield(&mut obj.on_str()?.to_vec(), &self.ops);
+                    Ok(())
+                })
+                .await;
+            match visited {
+                Ok(()) => {}
+                Err(CmdError::Null) => res = bitfield(&mut vec![], &self.ops),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Some(Resp3::new_array(res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let mut ops = vec![];
+        while let Some(sub_cmd) = args.next() {
+            let mut buf = [0; 8];
+            let len = util::uppercase(&sub_cmd, &mut buf).map_err(|_| Err::Syntax)?;
+
+            let op = match &buf[..len] {
+                b"GET" => {
+                    let typ = BitFieldType::parse(&args.next().ok_or(Err::Syntax)?)?;
+                    let offset = typ.parse_offset(&args.next().ok_or(Err::Syntax)?)?;
+                    BitFieldOp::Get(typ, offset)
+                }
+                b"SET" | b"INCRBY" => {
+                    let typ = BitFieldType::parse(&args.next().ok_or(Err::Syntax)?)?;
+                    let offset = typ.parse_offset(&args.next().ok_or(Err::Syntax)?)?;
+                    let value =
+                        atoi::<Int>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+                    if &buf[..len] == b"SET" {
+                        BitFieldOp::Set(typ, offset, value)
+                    } else {
+                        BitFieldOp::IncrBy(typ, offset, value)
+                    }
+                }
+                b"OVERFLOW" => {
+                    let mode = args.next().ok_or(Err::Syntax)?;
+                    let overflow = if mode.eq_ignore_ascii_case(b"WRAP") {
+                        BitFieldOverflow::Wrap
+                    } else if mode.eq_ignore_ascii_case(b"SAT") {
+                        BitFieldOverflow::Sat
+                    } else if mode.eq_ignore_ascii_case(b"FAIL") {
+                        BitFieldOverflow::Fail
+                    } else {
+                        return Err("ERR Invalid OVERFLOW type specified".into());
+                    };
+                    BitFieldOp::Overflow(overflow)
+                }
+                _ => return Err(Err::Syntax.into()),
+            };
+            ops.push(op);
+        }
+
+        Ok(BitField { key, ops })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BitFieldOp {
+    Get(BitFieldType, u64),
+    Set(BitFieldType, u64, Int),
+    IncrBy(BitFieldType, u64, Int),
+    Overflow(BitFieldOverflow),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BitFieldOverflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BitFieldType {
+    signed: bool,
+    bits: u32,
+}
+
+// 字符串最大为512MB
+const BITFIELD_MAX_BITS: u64 = 512 * 1024 * 1024 * 8;
+
+impl BitFieldType {
+    fn parse(typ: &[u8]) -> Result<Self, CmdError> {
+        let err = || {
+            CmdError::from(
+                "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.",
+            )
+        };
+
+        let (signed, bits) = match typ.split_first() {
+            Some((b'i' | b'I', bits)) => (true, bits),
+            Some((b'u' | b'U', bits)) => (false, bits),
+            _ => return Err(err()),
+        };
+        let bits = atoi::<u32>(bits).map_err(|_| err())?;
+        if bits == 0 || (signed && bits > 64) || (!signed && bits > 63) {
+            return Err(err());
+        }
+
+        Ok(BitFieldType { signed, bits })
+    }
+
+    fn parse_offset(&self, offset: &[u8]) -> Result<u64, CmdError> {
+        let err = || CmdError::from("ERR bit offset is not an integer or out of range");
+
+        let offset = match offset.strip_prefix(b"#") {
+            Some(n) => atoi::<u64>(n)
+                .ok()
+                .and_then(|n| n.checked_mul(self.bits as u64)),
+            None => atoi::<u64>(offset).ok(),
+        }
+        .ok_or_else(err)?;
+        if offset + self.bits as u64 > BITFIELD_MAX_BITS {
+            return Err(err());
+        }
+
+        Ok(offset)
+    }
+
+    // 位数组中超出字符串长度的部分视为0
+    fn get(&self, buf: &[u8], offset: u64) -> Int {
+        let mut value = 0_u64;
+        for pos in offset..offset + self.bits as u64 {
+            let byte = buf.get((pos >> 3) as usize).copied().unwrap_or(0);
+            value = (value << 1) | ((byte >> (7 - (pos & 7))) & 1) as u64;
+        }
+
+        // 符号扩展
+        if self.signed && self.bits < 64 && (value >> (self.bits - 1)) & 1 == 1 {
+            value |= u64::MAX << self.bits;
+        }
+        value as Int
+    }
+
+    // 字符串长度不足时用0填充
+    fn set(&self, buf: &mut Vec<u8>, offset: u64, value: Int) {
+        let end = ((offset + self.bits as u64 + 7) >> 3) as usize;
+        if buf.len() < end {
+            buf.resize(end, 0);
+        }
+
+        let value = value as u64;
+        for (i, pos) in (offset..offset + self.bits as u64).enumerate() {
+            let bit = (value >> (self.bits as usize - 1 - i)) & 1;
+            let byte = &mut buf[(pos >> 3) as usize];
+            let mask = 1 << (7 - (pos & 7));
+            if bit == 1 {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+    }
+
+    /// 将value按照溢出行为调整到类型的范围内，FAIL模式下溢出时返回None
+    fn fit(&self, value: i128, overflow: BitFieldOverflow) -> Option<Int> {
+        let (min, max) = if self.signed {
+            (
+                -(1_i128 << (self.bits - 1)),
+                (1_i128 << (self.bits - 1)) - 1,
+            )
+        } else {
+            (0, (1_i128 << self.bits) - 1)
+        };
+        if (min..=max).contains(&value) {
+            return Some(value as Int);
+        }
+
+        match overflow {
+            BitFieldOverflow::Wrap => {
+                let wrapped = value as u128 & ((1_u128 << self.bits) - 1);
+                if self.signed && (wrapped >> (self.bits - 1)) & 1 == 1 {
+                    Some((wrapped as i128 - (1_i128 << self.bits)) as Int)
+                } else {
+                    Some(wrapped as Int)
+                }
+            }
+            BitFieldOverflow::Sat => Some(if value > max { max } else { min } as Int),
+            BitFieldOverflow::Fail => None,
+        }
+    }
+}
+
+fn bitfield(buf: &mut Vec<u8>, ops: &[BitFieldOp]) -> Vec<Resp3> {
+    let mut overflow = BitFieldOverflow::Wrap;
+
+    ops.iter()
+        .filter_map(|op| match *op {
+            BitFieldOp::Overflow(o) => {
+                overflow = o;
+                None
+            }
+            BitFieldOp::Get(typ, offset) => Some(Resp3::new_integer(typ.get(buf, offset))),
+            BitFieldOp::Set(typ, offset, value) => {
+                let old = typ.get(buf, offset);
+                // 与Redis一致，无符号类型的值按照u64解释
+                let value = if typ.signed {
+                    value as i128
+                } else {
+                    value as u64 as i128
+                };
+                Some(match typ.fit(value, overflow) {
+                    Some(value) => {
+                        typ.set(buf, offset, value);
+                        Resp3::new_integer(old)
+                    }
+                    None => Resp3::Null,
+                })
+            }
+            BitFieldOp::IncrBy(typ, offset, incr) => {
+                let old = typ.get(buf, offset);
+                Some(match typ.fit(old as i128 + incr as i128, overflow) {
+                    Some(value) => {
+                        typ.set(buf, offset, value);
+                        Resp3::new_integer(value)
+                    }
+                    None => Resp3::Null,
+                })
+            }
+        })
+        .collect()
+}
+
 /// 将 key 中储存的数字值减一。
 /// # Reply:
 ///
@@ -84,6 +372,8 @@ impl CmdExecutor for Decr {
     const NAME: &'static str = "DECR";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = DECR_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -129,9 +419,11 @@ pub struct DecrBy {
 }
 
 impl CmdExecutor for DecrBy {
-    const NAME: &'static str = "DECYBY";
+    const NAME: &'static str = "DECRBY";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = DECRBY_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -183,6 +475,8 @@ impl CmdExecutor for Get {
     const NAME: &'static str = "GET";
     const TYPE: crate::cmd::CmdType = CmdType::Read;
     const FLAG: CmdFlag = GET_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[inline]
     #[instrument(level = "debug", skip(handler), ret, err)]
@@ -234,6 +528,8 @@ impl CmdExecutor for GetRange {
     const NAME: &'static str = "GETRANGE";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = GETRANGE_FLAG;
+    const ARITY: Int = 4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -278,7 +574,8 @@ impl CmdExecutor for GetRange {
     }
 }
 
-/// 将给定 key 的值设为 value ，并返回 key 的旧值(old value)。
+/// 将给定 key 的值设为 value ，并返回 key 的旧值(old value)。等价于SET key value GET，
+/// 会清除键的过期时间
 /// # Reply:
 ///
 /// **Bulk string reply:** the old value stored at the key.
@@ -293,25 +590,24 @@ impl CmdExecutor for GetSet {
     const NAME: &'static str = "GETSET";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = GETSET_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let mut old = "".into();
-
-        handler
-            .shared
-            .db()
-            .update_object(&self.key, |obj| {
-                let str = obj.on_str_mut()?;
-                old = str.set(self.new_value).to_bytes();
-                Ok(())
-            })
-            .await?;
-
-        Ok(Some(Resp3::new_blob_string(old)))
+        Set {
+            key: self.key,
+            value: self.new_value,
+            opt: None,
+            get: true,
+            expire: None,
+            relative_expire: false,
+        }
+        .execute(handler)
+        .await
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
@@ -344,6 +640,8 @@ impl CmdExecutor for Incr {
     const NAME: &'static str = "INCR";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = INCR_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -393,6 +691,8 @@ impl CmdExecutor for IncrBy {
     const NAME: &'static str = "INCRBY";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = INCRBY_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -444,27 +744,23 @@ impl CmdExecutor for MGet {
     const NAME: &'static str = "MGET";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = MGET_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, -1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        let mut res = Vec::with_capacity(self.keys.len());
-        for key in self.keys.iter() {
-            let mut str = "".into();
-
-            handler
-                .shared
-                .db()
-                .visit_object(key, |obj| {
-                    str = obj.on_str()?.to_bytes();
-                    Ok(())
-                })
-                .await?;
-
-            res.push(Resp3::new_blob_string(str));
-        }
+        // 键不存在或者不是字符串时返回nil
+        let res = handler
+            .shared
+            .db()
+            .multi_get(&self.keys, |obj| obj.on_str().map(|s| s.to_bytes()).ok())
+            .await
+            .into_iter()
+            .map(|str| str.flatten().map_or(Resp3::Null, Resp3::new_blob_string))
+            .collect::<Vec<_>>();
 
         Ok(Some(Resp3::new_array(res)))
     }
@@ -496,6 +792,8 @@ impl CmdExecutor for MSet {
     const NAME: &'static str = "MSET";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = MSET_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, -1, 2);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -514,7 +812,7 @@ impl CmdExecutor for MSet {
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
-        if args.len() < 2 || args.len() % 2 != 0 {
+        if args.len() < 2 || !args.len().is_multiple_of(2) {
             return Err(Err::WrongArgNum.into());
         }
 
@@ -546,31 +844,36 @@ impl CmdExecutor for MSetNx {
     const NAME: &'static str = "MSETNX";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = MSETNX_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, -1, 2);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        for (key, _) in &self.pairs {
-            if handler.shared.db().contains_object(key).await {
-                return Err(0.into());
-            }
-        }
-
-        for (key, value) in self.pairs {
-            handler
-                .shared
-                .db()
-                .insert_object(key, ObjectInner::new_str(value, None))
-                .await;
+        let db = handler.shared.db();
+
+        let mut pairs = self.pairs;
+        // 对于重复的键，以最后一个值为准
+        pairs.reverse();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        pairs.dedup_by(|a, b| a.0 == b.0);
+
+        // 检查所有键与写入所有键在同一次加锁中完成，要么全部写入，要么都不写入
+        let objects = pairs
+            .into_iter()
+            .map(|(key, value)| (key, ObjectInner::new_str(value, None)))
+            .collect();
+        if !db.insert_objects_if_absent(objects).await {
+            return Err(0.into());
         }
 
         Ok(Some(Resp3::new_integer(1)))
     }
 
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
-        if args.len() < 2 || args.len() % 2 != 0 {
+        if args.len() < 2 || !args.len().is_multiple_of(2) {
             return Err(Err::WrongArgNum.into());
         }
 
@@ -601,6 +904,7 @@ pub struct Set {
     opt: Option<SetOpt>,
     get: bool,
     expire: Option<Instant>, // None代表无要求，Some(EPOCH)代表保持原expire
+    relative_expire: bool,   // 是否由EX/PX设置的相对过期时间，只有相对过期时间才会加上抖动
 }
 
 #[derive(Debug)]
@@ -615,6 +919,8 @@ impl CmdExecutor for Set {
     const NAME: &'static str = "SET";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = SET_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[inline]
     #[instrument(level = "debug", skip(handler), ret, err)]
@@ -622,35 +928,46 @@ impl CmdExecutor for Set {
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        // 1. 是否要求键存在？
-        // 2. 满足命令对键的要求后，更新值
-        // 3. 是否需要更新expire?
+        // 1. 是否需要返回旧值？旧值不是字符串时返回WRONGTYPE，且不修改键
+        // 2. 是否要求键存在？
+        // 3. 满足命令对键的要求后，更新值
+        // 4. 是否需要更新expire?
+
+        let entry = handler.shared.db().get_object_entry_mut(self.key).await;
+
+        let old = if self.get {
+            match entry.value() {
+                Some(obj) => Some(obj.on_str()?.to_bytes()),
+                None => None,
+            }
+        } else {
+            None
+        };
+        let old_reply = || Some(old.clone().map_or(Resp3::Null, Resp3::new_blob_string));
 
-        let mut key_flag = match self.opt {
+        let key_flag = match self.opt {
             Some(SetOpt::NX) => Some(false),
             Some(SetOpt::XX) => Some(true),
             _ => None,
         };
-        if self.get {
-            key_flag = Some(true);
-        }
-        if let Some(ex) = self.expire {
-            if ex == epoch() {
-                key_flag = Some(true);
-            }
-        }
-
-        let entry = handler.shared.db().get_object_entry_mut(self.key).await;
         if let Some(flag) = key_flag {
             if flag != entry.is_object_existed() {
-                return Err(CmdError::Null);
+                // 未设置值，但带有GET时仍然返回旧值
+                return if self.get {
+                    Ok(old_reply())
+                } else {
+                    Err(CmdError::Null)
+                };
             }
         }
 
         let new_ex = if let Some(ex) = self.expire {
             if ex.duration_since(epoch()) < Duration::from_millis(10) {
-                // 保持不变
-                entry.value().unwrap().expire()
+                // 保持不变(KEEPTTL)，键不存在时永不过期
+                entry.value().and_then(|obj| obj.expire())
+            } else if self.relative_expire {
+                // 更新
+                Some(handler.shared.conf().server.jitter_expire(ex))
             } else {
                 // 更新
                 Some(ex)
@@ -660,13 +977,24 @@ impl CmdExecutor for Set {
             None
         };
 
+        if self.expire.is_some() {
+            // 过期时间改写为绝对时间后再传播。NX、XX等条件已经在此处检查过，无需传播
+            let mut wcmd = vec![
+                Bytes::from_static(b"SET"),
+                entry.key().clone(),
+                self.value.clone(),
+            ];
+            if let Some(ex) = new_ex {
+                wcmd.extend([Bytes::from_static(b"PXAT"), unix_millis(ex)]);
+            }
+            handler.context.wcmd_rewrite = Some(wcmd.into());
+        }
+
         let new_obj = ObjectInner::new_str(self.value, new_ex);
-        let (_, old) = entry.insert_object(new_obj);
+        entry.insert_object(new_obj);
 
         if self.get {
-            Ok(Some(Resp3::new_blob_string(
-                old.unwrap().on_str()?.to_bytes(),
-            )))
+            Ok(old_reply())
         } else {
             Ok(Some(Resp3::new_simple_string("OK".into())))
         }
@@ -696,6 +1024,7 @@ impl CmdExecutor for Set {
                     opt: None,
                     get: false,
                     expire: None,
+                    relative_expire: false,
                 });
             }
             Some(opt) => {
@@ -725,6 +1054,7 @@ impl CmdExecutor for Set {
                     opt,
                     get: false,
                     expire: None,
+                    relative_expire: false,
                 });
             }
             Some(get) => {
@@ -740,6 +1070,7 @@ impl CmdExecutor for Set {
             }
         };
 
+        let mut relative_expire = false;
         let expire = match next {
             None => {
                 // 已经没有参数了
@@ -749,6 +1080,7 @@ impl CmdExecutor for Set {
                     opt,
                     get,
                     expire: None,
+                    relative_expire: false,
                 });
             }
             Some(ex) => {
@@ -756,11 +1088,13 @@ impl CmdExecutor for Set {
                     b"KEEPTTL" => Some(epoch()),
                     b"EX" => {
                         let expire_value = args.next().ok_or(Err::WrongArgNum)?;
+                        relative_expire = true;
                         Some(Instant::now() + Duration::from_secs(atoi(&expire_value)?))
                     }
                     // PX milliseconds -- 以毫秒为单位设置键的过期时间
                     b"PX" => {
                         let expire_value = args.next().ok_or(Err::WrongArgNum)?;
+                        relative_expire = true;
                         Some(Instant::now() + Duration::from_millis(atoi(&expire_value)?))
                     }
                     // EXAT timestamp -- timestamp是以秒为单位的Unix时间戳
@@ -788,6 +1122,7 @@ impl CmdExecutor for Set {
             opt,
             get,
             expire,
+            relative_expire,
         })
     }
 }
@@ -807,19 +1142,35 @@ impl CmdExecutor for SetEx {
     const NAME: &'static str = "SETEX";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = SETEX_FLAG;
+    const ARITY: Int = 4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
         self,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
+        let expire = handler
+            .shared
+            .conf()
+            .server
+            .jitter_expire(Instant::now() + self.expire);
+
+        handler.context.wcmd_rewrite = Some(
+            vec![
+                Bytes::from_static(b"SET"),
+                self.key.clone(),
+                self.value.clone(),
+                Bytes::from_static(b"PXAT"),
+                unix_millis(expire),
+            ]
+            .into(),
+        );
+
         handler
             .shared
             .db()
-            .insert_object(
-                self.key,
-                ObjectInner::new_str(self.value, Some(Instant::now() + self.expire)),
-            )
+            .insert_object(self.key, ObjectInner::new_str(self.value, Some(expire)))
             .await;
 
         Ok(Some(Resp3::new_simple_string("OK".into())))
@@ -835,13 +1186,87 @@ impl CmdExecutor for SetEx {
             return Err(Err::NoPermission.into());
         }
 
-        let expire = Duration::from_secs(atoi(&args.next().unwrap())?);
+        let expire = atoi::<i64>(&args.next().unwrap())?;
+        if expire <= 0 {
+            return Err("ERR invalid expire time in 'setex' command".into());
+        }
+        let expire = Duration::from_secs(expire as u64);
         let value = args.next().unwrap();
 
         Ok(SetEx { key, value, expire })
     }
 }
 
+/// 将值 value 关联到 key ，并将 key 的过期时间设为 milliseconds (以毫秒为单位)。
+/// # Reply:
+///
+/// **Simple string reply:** OK.
+#[derive(Debug)]
+pub struct PSetEx {
+    pub key: Key,
+    pub expire: Duration,
+    pub value: Bytes,
+}
+
+impl CmdExecutor for PSetEx {
+    const NAME: &'static str = "PSETEX";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = PSETEX_FLAG;
+    const ARITY: Int = 4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let expire = handler
+            .shared
+            .conf()
+            .server
+            .jitter_expire(Instant::now() + self.expire);
+
+        handler.context.wcmd_rewrite = Some(
+            vec![
+                Bytes::from_static(b"SET"),
+                self.key.clone(),
+                self.value.clone(),
+                Bytes::from_static(b"PXAT"),
+                unix_millis(expire),
+            ]
+            .into(),
+        );
+
+        handler
+            .shared
+            .db()
+            .insert_object(self.key, ObjectInner::new_str(self.value, Some(expire)))
+            .await;
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 3 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let expire = atoi::<i64>(&args.next().unwrap())?;
+        if expire <= 0 {
+            return Err("ERR invalid expire time in 'psetex' command".into());
+        }
+        let expire = Duration::from_millis(expire as u64);
+        let value = args.next().unwrap();
+
+        Ok(PSetEx { key, value, expire })
+    }
+}
+
 /// 只有在 key 不存在时设置 key 的值。
 /// # Reply:
 ///
@@ -857,6 +1282,8 @@ impl CmdExecutor for SetNx {
     const NAME: &'static str = "SETNX";
     const TYPE: CmdType = CmdType::Write;
     const FLAG: CmdFlag = SETNX_FLAG;
+    const ARITY: Int = 3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -906,6 +1333,8 @@ impl CmdExecutor for StrLen {
     const NAME: &'static str = "STRLEN";
     const TYPE: CmdType = CmdType::Read;
     const FLAG: CmdFlag = STRLEN_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
 
     #[instrument(level = "debug", skip(handler), ret, err)]
     async fn execute(
@@ -942,7 +1371,7 @@ impl CmdExecutor for StrLen {
 #[cfg(test)]
 mod cmd_str_tests {
     use super::*;
-    use crate::util::test_init;
+    use crate::{connection::FakeStream, shared::db::List, util::test_init};
     use std::{
         thread::sleep,
         time::{Duration, SystemTime},
@@ -1082,6 +1511,7 @@ mod cmd_str_tests {
             "value_never_expire".as_bytes()
         );
 
+        // 键不存在时返回Null，但仍然设置值
         let set = Set::parse(
             &mut ["key_never_exist", "value_never_exist", "GET"]
                 .as_ref()
@@ -1089,10 +1519,14 @@ mod cmd_str_tests {
             &AccessControl::new_loose(),
         )
         .unwrap();
-        assert!(matches!(
-            set.execute(&mut handler).await.unwrap_err(),
-            CmdError::Null
-        ));
+        assert_eq!(set.execute(&mut handler).await.unwrap(), Some(Resp3::Null));
+        assert!(
+            handler
+                .shared
+                .db()
+                .contains_object(&"key_never_exist".into())
+                .await
+        );
 
         /**********************************/
         /* 测试带有EX过期时间的键值对存取 */
@@ -1349,4 +1783,624 @@ mod cmd_str_tests {
                 < Duration::from_millis(10)
         );
     }
+
+    #[tokio::test]
+    async fn mget_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object(Key::from("key1"), ObjectInner::new_str("value1", None))
+            .await;
+        db.insert_object(Key::from("key2"), ObjectInner::new_str("value2", None))
+            .await;
+        db.insert_object(
+            Key::from("list"),
+            ObjectInner::new_list(crate::shared::db::List::default(), None),
+        )
+        .await;
+
+        // 不存在的键以及非字符串的键返回nil
+        let mget = MGet::parse(
+            &mut CmdUnparsed::from(["key2", "key_nil", "list", "key1"].as_ref()),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            mget.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("value2".into()),
+                Resp3::Null,
+                Resp3::Null,
+                Resp3::new_blob_string("value1".into()),
+            ])
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn msetnx_atomic_test() {
+        test_init();
+        let (handler, _) = Handler::new_fake();
+        let shared = handler.shared.clone();
+
+        // 每个任务使用不同的值，写入部分重叠的键
+        let key_sets: Vec<Vec<String>> = (0..64)
+            .map(|i| {
+                (0..4)
+                    .map(|j| format!("msetnx{}", (i + j * 3) % 16))
+                    .collect()
+            })
+            .collect();
+
+        let mut tasks = Vec::new();
+        for (i, keys) in key_sets.iter().cloned().enumerate() {
+            let shared = shared.clone();
+            tasks.push(tokio::spawn(async move {
+                let (mut handler, _) = Handler::with_shared(shared);
+                let value = format!("value{i}");
+                let args: Vec<&str> = keys
+                    .iter()
+                    .flat_map(|k| [k.as_str(), value.as_str()])
+                    .collect();
+                let msetnx =
+                    MSetNx::parse(&mut args.as_slice().into(), &AccessControl::new_loose())
+                        .unwrap();
+
+                match msetnx.execute(&mut handler).await {
+                    Ok(res) => res.unwrap() == Resp3::new_integer(1),
+                    Err(_) => false,
+                }
+            }));
+        }
+
+        let mut success = 0;
+        for (i, task) in tasks.into_iter().enumerate() {
+            let set = task.await.unwrap();
+            let value = format!("value{i}");
+
+            let mut count = 0;
+            for key in &key_sets[i] {
+                let obj = shared.db().get_object_entry(&Key::from(key.clone())).await;
+                if obj.is_some_and(|obj| {
+                    obj.on_str().unwrap().unwrap().to_bytes() == value.as_bytes()
+                }) {
+                    count += 1;
+                }
+            }
+
+            // 要么所有键都被设置，要么都没有被设置
+            if set {
+                success += 1;
+                assert_eq!(count, key_sets[i].len());
+            } else {
+                assert_eq!(count, 0);
+            }
+        }
+        assert!(success > 0);
+
+        // 失败的MSETNX不会写入任何键，也不会触发任何键的事件
+        let (tx, rx) = flume::unbounded();
+        let db = shared.db();
+        db.add_may_update_event(Key::from("msetnx_new"), tx).await;
+        db.insert_object(Key::from("msetnx_old"), ObjectInner::new_str("v", None))
+            .await;
+        let (mut handler, _) = Handler::with_shared(shared.clone());
+        let msetnx = MSetNx::parse(
+            &mut ["msetnx_new", "v", "msetnx_old", "v"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert!(msetnx.execute(&mut handler).await.is_err());
+        assert!(rx.try_recv().is_err());
+        assert!(!db.contains_object(&Key::from("msetnx_new")).await);
+
+        let msetnx = MSetNx::parse(
+            &mut ["msetnx_new", "v1", "msetnx_new2", "v2", "msetnx_new", "v3"]
+                .as_ref()
+                .into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            msetnx.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_integer(1)
+        );
+        assert_eq!(rx.try_recv().unwrap(), Key::from("msetnx_new"));
+        let res = db
+            .multi_get(
+                &[Key::from("msetnx_new"), Key::from("msetnx_new2")],
+                |obj| obj.on_str().unwrap().to_bytes(),
+            )
+            .await;
+        assert_eq!(res, [Some("v3".into()), Some("v2".into())]);
+        assert_eq!(db.size(), db.len_nonexpired());
+    }
+
+    #[tokio::test]
+    async fn psetex_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        // 非正数的过期时间
+        for expire in ["0", "-1"] {
+            assert!(PSetEx::parse(
+                &mut ["key", expire, "value"].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .is_err());
+            assert!(SetEx::parse(
+                &mut ["key", expire, "value"].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .is_err());
+        }
+
+        let psetex = PSetEx::parse(
+            &mut ["key", "1500", "value"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            psetex.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_simple_string("OK".into())
+        );
+
+        let pttl = Pttl::parse(&mut ["key"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        let pttl = pttl
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap()
+            .try_integer()
+            .unwrap();
+        assert!(pttl > 1400 && pttl <= 1500);
+
+        let get = Get::parse(&mut ["key"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            get.execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_blob()
+                .unwrap(),
+            b"value".as_ref()
+        );
+    }
+
+    #[tokio::test]
+    async fn ttl_jitter_test() {
+        use crate::{
+            conf::{Conf, ServerConf},
+            shared::{db::Db, Shared},
+        };
+        use async_shutdown::ShutdownManager;
+        use std::sync::Arc;
+
+        test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                ttl_jitter_percent: 10,
+                ..Default::default()
+            },
+            rdb: None,
+            aof: None,
+            ..Default::default()
+        };
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            ShutdownManager::new(),
+        );
+        let (mut handler, _) = Handler::with_shared(shared);
+
+        let now = Instant::now();
+        let mut expires = Vec::new();
+        for key in ["key1", "key2", "key3"] {
+            let set = Set::parse(
+                &mut [key, "value", "EX", "1000"].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .unwrap();
+            set.execute(&mut handler).await.unwrap();
+
+            let expire = handler
+                .shared
+                .db()
+                .get_object_entry(&key.into())
+                .await
+                .unwrap()
+                .value()
+                .inner()
+                .unwrap()
+                .expire()
+                .unwrap();
+
+            // 抖动范围为±10%，且不会早于当前时间
+            assert!(expire > now + Duration::from_secs(899));
+            assert!(expire < now + Duration::from_secs(1101));
+            expires.push(expire);
+        }
+
+        // 相同的TTL得到不同的过期时间
+        assert!(expires.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[tokio::test]
+    async fn int_encoding_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn encoding(handler: &mut Handler<impl AsyncStream>, key: &str) -> Resp3 {
+            ObjectEncoding::parse(&mut [key].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        let set = Set::parse(
+            &mut ["k", "10"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        set.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler, "k").await.try_blob().unwrap(),
+            b"int".as_ref()
+        );
+
+        // INCR/DECR后仍为int编码
+        let incr = Incr::parse(&mut ["k"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            incr.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_integer(11)
+        );
+        let decr_by = DecrBy::parse(
+            &mut ["k", "5000000000"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            decr_by.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_integer(-4999999989)
+        );
+        assert_eq!(
+            encoding(&mut handler, "k").await.try_blob().unwrap(),
+            b"int".as_ref()
+        );
+
+        // 追加非数字后变为embstr编码
+        let append = Append::parse(
+            &mut ["k", "abc"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        append.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler, "k").await.try_blob().unwrap(),
+            b"embstr".as_ref()
+        );
+
+        let get = Get::parse(&mut ["k"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            get.execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_blob()
+                .unwrap(),
+            b"-4999999989abc".as_ref()
+        );
+
+        // 非规范形式的整数不使用int编码
+        let set = Set::parse(
+            &mut ["k2", "010"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        set.execute(&mut handler).await.unwrap();
+        assert_eq!(
+            encoding(&mut handler, "k2").await.try_blob().unwrap(),
+            b"embstr".as_ref()
+        );
+    }
+
+    #[tokio::test]
+    async fn str_encoding_test() {
+        use crate::shared::db::EMBSTR_SIZE_LIMIT;
+
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn encoding(handler: &mut Handler<impl AsyncStream>, key: &str) -> Resp3 {
+            ObjectEncoding::parse(&mut [key].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        let embstr = "a".repeat(EMBSTR_SIZE_LIMIT);
+        let raw = "a".repeat(EMBSTR_SIZE_LIMIT + 1);
+        for (key, value, expected) in [
+            ("int", "12345", "int"),
+            ("embstr", embstr.as_str(), "embstr"),
+            ("raw", raw.as_str(), "raw"),
+        ] {
+            Set::parse(
+                &mut [key, value].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap();
+            assert_eq!(
+                encoding(&mut handler, key).await.try_blob().unwrap(),
+                expected.as_bytes()
+            );
+        }
+
+        // 追加后超过长度限制则变为raw编码
+        Append::parse(
+            &mut ["embstr", "a"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap()
+        .execute(&mut handler)
+        .await
+        .unwrap();
+        assert_eq!(
+            encoding(&mut handler, "embstr").await.try_blob().unwrap(),
+            b"raw".as_ref()
+        );
+    }
+
+    #[tokio::test]
+    async fn incr_overflow_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let max = Int::MAX.to_string();
+        let min = Int::MIN.to_string();
+        for (key, value) in [("max", max.as_str()), ("min", min.as_str())] {
+            Set::parse(
+                &mut [key, value].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        }
+
+        let overflow = "ERR increment or decrement would overflow";
+        let incr = Incr::parse(&mut ["max"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            incr.execute(&mut handler).await.unwrap_err().to_string(),
+            overflow
+        );
+        let incr_by = IncrBy::parse(
+            &mut ["min", "-1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            incr_by.execute(&mut handler).await.unwrap_err().to_string(),
+            overflow
+        );
+        let decr = Decr::parse(&mut ["min"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            decr.execute(&mut handler).await.unwrap_err().to_string(),
+            overflow
+        );
+        let decr_by = DecrBy::parse(
+            &mut ["max", &min].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            decr_by.execute(&mut handler).await.unwrap_err().to_string(),
+            overflow
+        );
+
+        // 溢出时原值保持不变
+        for (key, value) in [("max", max.as_str()), ("min", min.as_str())] {
+            let get = Get::parse(&mut [key].as_ref().into(), &AccessControl::new_loose()).unwrap();
+            assert_eq!(
+                get.execute(&mut handler)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .try_blob()
+                    .unwrap(),
+                value.as_bytes()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn getset_and_set_get_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        // 没有过期时间时返回None
+        async fn pttl(handler: &mut Handler<FakeStream>, key: &str) -> Option<Int> {
+            Pttl::parse(&mut [key].as_ref().into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .ok()
+                .map(|res| res.unwrap().try_integer().unwrap())
+        }
+
+        // 键不存在时返回Null，并设置值
+        let getset = GetSet::parse(
+            &mut ["getset", "v1"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            getset.execute(&mut handler).await.unwrap(),
+            Some(Resp3::Null)
+        );
+        let set = Set::parse(
+            &mut ["setget", "v1", "GET"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(set.execute(&mut handler).await.unwrap(), Some(Resp3::Null));
+
+        for key in ["getset", "setget"] {
+            PSetEx::parse(
+                &mut [key, "100000", "v2"].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        }
+
+        // GETSET返回旧值，并清除过期时间
+        let getset = GetSet::parse(
+            &mut ["getset", "v3"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            getset.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_blob_string("v2".into())
+        );
+        assert_eq!(pttl(&mut handler, "getset").await, None);
+
+        // SET GET KEEPTTL返回旧值，并保留过期时间
+        let set = Set::parse(
+            &mut ["setget", "v3", "GET", "KEEPTTL"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            set.execute(&mut handler).await.unwrap().unwrap(),
+            Resp3::new_blob_string("v2".into())
+        );
+        assert!(pttl(&mut handler, "setget").await.unwrap() > 0);
+
+        // 旧值不是字符串时返回WRONGTYPE，且不修改键
+        handler
+            .shared
+            .db()
+            .insert_object("list".into(), ObjectInner::new_list(List::default(), None))
+            .await;
+        let getset = GetSet::parse(
+            &mut ["list", "v"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert!(getset.execute(&mut handler).await.is_err());
+        let set = Set::parse(
+            &mut ["list", "v", "GET"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert!(set.execute(&mut handler).await.is_err());
+        handler
+            .shared
+            .db()
+            .visit_object(&"list".into(), |obj| {
+                obj.on_list()?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn bitfield_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        async fn bitfield(handler: &mut Handler<FakeStream>, args: &[&str]) -> Vec<Resp3> {
+            BitField::parse(&mut args.into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap()
+                .try_array()
+                .unwrap()
+                .clone()
+        }
+
+        // 键不存在时只读不创建键
+        assert_eq!(
+            bitfield(&mut handler, &["bf", "GET", "u8", "0"]).await,
+            vec![Resp3::new_integer(0)]
+        );
+        assert!(!handler.shared.db().contains_object(&"bf".into()).await);
+
+        // 无符号SET返回旧值，#n表示类型宽度的倍数
+        assert_eq!(
+            bitfield(
+                &mut handler,
+                &["bf", "SET", "u8", "0", "255", "SET", "u4", "#2", "10", "GET", "u8", "0"]
+            )
+            .await,
+            vec![
+                Resp3::new_integer(0),
+                Resp3::new_integer(0),
+                Resp3::new_integer(255)
+            ]
+        );
+        assert_eq!(
+            bitfield(&mut handler, &["bf", "GET", "u4", "8", "GET", "i8", "0"]).await,
+            vec![Resp3::new_integer(10), Resp3::new_integer(-1)]
+        );
+
+        // 默认WRAP
+        assert_eq!(
+            bitfield(&mut handler, &["bf", "INCRBY", "u8", "0", "1"]).await,
+            vec![Resp3::new_integer(0)]
+        );
+        assert_eq!(
+            bitfield(&mut handler, &["bf", "INCRBY", "i8", "0", "-129"]).await,
+            vec![Resp3::new_integer(127)]
+        );
+
+        // SAT
+        assert_eq!(
+            bitfield(
+                &mut handler,
+                &["bf", "OVERFLOW", "SAT", "INCRBY", "i8", "0", "100", "INCRBY", "u2", "0", "-10"]
+            )
+            .await,
+            vec![Resp3::new_integer(127), Resp3::new_integer(0)]
+        );
+
+        // FAIL时不修改值并返回Null
+        assert_eq!(
+            bitfield(
+                &mut handler,
+                &["bf", "OVERFLOW", "FAIL", "INCRBY", "u8", "0", "1000", "GET", "u8", "0"]
+            )
+            .await,
+            vec![Resp3::Null, Resp3::new_integer(0x3f)]
+        );
+
+        // 非法参数
+        for args in [
+            ["bf", "GET", "u64", "0"],
+            ["bf", "GET", "i65", "0"],
+            ["bf", "GET", "x8", "0"],
+            ["bf", "GET", "u8", "-1"],
+            ["bf", "OVERFLOW", "FOO", "GET"],
+        ] {
+            assert!(
+                BitField::parse(&mut args.as_ref().into(), &AccessControl::new_loose()).is_err()
+            );
+        }
+    }
 }
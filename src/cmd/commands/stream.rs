@@ -0,0 +1,2045 @@
+// XAck
+// XAdd
+// XClaim
+// XGroupCreate
+// XGroupCreateConsumer
+// XGroupDestroy
+// XLen
+// XPending
+// XRange
+// XRead
+// XReadGroup
+// XRevRange
+
+use super::*;
+use crate::{
+    cmd::{CmdError, CmdExecutor, CmdType, CmdUnparsed, Err},
+    conf::AccessControl,
+    connection::AsyncStream,
+    frame::Resp3,
+    server::Handler,
+    shared::{
+        db::{ObjValueType, PendingEntry, Stream, StreamFields, StreamId},
+        Shared,
+    },
+    util::{self, atoi},
+    CmdFlag, Int, Key,
+};
+use bytes::Bytes;
+use flume::{Receiver, Sender};
+use std::{
+    collections::BTreeMap,
+    ops::Bound,
+    time::{Duration, SystemTime},
+};
+use tokio::time::Instant;
+use tracing::instrument;
+
+/// 向流中添加条目，ID为`*`时自动生成，为`<ms>-*`时自动生成序号。
+/// NOMKSTREAM表示流不存在时不创建；MAXLEN和MINID在添加后裁剪流，`~`(近似裁剪)
+/// 与`=`等价，LIMIT限制裁剪的条目数量
+/// # Reply:
+///
+/// **Bulk string reply:** The ID of the added entry.
+/// **Null reply:** if the NOMKSTREAM option is given and the key doesn't exist.
+#[derive(Debug)]
+pub struct XAdd {
+    pub key: Key,
+    pub no_mk_stream: bool,
+    pub trim: Option<XTrim>,
+    pub id: XAddId,
+    pub fields: StreamFields,
+}
+
+#[derive(Debug)]
+pub enum XAddId {
+    Auto,
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+#[derive(Debug)]
+pub struct XTrim {
+    pub strategy: XTrimStrategy,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum XTrimStrategy {
+    MaxLen(usize),
+    MinId(StreamId),
+}
+
+impl CmdExecutor for XAdd {
+    const NAME: &'static str = "XADD";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XADD_FLAG;
+    const ARITY: Int = -5;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut new_id = StreamId::MIN;
+
+        let f = |stream: &mut Stream| -> Result<(), CmdError> {
+            let id = match self.id {
+                XAddId::Auto => stream.next_auto_id(now_millis()),
+                XAddId::AutoSeq(ms) => stream.next_id_with_ms(ms),
+                XAddId::Explicit(id) => Some(id),
+            }
+            .filter(|id| *id > stream.last_id())
+            .ok_or(
+                "ERR The ID specified in XADD is equal or smaller than the target stream top item",
+            )?;
+
+            stream.insert(id, self.fields);
+            if let Some(trim) = &self.trim {
+                trim.apply(stream);
+            }
+            new_id = id;
+            Ok(())
+        };
+
+        let db = handler.shared.db();
+        if self.no_mk_stream {
+            match db
+                .update_object(&self.key, |obj| f(obj.on_stream_mut()?))
+                .await
+            {
+                Ok(()) => {}
+                Err(CmdError::Null) => return Ok(Some(Resp3::Null)),
+                Err(e) => return Err(e),
+            }
+        } else {
+            db.update_or_create_object(&self.key, ObjValueType::Stream, |obj| {
+                f(obj.on_stream_mut()?)
+            })
+            .await?;
+        }
+
+        Ok(Some(Resp3::new_blob_string(new_id.to_bytes())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let mut no_mk_stream = false;
+        let mut trim = None;
+        let id = loop {
+            let arg = args.next().ok_or(Err::WrongArgNum)?;
+            let mut buf = [0; 10];
+            let len = util::uppercase(&arg, &mut buf).unwrap_or(0);
+
+            match &buf[..len] {
+                b"NOMKSTREAM" => no_mk_stream = true,
+                b"MAXLEN" | b"MINID" => trim = Some(XTrim::parse(&buf[..len], args)?),
+                _ => break arg,
+            }
+        };
+
+        let id = if id.as_ref() == b"*" {
+            XAddId::Auto
+        } else if let Some(ms) = id.strip_suffix(b"-*") {
+            XAddId::AutoSeq(atoi::<u64>(ms).map_err(|_| invalid_id())?)
+        } else {
+            let id = parse_id(&id, 0)?;
+            if id == StreamId::MIN {
+                return Err("ERR The ID specified in XADD must be greater than 0-0".into());
+            }
+            XAddId::Explicit(id)
+        };
+
+        let fields: Vec<Bytes> = args.collect();
+        if fields.is_empty() || !fields.len().is_multiple_of(2) {
+            return Err(Err::WrongArgNum.into());
+        }
+        let fields = fields
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+
+        Ok(XAdd {
+            key,
+            no_mk_stream,
+            trim,
+            id,
+            fields,
+        })
+    }
+}
+
+impl XTrim {
+    /// strategy为已经转换为大写的MAXLEN或MINID
+    fn parse(strategy: &[u8], args: &mut CmdUnparsed) -> Result<Self, CmdError> {
+        let mut threshold = args.next().ok_or(Err::Syntax)?;
+        let mut approx = false;
+        if threshold.as_ref() == b"=" || threshold.as_ref() == b"~" {
+            approx = threshold.as_ref() == b"~";
+            threshold = args.next().ok_or(Err::Syntax)?;
+        }
+
+        let strategy = if strategy == b"MAXLEN" {
+            let max_len = atoi::<i64>(&threshold).map_err(|_| Err::A2IParse)?;
+            if max_len < 0 {
+                return Err("ERR The MAXLEN argument must be >= 0.".into());
+            }
+            XTrimStrategy::MaxLen(max_len as usize)
+        } else {
+            XTrimStrategy::MinId(parse_id(&threshold, 0)?)
+        };
+
+        let mut limit = None;
+        if !args.is_empty()
+            && args
+                .iter()
+                .next()
+                .is_some_and(|arg| arg.eq_ignore_ascii_case(b"LIMIT"))
+        {
+            args.next();
+            if !approx {
+                return Err(
+                    "ERR syntax error, LIMIT cannot be used without the special ~ option".into(),
+                );
+            }
+            let n = atoi::<i64>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+            if n < 0 {
+                return Err("ERR The LIMIT argument must be >= 0.".into());
+            }
+            // LIMIT 0表示不限制
+            limit = (n > 0).then_some(n as usize);
+        }
+
+        Ok(XTrim { strategy, limit })
+    }
+
+    fn apply(&self, stream: &mut Stream) -> usize {
+        match self.strategy {
+            XTrimStrategy::MaxLen(max_len) => stream.trim_max_len(max_len, self.limit),
+            XTrimStrategy::MinId(min_id) => stream.trim_min_id(min_id, self.limit),
+        }
+    }
+}
+
+/// 返回流中的条目数量
+/// # Reply:
+///
+/// **Integer reply:** the number of entries of the stream at key.
+#[derive(Debug)]
+pub struct XLen {
+    pub key: Key,
+}
+
+impl CmdExecutor for XLen {
+    const NAME: &'static str = "XLEN";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = XLEN_FLAG;
+    const ARITY: Int = 2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut len = 0;
+
+        let res = handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                len = obj.on_stream()?.len();
+                Ok(())
+            })
+            .await;
+        match res {
+            Ok(()) | Err(CmdError::Null) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Some(Resp3::new_integer(len as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 1 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XLen { key })
+    }
+}
+
+/// 按照ID从小到大返回[start, end]范围内的条目。`-`和`+`分别表示最小和最大的ID，
+/// 以`(`开头的ID表示不包含该ID
+/// # Reply:
+///
+/// **Array reply:** a list of stream entries with IDs matching the specified range.
+#[derive(Debug)]
+pub struct XRange {
+    pub key: Key,
+    // None表示范围为空
+    pub range: Option<(StreamId, StreamId)>,
+    pub count: Option<usize>,
+}
+
+impl CmdExecutor for XRange {
+    const NAME: &'static str = "XRANGE";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = XRANGE_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        range_entries(handler, &self.key, self.range, self.count, false).await
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (key, range, count) = parse_range(args, false)?;
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XRange { key, range, count })
+    }
+}
+
+/// 与XRANGE相同，但按照ID从大到小返回条目，并且参数的顺序为end start
+/// # Reply:
+///
+/// **Array reply:** The command returns the entries with IDs matching the specified range.
+/// The returned entries are complete, which means that the ID and all the fields they are
+/// composed of are returned. Moreover, the entries are returned with their fields and
+/// values in the same order as XADD added them.
+#[derive(Debug)]
+pub struct XRevRange {
+    pub key: Key,
+    // None表示范围为空
+    pub range: Option<(StreamId, StreamId)>,
+    pub count: Option<usize>,
+}
+
+impl CmdExecutor for XRevRange {
+    const NAME: &'static str = "XREVRANGE";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = XREVRANGE_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        range_entries(handler, &self.key, self.range, self.count, true).await
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (key, range, count) = parse_range(args, true)?;
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XRevRange { key, range, count })
+    }
+}
+
+/// 从一个或多个流中读取ID大于给定ID的条目，`$`表示流当前的最大ID。
+/// 指定BLOCK时，如果没有可读的条目则阻塞直到有新的条目或者超时(毫秒，0表示永不超时)
+/// # Reply:
+///
+/// **Map reply:** A map of key-value elements where each element is composed of the key
+/// name and the entries reported for that key. The entries reported are full stream entries,
+/// having IDs and the list of all the fields and values. Field and values are guaranteed to
+/// be reported in the same order they were added by XADD. (RESP2下为数组)
+/// **Null reply:** if the BLOCK option is given and a timeout occurs, or if there is no
+/// stream that can be served.
+#[derive(Debug)]
+pub struct XRead {
+    pub count: Option<usize>,
+    pub block: Option<u64>,
+    pub keys: Vec<Key>,
+    // None表示`$`
+    pub ids: Vec<Option<StreamId>>,
+}
+
+impl CmdExecutor for XRead {
+    const NAME: &'static str = "XREAD";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = XREAD_FLAG;
+    const ARITY: Int = -4;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let db = handler.shared.db();
+
+        // `$`在命令执行时解析为流当前的最大ID
+        let mut ids = Vec::with_capacity(self.ids.len());
+        for (key, id) in self.keys.iter().zip(self.ids) {
+            let id = match id {
+                Some(id) => id,
+                None => {
+                    let mut last_id = StreamId::MIN;
+                    let res = db
+                        .visit_object(key, |obj| {
+                            last_id = obj.on_stream()?.last_id();
+                            Ok(())
+                        })
+                        .await;
+                    if !matches!(res, Err(CmdError::Null)) {
+                        res?;
+                    }
+                    last_id
+                }
+            };
+            ids.push(id);
+        }
+
+        // 先加入监听事件再读取，避免读取之后、监听之前添加的条目被遗漏
+        let (key_tx, key_rx) = flume::unbounded();
+        if self.block.is_some() {
+            for key in &self.keys {
+                db.add_may_update_event(key.clone(), key_tx.clone()).await;
+            }
+        }
+
+        let mut res = read_streams(&handler.shared, &self.keys, &ids, self.count).await?;
+
+        if res.is_empty() {
+            let Some(block) = self.block else {
+                return Ok(Some(Resp3::Null));
+            };
+
+            let deadline = (block != 0).then(|| Instant::now() + Duration::from_millis(block));
+            let read = read_blocking(
+                &handler.shared,
+                &self.keys,
+                &ids,
+                self.count,
+                key_tx,
+                key_rx,
+            );
+            match handler.block_until(deadline, read).await {
+                Some(read) => res = read?,
+                // 超时或服务器关闭时返回Null
+                None => return Ok(Some(Resp3::Null)),
+            }
+        }
+
+        Ok(Some(streams_reply(handler.context.protocol_version, res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 3 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let mut count = None;
+        let mut block = None;
+        loop {
+            let arg = args.next().ok_or(Err::Syntax)?;
+            let mut buf = [0; 7];
+            let len = util::uppercase(&arg, &mut buf).map_err(|_| Err::Syntax)?;
+
+            match &buf[..len] {
+                b"COUNT" => {
+                    let n =
+                        atoi::<i64>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+                    // 与Redis一致，COUNT小于等于0时不限制数量
+                    count = (n > 0).then_some(n as usize);
+                }
+                b"BLOCK" => {
+                    let ms = atoi::<i64>(&args.next().ok_or(Err::Syntax)?)
+                        .map_err(|_| "ERR timeout is not an integer or out of range")?;
+                    if ms < 0 {
+                        return Err("ERR timeout is negative".into());
+                    }
+                    block = Some(ms as u64);
+                }
+                b"STREAMS" => break,
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        let mut rest: Vec<Bytes> = args.collect();
+        if rest.is_empty() || !rest.len().is_multiple_of(2) {
+            return Err("ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.".into());
+        }
+
+        let ids = rest
+            .split_off(rest.len() / 2)
+            .into_iter()
+            .map(|id| {
+                if id.as_ref() == b"$" {
+                    Ok(None)
+                } else {
+                    parse_id(&id, 0).map(Some)
+                }
+            })
+            .collect::<Result<_, CmdError>>()?;
+        let keys = rest;
+        if ac.is_forbidden_keys(&keys, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XRead {
+            count,
+            block,
+            keys,
+            ids,
+        })
+    }
+}
+
+/// 创建消费者组，ID为`$`表示只消费创建之后添加的条目。MKSTREAM表示流不存在时创建空的流
+/// # Reply:
+///
+/// **Simple string reply:** `OK`.
+#[derive(Debug)]
+pub struct XGroupCreate {
+    pub key: Key,
+    pub group: Bytes,
+    // None表示`$`
+    pub id: Option<StreamId>,
+    pub mk_stream: bool,
+}
+
+impl CmdExecutor for XGroupCreate {
+    const NAME: &'static str = "XGROUPCREATE";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XGROUP_CREATE_FLAG;
+    const ARITY: Int = -5;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let f = |stream: &mut Stream| -> Result<(), CmdError> {
+            let id = self.id.unwrap_or(stream.last_id());
+            if !stream.create_group(self.group, id) {
+                return Err("BUSYGROUP Consumer Group name already exists".into());
+            }
+            Ok(())
+        };
+
+        let db = handler.shared.db();
+        if self.mk_stream {
+            db.update_or_create_object(&self.key, ObjValueType::Stream, |obj| {
+                f(obj.on_stream_mut()?)
+            })
+            .await?;
+        } else {
+            db.update_object(&self.key, |obj| f(obj.on_stream_mut()?))
+                .await
+                .map_err(require_key)?;
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 3 && args.len() != 4 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let group = args.next().unwrap();
+        let id = args.next().unwrap();
+        let id = if id.as_ref() == b"$" {
+            None
+        } else {
+            Some(parse_id(&id, 0)?)
+        };
+
+        let mk_stream = match args.next() {
+            Some(arg) if arg.eq_ignore_ascii_case(b"MKSTREAM") => true,
+            Some(_) => return Err(Err::Syntax.into()),
+            None => false,
+        };
+
+        Ok(XGroupCreate {
+            key,
+            group,
+            id,
+            mk_stream,
+        })
+    }
+}
+
+/// 删除消费者组，组中的PEL和消费者也一并删除
+/// # Reply:
+///
+/// **Integer reply:** the number of destroyed consumer groups, either 0 or 1.
+#[derive(Debug)]
+pub struct XGroupDestroy {
+    pub key: Key,
+    pub group: Bytes,
+}
+
+impl CmdExecutor for XGroupDestroy {
+    const NAME: &'static str = "XGROUPDESTROY";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XGROUP_DESTROY_FLAG;
+    const ARITY: Int = 4;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut destroyed = false;
+
+        handler
+            .shared
+            .db()
+            .update_object(&self.key, |obj| {
+                destroyed = obj.on_stream_mut()?.destroy_group(&self.group);
+                Ok(())
+            })
+            .await
+            .map_err(require_key)?;
+
+        Ok(Some(Resp3::new_integer(destroyed as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XGroupDestroy {
+            key,
+            group: args.next().unwrap(),
+        })
+    }
+}
+
+/// 在消费者组中创建消费者
+/// # Reply:
+///
+/// **Integer reply:** the number of created consumers, either 0 or 1.
+#[derive(Debug)]
+pub struct XGroupCreateConsumer {
+    pub key: Key,
+    pub group: Bytes,
+    pub consumer: Bytes,
+}
+
+impl CmdExecutor for XGroupCreateConsumer {
+    const NAME: &'static str = "XGROUPCREATECONSUMER";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XGROUP_CREATECONSUMER_FLAG;
+    const ARITY: Int = 5;
+    const KEY_RANGE: (Int, Int, Int) = (2, 2, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut created = false;
+
+        handler
+            .shared
+            .db()
+            .update_object(&self.key, |obj| {
+                let group = obj
+                    .on_stream_mut()?
+                    .group_mut(&self.group)
+                    .ok_or_else(|| no_group(&self.key, &self.group))?;
+                created = group.create_consumer(self.consumer, now_millis());
+                Ok(())
+            })
+            .await
+            .map_err(require_key)?;
+
+        Ok(Some(Resp3::new_integer(created as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() != 3 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XGroupCreateConsumer {
+            key,
+            group: args.next().unwrap(),
+            consumer: args.next().unwrap(),
+        })
+    }
+}
+
+/// 以消费者组中某个消费者的身份读取流。ID为`>`时读取从未投递给该组的新条目，并记录到
+/// 消费者的PEL中(NOACK时不记录)；其它ID读取该消费者PEL中ID大于给定ID的条目，
+/// 已被删除的条目返回[id, Null]。只有读取新条目时BLOCK才会生效
+/// # Reply:
+///
+/// **Map reply:** A map of key-value elements where each element is composed of the key
+/// name and the entries reported for that key. The entries reported are full stream entries,
+/// having IDs and the list of all the fields and values. Field and values are guaranteed to
+/// be reported in the same order they were added by XADD. (RESP2下为数组)
+/// **Null reply:** if the BLOCK option is given and a timeout occurs, or if there is no
+/// stream that can be served.
+#[derive(Debug)]
+pub struct XReadGroup {
+    pub group: Bytes,
+    pub consumer: Bytes,
+    pub count: Option<usize>,
+    pub block: Option<u64>,
+    pub noack: bool,
+    pub keys: Vec<Key>,
+    // None表示`>`
+    pub ids: Vec<Option<StreamId>>,
+}
+
+impl CmdExecutor for XReadGroup {
+    const NAME: &'static str = "XREADGROUP";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XREADGROUP_FLAG;
+    const ARITY: Int = -7;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let may_block = self.block.is_some() && self.ids.iter().any(Option::is_none);
+
+        // 先加入监听事件再读取，避免读取之后、监听之前添加的条目被遗漏
+        let (key_tx, key_rx) = flume::unbounded();
+        if may_block {
+            for key in &self.keys {
+                handler
+                    .shared
+                    .db()
+                    .add_may_update_event(key.clone(), key_tx.clone())
+                    .await;
+            }
+        }
+
+        let mut res = self.read(&handler.shared).await?;
+
+        if res.is_empty() {
+            let Some(block) = self.block.filter(|_| may_block) else {
+                return Ok(Some(Resp3::Null));
+            };
+
+            let deadline = (block != 0).then(|| Instant::now() + Duration::from_millis(block));
+            // 阻塞期间不能阻止过期键的DEL命令的传播，因此先传播已缓存的写命令并释放hold
+            let shared = handler.shared.clone();
+            shared.wcmd_propagator().propagate_buffered(handler).await;
+
+            let read = self.read_blocking(&shared, &mut handler.context.wcmd_held, key_tx, key_rx);
+            match shared.block_until(deadline, read).await {
+                Some(read) => res = read?,
+                // 超时或服务器关闭时返回Null
+                None => return Ok(Some(Resp3::Null)),
+            }
+        }
+
+        Ok(Some(streams_reply(handler.context.protocol_version, res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 6 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        if !args.next().unwrap().eq_ignore_ascii_case(b"GROUP") {
+            return Err(Err::Syntax.into());
+        }
+        let group = args.next().unwrap();
+        let consumer = args.next().unwrap();
+
+        let mut count = None;
+        let mut block = None;
+        let mut noack = false;
+        loop {
+            let arg = args.next().ok_or(Err::Syntax)?;
+            let mut buf = [0; 7];
+            let len = util::uppercase(&arg, &mut buf).map_err(|_| Err::Syntax)?;
+
+            match &buf[..len] {
+                b"COUNT" => {
+                    let n =
+                        atoi::<i64>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+                    count = (n > 0).then_some(n as usize);
+                }
+                b"BLOCK" => {
+                    let ms = atoi::<i64>(&args.next().ok_or(Err::Syntax)?)
+                        .map_err(|_| "ERR timeout is not an integer or out of range")?;
+                    if ms < 0 {
+                        return Err("ERR timeout is negative".into());
+                    }
+                    block = Some(ms as u64);
+                }
+                b"NOACK" => noack = true,
+                b"STREAMS" => break,
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        let mut rest: Vec<Bytes> = args.collect();
+        if rest.is_empty() || !rest.len().is_multiple_of(2) {
+            return Err("ERR Unbalanced 'xreadgroup' list of streams: for each stream key an ID or '>' must be specified.".into());
+        }
+
+        let ids = rest
+            .split_off(rest.len() / 2)
+            .into_iter()
+            .map(|id| {
+                if id.as_ref() == b">" {
+                    Ok(None)
+                } else {
+                    parse_id(&id, 0).map(Some)
+                }
+            })
+            .collect::<Result<_, CmdError>>()?;
+        let keys = rest;
+        if ac.is_forbidden_keys(&keys, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(XReadGroup {
+            group,
+            consumer,
+            count,
+            block,
+            noack,
+            keys,
+            ids,
+        })
+    }
+}
+
+impl XReadGroup {
+    /// 读取每个流，读取新条目时忽略没有新条目的流
+    async fn read(&self, shared: &Shared) -> Result<Vec<(Key, Vec<Resp3>)>, CmdError> {
+        let now = now_millis();
+        let count = self.count.unwrap_or(usize::MAX);
+        let mut res = vec![];
+
+        for (key, id) in self.keys.iter().zip(&self.ids) {
+            let mut entries = vec![];
+            shared
+                .db()
+                .update_object(key, |obj| {
+                    let stream = obj.on_stream_mut()?;
+                    let Some(id) = id else {
+                        let delivered = stream
+                            .deliver_new(&self.group, &self.consumer, self.count, self.noack, now)
+                            .ok_or_else(|| no_group(key, &self.group))?;
+                        entries.extend(delivered.iter().map(|(id, f)| entry_reply(id, f)));
+                        return Ok(());
+                    };
+
+                    let pending: Vec<StreamId> = stream
+                        .group_mut(&self.group)
+                        .ok_or_else(|| no_group(key, &self.group))?
+                        .consumer_mut(&self.consumer, now)
+                        .pending
+                        .range((Bound::Excluded(*id), Bound::Unbounded))
+                        .take(count)
+                        .copied()
+                        .collect();
+                    entries.extend(pending.iter().map(|id| match stream.get(id) {
+                        Some(fields) => entry_reply(id, fields),
+                        None => Resp3::new_array(vec![
+                            Resp3::new_blob_string(id.to_bytes()),
+                            Resp3::Null,
+                        ]),
+                    }));
+                    Ok(())
+                })
+                .await
+                .map_err(|e| match e {
+                    CmdError::Null => no_group(key, &self.group),
+                    e => e,
+                })?;
+
+            // 读取历史条目时即使没有条目也返回该流
+            if id.is_some() || !entries.is_empty() {
+                res.push((key.clone(), entries));
+            }
+        }
+
+        Ok(res)
+    }
+
+    // 等待监听的流被更新，直到读取到新条目为止。超时由调用者控制。held为handler的
+    // `wcmd_held`，只在读取(更新流)时hold，见[`Propagator::hold_wcmd`]
+    async fn read_blocking(
+        &self,
+        shared: &Shared,
+        held: &mut bool,
+        key_tx: Sender<Key>,
+        key_rx: Receiver<Key>,
+    ) -> Result<Vec<(Key, Vec<Resp3>)>, CmdError> {
+        loop {
+            let Ok(key) = key_rx.recv_async().await else {
+                continue;
+            };
+            // 被唤醒时服务器可能处于暂停状态，等待暂停结束后才读取
+            shared.wait_unpaused().await;
+
+            // 读取会更新流，从而触发流上的事件，因此先以只读的方式检查是否有新条目，
+            // 避免被自己触发的事件反复唤醒
+            let mut has_new = false;
+            for key in &self.keys {
+                let visited = shared
+                    .db()
+                    .visit_object(key, |obj| {
+                        has_new |= obj.on_stream()?.has_new_for(&self.group);
+                        Ok(())
+                    })
+                    .await;
+                if !matches!(visited, Err(CmdError::Null)) {
+                    visited?;
+                }
+            }
+
+            if has_new {
+                shared.wcmd_propagator().hold_wcmd(held);
+                let res = self.read(shared).await?;
+                if !res.is_empty() {
+                    break Ok(res);
+                }
+
+                if std::mem::take(held) {
+                    shared.wcmd_propagator().release_wcmd();
+                }
+            }
+
+            // 事件触发后即被移除，需要重新加入
+            shared.db().add_may_update_event(key, key_tx.clone()).await;
+        }
+    }
+}
+
+/// 确认消费者组中的条目，将其从PEL中移除
+/// # Reply:
+///
+/// **Integer reply:** The command returns the number of messages successfully acknowledged.
+/// Certain message IDs may no longer be part of the PEL (for example because they have
+/// already been acknowledged), and XACK will not count them as successfully acknowledged.
+#[derive(Debug)]
+pub struct XAck {
+    pub key: Key,
+    pub group: Bytes,
+    pub ids: Vec<StreamId>,
+}
+
+impl CmdExecutor for XAck {
+    const NAME: &'static str = "XACK";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XACK_FLAG;
+    const ARITY: Int = -4;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut acked = 0;
+
+        let res = handler
+            .shared
+            .db()
+            .update_object(&self.key, |obj| {
+                if let Some(group) = obj.on_stream_mut()?.group_mut(&self.group) {
+                    acked = self.ids.iter().filter(|id| group.ack(id)).count();
+                }
+                Ok(())
+            })
+            .await;
+        match res {
+            Ok(()) | Err(CmdError::Null) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Some(Resp3::new_integer(acked as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 3 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        let group = args.next().unwrap();
+        let ids = args.map(|id| parse_id(&id, 0)).collect::<Result<_, _>>()?;
+
+        Ok(XAck { key, group, ids })
+    }
+}
+
+/// 查看消费者组的PEL。不带范围时返回摘要：条目数量、最小和最大的ID以及每个消费者的条目数量；
+/// 带范围时返回范围内的条目，可以通过IDLE和consumer过滤
+/// # Reply:
+///
+/// **Array reply:** different data depending on the way XPENDING is called. The summary form
+/// returns [count, smallest ID, greatest ID, [[consumer, count], ...]]; the extended form
+/// returns [[ID, consumer, idle time in milliseconds, delivery count], ...].
+#[derive(Debug)]
+pub struct XPending {
+    pub key: Key,
+    pub group: Bytes,
+    pub extended: Option<XPendingRange>,
+}
+
+#[derive(Debug)]
+pub struct XPendingRange {
+    pub min_idle: u64,
+    // None表示范围为空
+    pub range: Option<(StreamId, StreamId)>,
+    pub count: usize,
+    pub consumer: Option<Bytes>,
+}
+
+impl CmdExecutor for XPending {
+    const NAME: &'static str = "XPENDING";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = XPENDING_FLAG;
+    const ARITY: Int = -3;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let now = now_millis();
+        let mut res = Resp3::Null;
+
+        handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let pending = obj
+                    .on_stream()?
+                    .group(&self.group)
+                    .ok_or_else(|| no_group(&self.key, &self.group))?
+                    .pending();
+
+                let Some(extended) = &self.extended else {
+                    res = pending_summary(pending);
+                    return Ok(());
+                };
+
+                let mut entries = vec![];
+                if let Some((start, end)) = extended.range.filter(|(start, end)| start <= end) {
+                    entries.extend(
+                        pending
+                            .range(start..=end)
+                            .filter(|(_, e)| {
+                                extended.consumer.as_ref().is_none_or(|c| *c == e.consumer)
+                                    && now.saturating_sub(e.delivery_time) >= extended.min_idle
+                            })
+                            .take(extended.count)
+                            .map(|(id, e)| {
+                                Resp3::new_array(vec![
+                                    Resp3::new_blob_string(id.to_bytes()),
+                                    Resp3::new_blob_string(e.consumer.clone()),
+                                    Resp3::new_integer(now.saturating_sub(e.delivery_time) as Int),
+                                    Resp3::new_integer(e.delivery_count as Int),
+                                ])
+                            }),
+                    );
+                }
+                res = Resp3::new_array(entries);
+                Ok(())
+            })
+            .await
+            .map_err(|e| match e {
+                CmdError::Null => no_group(&self.key, &self.group),
+                e => e,
+            })?;
+
+        Ok(Some(res))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 2 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+        let group = args.next().unwrap();
+
+        if args.is_empty() {
+            return Ok(XPending {
+                key,
+                group,
+                extended: None,
+            });
+        }
+
+        let mut min_idle = 0;
+        let mut start = args.next().unwrap();
+        if start.eq_ignore_ascii_case(b"IDLE") {
+            let idle = atoi::<i64>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+            min_idle = idle.max(0) as u64;
+            start = args.next().ok_or(Err::Syntax)?;
+        }
+        let end = args.next().ok_or(Err::Syntax)?;
+        let count = atoi::<i64>(&args.next().ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+        let consumer = args.next();
+        if !args.is_empty() {
+            return Err(Err::Syntax.into());
+        }
+
+        let start = parse_range_bound(&start, true)?;
+        let end = parse_range_bound(&end, false)?;
+
+        Ok(XPending {
+            key,
+            group,
+            extended: Some(XPendingRange {
+                min_idle,
+                range: start.zip(end),
+                count: count.max(0) as usize,
+                consumer,
+            }),
+        })
+    }
+}
+
+/// 将空闲时间不小于min-idle的待确认条目转移给指定的消费者，并增加其投递次数(JUSTID时不增加)。
+/// IDLE和TIME设置条目的投递时间，RETRYCOUNT设置投递次数，FORCE表示条目不在PEL中时也加入PEL，
+/// LASTID在大于组的last_delivered时更新它。已被删除的条目会从PEL中移除
+/// # Reply:
+///
+/// **Array reply:** when the JUSTID option is specified, an array of IDs of messages
+/// successfully claimed. Otherwise an array of stream entries, each entry is an array of
+/// two elements: the entry ID and its fields.
+#[derive(Debug)]
+pub struct XClaim {
+    pub key: Key,
+    pub group: Bytes,
+    pub consumer: Bytes,
+    pub min_idle: u64,
+    pub ids: Vec<StreamId>,
+    pub idle: Option<u64>,
+    pub time: Option<u64>,
+    pub retry_count: Option<u64>,
+    pub force: bool,
+    pub just_id: bool,
+    pub last_id: Option<StreamId>,
+}
+
+impl CmdExecutor for XClaim {
+    const NAME: &'static str = "XCLAIM";
+    const TYPE: CmdType = CmdType::Write;
+    const FLAG: CmdFlag = XCLAIM_FLAG;
+    const ARITY: Int = -6;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let now = now_millis();
+        let delivery_time = self
+            .time
+            .or(self.idle.map(|idle| now.saturating_sub(idle)))
+            .unwrap_or(now);
+        let mut res = vec![];
+
+        handler
+            .shared
+            .db()
+            .update_object(&self.key, |obj| {
+                let stream = obj.on_stream_mut()?;
+                let exists: Vec<bool> =
+                    self.ids.iter().map(|id| stream.get(id).is_some()).collect();
+
+                let group = stream
+                    .group_mut(&self.group)
+                    .ok_or_else(|| no_group(&self.key, &self.group))?;
+                if let Some(last_id) = self.last_id {
+                    group.last_delivered = group.last_delivered.max(last_id);
+                }
+                group.consumer_mut(&self.consumer, now);
+
+                let mut claimed = vec![];
+                for (id, exists) in self.ids.iter().zip(exists) {
+                    let entry = group.pending().get(id);
+                    // 条目已被删除
+                    if !exists {
+                        if entry.is_some() {
+                            group.ack(id);
+                        }
+                        continue;
+                    }
+
+                    let delivery_count = match entry {
+                        Some(entry) if now.saturating_sub(entry.delivery_time) < self.min_idle => {
+                            continue
+                        }
+                        Some(entry) => entry.delivery_count,
+                        None if self.force => 0,
+                        None => continue,
+                    };
+                    let delivery_count = self.retry_count.unwrap_or(if self.just_id {
+                        delivery_count
+                    } else {
+                        delivery_count + 1
+                    });
+
+                    group.add_pending(*id, self.consumer.clone(), now, delivery_count);
+                    if let Some(entry) = group.pending_mut(id) {
+                        entry.delivery_time = delivery_time;
+                    }
+                    claimed.push(*id);
+                }
+
+                res.extend(claimed.iter().map(|id| {
+                    if self.just_id {
+                        Resp3::new_blob_string(id.to_bytes())
+                    } else {
+                        // claimed中的条目都存在
+                        entry_reply(id, stream.get(id).unwrap())
+                    }
+                }));
+                Ok(())
+            })
+            .await
+            .map_err(|e| match e {
+                CmdError::Null => no_group(&self.key, &self.group),
+                e => e,
+            })?;
+
+        Ok(Some(Resp3::new_array(res)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.len() < 5 {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let key = args.next().unwrap();
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+        let group = args.next().unwrap();
+        let consumer = args.next().unwrap();
+        let min_idle = atoi::<i64>(&args.next().unwrap())
+            .map_err(|_| "ERR Invalid min-idle-time argument for XCLAIM")?
+            .max(0) as u64;
+
+        let mut claim = XClaim {
+            key,
+            group,
+            consumer,
+            min_idle,
+            ids: vec![],
+            idle: None,
+            time: None,
+            retry_count: None,
+            force: false,
+            just_id: false,
+            last_id: None,
+        };
+
+        let parse_u64 = |arg: Option<Bytes>| -> Result<u64, CmdError> {
+            let n = atoi::<i64>(&arg.ok_or(Err::Syntax)?).map_err(|_| Err::A2IParse)?;
+            Ok(n.max(0) as u64)
+        };
+
+        // ID在选项之前，遇到第一个无法解析为ID的参数时开始解析选项
+        let mut in_options = false;
+        while let Some(arg) = args.next() {
+            if !in_options {
+                if let Ok(id) = parse_id(&arg, 0) {
+                    claim.ids.push(id);
+                    continue;
+                }
+                in_options = true;
+            }
+
+            let mut buf = [0; 10];
+            let len = util::uppercase(&arg, &mut buf).map_err(|_| Err::Syntax)?;
+            match &buf[..len] {
+                b"IDLE" => claim.idle = Some(parse_u64(args.next())?),
+                b"TIME" => claim.time = Some(parse_u64(args.next())?),
+                b"RETRYCOUNT" => claim.retry_count = Some(parse_u64(args.next())?),
+                b"FORCE" => claim.force = true,
+                b"JUSTID" => claim.just_id = true,
+                b"LASTID" => claim.last_id = Some(parse_id(&args.next().ok_or(Err::Syntax)?, 0)?),
+                _ => return Err(Err::Syntax.into()),
+            }
+        }
+
+        if claim.ids.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(claim)
+    }
+}
+
+#[inline]
+fn invalid_id() -> CmdError {
+    "ERR Invalid stream ID specified as stream command argument".into()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// 解析`<ms>-<seq>`或者`<ms>`形式的ID，后者的序号为default_seq
+fn parse_id(id: &[u8], default_seq: u64) -> Result<StreamId, CmdError> {
+    let (ms, seq) = match id.iter().position(|&b| b == b'-') {
+        Some(i) => (
+            atoi::<u64>(&id[..i]).map_err(|_| invalid_id())?,
+            atoi::<u64>(&id[i + 1..]).map_err(|_| invalid_id())?,
+        ),
+        None => (atoi::<u64>(id).map_err(|_| invalid_id())?, default_seq),
+    };
+
+    Ok(StreamId::new(ms, seq))
+}
+
+/// 解析范围的一端，返回包含在范围内的ID。不包含的ID溢出时返回None，表示范围为空
+fn parse_range_bound(bound: &[u8], is_start: bool) -> Result<Option<StreamId>, CmdError> {
+    match bound {
+        b"-" => Ok(Some(StreamId::MIN)),
+        b"+" => Ok(Some(StreamId::MAX)),
+        _ => {
+            let default_seq = if is_start { 0 } else { u64::MAX };
+            match bound.strip_prefix(b"(") {
+                Some(id) if is_start => Ok(parse_id(id, default_seq)?.next()),
+                Some(id) => Ok(parse_id(id, default_seq)?.prev()),
+                None => parse_id(bound, default_seq).map(Some),
+            }
+        }
+    }
+}
+
+type RangeArgs = (Key, Option<(StreamId, StreamId)>, Option<usize>);
+
+/// 解析`key start end [COUNT count]`，rev为true时参数顺序为`key end start`
+fn parse_range(args: &mut CmdUnparsed, rev: bool) -> Result<RangeArgs, CmdError> {
+    if args.len() != 3 && args.len() != 5 {
+        return Err(Err::WrongArgNum.into());
+    }
+
+    let key = args.next().unwrap();
+    let (mut start, mut end) = (args.next().unwrap(), args.next().unwrap());
+    if rev {
+        std::mem::swap(&mut start, &mut end);
+    }
+
+    let mut count = None;
+    if let Some(arg) = args.next() {
+        if !arg.eq_ignore_ascii_case(b"COUNT") {
+            return Err(Err::Syntax.into());
+        }
+        let n = atoi::<i64>(&args.next().unwrap()).map_err(|_| Err::A2IParse)?;
+        count = Some(n.max(0) as usize);
+    }
+
+    let start = parse_range_bound(&start, true)?;
+    let end = parse_range_bound(&end, false)?;
+    Ok((key, start.zip(end), count))
+}
+
+fn require_key(e: CmdError) -> CmdError {
+    match e {
+        CmdError::Null => "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.".into(),
+        e => e,
+    }
+}
+
+fn no_group(key: &Key, group: &[u8]) -> CmdError {
+    format!(
+        "NOGROUP No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(group)
+    )
+    .into()
+}
+
+fn entry_reply(id: &StreamId, fields: &StreamFields) -> Resp3 {
+    Resp3::new_array(vec![
+        Resp3::new_blob_string(id.to_bytes()),
+        Resp3::new_array(
+            fields
+                .iter()
+                .flat_map(|(f, v)| {
+                    [
+                        Resp3::new_blob_string(f.clone()),
+                        Resp3::new_blob_string(v.clone()),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+/// XREAD和XREADGROUP的回复，RESP3下为map，RESP2下为[key, entries]组成的数组
+fn streams_reply(protocol_version: u8, res: Vec<(Key, Vec<Resp3>)>) -> Resp3 {
+    if protocol_version == 3 {
+        Resp3::new_map(
+            res.into_iter()
+                .map(|(key, entries)| (Resp3::new_blob_string(key), Resp3::new_array(entries)))
+                .collect::<ahash::AHashMap<_, _>>(),
+        )
+    } else {
+        Resp3::new_array(
+            res.into_iter()
+                .map(|(key, entries)| {
+                    Resp3::new_array(vec![Resp3::new_blob_string(key), Resp3::new_array(entries)])
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+fn pending_summary(pending: &BTreeMap<StreamId, PendingEntry>) -> Resp3 {
+    let (Some((min, _)), Some((max, _))) = (pending.first_key_value(), pending.last_key_value())
+    else {
+        return Resp3::new_array(vec![
+            Resp3::new_integer(0),
+            Resp3::Null,
+            Resp3::Null,
+            Resp3::Null,
+        ]);
+    };
+
+    let mut consumers: BTreeMap<&Bytes, usize> = BTreeMap::new();
+    for entry in pending.values() {
+        *consumers.entry(&entry.consumer).or_default() += 1;
+    }
+
+    Resp3::new_array(vec![
+        Resp3::new_integer(pending.len() as Int),
+        Resp3::new_blob_string(min.to_bytes()),
+        Resp3::new_blob_string(max.to_bytes()),
+        Resp3::new_array(
+            consumers
+                .into_iter()
+                .map(|(consumer, count)| {
+                    Resp3::new_array(vec![
+                        Resp3::new_blob_string(consumer.clone()),
+                        Resp3::new_blob_string(count.to_string().into()),
+                    ])
+                })
+                .collect::<Vec<_>>(),
+        ),
+    ])
+}
+
+async fn range_entries(
+    handler: &mut Handler<impl AsyncStream>,
+    key: &Key,
+    range: Option<(StreamId, StreamId)>,
+    count: Option<usize>,
+    rev: bool,
+) -> Result<Option<Resp3>, CmdError> {
+    let mut res = vec![];
+
+    // BTreeMap::range要求start <= end
+    let Some((start, end)) = range.filter(|(start, end)| start <= end) else {
+        return Ok(Some(Resp3::new_array(res)));
+    };
+    let count = count.unwrap_or(usize::MAX);
+
+    let visited = handler
+        .shared
+        .db()
+        .visit_object(key, |obj| {
+            let entries = obj.on_stream()?.range(start..=end);
+            if rev {
+                res.extend(entries.rev().take(count).map(|(id, f)| entry_reply(id, f)));
+            } else {
+                res.extend(entries.take(count).map(|(id, f)| entry_reply(id, f)));
+            }
+            Ok(())
+        })
+        .await;
+    match visited {
+        Ok(()) | Err(CmdError::Null) => {}
+        Err(e) => return Err(e),
+    }
+
+    Ok(Some(Resp3::new_array(res)))
+}
+
+/// 读取每个流中ID大于对应ID的条目，忽略不存在或者没有新条目的流
+async fn read_streams(
+    shared: &Shared,
+    keys: &[Key],
+    ids: &[StreamId],
+    count: Option<usize>,
+) -> Result<Vec<(Key, Vec<Resp3>)>, CmdError> {
+    let mut res = vec![];
+
+    for (key, id) in keys.iter().zip(ids) {
+        let mut entries = vec![];
+        let visited = shared
+            .db()
+            .visit_object(key, |obj| {
+                entries.extend(
+                    obj.on_stream()?
+                        .range((Bound::Excluded(*id), Bound::Unbounded))
+                        .take(count.unwrap_or(usize::MAX))
+                        .map(|(id, f)| entry_reply(id, f)),
+                );
+                Ok(())
+            })
+            .await;
+        if !matches!(visited, Err(CmdError::Null)) {
+            visited?;
+        }
+
+        if !entries.is_empty() {
+            res.push((key.clone(), entries));
+        }
+    }
+
+    Ok(res)
+}
+
+// 等待监听的流被更新，并尝试读取新的条目，直到读取成功为止。超时由调用者控制
+async fn read_blocking(
+    shared: &Shared,
+    keys: &[Key],
+    ids: &[StreamId],
+    count: Option<usize>,
+    key_tx: Sender<Key>,
+    key_rx: Receiver<Key>,
+) -> Result<Vec<(Key, Vec<Resp3>)>, CmdError> {
+    loop {
+        let Ok(key) = key_rx.recv_async().await else {
+            continue;
+        };
+        // 被唤醒时服务器可能处于暂停状态，等待暂停结束后才读取
+        shared.wait_unpaused().await;
+
+        let res = read_streams(shared, keys, ids, count).await?;
+        if !res.is_empty() {
+            break Ok(res);
+        }
+
+        // 事件触发后即被移除，需要重新加入
+        shared.db().add_may_update_event(key, key_tx.clone()).await;
+    }
+}
+
+#[cfg(test)]
+mod cmd_stream_tests {
+    use super::*;
+    use crate::util::test_init;
+
+    fn id_of(entry: &Resp3) -> StreamId {
+        let id = entry.try_array().unwrap()[0].try_blob().unwrap();
+        parse_id(id, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn xadd_xrange_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let xadd = |args: &[&str]| XAdd::parse(&mut args.into(), &AccessControl::new_loose());
+
+        // 自动生成的ID单调递增
+        let mut last = StreamId::MIN;
+        for i in 0..100 {
+            let res = xadd(&["s", "*", "i", &i.to_string()])
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap();
+            let id = parse_id(res.try_blob().unwrap(), 0).unwrap();
+            assert!(id > last);
+            last = id;
+        }
+
+        // ID必须大于流中最大的ID
+        assert!(xadd(&["s", "1-1", "f", "v"])
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .is_err());
+        assert!(xadd(&["s", "0-0", "f", "v"]).is_err());
+        assert!(xadd(&["s", "1-x", "f", "v"]).is_err());
+        assert!(xadd(&["s", "*", "f"]).is_err());
+
+        // 显式ID与`<ms>-*`
+        for (args, expect) in [
+            (["s2", "5-1", "f", "v"], "5-1"),
+            (["s2", "5-*", "f", "v"], "5-2"),
+            (["s2", "6-*", "f", "v"], "6-0"),
+            (["s2", "7", "f", "v"], "7-0"),
+        ] {
+            assert_eq!(
+                xadd(&args).unwrap().execute(&mut handler).await.unwrap(),
+                Some(Resp3::new_blob_string(expect.into()))
+            );
+        }
+        assert!(xadd(&["s2", "6-*", "f", "v"])
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .is_err());
+
+        let xrange = |args: &[&str]| XRange::parse(&mut args.into(), &AccessControl::new_loose());
+        async fn ids(res: Result<Option<Resp3>, CmdError>) -> Vec<String> {
+            res.unwrap()
+                .unwrap()
+                .try_array()
+                .unwrap()
+                .iter()
+                .map(|e| id_of(e).to_string())
+                .collect()
+        }
+
+        assert_eq!(
+            ids(xrange(&["s2", "-", "+"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await,
+            ["5-1", "5-2", "6-0", "7-0"]
+        );
+        // 只给出毫秒时，起点的序号为0，终点的序号为最大值
+        assert_eq!(
+            ids(xrange(&["s2", "5", "6"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await,
+            ["5-1", "5-2", "6-0"]
+        );
+        // 不包含边界
+        assert_eq!(
+            ids(xrange(&["s2", "(5-1", "(7-0"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await,
+            ["5-2", "6-0"]
+        );
+        assert_eq!(
+            ids(xrange(&["s2", "-", "+", "COUNT", "2"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await,
+            ["5-1", "5-2"]
+        );
+        // 起点大于终点以及边界溢出时范围为空
+        assert!(ids(xrange(&["s2", "7", "5"])
+            .unwrap()
+            .execute(&mut handler)
+            .await)
+        .await
+        .is_empty());
+        assert!(
+            ids(xrange(&["s2", &format!("({}-{}", u64::MAX, u64::MAX), "+"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await
+            .is_empty()
+        );
+        assert!(xrange(&["s2", "a", "+"]).is_err());
+
+        let xrevrange = XRevRange::parse(
+            &mut ["s2", "+", "5-2", "COUNT", "2"].as_ref().into(),
+            &AccessControl::new_loose(),
+        )
+        .unwrap();
+        assert_eq!(
+            ids(xrevrange.execute(&mut handler).await).await,
+            ["7-0", "6-0"]
+        );
+
+        // 裁剪
+        assert_eq!(
+            xadd(&["s2", "MAXLEN", "=", "2", "8-0", "f", "v"])
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::new_blob_string("8-0".into()))
+        );
+        assert_eq!(
+            ids(xrange(&["s2", "-", "+"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await,
+            ["7-0", "8-0"]
+        );
+        xadd(&["s2", "MINID", "~", "9", "LIMIT", "1", "9-0", "f", "v"])
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(xrange(&["s2", "-", "+"])
+                .unwrap()
+                .execute(&mut handler)
+                .await)
+            .await,
+            ["8-0", "9-0"]
+        );
+        assert!(xadd(&["s2", "MAXLEN", "2", "LIMIT", "1", "*", "f", "v"]).is_err());
+
+        let xlen = XLen::parse(&mut ["s2"].as_ref().into(), &AccessControl::new_loose()).unwrap();
+        assert_eq!(
+            xlen.execute(&mut handler).await.unwrap(),
+            Some(Resp3::new_integer(2))
+        );
+
+        // NOMKSTREAM
+        assert_eq!(
+            xadd(&["nil", "NOMKSTREAM", "*", "f", "v"])
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::Null)
+        );
+        assert!(!handler.shared.db().contains_object(&"nil".into()).await);
+    }
+
+    #[tokio::test]
+    async fn xread_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let xadd =
+            |args: &[&str]| XAdd::parse(&mut args.into(), &AccessControl::new_loose()).unwrap();
+        let xread =
+            |args: &[&str]| XRead::parse(&mut args.into(), &AccessControl::new_loose()).unwrap();
+
+        xadd(&["r1", "1-0", "a", "1"])
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        xadd(&["r1", "2-0", "b", "2"])
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        xadd(&["r2", "1-0", "c", "3"])
+            .execute(&mut handler)
+            .await
+            .unwrap();
+
+        // RESP2下为[key, entries]组成的数组
+        let res = xread(&["COUNT", "1", "STREAMS", "r1", "r2", "nil", "1-0", "0", "0"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res,
+            Resp3::new_array(vec![
+                Resp3::new_array(vec![
+                    Resp3::new_blob_string("r1".into()),
+                    Resp3::new_array(vec![entry_reply(
+                        &StreamId::new(2, 0),
+                        &vec![("b".into(), "2".into())]
+                    )]),
+                ]),
+                Resp3::new_array(vec![
+                    Resp3::new_blob_string("r2".into()),
+                    Resp3::new_array(vec![entry_reply(
+                        &StreamId::new(1, 0),
+                        &vec![("c".into(), "3".into())]
+                    )]),
+                ]),
+            ])
+        );
+
+        // 没有新条目
+        assert_eq!(
+            xread(&["STREAMS", "r1", "$"])
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::Null)
+        );
+        assert!(XRead::parse(
+            &mut ["STREAMS", "r1", "r2", "0"].as_ref().into(),
+            &AccessControl::new_loose()
+        )
+        .is_err());
+
+        // 阻塞直到有新条目
+        let (mut handler2, _) = Handler::with_shared(handler.shared.clone());
+        let join = tokio::spawn(async move {
+            XRead::parse(
+                &mut ["BLOCK", "0", "STREAMS", "r1", "$"].as_ref().into(),
+                &AccessControl::new_loose(),
+            )
+            .unwrap()
+            .execute(&mut handler2)
+            .await
+            .unwrap()
+            .unwrap()
+        });
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        xadd(&["r1", "3-0", "d", "4"])
+            .execute(&mut handler)
+            .await
+            .unwrap();
+        let res = join.await.unwrap();
+        let entries = &res.try_array().unwrap()[0].try_array().unwrap()[1];
+        assert_eq!(id_of(&entries.try_array().unwrap()[0]), StreamId::new(3, 0));
+
+        // 超时返回Null
+        assert_eq!(
+            xread(&["BLOCK", "100", "STREAMS", "r1", "$"])
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::Null)
+        );
+    }
+
+    #[tokio::test]
+    async fn xgroup_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let ac = AccessControl::new_loose();
+
+        for i in 1..=4 {
+            XAdd::parse(&mut ["g", &format!("{i}-0"), "f", "v"].as_ref().into(), &ac)
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap();
+        }
+
+        // 键不存在时需要MKSTREAM
+        assert!(
+            XGroupCreate::parse(&mut ["nil", "grp", "$"].as_ref().into(), &ac)
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .is_err()
+        );
+        let create = |args: &[&str]| XGroupCreate::parse(&mut args.into(), &ac).unwrap();
+        assert_eq!(
+            create(&["g", "grp", "0"])
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::new_simple_string("OK".into()))
+        );
+        let err = create(&["g", "grp", "$"])
+            .execute(&mut handler)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("BUSYGROUP"));
+        create(&["s2", "grp", "$", "MKSTREAM"])
+            .execute(&mut handler)
+            .await
+            .unwrap();
+
+        async fn read_ids(handler: &mut Handler<impl AsyncStream>, args: &[&str]) -> Vec<StreamId> {
+            let res = XReadGroup::parse(&mut args.into(), &AccessControl::new_loose())
+                .unwrap()
+                .execute(handler)
+                .await
+                .unwrap()
+                .unwrap();
+            if res == Resp3::Null {
+                return vec![];
+            }
+            res.try_array().unwrap()[0].try_array().unwrap()[1]
+                .try_array()
+                .unwrap()
+                .iter()
+                .map(id_of)
+                .collect()
+        }
+
+        // 两个消费者分别读取新条目
+        assert_eq!(
+            read_ids(
+                &mut handler,
+                &["GROUP", "grp", "c1", "COUNT", "2", "STREAMS", "g", ">"]
+            )
+            .await,
+            [StreamId::new(1, 0), StreamId::new(2, 0)]
+        );
+        assert_eq!(
+            read_ids(&mut handler, &["GROUP", "grp", "c2", "STREAMS", "g", ">"]).await,
+            [StreamId::new(3, 0), StreamId::new(4, 0)]
+        );
+        assert!(
+            read_ids(&mut handler, &["GROUP", "grp", "c2", "STREAMS", "g", ">"])
+                .await
+                .is_empty()
+        );
+        // 读取c1的历史条目
+        assert_eq!(
+            read_ids(&mut handler, &["GROUP", "grp", "c1", "STREAMS", "g", "1"]).await,
+            [StreamId::new(2, 0)]
+        );
+
+        let ack = |args: &[&str]| XAck::parse(&mut args.into(), &ac).unwrap();
+        assert_eq!(
+            ack(&["g", "grp", "1-0", "1-0", "9-0"])
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::new_integer(1))
+        );
+
+        let pending = |args: &[&str]| XPending::parse(&mut args.into(), &ac).unwrap();
+        let summary = pending(&["g", "grp"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            summary,
+            Resp3::new_array(vec![
+                Resp3::new_integer(3),
+                Resp3::new_blob_string("2-0".into()),
+                Resp3::new_blob_string("4-0".into()),
+                Resp3::new_array(vec![
+                    Resp3::new_array(vec![
+                        Resp3::new_blob_string("c1".into()),
+                        Resp3::new_blob_string("1".into()),
+                    ]),
+                    Resp3::new_array(vec![
+                        Resp3::new_blob_string("c2".into()),
+                        Resp3::new_blob_string("2".into()),
+                    ]),
+                ]),
+            ])
+        );
+
+        let extended = pending(&["g", "grp", "-", "+", "10", "c2"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let extended = extended.try_array().unwrap();
+        assert_eq!(extended.len(), 2);
+        assert_eq!(id_of(&extended[0]), StreamId::new(3, 0));
+        let detail = extended[0].try_array().unwrap();
+        assert_eq!(detail[1], Resp3::new_blob_string("c2".into()));
+        assert_eq!(detail[3], Resp3::new_integer(1));
+        assert_eq!(
+            pending(&["g", "grp", "IDLE", "100000", "-", "+", "10"])
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::new_array(vec![]))
+        );
+
+        // c1认领c2的3-0，投递次数加1
+        let claimed = XClaim::parse(&mut ["g", "grp", "c1", "0", "3-0"].as_ref().into(), &ac)
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(id_of(&claimed.try_array().unwrap()[0]), StreamId::new(3, 0));
+        let extended = pending(&["g", "grp", "3-0", "3-0", "1"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let detail = extended.try_array().unwrap()[0].try_array().unwrap();
+        assert_eq!(detail[1], Resp3::new_blob_string("c1".into()));
+        assert_eq!(detail[3], Resp3::new_integer(2));
+        // 空闲时间不足时不认领
+        assert_eq!(
+            XClaim::parse(
+                &mut ["g", "grp", "c2", "100000", "3-0", "JUSTID"]
+                    .as_ref()
+                    .into(),
+                &ac
+            )
+            .unwrap()
+            .execute(&mut handler)
+            .await
+            .unwrap(),
+            Some(Resp3::new_array(vec![]))
+        );
+
+        // 组不存在
+        let err = pending(&["g", "nil"])
+            .execute(&mut handler)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("NOGROUP"));
+        assert!(XReadGroup::parse(
+            &mut ["GROUP", "nil", "c1", "STREAMS", "g", ">"].as_ref().into(),
+            &ac
+        )
+        .unwrap()
+        .execute(&mut handler)
+        .await
+        .is_err());
+
+        assert_eq!(
+            XGroupDestroy::parse(&mut ["g", "grp"].as_ref().into(), &ac)
+                .unwrap()
+                .execute(&mut handler)
+                .await
+                .unwrap(),
+            Some(Resp3::new_integer(1))
+        );
+    }
+}
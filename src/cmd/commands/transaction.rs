@@ -0,0 +1,557 @@
+// Discard
+// Exec
+// Multi
+// Unwatch
+// Watch
+
+use super::*;
+use crate::{
+    cmd::{dispatch, CmdError, CmdExecutor, CmdType, CmdUnparsed, Err},
+    conf::AccessControl,
+    connection::AsyncStream,
+    frame::Resp3,
+    server::{Handler, Transaction},
+    CmdFlag, Int, Key,
+};
+use tracing::instrument;
+
+/// 放弃执行事务中排队的所有命令。
+/// # Reply:
+///
+/// **Simple string reply:** always OK.
+#[derive(Debug)]
+pub struct Discard;
+
+impl CmdExecutor for Discard {
+    const NAME: &'static str = "DISCARD";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = DISCARD_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if handler.context.transaction.take().is_none() {
+            return Err("ERR DISCARD without MULTI".into());
+        }
+        unwatch(handler);
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(Discard)
+    }
+}
+
+/// 执行事务中排队的所有命令。如果排队时有命令出错(例如未知命令或参数错误)，则放
+/// 弃执行整个事务。事务执行期间，其它客户端对被监视的键的修改需要等待事务执行完毕。
+/// # Reply:
+///
+/// **Array reply:** each element being the reply to each of the commands in the
+/// atomic transaction.
+/// **Simple error reply:** EXECABORT if the transaction was discarded because of
+/// previous errors.
+/// **Null reply:** if the transaction was aborted because a watched key was modified.
+#[derive(Debug)]
+pub struct Exec;
+
+impl CmdExecutor for Exec {
+    const NAME: &'static str = "EXEC";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = EXEC_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let Some(tx) = handler.context.transaction.take() else {
+            return Err("ERR EXEC without MULTI".into());
+        };
+        let watched = handler.context.watched.take();
+
+        if tx.tx_dirty {
+            if let Some(watched) = watched {
+                watched.unwatch(handler.shared.db());
+            }
+            return Err(Err::ExecAbort.into());
+        }
+
+        let Some(watched) = watched else {
+            return exec_queued(tx, handler).await;
+        };
+
+        // 给被监视的键加上意向锁，直到事务执行完毕前，其它客户端都无法修改这些键，保证
+        // 检查与执行之间不会有其它客户端的写入。按键的顺序加锁并去重，避免与其它EXEC或
+        // EVAL交叉加锁导致死锁
+        let db = handler.shared.db().clone();
+        let mut keys: Vec<&Key> = watched.keys.iter().collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut locks = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(lock) = db
+                .add_lock_event(key.clone(), handler.context.client_id)
+                .await
+            {
+                locks.push((key, lock));
+            }
+        }
+
+        // 被监视的键在WATCH之后被修改过
+        let res = if !watched.rx.is_empty() {
+            Ok(Some(Resp3::Null))
+        } else {
+            exec_queued(tx, handler).await
+        };
+
+        for (key, lock) in locks {
+            db.remove_lock_event(key, lock);
+        }
+        watched.unwatch(&db);
+
+        res
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(Exec)
+    }
+}
+
+/// 开启事务，之后的命令会排队等待EXEC执行。
+/// # Reply:
+///
+/// **Simple string reply:** always OK.
+#[derive(Debug)]
+pub struct Multi;
+
+impl CmdExecutor for Multi {
+    const NAME: &'static str = "MULTI";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = MULTI_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if handler.context.transaction.is_some() {
+            return Err("ERR MULTI calls can not be nested".into());
+        }
+
+        handler.context.transaction = Some(Transaction::default());
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(Multi)
+    }
+}
+
+/// 取消对所有键的监视。
+/// # Reply:
+///
+/// **Simple string reply:** always OK.
+#[derive(Debug)]
+pub struct Unwatch;
+
+impl CmdExecutor for Unwatch {
+    const NAME: &'static str = "UNWATCH";
+    const TYPE: CmdType = CmdType::Other;
+    const FLAG: CmdFlag = UNWATCH_FLAG;
+    const ARITY: Int = 1;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        unwatch(handler);
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, _ac: &AccessControl) -> Result<Self, CmdError> {
+        if !args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        Ok(Unwatch)
+    }
+}
+
+/// 监视给定的键，如果在EXEC之前这些键被修改(包括只修改过期时间，例如EXPIRE和PERSIST)、
+/// 删除或者过期，则EXEC会放弃执行事务。EXEC或DISCARD之后取消所有的监视。
+/// # Reply:
+///
+/// **Simple string reply:** always OK.
+#[derive(Debug)]
+pub struct Watch {
+    keys: Vec<Key>,
+}
+
+impl CmdExecutor for Watch {
+    const NAME: &'static str = "WATCH";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = WATCH_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, -1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        if handler.context.transaction.is_some() {
+            return Err("ERR WATCH inside MULTI is not allowed".into());
+        }
+
+        let watched = handler.context.watched.get_or_insert_with(Default::default);
+
+        // 键被修改时触发MayUpdate事件，事件触发后即被移除
+        let db = handler.shared.db();
+        for key in self.keys {
+            db.add_may_update_event(key.clone(), watched.tx.clone())
+                .await;
+            watched.keys.push(key);
+        }
+
+        Ok(Some(Resp3::new_simple_string("OK".into())))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        if args.is_empty() {
+            return Err(Err::WrongArgNum.into());
+        }
+
+        let keys: Vec<_> = args.collect();
+        if ac.is_forbidden_keys(&keys, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(Watch { keys })
+    }
+}
+
+/// 依次执行事务中排队的命令
+async fn exec_queued(
+    tx: Transaction,
+    handler: &mut Handler<impl AsyncStream>,
+) -> Result<Option<Resp3>, CmdError> {
+    // 事务中命令的回复作为EXEC回复的一部分返回，不能直接写出到连接
+    let direct_reply = std::mem::replace(&mut handler.context.direct_reply, false);
+
+    let mut replies = Vec::with_capacity(tx.queued.len());
+    for cmd_frame in tx.queued {
+        // 命令执行出错时，错误作为该命令的回复，不影响其它命令的执行
+        let reply = Box::pin(dispatch(cmd_frame, handler)).await;
+        match reply {
+            Ok(reply) => replies.push(reply.unwrap_or(Resp3::Null)),
+            Err(e) => {
+                handler.context.direct_reply = direct_reply;
+                return Err(anyhow::Error::from(e).into());
+            }
+        }
+    }
+
+    handler.context.direct_reply = direct_reply;
+    Ok(Some(Resp3::new_array(replies)))
+}
+
+/// 取消对所有键的监视
+fn unwatch(handler: &mut Handler<impl AsyncStream>) {
+    if let Some(watched) = handler.context.watched.take() {
+        watched.unwatch(handler.shared.db());
+    }
+}
+
+#[cfg(test)]
+mod cmd_transaction_tests {
+    use super::*;
+    use crate::util::{cmd, test_init};
+
+    #[tokio::test]
+    async fn exec_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        let res = handler.dispatch(cmd(&["MULTI"])).await.unwrap().unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        let res = handler
+            .dispatch(cmd(&["SET", "k", "v"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "QUEUED");
+        let res = handler
+            .dispatch(cmd(&["INCR", "k"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "QUEUED");
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "QUEUED");
+
+        // 执行出错的命令不影响其它命令
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res[0].try_simple_string().unwrap(), "OK");
+        assert!(res[1].try_simple_error().is_some());
+        assert_eq!(res[2].try_blob().unwrap().as_ref(), b"v");
+
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), "ERR EXEC without MULTI");
+    }
+
+    #[tokio::test]
+    async fn exec_abort_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        let res = handler
+            .dispatch(cmd(&["SET", "k", "v"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "QUEUED");
+        let res = handler
+            .dispatch(cmd(&["UNKNOWN", "k"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().is_some());
+
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "EXECABORT Transaction discarded because of previous errors."
+        );
+        // 事务中的命令均未执行
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert!(res.is_null());
+
+        // 参数错误同样会使事务被放弃，DISCARD仍然可用
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        let res = handler.dispatch(cmd(&["GET"])).await.unwrap().unwrap();
+        assert!(res.try_simple_error().is_some());
+        let res = handler.dispatch(cmd(&["DISCARD"])).await.unwrap().unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        let res = handler.dispatch(cmd(&["DISCARD"])).await.unwrap().unwrap();
+        assert_eq!(res.try_simple_error().unwrap(), "ERR DISCARD without MULTI");
+    }
+
+    #[tokio::test]
+    async fn watch_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let (mut other, _) = Handler::with_shared(handler.shared.clone());
+
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+
+        // 其它连接只修改了被监视的键的过期时间，事务被放弃
+        for modify in [&["EXPIRE", "k", "100"][..], &["PERSIST", "k"]] {
+            let res = handler
+                .dispatch(cmd(&["WATCH", "k"]))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(res.try_simple_string().unwrap(), "OK");
+            let res = other.dispatch(cmd(modify)).await.unwrap().unwrap();
+            assert_eq!(res.try_integer().unwrap(), 1);
+
+            handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+            handler.dispatch(cmd(&["SET", "k", "new"])).await.unwrap();
+            let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+            assert!(res.is_null());
+            let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+            assert_eq!(res.try_blob().unwrap().as_ref(), b"v");
+        }
+
+        // 被监视的键没有被修改，事务正常执行；EXEC之后不再监视
+        handler.dispatch(cmd(&["WATCH", "k"])).await.unwrap();
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        let res = handler
+            .dispatch(cmd(&["WATCH", "k"]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            res.try_simple_error().unwrap(),
+            "ERR WATCH inside MULTI is not allowed"
+        );
+        handler.dispatch(cmd(&["DISCARD"])).await.unwrap();
+        assert!(handler.context.watched.is_none());
+
+        handler.dispatch(cmd(&["WATCH", "k"])).await.unwrap();
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        handler.dispatch(cmd(&["SET", "k", "new"])).await.unwrap();
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        assert_eq!(
+            res.try_array().unwrap()[0].try_simple_string().unwrap(),
+            "OK"
+        );
+        assert!(handler.context.watched.is_none());
+
+        // UNWATCH之后键的修改不影响事务
+        handler.dispatch(cmd(&["WATCH", "k"])).await.unwrap();
+        let res = handler.dispatch(cmd(&["UNWATCH"])).await.unwrap().unwrap();
+        assert_eq!(res.try_simple_string().unwrap(), "OK");
+        other.dispatch(cmd(&["EXPIRE", "k", "100"])).await.unwrap();
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        handler.dispatch(cmd(&["GET", "k"])).await.unwrap();
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        assert_eq!(
+            res.try_array().unwrap()[0].try_blob().unwrap().as_ref(),
+            b"new"
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_cleanup_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+        let missing = Key::from("missing");
+
+        // WATCH不存在的键时创建空对象，UNWATCH，EXEC以及DISCARD之后空对象被移除
+        for end in [&["UNWATCH"][..], &["EXEC"], &["DISCARD"]] {
+            handler
+                .dispatch(cmd(&["WATCH", "missing", "k"]))
+                .await
+                .unwrap();
+            assert!(db.entries().contains_key(&missing));
+            assert!(!db.contains_object(&missing).await);
+
+            if end != ["UNWATCH"] {
+                handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+            }
+            handler.dispatch(cmd(end)).await.unwrap();
+            assert!(!db.entries().contains_key(&missing));
+            assert!(handler.context.watched.is_none());
+        }
+
+        // 其它连接的监视不受影响
+        let (mut other, _) = Handler::with_shared(handler.shared.clone());
+        other.dispatch(cmd(&["WATCH", "missing"])).await.unwrap();
+        handler.dispatch(cmd(&["WATCH", "missing"])).await.unwrap();
+        handler.dispatch(cmd(&["UNWATCH"])).await.unwrap();
+        assert!(db.entries().contains_key(&missing));
+
+        // 连接断开时同样取消监视
+        drop(other);
+        assert!(!db.entries().contains_key(&missing));
+    }
+
+    #[tokio::test]
+    async fn exec_isolation_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let (mut other, _) = Handler::with_shared(handler.shared.clone());
+        let db = handler.shared.db().clone();
+
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+        handler.dispatch(cmd(&["WATCH", "k"])).await.unwrap();
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        handler
+            .dispatch(cmd(&["DEBUG", "SLEEP", "0.2"]))
+            .await
+            .unwrap();
+        handler.dispatch(cmd(&["GET", "k"])).await.unwrap();
+
+        // EXEC执行期间，其它客户端对被监视的键的修改需要等待事务执行完毕
+        let (res, _) = tokio::join!(handler.dispatch(cmd(&["EXEC"])), async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            other.dispatch(cmd(&["SET", "k", "other"])).await.unwrap();
+        });
+        let res = res.unwrap().unwrap();
+        assert_eq!(
+            res.try_array().unwrap()[1].try_blob().unwrap().as_ref(),
+            b"v"
+        );
+        let res = handler.dispatch(cmd(&["GET", "k"])).await.unwrap().unwrap();
+        assert_eq!(res.try_blob().unwrap().as_ref(), b"other");
+
+        // 没有其它客户端等待时，EXEC之后意向锁被移除，键恢复为普通的键
+        handler.dispatch(cmd(&["WATCH", "k"])).await.unwrap();
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        handler.dispatch(cmd(&["GET", "k"])).await.unwrap();
+        handler.dispatch(cmd(&["EXEC"])).await.unwrap();
+        other.dispatch(cmd(&["SET", "k", "v2"])).await.unwrap();
+
+        // WATCH不存在的键时，意向锁与空对象一起被移除
+        handler.dispatch(cmd(&["WATCH", "missing"])).await.unwrap();
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        handler.dispatch(cmd(&["EXEC"])).await.unwrap();
+        assert!(!db.entries().contains_key(&Key::from("missing")));
+
+        // 以相反的顺序监视相同的键(包括重复的键)，同时EXEC不会死锁
+        handler
+            .dispatch(cmd(&["WATCH", "a", "b", "a"]))
+            .await
+            .unwrap();
+        other
+            .dispatch(cmd(&["WATCH", "b", "a", "b"]))
+            .await
+            .unwrap();
+        for h in [&mut handler, &mut other] {
+            h.dispatch(cmd(&["MULTI"])).await.unwrap();
+            h.dispatch(cmd(&["DEBUG", "SLEEP", "0.05"])).await.unwrap();
+            h.dispatch(cmd(&["GET", "a"])).await.unwrap();
+        }
+        let (res1, res2) = tokio::time::timeout(std::time::Duration::from_secs(3), async {
+            tokio::join!(
+                handler.dispatch(cmd(&["EXEC"])),
+                other.dispatch(cmd(&["EXEC"]))
+            )
+        })
+        .await
+        .unwrap();
+        assert!(res1.unwrap().unwrap().try_array().is_some());
+        assert!(res2.unwrap().unwrap().try_array().is_some());
+        assert!(!db.entries().contains_key(&Key::from("a")));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exec_streaming_reply_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        // 模拟由连接直接执行的命令
+        handler.context.direct_reply = true;
+
+        let mut args = vec!["SADD", "big"];
+        let members: Vec<String> = (0..2000).map(|i| format!("member{i}")).collect();
+        args.extend(members.iter().map(String::as_str));
+        handler.dispatch(cmd(&args)).await.unwrap();
+
+        // 事务中命令的回复不能直接写出到连接，EXEC需要返回完整的回复
+        handler.dispatch(cmd(&["MULTI"])).await.unwrap();
+        handler.dispatch(cmd(&["SMEMBERS", "big"])).await.unwrap();
+        handler.dispatch(cmd(&["KEYS", ".*"])).await.unwrap();
+        let res = handler.dispatch(cmd(&["EXEC"])).await.unwrap().unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res[0].try_array().unwrap().len(), 2000);
+        assert_eq!(res[1].try_array().unwrap().len(), 1);
+        assert_eq!(handler.conn.pending_write_len(), 0);
+        assert!(handler.context.direct_reply);
+    }
+}
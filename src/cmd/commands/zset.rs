@@ -0,0 +1,280 @@
+// ZInterCard
+// ZRandMember
+
+use super::{
+    hash::parse_rand_args,
+    set::{inter_card, parse_inter_card},
+    *,
+};
+use crate::{
+    cmd::{CmdError, CmdExecutor, CmdType, CmdUnparsed, Err},
+    conf::AccessControl,
+    connection::AsyncStream,
+    frame::Resp3,
+    server::Handler,
+    shared::db::ObjValue,
+    util::random_indices,
+    CmdFlag, Int, Key,
+};
+use tracing::instrument;
+
+/// 返回多个有序集合交集的成员数量，普通集合视为所有成员分数相同的有序集合。
+/// LIMIT不为0时，交集的成员数量达到LIMIT后停止计算
+/// # Reply:
+///
+/// **Integer reply:** the number of members in the resulting intersection.
+#[derive(Debug)]
+pub struct ZInterCard {
+    pub keys: Vec<Key>,
+    pub limit: usize,
+}
+
+impl CmdExecutor for ZInterCard {
+    const NAME: &'static str = "ZINTERCARD";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = ZINTERCARD_FLAG;
+    const ARITY: Int = -3;
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let card = inter_card(handler, &self.keys, self.limit, |obj| match obj.value() {
+            ObjValue::Set(s) => Ok(s.iter().collect()),
+            _ => Ok(obj.on_zset()?.members().cloned().collect()),
+        })
+        .await?;
+
+        Ok(Some(Resp3::new_integer(card as Int)))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (keys, limit) = parse_inter_card(args)?;
+        if ac.is_forbidden_keys(&keys, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(ZInterCard { keys, limit })
+    }
+}
+
+/// # Reply:
+///
+/// **Bulk string reply:** without the additional count argument, the command returns a randomly selected member, or nil when key doesn't exist.
+/// **Array reply:** if the additional count argument is passed, the command returns an array of members, or an empty array when key doesn't exist.
+/// **Array reply:** if the WITHSCORES modifier is used, the reply is a list of members and their scores from the sorted set. In RESP3 each member and its score are returned as a two-element array.
+///
+/// # Desc:
+///
+/// ZRANDMEMBER key [count [WITHSCORES]]
+///
+/// count为正数时返回不重复的成员，count为负数时返回|count|个可能重复的成员
+#[derive(Debug)]
+pub struct ZRandMember {
+    pub key: Key,
+    pub count: Option<Int>,
+    pub with_scores: bool,
+}
+
+impl CmdExecutor for ZRandMember {
+    const NAME: &'static str = "ZRANDMEMBER";
+    const TYPE: CmdType = CmdType::Read;
+    const FLAG: CmdFlag = ZRANDMEMBER_FLAG;
+    const ARITY: Int = -2;
+    const KEY_RANGE: (Int, Int, Int) = (1, 1, 1);
+
+    #[instrument(level = "debug", skip(handler), ret, err)]
+    async fn execute(
+        self,
+        handler: &mut Handler<impl AsyncStream>,
+    ) -> Result<Option<Resp3>, CmdError> {
+        let mut picked = vec![];
+        let visit = handler
+            .shared
+            .db()
+            .visit_object(&self.key, |obj| {
+                let elems: Vec<_> = obj.on_zset()?.iter().collect();
+                picked = random_indices(elems.len(), self.count.unwrap_or(1))
+                    .into_iter()
+                    .map(|i| (elems[i].member().clone(), elems[i].score()))
+                    .collect();
+                Ok(())
+            })
+            .await;
+
+        match visit {
+            Ok(()) => {}
+            // 键不存在时，不带count返回nil，带count返回空数组
+            Err(CmdError::Null) if self.count.is_some() => {
+                return Ok(Some(Resp3::new_array(vec![])))
+            }
+            Err(e) => return Err(e),
+        }
+
+        let reply = match self.count {
+            None => picked
+                .pop()
+                .map_or(Resp3::Null, |(m, _)| Resp3::new_blob_string(m)),
+            Some(_) if self.with_scores => handler.new_pairs_reply(
+                picked
+                    .into_iter()
+                    .map(|(m, s)| (Resp3::new_blob_string(m), Resp3::new_double(s))),
+            ),
+            Some(_) => Resp3::new_array(
+                picked
+                    .into_iter()
+                    .map(|(m, _)| Resp3::new_blob_string(m))
+                    .collect::<Vec<_>>(),
+            ),
+        };
+
+        Ok(Some(reply))
+    }
+
+    fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError> {
+        let (key, count, with_scores) = parse_rand_args(args, b"WITHSCORES")?;
+        if ac.is_forbidden_key(&key, Self::TYPE) {
+            return Err(Err::NoPermission.into());
+        }
+
+        Ok(ZRandMember {
+            key,
+            count,
+            with_scores,
+        })
+    }
+}
+
+#[cfg(test)]
+mod cmd_zset_tests {
+    use super::*;
+    use crate::{
+        shared::db::{ObjectInner, Set, ZSet},
+        util::test_init,
+    };
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn zintercard_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object(
+            Key::from("zset1"),
+            ObjectInner::new_zset(
+                ZSet::from([(1.0, "a"), (2.0, "b"), (3.0, "c"), (4.0, "d")]),
+                None,
+            ),
+        )
+        .await;
+        db.insert_object(
+            Key::from("zset2"),
+            ObjectInner::new_zset(ZSet::from([(5.0, "b"), (6.0, "c"), (7.0, "x")]), None),
+        )
+        .await;
+        db.insert_object(
+            Key::from("set"),
+            ObjectInner::new_set(
+                Set::HashSet(["a", "b", "c"].into_iter().map(Bytes::from).collect()),
+                None,
+            ),
+        )
+        .await;
+
+        let zintercard = |args: &[&str]| {
+            ZInterCard::parse(&mut args.into(), &AccessControl::new_loose()).unwrap()
+        };
+
+        // 普通集合的成员也参与交集计算
+        assert_eq!(
+            zintercard(&["3", "zset1", "zset2", "set"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(2)
+        );
+
+        // 达到LIMIT后停止计算
+        assert_eq!(
+            zintercard(&["2", "zset1", "set", "LIMIT", "1"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(1)
+        );
+
+        // 交集为空
+        assert_eq!(
+            zintercard(&["2", "zset1", "zset_nil"])
+                .execute(&mut handler)
+                .await
+                .unwrap()
+                .unwrap(),
+            Resp3::new_integer(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn zrandmember_test() {
+        test_init();
+        let (mut handler, _) = Handler::new_fake();
+        let db = handler.shared.db().clone();
+
+        db.insert_object(
+            Key::from("zset"),
+            ObjectInner::new_zset(ZSet::from([(1.0, "a"), (2.5, "b")]), None),
+        )
+        .await;
+
+        let zrandmember = |args: &[&str]| {
+            ZRandMember::parse(&mut args.into(), &AccessControl::new_loose()).unwrap()
+        };
+        let score_of = |member: &Resp3| match member.try_blob().unwrap().as_ref() {
+            b"a" => 1.0,
+            b"b" => 2.5,
+            _ => panic!("unexpected member"),
+        };
+
+        // RESP2: [m1, s1, m2, s2, ...]，分数在编码时转换为bulk string
+        let res = zrandmember(&["zset", "-3", "WITHSCORES"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 6);
+        for pair in res.chunks(2) {
+            assert_eq!(pair[1].try_double().unwrap(), score_of(&pair[0]));
+        }
+
+        // RESP3: [[m1, s1], [m2, s2], ...]
+        handler.context.protocol_version = 3;
+        let res = zrandmember(&["zset", "5", "WITHSCORES"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        let res = res.try_array().unwrap();
+        assert_eq!(res.len(), 2);
+        for pair in res {
+            let pair = pair.try_array().unwrap();
+            assert_eq!(pair[1].try_double().unwrap(), score_of(&pair[0]));
+        }
+
+        // 不带WITHSCORES时只返回成员
+        let res = zrandmember(&["zset", "-4"])
+            .execute(&mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(res.try_array().unwrap().len(), 4);
+
+        // 键不存在
+        let res = zrandmember(&["nil"]).execute(&mut handler).await;
+        assert!(matches!(res, Err(CmdError::Null)));
+    }
+}
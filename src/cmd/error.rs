@@ -59,12 +59,14 @@ impl From<DbError> for CmdError {
     fn from(e: DbError) -> Self {
         match e {
             DbError::KeyNotFound => CmdError::Null,
-            DbError::TypeErr { expected, found } => Err::Other {
-                message: format!("WRONGTYPE expected: {expected} found {found}").into(),
-            }
-            .into(),
+            // 字符串对象不是整数编码，说明值无法解析为整数
+            DbError::TypeErr {
+                expected: "str::int",
+                ..
+            } => Err::A2IParse.into(),
+            DbError::TypeErr { .. } => Err::WrongType.into(),
             DbError::Overflow => Err::Other {
-                message: "ERR value out of range".into(),
+                message: "ERR increment or decrement would overflow".into(),
             }
             .into(),
         }
@@ -107,6 +109,91 @@ pub enum Err {
     Syntax,
     #[snafu(display("NOPERM this user has insufficient permissions"))]
     NoPermission,
+    #[snafu(display("WRONGTYPE Operation against a key holding the wrong kind of value"))]
+    WrongType,
+    #[snafu(display("NOAUTH Authentication required."))]
+    NoAuth,
+    #[snafu(display("WRONGPASS invalid username-password pair or user is disabled."))]
+    WrongPass,
+    #[snafu(display("NOPROTO unsupported protocol version"))]
+    NoProto,
+    #[snafu(display("NOSCRIPT No matching script."))]
+    NoScript,
+    #[snafu(display("BUSYKEY Target key name already exists."))]
+    BusyKey,
+    #[snafu(display("OOM command not allowed when used memory > 'maxmemory'."))]
+    OutOfMemory,
+    #[snafu(display("READONLY You can't write against a read only replica."))]
+    ReadOnly,
+    #[snafu(display(
+        "MASTERDOWN Link with MASTER is down and replica-serve-stale-data is set to 'no'."
+    ))]
+    MasterDown,
+    #[snafu(display("EXECABORT Transaction discarded because of previous errors."))]
+    ExecAbort,
+    #[snafu(display("ERR command execution timed out"))]
+    Timeout,
     #[snafu(display("{}", message))]
     Other { message: ByteString },
 }
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    fn render(e: impl Into<CmdError>) -> String {
+        let frame: Resp3 = e.into().try_into().unwrap();
+        match frame {
+            Resp3::SimpleError { inner, .. } => inner.to_string(),
+            _ => panic!("expected simple error, found {:?}", frame),
+        }
+    }
+
+    fn prefix(e: impl Into<CmdError>) -> String {
+        render(e).split(' ').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn error_prefix_test() {
+        assert_eq!(prefix(Err::UnknownCmd), "ERR");
+        assert_eq!(prefix(Err::Forbaiden), "ERR");
+        assert_eq!(prefix(Err::WrongArgNum), "ERR");
+        assert_eq!(prefix(Err::A2IParse), "ERR");
+        assert_eq!(prefix(Err::Syntax), "ERR");
+        assert_eq!(prefix(Err::NoPermission), "NOPERM");
+        assert_eq!(prefix(Err::WrongType), "WRONGTYPE");
+        assert_eq!(prefix(Err::NoAuth), "NOAUTH");
+        assert_eq!(prefix(Err::WrongPass), "WRONGPASS");
+        assert_eq!(prefix(Err::NoProto), "NOPROTO");
+        assert_eq!(prefix(Err::NoScript), "NOSCRIPT");
+        assert_eq!(prefix(Err::BusyKey), "BUSYKEY");
+        assert_eq!(prefix(Err::OutOfMemory), "OOM");
+        assert_eq!(prefix(Err::ReadOnly), "READONLY");
+        assert_eq!(prefix(Err::MasterDown), "MASTERDOWN");
+        assert_eq!(prefix(Err::ExecAbort), "EXECABORT");
+
+        assert_eq!(
+            render(DbError::TypeErr {
+                expected: "list",
+                found: "string",
+            }),
+            "WRONGTYPE Operation against a key holding the wrong kind of value"
+        );
+        assert_eq!(
+            prefix(DbError::TypeErr {
+                expected: "str::int",
+                found: "str::raw",
+            }),
+            "ERR"
+        );
+        assert_eq!(prefix(DbError::Overflow), "ERR");
+        assert!(matches!(
+            CmdError::from(DbError::KeyNotFound).try_into(),
+            Ok(Resp3::Null)
+        ));
+        assert!(matches!(
+            CmdError::from(1).try_into(),
+            Ok(Resp3::Integer { inner: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,810 @@
+use super::{commands::*, for_each_cmd, CmdExecutor};
+use crate::{conf::ACL_CATEGORIES, frame::Resp3, CatFlag, CmdFlag, Int};
+use ahash::{AHashMap, AHashSet};
+use bytes::Bytes;
+use bytestring::ByteString;
+use std::sync::LazyLock;
+
+/// 命令的元数据，用于COMMAND INFO等命令
+///
+/// arity: 命令的参数个数(包括命令名)，负数表示参数个数至少为其绝对值
+/// first_key, last_key, key_step: 键在参数中的位置(命令名的位置为0)。last_key
+/// 为负数时表示从末尾开始计数，三者均为0时表示命令不接受键
+#[derive(Debug)]
+pub struct CmdInfo {
+    /// 小写的命令名，子命令以`|`分隔，例如`script|exists`
+    pub name: String,
+    /// 命令组(例如`script`)的flag为0
+    pub flag: CmdFlag,
+    pub arity: Int,
+    pub first_key: Int,
+    pub last_key: Int,
+    pub key_step: Int,
+}
+
+macro_rules! cmd_infos {
+    ( $( $cmd_type:ident ),*; $( $cmd_group:expr => $( $cmd_type2:ident ),* );* ) => {
+        {
+            let mut infos = vec![$( CmdInfo::new::<$cmd_type>(None) ),*];
+            $(
+                infos.push(CmdInfo::container($cmd_group));
+                $( infos.push(CmdInfo::new::<$cmd_type2>(Some($cmd_group))); )*
+            )*
+            infos
+        }
+    };
+}
+
+/// 由注册的命令生成，命令组排在其子命令之前
+pub static CMD_INFOS: LazyLock<Vec<CmdInfo>> = LazyLock::new(|| for_each_cmd!(cmd_infos));
+
+/// 所有已注册的命令(不包括命令组本身)，每项为`(name, cmd_flag, cat_flag, arity)`。
+/// name与`CmdInfo::name`一致，cat_flag的第i位表示命令属于`ACL_CATEGORIES[i]`
+pub fn registry() -> impl Iterator<Item = (&'static str, CmdFlag, CatFlag, Int)> {
+    CMD_INFOS.iter().filter(|info| info.flag != 0).map(|info| {
+        let cat_flag = ACL_CATEGORIES
+            .iter()
+            .enumerate()
+            .filter(|(_, cat)| cat.flag & info.flag != 0)
+            .fold(0, |cat_flag, (i, _)| cat_flag | (1 << i));
+
+        (info.name.as_str(), info.flag, cat_flag, info.arity)
+    })
+}
+
+/// 命令的文档，用于COMMAND DOCS
+///
+/// arguments: 每个参数为(name, type, flags)，flags为空或者为`optional`、`multiple`，
+/// 多个flag以`,`分隔
+#[derive(Debug)]
+pub struct CmdDoc {
+    /// 与`CmdInfo::name`一致
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub since: &'static str,
+    pub arguments: &'static [(&'static str, &'static str, &'static str)],
+}
+
+macro_rules! cmd_doc {
+    ($name:expr, $summary:expr, $since:expr, [$(($arg:expr, $typ:expr, $flags:expr)),* $(,)?]) => {
+        CmdDoc {
+            name: $name,
+            summary: $summary,
+            since: $since,
+            arguments: &[$(($arg, $typ, $flags)),*],
+        }
+    };
+}
+
+pub static CMD_DOCS: &[CmdDoc] = &[
+    // commands::other
+    cmd_doc!("bgsave", "Asynchronously saves the database to disk.", "1.0.0", []),
+    cmd_doc!("ping", "Returns the server's liveliness response.", "1.0.0", [
+        ("message", "string", "optional"),
+    ]),
+    cmd_doc!("echo", "Returns the given string.", "1.0.0", [("message", "string", "")]),
+    cmd_doc!("time", "Returns the server time.", "2.6.0", []),
+    cmd_doc!("lolwut", "Displays computer art and the Redis version.", "5.0.0", [
+        ("version", "integer", "optional"),
+    ]),
+    cmd_doc!("auth", "Authenticates the connection.", "1.0.0", [
+        ("username", "string", "optional"),
+        ("password", "string", ""),
+    ]),
+    cmd_doc!("hello", "Handshakes with the Redis server.", "6.0.0", [
+        ("protover", "integer", "optional"),
+        ("auth", "block", "optional"),
+    ]),
+    cmd_doc!("info", "Returns information and statistics about the server.", "1.0.0", [
+        ("section", "string", "optional,multiple"),
+    ]),
+    cmd_doc!("replicaof", "Configures a server as replica of another, or promotes it to a master.", "5.0.0", [
+        ("args", "oneof", ""),
+    ]),
+    // commands::key
+    cmd_doc!("dbsize", "Returns the number of keys in the database.", "1.0.0", []),
+    cmd_doc!("del", "Deletes one or more keys.", "1.0.0", [("key", "key", "multiple")]),
+    cmd_doc!("dump", "Returns a serialized representation of the value stored at a key.", "2.6.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("exists", "Determines whether one or more keys exist.", "1.0.0", [
+        ("key", "key", "multiple"),
+    ]),
+    cmd_doc!("expire", "Sets the expiration time of a key in seconds.", "1.0.0", [
+        ("key", "key", ""),
+        ("seconds", "integer", ""),
+        ("condition", "oneof", "optional"),
+    ]),
+    cmd_doc!("expireat", "Sets the expiration time of a key to a Unix timestamp.", "1.2.0", [
+        ("key", "key", ""),
+        ("unix-time-seconds", "unix-time", ""),
+        ("condition", "oneof", "optional"),
+    ]),
+    cmd_doc!("expiretime", "Returns the expiration time of a key as a Unix timestamp.", "7.0.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("keys", "Returns all key names that match a pattern.", "1.0.0", [
+        ("pattern", "pattern", ""),
+    ]),
+    cmd_doc!("migrate", "Atomically transfers a key from one Redis instance to another.", "2.6.0", [
+        ("host", "string", ""),
+        ("port", "integer", ""),
+        ("key-selector", "oneof", ""),
+        ("destination-db", "integer", ""),
+        ("timeout", "integer", ""),
+        ("copy", "pure-token", "optional"),
+        ("replace", "pure-token", "optional"),
+        ("keys", "key", "optional,multiple"),
+    ]),
+    cmd_doc!("nbkeys", "Returns all key names that match a pattern without blocking the connection.", "0.1.0", [
+        ("pattern", "pattern", ""),
+        ("redirect", "integer", ""),
+    ]),
+    cmd_doc!("pexpireat", "Sets the expiration time of a key to a Unix milliseconds timestamp.", "2.6.0", [
+        ("key", "key", ""),
+        ("unix-time-milliseconds", "unix-time", ""),
+        ("condition", "oneof", "optional"),
+    ]),
+    cmd_doc!("persist", "Removes the expiration time of a key.", "2.2.0", [("key", "key", "")]),
+    cmd_doc!("pttl", "Returns the expiration time in milliseconds of a key.", "2.6.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("restore", "Creates a key from the serialized representation of a value.", "2.6.0", [
+        ("key", "key", ""),
+        ("ttl", "integer", ""),
+        ("serialized-value", "string", ""),
+        ("replace", "pure-token", "optional"),
+        ("absttl", "pure-token", "optional"),
+        ("seconds", "integer", "optional"),
+        ("frequency", "integer", "optional"),
+    ]),
+    cmd_doc!("ttl", "Returns the expiration time in seconds of a key.", "1.0.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("type", "Determines the type of value stored at a key.", "1.0.0", [
+        ("key", "key", ""),
+    ]),
+    // commands::str
+    cmd_doc!("append", "Appends a string to the value of a key. Creates the key if it doesn't exist.", "2.0.0", [
+        ("key", "key", ""),
+        ("value", "string", ""),
+    ]),
+    cmd_doc!("bitfield", "Performs arbitrary bitfield integer operations on strings.", "3.2.0", [
+        ("key", "key", ""),
+        ("operation", "block", "multiple"),
+    ]),
+    cmd_doc!("decr", "Decrements the integer value of a key by one.", "1.0.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("decrby", "Decrements a number from the integer value of a key.", "1.0.0", [
+        ("key", "key", ""),
+        ("decrement", "integer", ""),
+    ]),
+    cmd_doc!("get", "Returns the string value of a key.", "1.0.0", [("key", "key", "")]),
+    cmd_doc!("getrange", "Returns a substring of the string stored at a key.", "2.4.0", [
+        ("key", "key", ""),
+        ("start", "integer", ""),
+        ("end", "integer", ""),
+    ]),
+    cmd_doc!("getset", "Returns the previous string value of a key after setting it to a new value.", "1.0.0", [
+        ("key", "key", ""),
+        ("value", "string", ""),
+    ]),
+    cmd_doc!("incr", "Increments the integer value of a key by one.", "1.0.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("incrby", "Increments the integer value of a key by a number.", "1.0.0", [
+        ("key", "key", ""),
+        ("increment", "integer", ""),
+    ]),
+    cmd_doc!("mget", "Atomically returns the string values of one or more keys.", "1.0.0", [
+        ("key", "key", "multiple"),
+    ]),
+    cmd_doc!("mset", "Atomically creates or modifies the string values of one or more keys.", "1.0.1", [
+        ("data", "block", "multiple"),
+    ]),
+    cmd_doc!("msetnx", "Atomically modifies the string values of one or more keys only when all keys don't exist.", "1.0.1", [
+        ("data", "block", "multiple"),
+    ]),
+    cmd_doc!("psetex", "Sets both string value and expiration time in milliseconds of a key. The key is created if it doesn't exist.", "2.6.0", [
+        ("key", "key", ""),
+        ("milliseconds", "integer", ""),
+        ("value", "string", ""),
+    ]),
+    cmd_doc!("set", "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist.", "1.0.0", [
+        ("key", "key", ""),
+        ("value", "string", ""),
+        ("condition", "oneof", "optional"),
+        ("get", "pure-token", "optional"),
+        ("expiration", "oneof", "optional"),
+    ]),
+    cmd_doc!("setex", "Sets the string value and expiration time of a key. Creates the key if it doesn't exist.", "2.0.0", [
+        ("key", "key", ""),
+        ("seconds", "integer", ""),
+        ("value", "string", ""),
+    ]),
+    cmd_doc!("setnx", "Set the string value of a key only when the key doesn't exist.", "1.0.0", [
+        ("key", "key", ""),
+        ("value", "string", ""),
+    ]),
+    cmd_doc!("strlen", "Returns the length of a string value.", "2.2.0", [("key", "key", "")]),
+    // commands::list
+    cmd_doc!("lindex", "Returns an element from a list by its index.", "1.0.0", [
+        ("key", "key", ""),
+        ("index", "integer", ""),
+    ]),
+    cmd_doc!("llen", "Returns the length of a list.", "1.0.0", [("key", "key", "")]),
+    cmd_doc!("lpush", "Prepends one or more elements to a list. Creates the key if it doesn't exist.", "1.0.0", [
+        ("key", "key", ""),
+        ("element", "string", "multiple"),
+    ]),
+    cmd_doc!("lpop", "Returns the first elements in a list after removing it. Deletes the list if the last element was popped.", "1.0.0", [
+        ("key", "key", ""),
+        ("count", "integer", "optional"),
+    ]),
+    cmd_doc!("blpop", "Removes and returns the first element in a list. Blocks until an element is available otherwise.", "2.0.0", [
+        ("key", "key", "multiple"),
+        ("timeout", "double", ""),
+    ]),
+    cmd_doc!("lpos", "Returns the index of matching elements in a list.", "6.0.6", [
+        ("key", "key", ""),
+        ("element", "string", ""),
+        ("rank", "integer", "optional"),
+        ("num-matches", "integer", "optional"),
+        ("len", "integer", "optional"),
+    ]),
+    cmd_doc!("nblpop", "Removes and returns the first element in a list without blocking the connection.", "0.1.0", [
+        ("key", "key", "multiple"),
+        ("timeout", "integer", ""),
+        ("redirect", "integer", ""),
+    ]),
+    cmd_doc!("blmove", "Pops an element from a list, pushes it to another list and returns it. Blocks until an element is available otherwise.", "6.2.0", [
+        ("source", "key", ""),
+        ("destination", "key", ""),
+        ("wherefrom", "oneof", ""),
+        ("whereto", "oneof", ""),
+        ("timeout", "double", ""),
+    ]),
+    // commands::hash
+    cmd_doc!("hdel", "Deletes one or more fields and their values from a hash.", "2.0.0", [
+        ("key", "key", ""),
+        ("field", "string", "multiple"),
+    ]),
+    cmd_doc!("hexists", "Determines whether a field exists in a hash.", "2.0.0", [
+        ("key", "key", ""),
+        ("field", "string", ""),
+    ]),
+    cmd_doc!("hget", "Returns the value of a field in a hash.", "2.0.0", [
+        ("key", "key", ""),
+        ("field", "string", ""),
+    ]),
+    cmd_doc!("hgetall", "Returns all fields and values in a hash.", "2.0.0", [("key", "key", "")]),
+    cmd_doc!("hgetdel", "Returns the values of one or more fields and deletes them from a hash.", "8.0.0", [
+        ("key", "key", ""),
+        ("numfields", "integer", ""),
+        ("field", "string", "multiple"),
+    ]),
+    cmd_doc!("hgetex", "Returns the values of one or more fields. Field expiration options are not supported.", "8.0.0", [
+        ("key", "key", ""),
+        ("persist", "pure-token", "optional"),
+        ("numfields", "integer", ""),
+        ("field", "string", "multiple"),
+    ]),
+    cmd_doc!("hrandfield", "Returns one or more random fields from a hash.", "6.2.0", [
+        ("key", "key", ""),
+        ("options", "block", "optional"),
+    ]),
+    cmd_doc!("hset", "Creates or modifies the value of a field in a hash.", "2.0.0", [
+        ("key", "key", ""),
+        ("data", "block", "multiple"),
+    ]),
+    // commands::set
+    cmd_doc!("sadd", "Adds one or more members to a set. Creates the key if it doesn't exist.", "1.0.0", [
+        ("key", "key", ""),
+        ("member", "string", "multiple"),
+    ]),
+    cmd_doc!("sintercard", "Returns the number of members of the intersect of multiple sets.", "7.0.0", [
+        ("numkeys", "integer", ""),
+        ("key", "key", "multiple"),
+        ("limit", "integer", "optional"),
+    ]),
+    cmd_doc!("smembers", "Returns all members of a set.", "1.0.0", [("key", "key", "")]),
+    // commands::zset
+    cmd_doc!("zintercard", "Returns the number of members of the intersect of multiple sorted sets.", "7.0.0", [
+        ("numkeys", "integer", ""),
+        ("key", "key", "multiple"),
+        ("limit", "integer", "optional"),
+    ]),
+    cmd_doc!("zrandmember", "Returns one or more random members from a sorted set.", "6.2.0", [
+        ("key", "key", ""),
+        ("options", "block", "optional"),
+    ]),
+    // commands::geo
+    cmd_doc!("geoadd", "Adds one or more members to a geospatial index. The key is created if it doesn't exist.", "3.2.0", [
+        ("key", "key", ""),
+        ("condition", "oneof", "optional"),
+        ("change", "pure-token", "optional"),
+        ("data", "block", "multiple"),
+    ]),
+    cmd_doc!("geodist", "Returns the distance between two members of a geospatial index.", "3.2.0", [
+        ("key", "key", ""),
+        ("member1", "string", ""),
+        ("member2", "string", ""),
+        ("unit", "oneof", "optional"),
+    ]),
+    cmd_doc!("geopos", "Returns the longitude and latitude of members from a geospatial index.", "3.2.0", [
+        ("key", "key", ""),
+        ("member", "string", "optional,multiple"),
+    ]),
+    cmd_doc!("geosearch", "Queries a geospatial index for members inside an area of a box or a circle.", "6.2.0", [
+        ("key", "key", ""),
+        ("from", "oneof", ""),
+        ("by", "oneof", ""),
+        ("order", "oneof", "optional"),
+        ("count-block", "block", "optional"),
+        ("withcoord", "pure-token", "optional"),
+        ("withdist", "pure-token", "optional"),
+        ("withhash", "pure-token", "optional"),
+    ]),
+    // commands::stream
+    cmd_doc!("xadd", "Appends a new message to a stream. Creates the key if it doesn't exist.", "5.0.0", [
+        ("key", "key", ""),
+        ("nomkstream", "pure-token", "optional"),
+        ("trim", "block", "optional"),
+        ("id-selector", "oneof", ""),
+        ("data", "block", "multiple"),
+    ]),
+    cmd_doc!("xack", "Returns the number of messages that were successfully acknowledged by the consumer group member of a stream.", "5.0.0", [
+        ("key", "key", ""),
+        ("group", "string", ""),
+        ("id", "string", "multiple"),
+    ]),
+    cmd_doc!("xclaim", "Changes, or acquires, ownership of a message in a consumer group, as if the message was delivered a consumer group member.", "5.0.0", [
+        ("key", "key", ""),
+        ("group", "string", ""),
+        ("consumer", "string", ""),
+        ("min-idle-time", "string", ""),
+        ("id", "string", "multiple"),
+        ("ms", "integer", "optional"),
+        ("unix-time-milliseconds", "unix-time", "optional"),
+        ("count", "integer", "optional"),
+        ("force", "pure-token", "optional"),
+        ("justid", "pure-token", "optional"),
+        ("lastid", "string", "optional"),
+    ]),
+    cmd_doc!("xgroup", "A container for consumer groups commands.", "5.0.0", []),
+    cmd_doc!("xgroup|create", "Creates a consumer group.", "5.0.0", [
+        ("key", "key", ""),
+        ("group", "string", ""),
+        ("id-selector", "oneof", ""),
+        ("mkstream", "pure-token", "optional"),
+    ]),
+    cmd_doc!("xgroup|createconsumer", "Creates a consumer in a consumer group.", "6.2.0", [
+        ("key", "key", ""),
+        ("group", "string", ""),
+        ("consumer", "string", ""),
+    ]),
+    cmd_doc!("xgroup|destroy", "Destroys a consumer group.", "5.0.0", [
+        ("key", "key", ""),
+        ("group", "string", ""),
+    ]),
+    cmd_doc!("xlen", "Return the number of messages in a stream.", "5.0.0", [("key", "key", "")]),
+    cmd_doc!("xpending", "Returns the information and entries from a stream consumer group's pending entries list.", "5.0.0", [
+        ("key", "key", ""),
+        ("group", "string", ""),
+        ("filters", "block", "optional"),
+    ]),
+    cmd_doc!("xrange", "Returns the messages from a stream within a range of IDs.", "5.0.0", [
+        ("key", "key", ""),
+        ("start", "string", ""),
+        ("end", "string", ""),
+        ("count", "integer", "optional"),
+    ]),
+    cmd_doc!("xread", "Returns messages from multiple streams with IDs greater than the ones requested. Blocks until a message is available otherwise.", "5.0.0", [
+        ("count", "integer", "optional"),
+        ("milliseconds", "integer", "optional"),
+        ("streams", "block", ""),
+    ]),
+    cmd_doc!("xreadgroup", "Returns new or historical messages from a stream for a consumer in a group. Blocks until a message is available otherwise.", "5.0.0", [
+        ("group-block", "block", ""),
+        ("count", "integer", "optional"),
+        ("milliseconds", "integer", "optional"),
+        ("noack", "pure-token", "optional"),
+        ("streams", "block", ""),
+    ]),
+    cmd_doc!("xrevrange", "Returns the messages from a stream within a range of IDs in reverse order.", "5.0.0", [
+        ("key", "key", ""),
+        ("end", "string", ""),
+        ("start", "string", ""),
+        ("count", "integer", "optional"),
+    ]),
+    // commands::sort
+    cmd_doc!("sort", "Sorts the elements in a list, a set, or a sorted set, optionally storing the result.", "1.0.0", [
+        ("key", "key", ""),
+        ("pattern", "pattern", "optional"),
+        ("limit", "block", "optional"),
+        ("pattern", "pattern", "optional,multiple"),
+        ("order", "oneof", "optional"),
+        ("sorting", "pure-token", "optional"),
+        ("destination", "key", "optional"),
+    ]),
+    cmd_doc!("sort_ro", "Returns the sorted elements of a list, a set, or a sorted set.", "7.0.0", [
+        ("key", "key", ""),
+        ("pattern", "pattern", "optional"),
+        ("limit", "block", "optional"),
+        ("pattern", "pattern", "optional,multiple"),
+        ("order", "oneof", "optional"),
+        ("sorting", "pure-token", "optional"),
+    ]),
+    // commands::pub_sub
+    cmd_doc!("publish", "Posts a message to a channel.", "2.0.0", [
+        ("channel", "string", ""),
+        ("message", "string", ""),
+    ]),
+    cmd_doc!("subscribe", "Listens for messages published to channels.", "2.0.0", [
+        ("channel", "string", "multiple"),
+    ]),
+    cmd_doc!("unsubscribe", "Stops listening to messages posted to channels.", "2.0.0", [
+        ("channel", "string", "optional,multiple"),
+    ]),
+    // commands::script
+    cmd_doc!("eval", "Executes a server-side Lua script.", "2.6.0", [
+        ("script", "string", ""),
+        ("numkeys", "integer", ""),
+        ("key", "key", "optional,multiple"),
+        ("arg", "string", "optional,multiple"),
+    ]),
+    cmd_doc!("evalname", "Executes a server-side Lua script registered by name.", "0.1.0", [
+        ("name", "string", ""),
+        ("numkeys", "integer", ""),
+        ("key", "key", "optional,multiple"),
+        ("arg", "string", "optional,multiple"),
+    ]),
+    // commands::transaction
+    cmd_doc!("discard", "Discards a transaction.", "2.0.0", []),
+    cmd_doc!("exec", "Executes all commands in a transaction.", "1.2.0", []),
+    cmd_doc!("multi", "Starts a transaction.", "1.2.0", []),
+    cmd_doc!("unwatch", "Forgets about watched keys of a transaction.", "2.2.0", []),
+    cmd_doc!("watch", "Monitors changes to keys to determine the execution of a transaction.", "2.2.0", [
+        ("key", "key", "multiple"),
+    ]),
+    //
+    cmd_doc!("acl", "A container for Access List Control commands.", "6.0.0", []),
+    cmd_doc!("acl|cat", "Lists the ACL categories, or the commands inside a category.", "6.0.0", [
+        ("category", "string", "optional"),
+    ]),
+    cmd_doc!("acl|deluser", "Deletes ACL users, and terminates their connections.", "6.0.0", [
+        ("username", "string", "multiple"),
+    ]),
+    cmd_doc!("acl|setuser", "Creates and modifies an ACL user and its rules.", "6.0.0", [
+        ("username", "string", ""),
+        ("rule", "string", "optional,multiple"),
+    ]),
+    cmd_doc!("acl|users", "Lists all ACL users.", "6.0.0", []),
+    cmd_doc!("acl|whoami", "Returns the authenticated username of the current connection.", "6.0.0", []),
+    cmd_doc!("acl|save", "Saves the effective ACL rules in the configured ACL file.", "6.0.0", []),
+    cmd_doc!("acl|load", "Reloads the rules from the configured ACL file.", "6.0.0", []),
+    //
+    cmd_doc!("client", "A container for client connection commands.", "2.4.0", []),
+    cmd_doc!("client|id", "Returns the unique client ID of the connection.", "5.0.0", []),
+    cmd_doc!("client|info", "Returns information about the connection.", "6.2.0", []),
+    cmd_doc!("client|kill", "Terminates open connections.", "2.4.0", [
+        ("ip:port", "string", "optional"),
+        ("filter", "block", "optional,multiple"),
+    ]),
+    cmd_doc!("client|tracking", "Controls server-assisted client-side caching for the connection.", "6.0.0", [
+        ("status", "oneof", ""),
+        ("client-id", "integer", "optional"),
+    ]),
+    //
+    cmd_doc!("command", "A container for command introspection commands.", "2.8.13", []),
+    cmd_doc!("command|docs", "Returns documentary information about one, multiple or all commands.", "7.0.0", [
+        ("command-name", "string", "optional,multiple"),
+    ]),
+    cmd_doc!("command|info", "Returns information about one, multiple or all commands.", "2.8.13", [
+        ("command-name", "string", "optional,multiple"),
+    ]),
+    //
+    cmd_doc!("config", "A container for server configuration commands.", "2.0.0", []),
+    cmd_doc!("config|set", "Sets configuration parameters in-flight.", "2.0.0", [
+        ("data", "block", "multiple"),
+    ]),
+    cmd_doc!("config|rewrite", "Persists the effective configuration to file.", "2.8.0", []),
+    cmd_doc!("config|resetstat", "Resets the server's statistics.", "2.0.0", []),
+    //
+    cmd_doc!("memory", "A container for memory diagnostics commands.", "4.0.0", []),
+    cmd_doc!("memory|doctor", "Outputs a memory problems report.", "4.0.0", []),
+    cmd_doc!("memory|stats", "Returns details about memory usage.", "4.0.0", []),
+    //
+    cmd_doc!("debug", "A container for debugging commands.", "1.0.0", []),
+    cmd_doc!("debug|change-repl-id", "Changes the replication ID of the server.", "0.1.0", []),
+    cmd_doc!("debug|set-active-expire", "Enables or disables the active expiration of keys.", "0.1.0", [
+        ("flag", "integer", ""),
+    ]),
+    cmd_doc!("debug|quicklist-packed-threshold", "Sets the size threshold above which list elements are stored in plain nodes.", "0.1.0", [
+        ("size", "integer", ""),
+    ]),
+    cmd_doc!("debug|stringmatch-len", "Matches a string against a glob-style pattern.", "0.1.0", [
+        ("pattern", "pattern", ""),
+        ("string", "string", ""),
+    ]),
+    cmd_doc!("debug|object", "Returns debugging information about a key.", "1.0.0", [
+        ("encoding-force", "pure-token", "optional"),
+        ("key", "key", ""),
+        ("encoding", "string", "optional"),
+    ]),
+    cmd_doc!("debug|sleep", "Suspends the connection for the given number of seconds.", "1.0.0", [
+        ("seconds", "double", ""),
+    ]),
+    cmd_doc!("debug|sleep-block", "Suspends all connections for the given number of seconds.", "0.1.0", [
+        ("seconds", "double", ""),
+    ]),
+    cmd_doc!("debug|load-file", "Loads key-value pairs from a TSV or binary file as strings.", "0.1.0", [
+        ("path", "string", ""),
+        ("format", "oneof", ""),
+        ("seconds", "integer", "optional"),
+    ]),
+    cmd_doc!("debug|panic", "Panics the task of the current connection.", "0.1.0", []),
+    cmd_doc!("debug|oom", "Simulates an allocation failure on the current connection.", "0.1.0", []),
+    cmd_doc!("debug|reload", "Saves the dataset to the RDB file and reloads it.", "0.1.0", [
+        ("nosave", "pure-token", "optional"),
+    ]),
+    cmd_doc!("debug|flushall", "Removes all keys without persisting the change.", "0.1.0", []),
+    //
+    cmd_doc!("object", "A container for object introspection commands.", "2.2.3", []),
+    cmd_doc!("object|encoding", "Returns the internal encoding of an object.", "2.2.3", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("object|freq", "Returns the logarithmic access frequency counter of an object.", "4.0.0", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("object|idletime", "Returns the time since the last access to an object.", "2.2.3", [
+        ("key", "key", ""),
+    ]),
+    cmd_doc!("object|refcount", "Returns the reference count of a value of a key.", "2.2.3", [
+        ("key", "key", ""),
+    ]),
+    //
+    cmd_doc!("script", "A container for Lua scripts management commands.", "2.6.0", []),
+    cmd_doc!("script|exists", "Determines whether server-side Lua scripts exist in the script cache.", "2.6.0", [
+        ("name", "string", "multiple"),
+    ]),
+    cmd_doc!("script|flush", "Removes all server-side Lua scripts from the script cache.", "2.6.0", []),
+    cmd_doc!("script|register", "Registers a server-side Lua script by name.", "0.1.0", [
+        ("name", "string", ""),
+        ("script", "string", ""),
+    ]),
+];
+
+/// 通过命令名(大小写不敏感)获取命令的元数据
+pub fn cmd_info(cmd_name: &[u8]) -> Option<&'static CmdInfo> {
+    CMD_INFOS
+        .iter()
+        .find(|info| info.name.as_bytes().eq_ignore_ascii_case(cmd_name))
+}
+
+impl CmdInfo {
+    fn new<C: CmdExecutor>(group: Option<&str>) -> Self {
+        // 子命令的NAME为命令组名与子命令名的拼接，例如`SCRIPTEXISTS`
+        let name = match group {
+            Some(group) => format!("{}|{}", group, &C::NAME[group.len()..]),
+            None => C::NAME.to_string(),
+        };
+        let (first_key, last_key, key_step) = C::KEY_RANGE;
+
+        Self {
+            name: name.to_lowercase(),
+            flag: C::FLAG,
+            arity: C::ARITY,
+            first_key,
+            last_key,
+            key_step,
+        }
+    }
+
+    fn container(group: &str) -> Self {
+        Self {
+            name: group.to_lowercase(),
+            flag: 0,
+            arity: -2,
+            first_key: 0,
+            last_key: 0,
+            key_step: 0,
+        }
+    }
+
+    pub fn is_container(&self) -> bool {
+        self.flag == 0
+    }
+
+    /// 命令所属的ACL类别
+    pub fn categories(&self) -> impl Iterator<Item = &'static str> + '_ {
+        ACL_CATEGORIES
+            .iter()
+            .filter(|cat| cat.flag & self.flag != 0)
+            .map(|cat| cat.name)
+    }
+
+    /// 命令的flags，由命令所属的ACL类别推导得出
+    pub fn flags(&self) -> Vec<&'static str> {
+        self.categories()
+            .filter_map(|cat| match cat {
+                "WRITE" => Some("write"),
+                "READ" => Some("readonly"),
+                "ADMIN" => Some("admin"),
+                "PUBSUB" => Some("pubsub"),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 命令所属的组，由命令所属的ACL类别推导得出。命令组的组与其子命令相同
+    pub fn group(&self) -> &'static str {
+        if self.is_container() {
+            return self.subcommands().next().map_or("server", CmdInfo::group);
+        }
+
+        let categories: Vec<_> = self.categories().collect();
+        // 数据类型相关的类别优先
+        for (cat, group) in [
+            ("STRING", "string"),
+            ("LIST", "list"),
+            ("HASH", "hash"),
+            ("PUBSUB", "pubsub"),
+            ("SCRIPTING", "scripting"),
+            ("KEYSPACE", "generic"),
+            ("ADMIN", "server"),
+            ("CONNECTION", "connection"),
+        ] {
+            if categories.contains(&cat) {
+                return group;
+            }
+        }
+
+        "server"
+    }
+
+    pub fn doc(&self) -> Option<&'static CmdDoc> {
+        CMD_DOCS.iter().find(|doc| self.name == doc.name)
+    }
+
+    /// 返回COMMAND DOCS中命令的文档：summary, since, group, arguments
+    pub fn to_doc_resp3(&self) -> Resp3 {
+        let blob = |s: &'static str| Resp3::new_blob_string(Bytes::from_static(s.as_bytes()));
+        let map = |entries: Vec<(&'static str, Resp3)>| {
+            Resp3::new_map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (blob(k), v))
+                    .collect::<AHashMap<_, _>>(),
+            )
+        };
+
+        let mut entries = vec![("group", blob(self.group()))];
+        if let Some(doc) = self.doc() {
+            entries.push(("summary", blob(doc.summary)));
+            entries.push(("since", blob(doc.since)));
+
+            let arguments: Vec<_> = doc
+                .arguments
+                .iter()
+                .map(|(name, typ, flags)| {
+                    let mut arg = vec![("name", blob(name)), ("type", blob(typ))];
+                    if !flags.is_empty() {
+                        arg.push((
+                            "flags",
+                            Resp3::new_array(
+                                flags
+                                    .split(',')
+                                    .map(|f| Resp3::new_simple_string(ByteString::from_static(f)))
+                                    .collect::<Vec<_>>(),
+                            ),
+                        ));
+                    }
+                    map(arg)
+                })
+                .collect();
+            if !arguments.is_empty() {
+                entries.push(("arguments", Resp3::new_array(arguments)));
+            }
+        }
+
+        map(entries)
+    }
+
+    pub fn subcommands(&self) -> impl Iterator<Item = &'static CmdInfo> + '_ {
+        CMD_INFOS.iter().filter(move |info| {
+            self.is_container()
+                && info
+                    .name
+                    .strip_prefix(self.name.as_str())
+                    .is_some_and(|sub| sub.starts_with('|'))
+        })
+    }
+
+    /// 返回COMMAND INFO的10个字段：name, arity, flags, first key, last key, key step,
+    /// acl categories, tips, key specs, subcommands
+    pub fn to_resp3(&self) -> Resp3 {
+        let simple_string = |s: String| Resp3::new_simple_string(ByteString::from(s));
+
+        let flags: AHashSet<_> = self
+            .flags()
+            .into_iter()
+            .map(|flag| simple_string(flag.to_string()))
+            .collect();
+
+        let categories: AHashSet<_> = self
+            .categories()
+            .map(|cat| simple_string(format!("@{}", cat.to_lowercase())))
+            .collect();
+
+        let key_specs = if self.first_key > 0 {
+            vec![self.key_spec()]
+        } else {
+            vec![]
+        };
+
+        Resp3::new_array(vec![
+            Resp3::new_blob_string(Bytes::copy_from_slice(self.name.as_bytes())),
+            Resp3::new_integer(self.arity),
+            Resp3::new_set(flags),
+            Resp3::new_integer(self.first_key),
+            Resp3::new_integer(self.last_key),
+            Resp3::new_integer(self.key_step),
+            Resp3::new_set(categories),
+            Resp3::new_array(vec![]),
+            Resp3::new_array(key_specs),
+            Resp3::new_array(
+                self.subcommands()
+                    .map(CmdInfo::to_resp3)
+                    .collect::<Vec<_>>(),
+            ),
+        ])
+    }
+
+    fn key_spec(&self) -> Resp3 {
+        let map = |entries: Vec<(&'static str, Resp3)>| {
+            Resp3::new_map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (Resp3::new_blob_string(Bytes::from_static(k.as_bytes())), v))
+                    .collect::<AHashMap<_, _>>(),
+            )
+        };
+        let blob = |s: &'static str| Resp3::new_blob_string(Bytes::from_static(s.as_bytes()));
+
+        // lastkey为负数时表示从末尾开始计数，否则为相对于first_key的偏移量
+        let last_key = if self.last_key < 0 {
+            self.last_key
+        } else {
+            self.last_key - self.first_key
+        };
+
+        map(vec![
+            (
+                "begin_search",
+                map(vec![
+                    ("type", blob("index")),
+                    (
+                        "spec",
+                        map(vec![("index", Resp3::new_integer(self.first_key))]),
+                    ),
+                ]),
+            ),
+            (
+                "find_keys",
+                map(vec![
+                    ("type", blob("range")),
+                    (
+                        "spec",
+                        map(vec![
+                            ("lastkey", Resp3::new_integer(last_key)),
+                            ("keystep", Resp3::new_integer(self.key_step)),
+                            ("limit", Resp3::new_integer(0)),
+                        ]),
+                    ),
+                ]),
+            ),
+        ])
+    }
+}
@@ -1,17 +1,20 @@
 pub mod commands;
 pub mod error;
+pub mod info;
 
 pub use error::*;
+pub use info::*;
 
 use crate::{
     conf::AccessControl,
     connection::AsyncStream,
     frame::Resp3,
     server::{Handler, ServerError},
-    util, CmdFlag,
+    util, CmdFlag, Int,
 };
 use bytes::Bytes;
 use commands::*;
+use std::time::{Duration, Instant};
 use tracing::instrument;
 
 #[allow(async_fn_in_trait)]
@@ -19,22 +22,120 @@ pub trait CmdExecutor: Sized + std::fmt::Debug {
     const NAME: &'static str;
     const TYPE: CmdType;
     const FLAG: CmdFlag;
+    /// 命令的参数个数(包括命令名)，负数表示参数个数至少为其绝对值
+    const ARITY: Int;
+    /// 键在参数中的位置(first_key, last_key, key_step)，命令名的位置为0。last_key为负数时
+    /// 表示从末尾开始计数，三者均为0时表示命令不接受键
+    const KEY_RANGE: (Int, Int, Int) = (0, 0, 0);
 
     #[inline]
     async fn apply(
         mut args: CmdUnparsed,
         handler: &mut Handler<impl AsyncStream>,
     ) -> Result<Option<Resp3>, CmdError> {
-        // 检查是否有权限执行该命令
-        if handler.context.ac.is_forbidden_cmd(Self::FLAG) {
+        // 未认证的客户端只能执行AUTH和HELLO(只有带有AUTH的HELLO才能成功，见Hello)。default
+        // 用户的密码被移除后，视为已认证
+        if !handler.context.authenticated {
+            if handler
+                .shared
+                .conf()
+                .security
+                .default_ac
+                .load()
+                .password
+                .is_empty()
+            {
+                handler.context.authenticated = true;
+            } else if Self::FLAG & (AUTH_FLAG | HELLO_FLAG) == 0 {
+                return Err(Err::NoAuth.into());
+            }
+        }
+
+        // 检查是否有权限执行该命令，基本规则或任意一个selector允许即可
+        if !handler.context.ac.permits_cmd(Self::FLAG) {
             return Err(Err::NoPermission.into());
         }
 
-        let cmd = Self::parse(&mut args, &handler.context.ac)?;
+        // 尚未实现淘汰，超过maxmemory时相当于noeviction策略：拒绝可能增加内存占用的命令，
+        // 读命令和删除命令仍可执行。在事务中排队时拒绝会使EXEC放弃整个事务
+        if Self::FLAG & DENY_OOM_FLAG != 0 && handler.shared.conf().memory.is_oom() {
+            return Err(Err::OutOfMemory.into());
+        }
+
+        // 事务中的命令只检查参数并排队，执行EXEC时才会执行
+        if handler.context.transaction.is_some()
+            && Self::FLAG & (MULTI_FLAG | EXEC_FLAG | DISCARD_FLAG | WATCH_FLAG) == 0
+        {
+            handler
+                .context
+                .ac
+                .parse_permitted(Self::FLAG, &mut args.clone(), Self::parse)?;
+            if let Some(tx) = handler.context.transaction.as_mut() {
+                tx.queued.push(args.into());
+            }
+
+            return Ok(Some(Resp3::new_simple_string("QUEUED".into())));
+        }
+
+        let cmd = handler
+            .context
+            .ac
+            .parse_permitted(Self::FLAG, &mut args, Self::parse)?;
 
-        let res = cmd.execute(handler).await?;
+        // 命令执行前wcmd_buf的长度，命令执行失败时据此回滚
+        let wcmd_mark = handler.context.wcmd_buf.len();
+        // 在写命令传播之前，期间过期的键的DEL命令不能传播。阻塞的写命令(例如BLPOP)在阻塞
+        // 之前释放，之后只在修改数据时重新hold
+        if Self::TYPE == CmdType::Write && !handler.context.from_master {
+            handler
+                .shared
+                .wcmd_propagator()
+                .hold_wcmd(&mut handler.context.wcmd_held);
+        }
+
+        // 只有注册了观察者时才计时
+        let start = handler.shared.observer().map(|_| Instant::now());
+        let timeout = handler.shared.conf().server.command_timeout_ms;
+        let res = if timeout == 0 || Self::FLAG & NO_TIMEOUT_FLAG != 0 {
+            cmd.execute(handler).await
+        } else {
+            let timeout = Duration::from_millis(timeout);
+            handler.context.deadline = Some(tokio::time::Instant::now() + timeout);
+            // 写命令在任意await处被取消都可能只执行了一部分(或者执行了却未被传播)，因此
+            // 只能由命令自己在修改数据之前检查截止时间
+            let res = if Self::TYPE == CmdType::Write || Self::FLAG & SELF_TIMEOUT_FLAG != 0 {
+                cmd.execute(handler).await
+            } else {
+                tokio::time::timeout(timeout, cmd.execute(handler))
+                    .await
+                    .unwrap_or_else(|_| Err(Err::Timeout.into()))
+            };
+            handler.context.deadline = None;
+            res
+        };
+        // 命令执行期间被移除的键，在释放锁之后才调用expire hook
+        handler.shared.db().deliver_expire_events();
+        if let (Some(start), Some(observer)) = (start, handler.shared.observer()) {
+            let err = matches!(res, Err(CmdError::Err { .. } | CmdError::ServerErr { .. }));
+            observer.on_command(Self::NAME, start.elapsed(), err);
+        }
+        // 命令可能将自身改写为确定性的形式(例如将相对过期时间改写为绝对过期时间)，
+        // 以保证replica和AOF中的结果与master一致
+        let rewrite = handler.context.wcmd_rewrite.take();
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                // 只回滚写命令。EXEC等命令中已经执行成功的子命令必须传播，否则replica会与master不一致
+                if Self::TYPE == CmdType::Write {
+                    handler.context.rollback_wcmd(wcmd_mark);
+                }
+                return Err(e);
+            }
+        };
 
-        if Self::TYPE == CmdType::Write {
+        // 来自master的命令已经被master传播过，不再重复传播
+        if Self::TYPE == CmdType::Write && !handler.context.from_master {
+            let args = rewrite.unwrap_or(args);
             // 也许存在replicate需要传播
             handler
                 .shared
@@ -62,6 +163,16 @@ pub trait CmdExecutor: Sized + std::fmt::Debug {
     fn parse(args: &mut CmdUnparsed, ac: &AccessControl) -> Result<Self, CmdError>;
 }
 
+/// 超过command_timeout_ms设置的截止时间时返回超时错误。同步执行的长循环(例如KEYS，
+/// SORT)不会被tokio::time::timeout中断，需要在循环中调用。写命令只能在修改数据之前调用
+#[inline]
+pub fn check_deadline(deadline: Option<tokio::time::Instant>) -> CmdResult<()> {
+    match deadline {
+        Some(deadline) if tokio::time::Instant::now() >= deadline => Err(Err::Timeout.into()),
+        _ => Ok(()),
+    }
+}
+
 #[derive(PartialEq)]
 pub enum CmdType {
     Read,
@@ -74,15 +185,111 @@ pub async fn dispatch(
     cmd_frame: Resp3,
     handler: &mut Handler<impl AsyncStream>,
 ) -> Result<Option<Resp3>, ServerError> {
-    match _dispatch(cmd_frame, handler).await {
+    // DEBUG SLEEP-BLOCK期间，所有连接的命令都需要等待
+    handler.shared.wait_unpaused().await;
+    handler.context.cmd_count += 1;
+
+    let res = _dispatch(cmd_frame, handler).await;
+
+    // 批处理中的写命令会被缓存到wcmd_buf，直到最后一个命令时才传播。如果最后一个命令不是
+    // 写命令或者执行失败，则由此处传播，避免缓存的写命令滞留到之后的批处理
+    if (!handler.context.wcmd_buf.is_empty() || handler.context.wcmd_held)
+        && handler.conn.unhandled_count() <= 1
+    {
+        handler
+            .shared
+            .wcmd_propagator()
+            .clone()
+            .propagate_buffered(handler)
+            .await;
+    }
+
+    match res {
         Ok(res) => Ok(res),
         Err(e) => {
+            // 事务中的命令排队失败(例如未知命令或参数错误)时，EXEC会放弃执行整个事务
+            if let (Some(tx), CmdError::Err { .. }) = (handler.context.transaction.as_mut(), &e) {
+                tx.tx_dirty = true;
+            }
+
             let frame = e.try_into()?; // 尝试将错误转换为RESP3
             Ok(Some(frame))
         }
     }
 }
 
+/// 所有注册的命令，dispatch以及COMMAND INFO等命令的元数据均由该列表生成。
+/// 以`$callback!($args, 命令列表)`的形式展开，命令列表中先是普通命令，然后是
+/// `"命令组" => 子命令`
+macro_rules! for_each_cmd {
+    ($callback:ident $(, $arg:expr)*) => {
+        $callback!(
+            $($arg,)*
+            // commands::other
+            BgSave, Ping, Echo, Auth, Hello, Info, ReplicaOf, Time, Lolwut,
+
+            // commands::key
+            DbSize, Del, Dump, Exists, Expire, ExpireAt, ExpireTime, Keys, Migrate, NBKeys, PExpireAt,
+            Persist, Pttl, Restore, Ttl, Type,
+
+            // commands::str
+            Append, BitField, Decr, DecrBy, Get, GetRange, GetSet, Incr, IncrBy, MGet, MSet,
+            MSetNx, PSetEx, Set, SetEx, SetNx, StrLen,
+
+            // commands::list
+            LIndex, LLen, LPush, LPop, BLPop, LPos, NBLPop, BLMove,
+
+            // commands::hash
+            HDel, HExists, HGet, HGetAll, HGetDel, HGetEx, HRandField, HSet,
+
+            // commands::set
+            SAdd, SInterCard, SMembers,
+
+            // commands::zset
+            ZInterCard, ZRandMember,
+
+            // commands::geo
+            GeoAdd, GeoDist, GeoPos, GeoSearch,
+
+            // commands::stream
+            XAck, XAdd, XClaim, XLen, XPending, XRange, XRead, XReadGroup, XRevRange,
+
+            // commands::sort
+            Sort, SortRo,
+
+            // commands::pub_sub
+            Publish, Subscribe, Unsubscribe,
+
+            // commands::script
+            Eval, EvalName,
+
+            // commands::transaction
+            Discard, Exec, Multi, Unwatch, Watch;
+
+            "ACL" => AclCat, AclDelUser, AclSetUser, AclUsers, AclWhoAmI, AclSave, AclLoad;
+
+            "CLIENT" => ClientId, ClientInfo, ClientKill, ClientTracking;
+
+            "COMMAND" => CommandDocs, CommandInfo;
+
+            "CONFIG" => ConfigSet, ConfigRewrite, ConfigResetStat;
+
+            "MEMORY" => MemoryDoctor, MemoryStats;
+
+            "XGROUP" => XGroupCreate, XGroupCreateConsumer, XGroupDestroy;
+
+            "DEBUG" => DebugChangeReplId, DebugSetActiveExpire, DebugQuicklistPackedThreshold,
+                DebugStringMatchLen, DebugObject, DebugSleep, DebugSleepBlock, DebugLoadFile,
+                DebugPanic, DebugOom, DebugReload, DebugFlushAll;
+
+            "OBJECT" => ObjectEncoding, ObjectFreq, ObjectIdleTime, ObjectRefCount;
+
+            "SCRIPT" => ScriptExists, ScriptFlush, ScriptRegister
+        )
+    };
+}
+pub(crate) use for_each_cmd;
+
 #[inline]
 #[instrument(level = "debug", skip(handler), err, ret)]
 pub async fn _dispatch(
@@ -94,6 +301,8 @@ pub async fn _dispatch(
             {
                 let mut buf = [0; 32];
                 let cmd_name = $cmd.next().ok_or(Err::Syntax)?;
+                // 命令可能被重命名或禁用
+                let cmd_name = $handler.shared.conf().security.rename_commands.resolve(cmd_name)?;
 
                 debug_assert!(cmd_name.len() <= buf.len());
                 let len1 = util::uppercase(&cmd_name, &mut buf).unwrap();
@@ -134,38 +343,22 @@ pub async fn _dispatch(
         };
     }
 
-    let mut cmd: CmdUnparsed = cmd_frame.try_into()?;
-
-    dispatch_command!(
-        cmd,
-        handler,
-        // commands::other
-        BgSave, Ping, Echo, Auth,
-
-        // commands::key
-        Del, Dump, Exists, Expire, ExpireAt, ExpireTime, Keys, NBKeys, Persist,
-        Pttl, Ttl, Type,
-
-        // commands::str
-        Append, Decr, DecrBy, Get, GetRange, GetSet, Incr, IncrBy, MGet, MSet,
-        MSetNx, Set, SetEx, SetNx, StrLen,
-
-        // commands::list
-        LLen, LPush, LPop, BLPop, LPos, NBLPop, BLMove,
-
-        // commands::hash
-        HDel, HExists, HGet, HSet,
-
-        // commands::pub_sub
-        Publish, Subscribe, Unsubscribe,
-
-        // commands::script
-        Eval, EvalName;
-
-        "CLIENT" => ClientTracking;
+    let mut cmd: CmdUnparsed = match cmd_frame {
+        // inline形式的HELLO命令会被解码为Resp3::Hello
+        Resp3::Hello { version, auth } => {
+            let mut args = vec![
+                Bytes::from_static(b"HELLO"),
+                Bytes::copy_from_slice(itoa::Buffer::new().format(version).as_bytes()),
+            ];
+            if let Some((username, password)) = auth {
+                args.extend([Bytes::from_static(b"AUTH"), username, password]);
+            }
+            args.into()
+        }
+        cmd_frame => cmd_frame.try_into()?,
+    };
 
-        "SCRIPT" => ScriptExists, ScriptFlush, ScriptRegister
-    )
+    for_each_cmd!(dispatch_command, cmd, handler)
 }
 
 pub fn cmd_name_to_flag(cmd_name: &[u8]) -> anyhow::Result<CmdFlag> {
@@ -190,8 +383,16 @@ pub fn cmd_name_to_flag(cmd_name: &[u8]) -> anyhow::Result<CmdFlag> {
         BgSave,
         Ping,
         Echo,
+        Time,
+        Lolwut,
+        MemoryDoctor,
+        MemoryStats,
         Auth,
+        Hello,
+        Info,
+        ReplicaOf,
         // commands::key
+        DbSize,
         Del,
         Dump,
         Exists,
@@ -199,13 +400,17 @@ pub fn cmd_name_to_flag(cmd_name: &[u8]) -> anyhow::Result<CmdFlag> {
         ExpireAt,
         ExpireTime,
         Keys,
+        Migrate,
         NBKeys,
+        PExpireAt,
         Persist,
         Pttl,
+        Restore,
         Ttl,
         Type,
         // commands::str
         Append,
+        BitField,
         Decr,
         DecrBy,
         Get,
@@ -216,11 +421,13 @@ pub fn cmd_name_to_flag(cmd_name: &[u8]) -> anyhow::Result<CmdFlag> {
         MGet,
         MSet,
         MSetNx,
+        PSetEx,
         Set,
         SetEx,
         SetNx,
         StrLen,
         // commands::list
+        LIndex,
         LLen,
         LPush,
         LPop,
@@ -232,7 +439,39 @@ pub fn cmd_name_to_flag(cmd_name: &[u8]) -> anyhow::Result<CmdFlag> {
         HDel,
         HExists,
         HGet,
+        HGetAll,
+        HRandField,
+        HGetDel,
+        HGetEx,
         HSet,
+        // commands::set
+        SAdd,
+        SInterCard,
+        SMembers,
+        // commands::zset
+        ZInterCard,
+        ZRandMember,
+        // commands::geo
+        GeoAdd,
+        GeoDist,
+        GeoPos,
+        GeoSearch,
+        // commands::stream
+        XAck,
+        XAdd,
+        XClaim,
+        XGroupCreate,
+        XGroupCreateConsumer,
+        XGroupDestroy,
+        XLen,
+        XPending,
+        XRange,
+        XRead,
+        XReadGroup,
+        XRevRange,
+        // commands::sort
+        Sort,
+        SortRo,
         // commands::pub_sub
         Publish,
         Subscribe,
@@ -240,9 +479,51 @@ pub fn cmd_name_to_flag(cmd_name: &[u8]) -> anyhow::Result<CmdFlag> {
         // commands::script
         Eval,
         EvalName,
+        // commands::transaction
+        Discard,
+        Exec,
+        Multi,
+        Unwatch,
+        Watch,
         //
+        AclCat,
+        AclDelUser,
+        AclSetUser,
+        AclUsers,
+        AclWhoAmI,
+        AclSave,
+        AclLoad,
+        //
+        ClientId,
+        ClientInfo,
+        ClientKill,
         ClientTracking,
         //
+        CommandDocs,
+        CommandInfo,
+        //
+        ConfigSet,
+        ConfigRewrite,
+        ConfigResetStat,
+        //
+        DebugChangeReplId,
+        DebugSetActiveExpire,
+        DebugQuicklistPackedThreshold,
+        DebugStringMatchLen,
+        DebugObject,
+        DebugSleep,
+        DebugSleepBlock,
+        DebugLoadFile,
+        DebugPanic,
+        DebugOom,
+        DebugReload,
+        DebugFlushAll,
+        //
+        ObjectEncoding,
+        ObjectFreq,
+        ObjectIdleTime,
+        ObjectRefCount,
+        //
         ScriptExists,
         ScriptFlush,
         ScriptRegister
@@ -269,8 +550,16 @@ pub fn flag_to_cmd_names(flag: CmdFlag) -> Result<Vec<&'static str>, &'static st
         BgSave,
         Ping,
         Echo,
+        Time,
+        Lolwut,
+        MemoryDoctor,
+        MemoryStats,
         Auth,
+        Hello,
+        Info,
+        ReplicaOf,
         // commands::key
+        DbSize,
         Del,
         Dump,
         Exists,
@@ -278,13 +567,17 @@ pub fn flag_to_cmd_names(flag: CmdFlag) -> Result<Vec<&'static str>, &'static st
         ExpireAt,
         ExpireTime,
         Keys,
+        Migrate,
         NBKeys,
+        PExpireAt,
         Persist,
         Pttl,
+        Restore,
         Ttl,
         Type,
         // commands::str
         Append,
+        BitField,
         Decr,
         DecrBy,
         Get,
@@ -295,11 +588,13 @@ pub fn flag_to_cmd_names(flag: CmdFlag) -> Result<Vec<&'static str>, &'static st
         MGet,
         MSet,
         MSetNx,
+        PSetEx,
         Set,
         SetEx,
         SetNx,
         StrLen,
         // commands::list
+        LIndex,
         LLen,
         LPush,
         LPop,
@@ -311,7 +606,39 @@ pub fn flag_to_cmd_names(flag: CmdFlag) -> Result<Vec<&'static str>, &'static st
         HDel,
         HExists,
         HGet,
+        HGetAll,
+        HRandField,
+        HGetDel,
+        HGetEx,
         HSet,
+        // commands::set
+        SAdd,
+        SInterCard,
+        SMembers,
+        // commands::zset
+        ZInterCard,
+        ZRandMember,
+        // commands::geo
+        GeoAdd,
+        GeoDist,
+        GeoPos,
+        GeoSearch,
+        // commands::stream
+        XAck,
+        XAdd,
+        XClaim,
+        XGroupCreate,
+        XGroupCreateConsumer,
+        XGroupDestroy,
+        XLen,
+        XPending,
+        XRange,
+        XRead,
+        XReadGroup,
+        XRevRange,
+        // commands::sort
+        Sort,
+        SortRo,
         // commands::pub_sub
         Publish,
         Subscribe,
@@ -319,9 +646,51 @@ pub fn flag_to_cmd_names(flag: CmdFlag) -> Result<Vec<&'static str>, &'static st
         // commands::script
         Eval,
         EvalName,
+        // commands::transaction
+        Discard,
+        Exec,
+        Multi,
+        Unwatch,
+        Watch,
+        //
+        AclCat,
+        AclDelUser,
+        AclSetUser,
+        AclUsers,
+        AclWhoAmI,
+        AclSave,
+        AclLoad,
         //
+        ClientId,
+        ClientInfo,
+        ClientKill,
         ClientTracking,
         //
+        CommandDocs,
+        CommandInfo,
+        //
+        ConfigSet,
+        ConfigRewrite,
+        ConfigResetStat,
+        //
+        DebugChangeReplId,
+        DebugSetActiveExpire,
+        DebugQuicklistPackedThreshold,
+        DebugStringMatchLen,
+        DebugObject,
+        DebugSleep,
+        DebugSleepBlock,
+        DebugLoadFile,
+        DebugPanic,
+        DebugOom,
+        DebugReload,
+        DebugFlushAll,
+        //
+        ObjectEncoding,
+        ObjectFreq,
+        ObjectIdleTime,
+        ObjectRefCount,
+        //
         ScriptExists,
         ScriptFlush,
         ScriptRegister
@@ -330,7 +699,7 @@ pub fn flag_to_cmd_names(flag: CmdFlag) -> Result<Vec<&'static str>, &'static st
     Ok(names)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CmdUnparsed {
     inner: Vec<Resp3>,
     start: usize,
@@ -340,8 +709,12 @@ pub struct CmdUnparsed {
 impl CmdUnparsed {
     #[inline]
     pub fn len(&self) -> usize {
-        // self.end - self.start + 1
-        self.end.saturating_sub(self.start) + 1
+        // 参数为空时start > end
+        if self.is_empty() {
+            0
+        } else {
+            self.end - self.start + 1
+        }
     }
 
     pub const fn is_empty(&self) -> bool {
@@ -446,6 +819,21 @@ impl From<&[&str]> for CmdUnparsed {
     }
 }
 
+impl From<Vec<Bytes>> for CmdUnparsed {
+    fn from(val: Vec<Bytes>) -> Self {
+        let inner: Vec<_> = val.into_iter().map(Resp3::new_blob_string).collect();
+        if inner.is_empty() {
+            Self::default()
+        } else {
+            Self {
+                end: inner.len() - 1,
+                inner,
+                start: 0,
+            }
+        }
+    }
+}
+
 impl From<CmdUnparsed> for Resp3 {
     #[inline]
     fn from(val: CmdUnparsed) -> Self {
@@ -1,19 +1,226 @@
-use serde::Deserialize;
+use crate::util::{allocated_memory, atoi};
+use serde::{Deserialize, Deserializer};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "memory")]
 pub struct MemoryConf {
-    pub max_memory: u64,
+    // 最大内存，单位为字节，0表示不限制。配置文件中可以使用字符串形式的带单位的值或者系统总内存
+    // 的百分比(见[`parse_memory_size()`])。可通过CONFIG SET修改
+    #[serde(deserialize_with = "deserialize_memory_size")]
+    pub max_memory: AtomicU64,
     // pub max_memory_policy: String,
     // pub max_memory_samples: u64,
+    // Hash的元素数量超过该值时，由ziplist编码转换为hashtable编码。可通过CONFIG SET修改
+    pub hash_max_listpack_entries: AtomicUsize,
+    // Hash的field或value的长度超过该值时，由ziplist编码转换为hashtable编码。可通过CONFIG SET修改
+    pub hash_max_listpack_value: AtomicUsize,
+    // List的元素数量超过该值时，OBJECT ENCODING返回quicklist。可通过CONFIG SET修改
+    pub list_max_listpack_entries: AtomicUsize,
+    // List的元素长度达到该值时会单独成为一个节点，OBJECT ENCODING返回quicklist。可通过
+    // DEBUG QUICKLIST-PACKED-THRESHOLD修改
+    pub list_packed_threshold: AtomicUsize,
+    // Set的元素均为整数且数量不超过该值时使用intset编码。可通过CONFIG SET修改
+    pub set_max_intset_entries: AtomicUsize,
+    // 因DEL，淘汰或过期而移除的大对象是否在后台线程中释放
+    pub lazyfree_lazy_user_del: bool,
+    pub lazyfree_lazy_eviction: bool,
+    pub lazyfree_lazy_expire: bool,
+    // 被新值覆盖(例如SET覆盖已有的键)的大对象是否在后台线程中释放
+    #[serde(default)]
+    pub lazyfree_lazy_server_del: bool,
+    // 对象的编码改变时是否向__keyevent@0__:encoding-change频道发布键名(非标准)
+    #[serde(default)]
+    pub notify_encoding_change: bool,
 }
 
 impl Default for MemoryConf {
     fn default() -> Self {
         Self {
-            max_memory: 1024 * 1024 * 4,
+            max_memory: AtomicU64::new(0),
             // max_memory_policy: "noeviction".to_string(),
             // max_memory_samples: 5,
+            hash_max_listpack_entries: AtomicUsize::new(128),
+            hash_max_listpack_value: AtomicUsize::new(64),
+            list_max_listpack_entries: AtomicUsize::new(128),
+            list_packed_threshold: AtomicUsize::new(1 << 30),
+            set_max_intset_entries: AtomicUsize::new(512),
+            lazyfree_lazy_user_del: false,
+            lazyfree_lazy_eviction: false,
+            lazyfree_lazy_expire: false,
+            lazyfree_lazy_server_del: false,
+            notify_encoding_change: false,
+        }
+    }
+}
+
+impl MemoryConf {
+    /// 设置了maxmemory且全局分配器提交的内存超过了maxmemory
+    pub fn is_oom(&self) -> bool {
+        let max_memory = self.max_memory.load(Ordering::Relaxed);
+        max_memory != 0 && allocated_memory().0 as u64 > max_memory
+    }
+}
+
+/// 解析内存大小，单位大小写不敏感：
+/// - 不带单位或者单位为b时，单位为字节
+/// - k, m, g以1000为基数；kb, mb, gb以及kib, mib, gib以1024为基数(与Redis一致)
+/// - `<n>%`表示系统总内存的n%，n的范围为0~100
+pub fn parse_memory_size(value: &[u8]) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "argument must be a memory or percent value, got '{}'",
+            String::from_utf8_lossy(value)
+        )
+    };
+
+    let value = value.to_ascii_lowercase();
+    if let Some(percent) = value.strip_suffix(b"%") {
+        let percent = atoi::<u64>(percent).map_err(|_| invalid())?;
+        if percent > 100 {
+            return Err(format!(
+                "percent value must be between 0 and 100, got {percent}"
+            ));
+        }
+        let total = total_system_memory().ok_or("failed to get the total system memory")?;
+        return Ok((total as u128 * percent as u128 / 100) as u64);
+    }
+
+    let unit_start = value
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (n, unit) = value.split_at(unit_start);
+    let n = atoi::<u64>(n).map_err(|_| invalid())?;
+    let multiplier: u64 = match unit {
+        b"" | b"b" => 1,
+        b"k" => 1000,
+        b"kb" | b"kib" => 1024,
+        b"m" => 1000 * 1000,
+        b"mb" | b"mib" => 1024 * 1024,
+        b"g" => 1000 * 1000 * 1000,
+        b"gb" | b"gib" => 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+
+    n.checked_mul(multiplier)
+        .ok_or_else(|| "memory value is out of range".to_string())
+}
+
+/// 系统的总内存，单位为字节。目前只支持Linux(读取/proc/meminfo)
+pub fn total_system_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kb = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+
+    Some(kb * 1024)
+}
+
+fn deserialize_memory_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<AtomicU64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MemorySize {
+        Bytes(u64),
+        Text(String),
+    }
+
+    let bytes = match MemorySize::deserialize(deserializer)? {
+        MemorySize::Bytes(bytes) => bytes,
+        MemorySize::Text(text) => {
+            parse_memory_size(text.as_bytes()).map_err(serde::de::Error::custom)?
+        }
+    };
+
+    Ok(AtomicU64::new(bytes))
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+
+    #[test]
+    fn parse_memory_size_test() {
+        for (value, expect) in [
+            ("0", 0),
+            ("100", 100),
+            ("100b", 100),
+            ("1k", 1000),
+            ("1kb", 1024),
+            ("1KiB", 1024),
+            ("512m", 512 * 1000 * 1000),
+            ("512mb", 512 * 1024 * 1024),
+            ("512MiB", 512 * 1024 * 1024),
+            ("2g", 2 * 1000 * 1000 * 1000),
+            ("2GB", 2 * 1024 * 1024 * 1024),
+            ("2gib", 2 * 1024 * 1024 * 1024),
+        ] {
+            assert_eq!(parse_memory_size(value.as_bytes()), Ok(expect), "{value}");
+        }
+
+        for value in [
+            "",
+            "mb",
+            "-1",
+            "1.5gb",
+            "1tb",
+            "1 mb",
+            "abc",
+            "101%",
+            "-1%",
+            "%",
+            "18446744073709551615kb",
+        ] {
+            assert!(parse_memory_size(value.as_bytes()).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn parse_memory_percent_test() {
+        let Some(total) = total_system_memory() else {
+            // 无法获取系统总内存时，百分比形式返回错误
+            assert!(parse_memory_size(b"50%").is_err());
+            return;
+        };
+
+        assert_eq!(parse_memory_size(b"100%"), Ok(total));
+        assert_eq!(parse_memory_size(b"50%"), Ok(total / 2));
+        assert_eq!(parse_memory_size(b"0%"), Ok(0));
+    }
+
+    #[test]
+    fn deserialize_max_memory_test() {
+        // 配置文件中可以使用整数或者带单位的字符串
+        for (value, expect) in [("4194304", 4 * 1024 * 1024), (r#""4mb""#, 4 * 1024 * 1024)] {
+            let conf: MemoryConf = config::Config::builder()
+                .add_source(config::File::from_str(
+                    &format!(
+                        r#"
+                        max_memory = {value}
+                        hash_max_listpack_entries = 128
+                        hash_max_listpack_value = 64
+                        list_max_listpack_entries = 128
+                        list_packed_threshold = 1073741824
+                        set_max_intset_entries = 512
+                        lazyfree_lazy_user_del = false
+                        lazyfree_lazy_eviction = false
+                        lazyfree_lazy_expire = false
+                        "#
+                    ),
+                    config::FileFormat::Toml,
+                ))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap();
+            assert_eq!(conf.max_memory.into_inner(), expect);
         }
     }
 }
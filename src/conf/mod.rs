@@ -22,13 +22,19 @@ use crate::{
     persist::{aof::Aof, rdb::Rdb},
     server::Listener,
     shared::Shared,
+    util::gen_run_id,
 };
+use bytes::Bytes;
 use clap::Parser;
-use rand::Rng;
 use serde::Deserialize;
-use std::{fs::File, io::BufReader, sync::Arc, time::Duration};
-use tokio::{runtime::Handle, time::Instant};
+use std::{
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::runtime::Handle;
 use tokio_rustls::rustls;
+use toml_edit::{DocumentMut, Item, Table, Value};
 use tracing::{error, info};
 
 #[derive(Debug, Deserialize)]
@@ -40,6 +46,9 @@ pub struct Conf {
     pub aof: Option<AofConf>,
     pub memory: MemoryConf,
     pub tls: Option<TLSConf>,
+    // 加载的用户配置文件，CONFIG REWRITE会将运行时配置写回该文件
+    #[serde(skip)]
+    pub config_file: Option<PathBuf>,
 }
 
 impl Default for Conf {
@@ -52,6 +61,7 @@ impl Default for Conf {
             aof: Some(AofConf::default()),
             memory: MemoryConf::default(),
             tls: None,
+            config_file: None,
         }
     }
 }
@@ -65,10 +75,8 @@ impl Conf {
         ));
 
         // 2. 从用户自定义配置文件中加载配置
-        let config_builder = config_builder.add_source(config::File::new(
-            "config/custom.toml",
-            config::FileFormat::Toml,
-        ));
+        let config_file = PathBuf::from("config/custom.toml");
+        let config_builder = config_builder.add_source(config::File::from(config_file.as_path()));
 
         // 3. 从命令行中加载配置
         let cli = Cli::parse();
@@ -77,17 +85,19 @@ impl Conf {
             .set_override_option("server.port", cli.port)?
             .set_override_option("rdb.file_path", cli.rdb_path)?;
 
-        let mut config: Conf = config_builder.build()?.try_deserialize()?;
+        let mut config: Conf =
+            rename_deprecated_keys(config_builder.build()?)?.try_deserialize()?;
 
         // 4. 运行时配置
-        let run_id: String = rand::thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(40)
-            .map(char::from)
-            .collect();
-
-        config.server.run_id = run_id;
+        config.config_file = Some(config_file);
+        config.server.run_id = gen_run_id();
+        config.replica.master_replid.store(Arc::new(gen_run_id()));
         // 由于AtomicCell<u64>默认值为0，所以不需要设置。repli_backlog同理
+        if let Some(requirepass) = config.security.requirepass.as_ref() {
+            config
+                .security
+                .set_requirepass(Bytes::copy_from_slice(requirepass.as_bytes()));
+        }
 
         Ok(config)
     }
@@ -125,27 +135,11 @@ impl Conf {
         let handle = Handle::current();
         std::thread::spawn({
             let shared = shared.clone();
-            move || {
+            move || loop {
                 std::thread::sleep(period);
-                loop {
-                    let shared = shared.clone();
-
-                    let now = Instant::now();
-                    let expired_keys: Vec<_> = shared
-                        .db()
-                        .entry_expire_records()
-                        .iter()
-                        .filter(|entry| entry.key().0 <= now)
-                        .map(|entry| entry.key().1.clone())
-                        .collect();
-
-                    for key in expired_keys {
-                        tracing::trace!("key {:?} is expired", key);
-                        // 删除过期键，该过程会自动删除对应的expire_record
-                        // WARN: 执行remove_object时，不应该持有entry_expire_records元素的引用，否则会导致死锁
-                        handle.block_on(shared.db().remove_object(&key));
-                    }
-                }
+
+                // 删除过期键，该过程会自动删除对应的expire_record
+                handle.block_on(shared.active_expire_cycle());
             }
         });
 
@@ -155,24 +149,88 @@ impl Conf {
     pub fn get_tls_config(&self) -> Option<rustls::ServerConfig> {
         let tls = self.tls.as_ref()?;
 
-        let cert = rustls_pemfile::certs(&mut BufReader::new(File::open(&tls.cert_file).unwrap()))
-            .map(|cert| cert.unwrap())
-            .collect();
+        Some(tls.server_config().unwrap())
+    }
+
+    /// 将可在运行时修改的配置写回配置文件(CONFIG REWRITE)。只更新这些配置项的值，文件中的其余
+    /// 内容(包括注释)保持不变
+    pub fn rewrite(&self) -> anyhow::Result<()> {
+        let path = self
+            .config_file
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the server is running without a config file"))?;
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut doc: DocumentMut = content.parse()?;
+
+        let memory = &self.memory;
+        let table = rewrite_table(&mut doc, "memory")?;
+        rewrite_value(
+            table,
+            "max_memory",
+            memory.max_memory.load(Ordering::Relaxed) as i64,
+        );
+        rewrite_value(
+            table,
+            "hash_max_listpack_entries",
+            memory.hash_max_listpack_entries.load(Ordering::Relaxed) as i64,
+        );
+        rewrite_value(
+            table,
+            "hash_max_listpack_value",
+            memory.hash_max_listpack_value.load(Ordering::Relaxed) as i64,
+        );
+        rewrite_value(
+            table,
+            "list_max_listpack_entries",
+            memory.list_max_listpack_entries.load(Ordering::Relaxed) as i64,
+        );
+        rewrite_value(
+            table,
+            "set_max_intset_entries",
+            memory.set_max_intset_entries.load(Ordering::Relaxed) as i64,
+        );
+
+        // requirepass为空时表示不需要密码
+        let table = rewrite_table(&mut doc, "security")?;
+        let password = self.security.default_ac.load().password.clone();
+        if password.is_empty() {
+            table.remove("requirepass");
+        } else {
+            rewrite_value(
+                table,
+                "requirepass",
+                String::from_utf8_lossy(&password).into_owned(),
+            );
+        }
 
-        let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(
-            File::open(&tls.key_file).unwrap(),
-        ))
-        .next()
-        .unwrap()
-        .unwrap();
+        // 先写入临时文件再重命名，避免写入中途失败时破坏原文件
+        let tmp_path = path.with_extension("toml.tmp");
+        std::fs::write(&tmp_path, doc.to_string())?;
+        std::fs::rename(&tmp_path, path)?;
 
-        let config = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(cert, rustls::pki_types::PrivateKeyDer::Pkcs8(key))
-            .unwrap();
+        Ok(())
+    }
+}
 
-        Some(config)
+fn rewrite_table<'a>(doc: &'a mut DocumentMut, name: &str) -> anyhow::Result<&'a mut Table> {
+    doc.entry(name)
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("'{name}' in the config file is not a table"))
+}
+
+// 保留原值的注释等修饰
+fn rewrite_value(table: &mut Table, key: &str, value: impl Into<Value>) {
+    let mut value = value.into();
+    if let Some(old) = table.get(key).and_then(Item::as_value) {
+        *value.decor_mut() = old.decor().clone();
     }
+    table.insert(key, Item::Value(value));
 }
 
 async fn enable_aof(
@@ -208,9 +266,29 @@ async fn enable_aof(
     Ok(())
 }
 
+// 已更名的配置项，旧名称的值会覆盖新名称的值，使旧的配置文件依然生效
+const RENAMED_KEYS: [(&str, &str); 1] = [("server.max_connections", "server.max_clients")];
+
+fn rename_deprecated_keys(config: config::Config) -> anyhow::Result<config::Config> {
+    let mut builder = config::Config::builder().add_source(config.clone());
+    for (old, new) in RENAMED_KEYS {
+        if let Ok(value) = config.get::<config::Value>(old) {
+            builder = builder.set_override(new, value)?;
+        }
+    }
+
+    Ok(builder.build()?)
+}
+
 #[cfg(test)]
 mod conf_tests {
-    use crate::{cmd::dispatch, frame::Resp3, server::Handler, shared::db::Db, util::test_init};
+    use crate::{
+        cmd::dispatch,
+        frame::Resp3,
+        server::Handler,
+        shared::db::Db,
+        util::{cmd, test_init},
+    };
     use std::io::Write;
 
     use super::*;
@@ -325,4 +403,114 @@ mod conf_tests {
         tokio::time::sleep(Duration::from_millis(300)).await;
         shutdown.trigger_shutdown(()).unwrap();
     }
+
+    #[test]
+    fn rename_deprecated_keys_test() {
+        let config = config::Config::builder()
+            .add_source(config::File::new(
+                "config/default.toml",
+                config::FileFormat::Toml,
+            ))
+            .add_source(config::File::from_str(
+                "[server]\nmax_connections = 7",
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let conf: Conf = rename_deprecated_keys(config)
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(conf.server.max_clients, 7);
+    }
+
+    #[tokio::test]
+    async fn rename_commands_test() {
+        test_init();
+
+        let conf = Conf {
+            security: SecurityConf {
+                rename_commands: RenameCommands::new([("DEL", ""), ("GET", "secret_get")]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let shared = Shared::new(Arc::new(Db::default()), Arc::new(conf), Default::default());
+        let (mut handler, _) = Handler::new_fake_with(shared, None, None);
+
+        let unknown_cmd = Resp3::new_simple_error("ERR unknown command".into());
+
+        dispatch(cmd(&["SET", "key", "value"]), &mut handler)
+            .await
+            .unwrap();
+
+        // 被禁用的命令无法执行
+        let res = dispatch(cmd(&["DEL", "key"]), &mut handler).await.unwrap();
+        assert_eq!(res, Some(unknown_cmd.clone()));
+
+        // 被重命名的命令只能通过新名称执行
+        let res = dispatch(cmd(&["GET", "key"]), &mut handler).await.unwrap();
+        assert_eq!(res, Some(unknown_cmd.clone()));
+        let res = dispatch(cmd(&["Secret_Get", "key"]), &mut handler)
+            .await
+            .unwrap();
+        assert_eq!(res, Some(Resp3::new_blob_string("value".into())));
+    }
+
+    #[tokio::test]
+    async fn config_rewrite_test() {
+        test_init();
+
+        let ok = Some(Resp3::new_simple_string("OK".into()));
+
+        // 未加载配置文件时报错
+        let (mut handler, _) = Handler::new_fake();
+        let res = dispatch(cmd(&["CONFIG", "REWRITE"]), &mut handler)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(res.try_simple_error().is_some());
+
+        let config_file = std::env::temp_dir().join("rutin_config_rewrite_test.toml");
+        std::fs::copy("config/custom.toml", &config_file).unwrap();
+
+        let conf = Conf {
+            config_file: Some(config_file.clone()),
+            ..Default::default()
+        };
+        let shared = Shared::new(Arc::new(Db::default()), Arc::new(conf), Default::default());
+        let (mut handler, _) = Handler::new_fake_with(shared, None, None);
+
+        let res = dispatch(cmd(&["CONFIG", "SET", "maxmemory", "200mb"]), &mut handler)
+            .await
+            .unwrap();
+        assert_eq!(res, ok);
+        let res = dispatch(cmd(&["CONFIG", "REWRITE"]), &mut handler)
+            .await
+            .unwrap();
+        assert_eq!(res, ok);
+
+        // 重新加载配置，修改后的值依然生效
+        let conf: Conf = config::Config::builder()
+            .add_source(config::File::new(
+                "config/default.toml",
+                config::FileFormat::Toml,
+            ))
+            .add_source(config::File::from(config_file.as_path()))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(
+            conf.memory.max_memory.load(Ordering::Relaxed),
+            200 * 1024 * 1024
+        );
+
+        // 文件中的其余内容(包括注释)保持不变
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(content.contains("port = 6379                     # 服务器端口"));
+
+        std::fs::remove_file(&config_file).unwrap();
+    }
 }
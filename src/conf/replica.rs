@@ -1,16 +1,28 @@
+use crate::util::gen_run_id;
+use arc_swap::{ArcSwap, ArcSwapOption};
 use crossbeam::atomic::AtomicCell;
 use serde::Deserialize;
+use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "replication")]
 pub struct ReplicaConf {
-    pub replicaof: Option<String>, // 主服务器的地址
+    // 主服务器的地址，None代表当前服务器为主服务器。可通过REPLICAOF修改
+    #[serde(default)]
+    pub replicaof: ArcSwapOption<String>,
     /// 最多允许多少个从服务器连接到当前服务器
     pub max_replica: u8,
     /// 用于记录当前服务器的复制偏移量。当从服务器发送 PSYNC
     /// 命令给主服务器时，比较从服务器和主服务器的ACK_OFFSET，从而判断主从是否一致。
     #[serde(skip)]
     pub offset: AtomicCell<u64>,
+    /// 复制ID，由40个随机的十六进制字符组成。replid改变后，从服务器无法再进行部分重同步
+    #[serde(skip)]
+    pub master_replid: ArcSwap<String>,
+    /// 作为从服务器时，与主服务器的连接是否可用。收到主服务器传播的命令后变为可用，
+    /// 修改主服务器后变为不可用
+    #[serde(skip)]
+    pub master_link_up: AtomicCell<bool>,
     #[serde(skip)]
     // pub repli_backlog: RepliBackLog, // 复制积压缓冲区大小
     pub masterauth: Option<String>, // 主服务器密码，设置该值之后，当从服务器连接到主服务器时会发送该值
@@ -19,9 +31,11 @@ pub struct ReplicaConf {
 impl Default for ReplicaConf {
     fn default() -> Self {
         Self {
-            replicaof: None,
+            replicaof: ArcSwapOption::empty(),
             max_replica: 6,
             offset: AtomicCell::new(0),
+            master_replid: ArcSwap::new(Arc::new(gen_run_id())),
+            master_link_up: AtomicCell::new(false),
             // repli_backlog: RepliBackLog::default(),
             masterauth: None,
         }
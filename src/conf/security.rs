@@ -1,7 +1,13 @@
 use crate::{
-    cmd::{cmd_name_to_flag, commands::*, CmdExecutor, CmdType},
+    cmd::{
+        cmd_name_to_flag, commands::*, flag_to_cmd_names, CmdError, CmdExecutor, CmdType,
+        CmdUnparsed, Err,
+    },
+    frame::{quote_inline_arg, split_inline_args},
+    util::glob_match,
     CmdFlag,
 };
+use ahash::AHashMap;
 use arc_swap::ArcSwap;
 use bytes::Bytes;
 use dashmap::DashMap;
@@ -11,6 +17,7 @@ use dashmap::{
 };
 use regex::bytes::RegexSet;
 use serde::Deserialize;
+use std::{path::PathBuf, sync::Arc};
 
 pub const DEFAULT_USER: Bytes = Bytes::from_static(b"default_ac");
 
@@ -19,33 +26,85 @@ pub struct AclCategory {
     pub flag: CmdFlag,
 }
 
-pub const ACL_CATEGORIES: [AclCategory; 10] = [
+pub const ACL_CATEGORIES: [AclCategory; 15] = [
     AclCategory {
         name: "ADMIN",
-        flag: BgSave::FLAG,
+        flag: BgSave::FLAG
+            | ReplicaOf::FLAG
+            | DebugChangeReplId::FLAG
+            | DebugSetActiveExpire::FLAG
+            | DebugQuicklistPackedThreshold::FLAG
+            | DebugStringMatchLen::FLAG
+            | DebugObject::FLAG
+            | DebugSleep::FLAG
+            | DebugSleepBlock::FLAG
+            | DebugLoadFile::FLAG
+            | DebugPanic::FLAG
+            | DebugOom::FLAG
+            | DebugReload::FLAG
+            | DebugFlushAll::FLAG
+            | AclDelUser::FLAG
+            | AclSetUser::FLAG
+            | AclUsers::FLAG
+            | AclSave::FLAG
+            | AclLoad::FLAG
+            | ClientKill::FLAG
+            | ConfigSet::FLAG
+            | ConfigRewrite::FLAG
+            | ConfigResetStat::FLAG,
     },
     AclCategory {
         name: "READ",
         flag: Get::FLAG
             | GetRange::FLAG
             | MGet::FLAG
+            | StrLen::FLAG
+            | LIndex::FLAG
             | LLen::FLAG
             | LPos::FLAG
             | HGet::FLAG
-            | HDel::FLAG
+            | HGetAll::FLAG
+            | HExists::FLAG
+            | HRandField::FLAG
+            | SInterCard::FLAG
+            | SMembers::FLAG
+            | ZInterCard::FLAG
+            | ZRandMember::FLAG
+            | GeoDist::FLAG
+            | GeoPos::FLAG
+            | GeoSearch::FLAG
+            | XLen::FLAG
+            | XRange::FLAG
+            | XRevRange::FLAG
+            | XRead::FLAG
+            | XPending::FLAG
+            | SortRo::FLAG
+            | Lolwut::FLAG
+            | DbSize::FLAG
+            | Dump::FLAG
+            | ExpireTime::FLAG
             | Exists::FLAG
             | Keys::FLAG
             | NBKeys::FLAG
             | Pttl::FLAG
             | Ttl::FLAG
-            | Type::FLAG,
+            | Type::FLAG
+            | ObjectEncoding::FLAG
+            | ObjectFreq::FLAG
+            | ObjectIdleTime::FLAG
+            | ObjectRefCount::FLAG,
     },
     AclCategory {
         name: "WRITE",
         flag: Set::FLAG
             | SetEx::FLAG
+            | PSetEx::FLAG
             | SetNx::FLAG
+            | GetSet::FLAG
+            | MSet::FLAG
+            | MSetNx::FLAG
             | Append::FLAG
+            | BitField::FLAG
             | Incr::FLAG
             | IncrBy::FLAG
             | Decr::FLAG
@@ -53,21 +112,55 @@ pub const ACL_CATEGORIES: [AclCategory; 10] = [
             | LPush::FLAG
             | LPop::FLAG
             | BLPop::FLAG
+            | NBLPop::FLAG
+            | BLMove::FLAG
             | HSet::FLAG
-            | HExists::FLAG
+            | HDel::FLAG
+            | HGetDel::FLAG
+            | HGetEx::FLAG
+            | SAdd::FLAG
+            | GeoAdd::FLAG
+            | XAdd::FLAG
+            | XGroupCreate::FLAG
+            | XGroupCreateConsumer::FLAG
+            | XGroupDestroy::FLAG
+            | XReadGroup::FLAG
+            | XAck::FLAG
+            | XClaim::FLAG
+            | Del::FLAG
             | Expire::FLAG
             | ExpireAt::FLAG
-            | ExpireTime::FLAG
+            | PExpireAt::FLAG
             | Persist::FLAG
+            | Restore::FLAG
+            | Migrate::FLAG
             | Publish::FLAG,
     },
     AclCategory {
         name: "CONNECTION",
-        flag: BgSave::FLAG | Ping::FLAG | Echo::FLAG | Auth::FLAG | ClientTracking::FLAG,
+        flag: BgSave::FLAG
+            | Ping::FLAG
+            | Echo::FLAG
+            | Time::FLAG
+            | Lolwut::FLAG
+            | Auth::FLAG
+            | Hello::FLAG
+            | Info::FLAG
+            | MemoryDoctor::FLAG
+            | MemoryStats::FLAG
+            | AclCat::FLAG
+            | AclWhoAmI::FLAG
+            | ClientId::FLAG
+            | ClientInfo::FLAG
+            | ClientKill::FLAG
+            | ClientTracking::FLAG
+            | CommandDocs::FLAG
+            | CommandInfo::FLAG,
     },
     AclCategory {
         name: "KEYSPACE",
-        flag: Del::FLAG
+        flag: DbSize::FLAG
+            | Del::FLAG
             | Dump::FLAG
             | Exists::FLAG
             | Expire::FLAG
@@ -75,14 +168,22 @@ pub const ACL_CATEGORIES: [AclCategory; 10] = [
             | ExpireTime::FLAG
             | Keys::FLAG
             | NBKeys::FLAG
+            | PExpireAt::FLAG
             | Persist::FLAG
             | Pttl::FLAG
+            | Restore::FLAG
+            | Migrate::FLAG
             | Ttl::FLAG
-            | Type::FLAG,
+            | Type::FLAG
+            | ObjectEncoding::FLAG
+            | ObjectFreq::FLAG
+            | ObjectIdleTime::FLAG
+            | ObjectRefCount::FLAG,
     },
     AclCategory {
         name: "STRING",
         flag: Append::FLAG
+            | BitField::FLAG
             | Decr::FLAG
             | DecrBy::FLAG
             | Get::FLAG
@@ -95,22 +196,68 @@ pub const ACL_CATEGORIES: [AclCategory; 10] = [
             | MSetNx::FLAG
             | Set::FLAG
             | SetEx::FLAG
+            | PSetEx::FLAG
             | SetNx::FLAG
             | StrLen::FLAG,
     },
     AclCategory {
         name: "LIST",
-        flag: LLen::FLAG
+        flag: LIndex::FLAG
+            | LLen::FLAG
             | LPush::FLAG
             | LPop::FLAG
             | BLPop::FLAG
             | LPos::FLAG
             | NBLPop::FLAG
-            | BLMove::FLAG,
+            | BLMove::FLAG
+            | Sort::FLAG
+            | SortRo::FLAG,
     },
     AclCategory {
         name: "HASH",
-        flag: HDel::FLAG | HExists::FLAG | HGet::FLAG | HSet::FLAG,
+        flag: HDel::FLAG
+            | HExists::FLAG
+            | HGet::FLAG
+            | HGetAll::FLAG
+            | HGetDel::FLAG
+            | HGetEx::FLAG
+            | HRandField::FLAG
+            | HSet::FLAG
+            | Sort::FLAG,
+    },
+    AclCategory {
+        name: "SET",
+        flag: SAdd::FLAG | SInterCard::FLAG | SMembers::FLAG | Sort::FLAG | SortRo::FLAG,
+    },
+    AclCategory {
+        name: "SORTEDSET",
+        flag: ZInterCard::FLAG
+            | ZRandMember::FLAG
+            | GeoAdd::FLAG
+            | GeoDist::FLAG
+            | GeoPos::FLAG
+            | GeoSearch::FLAG
+            | Sort::FLAG
+            | SortRo::FLAG,
+    },
+    AclCategory {
+        name: "GEO",
+        flag: GeoAdd::FLAG | GeoDist::FLAG | GeoPos::FLAG | GeoSearch::FLAG,
+    },
+    AclCategory {
+        name: "STREAM",
+        flag: XAdd::FLAG
+            | XLen::FLAG
+            | XRange::FLAG
+            | XRevRange::FLAG
+            | XRead::FLAG
+            | XGroupCreate::FLAG
+            | XGroupCreateConsumer::FLAG
+            | XGroupDestroy::FLAG
+            | XReadGroup::FLAG
+            | XAck::FLAG
+            | XPending::FLAG
+            | XClaim::FLAG,
     },
     AclCategory {
         name: "PUBSUB",
@@ -118,10 +265,40 @@ pub const ACL_CATEGORIES: [AclCategory; 10] = [
     },
     AclCategory {
         name: "SCRIPTING",
-        flag: Eval::FLAG | EvalName::FLAG | ScriptExists::FLAG,
+        flag: Eval::FLAG
+            | EvalName::FLAG
+            | ScriptExists::FLAG
+            | ScriptFlush::FLAG
+            | ScriptRegister::FLAG,
+    },
+    AclCategory {
+        name: "TRANSACTION",
+        flag: Discard::FLAG | Exec::FLAG | Multi::FLAG | Unwatch::FLAG | Watch::FLAG,
     },
 ];
 
+// flag中所有命令的名称
+fn cmd_names(flag: CmdFlag) -> Vec<String> {
+    (0..CmdFlag::BITS)
+        .map(|i| 1 << i)
+        .filter(|bit| flag & bit != 0)
+        .filter_map(|bit| flag_to_cmd_names(bit).ok())
+        .flatten()
+        .map(str::to_string)
+        .collect()
+}
+
+fn category_flag(cat_name: &[u8]) -> anyhow::Result<CmdFlag> {
+    let mut buf = [0; 32];
+    let cat_name = crate::util::get_uppercase(cat_name, &mut buf)?;
+
+    ACL_CATEGORIES
+        .iter()
+        .find(|cat| cat.name.as_bytes() == cat_name)
+        .map(|cat| cat.flag)
+        .ok_or_else(|| anyhow::anyhow!("unknown category"))
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename = "security")]
 pub struct SecurityConf {
@@ -129,11 +306,106 @@ pub struct SecurityConf {
     // TODO:
     #[serde(skip)]
     pub forbaiden_commands: Vec<bool>,
-    // TODO:
-    #[serde(skip)]
-    pub rename_commands: Vec<Option<String>>,
+    #[serde(default)]
+    pub rename_commands: RenameCommands,
     pub default_ac: ArcSwap<AccessControl>,
     pub acl: Option<Acl>, // None代表禁用ACL
+    #[serde(default)]
+    pub aclfile: Option<PathBuf>, // ACL SAVE与ACL LOAD读写的文件
+}
+
+impl SecurityConf {
+    /// 设置default用户的密码。密码为空时表示default用户不需要密码(nopass)
+    pub fn set_requirepass(&self, password: Bytes) {
+        let mut ac = AccessControl::clone(&self.default_ac.load());
+        ac.password = password;
+        self.default_ac.store(Arc::new(ac));
+    }
+
+    /// 将所有用户的规则写入aclfile(ACL SAVE)。每个用户占一行，格式为`user <name> <rules>`，
+    /// 其中rules即ACL SETUSER的参数，因此文件可以手动编辑
+    pub fn save_acl(&self) -> anyhow::Result<()> {
+        let path = self
+            .aclfile
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the server is running without an aclfile"))?;
+
+        let mut content = Vec::new();
+        let mut write_user = |name: &[u8], ac: &AccessControl| {
+            content.extend_from_slice(b"user ");
+            content.extend_from_slice(&quote_inline_arg(name));
+            for rule in ac.to_rules() {
+                content.push(b' ');
+                content.extend_from_slice(&quote_inline_arg(&rule));
+            }
+            content.push(b'\n');
+        };
+
+        write_user(&DEFAULT_USER, &self.default_ac.load());
+        if let Some(acl) = &self.acl {
+            for entry in acl.iter() {
+                write_user(entry.key(), entry.value());
+            }
+        }
+
+        // 先写入临时文件再重命名，避免写入中途失败时破坏原文件
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// 使用aclfile中的规则替换内存中的ACL(ACL LOAD)。文件中的任意一行有误时返回错误，
+    /// 且不修改任何用户。文件中没有default_ac时保留当前的default_ac
+    pub fn load_acl(&self) -> anyhow::Result<()> {
+        let path = self
+            .aclfile
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the server is running without an aclfile"))?;
+
+        let content = std::fs::read(path)?;
+
+        let mut default_ac = None;
+        let mut users = Vec::new();
+        for (i, line) in content.split(|&c| c == b'\n').enumerate() {
+            let line_err = |msg: &dyn std::fmt::Display| anyhow::anyhow!("line {}: {msg}", i + 1);
+
+            // 跳过空行与注释
+            if line.trim_ascii_start().first().is_none_or(|&c| c == b'#') {
+                continue;
+            }
+
+            let mut args = split_inline_args(line).map_err(|e| line_err(&e))?;
+            if args.len() < 2 || args[0] != "user" {
+                return Err(line_err(&"expected 'user <name> <rules>'"));
+            }
+
+            let AclSetUser { name, aci } =
+                AclSetUser::parse(&mut args.split_off(1).into(), &AccessControl::new_loose())
+                    .map_err(|e| line_err(&e))?;
+            let ac = AccessControl::try_from(aci).map_err(|e| line_err(&e))?;
+
+            if name == DEFAULT_USER {
+                default_ac = Some(ac);
+            } else {
+                users.push((name, ac));
+            }
+        }
+
+        if let Some(ac) = default_ac {
+            self.default_ac.store(Arc::new(ac));
+        }
+        if let Some(acl) = &self.acl {
+            acl.clear();
+            for (name, ac) in users {
+                acl.insert(name, ac);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for SecurityConf {
@@ -141,13 +413,62 @@ impl Default for SecurityConf {
         Self {
             requirepass: None,
             forbaiden_commands: vec![],
-            rename_commands: vec![],
+            rename_commands: RenameCommands::default(),
             default_ac: ArcSwap::from_pointee(AccessControl::new_loose()),
             acl: Some(Acl::new()),
+            aclfile: None,
         }
     }
 }
 
+/// 命令重命名表。键为大写的命令名，值为实际执行的命令名，值为None代表该命令名不可用。
+/// 命令被重命名或禁用(重命名为空字符串)后，只能通过新的名称访问
+#[derive(Debug, Default)]
+pub struct RenameCommands(AHashMap<Bytes, Option<Bytes>>);
+
+impl RenameCommands {
+    pub fn new<'a>(renames: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut map = AHashMap::new();
+        for (cmd_name, new_name) in renames {
+            let cmd_name = Bytes::from(cmd_name.to_ascii_uppercase());
+            // 原命令名不再可用
+            map.entry(cmd_name.clone()).or_insert(None);
+            if !new_name.is_empty() {
+                map.insert(Bytes::from(new_name.to_ascii_uppercase()), Some(cmd_name));
+            }
+        }
+
+        Self(map)
+    }
+
+    /// 将客户端发送的命令名解析为实际执行的命令名
+    #[inline]
+    pub fn resolve(&self, cmd_name: Bytes) -> Result<Bytes, CmdError> {
+        if self.0.is_empty() {
+            return Ok(cmd_name);
+        }
+
+        match self.0.get(cmd_name.to_ascii_uppercase().as_slice()) {
+            None => Ok(cmd_name),
+            Some(Some(cmd_name)) => Ok(cmd_name.clone()),
+            Some(None) => Err(Err::UnknownCmd.into()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RenameCommands {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let renames = std::collections::HashMap::<String, String>::deserialize(deserializer)?;
+
+        Ok(Self::new(
+            renames.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+        ))
+    }
+}
+
 #[repr(transparent)]
 #[derive(Debug, Deserialize, Default)]
 pub struct Acl(DashMap<Bytes, AccessControl>);
@@ -185,6 +506,10 @@ impl Acl {
         self.0.remove(key)
     }
 
+    pub fn clear(&self) {
+        self.0.clear();
+    }
+
     pub fn disable(&self, key: &Bytes) {
         if let Some(mut ac) = self.0.get_mut(key) {
             ac.enable = false;
@@ -222,6 +547,10 @@ pub struct AccessControl {
     pub deny_write_key_patterns: Option<RegexSet>,
     // pubsub的限制模式
     pub deny_channel_patterns: Option<RegexSet>,
+    // 允许访问的key的glob模式，None表示允许访问所有key。目前只用于selector
+    pub allow_key_patterns: Option<Vec<Bytes>>,
+    // 备选的权限集合，基本规则或任意一个selector允许执行命令即可
+    pub selectors: Vec<AccessControl>,
 }
 
 impl AccessControl {
@@ -233,6 +562,8 @@ impl AccessControl {
             deny_read_key_patterns: None,
             deny_write_key_patterns: None,
             deny_channel_patterns: None,
+            allow_key_patterns: None,
+            selectors: Vec::new(),
         }
     }
 
@@ -244,6 +575,8 @@ impl AccessControl {
             deny_read_key_patterns: None,
             deny_write_key_patterns: None,
             deny_channel_patterns: None,
+            allow_key_patterns: None,
+            selectors: Vec::new(),
         }
     }
 
@@ -259,20 +592,9 @@ impl AccessControl {
             }
         }
 
-        let cat_name_to_cat_flag = |cat_name: &Bytes| -> anyhow::Result<CmdFlag> {
-            let mut buf = [0; 32];
-            let cat_name = crate::util::get_uppercase(cat_name, &mut buf)?;
-
-            ACL_CATEGORIES
-                .iter()
-                .find(|cat| cat.name.as_bytes() == cat_name)
-                .map(|cat| cat.flag)
-                .ok_or_else(|| anyhow::anyhow!("unknown category"))
-        };
-
         if let Some(allow_categories) = other.allow_categories {
             for category_name in &allow_categories {
-                let flag = cat_name_to_cat_flag(category_name)?;
+                let flag = category_flag(category_name)?;
 
                 self.cmd_flag |= flag; // 允许某类命令执行
             }
@@ -293,7 +615,7 @@ impl AccessControl {
 
         if let Some(deny_categories) = other.deny_categories {
             for category_name in &deny_categories {
-                let flag = cat_name_to_cat_flag(category_name)?;
+                let flag = category_flag(category_name)?;
 
                 self.cmd_flag &= !flag; // 禁止某类命令执行
             }
@@ -371,13 +693,175 @@ impl AccessControl {
             self.deny_channel_patterns = Some(RegexSet::new(patterns)?);
         }
 
+        // 追加selectors，RESET表示先清空已有的selectors
+        if let Some(selectors) = other.selectors {
+            if selectors.iter().any(|s| s.eq_ignore_ascii_case(b"RESET")) {
+                self.selectors.clear();
+            }
+
+            for selector in selectors
+                .iter()
+                .filter(|s| !s.eq_ignore_ascii_case(b"RESET"))
+            {
+                self.selectors.push(Self::parse_selector(selector)?);
+            }
+        }
+
         Ok(())
     }
 
+    /// 解析selector，例如`+GET +@string -set ~cache:*`。支持的规则有：`+<cmd>`, `-<cmd>`,
+    /// `+@<category>`, `-@<category>`, `allcommands`, `~<pattern>`(glob风格)以及`allkeys`。
+    /// selector初始时不允许执行任何命令，也不允许访问任何key
+    pub fn parse_selector(rules: &[u8]) -> anyhow::Result<AccessControl> {
+        let mut selector = AccessControl {
+            allow_key_patterns: Some(Vec::new()),
+            ..AccessControl::new_strict()
+        };
+
+        for rule in rules
+            .split(u8::is_ascii_whitespace)
+            .filter(|r| !r.is_empty())
+        {
+            match rule {
+                r if r.eq_ignore_ascii_case(b"allcommands") => selector.cmd_flag = ALL_CMD_FLAG,
+                r if r.eq_ignore_ascii_case(b"allkeys") => selector.allow_key_patterns = None,
+                [b'~', pattern @ ..] => {
+                    if let Some(patterns) = selector.allow_key_patterns.as_mut() {
+                        patterns.push(Bytes::copy_from_slice(pattern));
+                    }
+                }
+                [b'+', b'@', cat @ ..] => selector.cmd_flag |= category_flag(cat)?,
+                [b'-', b'@', cat @ ..] => selector.cmd_flag &= !category_flag(cat)?,
+                [b'+', cmd @ ..] => {
+                    selector.cmd_flag |=
+                        cmd_name_to_flag(cmd).map_err(|e| anyhow::anyhow!("{e}"))?
+                }
+                [b'-', cmd @ ..] => {
+                    selector.cmd_flag &=
+                        !cmd_name_to_flag(cmd).map_err(|e| anyhow::anyhow!("{e}"))?
+                }
+                _ => anyhow::bail!("unknown selector rule '{}'", String::from_utf8_lossy(rule)),
+            }
+        }
+
+        Ok(selector)
+    }
+
+    /// [`AccessControl::parse_selector`]的逆操作
+    fn selector_rules(&self) -> String {
+        let mut rules = Vec::new();
+
+        if self.cmd_flag == ALL_CMD_FLAG {
+            rules.push("allcommands".to_string());
+        } else {
+            let mut allow = self.cmd_flag & !NO_CMD_FLAG;
+            for cat in &ACL_CATEGORIES {
+                if cat.flag & self.cmd_flag == cat.flag && cat.flag & allow != 0 {
+                    rules.push(format!("+@{}", cat.name));
+                    allow &= !cat.flag;
+                }
+            }
+            rules.extend(cmd_names(allow).into_iter().map(|name| format!("+{name}")));
+            rules.extend(
+                cmd_names(NO_CMD_FLAG & !self.cmd_flag)
+                    .into_iter()
+                    .map(|name| format!("-{name}")),
+            );
+        }
+
+        match &self.allow_key_patterns {
+            None => rules.push("allkeys".to_string()),
+            Some(patterns) => rules.extend(
+                patterns
+                    .iter()
+                    .map(|p| format!("~{}", String::from_utf8_lossy(p))),
+            ),
+        }
+
+        rules.join(" ")
+    }
+
     pub const fn cmd_flag(&self) -> CmdFlag {
         self.cmd_flag
     }
 
+    /// 将权限转换为ACL SETUSER的参数(不包括用户名)。使用这些参数创建的用户与self拥有
+    /// 相同的权限
+    pub fn to_rules(&self) -> Vec<Bytes> {
+        let mut rules = vec![Bytes::from_static(if self.enable {
+            b"enable"
+        } else {
+            b"disable"
+        })];
+
+        if !self.password.is_empty() {
+            rules.extend([Bytes::from_static(b"PWD"), self.password.clone()]);
+        }
+
+        // 新用户只允许NO_CMD_FLAG中的命令。允许的命令较多时，先允许所有命令，再禁止其余的命令
+        let base = if self.cmd_flag.count_ones() > CmdFlag::BITS / 2 {
+            ALL_CMD_FLAG
+        } else {
+            NO_CMD_FLAG
+        };
+        let mut allow = self.cmd_flag & !base;
+        let mut deny = base & !self.cmd_flag;
+
+        // 尽量以类别的形式表示，使规则更易读
+        let mut allow_categories = Vec::new();
+        let mut deny_categories = Vec::new();
+        for cat in &ACL_CATEGORIES {
+            if cat.flag & self.cmd_flag == cat.flag && cat.flag & allow != 0 {
+                allow_categories.push(cat.name.to_string());
+                allow &= !cat.flag;
+            } else if cat.flag & self.cmd_flag == 0 && cat.flag & deny != 0 {
+                deny_categories.push(cat.name.to_string());
+                deny &= !cat.flag;
+            }
+        }
+
+        // 以','结尾的参数表示列表尚未结束
+        let mut push_list = |keyword: &'static str, items: Vec<String>| {
+            if items.is_empty() {
+                return;
+            }
+
+            rules.push(Bytes::from_static(keyword.as_bytes()));
+            let last = items.len() - 1;
+            for (i, item) in items.into_iter().enumerate() {
+                rules.push(if i == last {
+                    item.into()
+                } else {
+                    format!("{item},").into()
+                });
+            }
+        };
+
+        if base == ALL_CMD_FLAG {
+            push_list("ALLOWCMD", vec!["ALL".to_string()]);
+        }
+        push_list("ALLOWCAT", allow_categories);
+        push_list("ALLOWCMD", cmd_names(allow));
+        push_list("DENYCAT", deny_categories);
+        push_list("DENYCMD", cmd_names(deny));
+
+        let patterns = |set: &Option<RegexSet>| {
+            set.as_ref()
+                .map_or_else(Vec::new, |set| set.patterns().to_vec())
+        };
+        push_list("DENYRKEY", patterns(&self.deny_read_key_patterns));
+        push_list("DENYWKEY", patterns(&self.deny_write_key_patterns));
+        push_list("DENYCHANNEL", patterns(&self.deny_channel_patterns));
+
+        // 每个selector作为一个参数，以括号包围
+        for selector in &self.selectors {
+            rules.push(format!("({})", selector.selector_rules()).into());
+        }
+
+        rules
+    }
+
     // 密码是否正确
     #[inline]
     pub fn is_pwd_correct(&self, pwd: &Bytes) -> bool {
@@ -395,12 +879,81 @@ impl AccessControl {
         self.cmd_flag & check == 0
     }
 
+    /// 基本规则或任意一个selector允许执行该命令时返回true
+    pub fn permits_cmd(&self, check: CmdFlag) -> bool {
+        if !self.enable {
+            return false;
+        }
+
+        !self.is_forbidden_cmd(check) || self.selectors.iter().any(|s| !s.is_forbidden_cmd(check))
+    }
+
+    /// 解析命令。key的权限在解析命令时检查，因此依次使用允许执行该命令的基本规则与各个
+    /// selector解析，直到某个权限集合允许访问命令中的所有key
+    pub fn parse_permitted<T>(
+        &self,
+        check: CmdFlag,
+        args: &mut CmdUnparsed,
+        parse: impl Fn(&mut CmdUnparsed, &AccessControl) -> Result<T, CmdError>,
+    ) -> Result<T, CmdError> {
+        if self.selectors.is_empty() {
+            return parse(args, self);
+        }
+
+        let mut res = Err(Err::NoPermission.into());
+        if !self.enable {
+            return res;
+        }
+
+        for ac in std::iter::once(self).chain(&self.selectors) {
+            if ac.is_forbidden_cmd(check) {
+                continue;
+            }
+
+            let mut tried = args.clone();
+            res = parse(&mut tried, ac);
+            // 只有没有权限时才尝试下一个权限集合
+            if !matches!(
+                res,
+                Err(CmdError::Err {
+                    source: Err::NoPermission
+                })
+            ) {
+                *args = tried;
+                break;
+            }
+        }
+
+        res
+    }
+
+    /// 是否允许读取任意key。用于无法预先确定要访问哪些key的命令，例如SORT的BY和GET
+    pub fn can_read_all_keys(&self) -> bool {
+        self.enable
+            && self.deny_read_key_patterns.is_none()
+            && self
+                .allow_key_patterns
+                .as_ref()
+                .is_none_or(|patterns| patterns.iter().any(|p| p.as_ref() == b"*"))
+    }
+
+    // 是否允许访问该key，只有设置了allow_key_patterns时才有限制
+    fn is_allowed_key(&self, key: &[u8]) -> bool {
+        self.allow_key_patterns
+            .as_ref()
+            .is_none_or(|patterns| patterns.iter().any(|p| glob_match(p, key, false)))
+    }
+
     #[inline]
     pub fn is_forbidden_key(&self, key: &dyn AsRef<[u8]>, cmd_type: CmdType) -> bool {
         if !self.enable {
             return true;
         }
 
+        if cmd_type != CmdType::Other && !self.is_allowed_key(key.as_ref()) {
+            return true;
+        }
+
         match cmd_type {
             CmdType::Read => {
                 if let Some(patterns) = &self.deny_read_key_patterns {
@@ -426,6 +979,10 @@ impl AccessControl {
             return true;
         }
 
+        if cmd_type != CmdType::Other && !keys.iter().all(|key| self.is_allowed_key(key.as_ref())) {
+            return true;
+        }
+
         match cmd_type {
             CmdType::Read => {
                 if let Some(patterns) = &self.deny_read_key_patterns {
@@ -501,6 +1058,7 @@ pub struct AccessControlIntermedium {
     pub deny_read_key_patterns: Option<Vec<String>>,
     pub deny_write_key_patterns: Option<Vec<String>>,
     pub deny_channel_patterns: Option<Vec<String>>,
+    pub selectors: Option<Vec<Bytes>>,
 }
 
 impl TryFrom<AccessControlIntermedium> for AccessControl {
@@ -1,5 +1,8 @@
+use crate::util::gen_run_id;
 use rand::Rng;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+use tokio::time::Instant;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "server")]
@@ -7,29 +10,147 @@ pub struct ServerConf {
     pub addr: String,
     pub port: u16,
     #[serde(skip)]
-    pub run_id: String, // 服务器的运行ID。由40个随机字符组成
+    pub run_id: String, // 服务器的运行ID。由40个随机的十六进制字符组成
     pub expire_check_interval_secs: u64, // 检查过期键的周期
+    pub ttl_jitter_percent: u64, // 为EX/PX等相对过期时间加上±ttl_jitter_percent%的随机抖动，0表示不开启
     pub log_level: String,
-    pub max_connections: usize,
+    pub max_clients: usize, // 最大客户端数量，达到上限后拒绝新的连接
+    pub tcp_backlog: u32,   // 监听套接字的全连接队列长度
     pub max_batch: usize,
+    pub db_shards: usize,           // 数据库的分片数量，0表示根据CPU核数自动设置
+    pub db_initial_capacity: usize, // 数据库的初始容量，避免过早扩容
+    pub streaming_reply_threshold: usize, // 集合元素数量达到该值时，回复逐个元素写入写缓冲区，0表示不开启
+    pub client_output_buffer_limit: ClientOutputBufferLimit,
+    #[serde(default)]
+    pub enable_debug_crash: bool, // 是否允许执行DEBUG PANIC和DEBUG OOM，用于测试崩溃隔离
+    #[serde(default)]
+    pub command_timeout_ms: u64, // 单个命令的最长执行时间，超时后中断命令(写命令只会在修改数据之前中断)，0表示不限制
+    #[serde(
+        default = "default_resp_version",
+        deserialize_with = "deserialize_resp_version"
+    )]
+    pub default_resp_version: u8, // 新连接使用的协议版本(2或3)，之后仍然可以通过HELLO切换
+    #[serde(default = "default_max_wcmd_buf")]
+    pub max_wcmd_buf: usize, // 批处理中缓存的写命令达到该字节数时立即传播，不再等待批处理结束
 }
 
 impl Default for ServerConf {
     fn default() -> Self {
-        let run_id: String = rand::thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(40)
-            .map(char::from)
-            .collect();
-
         Self {
             addr: "127.0.0.1".to_string(),
             port: 6379,
-            run_id,
+            run_id: gen_run_id(),
             expire_check_interval_secs: 1,
+            ttl_jitter_percent: 0,
             log_level: "info".to_string(),
-            max_connections: 1024,
+            max_clients: 1024,
+            tcp_backlog: 511,
             max_batch: 1024,
+            db_shards: 0,
+            db_initial_capacity: 1024 * 16,
+            streaming_reply_threshold: 1024,
+            client_output_buffer_limit: ClientOutputBufferLimit::default(),
+            enable_debug_crash: false,
+            command_timeout_ms: 0,
+            default_resp_version: default_resp_version(),
+            max_wcmd_buf: default_max_wcmd_buf(),
+        }
+    }
+}
+
+fn default_resp_version() -> u8 {
+    2
+}
+
+fn default_max_wcmd_buf() -> usize {
+    1024 * 1024
+}
+
+fn deserialize_resp_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let version = u8::deserialize(deserializer)?;
+    if version != 2 && version != 3 {
+        return Err(serde::de::Error::custom(format!(
+            "default_resp_version should be 2 or 3, but got {version}"
+        )));
+    }
+
+    Ok(version)
+}
+
+impl ServerConf {
+    /// 为相对过期时间加上随机抖动，避免大量键在同一时刻过期。抖动后的过期时间不会早于当前时间
+    pub fn jitter_expire(&self, expire: Instant) -> Instant {
+        let percent = self.ttl_jitter_percent.min(100);
+        if percent == 0 {
+            return expire;
+        }
+
+        let now = Instant::now();
+        let ttl = expire.saturating_duration_since(now).as_millis() as u64;
+        let max_jitter = ttl * percent / 100;
+        if max_jitter == 0 {
+            return expire;
+        }
+
+        let ttl = ttl - max_jitter + rand::thread_rng().gen_range(0..=max_jitter * 2);
+        now + Duration::from_millis(ttl.max(1))
+    }
+}
+
+/// 客户端输出缓冲区的限制，单位为字节，0表示不限制。缓冲区大小超过hard_limit时立即断开
+/// 连接；持续超过soft_limit达到soft_seconds秒时断开连接
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct OutputBufferLimit {
+    pub hard_limit: usize,
+    pub soft_limit: usize,
+    pub soft_seconds: u64,
+}
+
+impl OutputBufferLimit {
+    pub const fn is_unlimited(&self) -> bool {
+        self.hard_limit == 0 && self.soft_limit == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientClass {
+    Normal,
+    PubSub,
+    Replica,
+}
+
+/// 不同类型的客户端使用不同的输出缓冲区限制
+#[derive(Debug, Deserialize)]
+pub struct ClientOutputBufferLimit {
+    pub normal: OutputBufferLimit,
+    pub pubsub: OutputBufferLimit,
+    pub replica: OutputBufferLimit,
+}
+
+impl ClientOutputBufferLimit {
+    pub fn get(&self, class: ClientClass) -> OutputBufferLimit {
+        match class {
+            ClientClass::Normal => self.normal,
+            ClientClass::PubSub => self.pubsub,
+            ClientClass::Replica => self.replica,
+        }
+    }
+}
+
+impl Default for ClientOutputBufferLimit {
+    fn default() -> Self {
+        Self {
+            normal: OutputBufferLimit::default(),
+            pubsub: OutputBufferLimit {
+                hard_limit: 32 << 20,
+                soft_limit: 8 << 20,
+                soft_seconds: 60,
+            },
+            replica: OutputBufferLimit {
+                hard_limit: 256 << 20,
+                soft_limit: 64 << 20,
+                soft_seconds: 60,
+            },
         }
     }
 }
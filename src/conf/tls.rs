@@ -1,4 +1,9 @@
+use rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
 use serde::Deserialize;
+use std::{collections::HashMap, fs::File, io::BufReader, sync::Arc};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename = "tls")]
@@ -6,4 +11,145 @@ pub struct TLSConf {
     pub port: u16,
     pub cert_file: String,
     pub key_file: String,
+    // 根据客户端的SNI选择证书(hostname -> 证书)，未匹配时使用cert_file和key_file
+    #[serde(default)]
+    pub sni: HashMap<String, TLSCert>,
+    // 通过ALPN通告的协议，为空时不进行ALPN协商
+    #[serde(default)]
+    pub alpn_protocols: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TLSCert {
+    pub cert_file: String,
+    pub key_file: String,
+}
+
+impl TLSConf {
+    pub fn server_config(&self) -> anyhow::Result<rustls::ServerConfig> {
+        let mut resolver = SniCertResolver {
+            default: Arc::new(load_certified_key(&self.cert_file, &self.key_file)?),
+            by_name: HashMap::with_capacity(self.sni.len()),
+        };
+        for (hostname, cert) in &self.sni {
+            resolver.by_name.insert(
+                hostname.to_ascii_lowercase(),
+                Arc::new(load_certified_key(&cert.cert_file, &cert.key_file)?),
+            );
+        }
+
+        let mut config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+
+        config.alpn_protocols = self
+            .alpn_protocols
+            .iter()
+            .map(|p| p.as_bytes().to_vec())
+            .collect();
+
+        Ok(config)
+    }
+}
+
+/// 根据客户端的SNI选择证书，客户端未发送SNI或者没有匹配的证书时使用默认证书
+#[derive(Debug)]
+struct SniCertResolver {
+    default: Arc<CertifiedKey>,
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniCertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let cert = client_hello
+            .server_name()
+            .and_then(|name| self.by_name.get(&name.to_ascii_lowercase()))
+            .unwrap_or(&self.default);
+
+        Some(cert.clone())
+    }
+}
+
+fn load_certified_key(cert_file: &str, key_file: &str) -> anyhow::Result<CertifiedKey> {
+    let cert = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_file)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls::crypto::aws_lc_rs::sign::any_supported_type(&load_private_key(key_file)?)?;
+
+    Ok(CertifiedKey::new(cert, key))
+}
+
+fn load_private_key(key_file: &str) -> anyhow::Result<rustls::pki_types::PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut BufReader::new(File::open(key_file)?))?
+        .ok_or_else(|| anyhow::anyhow!("no private key found in {key_file}"))
+}
+
+#[cfg(test)]
+mod tls_tests {
+    use super::*;
+    use rustls::pki_types::{CertificateDer, ServerName};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+    fn certs(file: &str) -> Vec<CertificateDer<'static>> {
+        rustls_pemfile::certs(&mut BufReader::new(File::open(file).unwrap()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sni_test() {
+        crate::util::test_init();
+
+        let sni_cert = |host: &str| TLSCert {
+            cert_file: format!("ca/sni/{host}.crt"),
+            key_file: format!("ca/sni/{host}.key"),
+        };
+        let tls_conf = TLSConf {
+            port: 0,
+            cert_file: "ca/rutin.crt".to_string(),
+            key_file: "ca/rutin.key".to_string(),
+            sni: HashMap::from([
+                ("a.rutin.test".to_string(), sni_cert("a.rutin.test")),
+                ("B.rutin.test".to_string(), sni_cert("b.rutin.test")),
+            ]),
+            alpn_protocols: vec!["redis".to_string()],
+        };
+        let acceptor = TlsAcceptor::from(Arc::new(tls_conf.server_config().unwrap()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move { acceptor.accept(stream).await.ok() });
+            }
+        });
+
+        let mut roots = rustls::RootCertStore::empty();
+        for host in ["a.rutin.test", "b.rutin.test"] {
+            roots.add_parsable_certificates(certs(&format!("ca/sni/{host}.crt")));
+        }
+        let mut client_conf = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        client_conf.alpn_protocols = vec![b"redis".to_vec()];
+        let connector = TlsConnector::from(Arc::new(client_conf));
+
+        // 不同的SNI得到不同的证书
+        for host in ["a.rutin.test", "b.rutin.test"] {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let tls_stream = connector
+                .connect(ServerName::try_from(host.to_string()).unwrap(), stream)
+                .await
+                .unwrap();
+
+            let (_, conn) = tls_stream.get_ref();
+            assert_eq!(
+                conn.peer_certificates().unwrap(),
+                certs(&format!("ca/sni/{host}.crt"))
+            );
+            assert_eq!(conn.alpn_protocol(), Some(b"redis".as_ref()));
+        }
+    }
 }
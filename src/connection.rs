@@ -16,6 +16,9 @@ use tokio::{
 };
 use tracing::{error, instrument, trace};
 
+// 批处理中写缓冲区的大小达到该值时提前写出，避免大量回复占用过多内存
+const WRITE_FLUSH_THRESHOLD: usize = 64 * 1024;
+
 pub trait AsyncStream:
     AsyncRead + AsyncReadExt + AsyncWrite + AsyncWriteExt + Unpin + Send
 {
@@ -33,6 +36,9 @@ where
     /// 支持批处理
     batch: usize,
     pub max_batch: usize,
+    // 从stream读取和写入stream的总字节数
+    net_in: u64,
+    net_out: u64,
 }
 
 impl<S: AsyncStream> Connection<S> {
@@ -43,6 +49,8 @@ impl<S: AsyncStream> Connection<S> {
             writer_buf: BytesMut::with_capacity(1024),
             batch: 0,
             max_batch: max_batch_count,
+            net_in: 0,
+            net_out: 0,
         }
     }
 
@@ -54,6 +62,21 @@ impl<S: AsyncStream> Connection<S> {
         self.batch = count;
     }
 
+    /// 从stream读取的总字节数
+    pub const fn net_in(&self) -> u64 {
+        self.net_in
+    }
+
+    /// 写入stream的总字节数
+    pub const fn net_out(&self) -> u64 {
+        self.net_out
+    }
+
+    /// 尚未写出到stream的字节数
+    pub fn pending_write_len(&self) -> usize {
+        self.writer_buf.len()
+    }
+
     #[inline]
     pub async fn shutdown(&mut self) -> io::Result<()> {
         self.stream.shutdown().await
@@ -66,23 +89,30 @@ impl<S: AsyncStream> Connection<S> {
 
     #[inline]
     pub async fn read_buf<B: BufMut + ?Sized>(&mut self, buf: &mut B) -> io::Result<usize> {
-        self.stream.read_buf(buf).await
+        let n = self.stream.read_buf(buf).await?;
+        self.net_in += n as u64;
+        Ok(n)
     }
 
     #[inline]
     pub async fn write_buf<B: Buf>(&mut self, buf: &mut B) -> io::Result<usize> {
-        self.stream.write_buf(buf).await
+        let n = self.stream.write_buf(buf).await?;
+        self.net_out += n as u64;
+        Ok(n)
     }
 
     #[inline]
     pub async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.stream.write_all(buf).await
+        self.stream.write_all(buf).await?;
+        self.net_out += buf.len() as u64;
+        Ok(())
     }
 
     #[inline]
     #[instrument(level = "trace", skip(self), ret, err)]
     pub async fn read_frame(&mut self) -> FrameResult<Option<Resp3>> {
-        Resp3::decode_async(&mut self.stream, &mut self.reader_buf).await
+        let mut stream = CountRead::new(&mut self.stream, &mut self.net_in);
+        Resp3::decode_async(&mut stream, &mut self.reader_buf).await
     }
 
     // 尝试读取多个frame，直到buffer和stream都为空
@@ -92,7 +122,8 @@ impl<S: AsyncStream> Connection<S> {
         let mut frames = Vec::with_capacity(32);
 
         loop {
-            let frame = match Resp3::decode_async(&mut self.stream, &mut self.reader_buf).await? {
+            let mut stream = CountRead::new(&mut self.stream, &mut self.net_in);
+            let frame = match Resp3::decode_async(&mut stream, &mut self.reader_buf).await? {
                 Some(frame) => frame,
                 None => return Ok(None),
             };
@@ -108,7 +139,7 @@ impl<S: AsyncStream> Connection<S> {
 
             // 尝试继续从stream读取数据到buffer，如果buffer为空则继续读取，如果阻塞则返回结果
             while self.reader_buf.is_empty() {
-                let fut = self.stream.read_buf(&mut self.reader_buf);
+                let fut = self.read_buf_inner();
                 pin_mut!(fut);
 
                 let mut cx = Context::from_waker(noop_waker_ref());
@@ -128,18 +159,120 @@ impl<S: AsyncStream> Connection<S> {
         St: AsRef<str> + PartialEq + std::fmt::Debug,
     {
         frame.encode_buf(&mut self.writer_buf);
+        self.may_flush().await
+    }
+
+    /// 以RESP2格式写出frame
+    #[inline]
+    #[instrument(level = "trace", skip(self), err)]
+    pub async fn write_resp2_frame<B, St>(&mut self, frame: &Resp3<B, St>) -> io::Result<()>
+    where
+        B: AsRef<[u8]> + PartialEq + std::fmt::Debug,
+        St: AsRef<str> + PartialEq + std::fmt::Debug,
+    {
+        frame.encode_resp2_buf(&mut self.writer_buf);
+        self.may_flush().await
+    }
+
+    /// 只将frame编码到写缓冲区而不写出，之后需要调用`write_buffered`
+    #[inline]
+    pub fn buffer_frame<B, St>(&mut self, frame: &Resp3<B, St>, resp3: bool)
+    where
+        B: AsRef<[u8]> + PartialEq,
+        St: AsRef<str> + PartialEq,
+    {
+        if resp3 {
+            frame.encode_buf(&mut self.writer_buf);
+        } else {
+            frame.encode_resp2_buf(&mut self.writer_buf);
+        }
+    }
+
+    /// 只将聚合类型的头部(例如`*<len>\r\n`)编码到写缓冲区，之后需要调用`write_buffered`
+    #[inline]
+    pub fn buffer_header(&mut self, prefix: u8, len: usize) {
+        self.writer_buf.put_u8(prefix);
+        self.writer_buf
+            .put_slice(itoa::Buffer::new().format(len).as_bytes());
+        self.writer_buf.put_slice(b"\r\n");
+    }
+
+    /// 写出`buffer_frame`和`buffer_header`编码的数据，与`write_frame`一样遵循批处理
+    #[inline]
+    pub async fn write_buffered(&mut self) -> io::Result<()> {
+        self.may_flush().await
+    }
 
-        if self.batch > 0 {
-            self.batch -= 1;
+    /// 写缓冲区达到`WRITE_FLUSH_THRESHOLD`时立即写出(即使在批处理中)，否则什么也不做。
+    /// 用于流式回复，避免逐个编码的元素全部滞留在写缓冲区
+    #[inline]
+    pub async fn flush_if_full(&mut self) -> io::Result<()> {
+        if self.writer_buf.len() >= WRITE_FLUSH_THRESHOLD {
+            self.may_flush().await?;
         }
 
-        if self.batch == 0 {
-            self.stream.write_buf(&mut self.writer_buf).await?;
+        Ok(())
+    }
+
+    /// 批处理中的一个命令处理完毕。最后一个命令处理完毕时(即读缓冲区中没有待处理的命令)，
+    /// 一次写出所有缓存的回复
+    #[inline]
+    pub async fn finish_command(&mut self) -> io::Result<()> {
+        self.batch = self.batch.saturating_sub(1);
+        self.may_flush().await
+    }
+
+    // 不在批处理中或者写缓冲区过大时才写出，否则等待批处理结束，以减少系统调用
+    #[inline]
+    async fn may_flush(&mut self) -> io::Result<()> {
+        if self.batch == 0 || self.writer_buf.len() >= WRITE_FLUSH_THRESHOLD {
+            // write_buf可能只写出部分数据，剩余的数据不能滞留到下一次写出
+            while !self.writer_buf.is_empty() {
+                let n = self.stream.write_buf(&mut self.writer_buf).await?;
+                // 与write_all一致，对端不再接收数据时返回错误，而不是丢弃剩余的数据
+                if n == 0 {
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                self.net_out += n as u64;
+            }
             self.flush().await?;
         }
 
         Ok(())
     }
+
+    #[inline]
+    async fn read_buf_inner(&mut self) -> io::Result<usize> {
+        let n = self.stream.read_buf(&mut self.reader_buf).await?;
+        self.net_in += n as u64;
+        Ok(n)
+    }
+}
+
+/// 统计从stream读取的字节数
+struct CountRead<'a, S> {
+    stream: &'a mut S,
+    count: &'a mut u64,
+}
+
+impl<'a, S> CountRead<'a, S> {
+    fn new(stream: &'a mut S, count: &'a mut u64) -> Self {
+        Self { stream, count }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountRead<'_, S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut *this.stream).poll_read(cx, buf);
+        *this.count += (buf.filled().len() - filled) as u64;
+        res
+    }
 }
 
 impl Connection<FakeStream> {
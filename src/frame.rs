@@ -10,7 +10,7 @@ use bytestring::ByteString;
 use mlua::{prelude::*, Value};
 use num_bigint::BigInt;
 use snafu::Snafu;
-use std::{hash::Hash, io, iter::Iterator, ops::Range, ptr::slice_from_raw_parts};
+use std::{borrow::Cow, hash::Hash, io, iter::Iterator, ops::Range, ptr::slice_from_raw_parts};
 use strum::{EnumDiscriminants, IntoStaticStr};
 use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio_util::{
@@ -52,6 +52,9 @@ const PUSH_PREFIX: u8 = b'>';
 const CHUNKED_STRING_PREFIX: u8 = b'?';
 const CHUNKED_STRING_LENGTH_PREFIX: u8 = b';';
 
+// inline command的最大长度
+const INLINE_MAX_SIZE: usize = 1024 * 64;
+
 pub type Attributes<B, S> = AHashMap<Resp3<B, S>, Resp3<B, S>>;
 
 #[derive(Clone, Debug, IntoStaticStr, EnumDiscriminants)]
@@ -797,8 +800,8 @@ where
                     encode_attributes(buf, attr)
                 }
                 buf.put_u8(DOUBLE_PREFIX);
-                if inner.fract() == 0.0 {
-                    buf.put_slice(itoa::Buffer::new().format((*inner) as i64).as_bytes());
+                if let Some(i) = double_as_int(*inner) {
+                    buf.put_slice(itoa::Buffer::new().format(i).as_bytes());
                 } else {
                     buf.put_slice(ryu::Buffer::new().format(*inner).as_bytes());
                 }
@@ -901,6 +904,196 @@ where
             }
         }
     }
+
+    /// 以RESP3格式编码后的字节数，与`encode().len()`相同，但无需编码
+    pub fn encoded_len(&self) -> usize {
+        #[inline]
+        fn int_len(n: impl itoa::Integer) -> usize {
+            itoa::Buffer::new().format(n).len()
+        }
+
+        // 前缀、长度以及CRLF
+        #[inline]
+        fn header_len(len: usize) -> usize {
+            1 + int_len(len) + 2
+        }
+
+        let attributes_len = |attr: Option<&Attributes<B, S>>| {
+            attr.map_or(0, |attr| {
+                header_len(attr.len())
+                    + attr
+                        .iter()
+                        .map(|(k, v)| k.encoded_len() + v.encoded_len())
+                        .sum::<usize>()
+            })
+        };
+
+        match self {
+            Resp3::SimpleString { inner, attributes }
+            | Resp3::SimpleError { inner, attributes } => {
+                attributes_len(attributes.as_ref()) + 1 + inner.as_ref().len() + 2
+            }
+            Resp3::Integer { inner, attributes } => {
+                attributes_len(attributes.as_ref()) + 1 + int_len(*inner) + 2
+            }
+            Resp3::BlobString { inner, attributes } | Resp3::BlobError { inner, attributes } => {
+                let len = inner.as_ref().len();
+                attributes_len(attributes.as_ref()) + header_len(len) + len + 2
+            }
+            Resp3::Array { inner, attributes } | Resp3::Push { inner, attributes } => {
+                attributes_len(attributes.as_ref())
+                    + header_len(inner.len())
+                    + inner.iter().map(Resp3::encoded_len).sum::<usize>()
+            }
+            Resp3::Set { inner, attributes } => {
+                attributes_len(attributes.as_ref())
+                    + header_len(inner.len())
+                    + inner.iter().map(Resp3::encoded_len).sum::<usize>()
+            }
+            Resp3::Null => 3,
+            Resp3::Boolean { attributes, .. } => attributes_len(attributes.as_ref()) + 4,
+            Resp3::Double { inner, attributes } => {
+                let len = if let Some(i) = double_as_int(*inner) {
+                    int_len(i)
+                } else {
+                    ryu::Buffer::new().format(*inner).len()
+                };
+                attributes_len(attributes.as_ref()) + 1 + len + 2
+            }
+            Resp3::BigNumber { inner, attributes } => {
+                attributes_len(attributes.as_ref()) + 1 + inner.to_str_radix(10).len() + 2
+            }
+            Resp3::VerbatimString {
+                format,
+                data,
+                attributes,
+            } => {
+                let len = data.as_ref().len();
+                attributes_len(attributes.as_ref())
+                    + header_len(len + 4)
+                    + format.len()
+                    + 1
+                    + len
+                    + 2
+            }
+            Resp3::Map { inner, attributes } => {
+                attributes_len(attributes.as_ref())
+                    + header_len(inner.len())
+                    + inner
+                        .iter()
+                        .map(|(k, v)| k.encoded_len() + v.encoded_len())
+                        .sum::<usize>()
+            }
+            Resp3::ChunkedString(chunks) => {
+                4 + chunks
+                    .iter()
+                    .map(|chunk| header_len(chunk.as_ref().len()) + chunk.as_ref().len() + 2)
+                    .sum::<usize>()
+                    + 4
+            }
+            Resp3::Hello { version, auth } => {
+                let auth_len = auth.as_ref().map_or(0, |(user, pass)| {
+                    5 + user.as_ref().len() + 1 + pass.as_ref().len()
+                });
+                6 + int_len(*version) + 1 + auth_len + 2
+            }
+        }
+    }
+
+    #[inline]
+    pub fn encode_resp2(&self) -> BytesMut {
+        let mut buf = BytesMut::with_capacity(64);
+        self.encode_resp2_buf(&mut buf);
+        buf.split()
+    }
+
+    /// 以RESP2格式编码。RESP2不支持的类型会被转换为最接近的RESP2类型，属性会被忽略
+    pub fn encode_resp2_buf(&self, buf: &mut impl BufMut) {
+        #[inline]
+        fn put_blob(buf: &mut impl BufMut, blob: &[u8]) {
+            buf.put_u8(BLOB_STRING_PREFIX);
+            buf.put_slice(itoa::Buffer::new().format(blob.len()).as_bytes());
+            buf.put_slice(CRLF);
+            buf.put_slice(blob);
+            buf.put_slice(CRLF);
+        }
+
+        #[inline]
+        fn put_array_len(buf: &mut impl BufMut, len: usize) {
+            buf.put_u8(ARRAY_PREFIX);
+            buf.put_slice(itoa::Buffer::new().format(len).as_bytes());
+            buf.put_slice(CRLF);
+        }
+
+        match self {
+            Resp3::SimpleString { inner, .. } => {
+                buf.put_u8(SIMPLE_STRING_PREFIX);
+                buf.put_slice(inner.as_ref().as_bytes());
+                buf.put_slice(CRLF);
+            }
+            Resp3::SimpleError { inner, .. } => {
+                buf.put_u8(ERROR_PREFIX);
+                buf.put_slice(inner.as_ref().as_bytes());
+                buf.put_slice(CRLF);
+            }
+            Resp3::Integer { inner, .. } => {
+                buf.put_u8(INTEGER_PREFIX);
+                buf.put_slice(itoa::Buffer::new().format(*inner).as_bytes());
+                buf.put_slice(CRLF);
+            }
+            Resp3::BlobString { inner, .. } => put_blob(buf, inner.as_ref()),
+            Resp3::Array { inner, .. } | Resp3::Push { inner, .. } => {
+                put_array_len(buf, inner.len());
+                for frame in inner {
+                    frame.encode_resp2_buf(buf);
+                }
+            }
+            // RESP2使用null bulk string表示空值
+            Resp3::Null => buf.put_slice(b"$-1\r\n"),
+            Resp3::Boolean { inner, .. } => {
+                buf.put_slice(if *inner { b":1\r\n" } else { b":0\r\n" })
+            }
+            Resp3::Double { inner, .. } => {
+                if let Some(i) = double_as_int(*inner) {
+                    put_blob(buf, itoa::Buffer::new().format(i).as_bytes());
+                } else {
+                    put_blob(buf, ryu::Buffer::new().format(*inner).as_bytes());
+                }
+            }
+            Resp3::BigNumber { inner, .. } => put_blob(buf, inner.to_str_radix(10).as_bytes()),
+            Resp3::BlobError { inner, .. } => {
+                buf.put_u8(ERROR_PREFIX);
+                buf.put_slice(inner.as_ref());
+                buf.put_slice(CRLF);
+            }
+            Resp3::VerbatimString { data, .. } => put_blob(buf, data.as_ref()),
+            // map被展开为[k1, v1, k2, v2, ...]
+            Resp3::Map { inner, .. } => {
+                put_array_len(buf, inner.len() * 2);
+                for (k, v) in inner {
+                    k.encode_resp2_buf(buf);
+                    v.encode_resp2_buf(buf);
+                }
+            }
+            Resp3::Set { inner, .. } => {
+                put_array_len(buf, inner.len());
+                for frame in inner {
+                    frame.encode_resp2_buf(buf);
+                }
+            }
+            Resp3::ChunkedString(chunks) => {
+                let len: usize = chunks.iter().map(|chunk| chunk.as_ref().len()).sum();
+                buf.put_u8(BLOB_STRING_PREFIX);
+                buf.put_slice(itoa::Buffer::new().format(len).as_bytes());
+                buf.put_slice(CRLF);
+                for chunk in chunks {
+                    buf.put_slice(chunk.as_ref());
+                }
+                buf.put_slice(CRLF);
+            }
+            Resp3::Hello { .. } => self.encode_buf(buf),
+        }
+    }
 }
 
 // 解码
@@ -923,248 +1116,281 @@ impl Resp3<BytesMut, ByteString> {
             io_read: &mut R,
             src: &mut BytesMut,
         ) -> FrameResult<Resp3> {
-            let res = match src.get_u8() {
-                SIMPLE_STRING_PREFIX => Resp3::SimpleString {
-                    inner: Resp3::decode_string_async(io_read, src).await?,
-                    attributes: None,
-                },
-                ERROR_PREFIX => Resp3::SimpleError {
-                    inner: Resp3::decode_string_async(io_read, src).await?,
-                    attributes: None,
-                },
-                INTEGER_PREFIX => Resp3::Integer {
-                    inner: Resp3::decode_decimal_async(io_read, src).await?,
-                    attributes: None,
-                },
-                BLOB_STRING_PREFIX => {
-                    let line = Resp3::decode_line_async(io_read, src).await?;
-
-                    if Resp3::get(&line, 0..1)?[0] == CHUNKED_STRING_PREFIX {
-                        let mut chunks = Vec::new();
-                        loop {
-                            let mut line = Resp3::decode_line_async(io_read, src).await?;
-
-                            if Resp3::get_u8(&mut line)? != CHUNKED_STRING_LENGTH_PREFIX {
-                                return Err(FrameError::InvalidFormat {
-                                    msg: "invalid chunk length prefix".to_string(),
-                                });
+            // 忽略空行之后继续解析下一行。使用循环而不是递归，避免大量的空行导致栈溢出
+            let res = loop {
+                // 聚合类型的元素可能尚未到达
+                Resp3::need_bytes_async(io_read, src, 1).await?;
+
+                break match src.get_u8() {
+                    SIMPLE_STRING_PREFIX => Resp3::SimpleString {
+                        inner: Resp3::decode_string_async(io_read, src).await?,
+                        attributes: None,
+                    },
+                    ERROR_PREFIX => Resp3::SimpleError {
+                        inner: Resp3::decode_string_async(io_read, src).await?,
+                        attributes: None,
+                    },
+                    INTEGER_PREFIX => Resp3::Integer {
+                        inner: Resp3::decode_decimal_async(io_read, src).await?,
+                        attributes: None,
+                    },
+                    BLOB_STRING_PREFIX => {
+                        let line = Resp3::decode_line_async(io_read, src).await?;
+
+                        if Resp3::get(&line, 0..1)?[0] == CHUNKED_STRING_PREFIX {
+                            let mut chunks = Vec::new();
+                            loop {
+                                let mut line = Resp3::decode_line_async(io_read, src).await?;
+
+                                if Resp3::get_u8(&mut line)? != CHUNKED_STRING_LENGTH_PREFIX {
+                                    return Err(FrameError::InvalidFormat {
+                                        msg: "invalid chunk length prefix".to_string(),
+                                    });
+                                }
+
+                                let len = util::atoi(&line).map_err(|_| {
+                                    io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        "invalid chunk length",
+                                    )
+                                })?;
+
+                                if len == 0 {
+                                    break;
+                                }
+
+                                Resp3::need_bytes_async(io_read, src, len + 2).await?;
+                                let res = src.split_to(len);
+                                src.advance(2);
+
+                                chunks.push(res.freeze());
                             }
 
-                            let len = util::atoi(&line).map_err(|_| {
-                                io::Error::new(io::ErrorKind::InvalidData, "invalid chunk length")
+                            Resp3::ChunkedString(chunks)
+                        } else if line.as_ref() == b"-1" {
+                            // RESP2的null bulk string
+                            Resp3::Null
+                        } else {
+                            let len = util::atoi(&line).map_err(|_| FrameError::InvalidFormat {
+                                msg: "invalid blob string length".to_string(),
                             })?;
 
-                            if len == 0 {
-                                break;
-                            }
-
                             Resp3::need_bytes_async(io_read, src, len + 2).await?;
                             let res = src.split_to(len);
                             src.advance(2);
 
-                            chunks.push(res.freeze());
+                            Resp3::BlobString {
+                                inner: res.freeze(),
+                                attributes: None,
+                            }
                         }
+                    }
+                    ARRAY_PREFIX => {
+                        let len = Resp3::decode_decimal_async(io_read, src).await?;
+
+                        // RESP2的null array
+                        if len == -1 {
+                            Resp3::Null
+                        } else {
+                            let len =
+                                usize::try_from(len).map_err(|_| FrameError::InvalidFormat {
+                                    msg: "invalid array length".to_string(),
+                                })?;
+
+                            let mut frames = Vec::with_capacity(len);
+                            for _ in 0..len {
+                                let frame = Box::pin(_decode_async(io_read, src)).await?;
+                                frames.push(frame);
+                            }
 
-                        Resp3::ChunkedString(chunks)
-                    } else {
-                        let len = util::atoi(&line).map_err(|_| FrameError::InvalidFormat {
-                            msg: "invalid blob string length".to_string(),
-                        })?;
-
-                        Resp3::need_bytes_async(io_read, src, len + 2).await?;
-                        let res = src.split_to(len);
-                        src.advance(2);
-
-                        Resp3::BlobString {
-                            inner: res.freeze(),
-                            attributes: None,
+                            Resp3::Array {
+                                inner: frames,
+                                attributes: None,
+                            }
                         }
                     }
-                }
-                ARRAY_PREFIX => {
-                    let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
-
-                    let mut frames = Vec::with_capacity(len);
-                    for _ in 0..len {
-                        let frame = Box::pin(_decode_async(io_read, src)).await?;
-                        frames.push(frame);
+                    NULL_PREFIX => {
+                        Resp3::need_bytes_async(io_read, src, 2).await?;
+                        src.advance(2);
+                        Resp3::Null
                     }
+                    BOOLEAN_PREFIX => {
+                        Resp3::need_bytes_async(io_read, src, 3).await?;
 
-                    Resp3::Array {
-                        inner: frames,
-                        attributes: None,
-                    }
-                }
-                NULL_PREFIX => {
-                    Resp3::need_bytes_async(io_read, src, 2).await?;
-                    src.advance(2);
-                    Resp3::Null
-                }
-                BOOLEAN_PREFIX => {
-                    Resp3::need_bytes_async(io_read, src, 3).await?;
+                        let b = match src[0] {
+                            b't' => true,
+                            b'f' => false,
+                            _ => {
+                                return Err(FrameError::InvalidFormat {
+                                    msg: "invalid boolean".to_string(),
+                                });
+                            }
+                        };
+                        src.advance(3);
 
-                    let b = match src[0] {
-                        b't' => true,
-                        b'f' => false,
-                        _ => {
-                            return Err(FrameError::InvalidFormat {
-                                msg: "invalid boolean".to_string(),
-                            });
+                        Resp3::Boolean {
+                            inner: b,
+                            attributes: None,
                         }
-                    };
-                    src.advance(3);
-
-                    Resp3::Boolean {
-                        inner: b,
-                        attributes: None,
                     }
-                }
-                DOUBLE_PREFIX => {
-                    let line = Resp3::decode_line_async(io_read, src).await?;
+                    DOUBLE_PREFIX => {
+                        let line = Resp3::decode_line_async(io_read, src).await?;
 
-                    let double = atof(&line)
-                        .map_err(|e| FrameError::InvalidFormat { msg: e.to_string() })?;
+                        let double = atof(&line)
+                            .map_err(|e| FrameError::InvalidFormat { msg: e.to_string() })?;
 
-                    Resp3::Double {
-                        inner: double,
-                        attributes: None,
+                        Resp3::Double {
+                            inner: double,
+                            attributes: None,
+                        }
                     }
-                }
-                BIG_NUMBER_PREFIX => {
-                    let line = Resp3::decode_line_async(io_read, src).await?;
+                    BIG_NUMBER_PREFIX => {
+                        let line = Resp3::decode_line_async(io_read, src).await?;
 
-                    let n = BigInt::parse_bytes(&line, 10).ok_or_else(|| {
-                        FrameError::InvalidFormat {
-                            msg: "invalid big number".to_string(),
-                        }
-                    })?;
+                        let n = BigInt::parse_bytes(&line, 10).ok_or_else(|| {
+                            FrameError::InvalidFormat {
+                                msg: "invalid big number".to_string(),
+                            }
+                        })?;
 
-                    Resp3::BigNumber {
-                        inner: n,
-                        attributes: None,
+                        Resp3::BigNumber {
+                            inner: n,
+                            attributes: None,
+                        }
                     }
-                }
-                BLOB_ERROR_PREFIX => {
-                    let len = Resp3::decode_length_async(io_read, src).await?;
+                    BLOB_ERROR_PREFIX => {
+                        let len = Resp3::decode_length_async(io_read, src).await?;
 
-                    Resp3::need_bytes_async(io_read, src, len + 2).await?;
-                    let e = src.split_to(len);
-                    src.advance(2);
+                        Resp3::need_bytes_async(io_read, src, len + 2).await?;
+                        let e = src.split_to(len);
+                        src.advance(2);
 
-                    Resp3::BlobError {
-                        inner: e.freeze(),
-                        attributes: None,
+                        Resp3::BlobError {
+                            inner: e.freeze(),
+                            attributes: None,
+                        }
                     }
-                }
-                VERBATIM_STRING_PREFIX => {
-                    let len = Resp3::decode_length_async(io_read, src).await?;
-
-                    Resp3::need_bytes_async(io_read, src, len + 2).await?;
+                    VERBATIM_STRING_PREFIX => {
+                        let len = Resp3::decode_length_async(io_read, src).await?;
 
-                    let format = src[0..3].try_into().unwrap();
-                    src.advance(4);
+                        Resp3::need_bytes_async(io_read, src, len + 2).await?;
 
-                    let data = src.split_to(len).freeze();
-                    src.advance(2);
+                        let format = src[0..3].try_into().unwrap();
+                        src.advance(4);
 
-                    Resp3::VerbatimString {
-                        format,
-                        data,
-                        attributes: None,
-                    }
-                }
-                MAP_PREFIX => {
-                    let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
-
-                    let mut map = AHashMap::with_capacity(len);
-                    for _ in 0..len {
-                        let k = Box::pin(_decode_async(io_read, src)).await?;
-                        let v = Box::pin(_decode_async(io_read, src)).await?;
-                        map.insert(k, v);
-                    }
+                        let data = src.split_to(len - 4).freeze();
+                        src.advance(2);
 
-                    // map的key由客户端保证唯一
-                    Resp3::Map {
-                        inner: map,
-                        attributes: None,
+                        Resp3::VerbatimString {
+                            format,
+                            data,
+                            attributes: None,
+                        }
                     }
-                }
-                SET_PREFIX => {
-                    let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
+                    MAP_PREFIX => {
+                        let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
+
+                        let mut map = AHashMap::with_capacity(len);
+                        for _ in 0..len {
+                            let k = Box::pin(_decode_async(io_read, src)).await?;
+                            let v = Box::pin(_decode_async(io_read, src)).await?;
+                            map.insert(k, v);
+                        }
 
-                    let mut set = AHashSet::with_capacity(len);
-                    for _ in 0..len {
-                        let frame = Box::pin(_decode_async(io_read, src)).await?;
-                        set.insert(frame);
+                        // map的key由客户端保证唯一
+                        Resp3::Map {
+                            inner: map,
+                            attributes: None,
+                        }
                     }
+                    SET_PREFIX => {
+                        let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
 
-                    // set的元素由客户端保证唯一
-                    Resp3::Set {
-                        inner: set,
-                        attributes: None,
-                    }
-                }
-                PUSH_PREFIX => {
-                    let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
+                        let mut set = AHashSet::with_capacity(len);
+                        for _ in 0..len {
+                            let frame = Box::pin(_decode_async(io_read, src)).await?;
+                            set.insert(frame);
+                        }
 
-                    let mut frames = Vec::with_capacity(len);
-                    for _ in 0..len {
-                        let frame = Box::pin(_decode_async(io_read, src)).await?;
-                        frames.push(frame);
+                        // set的元素由客户端保证唯一
+                        Resp3::Set {
+                            inner: set,
+                            attributes: None,
+                        }
                     }
+                    PUSH_PREFIX => {
+                        let len = Resp3::decode_decimal_async(io_read, src).await? as usize;
 
-                    Resp3::Push {
-                        inner: frames,
-                        attributes: None,
-                    }
-                }
-                b'H' => {
-                    let mut line = Resp3::decode_line_async(io_read, src).await?;
+                        let mut frames = Vec::with_capacity(len);
+                        for _ in 0..len {
+                            let frame = Box::pin(_decode_async(io_read, src)).await?;
+                            frames.push(frame);
+                        }
 
-                    let ello = Resp3::decode_until_async(io_read, &mut line, b' ').await?;
-                    if ello != b"ELLO".as_slice() {
-                        return Err(FrameError::InvalidFormat {
-                            msg: "failed to parse hello".to_string(),
-                        });
+                        Resp3::Push {
+                            inner: frames,
+                            attributes: None,
+                        }
                     }
+                    b'H' => {
+                        let mut line = Resp3::decode_inline_line_async(io_read, src).await?;
 
-                    let version =
-                        util::atoi(&Resp3::decode_until(&mut line, b' ')?).map_err(|e| {
-                            FrameError::InvalidFormat {
-                                msg: format!("invalid version: {}", e),
-                            }
-                        })?;
-
-                    if line.is_empty() {
-                        Resp3::Hello {
-                            version,
-                            auth: None,
+                        // 不是HELLO，则作为inline command处理
+                        if !line.starts_with(b"ELLO ") {
+                            return Resp3::decode_inline(b'H', &line)?
+                                .ok_or(FrameError::Incomplete);
                         }
-                    } else {
-                        let auth = Resp3::decode_until_async(io_read, &mut line, b' ').await?;
-                        if auth != b"AUTH".as_slice() {
+
+                        let ello = Resp3::decode_until_async(io_read, &mut line, b' ').await?;
+                        if ello != b"ELLO".as_slice() {
                             return Err(FrameError::InvalidFormat {
-                                msg: "invalid auth".to_string(),
+                                msg: "failed to parse hello".to_string(),
                             });
                         }
 
-                        let username = Resp3::decode_until_async(io_read, &mut line, b' ')
-                            .await?
-                            .freeze();
+                        let version =
+                            util::atoi(&Resp3::decode_until(&mut line, b' ')?).map_err(|e| {
+                                FrameError::InvalidFormat {
+                                    msg: format!("invalid version: {}", e),
+                                }
+                            })?;
+
+                        if line.is_empty() {
+                            Resp3::Hello {
+                                version,
+                                auth: None,
+                            }
+                        } else {
+                            let auth = Resp3::decode_until_async(io_read, &mut line, b' ').await?;
+                            if auth != b"AUTH".as_slice() {
+                                return Err(FrameError::InvalidFormat {
+                                    msg: "invalid auth".to_string(),
+                                });
+                            }
+
+                            let username = Resp3::decode_until_async(io_read, &mut line, b' ')
+                                .await?
+                                .freeze();
 
-                        let password = line.split().freeze();
+                            let password = line.split().freeze();
 
-                        Resp3::Hello {
-                            version,
-                            auth: Some((username, password)),
+                            Resp3::Hello {
+                                version,
+                                auth: Some((username, password)),
+                            }
                         }
                     }
-                }
-                prefix => {
-                    return Err(FrameError::InvalidFormat {
-                        msg: format!("invalid prefix: {}", prefix),
-                    });
-                }
+                    // 不是RESP的前缀，则作为inline command处理
+                    prefix => {
+                        let line = Resp3::decode_inline_line_async(io_read, src).await?;
+
+                        match Resp3::decode_inline(prefix, &line)? {
+                            Some(frame) => frame,
+                            // 忽略空行
+                            None => continue,
+                        }
+                    }
+                };
             };
 
             Ok(res)
@@ -1207,6 +1433,24 @@ impl Resp3<BytesMut, ByteString> {
         }
     }
 
+    #[inline]
+    async fn decode_inline_line_async<R: AsyncRead + Unpin + Send>(
+        io_read: &mut R,
+        src: &mut BytesMut,
+    ) -> FrameResult<BytesMut> {
+        loop {
+            match Self::decode_inline_line(src) {
+                Ok(line) => return Ok(line),
+                Err(FrameError::Incomplete) => {
+                    if io_read.read_buf(src).await? == 0 {
+                        return Err(FrameError::Incomplete);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     #[inline]
     async fn decode_until_async<R: AsyncRead + Unpin + Send>(
         io_read: &mut R,
@@ -1335,6 +1579,52 @@ impl Resp3<BytesMut, ByteString> {
         Err(FrameError::Incomplete)
     }
 
+    // inline command以`\r\n`或者`\n`结尾
+    #[inline]
+    fn decode_inline_line(src: &mut BytesMut) -> FrameResult<BytesMut> {
+        match memchr::memchr(b'\n', src) {
+            Some(i) if i <= INLINE_MAX_SIZE => {
+                let mut line = src.split_to(i);
+                src.advance(1);
+
+                if line.last() == Some(&b'\r') {
+                    line.truncate(line.len() - 1);
+                }
+
+                Ok(line)
+            }
+            None if src.len() <= INLINE_MAX_SIZE => Err(FrameError::Incomplete),
+            _ => Err(FrameError::InvalidFormat {
+                msg: "too big inline request".to_string(),
+            }),
+        }
+    }
+
+    /// # Desc:
+    ///
+    /// 解析inline command，例如`SET key "hello world"`。参数的拆分规则见
+    /// [`split_inline_args`]。prefix为已经被读取的第一个字节
+    ///
+    /// # Return:
+    ///
+    /// 返回由BlobString组成的Array，如果没有任何参数则返回None
+    fn decode_inline(prefix: u8, line: &[u8]) -> FrameResult<Option<Resp3>> {
+        let mut input = Vec::with_capacity(line.len() + 1);
+        input.push(prefix);
+        input.extend_from_slice(line);
+
+        let args = split_inline_args(&input)?;
+        if args.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Resp3::new_array(
+            args.into_iter()
+                .map(Resp3::new_blob_string)
+                .collect::<Vec<_>>(),
+        )))
+    }
+
     #[inline]
     fn decode_until(src: &mut BytesMut, byte: u8) -> FrameResult<BytesMut> {
         if let Some(i) = memchr::memchr(byte, src) {
@@ -1448,251 +1738,278 @@ impl Decoder for RESP3Decoder {
         ) -> Result<<RESP3Decoder as Decoder>::Item, <RESP3Decoder as Decoder>::Error> {
             let src = &mut decoder.buf;
 
-            if src.is_empty() {
-                return Err(FrameError::Incomplete);
-            }
+            // 忽略空行之后继续解析下一行。使用循环而不是递归，避免大量的空行导致栈溢出
+            let res = loop {
+                if src.is_empty() {
+                    return Err(FrameError::Incomplete);
+                }
 
-            let res = match src.get_u8() {
-                SIMPLE_STRING_PREFIX => Resp3::SimpleString {
-                    inner: Resp3::decode_string(src)?,
-                    attributes: None,
-                },
-                ERROR_PREFIX => Resp3::SimpleError {
-                    inner: Resp3::decode_string(src)?,
-                    attributes: None,
-                },
-                INTEGER_PREFIX => Resp3::Integer {
-                    inner: Resp3::decode_decimal(src)?,
-                    attributes: None,
-                },
-                BLOB_STRING_PREFIX => {
-                    let line = Resp3::decode_line(src)?;
+                break match src.get_u8() {
+                    SIMPLE_STRING_PREFIX => Resp3::SimpleString {
+                        inner: Resp3::decode_string(src)?,
+                        attributes: None,
+                    },
+                    ERROR_PREFIX => Resp3::SimpleError {
+                        inner: Resp3::decode_string(src)?,
+                        attributes: None,
+                    },
+                    INTEGER_PREFIX => Resp3::Integer {
+                        inner: Resp3::decode_decimal(src)?,
+                        attributes: None,
+                    },
+                    BLOB_STRING_PREFIX => {
+                        let line = Resp3::decode_line(src)?;
 
-                    if Resp3::get(&line, 0..1)?[0] == CHUNKED_STRING_PREFIX {
-                        let mut chunks = Vec::new();
-                        loop {
-                            let mut line = Resp3::decode_line(src)?;
+                        if Resp3::get(&line, 0..1)?[0] == CHUNKED_STRING_PREFIX {
+                            let mut chunks = Vec::new();
+                            loop {
+                                let mut line = Resp3::decode_line(src)?;
 
-                            if Resp3::get_u8(&mut line)? != CHUNKED_STRING_LENGTH_PREFIX {
-                                return Err(FrameError::InvalidFormat {
-                                    msg: "invalid chunk length prefix".to_string(),
-                                });
+                                if Resp3::get_u8(&mut line)? != CHUNKED_STRING_LENGTH_PREFIX {
+                                    return Err(FrameError::InvalidFormat {
+                                        msg: "invalid chunk length prefix".to_string(),
+                                    });
+                                }
+
+                                let len = util::atoi(&line).map_err(|_| {
+                                    io::Error::new(io::ErrorKind::InvalidData, "invalid length")
+                                })?;
+
+                                if len == 0 {
+                                    break;
+                                }
+
+                                Resp3::need_bytes(src, len + 2)?;
+                                let res = src.split_to(len);
+                                src.advance(2);
+
+                                chunks.push(res.freeze());
                             }
 
+                            Resp3::ChunkedString(chunks)
+                        } else if line.as_ref() == b"-1" {
+                            // RESP2的null bulk string
+                            Resp3::Null
+                        } else {
                             let len = util::atoi(&line).map_err(|_| {
                                 io::Error::new(io::ErrorKind::InvalidData, "invalid length")
                             })?;
 
-                            if len == 0 {
-                                break;
-                            }
-
                             Resp3::need_bytes(src, len + 2)?;
                             let res = src.split_to(len);
                             src.advance(2);
 
-                            chunks.push(res.freeze());
+                            Resp3::BlobString {
+                                inner: res.freeze(),
+                                attributes: None,
+                            }
                         }
+                    }
+                    ARRAY_PREFIX => {
+                        let len = Resp3::decode_decimal(src)?;
+
+                        // RESP2的null array
+                        if len == -1 {
+                            Resp3::Null
+                        } else {
+                            let len =
+                                usize::try_from(len).map_err(|_| FrameError::InvalidFormat {
+                                    msg: "invalid array length".to_string(),
+                                })?;
+
+                            let mut frames = Vec::with_capacity(len);
+                            for _ in 0..len {
+                                let frame = _decode(decoder)?;
+                                frames.push(frame);
+                            }
 
-                        Resp3::ChunkedString(chunks)
-                    } else {
-                        let len = util::atoi(&line).map_err(|_| {
-                            io::Error::new(io::ErrorKind::InvalidData, "invalid length")
-                        })?;
-
-                        Resp3::need_bytes(src, len + 2)?;
-                        let res = src.split_to(len);
-                        src.advance(2);
-
-                        Resp3::BlobString {
-                            inner: res.freeze(),
-                            attributes: None,
+                            Resp3::Array {
+                                inner: frames,
+                                attributes: None,
+                            }
                         }
                     }
-                }
-                ARRAY_PREFIX => {
-                    let len = Resp3::decode_length(src)?;
-
-                    let mut frames = Vec::with_capacity(len);
-                    for _ in 0..len {
-                        let frame = _decode(decoder)?;
-                        frames.push(frame);
+                    NULL_PREFIX => {
+                        Resp3::need_bytes(src, 2)?;
+                        src.advance(2);
+                        Resp3::Null
                     }
+                    BOOLEAN_PREFIX => {
+                        Resp3::need_bytes(src, 3)?;
 
-                    Resp3::Array {
-                        inner: frames,
-                        attributes: None,
-                    }
-                }
-                NULL_PREFIX => {
-                    Resp3::need_bytes(src, 2)?;
-                    src.advance(2);
-                    Resp3::Null
-                }
-                BOOLEAN_PREFIX => {
-                    Resp3::need_bytes(src, 3)?;
+                        let b = match src[0] {
+                            b't' => true,
+                            b'f' => false,
+                            _ => {
+                                return Err(FrameError::InvalidFormat {
+                                    msg: "invalid boolean".to_string(),
+                                });
+                            }
+                        };
+                        src.advance(3);
 
-                    let b = match src[0] {
-                        b't' => true,
-                        b'f' => false,
-                        _ => {
-                            return Err(FrameError::InvalidFormat {
-                                msg: "invalid boolean".to_string(),
-                            });
+                        Resp3::Boolean {
+                            inner: b,
+                            attributes: None,
                         }
-                    };
-                    src.advance(3);
-
-                    Resp3::Boolean {
-                        inner: b,
-                        attributes: None,
                     }
-                }
-                DOUBLE_PREFIX => {
-                    let line = Resp3::decode_line(src)?;
+                    DOUBLE_PREFIX => {
+                        let line = Resp3::decode_line(src)?;
 
-                    let double = atof(&line)
-                        .map_err(|e| FrameError::InvalidFormat { msg: e.to_string() })?;
+                        let double = atof(&line)
+                            .map_err(|e| FrameError::InvalidFormat { msg: e.to_string() })?;
 
-                    Resp3::Double {
-                        inner: double,
-                        attributes: None,
+                        Resp3::Double {
+                            inner: double,
+                            attributes: None,
+                        }
                     }
-                }
-                BIG_NUMBER_PREFIX => {
-                    let line = Resp3::decode_line(src)?;
+                    BIG_NUMBER_PREFIX => {
+                        let line = Resp3::decode_line(src)?;
 
-                    let n = BigInt::parse_bytes(&line, 10).ok_or_else(|| {
-                        FrameError::InvalidFormat {
-                            msg: "invalid big number".to_string(),
-                        }
-                    })?;
+                        let n = BigInt::parse_bytes(&line, 10).ok_or_else(|| {
+                            FrameError::InvalidFormat {
+                                msg: "invalid big number".to_string(),
+                            }
+                        })?;
 
-                    Resp3::BigNumber {
-                        inner: n,
-                        attributes: None,
+                        Resp3::BigNumber {
+                            inner: n,
+                            attributes: None,
+                        }
                     }
-                }
-                BLOB_ERROR_PREFIX => {
-                    let len = Resp3::decode_length(src)?;
+                    BLOB_ERROR_PREFIX => {
+                        let len = Resp3::decode_length(src)?;
 
-                    Resp3::need_bytes(src, len + 2)?;
-                    let e = src.split_to(len);
-                    src.advance(2);
+                        Resp3::need_bytes(src, len + 2)?;
+                        let e = src.split_to(len);
+                        src.advance(2);
 
-                    Resp3::BlobError {
-                        inner: e.freeze(),
+                        Resp3::BlobError {
+                            inner: e.freeze(),
 
-                        attributes: None,
+                            attributes: None,
+                        }
                     }
-                }
-                VERBATIM_STRING_PREFIX => {
-                    let len = Resp3::decode_length(src)?;
+                    VERBATIM_STRING_PREFIX => {
+                        let len = Resp3::decode_length(src)?;
 
-                    Resp3::need_bytes(src, len + 2)?;
-
-                    let format = src[0..3].try_into().unwrap();
-                    src.advance(4);
+                        Resp3::need_bytes(src, len + 2)?;
 
-                    let data = src.split_to(len - 4).freeze();
-                    src.advance(2);
+                        let format = src[0..3].try_into().unwrap();
+                        src.advance(4);
 
-                    Resp3::VerbatimString {
-                        format,
-                        data,
-                        attributes: None,
-                    }
-                }
-                MAP_PREFIX => {
-                    let len = Resp3::decode_length(src)?;
-
-                    let mut map = AHashMap::with_capacity(len);
-                    for _ in 0..len {
-                        let k = _decode(decoder)?;
-                        let v = _decode(decoder)?;
-                        map.insert(k, v);
-                    }
+                        let data = src.split_to(len - 4).freeze();
+                        src.advance(2);
 
-                    // map的key由客户端保证唯一
-                    Resp3::Map {
-                        inner: map,
-                        attributes: None,
+                        Resp3::VerbatimString {
+                            format,
+                            data,
+                            attributes: None,
+                        }
                     }
-                }
-                SET_PREFIX => {
-                    let len = Resp3::decode_length(src)?;
+                    MAP_PREFIX => {
+                        let len = Resp3::decode_length(src)?;
+
+                        let mut map = AHashMap::with_capacity(len);
+                        for _ in 0..len {
+                            let k = _decode(decoder)?;
+                            let v = _decode(decoder)?;
+                            map.insert(k, v);
+                        }
 
-                    let mut set = AHashSet::with_capacity(len);
-                    for _ in 0..len {
-                        let frame = _decode(decoder)?;
-                        set.insert(frame);
+                        // map的key由客户端保证唯一
+                        Resp3::Map {
+                            inner: map,
+                            attributes: None,
+                        }
                     }
+                    SET_PREFIX => {
+                        let len = Resp3::decode_length(src)?;
 
-                    // set的元素由客户端保证唯一
-                    Resp3::Set {
-                        inner: set,
-                        attributes: None,
-                    }
-                }
-                PUSH_PREFIX => {
-                    let len = Resp3::decode_length(src)?;
+                        let mut set = AHashSet::with_capacity(len);
+                        for _ in 0..len {
+                            let frame = _decode(decoder)?;
+                            set.insert(frame);
+                        }
 
-                    let mut frames = Vec::with_capacity(len);
-                    for _ in 0..len {
-                        let frame = _decode(decoder)?;
-                        frames.push(frame);
+                        // set的元素由客户端保证唯一
+                        Resp3::Set {
+                            inner: set,
+                            attributes: None,
+                        }
                     }
+                    PUSH_PREFIX => {
+                        let len = Resp3::decode_length(src)?;
 
-                    Resp3::Push {
-                        inner: frames,
-                        attributes: None,
-                    }
-                }
-                b'H' => {
-                    let mut line = Resp3::decode_line(src)?;
+                        let mut frames = Vec::with_capacity(len);
+                        for _ in 0..len {
+                            let frame = _decode(decoder)?;
+                            frames.push(frame);
+                        }
 
-                    let ello = Resp3::decode_until(&mut line, b' ')?;
-                    if ello != b"ELLO".as_slice() {
-                        return Err(FrameError::InvalidFormat {
-                            msg: "expect 'HELLO'".to_string(),
-                        });
+                        Resp3::Push {
+                            inner: frames,
+                            attributes: None,
+                        }
                     }
+                    b'H' => {
+                        let mut line = Resp3::decode_inline_line(src)?;
 
-                    let version =
-                        util::atoi(&Resp3::decode_until(&mut line, b' ')?).map_err(|e| {
-                            FrameError::InvalidFormat {
-                                msg: format!("invalid version: {}", e),
-                            }
-                        })?;
-
-                    if line.is_empty() {
-                        Resp3::Hello {
-                            version,
-                            auth: None,
+                        // 不是HELLO，则作为inline command处理
+                        if !line.starts_with(b"ELLO ") {
+                            return Resp3::decode_inline(b'H', &line)?
+                                .ok_or(FrameError::Incomplete);
                         }
-                    } else {
-                        let auth = Resp3::decode_until(&mut line, b' ')?;
-                        if auth != b"AUTH".as_slice() {
+
+                        let ello = Resp3::decode_until(&mut line, b' ')?;
+                        if ello != b"ELLO".as_slice() {
                             return Err(FrameError::InvalidFormat {
-                                msg: "invalid auth".to_string(),
+                                msg: "expect 'HELLO'".to_string(),
                             });
                         }
 
-                        let username = Resp3::decode_until(&mut line, b' ')?.freeze();
+                        let version =
+                            util::atoi(&Resp3::decode_until(&mut line, b' ')?).map_err(|e| {
+                                FrameError::InvalidFormat {
+                                    msg: format!("invalid version: {}", e),
+                                }
+                            })?;
+
+                        if line.is_empty() {
+                            Resp3::Hello {
+                                version,
+                                auth: None,
+                            }
+                        } else {
+                            let auth = Resp3::decode_until(&mut line, b' ')?;
+                            if auth != b"AUTH".as_slice() {
+                                return Err(FrameError::InvalidFormat {
+                                    msg: "invalid auth".to_string(),
+                                });
+                            }
+
+                            let username = Resp3::decode_until(&mut line, b' ')?.freeze();
 
-                        let password = line.split().freeze();
+                            let password = line.split().freeze();
 
-                        Resp3::Hello {
-                            version,
-                            auth: Some((username, password)),
+                            Resp3::Hello {
+                                version,
+                                auth: Some((username, password)),
+                            }
                         }
                     }
-                }
-                prefix => {
-                    return Err(FrameError::InvalidFormat {
-                        msg: format!("invalid prefix: {}", prefix),
-                    });
-                }
+                    // 不是RESP的前缀，则作为inline command处理
+                    prefix => {
+                        let line = Resp3::decode_inline_line(src)?;
+
+                        match Resp3::decode_inline(prefix, &line)? {
+                            Some(frame) => frame,
+                            // 忽略空行
+                            None => continue,
+                        }
+                    }
+                };
             };
 
             Ok(res)
@@ -2063,34 +2380,28 @@ impl<S: AsRef<str> + PartialEq> mlua::IntoLua<'_> for Resp3<Bytes, S> {
                 table.set("set", set_table)?;
                 Ok(Value::Table(table))
             }
-            // // Lua table with a single push field containing a Lua table representing the frames of the push message.
-            // // Push -> Lua Table { push: Lua Table }
-            // RESP3::Push { inner, .. } => {
-            //     let push_table = inner.into_lua(lua)?;
-            //
-            //     let table = lua.create_table()?;
-            //     table.set("push", push_table)?;
-            //     Ok(Value::Table(table))
-            // }
-            // // Lua table with a single chunk field containing a Lua table representing the chunks of the chunked message.
-            // // ChunkedString -> Lua Table { chunk: Lua Table }
-            // RESP3::ChunkedString(chunks) => {
-            //     let chunk_table = lua.create_table()?;
-            //     for (i, c) in chunks.iter().enumerate() {
-            //         let c = std::str::from_utf8(c)
-            //             .map_err(|_| mlua::Error::FromLuaConversionError {
-            //                 from: "Blob",
-            //                 to: "String",
-            //                 message: Some("invalid utf-8 string".to_string()),
-            //             })?
-            //             .into_lua(lua)?;
-            //         chunk_table.set(i + 1, c)?;
-            //     }
-            //
-            //     let table = lua.create_table()?;
-            //     table.set("chunk", chunk_table)?;
-            //     Ok(Value::Table(table))
-            // }
+            // Lua table with a single push field containing a Lua table representing the frames of the push message.
+            // Push -> Lua Table { push: Lua Table }
+            Resp3::Push { inner, .. } => {
+                let push_table = inner.into_lua(lua)?;
+
+                let table = lua.create_table()?;
+                table.set("push", push_table)?;
+                Ok(Value::Table(table))
+            }
+            // Lua table with a single chunk field containing a Lua table representing the chunks of the chunked message.
+            // ChunkedString -> Lua Table { chunk: Lua Table }
+            Resp3::ChunkedString(chunks) => {
+                let chunk_table = lua.create_table()?;
+                for (i, c) in chunks.iter().enumerate() {
+                    // Lua String可以包含任意字节，无需是合法的utf-8
+                    chunk_table.set(i + 1, lua.create_string(c)?)?;
+                }
+
+                let table = lua.create_table()?;
+                table.set("chunk", chunk_table)?;
+                Ok(Value::Table(table))
+            }
             _ => Err(mlua::Error::FromLuaConversionError {
                 from: "RESP3",
                 to: "LuaValue",
@@ -2127,10 +2438,13 @@ impl FromLua<'_> for Resp3 {
             }),
             // Lua Table { ok: Lua String } -> SimpleString
             // Lua Table { err: Lua String } -> SimpleError
+            // Lua Table { double: Lua Number } -> Double
+            // Lua Table { big_number: Lua String } -> BigNumber
             // Lua Table { verbatim_string: Lua Table { string: Lua String, format: Lua String } } -> VerbatimString
             // Lua Table { map: Lua Table } -> Map
             // Lua Table { set: Lua Table } -> Set
             // Lua Table { push: Lua Table } -> Push
+            // Lua Table { chunk: Lua Table } -> ChunkedString
             // Lua Table -> Array
             LuaValue::Table(table) => {
                 let ok = table.raw_get("ok")?;
@@ -2151,6 +2465,41 @@ impl FromLua<'_> for Resp3 {
                     });
                 }
 
+                let double = table.raw_get("double")?;
+
+                match double {
+                    LuaValue::Number(n) => {
+                        return Ok(Resp3::Double {
+                            inner: n,
+                            attributes: None,
+                        });
+                    }
+                    LuaValue::Integer(n) => {
+                        return Ok(Resp3::Double {
+                            inner: n as f64,
+                            attributes: None,
+                        });
+                    }
+                    _ => {}
+                }
+
+                let big_number = table.raw_get("big_number")?;
+
+                if let LuaValue::String(n) = big_number {
+                    let n = BigInt::parse_bytes(n.as_bytes(), 10).ok_or_else(|| {
+                        mlua::Error::FromLuaConversionError {
+                            from: "table",
+                            to: "RESP3::BigNumber",
+                            message: Some("invalid big number".to_string()),
+                        }
+                    })?;
+
+                    return Ok(Resp3::BigNumber {
+                        inner: n,
+                        attributes: None,
+                    });
+                }
+
                 let verbatim_string = table.raw_get("verbatim_string")?;
 
                 if let LuaValue::Table(verbatim_string) = verbatim_string {
@@ -2216,36 +2565,32 @@ impl FromLua<'_> for Resp3 {
                     });
                 }
 
-                // let push = table.raw_get("push")?;
-                //
-                // if let LuaValue::Table(push) = push {
-                //     let mut push_table = Vec::with_capacity(push.raw_len());
-                //     for pair in push.pairs::<usize, LuaValue>() {
-                //         let ele = pair?.1;
-                //         push_table.push(RESP3::from_lua(ele, _lua)?);
-                //     }
-                //
-                //     return Ok(RESP3::Push {
-                //         inner: push_table,
-                //         attributes: None,
-                //     });
-                // }
-                //
-                // let chunk = table.raw_get("chunk")?;
-                //
-                // if let LuaValue::Table(chunk) = chunk {
-                //     let mut chunk_table = Vec::with_capacity(chunk.raw_len());
-                //     for pair in chunk.pairs::<usize, LuaValue>() {
-                //         let ele = pair?.1;
-                //
-                //         debug_assert!(ele.is_string());
-                //
-                //         let ele = ele.as_string().unwrap();
-                //         chunk_table.push(Bytes::copy_from_slice(ele.as_bytes()));
-                //     }
-                //
-                //     return Ok(RESP3::ChunkedString(chunk_table));
-                // }
+                let push = table.raw_get("push")?;
+
+                if let LuaValue::Table(push) = push {
+                    let mut push_table = Vec::with_capacity(push.raw_len());
+                    for pair in push.pairs::<usize, LuaValue>() {
+                        let ele = pair?.1;
+                        push_table.push(Resp3::from_lua(ele, _lua)?);
+                    }
+
+                    return Ok(Resp3::Push {
+                        inner: push_table,
+                        attributes: None,
+                    });
+                }
+
+                let chunk = table.raw_get("chunk")?;
+
+                if let LuaValue::Table(chunk) = chunk {
+                    let mut chunk_table = Vec::with_capacity(chunk.raw_len());
+                    for pair in chunk.pairs::<usize, mlua::String>() {
+                        let ele = pair?.1;
+                        chunk_table.push(Bytes::copy_from_slice(ele.as_bytes()));
+                    }
+
+                    return Ok(Resp3::ChunkedString(chunk_table));
+                }
 
                 let mut array = Vec::with_capacity(table.raw_len());
                 for pair in table.pairs::<usize, Value>() {
@@ -2267,6 +2612,125 @@ impl FromLua<'_> for Resp3 {
     }
 }
 
+/// 按照inline command的规则拆分一行文本：参数以空白符分隔，支持单引号与双引号，双引号中
+/// 支持`\n`, `\r`, `\t`, `\b`, `\a`以及`\xhh`等转义字符
+pub fn split_inline_args(input: &[u8]) -> FrameResult<Vec<Bytes>> {
+    let unbalanced = || FrameError::InvalidFormat {
+        msg: "unbalanced quotes in request".to_string(),
+    };
+
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < input.len() && input[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == input.len() {
+            break;
+        }
+
+        let mut arg = BytesMut::new();
+        match input[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                loop {
+                    match (input.get(i), input.get(i + 1)) {
+                        (None, _) => return Err(unbalanced()),
+                        (Some(&c), _) if c == quote => {
+                            i += 1;
+                            break;
+                        }
+                        (Some(b'\\'), Some(&next)) if quote == b'\'' => {
+                            if next == b'\'' {
+                                arg.put_u8(b'\'');
+                                i += 2;
+                            } else {
+                                arg.put_u8(b'\\');
+                                i += 1;
+                            }
+                        }
+                        (Some(b'\\'), Some(b'x'))
+                            if input.get(i + 2).is_some_and(u8::is_ascii_hexdigit)
+                                && input.get(i + 3).is_some_and(u8::is_ascii_hexdigit) =>
+                        {
+                            let hex = std::str::from_utf8(&input[i + 2..i + 4]).unwrap();
+                            arg.put_u8(u8::from_str_radix(hex, 16).unwrap());
+                            i += 4;
+                        }
+                        (Some(b'\\'), Some(&next)) => {
+                            arg.put_u8(match next {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                c => c,
+                            });
+                            i += 2;
+                        }
+                        (Some(&c), _) => {
+                            arg.put_u8(c);
+                            i += 1;
+                        }
+                    }
+                }
+
+                // 闭合的引号后必须是空白符
+                if i < input.len() && !input[i].is_ascii_whitespace() {
+                    return Err(unbalanced());
+                }
+            }
+            _ => {
+                while i < input.len() && !input[i].is_ascii_whitespace() {
+                    arg.put_u8(input[i]);
+                    i += 1;
+                }
+            }
+        }
+
+        args.push(arg.freeze());
+    }
+
+    Ok(args)
+}
+
+/// [`split_inline_args`]的逆操作，必要时为参数加上双引号并转义，使其拆分后保持不变
+pub fn quote_inline_arg(arg: &[u8]) -> Cow<'_, [u8]> {
+    let plain = !arg.is_empty()
+        && arg
+            .iter()
+            .all(|&c| c.is_ascii_graphic() && c != b'"' && c != b'\'' && c != b'\\');
+    if plain {
+        return Cow::Borrowed(arg);
+    }
+
+    let mut quoted = Vec::with_capacity(arg.len() + 2);
+    quoted.push(b'"');
+    for &c in arg {
+        match c {
+            b'"' | b'\\' => quoted.extend_from_slice(&[b'\\', c]),
+            b'\n' => quoted.extend_from_slice(b"\\n"),
+            b'\r' => quoted.extend_from_slice(b"\\r"),
+            b'\t' => quoted.extend_from_slice(b"\\t"),
+            b' ' => quoted.push(c),
+            c if c.is_ascii_graphic() => quoted.push(c),
+            c => quoted.extend_from_slice(format!("\\x{c:02x}").as_bytes()),
+        }
+    }
+    quoted.push(b'"');
+
+    Cow::Owned(quoted)
+}
+
+// 没有小数部分的double按整数格式编码。inf、NaN、超出i64范围的值以及-0.0无法用整数表示
+fn double_as_int(f: f64) -> Option<i64> {
+    if f.fract() == 0.0 && f.abs() < i64::MAX as f64 && !(f == 0.0 && f.is_sign_negative()) {
+        Some(f as i64)
+    } else {
+        None
+    }
+}
+
 fn encode_attributes<B, S>(buf: &mut impl BufMut, attr: &Attributes<B, S>)
 where
     B: AsRef<[u8]> + PartialEq,
@@ -2297,6 +2761,111 @@ mod frame_tests {
         assert_eq!(decoder.buf, src_clone);
     }
 
+    #[test]
+    fn decode_inline_test() {
+        let mut decoder = RESP3Decoder::default();
+
+        let mut src = BytesMut::from("PING\r\n\r\n  SET k \"hello \\\"world\\\"\\x21\" 'v'\n");
+        assert_eq!(
+            decoder.decode(&mut src).unwrap().unwrap(),
+            Resp3::new_array(vec![Resp3::new_blob_string("PING".into())])
+        );
+        // 空行被忽略
+        assert_eq!(
+            decoder.decode(&mut src).unwrap().unwrap(),
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("SET".into()),
+                Resp3::new_blob_string("k".into()),
+                Resp3::new_blob_string("hello \"world\"!".into()),
+                Resp3::new_blob_string("v".into()),
+            ])
+        );
+
+        // 不完整的inline command
+        let mut src = BytesMut::from("GET k");
+        assert!(decoder.decode(&mut src).unwrap().is_none());
+        let mut src = BytesMut::from("\r\n");
+        assert_eq!(
+            decoder.decode(&mut src).unwrap().unwrap(),
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("GET".into()),
+                Resp3::new_blob_string("k".into()),
+            ])
+        );
+
+        let mut decoder = RESP3Decoder::default();
+        let mut src = BytesMut::from("SET k \"v\r\n");
+        assert!(decoder.decode(&mut src).is_err());
+
+        let mut decoder = RESP3Decoder::default();
+        let mut src = BytesMut::from("SET k ");
+        src.extend_from_slice(&[b'v'; INLINE_MAX_SIZE]);
+        assert!(decoder.decode(&mut src).is_err());
+    }
+
+    #[tokio::test]
+    async fn decode_blank_lines_test() {
+        let ping = Resp3::new_array(vec![Resp3::new_blob_string("PING".into())]);
+
+        // 大量的空行不会导致栈溢出，之后的inline command和RESP命令都能被解析
+        let mut input = b"\r\n".repeat(1_000_000);
+        input.extend_from_slice(b"PING\r\n");
+        input.extend_from_slice(&b"\n".repeat(1_000_000));
+        input.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+
+        let mut decoder = RESP3Decoder::default();
+        let mut src = BytesMut::from(input.as_slice());
+        assert_eq!(decoder.decode(&mut src).unwrap().unwrap(), ping);
+        assert_eq!(decoder.decode(&mut src).unwrap().unwrap(), ping);
+
+        let mut io_read = input.as_slice();
+        let mut src = BytesMut::new();
+        for _ in 0..2 {
+            let frame = Resp3::decode_async(&mut io_read, &mut src)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(frame, ping);
+        }
+    }
+
+    #[tokio::test]
+    async fn inline_command_test() {
+        use crate::server::Handler;
+
+        let (mut handler, _) = Handler::new_fake();
+
+        let mut io_read: &[u8] = b"PING\r\nSET k v\r\nGET k\r\n";
+        let mut src = BytesMut::new();
+
+        let ping = Resp3::decode_async(&mut io_read, &mut src)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            handler.dispatch(ping).await.unwrap().unwrap(),
+            Resp3::new_simple_string("PONG".into())
+        );
+
+        let set = Resp3::decode_async(&mut io_read, &mut src)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            handler.dispatch(set).await.unwrap().unwrap(),
+            Resp3::new_simple_string("OK".into())
+        );
+
+        let get = Resp3::decode_async(&mut io_read, &mut src)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            handler.dispatch(get).await.unwrap().unwrap(),
+            Resp3::new_blob_string("v".into())
+        );
+    }
+
     #[test]
     fn encode_decode_test() {
         let cases = vec![
@@ -2474,4 +3043,227 @@ mod frame_tests {
             );
         }
     }
+
+    // 覆盖各种编码的帧(包括RESP2的null以及inline command)连续排列成的字节流，以及期望
+    // 解码得到的帧
+    fn pipelined_frames() -> (BytesMut, Vec<Resp3>) {
+        let mut frames = vec![
+            Resp3::new_simple_string("OK".into()),
+            Resp3::new_simple_error("ERR unknown".into()),
+            Resp3::new_integer(-1234567890),
+            Resp3::new_blob_string("blob with \r\n inside".into()),
+            Resp3::new_blob_string("".into()),
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("SET".into()),
+                Resp3::new_array(vec![Resp3::new_integer(1), Resp3::Null]),
+                Resp3::new_blob_string("x".repeat(1234).into()),
+            ]),
+            Resp3::Null,
+            Resp3::new_boolean(false),
+            Resp3::new_double(-3.15),
+            Resp3::BigNumber {
+                inner: BigInt::from(1234567890123456789_i64),
+                attributes: None,
+            },
+            Resp3::BlobError {
+                inner: Bytes::from("SYNTAX invalid"),
+                attributes: None,
+            },
+            Resp3::VerbatimString {
+                format: *b"txt",
+                data: Bytes::from("Some string"),
+                attributes: None,
+            },
+            Resp3::new_map(AHashMap::from_iter([(
+                Resp3::new_simple_string("key".into()),
+                Resp3::new_integer(10),
+            )])),
+            Resp3::new_set(AHashSet::from_iter([Resp3::new_simple_string(
+                "member".into(),
+            )])),
+            Resp3::new_push(vec![
+                Resp3::new_blob_string("message".into()),
+                Resp3::new_blob_string("channel".into()),
+            ]),
+            Resp3::ChunkedString(vec![Bytes::from("chunk1"), Bytes::from("chunk22")]),
+            Resp3::Hello {
+                version: 3,
+                auth: Some(("user".into(), "password".into())),
+            },
+        ];
+
+        let mut buf = BytesMut::new();
+        for frame in &frames {
+            frame.encode_buf(&mut buf);
+        }
+
+        // RESP2的null bulk string与null array，以及inline command
+        buf.extend_from_slice(b"$-1\r\n*-1\r\nSET k \"v 1\"\r\n");
+        frames.extend([
+            Resp3::Null,
+            Resp3::Null,
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("SET".into()),
+                Resp3::new_blob_string("k".into()),
+                Resp3::new_blob_string("v 1".into()),
+            ]),
+        ]);
+
+        (buf, frames)
+    }
+
+    #[test]
+    fn decode_partial_frames_test() {
+        let (input, expected) = pipelined_frames();
+
+        let decode_all = |decoder: &mut RESP3Decoder, chunk: &[u8], frames: &mut Vec<Resp3>| {
+            let mut src = BytesMut::from(chunk);
+            while let Some(frame) = decoder.decode(&mut src).unwrap() {
+                frames.push(frame);
+            }
+        };
+
+        // 每次只到达一个字节，长度、CRLF等都可能被分割
+        let mut decoder = RESP3Decoder::default();
+        let mut frames = Vec::new();
+        for i in 0..input.len() {
+            decode_all(&mut decoder, &input[i..i + 1], &mut frames);
+        }
+        assert_eq!(frames, expected);
+        assert!(decoder.buf.is_empty());
+
+        // 在任意位置分割为两次读取
+        for i in 0..=input.len() {
+            let mut decoder = RESP3Decoder::default();
+            let mut frames = Vec::new();
+            decode_all(&mut decoder, &input[..i], &mut frames);
+            decode_all(&mut decoder, &input[i..], &mut frames);
+            assert_eq!(frames, expected, "split at {i}");
+        }
+    }
+
+    #[tokio::test]
+    async fn decode_async_partial_frames_test() {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll},
+        };
+        use tokio::io::ReadBuf;
+
+        // 每次读取只返回一个字节
+        struct OneByteReader(BytesMut);
+
+        impl AsyncRead for OneByteReader {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                if !self.0.is_empty() {
+                    buf.put_slice(&[self.0.get_u8()]);
+                }
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let (input, expected) = pipelined_frames();
+        let mut io_read = OneByteReader(input);
+        let mut src = BytesMut::new();
+
+        let mut frames = Vec::new();
+        while let Some(frame) = Resp3::decode_async(&mut io_read, &mut src).await.unwrap() {
+            frames.push(frame);
+        }
+        assert_eq!(frames, expected);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn encoded_len_test() {
+        let mut attrs = AHashMap::new();
+        attrs.insert(
+            Resp3::new_simple_string("ttl".into()),
+            Resp3::new_integer(-3600),
+        );
+
+        let frames: Vec<Resp3> = vec![
+            Resp3::new_simple_string("OK".into()),
+            Resp3::new_simple_error("ERR bad".into()),
+            Resp3::new_integer(i64::MIN),
+            Resp3::new_blob_string("hello".into()),
+            Resp3::Null,
+            Resp3::new_boolean(true),
+            Resp3::new_double(1.5),
+            Resp3::new_double(-3.0),
+            Resp3::new_double(f64::INFINITY),
+            Resp3::new_big_number("-123456789012345678901234567890".parse().unwrap()),
+            Resp3::new_blob_error("ERR blob".into()),
+            Resp3::new_verbatim_string(*b"txt", "Some string".into()),
+            Resp3::ChunkedString(vec!["hel".into(), "lo".into()]),
+            Resp3::Hello {
+                version: 3,
+                auth: Some(("user".into(), "pass".into())),
+            },
+            Resp3::Array {
+                inner: vec![Resp3::new_integer(1), Resp3::new_blob_string("a".into())],
+                attributes: Some(attrs.clone()),
+            },
+            Resp3::new_map(AHashMap::from_iter([(
+                Resp3::new_simple_string("k".into()),
+                Resp3::new_set(AHashSet::from_iter([Resp3::new_integer(7)])),
+            )])),
+            Resp3::new_push(vec![Resp3::new_blob_string("message".into())]),
+        ];
+
+        for frame in frames {
+            assert_eq!(frame.encoded_len(), frame.encode().len(), "{frame:?}");
+        }
+    }
+
+    #[test]
+    fn double_encode_test() {
+        let resp2 = |f: f64| Resp3::<Bytes, ByteString>::new_double(f).encode_resp2();
+        let resp3 = |f: f64| Resp3::<Bytes, ByteString>::new_double(f).encode();
+
+        assert_eq!(resp2(3.0), "$1\r\n3\r\n");
+        assert_eq!(resp2(1.5), "$3\r\n1.5\r\n");
+        assert_eq!(resp2(f64::INFINITY), "$3\r\ninf\r\n");
+        assert_eq!(resp2(-0.0), "$4\r\n-0.0\r\n");
+        assert_eq!(resp2(1e20), "$4\r\n1e20\r\n");
+        assert_eq!(resp3(-4.0), ",-4\r\n");
+        assert_eq!(resp3(1e20), ",1e20\r\n");
+    }
+
+    #[test]
+    fn lua_push_chunk_test() {
+        let lua = Lua::new();
+
+        let push = Resp3::<Bytes, ByteString>::new_push(vec![
+            Resp3::new_blob_string("message".into()),
+            Resp3::new_blob_string("channel".into()),
+            Resp3::new_integer(1),
+        ]);
+        let value = push.clone().into_lua(&lua).unwrap();
+        assert_eq!(Resp3::from_lua(value, &lua).unwrap(), push);
+
+        // 非utf-8的字节也能正确转换
+        let chunked = Resp3::<Bytes, ByteString>::new_chunked_string(vec![
+            Bytes::from_static(b"hello"),
+            Bytes::from_static(b"\xff\xfe"),
+        ]);
+        let value = chunked.clone().into_lua(&lua).unwrap();
+        assert_eq!(Resp3::from_lua(value, &lua).unwrap(), chunked);
+
+        let res: Resp3 = lua
+            .load("return {push={'message', 'channel', 1}}")
+            .eval()
+            .unwrap();
+        assert_eq!(res, push);
+
+        let res: Resp3 = lua
+            .load("return {chunk={'hello', '\\xff\\xfe'}}")
+            .eval()
+            .unwrap();
+        assert_eq!(res, chunked);
+    }
 }
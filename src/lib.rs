@@ -1,5 +1,3 @@
-#![feature(associated_type_defaults)]
-#![feature(never_type)]
 #![warn(clippy::print_stdout)]
 
 use mimalloc::MiMalloc;
@@ -25,3 +23,4 @@ pub type Key = bytes::Bytes;
 pub type Int = i64;
 pub type Id = u128;
 pub type CmdFlag = u128;
+pub type CatFlag = u16;
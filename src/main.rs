@@ -18,10 +18,7 @@ async fn main() {
 
     rutin::init(conf.server.log_level.as_str());
 
-    let listener =
-        tokio::net::TcpListener::bind(format!("{}:{}", conf.server.addr, conf.server.port))
-            .await
-            .unwrap();
+    let listener = rutin::server::Listener::bind(&conf.server).await.unwrap();
 
     rutin::run(listener, conf).await;
 }
@@ -2,7 +2,11 @@ use crate::{
     cmd::dispatch,
     conf::Conf,
     frame::RESP3Decoder,
-    persist::rdb::{rdb_load, rdb_save},
+    persist::{
+        atomic_replace,
+        rdb::{rdb_load, rdb_save},
+        temp_path,
+    },
     server::Handler,
     shared::Shared,
 };
@@ -44,8 +48,10 @@ impl Aof {
 
     async fn rewrite(&mut self) -> anyhow::Result<()> {
         let path = self.conf.aof.as_ref().unwrap().file_path.clone();
-        let temp_path = format!("{}.tmp", path);
+        let temp_path = temp_path(&path);
         let bak_path = format!("{}.bak", path);
+        // 上次重写时崩溃可能遗留不完整的临时文件，不能在其后追加
+        tokio::fs::remove_file(&temp_path).await.ok();
         // 创建临时文件，先使用RDB格式保存数据
         let mut temp_file = tokio::fs::OpenOptions::new()
             .read(true)
@@ -56,14 +62,18 @@ impl Aof {
 
         // 将数据保存到临时文件
         rdb_save(&mut temp_file, self.shared.db(), true).await?;
+        temp_file.sync_all().await?;
 
         // 将数据保存到临时文件后，将原来的AOF文件关闭
         self.file = temp_file;
 
-        // 将旧AOF文件备份
-        tokio::fs::rename(&path, &bak_path).await?;
-        // 将新AOF文件重命名为AOF文件
-        tokio::fs::rename(&temp_path, &path).await?;
+        // 通过硬链接备份旧AOF文件，保证替换的过程中AOF文件始终存在
+        tokio::fs::remove_file(&bak_path).await.ok();
+        if let Err(e) = tokio::fs::hard_link(&path, &bak_path).await {
+            tracing::warn!(cause = ?e, "failed to backup AOF file");
+        }
+        // 将新AOF文件原子地替换为AOF文件
+        atomic_replace(&temp_path, &path).await?;
 
         Ok(())
     }
@@ -0,0 +1,113 @@
+//! 从文件批量导入键值对，用于预热缓存。文件以流的方式读取，不会一次性载入内存。
+//! 支持两种格式：
+//! - TSV：每行一个键值对，键与值之间以`\t`分隔，空行会被忽略
+//! - Binary：由`[key_len: u32 BE][key][value_len: u32 BE][value]`重复组成
+
+use crate::{
+    shared::db::{Db, ObjectInner},
+    Key,
+};
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use std::{path::Path, time::Duration};
+use tokio::{
+    fs::File,
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader},
+    time::Instant,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkFormat {
+    Tsv,
+    Binary,
+}
+
+impl TryFrom<&[u8]> for BulkFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        if value.eq_ignore_ascii_case(b"TSV") {
+            Ok(BulkFormat::Tsv)
+        } else if value.eq_ignore_ascii_case(b"BINARY") {
+            Ok(BulkFormat::Binary)
+        } else {
+            bail!("unknown format, expected TSV or BINARY")
+        }
+    }
+}
+
+/// 将文件中的键值对作为字符串对象插入Db，已存在的键会被覆盖。ttl为None时键不会过期。
+/// 返回导入的键值对数量
+pub async fn bulk_load(
+    db: &Db,
+    file_path: impl AsRef<Path>,
+    format: BulkFormat,
+    ttl: Option<Duration>,
+) -> Result<usize> {
+    let mut reader = BufReader::new(File::open(file_path).await?);
+    let expire = ttl.map(|ttl| Instant::now() + ttl);
+
+    let mut count = 0;
+    match format {
+        BulkFormat::Tsv => {
+            let mut line = Vec::new();
+            let mut line_no = 0;
+            loop {
+                line.clear();
+                if reader.read_until(b'\n', &mut line).await? == 0 {
+                    break;
+                }
+                line_no += 1;
+
+                let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                if line.is_empty() {
+                    continue;
+                }
+
+                let Some(i) = line.iter().position(|&b| b == b'\t') else {
+                    bail!("invalid TSV line {line_no}: missing tab");
+                };
+                let key = Key::copy_from_slice(&line[..i]);
+                let value = Bytes::copy_from_slice(&line[i + 1..]);
+
+                db.insert_object(key, ObjectInner::new_str(value, expire))
+                    .await;
+                count += 1;
+            }
+        }
+        BulkFormat::Binary => loop {
+            // 只允许在记录边界处结束
+            if reader.fill_buf().await?.is_empty() {
+                break;
+            }
+
+            let key_len = reader.read_u32().await?;
+            let key = read_field(&mut reader, key_len).await?;
+
+            let value_len = reader.read_u32().await?;
+            let value = read_field(&mut reader, value_len).await?;
+
+            db.insert_object(
+                Key::from(key),
+                ObjectInner::new_str(Bytes::from(value), expire),
+            )
+            .await;
+            count += 1;
+        },
+    }
+
+    Ok(count)
+}
+
+// 读取长度为len的字段。长度来自文件，不能据此预先分配内存，否则损坏的文件会导致分配
+// 过大的内存，因此只读取文件中实际存在的数据
+async fn read_field(reader: &mut (impl AsyncBufRead + Unpin), len: u32) -> Result<Vec<u8>> {
+    let mut field = Vec::new();
+    reader.take(len as u64).read_to_end(&mut field).await?;
+    if field.len() != len as usize {
+        bail!("unexpected end of file: truncated record");
+    }
+
+    Ok(field)
+}
@@ -1,6 +1,31 @@
 pub mod aof;
+pub mod bulk;
 pub mod rdb;
 
+use std::path::Path;
+
+/// 持久化文件的临时文件路径。数据先写入临时文件，写完并fsync后再替换目标文件，
+/// 因此写入过程中崩溃不会损坏原有的数据文件
+pub fn temp_path(path: &str) -> String {
+    format!("{path}.tmp")
+}
+
+/// 将已经fsync的临时文件原子地重命名为目标文件，并fsync目标文件所在的目录，
+/// 确保崩溃后重命名依然有效
+pub async fn atomic_replace(
+    temp_path: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let path = path.as_ref();
+    tokio::fs::rename(temp_path, path).await?;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    tokio::fs::File::open(dir).await?.sync_all().await
+}
+
 // #[allow(async_fn_in_trait)]
 // pub trait Persist {
 //     async fn save(&mut self) -> anyhow::Result<()>;
@@ -1,7 +1,11 @@
 #![allow(dead_code)]
-use crate::shared::{
-    db::{Db, Hash, List, ObjValue, Set, Str, ZSet},
-    Shared,
+use crate::{
+    persist::{atomic_replace, temp_path},
+    shared::{
+        db::{Db, Hash, List, ObjValue, ObjectInner, Set, Str, Stream, StreamId, ZSet},
+        Shared,
+    },
+    Key,
 };
 use ahash::{AHashMap, AHashSet};
 use anyhow::bail;
@@ -15,10 +19,12 @@ use tokio::{
 };
 use tracing::trace;
 
+pub use rdb_load::decode_dump_payload;
 pub(super) use rdb_load::rdb_load;
 pub(super) use rdb_save::rdb_save;
 pub use rdb_save::{
-    encode_hash_value, encode_list_value, encode_set_value, encode_str_value, encode_zset_value,
+    encode_dump_payload, encode_hash_value, encode_list_value, encode_object_value,
+    encode_set_value, encode_str_value, encode_stream_value, encode_zset_value,
 };
 
 const RDB_VERSION: u32 = 7;
@@ -79,19 +85,35 @@ impl Rdb {
     }
 }
 
+// 临时文件的路径是固定的，同一时间只允许一个保存操作，避免并发的SAVE和BGSAVE写入同一个
+// 临时文件
+static SAVE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
 impl Rdb {
     pub async fn save(&mut self) -> anyhow::Result<()> {
-        let mut file = tokio::fs::File::create(&self.path).await?;
+        let _guard = SAVE_LOCK.lock().await;
+
+        // 先写入临时文件，完整写入并fsync后再替换原文件
+        let temp_path = temp_path(&self.path);
+        let mut file = tokio::fs::File::create(&temp_path).await?;
 
-        if let Ok(fut) = self.shutdown.wrap_delay_shutdown(rdb_save::rdb_save(
+        let Ok(fut) = self.shutdown.wrap_delay_shutdown(rdb_save::rdb_save(
             &mut file,
             &self.db,
             self.enable_checksum,
-        )) {
-            fut.await?;
-        } else {
+        )) else {
+            // 服务器已经关闭，放弃保存
+            tokio::fs::remove_file(&temp_path).await.ok();
             return Ok(());
+        };
+
+        if let Err(e) = fut.await {
+            tokio::fs::remove_file(&temp_path).await.ok();
+            return Err(e);
         }
+        file.sync_all().await?;
+
+        atomic_replace(&temp_path, &self.path).await?;
 
         Ok(())
     }
@@ -122,65 +144,96 @@ mod rdb_save {
         buf.extend_from_slice(b"REDIS");
         buf.put_u32(RDB_VERSION);
         buf.put_u8(RDB_OPCODE_SELECTDB);
-        buf.put_u32(0);
+        // 与加载时一致，数据库编号使用长度编码
+        encode_length(&mut buf, 0, None);
+
+        let crc = crc::Crc::<u64>::new(&crc::CRC_64_REDIS);
+        let mut digest = crc.digest();
 
+        // 快照没有fork，而是逐个shard拷贝对象，释放shard的锁之后再进行编码和写入，
+        // 因此保存期间服务器可以继续处理写命令。快照只保证单个键的一致性，不保证全局
+        // 的原子性：多键写命令(如MSET)可能只有部分键的修改被快照观察到
         let max_buf_size = 2 << 28;
-        for entry in db.entries().iter() {
-            let (key, obj) = (entry.key().clone(), entry.value().clone());
-            let obj_inner = if let Some(inner) = obj.inner() {
-                if inner.is_expired() {
-                    continue;
+        for shard in db.entries().shards() {
+            let objects: Vec<(Key, ObjectInner)> = {
+                let shard = shard.read();
+                // 不使用DashMap::iter()：它在遍历完整个shard之前一直持有读锁，而这里需要在
+                // 编码和写入文件(await)之前释放锁
+                //
+                // SAFETY: RawTable::iter()和Bucket::as_ref()要求迭代期间table不被修改，且
+                // bucket指向已初始化的元素。整个迭代过程都持有shard的读锁，而插入、删除和
+                // 扩容都需要获取写锁，因此table不会被修改，迭代器返回的bucket始终有效。
+                // as_ref()返回的引用不会离开读锁的作用域：键和对象在释放锁之前已经被clone。
+                // DashMap自身的iter()也是以同样的方式遍历shard的
+                unsafe {
+                    shard
+                        .iter()
+                        .map(|bucket| bucket.as_ref())
+                        .filter_map(|(key, obj)| {
+                            obj.get()
+                                .inner()
+                                .filter(|inner| !inner.is_expired())
+                                .map(|inner| (key.clone(), inner.clone()))
+                        })
+                        .collect()
                 }
-                inner
-            } else {
-                continue;
             };
 
-            if let Some(ex) = obj_inner.expire() {
-                let ex = ex.duration_since(epoch());
-                if ex == Duration::from_secs(0) {
-                    continue;
-                }
-
-                encode_expire(&mut buf, ex);
-            }
+            for (key, obj_inner) in objects {
+                if let Some(ex) = obj_inner.expire() {
+                    let ex = ex.duration_since(epoch());
+                    if ex == Duration::from_secs(0) {
+                        continue;
+                    }
 
-            match obj_inner.value().clone() {
-                ObjValue::Str(value) => {
-                    buf.put_u8(RDB_TYPE_STRING);
-                    encode_key(&mut buf, key);
-                    encode_str_value(&mut buf, value);
-                }
-                ObjValue::List(value) => {
-                    buf.put_u8(RDB_TYPE_LIST);
-                    encode_key(&mut buf, key);
-                    encode_list_value(&mut buf, value);
-                }
-                ObjValue::Set(value) => {
-                    buf.put_u8(RDB_TYPE_SET);
-                    encode_key(&mut buf, key);
-                    encode_set_value(&mut buf, value);
-                }
-                ObjValue::Hash(value) => {
-                    buf.put_u8(RDB_TYPE_HASH);
-                    encode_key(&mut buf, key);
-                    encode_hash_value(&mut buf, value);
+                    encode_expire(&mut buf, ex);
                 }
-                ObjValue::ZSet(value) => {
-                    buf.put_u8(RDB_TYPE_ZSET);
-                    encode_key(&mut buf, key);
-                    encode_zset_value(&mut buf, value)
+
+                match obj_inner.value().clone() {
+                    ObjValue::Str(value) => {
+                        buf.put_u8(RDB_TYPE_STRING);
+                        encode_key(&mut buf, key);
+                        encode_str_value(&mut buf, value);
+                    }
+                    ObjValue::List(value) => {
+                        buf.put_u8(RDB_TYPE_LIST);
+                        encode_key(&mut buf, key);
+                        encode_list_value(&mut buf, value);
+                    }
+                    ObjValue::Set(value) => {
+                        buf.put_u8(RDB_TYPE_SET);
+                        encode_key(&mut buf, key);
+                        encode_set_value(&mut buf, value);
+                    }
+                    ObjValue::Hash(value) => {
+                        buf.put_u8(RDB_TYPE_HASH);
+                        encode_key(&mut buf, key);
+                        encode_hash_value(&mut buf, value);
+                    }
+                    ObjValue::ZSet(value) => {
+                        buf.put_u8(RDB_TYPE_ZSET);
+                        encode_key(&mut buf, key);
+                        encode_zset_value(&mut buf, value)
+                    }
+                    ObjValue::Stream(value) => {
+                        buf.put_u8(RDB_TYPE_STREAM_LISTPACKS);
+                        encode_key(&mut buf, key);
+                        encode_stream_value(&mut buf, value)
+                    }
                 }
-            }
 
-            if buf.len() >= max_buf_size {
-                file.write_all_buf(&mut buf.split()).await?;
+                if buf.len() >= max_buf_size {
+                    let chunk = buf.split();
+                    digest.update(&chunk);
+                    file.write_all(&chunk).await?;
+                }
             }
         }
 
         buf.put_u8(RDB_OPCODE_EOF);
         let checksum = if enable_checksum {
-            crc::Crc::<u64>::new(&crc::CRC_64_REDIS).checksum(&buf)
+            digest.update(&buf);
+            digest.finalize()
         } else {
             0
         };
@@ -195,6 +248,37 @@ mod rdb_save {
         buf.put_u64_le(expire.as_millis() as u64);
     }
 
+    /// 编码DUMP命令的返回值：对象类型 + 对象值 + RDB版本(2字节) + CRC64校验和(8字节)
+    pub fn encode_dump_payload(buf: &mut BytesMut, value: ObjValue) {
+        let typ = match value {
+            ObjValue::Str(_) => RDB_TYPE_STRING,
+            ObjValue::List(_) => RDB_TYPE_LIST,
+            ObjValue::Set(_) => RDB_TYPE_SET,
+            ObjValue::Hash(_) => RDB_TYPE_HASH,
+            ObjValue::ZSet(_) => RDB_TYPE_ZSET,
+            ObjValue::Stream(_) => RDB_TYPE_STREAM_LISTPACKS,
+        };
+        buf.put_u8(typ);
+        encode_object_value(buf, value);
+
+        buf.put_u16_le(RDB_VERSION as u16);
+        let crc = crc::Crc::<u64>::new(&crc::CRC_64_REDIS);
+        let checksum = crc.checksum(buf);
+        buf.put_u64_le(checksum);
+    }
+
+    /// 按照RDB格式编码对象值(不包含类型)
+    pub fn encode_object_value(buf: &mut BytesMut, value: ObjValue) {
+        match value {
+            ObjValue::Str(value) => encode_str_value(buf, value),
+            ObjValue::List(value) => encode_list_value(buf, value),
+            ObjValue::Set(value) => encode_set_value(buf, value),
+            ObjValue::Hash(value) => encode_hash_value(buf, value),
+            ObjValue::ZSet(value) => encode_zset_value(buf, value),
+            ObjValue::Stream(value) => encode_stream_value(buf, value),
+        }
+    }
+
     pub fn encode_zset_value(buf: &mut BytesMut, value: ZSet) {
         match value {
             ZSet::SkipList(zset) => {
@@ -211,6 +295,50 @@ mod rdb_save {
         }
     }
 
+    /// 流并不使用Redis的listpack编码，而是依次编码条目数量、每个条目的ID和字段、
+    /// last_id，最后编码消费者组(last_delivered、PEL以及消费者)
+    pub fn encode_stream_value(buf: &mut BytesMut, value: Stream) {
+        encode_length(buf, value.len() as u32, None);
+        for (id, fields) in value.range(..) {
+            encode_stream_id(buf, *id);
+            encode_length(buf, fields.len() as u32, None);
+            for (f, v) in fields {
+                encode_raw(buf, f.clone());
+                encode_raw(buf, v.clone());
+            }
+        }
+        encode_stream_id(buf, value.last_id());
+
+        encode_length(buf, value.groups().count() as u32, None);
+        for (name, group) in value.groups() {
+            encode_raw(buf, name.clone());
+            encode_stream_id(buf, group.last_delivered);
+
+            encode_length(buf, group.pending().len() as u32, None);
+            for (id, entry) in group.pending() {
+                encode_stream_id(buf, *id);
+                encode_raw(buf, entry.consumer.clone());
+                encode_u64(buf, entry.delivery_time);
+                encode_u64(buf, entry.delivery_count);
+            }
+
+            encode_length(buf, group.consumers().len() as u32, None);
+            for (name, consumer) in group.consumers() {
+                encode_raw(buf, name.clone());
+                encode_u64(buf, consumer.seen_time);
+            }
+        }
+    }
+
+    fn encode_stream_id(buf: &mut BytesMut, id: StreamId) {
+        encode_u64(buf, id.ms);
+        encode_u64(buf, id.seq);
+    }
+
+    fn encode_u64(buf: &mut BytesMut, n: u64) {
+        encode_raw(buf, itoa::Buffer::new().format(n).to_owned().into());
+    }
+
     pub fn encode_hash_value(buf: &mut BytesMut, value: Hash) {
         match value {
             Hash::HashMap(hash) => {
@@ -220,7 +348,13 @@ mod rdb_save {
                     encode_raw(buf, v);
                 }
             }
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => {
+                encode_length(buf, list.len() as u32, None);
+                for (k, v) in list {
+                    encode_raw(buf, k);
+                    encode_raw(buf, v);
+                }
+            }
         }
     }
 
@@ -232,7 +366,12 @@ mod rdb_save {
                     encode_raw(buf, elem);
                 }
             }
-            Set::IntSet => unimplemented!(),
+            Set::IntSet(set) => {
+                encode_length(buf, set.len() as u32, None);
+                for elem in set {
+                    encode_raw(buf, itoa::Buffer::new().format(elem).to_owned().into());
+                }
+            }
         }
     }
 
@@ -418,6 +557,16 @@ mod rdb_load {
                         .await;
                     expire = None;
                 }
+                RDB_TYPE_STREAM_LISTPACKS => {
+                    let key = decode_key(rdb)?;
+                    let value = decode_stream_value(rdb)?;
+
+                    trace!("Stream: key: {:?}, value: {:?}", key, value);
+
+                    db.insert_object(key, ObjectInner::new_stream(value, expire))
+                        .await;
+                    expire = None;
+                }
                 invalid_ctrl => bail!("invalid RDB control byte: {:?}", invalid_ctrl),
             }
         }
@@ -425,6 +574,46 @@ mod rdb_load {
         Ok(())
     }
 
+    /// 解码由[`encode_dump_payload`](super::encode_dump_payload)编码的对象值，校验和
+    /// 或版本不匹配时返回错误
+    pub fn decode_dump_payload(bytes: &mut BytesMut) -> anyhow::Result<ObjValue> {
+        // 至少包含类型(1字节)、版本(2字节)以及校验和(8字节)
+        if bytes.len() < 11 {
+            bail!("payload too short");
+        }
+
+        let mut footer = bytes.split_off(bytes.len() - 10);
+        let version = footer.get_u16_le();
+        let checksum = footer.get_u64_le();
+
+        let crc = crc::Crc::<u64>::new(&crc::CRC_64_REDIS);
+        let mut digest = crc.digest();
+        digest.update(bytes);
+        digest.update(&version.to_le_bytes());
+        if digest.finalize() != checksum {
+            bail!("checksum mismatch");
+        }
+        if version as u32 > RDB_VERSION {
+            bail!("unsupported RDB version: {version}");
+        }
+
+        let value = match bytes.get_u8() {
+            RDB_TYPE_STRING => ObjValue::Str(decode_str_value(bytes)?),
+            RDB_TYPE_LIST => ObjValue::List(decode_list_kv(bytes)?),
+            RDB_TYPE_SET => ObjValue::Set(decode_set_value(bytes)?),
+            RDB_TYPE_HASH => ObjValue::Hash(decode_hash_value(bytes)?),
+            RDB_TYPE_ZSET => ObjValue::ZSet(decode_zset_value(bytes)?),
+            RDB_TYPE_STREAM_LISTPACKS => ObjValue::Stream(decode_stream_value(bytes)?),
+            typ => bail!("invalid object type: {typ}"),
+        };
+
+        if !bytes.is_empty() {
+            bail!("trailing bytes after object value");
+        }
+
+        Ok(value)
+    }
+
     pub fn decode_zset_value(bytes: &mut BytesMut) -> anyhow::Result<ZSet> {
         if let Length::Len(zset_size) = decode_length(bytes)? {
             let mut zset = OrderedSkipList::new();
@@ -454,6 +643,70 @@ mod rdb_load {
         }
     }
 
+    pub fn decode_stream_value(bytes: &mut BytesMut) -> anyhow::Result<Stream> {
+        let Length::Len(stream_size) = decode_length(bytes)? else {
+            bail!("invalid stream length")
+        };
+
+        let mut stream = Stream::default();
+        for _ in 0..stream_size {
+            let id = decode_stream_id(bytes)?;
+            let Length::Len(fields_size) = decode_length(bytes)? else {
+                bail!("invalid stream entry length")
+            };
+
+            let mut fields = Vec::with_capacity(fields_size);
+            for _ in 0..fields_size {
+                let field = decode_str_value(bytes)?.to_bytes();
+                let value = decode_str_value(bytes)?.to_bytes();
+                fields.push((field, value));
+            }
+            if !stream.insert(id, fields) {
+                bail!("stream entry ids are not increasing");
+            }
+        }
+        stream.set_last_id(decode_stream_id(bytes)?);
+
+        let Length::Len(groups_size) = decode_length(bytes)? else {
+            bail!("invalid stream groups length")
+        };
+        for _ in 0..groups_size {
+            let name = decode_str_value(bytes)?.to_bytes();
+            stream.create_group(name.clone(), decode_stream_id(bytes)?);
+            let group = stream.group_mut(&name).unwrap();
+
+            let Length::Len(pending_size) = decode_length(bytes)? else {
+                bail!("invalid stream pending entries length")
+            };
+            for _ in 0..pending_size {
+                let id = decode_stream_id(bytes)?;
+                let consumer = decode_str_value(bytes)?.to_bytes();
+                let delivery_time = decode_u64(bytes)?;
+                let delivery_count = decode_u64(bytes)?;
+                group.add_pending(id, consumer, delivery_time, delivery_count);
+            }
+
+            // 消费者在PEL之后解码，以覆盖add_pending设置的活动时间
+            let Length::Len(consumers_size) = decode_length(bytes)? else {
+                bail!("invalid stream consumers length")
+            };
+            for _ in 0..consumers_size {
+                let name = decode_str_value(bytes)?.to_bytes();
+                group.consumer_mut(&name, decode_u64(bytes)?);
+            }
+        }
+
+        Ok(stream)
+    }
+
+    fn decode_stream_id(bytes: &mut BytesMut) -> anyhow::Result<StreamId> {
+        Ok(StreamId::new(decode_u64(bytes)?, decode_u64(bytes)?))
+    }
+
+    fn decode_u64(bytes: &mut BytesMut) -> anyhow::Result<u64> {
+        Ok(std::str::from_utf8(&decode_str_value(bytes)?.to_bytes())?.parse()?)
+    }
+
     pub fn decode_hash_value(bytes: &mut BytesMut) -> anyhow::Result<Hash> {
         if let Length::Len(hash_size) = decode_length(bytes)? {
             let mut hash = AHashMap::with_capacity(hash_size);
@@ -934,6 +1187,23 @@ mod rdb_test {
         db.insert_object("zs3".into(), zs3.clone()).await;
         db.insert_object("zs4".into(), zs4.clone()).await;
 
+        // last_id大于最后一个条目的ID时也需要被保存
+        let mut stream = Stream::default();
+        stream.insert(StreamId::new(1, 0), vec![("f1".into(), "v1".into())]);
+        stream.insert(
+            StreamId::new(1, 1),
+            vec![("f1".into(), "v1".into()), ("f2".into(), "v2".into())],
+        );
+        stream.set_last_id(StreamId::new(5, 0));
+        stream.create_group("g1".into(), StreamId::MIN);
+        stream.deliver_new(b"g1", &"c1".into(), Some(1), false, 100);
+        stream
+            .group_mut(b"g1")
+            .unwrap()
+            .consumer_mut(&"c2".into(), 200);
+        let st1 = ObjectInner::new_stream(stream, None);
+        db.insert_object("st1".into(), st1.clone()).await;
+
         let mut rdb = Rdb::new(&shared, "tests/dump/dump_temp.rdb".into(), true);
         rdb.save().await.unwrap();
 
@@ -1085,5 +1355,147 @@ mod rdb_test {
                 .inner_unchecked(),
             &zs4
         );
+
+        let loaded = shared.db().get_object_entry(&"st1".into()).await.unwrap();
+        let loaded = loaded.inner_unchecked();
+        assert_eq!(loaded, &st1);
+        assert_eq!(loaded.on_stream().unwrap().last_id(), StreamId::new(5, 0));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn bgsave_with_concurrent_writes_test() {
+        test_init();
+
+        let path = "tests/dump/bgsave_temp.rdb";
+        let shared = Shared::default();
+        let db = shared.db().clone();
+
+        for i in 0..1000 {
+            db.insert_object(
+                Key::from(format!("key:{i}")),
+                ObjectInner::new_str("old", None),
+            )
+            .await;
+        }
+
+        // 保存快照的同时不断写入
+        let writer = tokio::spawn(async move {
+            for i in 0..2000 {
+                db.insert_object(
+                    Key::from(format!("key:{i}")),
+                    ObjectInner::new_str("new", None),
+                )
+                .await;
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let mut rdb = Rdb::new(&shared, path.into(), true);
+        rdb.save().await.unwrap();
+        writer.await.unwrap();
+
+        let loaded = Shared::default();
+        let mut rdb = Rdb::new(&loaded, path.into(), true);
+        rdb.load().await.unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // 快照前已存在的键都在快照中，值为写入前或写入后的值
+        for i in 0..1000 {
+            let value = loaded
+                .db()
+                .get_object_entry(&Key::from(format!("key:{i}")))
+                .await
+                .unwrap()
+                .on_str()
+                .unwrap()
+                .unwrap()
+                .to_vec();
+            assert!(value == b"old" || value == b"new");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_save_test() {
+        test_init();
+
+        let path = "tests/dump/concurrent_save_temp.rdb";
+        let shared = Shared::default();
+        for i in 0..1000 {
+            shared
+                .db()
+                .insert_object(
+                    Key::from(format!("key:{i}")),
+                    ObjectInner::new_str("value", None),
+                )
+                .await;
+        }
+
+        // 同时进行的两次保存不会互相覆盖临时文件
+        let saves: Vec<_> = (0..2)
+            .map(|_| {
+                let mut rdb = Rdb::new(&shared, path.into(), true);
+                tokio::spawn(async move { rdb.save().await })
+            })
+            .collect();
+        for save in saves {
+            save.await.unwrap().unwrap();
+        }
+
+        let loaded = Shared::default();
+        Rdb::new(&loaded, path.into(), true).load().await.unwrap();
+        assert_eq!(loaded.db().len_nonexpired(), 1000);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn atomic_save_test() {
+        test_init();
+
+        let path = "tests/dump/atomic_save_temp.rdb";
+        let temp_path = temp_path(path);
+
+        let load_value = || async {
+            let loaded = Shared::default();
+            Rdb::new(&loaded, path.into(), true).load().await.unwrap();
+            let value = loaded
+                .db()
+                .get_object_entry(&"key".into())
+                .await
+                .unwrap()
+                .on_str()
+                .unwrap()
+                .unwrap()
+                .to_vec();
+            value
+        };
+
+        let shared = Shared::default();
+        shared
+            .db()
+            .insert_object("key".into(), ObjectInner::new_str("good", None))
+            .await;
+        let mut rdb = Rdb::new(&shared, path.into(), true);
+        rdb.save().await.unwrap();
+        assert!(!std::path::Path::new(&temp_path).exists());
+
+        // 写入过程中崩溃，遗留不完整的临时文件，原文件不受影响
+        std::fs::write(&temp_path, b"REDIS0009 partial").unwrap();
+        assert_eq!(load_value().await, b"good");
+
+        // 保存失败时，原文件不受影响
+        std::fs::remove_file(&temp_path).unwrap();
+        std::fs::create_dir(&temp_path).unwrap();
+        shared
+            .db()
+            .insert_object("key".into(), ObjectInner::new_str("new", None))
+            .await;
+        assert!(rdb.save().await.is_err());
+        assert_eq!(load_value().await, b"good");
+        std::fs::remove_dir(&temp_path).unwrap();
+
+        // 保存成功后才替换原文件
+        rdb.save().await.unwrap();
+        assert_eq!(load_value().await, b"new");
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -1,12 +1,69 @@
-use crate::frame::Resp3;
-use flume::{Receiver, Sender};
+use crate::{conf::OutputBufferLimit, frame::Resp3};
+use crossbeam::atomic::AtomicCell;
+use flume::{Receiver, SendError, Sender};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::{sync::Notify, time::Instant};
 
-pub type BgTaskSender = Sender<Resp3>;
+#[derive(Debug, Clone)]
+pub struct BgTaskSender {
+    tx: Sender<(Resp3, usize)>,
+    output_buffer: Arc<OutputBuffer>,
+    // 用于通知连接断开(例如CLIENT KILL)
+    kill: Arc<Notify>,
+}
+
+impl BgTaskSender {
+    // 如果发送后输出缓冲区超出限制，则发送失败，并通知连接断开
+    #[allow(clippy::result_large_err)]
+    pub fn send(&self, frame: Resp3) -> Result<(), SendError<Resp3>> {
+        let Some(size) = self.output_buffer.reserve(&frame) else {
+            return Err(SendError(frame));
+        };
+
+        self.tx
+            .send((frame, size))
+            .map_err(|SendError((frame, size))| {
+                self.output_buffer.release(size);
+                SendError(frame)
+            })
+    }
+
+    pub async fn send_async(&self, frame: Resp3) -> Result<(), SendError<Resp3>> {
+        let Some(size) = self.output_buffer.reserve(&frame) else {
+            return Err(SendError(frame));
+        };
+
+        self.tx
+            .send_async((frame, size))
+            .await
+            .map_err(|SendError((frame, size))| {
+                self.output_buffer.release(size);
+                SendError(frame)
+            })
+    }
+
+    pub fn same_channel(&self, other: &BgTaskSender) -> bool {
+        self.tx.same_channel(&other.tx)
+    }
+
+    /// 接收端是否已经被丢弃(连接已经断开)
+    pub fn is_disconnected(&self) -> bool {
+        self.tx.is_disconnected()
+    }
+
+    /// 通知连接断开。连接尚未开始等待时，通知也不会丢失
+    pub fn kill(&self) {
+        self.kill.notify_one();
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct BgTaskChannel {
     tx: BgTaskSender,
-    rx: Receiver<Resp3>,
+    rx: Receiver<(Resp3, usize)>,
 }
 
 impl BgTaskChannel {
@@ -19,13 +76,110 @@ impl BgTaskChannel {
     }
 
     pub async fn recv_from_bg_task(&self) -> Resp3 {
-        self.rx.recv_async().await.unwrap()
+        let (frame, size) = self.rx.recv_async().await.unwrap();
+        self.tx.output_buffer.release(size);
+        frame
+    }
+
+    /// 不等待地取出一个已经就绪的frame，channel为空时返回None
+    pub fn try_recv_from_bg_task(&self) -> Option<Resp3> {
+        let (frame, size) = self.rx.try_recv().ok()?;
+        self.tx.output_buffer.release(size);
+        Some(frame)
+    }
+
+    pub fn output_buffer(&self) -> &Arc<OutputBuffer> {
+        &self.tx.output_buffer
+    }
+
+    /// 通过`BgTaskSender::kill`通知连接断开时，该Notify会被唤醒
+    pub fn kill_signal(&self) -> &Arc<Notify> {
+        &self.tx.kill
+    }
+
+    pub fn set_output_buffer_limit(&self, limit: OutputBufferLimit) {
+        self.tx.output_buffer.limit.store(limit);
     }
 }
 
 impl Default for BgTaskChannel {
     fn default() -> Self {
         let (tx, rx) = flume::bounded(1024);
-        Self { tx, rx }
+        Self {
+            tx: BgTaskSender {
+                tx,
+                output_buffer: Default::default(),
+                kill: Default::default(),
+            },
+            rx,
+        }
+    }
+}
+
+/// 记录尚未写入到客户端的字节数，由`BgTaskChannel`及其所有的`BgTaskSender`共享
+#[derive(Debug, Default)]
+pub struct OutputBuffer {
+    limit: AtomicCell<OutputBufferLimit>,
+    // 已发送到channel但还未被连接取出的字节数
+    pending: AtomicUsize,
+    // 缓冲区大小开始超过soft_limit的时间
+    soft_exceeded_since: AtomicCell<Option<Instant>>,
+    overflow: Notify,
+}
+
+impl OutputBuffer {
+    /// 连接中还有extra字节尚未写出时，输出缓冲区是否超出限制。超出限制时会通知连接断开
+    pub fn check(&self, extra: usize) -> bool {
+        let limit = self.limit.load();
+        if limit.is_unlimited() {
+            return false;
+        }
+
+        let size = self.pending.load(Ordering::Relaxed) + extra;
+
+        let exceeded = if limit.hard_limit != 0 && size > limit.hard_limit {
+            true
+        } else if limit.soft_limit != 0 && size > limit.soft_limit {
+            let now = Instant::now();
+            let since = self.soft_exceeded_since.load().unwrap_or_else(|| {
+                self.soft_exceeded_since.store(Some(now));
+                now
+            });
+            now.duration_since(since).as_secs() >= limit.soft_seconds
+        } else {
+            self.soft_exceeded_since.store(None);
+            false
+        };
+
+        if exceeded {
+            self.overflow.notify_one();
+        }
+        exceeded
+    }
+
+    /// 等待输出缓冲区超出限制
+    pub async fn overflowed(&self) {
+        self.overflow.notified().await
+    }
+
+    // 为frame预留缓冲区，返回frame的大小。如果超出限制则返回None
+    fn reserve(&self, frame: &Resp3) -> Option<usize> {
+        // 不限制时无需计算frame的大小
+        if self.limit.load().is_unlimited() {
+            return Some(0);
+        }
+
+        let size = frame.encoded_len();
+        self.pending.fetch_add(size, Ordering::Relaxed);
+        if self.check(0) {
+            self.release(size);
+            return None;
+        }
+
+        Some(size)
+    }
+
+    fn release(&self, size: usize) {
+        self.pending.fetch_sub(size, Ordering::Relaxed);
     }
 }
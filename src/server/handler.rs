@@ -1,14 +1,16 @@
-use super::{BgTaskChannel, BgTaskSender, ServerError, CLIENT_ID_COUNT, ID};
+use super::{BgTaskChannel, BgTaskSender, ServerError, ID};
 use crate::{
-    cmd::dispatch,
-    conf::{AccessControl, DEFAULT_USER},
+    cmd::{dispatch, CmdError, CmdResult, CmdUnparsed},
+    conf::{AccessControl, ClientClass, DEFAULT_USER},
     connection::{AsyncStream, Connection, FakeStream},
     frame::Resp3,
-    shared::Shared,
+    shared::{db::Db, Shared},
     Id, Key,
 };
-use bytes::BytesMut;
-use std::sync::Arc;
+use bytes::{Bytes, BytesMut};
+use snafu::{location, Location};
+use std::{future::Future, net::SocketAddr, sync::Arc};
+use tokio::{io::DuplexStream, time::Instant};
 use tracing::{debug, instrument};
 
 pub struct Handler<S: AsyncStream> {
@@ -22,69 +24,284 @@ impl<S: AsyncStream> Handler<S> {
     #[inline]
     pub fn new(shared: Shared, stream: S) -> Self {
         let bg_task_channel = BgTaskChannel::default();
+        bg_task_channel.set_output_buffer_limit(
+            shared
+                .conf()
+                .server
+                .client_output_buffer_limit
+                .get(ClientClass::Normal),
+        );
         let client_id = Self::create_client_id(&shared, &bg_task_channel);
         // 使用默认ac
         let ac = shared.conf().security.default_ac.load_full();
+        let mut context = HandlerContext::new(client_id, DEFAULT_USER, ac);
+        // default用户设置了密码时，客户端需要先通过AUTH认证
+        context.authenticated = context.ac.password.is_empty();
+        context.protocol_version = shared.conf().server.default_resp_version;
 
         Self {
             conn: Connection::new(stream, shared.conf().server.max_batch),
             shared,
             bg_task_channel,
-            context: HandlerContext::new(client_id, DEFAULT_USER, ac),
+            context,
         }
     }
 
     #[inline]
     #[instrument(level = "debug", skip(self), fields(client_id), err)]
     pub async fn run(&mut self) -> anyhow::Result<()> {
+        let output_buffer = self.bg_task_channel.output_buffer().clone();
+        let kill_signal = self.bg_task_channel.kill_signal().clone();
+
         ID.scope(self.context.client_id, async {
-            loop {
-                tokio::select! {
-                    // 等待shutdown信号
-                    _signal = self.shared.shutdown().wait_shutdown_triggered() => {
-                        debug!("handler received shutdown signal");
-                        return Ok(());
-                    }
-                    // 等待客户端请求
-                    frames =  self.conn.read_frames() => {
-                        if let Some(frames) = frames? {
-                            for f in frames.into_iter() {
-                                if let Some(resp) = dispatch(f, self).await? {
-                                    self.conn.write_frame(&resp).await?;
-                                }
-                            }
-                        } else {
-                            return Ok(());
-                        }
-                    },
-                    // 从后台任务接收数据，并发送给客户端。只要拥有对应的BgTaskSender，
-                    // 任何其它连接 都可以向当前连接的客户端发送消息
-                    frame = self.bg_task_channel.recv_from_bg_task() => {
-                        debug!("handler received from background task: {:?}", frame);
-                        self.conn.write_frame(&frame).await?;
-                    },
-                };
+            tokio::select! {
+                res = self.serve() => res,
+                // 输出缓冲区超出限制(例如订阅者一直不读取消息)时，断开连接
+                _ = output_buffer.overflowed() => {
+                    Err(anyhow::anyhow!("client output buffer limit reached"))
+                }
+                // 被其它连接(例如CLIENT KILL)要求断开连接
+                _ = kill_signal.notified() => {
+                    debug!("handler was killed");
+                    Ok(())
+                }
             }
         })
         .await
     }
 
+    async fn serve(&mut self) -> anyhow::Result<()> {
+        // 由连接直接执行的命令，其回复直接写出到连接
+        self.context.direct_reply = true;
+
+        loop {
+            tokio::select! {
+                // 等待shutdown信号
+                _signal = self.shared.shutdown().wait_shutdown_triggered() => {
+                    debug!("handler received shutdown signal");
+                    return Ok(());
+                }
+                // 等待客户端请求
+                frames =  self.conn.read_frames() => {
+                    if let Some(frames) = frames? {
+                        // 回复先缓存在写缓冲区中，批处理结束时才写出。由后台任务负责回复的
+                        // 命令(例如BLPOP)也需要计入已处理的命令
+                        for f in frames.into_iter() {
+                            if let Some(resp) = dispatch(f, self).await? {
+                                self.write_reply(&resp).await?;
+                            }
+                            self.conn.finish_command().await?;
+                        }
+                    } else {
+                        return Ok(());
+                    }
+                },
+                // 从后台任务接收数据，并发送给客户端。只要拥有对应的BgTaskSender，
+                // 任何其它连接 都可以向当前连接的客户端发送消息
+                frame = self.bg_task_channel.recv_from_bg_task() => {
+                    debug!("handler received from background task: {:?}", frame);
+                    self.write_bg_frames(frame).await?;
+                },
+            };
+
+            // 未能写出的数据也计入输出缓冲区
+            if self
+                .bg_task_channel
+                .output_buffer()
+                .check(self.conn.pending_write_len())
+            {
+                anyhow::bail!("client output buffer limit reached");
+            }
+        }
+    }
+
     #[inline]
     pub async fn dispatch(&mut self, cmd_frame: Resp3) -> Result<Option<Resp3>, ServerError> {
         ID.scope(self.context.client_id, dispatch(cmd_frame, self))
             .await
     }
 
+    /// 按照客户端协商的协议版本写出回复。客户端未通过HELLO切换到RESP3之前，
+    /// 回复都以RESP2格式编码
+    #[inline]
+    pub async fn write_reply(&mut self, reply: &Resp3) -> std::io::Result<()> {
+        if self.context.protocol_version == 3 {
+            self.conn.write_frame(reply).await
+        } else {
+            self.conn.write_resp2_frame(reply).await
+        }
+    }
+
+    /// 写出后台任务发送的frame。channel中已经就绪的frame(例如高扇出频道在短时间内发布的
+    /// 多条消息)会被一起编码到写缓冲区后只写出一次，以减少系统调用。一次最多合并
+    /// max_batch个frame，避免一直有消息到达时迟迟不写出
+    async fn write_bg_frames(&mut self, frame: Resp3) -> std::io::Result<()> {
+        let resp3 = self.context.protocol_version == 3;
+
+        self.conn.buffer_frame(&frame, resp3);
+        for _ in 1..self.conn.max_batch.max(1) {
+            let Some(frame) = self.bg_task_channel.try_recv_from_bg_task() else {
+                break;
+            };
+            self.conn.buffer_frame(&frame, resp3);
+        }
+
+        self.conn.write_buffered().await
+    }
+
+    /// 返回格式化的文本(例如INFO的输出)。RESP3下为格式为txt的Verbatim String，
+    /// RESP2下为Blob String
+    pub fn new_text_reply(&self, text: impl Into<Bytes>) -> Resp3 {
+        if self.context.protocol_version == 3 {
+            Resp3::new_verbatim_string(*b"txt", text.into())
+        } else {
+            Resp3::new_blob_string(text.into())
+        }
+    }
+
+    /// 返回成对的元素(例如HRANDFIELD的WITHVALUES)。RESP3下为由二元数组组成的数组，
+    /// RESP2下展开为[k1, v1, k2, v2, ...]
+    pub fn new_pairs_reply(&self, pairs: impl IntoIterator<Item = (Resp3, Resp3)>) -> Resp3 {
+        let pairs = pairs.into_iter();
+        if self.context.protocol_version == 3 {
+            Resp3::new_array(
+                pairs
+                    .map(|(k, v)| Resp3::new_array(vec![k, v]))
+                    .collect::<Vec<_>>(),
+            )
+        } else {
+            Resp3::new_array(pairs.flat_map(|(k, v)| [k, v]).collect::<Vec<_>>())
+        }
+    }
+
+    /// 集合的元素数量达到`streaming_reply_threshold`时，不构造完整的Resp3，而是通过
+    /// `write_streaming_array`等函数直接将元素逐个编码到写缓冲区，此时命令返回None。
+    /// 只有命令的回复直接写出到连接时才使用流式回复：EXEC中的命令以及脚本中的命令需要
+    /// 获取完整的回复；设置了截止时间的命令可能在编码到一半时被取消
+    pub fn should_stream_reply(&self, len: usize) -> bool {
+        let threshold = self.shared.conf().server.streaming_reply_threshold;
+        threshold != 0
+            && len >= threshold
+            && self.context.direct_reply
+            && self.context.deadline.is_none()
+    }
+
+    /// 先写入数组的头部，再将元素逐个编码到写缓冲区。写缓冲区达到阈值时立即写出，剩余的
+    /// 部分与其它命令的回复一样，由批处理结束时的`conn.finish_command`写出。该函数会等待
+    /// IO，因此不能在访问对象(持有键的锁)时调用。elems的数量必须等于len
+    pub async fn write_streaming_array(
+        &mut self,
+        len: usize,
+        elems: impl IntoIterator<Item = Resp3>,
+    ) -> CmdResult<()> {
+        let resp3 = self.context.protocol_version == 3;
+
+        self.conn.buffer_header(b'*', len);
+        for elem in elems {
+            self.conn.buffer_frame(&elem, resp3);
+            self.flush_streaming().await?;
+        }
+
+        Ok(())
+    }
+
+    /// 与`write_streaming_array`类似，RESP2下map被展开为数组。pairs的数量必须等于len
+    pub async fn write_streaming_map(
+        &mut self,
+        len: usize,
+        pairs: impl IntoIterator<Item = (Resp3, Resp3)>,
+    ) -> CmdResult<()> {
+        let resp3 = self.context.protocol_version == 3;
+
+        if resp3 {
+            self.conn.buffer_header(b'%', len);
+        } else {
+            self.conn.buffer_header(b'*', len * 2);
+        }
+        for (k, v) in pairs {
+            self.conn.buffer_frame(&k, resp3);
+            self.conn.buffer_frame(&v, resp3);
+            self.flush_streaming().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn flush_streaming(&mut self) -> CmdResult<()> {
+        self.conn
+            .flush_if_full()
+            .await
+            .map_err(|e| CmdError::ServerErr {
+                source: e.into(),
+                loc: location!(),
+            })
+    }
+
+    /// 执行master传播过来的写命令。master是可信的，因此不检查权限；命令也不会被
+    /// 再次传播。执行后按命令的字节数推进复制偏移量
+    pub async fn apply_from_master(
+        &mut self,
+        cmd_frame: Resp3,
+    ) -> Result<Option<Resp3>, ServerError> {
+        let len = cmd_frame.encoded_len() as u64;
+
+        let ac = std::mem::replace(&mut self.context.ac, Arc::new(AccessControl::new_loose()));
+        self.context.from_master = true;
+
+        let res = self.dispatch(cmd_frame).await;
+
+        self.context.from_master = false;
+        self.context.ac = ac;
+        let replica_conf = &self.shared.conf().replica;
+        replica_conf.offset.fetch_add(len);
+        replica_conf.master_link_up.store(true);
+
+        res
+    }
+
+    /// 阻塞命令等待fut完成，直到deadline(None表示永不超时)或者服务器关闭。超时或服务器
+    /// 关闭时返回None
+    #[inline]
+    pub async fn block_until<T>(
+        &self,
+        deadline: Option<Instant>,
+        fut: impl Future<Output = T>,
+    ) -> Option<T> {
+        self.shared.block_until(deadline, fut).await
+    }
+
+    /// 记录连接的对端地址，以便通过地址查找该连接
+    pub fn set_peer_addr(&mut self, addr: SocketAddr) {
+        self.shared
+            .db()
+            .record_client_addr(addr, self.context.client_id);
+        self.context.peer_addr = Some(addr);
+    }
+
     #[inline]
     pub fn create_client_id(shared: &Shared, bg_task_channel: &BgTaskChannel) -> Id {
-        let id_may_occupied = CLIENT_ID_COUNT.fetch_add(1);
-        let client_id = shared
+        shared
             .db()
-            .record_client_id(id_may_occupied, bg_task_channel.new_sender());
-        if id_may_occupied != client_id {
-            CLIENT_ID_COUNT.store(client_id);
+            .record_client_id(shared.next_client_id(), bg_task_channel.new_sender())
+    }
+}
+
+impl<S: AsyncStream> Drop for Handler<S> {
+    fn drop(&mut self) {
+        // 连接断开时注销客户端ID，使其不再计入客户端数量
+        self.shared
+            .db()
+            .remove_client_record(self.context.client_id);
+        if let Some(watched) = self.context.watched.take() {
+            watched.unwatch(self.shared.db());
+        }
+        if let Some(addr) = &self.context.peer_addr {
+            self.shared.db().remove_client_addr(addr);
+        }
+        // 连接在传播写命令之前断开，不能让过期键的DEL命令一直等待
+        if self.context.wcmd_held {
+            self.shared.wcmd_propagator().release_wcmd();
         }
-        client_id
     }
 }
 
@@ -97,8 +314,31 @@ pub struct HandlerContext {
     pub client_track: Option<BgTaskSender>,
     // 用于缓存需要传播的写命令
     pub wcmd_buf: BytesMut,
+    // 是否持有Propagator::hold_wcmd，在wcmd_buf被传播时释放
+    pub wcmd_held: bool,
+    // 写命令执行后改写成的确定性形式，传播时代替原命令
+    pub wcmd_rewrite: Option<CmdUnparsed>,
+    // 当前执行的命令是否来自master的复制流
+    pub from_master: bool,
+    // 客户端使用的协议版本，默认为RESP2，通过HELLO 3切换到RESP3
+    pub protocol_version: u8,
     pub user: bytes::Bytes,
     pub ac: Arc<AccessControl>,
+    // 客户端是否已通过认证，未认证时只能执行AUTH和HELLO
+    pub authenticated: bool,
+    // 通过MULTI开启的事务
+    pub transaction: Option<Transaction>,
+    // 通过WATCH监视的键
+    pub watched: Option<Watched>,
+    // 连接执行过的命令数
+    pub cmd_count: u64,
+    // 当前命令的截止时间，未设置command_timeout_ms时为None
+    pub deadline: Option<Instant>,
+    // 命令的回复是否直接写出到连接(而不是例如作为EXEC回复的一部分)，为true时才能使用
+    // 流式回复
+    pub direct_reply: bool,
+    // 客户端的地址，非网络连接(例如脚本使用的handler)为None
+    pub peer_addr: Option<SocketAddr>,
 }
 
 impl HandlerContext {
@@ -108,10 +348,66 @@ impl HandlerContext {
             subscribed_channels: None,
             client_track: None,
             wcmd_buf: BytesMut::new(),
+            wcmd_held: false,
+            wcmd_rewrite: None,
+            from_master: false,
+            protocol_version: 2,
             user,
             ac,
+            authenticated: true,
+            transaction: None,
+            watched: None,
+            cmd_count: 0,
+            deadline: None,
+            direct_reply: false,
+            peer_addr: None,
         }
     }
+
+    /// 将wcmd_buf回滚到mark处，丢弃执行失败的命令缓存的写命令。wcmd_buf在此期间可能已经
+    /// 因达到上限而被传播，此时已传播的部分无法撤回，只丢弃剩余的部分
+    pub fn rollback_wcmd(&mut self, mark: usize) {
+        self.wcmd_buf.truncate(mark);
+    }
+}
+
+/// WATCH监视的键
+#[derive(Debug)]
+pub struct Watched {
+    // 被监视的键被修改(包括只修改过期时间)时，会向该通道发送键名。EXEC时如果通道中有
+    // 消息，则放弃执行事务
+    pub tx: flume::Sender<Bytes>,
+    pub rx: flume::Receiver<Bytes>,
+    pub keys: Vec<Key>,
+}
+
+impl Watched {
+    /// 取消监视，移除WATCH添加的事件以及只为监视而创建的空对象
+    pub fn unwatch(self, db: &Db) {
+        for key in &self.keys {
+            db.remove_may_update_event(key, &self.tx);
+        }
+    }
+}
+
+impl Default for Watched {
+    fn default() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self {
+            tx,
+            rx,
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// MULTI开启的事务状态
+#[derive(Debug, Default)]
+pub struct Transaction {
+    // 排队等待EXEC执行的命令
+    pub queued: Vec<Resp3>,
+    // 排队时是否有命令出错，为true时EXEC会放弃执行整个事务
+    pub tx_dirty: bool,
 }
 
 impl Handler<FakeStream> {
@@ -139,6 +435,13 @@ impl Handler<FakeStream> {
         };
 
         let bg_task_channel = BgTaskChannel::default();
+        bg_task_channel.set_output_buffer_limit(
+            shared
+                .conf()
+                .server
+                .client_output_buffer_limit
+                .get(ClientClass::Normal),
+        );
 
         let context = if let Some(cx) = context {
             let client_id = Self::create_client_id(&shared, &bg_task_channel);
@@ -164,3 +467,343 @@ impl Handler<FakeStream> {
         )
     }
 }
+
+// 内存管道的缓冲区大小，写满后写端会等待读端读取
+const MEMORY_STREAM_BUF_SIZE: usize = 64 * 1024;
+
+impl Handler<DuplexStream> {
+    pub fn new_memory() -> (Self, Connection<DuplexStream>) {
+        Self::new_memory_with(Shared::default())
+    }
+
+    /// 创建通过内存中的双工管道与客户端通信的handler，返回的Connection作为客户端使用。
+    /// 与FakeStream不同，双方读写的是完整的RESP字节流，因此无需绑定端口即可通过
+    /// `handler.run()`端到端地测试命令
+    pub fn new_memory_with(shared: Shared) -> (Self, Connection<DuplexStream>) {
+        let (server, client) = tokio::io::duplex(MEMORY_STREAM_BUF_SIZE);
+        let max_batch = shared.conf().server.max_batch;
+
+        (
+            Handler::new(shared, server),
+            Connection::new(client, max_batch),
+        )
+    }
+}
+
+#[cfg(test)]
+mod handler_tests {
+    use super::*;
+    use crate::{frame::RESP3Decoder, util::cmd};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Decoder;
+
+    #[tokio::test]
+    async fn resp2_by_default_test() {
+        crate::util::test_init();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        let stream = listener.accept().await.unwrap().0;
+        let mut handler = Handler::new(Shared::default(), stream);
+        tokio::spawn(async move { handler.run().await });
+
+        // 发送命令并返回完整的原始回复
+        async fn call(client: &mut Connection<TcpStream>, args: &[&'static str]) -> BytesMut {
+            client.write_frame(&cmd(args)).await.unwrap();
+
+            let mut buf = BytesMut::new();
+            loop {
+                client.read_buf(&mut buf).await.unwrap();
+                if RESP3Decoder::default()
+                    .decode(&mut buf.clone())
+                    .is_ok_and(|f| f.is_some())
+                {
+                    return buf;
+                }
+            }
+        }
+
+        // 没有发送过HELLO的客户端收到RESP2格式的回复
+        let reply = call(&mut client, &["GET", "key_nil"]).await;
+        assert_eq!(reply.as_ref(), b"$-1\r\n");
+
+        // 不支持的协议版本不会改变协议
+        let reply = call(&mut client, &["HELLO", "4"]).await;
+        assert!(reply.starts_with(b"-NOPROTO"));
+        let reply = call(&mut client, &["GET", "key_nil"]).await;
+        assert_eq!(reply.as_ref(), b"$-1\r\n");
+
+        // HELLO 3之后使用RESP3
+        let reply = call(&mut client, &["HELLO", "3"]).await;
+        assert!(reply.starts_with(b"%7\r\n"));
+        let reply = call(&mut client, &["GET", "key_nil"]).await;
+        assert_eq!(reply.as_ref(), b"_\r\n");
+    }
+
+    #[tokio::test]
+    async fn default_resp_version_test() {
+        use crate::{
+            conf::{Conf, ServerConf},
+            shared::db::Db,
+        };
+
+        crate::util::test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                default_resp_version: 3,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let shared = Shared::new(Arc::new(Db::default()), Arc::new(conf), Default::default());
+        let (mut handler, mut client) = Handler::new_memory_with(shared);
+        tokio::spawn(async move { handler.run().await });
+
+        async fn call(client: &mut Connection<DuplexStream>, args: &[&'static str]) -> BytesMut {
+            client.write_frame(&cmd(args)).await.unwrap();
+
+            let mut buf = BytesMut::new();
+            loop {
+                client.read_buf(&mut buf).await.unwrap();
+                if RESP3Decoder::default()
+                    .decode(&mut buf.clone())
+                    .is_ok_and(|f| f.is_some())
+                {
+                    return buf;
+                }
+            }
+        }
+
+        // 没有发送过HELLO的客户端收到RESP3格式的回复
+        let reply = call(&mut client, &["GET", "key_nil"]).await;
+        assert_eq!(reply.as_ref(), b"_\r\n");
+
+        // 仍然可以通过HELLO切换到RESP2
+        let reply = call(&mut client, &["HELLO", "2"]).await;
+        assert!(reply.starts_with(b"*14\r\n"));
+        let reply = call(&mut client, &["GET", "key_nil"]).await;
+        assert_eq!(reply.as_ref(), b"$-1\r\n");
+    }
+
+    #[tokio::test]
+    async fn memory_stream_test() {
+        crate::util::test_init();
+
+        let (mut handler, mut client) = Handler::new_memory();
+        tokio::spawn(async move { handler.run().await });
+
+        async fn call(client: &mut Connection<DuplexStream>, args: &[&'static str]) -> Resp3 {
+            client.write_frame(&cmd(args)).await.unwrap();
+            client.read_frame().await.unwrap().unwrap()
+        }
+
+        assert_eq!(
+            call(&mut client, &["SET", "key", "value"]).await,
+            Resp3::new_simple_string("OK".into())
+        );
+        assert_eq!(
+            call(&mut client, &["GET", "key"]).await,
+            Resp3::new_blob_string("value".into())
+        );
+        assert_eq!(call(&mut client, &["GET", "key_nil"]).await, Resp3::Null);
+    }
+
+    #[tokio::test]
+    async fn client_info_net_counters_test() {
+        crate::util::test_init();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        let stream = listener.accept().await.unwrap().0;
+        let mut handler = Handler::new(Shared::default(), stream);
+        tokio::spawn(async move { handler.run().await });
+
+        async fn call(client: &mut Connection<TcpStream>, args: &[&'static str]) -> Resp3 {
+            client.write_frame(&cmd(args)).await.unwrap();
+            client.read_frame().await.unwrap().unwrap()
+        }
+
+        call(&mut client, &["SET", "key", "value"]).await;
+        call(&mut client, &["GET", "key"]).await;
+        call(&mut client, &["PING"]).await;
+        let (sent, received) = (client.net_out(), client.net_in());
+
+        let info = call(&mut client, &["CLIENT", "INFO"]).await;
+        let info = String::from_utf8(info.try_blob().unwrap().to_vec()).unwrap();
+        let field = |name: &str| -> u64 {
+            info.trim_end()
+                .split(' ')
+                .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+                .unwrap()
+                .parse()
+                .unwrap()
+        };
+
+        assert_eq!(field("tot-cmds"), 4);
+        // 服务端读取的字节数包括CLIENT INFO命令本身，写出的字节数不包括本次回复
+        assert!(field("tot-net-in") > sent);
+        assert_eq!(field("tot-net-in"), client.net_out());
+        assert!(field("tot-net-out") > 0);
+        assert_eq!(field("tot-net-out"), received);
+    }
+
+    use std::{
+        pin::Pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll},
+    };
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    // 统计写出的次数
+    struct CountWrite {
+        inner: DuplexStream,
+        writes: Arc<AtomicUsize>,
+    }
+
+    impl AsyncRead for CountWrite {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for CountWrite {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+            if res.is_ready() {
+                self.writes.fetch_add(1, Ordering::Relaxed);
+            }
+            res
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn bg_frames_coalesce_test() {
+        crate::util::test_init();
+
+        const COUNT: usize = 500;
+
+        let (server, client) = tokio::io::duplex(MEMORY_STREAM_BUF_SIZE);
+        let writes = Arc::new(AtomicUsize::new(0));
+        let mut handler = Handler::new(
+            Shared::default(),
+            CountWrite {
+                inner: server,
+                writes: writes.clone(),
+            },
+        );
+        let mut client = Connection::new(client, 0);
+
+        // 在handler开始运行之前发送所有消息，模拟短时间内到达的多条消息
+        let sender = handler.bg_task_channel.new_sender();
+        for i in 0..COUNT {
+            sender
+                .send(Resp3::new_blob_string(i.to_string().into()))
+                .unwrap();
+        }
+        tokio::spawn(async move { handler.run().await });
+
+        // 消息按顺序完整到达
+        for i in 0..COUNT {
+            assert_eq!(
+                client.read_frame().await.unwrap().unwrap(),
+                Resp3::new_blob_string(i.to_string().into())
+            );
+        }
+        // 多条消息被合并写出
+        assert!(writes.load(Ordering::Relaxed) < COUNT / 10);
+    }
+
+    #[tokio::test]
+    async fn pipeline_reply_test() {
+        crate::util::test_init();
+
+        // 小的批处理的所有回复只写出一次
+        let (server, client) = tokio::io::duplex(MEMORY_STREAM_BUF_SIZE);
+        let writes = Arc::new(AtomicUsize::new(0));
+        let mut handler = Handler::new(
+            Shared::default(),
+            CountWrite {
+                inner: server,
+                writes: writes.clone(),
+            },
+        );
+        let mut client = Connection::new(client, 0);
+        tokio::spawn(async move { handler.run().await });
+
+        const COUNT: usize = 100;
+        let mut pipeline = BytesMut::new();
+        for _ in 0..COUNT {
+            cmd(&["PING"]).encode_buf(&mut pipeline);
+        }
+        client.write_all(&pipeline).await.unwrap();
+        for _ in 0..COUNT {
+            assert_eq!(
+                client.read_frame().await.unwrap().unwrap(),
+                Resp3::new_simple_string("PONG".into())
+            );
+        }
+        assert_eq!(writes.load(Ordering::Relaxed), 1);
+
+        // 回复的总大小远超写缓冲区的上限时，回复仍然按顺序完整到达
+        let (server, client) = tokio::io::duplex(MEMORY_STREAM_BUF_SIZE);
+        let mut handler = Handler::new(Shared::default(), server);
+        tokio::spawn(async move { handler.run().await });
+        let (mut reader, mut writer) = tokio::io::split(client);
+
+        const BIG_COUNT: usize = 300;
+        let value = "v".repeat(1024);
+        let mut pipeline = BytesMut::new();
+        for i in 0..BIG_COUNT {
+            let key = format!("key{i}");
+            cmd(&["SET", &key, &value]).encode_buf(&mut pipeline);
+            cmd(&["GET", &key]).encode_buf(&mut pipeline);
+        }
+        // 客户端需要同时读取回复，否则双方都会因为管道写满而阻塞
+        tokio::spawn(async move {
+            tokio::io::AsyncWriteExt::write_all(&mut writer, &pipeline)
+                .await
+                .unwrap();
+        });
+
+        let mut decoder = RESP3Decoder::default();
+        let mut buf = BytesMut::new();
+        let mut replies = Vec::with_capacity(BIG_COUNT * 2);
+        while replies.len() < BIG_COUNT * 2 {
+            match decoder.decode(&mut buf).unwrap() {
+                Some(frame) => replies.push(frame),
+                None => {
+                    let n = tokio::io::AsyncReadExt::read_buf(&mut reader, &mut buf)
+                        .await
+                        .unwrap();
+                    assert_ne!(n, 0);
+                }
+            }
+        }
+        for (i, pair) in replies.chunks(2).enumerate() {
+            assert_eq!(pair[0], Resp3::new_simple_string("OK".into()), "{i}");
+            assert_eq!(pair[1], Resp3::new_blob_string(value.clone().into()), "{i}");
+        }
+    }
+}
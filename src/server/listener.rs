@@ -1,22 +1,46 @@
 use super::Handler;
 
-use crate::{conf::Conf, persist::rdb::Rdb, shared::Shared};
+use crate::{
+    conf::{Conf, ServerConf},
+    connection::{AsyncStream, Connection},
+    frame::Resp3,
+    persist::rdb::Rdb,
+    shared::Shared,
+};
 use async_shutdown::DelayShutdownToken;
 use backon::Retryable;
-use std::sync::Arc;
-use tokio::{io, net::TcpListener, sync::Semaphore};
+use tokio::{
+    io,
+    net::{lookup_host, TcpListener, TcpSocket},
+};
 use tokio_rustls::TlsAcceptor;
-use tracing::error;
+use tracing::{error, warn};
 
 pub struct Listener {
     pub shared: Shared,
     pub listener: TcpListener,
     pub tls_acceptor: Option<TlsAcceptor>,
-    pub limit_connections: Arc<Semaphore>,
     pub delay_token: DelayShutdownToken<()>,
 }
 
 impl Listener {
+    /// 根据配置绑定监听套接字。开启SO_REUSEADDR，避免重启时因旧连接处于TIME_WAIT而绑定失败
+    pub async fn bind(conf: &ServerConf) -> Result<TcpListener, io::Error> {
+        let addr = lookup_host((conf.addr.as_str(), conf.port))
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid server address"))?;
+
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+        socket.listen(conf.tcp_backlog)
+    }
+
     #[inline]
     pub async fn run(&mut self) -> Result<(), io::Error> {
         tracing::info!(
@@ -31,25 +55,30 @@ impl Listener {
         println!("debug mode is enabled");
 
         loop {
-            #[cfg(not(feature = "debug"))]
-            let permit = self
-                .limit_connections
-                .clone()
-                .acquire_owned()
-                .await
-                .unwrap();
-
-            let (stream, _) = (|| async { self.listener.accept().await })
+            let (stream, addr) = (|| async { self.listener.accept().await })
                 .retry(&backon::ExponentialBuilder::default())
                 .await?;
+            // 关闭Nagle算法，降低小包回复的延迟
+            if let Err(e) = stream.set_nodelay(true) {
+                warn!(cause = ?e, "failed to set TCP_NODELAY");
+            }
 
             let shared = self.shared.clone();
 
+            // 客户端数量达到上限时，拒绝新的连接
+            let reach_max_clients = shared.db().client_count() >= shared.conf().server.max_clients;
+
             // 对于每个连接都创建一个delay_token，只有当所有连接都正常退出时，才关闭服务
             let delay_token = self.delay_token.clone();
             match &self.tls_acceptor {
                 None => {
+                    if reach_max_clients {
+                        tokio::spawn(reject(stream));
+                        continue;
+                    }
+
                     let mut handler = Handler::new(shared, stream);
+                    handler.set_peer_addr(addr);
 
                     tokio::spawn(async move {
                         // 开始处理连接
@@ -59,13 +88,18 @@ impl Listener {
 
                         // handler.run()不应该block，这会导致delay_token无法释放
                         drop(delay_token);
-                        #[cfg(not(feature = "debug"))]
-                        drop(permit);
                     });
                 }
                 // 如果开启了TLS，则使用TlsStream
                 Some(tls_acceptor) => {
-                    let mut handler = Handler::new(shared, tls_acceptor.accept(stream).await?);
+                    let stream = tls_acceptor.accept(stream).await?;
+                    if reach_max_clients {
+                        tokio::spawn(reject(stream));
+                        continue;
+                    }
+
+                    let mut handler = Handler::new(shared, stream);
+                    handler.set_peer_addr(addr);
 
                     tokio::spawn(async move {
                         // 开始处理连接
@@ -74,8 +108,6 @@ impl Listener {
                         }
 
                         drop(delay_token);
-                        #[cfg(not(feature = "debug"))]
-                        drop(permit);
                     });
                 }
             };
@@ -92,3 +124,211 @@ impl Listener {
         }
     }
 }
+
+// 向客户端返回错误后关闭连接。写入可能阻塞，因此在单独的任务中执行，避免阻塞accept
+async fn reject(stream: impl AsyncStream) {
+    warn!("max number of clients reached, reject new connection");
+
+    let mut conn = Connection::new(stream, 0);
+    conn.write_frame(&Resp3::<&[u8], &str>::new_simple_error(
+        "ERR max number of clients reached",
+    ))
+    .await
+    .ok();
+    conn.shutdown().await.ok();
+}
+
+#[cfg(test)]
+mod listener_tests {
+    use super::*;
+    use crate::{
+        conf::ServerConf,
+        shared::{db::Db, Shared},
+        util::{cmd, test_init},
+    };
+    use async_shutdown::ShutdownManager;
+    use std::{sync::Arc, time::Duration};
+    use tokio::net::TcpStream;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn max_clients_test() {
+        test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                max_clients: 1,
+                expire_check_interval_secs: 3600,
+                ..Default::default()
+            },
+            rdb: None,
+            aof: None,
+            ..Default::default()
+        };
+        let shutdown_manager = ShutdownManager::new();
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            shutdown_manager.clone(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+        let mut listener = Listener {
+            shared: shared.clone(),
+            listener: tcp_listener,
+            tls_acceptor: None,
+            delay_token: shutdown_manager.delay_shutdown_token().unwrap(),
+        };
+        tokio::spawn(async move { listener.run().await });
+
+        let ping = Resp3::<_, String>::new_array(vec![Resp3::new_blob_string("PING".as_bytes())]);
+
+        let mut client1 = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        client1.write_frame(&ping).await.unwrap();
+        assert_eq!(
+            client1.read_frame().await.unwrap().unwrap(),
+            Resp3::new_simple_string("PONG".into())
+        );
+
+        // 第二个连接被拒绝
+        let mut client2 = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        assert_eq!(
+            client2.read_frame().await.unwrap().unwrap(),
+            Resp3::new_simple_error("ERR max number of clients reached".into())
+        );
+        assert!(client2.read_frame().await.unwrap().is_none());
+
+        // 第一个连接断开后，客户端数量减少，新的连接可以被接受
+        drop(client1);
+        while shared.db().client_count() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let mut client3 = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        client3.write_frame(&ping).await.unwrap();
+        assert_eq!(
+            client3.read_frame().await.unwrap().unwrap(),
+            Resp3::new_simple_string("PONG".into())
+        );
+
+        shutdown_manager.trigger_shutdown(()).ok();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn bind_with_backlog_test() {
+        test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                port: 0,
+                tcp_backlog: 16,
+                expire_check_interval_secs: 3600,
+                ..Default::default()
+            },
+            rdb: None,
+            aof: None,
+            ..Default::default()
+        };
+        let tcp_listener = Listener::bind(&conf.server).await.unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+
+        let shutdown_manager = ShutdownManager::new();
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            shutdown_manager.clone(),
+        );
+        let mut listener = Listener {
+            shared,
+            listener: tcp_listener,
+            tls_acceptor: None,
+            delay_token: shutdown_manager.delay_shutdown_token().unwrap(),
+        };
+        tokio::spawn(async move { listener.run().await });
+
+        let ping = Resp3::<_, String>::new_array(vec![Resp3::new_blob_string("PING".as_bytes())]);
+
+        let mut clients = Vec::new();
+        for _ in 0..8 {
+            clients.push(Connection::new(TcpStream::connect(addr).await.unwrap(), 0));
+        }
+        for client in clients.iter_mut() {
+            client.write_frame(&ping).await.unwrap();
+            assert_eq!(
+                client.read_frame().await.unwrap().unwrap(),
+                Resp3::new_simple_string("PONG".into())
+            );
+        }
+
+        shutdown_manager.trigger_shutdown(()).ok();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn handler_panic_isolated_test() {
+        test_init();
+
+        let conf = Conf {
+            server: ServerConf {
+                enable_debug_crash: true,
+                expire_check_interval_secs: 3600,
+                ..Default::default()
+            },
+            rdb: None,
+            aof: None,
+            ..Default::default()
+        };
+        let shutdown_manager = ShutdownManager::new();
+        let shared = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            shutdown_manager.clone(),
+        );
+
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+        let mut listener = Listener {
+            shared: shared.clone(),
+            listener: tcp_listener,
+            tls_acceptor: None,
+            delay_token: shutdown_manager.delay_shutdown_token().unwrap(),
+        };
+        tokio::spawn(async move { listener.run().await });
+
+        let mut client1 = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        let mut client2 = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        client2
+            .write_frame(&cmd(&["SET", "key", "value"]))
+            .await
+            .unwrap();
+        client2.read_frame().await.unwrap().unwrap();
+
+        // 连接的任务panic后，该连接被断开
+        client1
+            .write_frame(&cmd(&["DEBUG", "PANIC"]))
+            .await
+            .unwrap();
+        assert!(client1.read_frame().await.map_or(true, |f| f.is_none()));
+
+        // 其它连接以及新的连接不受影响
+        client2.write_frame(&cmd(&["GET", "key"])).await.unwrap();
+        assert_eq!(
+            client2.read_frame().await.unwrap().unwrap(),
+            Resp3::new_blob_string("value".into())
+        );
+        let mut client3 = Connection::new(TcpStream::connect(addr).await.unwrap(), 0);
+        client3.write_frame(&cmd(&["DEBUG", "OOM"])).await.unwrap();
+        assert!(client3.read_frame().await.map_or(true, |f| f.is_none()));
+
+        client2.write_frame(&cmd(&["PING"])).await.unwrap();
+        assert_eq!(
+            client2.read_frame().await.unwrap().unwrap(),
+            Resp3::new_simple_string("PONG".into())
+        );
+        // panic的连接不再计入客户端数量
+        while shared.db().client_count() > 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        shutdown_manager.trigger_shutdown(()).ok();
+    }
+}
@@ -14,16 +14,14 @@ use crate::{
     Id,
 };
 use async_shutdown::ShutdownManager;
-use crossbeam::atomic::AtomicCell;
 use std::sync::Arc;
-use tokio::{net::TcpListener, sync::Semaphore, task_local};
+use tokio::{net::TcpListener, task_local};
 use tokio_rustls::TlsAcceptor;
 use tracing::{debug, error};
 
+// 保留前20个ID，专用于事务处理。新连接的客户端ID由`Shared::next_client_id()`分配，
+// 已连接的客户端的ID会被记录在`Db`中
 pub const RESERVE_MAX_ID: u128 = 20;
-// 该值作为新连接的客户端的ID。已连接的客户端的ID会被记录在`Shared`中，在设置ID时
-// 需要检查是否已经存在相同的ID。保留前20个ID，专用于事务处理
-pub static CLIENT_ID_COUNT: AtomicCell<u128> = AtomicCell::new(RESERVE_MAX_ID);
 
 task_local! { pub static ID: Id; }
 
@@ -52,16 +50,11 @@ pub async fn run(listener: TcpListener, conf: Conf) {
         None
     };
 
-    let limit_connections = Arc::new(Semaphore::new(conf.server.max_connections));
+    let db = Db::new(conf.server.db_shards, conf.server.db_initial_capacity);
     let mut server = Listener {
-        shared: Shared::new(
-            Arc::new(Db::default()),
-            Arc::new(conf),
-            shutdown_manager.clone(),
-        ),
+        shared: Shared::new(Arc::new(db), Arc::new(conf), shutdown_manager.clone()),
         listener,
         tls_acceptor,
-        limit_connections,
         delay_token: shutdown_manager.delay_shutdown_token().unwrap(),
     };
 
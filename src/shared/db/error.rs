@@ -17,7 +17,7 @@ impl std::fmt::Display for DbError {
             DbError::TypeErr { expected, found } => {
                 write!(f, "type error expected: {expected} found {found}")
             }
-            DbError::Overflow => write!(f, "value out of range"),
+            DbError::Overflow => write!(f, "increment or decrement would overflow"),
         }
     }
 }
@@ -0,0 +1,100 @@
+use super::{ExpireReason, Object};
+use flume::Sender;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, OnceLock,
+};
+
+// 对象的元素数量超过该值时才会在后台释放，释放小对象的开销小于发送到后台线程的开销
+pub const LAZYFREE_THRESHOLD: usize = 64;
+
+/// 在后台线程中释放大对象，避免释放包含大量元素的对象时阻塞命令的执行。后台线程在第
+/// 一次需要时才会创建
+#[derive(Debug, Default)]
+pub struct LazyFree {
+    sender: OnceLock<Sender<Object>>,
+    // 已发送到后台线程但尚未释放的对象数量
+    pending: Arc<AtomicUsize>,
+    // 以下开关分别对应因DEL，淘汰以及过期而移除的对象
+    lazy_user_del: AtomicBool,
+    lazy_eviction: AtomicBool,
+    lazy_expire: AtomicBool,
+    // 被新值覆盖(例如SET覆盖已有的键)的旧对象
+    lazy_server_del: AtomicBool,
+}
+
+impl LazyFree {
+    pub fn set_lazy_user_del(&self, enable: bool) {
+        self.lazy_user_del.store(enable, Ordering::Relaxed);
+    }
+
+    pub fn set_lazy_eviction(&self, enable: bool) {
+        self.lazy_eviction.store(enable, Ordering::Relaxed);
+    }
+
+    pub fn set_lazy_expire(&self, enable: bool) {
+        self.lazy_expire.store(enable, Ordering::Relaxed);
+    }
+
+    pub fn set_lazy_server_del(&self, enable: bool) {
+        self.lazy_server_del.store(enable, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    /// 释放因reason而被移除的对象。如果开启了对应的开关且对象足够大，则交给后台线程
+    /// 释放并返回true，否则直接释放并返回false
+    pub fn free(&self, obj: Object, reason: ExpireReason) -> bool {
+        let lazy = match reason {
+            ExpireReason::Deleted => &self.lazy_user_del,
+            ExpireReason::Evicted => &self.lazy_eviction,
+            ExpireReason::Expired => &self.lazy_expire,
+        };
+        self.free_if(lazy, obj)
+    }
+
+    /// 释放被新值覆盖的旧对象，返回值同[`LazyFree::free`]
+    pub fn free_overwritten(&self, obj: Object) -> bool {
+        self.free_if(&self.lazy_server_del, obj)
+    }
+
+    fn free_if(&self, lazy: &AtomicBool, obj: Object) -> bool {
+        if !lazy.load(Ordering::Relaxed)
+            || obj.inner().map_or(0, |inner| inner.free_effort()) <= LAZYFREE_THRESHOLD
+        {
+            return false;
+        }
+
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        if let Err(e) = self.sender().send(obj) {
+            // 后台线程已退出，直接释放
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            drop(e.into_inner());
+            return false;
+        }
+
+        true
+    }
+
+    fn sender(&self) -> &Sender<Object> {
+        self.sender.get_or_init(|| {
+            let (tx, rx) = flume::unbounded::<Object>();
+            let pending = self.pending.clone();
+            std::thread::Builder::new()
+                .name("lazy-free".to_string())
+                .spawn(move || {
+                    // 所有Sender被释放(即Db被释放)后退出
+                    for obj in rx {
+                        drop(obj);
+                        pending.fetch_sub(1, Ordering::AcqRel);
+                    }
+                })
+                .expect("failed to spawn lazy free thread");
+
+            tx
+        })
+    }
+}
@@ -1,9 +1,11 @@
 mod error;
+mod lazy_free;
 mod object;
 mod object_entry;
 
 use bytes::Bytes;
 pub use error::DbError;
+pub use lazy_free::{LazyFree, LAZYFREE_THRESHOLD};
 pub use object::*;
 use object_entry::IntentionLock;
 pub use object_entry::ObjectEntryMut;
@@ -12,17 +14,35 @@ use crate::{
     cmd::CmdResult,
     frame::Resp3,
     server::{BgTaskSender, RESERVE_MAX_ID},
+    shared::{propagator::Propagator, Observer},
     Id, Key,
 };
 use ahash::RandomState;
+use crossbeam::queue::SegQueue;
 use dashmap::{
-    mapref::{entry::Entry, one::Ref},
-    DashMap, DashSet,
+    mapref::{entry::Entry, multiple::RefMulti, one::Ref},
+    DashMap, DashSet, SharedValue,
 };
 use flume::Sender;
+use parking_lot::RwLock;
+use rayon::prelude::*;
+use std::{
+    fmt::Debug,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use strum::IntoStaticStr;
 use tokio::time::Instant;
 use tracing::{error, instrument};
 
+// 键的数量超过该值时，并行收集匹配的键
+const PAR_SNAPSHOT_THRESHOLD: usize = 1024 << 32;
+// 遍历键时每隔多少个键检查一次是否超过截止时间
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
 #[derive(Debug)]
 pub struct Db {
     // 键值对存在不一定代表着对象一定是有效的，例如当只希望监听键的事件而不希望创建对
@@ -34,14 +54,44 @@ pub struct Db {
     // 记录具有expire的键，以便进行**定期删除**，所有修改过期时间的操作都应该更新记录
     entry_expire_records: DashSet<(Instant, Key), RandomState>,
 
+    // 非空对象的数量(包含已过期但尚未被移除的对象)，所有创建或移除对象的操作都应该更新
+    object_count: AtomicUsize,
+
+    // 读命令查找键时命中与未命中的次数，用于INFO stats
+    keyspace_hits: AtomicU64,
+    keyspace_misses: AtomicU64,
+
+    // 因被访问而惰性删除与被定期删除的过期键数量，用于INFO stats
+    expired_keys_lazy: AtomicU64,
+    expired_keys_active: AtomicU64,
+
     // Key代表频道名，每个频道名映射着一组Sender，通过这些Sender可以发送消息给订阅频道
     // 的客户端
-    pub_sub: DashMap<Key, Vec<Sender<Resp3>>, RandomState>,
+    pub_sub: DashMap<Key, Vec<BgTaskSender>, RandomState>,
 
     // 记录已经连接的客户端，并且映射到该连接的`BgTaskSender`，使用该sender可以向该连接
     // 的客户端发送消息。利用client_records，一个连接可以代表另一个连接向其客户端发送
     // 消息
     client_records: DashMap<Id, BgTaskSender, RandomState>,
+
+    // 客户端地址到客户端ID的索引，用于CLIENT KILL ADDR等按地址查找连接的操作。只记录
+    // 网络连接，由handler在连接建立时记录，在连接断开时移除
+    client_addrs: DashMap<SocketAddr, Id, RandomState>,
+
+    // 键被移除时调用的回调函数以及观察者，用于通知外部(例如二级缓存)
+    expire_hook: ExpireHook,
+
+    // master删除过期键时通过它传播DEL命令，由Shared设置
+    wcmd_propagator: RwLock<Option<Arc<Propagator>>>,
+
+    // 用于在后台线程中释放被移除的大对象
+    lazy_free: LazyFree,
+
+    // 对象的编码改变时是否发布键空间事件
+    notify_encoding_change: AtomicBool,
+
+    // 作为replica运行时，访问已过期的键只视为键不存在，不删除键
+    replica_mode: AtomicBool,
 }
 
 impl Db {
@@ -49,10 +99,185 @@ impl Db {
         &self.entries
     }
 
+    pub fn lazy_free(&self) -> &LazyFree {
+        &self.lazy_free
+    }
+
+    /// 键值对的数量，包含空对象以及已过期但尚未被移除的对象
     pub fn size(&self) -> usize {
         self.entries.len()
     }
 
+    /// 合法对象的数量，不包含空对象以及已过期的对象。需要遍历所有键值对，优先使用
+    /// [`Db::len_nonexpired()`]
+    pub fn valid_size(&self) -> usize {
+        self.entries.iter().filter(|e| e.is_valid()).count()
+    }
+
+    /// 返回所有匹配pattern的键，跳过空对象以及已过期的对象。键是逐个分片收集的，每个分片
+    /// 只在遍历时持有读锁，因此结果不是全局原子的快照：遍历期间其它分片上新增或删除的键
+    /// 可能出现也可能不出现，但返回的键在遍历到时一定存在且未过期。调用者应该在
+    /// `block_in_place`中调用该函数
+    ///
+    /// # Return:
+    ///
+    /// 遍历期间超过deadline时返回None
+    pub fn snapshot_keys(
+        &self,
+        pattern: &regex::bytes::Regex,
+        deadline: Option<Instant>,
+    ) -> Option<Vec<Key>> {
+        let matched = |entry: RefMulti<'_, Key, Object>| {
+            (entry.is_valid() && pattern.is_match(entry.key())).then(|| entry.key().clone())
+        };
+        let timed_out = |visited: usize| {
+            visited.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+                && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        };
+
+        if self.size() > PAR_SNAPSHOT_THRESHOLD {
+            self.entries
+                .par_iter()
+                .try_fold(
+                    || (0, Vec::new()),
+                    |(visited, mut keys), entry| {
+                        if timed_out(visited) {
+                            return None;
+                        }
+                        keys.extend(matched(entry));
+                        Some((visited + 1, keys))
+                    },
+                )
+                .map(|res| res.map(|(_, keys)| keys))
+                .try_reduce(Vec::new, |mut a, b| {
+                    a.extend(b);
+                    Some(a)
+                })
+        } else {
+            let mut keys = Vec::new();
+            for (visited, entry) in self.entries.iter().enumerate() {
+                if timed_out(visited) {
+                    return None;
+                }
+                keys.extend(matched(entry));
+            }
+            Some(keys)
+        }
+    }
+
+    /// 合法对象的键和值占用的字节数的估计值(见[`ObjectInner::value_bytes()`])。需要
+    /// 遍历所有键值对，调用者应该在`block_in_place`中调用该函数
+    pub fn dataset_bytes(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.is_valid())
+            .map(|e| e.key().len() + e.inner().map_or(0, ObjectInner::value_bytes))
+            .sum()
+    }
+
+    /// 合法对象的数量，不包含空对象以及已过期的对象。所有带有过期时间的对象都存在过期
+    /// 记录，因此只需从非空对象的数量中减去已过期但尚未被移除的对象，无需遍历所有键值对
+    pub fn len_nonexpired(&self) -> usize {
+        let now = Instant::now();
+        // WARN: 访问entries时，不应该持有entry_expire_records元素的引用，否则会导致死锁
+        let expired_records: Vec<_> = self
+            .entry_expire_records
+            .iter()
+            .filter(|record| record.key().0 <= now)
+            .map(|record| record.key().clone())
+            .collect();
+
+        // 过期记录可能与对象不一致(例如对象已被移除)，只计入确实已过期的对象
+        let expired = expired_records
+            .iter()
+            .filter(|(ex, key)| {
+                self.entries.get(key).is_some_and(|e| {
+                    e.inner()
+                        .is_some_and(|inner| inner.expire() == Some(*ex) && inner.is_expired())
+                })
+            })
+            .count();
+
+        self.object_count
+            .load(Ordering::Relaxed)
+            .saturating_sub(expired)
+    }
+
+    pub fn keyspace_hits(&self) -> u64 {
+        self.keyspace_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn keyspace_misses(&self) -> u64 {
+        self.keyspace_misses.load(Ordering::Relaxed)
+    }
+
+    pub fn expired_keys_lazy(&self) -> u64 {
+        self.expired_keys_lazy.load(Ordering::Relaxed)
+    }
+
+    pub fn expired_keys_active(&self) -> u64 {
+        self.expired_keys_active.load(Ordering::Relaxed)
+    }
+
+    /// 清零INFO stats中的统计(CONFIG RESETSTAT)
+    pub fn reset_stats(&self) {
+        self.keyspace_hits.store(0, Ordering::Relaxed);
+        self.keyspace_misses.store(0, Ordering::Relaxed);
+        self.expired_keys_lazy.store(0, Ordering::Relaxed);
+        self.expired_keys_active.store(0, Ordering::Relaxed);
+    }
+
+    /// 移除所有对象，返回移除的对象数量。与逐个删除键相同，会更新过期记录并触发对象上的
+    /// 事件
+    pub async fn clear(&self) -> usize {
+        let keys: Vec<Key> = self
+            .entries
+            .iter()
+            .filter(|e| e.inner().is_some())
+            .map(|e| e.key().clone())
+            .collect();
+
+        let mut count = 0;
+        for key in keys {
+            if self.remove_object(&key).await.is_some() {
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /// 记录一次读命令对键的查找。visit_object()和multi_get()会自动记录，写命令以及内部
+    /// 的查找不应该记录
+    #[inline]
+    pub fn record_keyspace_lookup(&self, hit: bool) {
+        if hit {
+            self.keyspace_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.keyspace_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 记录一个被移除的过期键。active为true表示由定期删除移除，否则表示因被访问而惰性删除
+    #[inline]
+    pub fn record_expired_key(&self, active: bool) {
+        if active {
+            self.expired_keys_active.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.expired_keys_lazy.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    pub(super) fn incr_object_count(&self) {
+        self.object_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(super) fn decr_object_count(&self) {
+        self.object_count.fetch_sub(1, Ordering::Relaxed);
+    }
+
     // 记录客户端ID和其对应的`BgTaskSender`，用于向客户端发送消息
     #[inline]
     #[instrument(level = "debug", skip(self, id, bg_sender), ret)]
@@ -75,11 +300,39 @@ impl Db {
         }
     }
 
+    #[inline]
+    pub fn remove_client_record(&self, client_id: Id) -> Option<BgTaskSender> {
+        self.client_records
+            .remove(&client_id)
+            .map(|(_, sender)| sender)
+    }
+
+    /// 当前已连接的客户端数量
+    #[inline]
+    pub fn client_count(&self) -> usize {
+        self.client_records.len()
+    }
+
     #[instrument(level = "debug", skip(self), ret)]
     pub fn get_client_bg_sender(&self, client_id: Id) -> Option<BgTaskSender> {
         self.client_records.get(&client_id).map(|e| e.clone())
     }
 
+    #[inline]
+    pub fn record_client_addr(&self, addr: SocketAddr, client_id: Id) {
+        self.client_addrs.insert(addr, client_id);
+    }
+
+    #[inline]
+    pub fn remove_client_addr(&self, addr: &SocketAddr) -> Option<Id> {
+        self.client_addrs.remove(addr).map(|(_, id)| id)
+    }
+
+    #[inline]
+    pub fn get_client_id_by_addr(&self, addr: &SocketAddr) -> Option<Id> {
+        self.client_addrs.get(addr).map(|e| *e)
+    }
+
     pub async fn add_lock_event(&self, key: Key, target_id: Id) -> Option<IntentionLock> {
         self.get_object_entry_mut(key)
             .await
@@ -87,6 +340,16 @@ impl Db {
             .1
     }
 
+    /// 释放通过[`Db::add_lock_event`]添加的意向锁，唤醒下一个等待的任务。如果没有任务在
+    /// 等待，则同时移除意向锁事件(以及不再有任何事件的空对象)
+    pub fn remove_lock_event(&self, key: &Key, lock: IntentionLock) {
+        self.entries.remove_if_mut(key, |_, obj| {
+            obj.remove_idle_lock_event();
+            obj.is_unused()
+        });
+        drop(lock);
+    }
+
     pub async fn add_may_update_event(&self, key: Key, sender: Sender<Bytes>) {
         let _ = self
             .get_object_entry_mut(key)
@@ -94,6 +357,15 @@ impl Db {
             .add_may_update_event(sender);
     }
 
+    /// 移除键中通过sender发送的MayUpdate事件。如果键对应的是空对象(例如为了WATCH一个
+    /// 不存在的键而创建)且不再有任何事件，则同时移除该键值对
+    pub fn remove_may_update_event(&self, key: &Key, sender: &Sender<Bytes>) {
+        self.entries.remove_if_mut(key, |_, obj| {
+            obj.remove_may_update_event(sender);
+            obj.is_unused()
+        });
+    }
+
     pub async fn add_track_event(&self, key: Key, sender: Sender<Resp3>) {
         let _ = self.get_object_entry_mut(key).await.add_track_event(sender);
     }
@@ -119,17 +391,142 @@ impl Db {
         if let Some(old_ex) = old_ex {
             self.entry_expire_records.remove(&(old_ex, key.clone()));
         }
-        // 如果new_expire不为None，则需要记录新的过期时间
+        // 如果new_expire不为None，则需要记录新的过期时间。即使已经过期也需要记录，以便
+        // 定期删除以及统计未过期对象的数量
         if let Some(new_ex) = new_ex {
-            if new_ex > Instant::now() {
-                self.entry_expire_records.insert((new_ex, key.clone()));
-            }
+            self.entry_expire_records.insert((new_ex, key.clone()));
         }
     }
 
     pub fn remove_expire_record(&self, record: &(Instant, Key)) {
         self.entry_expire_records.remove(record);
     }
+
+    /// 定期删除：移除所有过期记录早于`now`的对象，返回移除的对象数量。只有对象确实已
+    /// 过期时才会被移除，如果对象的过期时间已被修改(例如执行了PERSIST)，则只移除过期记录
+    pub async fn remove_expired_objects(&self, now: Instant) -> usize {
+        let expired_records: Vec<_> = self
+            .entry_expire_records
+            .iter()
+            .filter(|record| record.key().0 <= now)
+            .map(|record| record.key().clone())
+            .collect();
+
+        let mut count = 0;
+        for record in expired_records {
+            // WARN: 执行remove_object时，不应该持有entry_expire_records元素的引用，否则会导致死锁
+            let entry = self.get_object_entry_mut(record.1.clone()).await;
+            if entry.is_object_expired() {
+                if let Some((_, obj)) = entry.remove_object() {
+                    tracing::trace!("key {:?} is expired", record.1);
+                    self.lazy_free.free(obj, ExpireReason::Expired);
+                    self.record_expired_key(true);
+                    count += 1;
+                }
+            }
+
+            self.remove_expire_record(&record);
+        }
+
+        count
+    }
+
+    /// 设置键被移除(过期，淘汰或者删除)时调用的回调函数，会覆盖之前设置的回调函数，但不
+    /// 影响复制与观察者。回调函数在释放Db的锁之后调用，因此可以访问Db
+    pub fn set_expire_hook(&self, hook: impl Fn(&Key, ExpireReason) + Send + Sync + 'static) {
+        *self.expire_hook.hook.write() = Some(Arc::new(hook));
+    }
+
+    pub fn remove_expire_hook(&self) {
+        *self.expire_hook.hook.write() = None;
+    }
+
+    /// 设置键过期或者被淘汰时通知的观察者，与expire hook一样在释放Db的锁之后调用
+    pub fn set_observer(&self, observer: Option<Arc<dyn Observer>>) {
+        *self.expire_hook.observer.write() = observer;
+    }
+
+    /// 设置传播过期键的DEL命令的Propagator
+    pub fn set_wcmd_propagator(&self, wcmd_propagator: Arc<Propagator>) {
+        *self.wcmd_propagator.write() = Some(wcmd_propagator);
+    }
+
+    /// 传播过期键的删除。必须在持有键的锁时调用，以保证DEL命令先于之后对该键的写命令传播
+    pub(super) fn propagate_expired_del(&self, key: &Key) {
+        if let Some(wcmd_propagator) = self.wcmd_propagator.read().as_ref() {
+            wcmd_propagator.propagate_expired_del(key);
+        }
+    }
+
+    /// 缓存键被移除的事件。回调函数可能访问Db，因此不能在持有锁时调用，之后由
+    /// [`Db::deliver_expire_events`]调用
+    pub(super) fn trigger_expire_hook(&self, key: &Key, reason: ExpireReason) {
+        if self.expire_hook.hook.read().is_some() || self.expire_hook.observer.read().is_some() {
+            self.expire_hook.events.push((key.clone(), reason));
+        }
+    }
+
+    /// 为缓存的事件调用expire hook以及观察者。不能在持有Db的锁时调用
+    pub fn deliver_expire_events(&self) {
+        if self.expire_hook.events.is_empty() {
+            return;
+        }
+
+        let hook = self.expire_hook.hook.read().clone();
+        let observer = self.expire_hook.observer.read().clone();
+        while let Some((key, reason)) = self.expire_hook.events.pop() {
+            if let Some(hook) = &hook {
+                hook(&key, reason);
+            }
+            if let Some(observer) = &observer {
+                match reason {
+                    ExpireReason::Expired => observer.on_expire(&key),
+                    ExpireReason::Evicted => observer.on_evict(&key),
+                    ExpireReason::Deleted => {}
+                }
+            }
+        }
+    }
+
+    /// 开启后，对象的编码改变时(例如Hash由ziplist转换为hashtable)向
+    /// `__keyevent@0__:encoding-change`频道发布键名。这是非标准的事件，默认关闭
+    pub fn set_notify_encoding_change(&self, enable: bool) {
+        self.notify_encoding_change.store(enable, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(super) fn is_notify_encoding_change(&self) -> bool {
+        self.notify_encoding_change.load(Ordering::Relaxed)
+    }
+
+    /// 开启后(作为replica运行时)，访问已过期的键时只视为键不存在，而不删除键。replica
+    /// 不会自行删除过期键，而是等待master传播的DEL命令，否则可能与master不一致
+    pub fn set_replica_mode(&self, enable: bool) {
+        self.replica_mode.store(enable, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn is_replica_mode(&self) -> bool {
+        self.replica_mode.load(Ordering::Relaxed)
+    }
+
+    /// 向`__keyevent@0__:<event>`频道发布键名。可能在持有对象的锁时调用，因此不能阻塞
+    pub(super) fn notify_keyevent(&self, event: &str, key: &Key) {
+        let channel = format!("__keyevent@0__:{event}");
+        let Some(listeners) = self.get_channel_all_listener(channel.as_bytes()) else {
+            return;
+        };
+
+        let channel = Bytes::from(channel);
+        for listener in listeners {
+            // 发送失败的订阅者由之后的PUBLISH移除
+            let _ = listener.send(Resp3::new_push(vec![
+                Resp3::new_blob_string("message".into()),
+                Resp3::new_blob_string(channel.clone()),
+                Resp3::new_blob_string(key.clone()),
+            ]));
+        }
+    }
 }
 
 // cmd模块只应该使用以下接口操作数据库
@@ -145,7 +542,7 @@ impl Db {
 
                 // 对象已过期，移除该键值对
                 drop(e);
-                self.remove_object(key).await;
+                self.remove_expired_object(key).await;
             }
         }
         false
@@ -164,16 +561,48 @@ impl Db {
 
                 // 对象已过期，移除该键值对
                 drop(e);
-                self.remove_object(key).await;
+                self.remove_expired_object(key).await;
             }
         }
 
         None
     }
 
+    /// # Desc:
+    ///
+    /// 在读锁下判断对象是否满足条件，不计入keyspace统计。用于只在满足条件时才修改对象的命令
+    /// (例如EXPIRE GT)，条件不满足时无需获取写锁。由于释放读锁后对象可能被修改，获取写锁后
+    /// 需要重新判断条件
+    ///
+    /// # Return:
+    ///
+    /// 对象不存在，对象为空或者对象已过期时返回false
+    pub async fn check_object(&self, key: &Key, cond: impl FnOnce(&ObjectInner) -> bool) -> bool {
+        self.get_object_entry(key)
+            .await
+            .is_some_and(|e| cond(e.inner_unchecked()))
+    }
+
+    /// 对象存在且类型不是typ时返回类型错误，对象不存在时返回Ok。需要修改多个对象的命令
+    /// 可以在修改其它对象前检查目标对象的类型，避免只完成了一部分修改
+    pub async fn check_object_type(&self, key: &Key, typ: ObjValueType) -> CmdResult<()> {
+        if let Some(e) = self.get_object_entry(key).await {
+            let inner = e.inner_unchecked();
+            if inner.typ() != typ {
+                return Err(DbError::TypeErr {
+                    expected: typ.as_str(),
+                    found: inner.type_str(),
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
     #[inline]
     #[instrument(level = "debug", skip(self))]
-    pub async fn get_object_entry_mut(&self, key: Key) -> ObjectEntryMut {
+    pub async fn get_object_entry_mut(&self, key: Key) -> ObjectEntryMut<'_> {
         Object::trigger_lock_event(self, key).await
     }
 
@@ -195,6 +624,7 @@ impl Db {
         } else {
             // 对象不存在
             error!("object not found");
+            self.record_keyspace_lookup(false);
             return Err(DbError::KeyNotFound.into());
         };
 
@@ -203,6 +633,7 @@ impl Db {
         } else {
             // 对象为空对象
             error!("object is None");
+            self.record_keyspace_lookup(false);
             return Err(DbError::KeyNotFound.into());
         };
 
@@ -210,16 +641,207 @@ impl Db {
             // 对象已过期，移除该键值对
             error!("object is expired");
             drop(entry);
-            self.remove_object(key).await;
+            self.remove_expired_object(key).await;
+            self.record_keyspace_lookup(false);
             return Err(DbError::KeyNotFound.into());
         }
 
         // 对象合法，可以进行访问
+        self.record_keyspace_lookup(true);
         f(obj_inner)
     }
 
-    pub async fn insert_object(&self, key: Key, object: ObjectInner) -> Option<ObjectInner> {
-        self.get_object_entry_mut(key).await.insert_object(object).1
+    /// # Desc:
+    ///
+    /// 批量访问多个对象。键按照shard分组，每个shard只加一次读锁，以减少MGET等多键命令
+    /// 的加锁次数。已过期的对象会在访问结束后被移除
+    ///
+    /// # Return:
+    ///
+    /// 按照keys的原始顺序返回每个键的访问结果，对象不存在，对象为空或者对象已过期时为None
+    #[instrument(level = "debug", skip(self, f))]
+    pub async fn multi_get<T>(
+        &self,
+        keys: &[Key],
+        mut f: impl FnMut(&ObjectInner) -> T,
+    ) -> Vec<Option<T>> {
+        let mut res: Vec<Option<T>> = keys.iter().map(|_| None).collect();
+
+        // (shard, hash, 键在keys中的位置)
+        let mut groups: Vec<(usize, u64, usize)> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let hash = self.entries.hash_usize(key);
+                (self.entries.determine_shard(hash), hash as u64, i)
+            })
+            .collect();
+        groups.sort_unstable_by_key(|(shard, ..)| *shard);
+
+        let mut expired = Vec::new();
+        for group in groups.chunk_by(|a, b| a.0 == b.0) {
+            let shard = self.entries.shards()[group[0].0].read();
+
+            for &(_, hash, i) in group {
+                let Some((_, obj)) = shard.get(hash, |(k, _)| k == &keys[i]) else {
+                    continue;
+                };
+
+                if let Some(inner) = obj.get().inner() {
+                    if inner.is_expired() {
+                        expired.push(i);
+                    } else {
+                        res[i] = Some(f(inner));
+                    }
+                }
+            }
+        }
+
+        let hits = res.iter().filter(|r| r.is_some()).count() as u64;
+        self.keyspace_hits.fetch_add(hits, Ordering::Relaxed);
+        self.keyspace_misses
+            .fetch_add(keys.len() as u64 - hits, Ordering::Relaxed);
+
+        // 对象已过期，移除该键值对
+        if self.is_replica_mode() {
+            return res;
+        }
+        for i in expired {
+            let entry = self.get_object_entry_mut(keys[i].clone()).await;
+            if entry.is_object_expired() {
+                if let Some((_, obj)) = entry.remove_object() {
+                    self.lazy_free.free(obj, ExpireReason::Expired);
+                    self.record_expired_key(false);
+                }
+            }
+        }
+
+        res
+    }
+
+    pub async fn insert_object(&self, key: Key, object: ObjectInner) {
+        self.get_object_entry_mut(key).await.insert_object(object);
+        self.deliver_expire_events();
+    }
+
+    /// # Desc:
+    ///
+    /// 当且仅当所有键都不存在时插入所有对象，用于MSETNX等需要原子地写入多个键的命令。
+    /// 涉及的shard按顺序一次性加写锁，检查与写入都在持有写锁期间完成，因此其它客户端
+    /// 不会观察到只写入了一部分的结果。objects中的键不能重复
+    ///
+    /// # Return:
+    ///
+    /// 所有对象都已插入时返回true，存在任一键时不做任何修改并返回false
+    #[instrument(level = "debug", skip(self, objects))]
+    pub async fn insert_objects_if_absent(&self, mut objects: Vec<(Key, ObjectInner)>) -> bool {
+        loop {
+            match self.try_insert_objects_if_absent(&mut objects) {
+                Ok(inserted) => {
+                    self.deliver_expire_events();
+                    return inserted;
+                }
+                // 键被其它任务加上了意向锁，等待意向锁释放后重试
+                Err(key) => drop(self.get_object_entry_mut(key).await),
+            }
+        }
+    }
+
+    /// 成功时objects被清空。如果有键被其它任务加上了意向锁，则不做任何修改并返回该键
+    fn try_insert_objects_if_absent(
+        &self,
+        objects: &mut Vec<(Key, ObjectInner)>,
+    ) -> Result<bool, Key> {
+        // (shard, hash, 键在objects中的位置)
+        let mut groups: Vec<(usize, u64, usize)> = objects
+            .iter()
+            .enumerate()
+            .map(|(i, (key, _))| {
+                let hash = self.entries.hash_usize(key);
+                (self.entries.determine_shard(hash), hash as u64, i)
+            })
+            .collect();
+        groups.sort_unstable_by_key(|(shard, ..)| *shard);
+
+        // 按shard的顺序加写锁。其它操作同一时间最多只持有一个shard的锁，因此不会死锁
+        let mut shards: Vec<_> = groups
+            .chunk_by(|a, b| a.0 == b.0)
+            .map(|group| (group, self.entries.shards()[group[0].0].write()))
+            .collect();
+
+        for (group, shard) in &shards {
+            for &(_, hash, i) in group.iter() {
+                let key = &objects[i].0;
+                let Some((_, obj)) = shard.get(hash, |(k, _)| k == key) else {
+                    continue;
+                };
+
+                let obj = obj.get();
+                if obj.is_locked_by_others() {
+                    return Err(key.clone());
+                }
+                if obj.is_valid() {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let mut objects: Vec<_> = objects.drain(..).map(Some).collect();
+        for (group, shard) in &mut shards {
+            for &(_, hash, i) in group.iter() {
+                let (key, object) = objects[i].take().expect("keys must be unique");
+                let new_ex = object.expire();
+
+                if let Some((_, obj)) = shard.get_mut(hash, |(k, _)| k == &key) {
+                    // 空对象或者已过期的对象
+                    let old_obj = std::mem::replace(obj.get_mut(), object.into());
+                    self.after_replace_object(&key, old_obj, new_ex);
+                } else {
+                    self.update_expire_records(&key, new_ex, None);
+                    self.incr_object_count();
+                    shard.insert(hash, (key, SharedValue::new(object.into())), |(k, _)| {
+                        self.entries.hash_usize(k) as u64
+                    });
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// 键的旧对象被新对象替换后调用。触发旧对象中的**MayUpdate**和**Track**事件，更新
+    /// 过期记录与对象数量，并释放旧对象
+    fn after_replace_object(&self, key: &Key, mut old_obj: Object, new_ex: Option<Instant>) {
+        old_obj.trigger_may_update_event(key);
+        old_obj.trigger_track_event(key);
+
+        if let Some(old_obj_inner) = old_obj.inner() {
+            // 旧对象已过期，相当于先移除了旧对象
+            if old_obj_inner.is_expired() {
+                self.propagate_expired_del(key);
+                self.trigger_expire_hook(key, ExpireReason::Expired);
+            }
+
+            // 旧对象为有效对象
+            self.update_expire_records(key, new_ex, old_obj_inner.expire());
+        } else {
+            // 旧对象中为空对象，则old_expire为None
+            self.update_expire_records(key, new_ex, None);
+            self.incr_object_count();
+        }
+        self.lazy_free.free_overwritten(old_obj);
+    }
+
+    /// 惰性删除已过期的对象，大对象可能在后台释放。replica模式下不删除
+    async fn remove_expired_object(&self, key: &Key) {
+        if self.is_replica_mode() {
+            return;
+        }
+
+        if let Some((_, obj)) = self.remove_object(key).await {
+            self.lazy_free.free(obj, ExpireReason::Expired);
+            self.record_expired_key(false);
+        }
     }
 
     /// # Desc:
@@ -259,13 +881,13 @@ impl Db {
 impl Db {
     // 获取该频道的所有监听者
     #[instrument(level = "debug", skip(self))]
-    pub fn get_channel_all_listener(&self, topic: &[u8]) -> Option<Vec<Sender<Resp3>>> {
+    pub fn get_channel_all_listener(&self, topic: &[u8]) -> Option<Vec<BgTaskSender>> {
         self.pub_sub.get(topic).map(|listener| listener.clone())
     }
 
     // 向频道添加一个监听者
     #[instrument(level = "debug", skip(self, listener))]
-    pub fn add_channel_listener(&self, topic: Key, listener: Sender<Resp3>) {
+    pub fn add_channel_listener(&self, topic: Key, listener: BgTaskSender) {
         self.pub_sub.entry(topic).or_default().push(listener);
     }
 
@@ -274,8 +896,8 @@ impl Db {
     pub fn remove_channel_listener(
         &self,
         topic: &[u8],
-        listener: &Sender<Resp3>,
-    ) -> Option<Sender<Resp3>> {
+        listener: &BgTaskSender,
+    ) -> Option<BgTaskSender> {
         if let Some(mut pubs) = self.pub_sub.get_mut(topic) {
             // 如果找到匹配的listener，则移除
             if let Some(index) = pubs.iter().position(|l| l.same_channel(listener)) {
@@ -290,25 +912,152 @@ impl Db {
         }
         None
     }
+
+    // 只保留频道中满足f的监听者，返回移除的数量。如果移除后该频道已经没有订阅者，则删除
+    // 该频道
+    #[instrument(level = "debug", skip(self, f))]
+    pub fn retain_channel_listeners(
+        &self,
+        topic: &[u8],
+        f: impl FnMut(&BgTaskSender) -> bool,
+    ) -> usize {
+        let Some(mut pubs) = self.pub_sub.get_mut(topic) else {
+            return 0;
+        };
+
+        let len = pubs.len();
+        pubs.retain(f);
+        let removed = len - pubs.len();
+        drop(pubs);
+
+        // 释放锁后可能有新的订阅者加入，因此只在频道仍然为空时删除
+        self.pub_sub.remove_if(topic, |_, pubs| pubs.is_empty());
+
+        removed
+    }
 }
 
-impl Default for Db {
-    fn default() -> Self {
+/// 键被移除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoStaticStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum ExpireReason {
+    Expired,
+    Evicted,
+    Deleted,
+}
+
+type ExpireHookFn = dyn Fn(&Key, ExpireReason) + Send + Sync;
+
+#[derive(Default)]
+struct ExpireHook {
+    hook: RwLock<Option<Arc<ExpireHookFn>>>,
+    observer: RwLock<Option<Arc<dyn Observer>>>,
+    // 尚未调用回调函数的事件
+    events: SegQueue<(Key, ExpireReason)>,
+}
+
+impl Debug for ExpireHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExpireHook")
+            .field("hook", &self.hook.read().is_some())
+            .field("observer", &self.observer.read().is_some())
+            .field("events", &self.events.len())
+            .finish()
+    }
+}
+
+impl Db {
+    /// shards为entries的分片数量，0表示根据CPU核数自动设置，否则向上取整为2的幂(至少为2)。
+    /// capacity为entries的初始容量
+    pub fn new(shards: usize, capacity: usize) -> Self {
+        let shards = if shards == 0 {
+            (std::thread::available_parallelism().map_or(1, usize::from) * 4).next_power_of_two()
+        } else {
+            shards.next_power_of_two().max(2)
+        };
+
         Self {
-            entries: DashMap::with_capacity_and_hasher(1024 * 16, RandomState::new()),
+            entries: DashMap::with_capacity_and_hasher_and_shard_amount(
+                capacity,
+                RandomState::new(),
+                shards,
+            ),
             entry_expire_records: DashSet::with_capacity_and_hasher(512, RandomState::new()),
+            object_count: AtomicUsize::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+            expired_keys_lazy: AtomicU64::new(0),
+            expired_keys_active: AtomicU64::new(0),
             pub_sub: DashMap::with_capacity_and_hasher(8, RandomState::new()),
             client_records: DashMap::with_capacity_and_hasher(1024, RandomState::new()),
+            client_addrs: DashMap::with_capacity_and_hasher(1024, RandomState::new()),
+            expire_hook: ExpireHook::default(),
+            wcmd_propagator: RwLock::new(None),
+            lazy_free: LazyFree::default(),
+            notify_encoding_change: AtomicBool::new(false),
+            replica_mode: AtomicBool::new(false),
         }
     }
 }
 
+impl Default for Db {
+    fn default() -> Self {
+        Self::new(0, 1024 * 16)
+    }
+}
+
 #[cfg(test)]
 pub mod db_tests {
     use crate::util::test_init;
 
     use super::*;
 
+    #[tokio::test]
+    async fn multi_get_test() {
+        test_init();
+
+        let db = Db::default();
+
+        let keys: Vec<Key> = (0..100).map(|i| Key::from(format!("key{i}"))).collect();
+        // 偶数键存在，奇数键不存在
+        for key in keys.iter().step_by(2) {
+            db.insert_object(key.clone(), ObjectInner::new_str(key.clone(), None))
+                .await;
+        }
+        db.insert_object(
+            Key::from("key_expired"),
+            ObjectInner::new_str(
+                "v",
+                Some(Instant::now() + std::time::Duration::from_millis(10)),
+            ),
+        )
+        .await;
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        let mut query = keys.clone();
+        query.push(Key::from("key_expired"));
+        query.push(keys[0].clone());
+
+        let res = db
+            .multi_get(&query, |obj| obj.on_str().unwrap().to_bytes())
+            .await;
+
+        // 结果按照原始顺序返回
+        assert_eq!(res.len(), query.len());
+        for (i, key) in keys.iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(res[i].as_ref(), Some(key));
+            } else {
+                assert_eq!(res[i], None);
+            }
+        }
+        assert_eq!(res[100], None);
+        assert_eq!(res[101].as_ref(), Some(&keys[0]));
+
+        // 已过期的对象被移除
+        assert!(db.entries().get(&Key::from("key_expired")).is_none());
+    }
+
     #[tokio::test]
     async fn insert_object_test() {
         test_init();
@@ -533,4 +1282,234 @@ pub mod db_tests {
         let event_res = rx.recv().unwrap();
         assert_eq!(event_res.as_ref(), b"key_none");
     }
+
+    #[tokio::test]
+    async fn null_object_test() {
+        test_init();
+
+        let db = Db::default();
+
+        // 监听不存在的键，这会创建一个空对象
+        let (tx, rx) = flume::unbounded();
+        db.add_may_update_event("key_null".into(), tx).await;
+
+        // 空对象不应该被视为存在的键
+        assert!(!db.contains_object(&"key_null".into()).await);
+        assert!(db.get_object_entry(&"key_null".into()).await.is_none());
+        assert_eq!(db.size(), 1);
+        assert_eq!(db.valid_size(), 0);
+
+        // 空对象的事件仍然能够被触发
+        db.insert_object("key_null".into(), ObjectInner::new_str("value", None))
+            .await;
+        assert_eq!(rx.recv().unwrap().as_ref(), b"key_null");
+        assert!(db.contains_object(&"key_null".into()).await);
+        assert_eq!(db.valid_size(), 1);
+    }
+
+    #[tokio::test]
+    async fn len_nonexpired_test() {
+        test_init();
+
+        let db = Db::default();
+        let expire = Some(Instant::now() + std::time::Duration::from_millis(10));
+
+        db.insert_object("key1".into(), ObjectInner::new_str("value", expire))
+            .await;
+        db.insert_object("key2".into(), ObjectInner::new_str("value", expire))
+            .await;
+        db.insert_object("key3".into(), ObjectInner::new_str("value", None))
+            .await;
+        // 覆盖已存在的对象不改变数量
+        db.insert_object("key3".into(), ObjectInner::new_str("value", None))
+            .await;
+        // 空对象不计入
+        let (tx, _rx) = flume::unbounded();
+        db.add_may_update_event("key_null".into(), tx).await;
+        assert_eq!(db.len_nonexpired(), 3);
+        assert_eq!(db.valid_size(), 3);
+
+        // 对象过期但尚未被移除
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(db.size(), 4);
+        assert_eq!(db.len_nonexpired(), 1);
+        assert_eq!(db.valid_size(), 1);
+
+        // 写入已过期的对象
+        db.insert_object(
+            "key4".into(),
+            ObjectInner::new_str("value", Some(Instant::now())),
+        )
+        .await;
+        assert_eq!(db.len_nonexpired(), 1);
+
+        // 空对象变为有效对象
+        db.insert_object("key_null".into(), ObjectInner::new_str("value", None))
+            .await;
+        assert_eq!(db.len_nonexpired(), 2);
+
+        assert_eq!(db.remove_expired_objects(Instant::now()).await, 3);
+        assert_eq!(db.len_nonexpired(), 2);
+
+        db.remove_object(&"key3".into()).await;
+        assert_eq!(db.len_nonexpired(), 1);
+        assert_eq!(db.valid_size(), 1);
+    }
+
+    #[tokio::test]
+    async fn expire_hook_test() {
+        test_init();
+
+        let db = Arc::new(Db::default());
+
+        // 回调函数在释放锁之后调用，因此可以访问Db
+        let (tx, rx) = flume::unbounded();
+        let weak_db = Arc::downgrade(&db);
+        db.set_expire_hook(move |key, reason| {
+            // 如果在持有锁时调用，则会死锁
+            weak_db.upgrade().unwrap().entries().contains_key(key);
+            tx.send((key.clone(), reason)).unwrap();
+        });
+
+        db.insert_object(
+            "key_expire".into(),
+            ObjectInner::new_str(
+                "value",
+                Some(Instant::now() + std::time::Duration::from_millis(10)),
+            ),
+        )
+        .await;
+        db.insert_object("key_del".into(), ObjectInner::new_str("value", None))
+            .await;
+        assert!(rx.is_empty());
+
+        // 键过期后被访问，触发expired回调
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(db.get_object_entry(&"key_expire".into()).await.is_none());
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ("key_expire".into(), ExpireReason::Expired)
+        );
+
+        // 主动删除键，触发deleted回调
+        db.remove_object(&"key_del".into()).await;
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ("key_del".into(), ExpireReason::Deleted)
+        );
+        assert_eq!(<&str>::from(ExpireReason::Deleted), "deleted");
+
+        // 覆盖已过期的键，触发expired回调
+        db.insert_object(
+            "key_expire".into(),
+            ObjectInner::new_str("value", Some(Instant::now())),
+        )
+        .await;
+        db.insert_object("key_expire".into(), ObjectInner::new_str("value", None))
+            .await;
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ("key_expire".into(), ExpireReason::Expired)
+        );
+        db.remove_object(&"key_expire".into()).await;
+        rx.try_recv().unwrap();
+
+        db.remove_expire_hook();
+        db.insert_object("key_del".into(), ObjectInner::new_str("value", None))
+            .await;
+        db.remove_object(&"key_del".into()).await;
+        assert!(rx.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_keys_stats_test() {
+        test_init();
+
+        let db = Db::default();
+
+        let expire = Instant::now() + std::time::Duration::from_millis(10);
+        for key in ["key1", "key2", "key3"] {
+            db.insert_object(key.into(), ObjectInner::new_str("value", Some(expire)))
+                .await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        // 访问过期键时惰性删除
+        assert!(db.get_object_entry(&"key1".into()).await.is_none());
+        assert!(!db.contains_object(&"key2".into()).await);
+        assert_eq!((db.expired_keys_lazy(), db.expired_keys_active()), (2, 0));
+
+        // 已经被移除的键不再计数
+        assert!(db.get_object_entry(&"key1".into()).await.is_none());
+        assert_eq!((db.expired_keys_lazy(), db.expired_keys_active()), (2, 0));
+
+        // 定期删除移除剩余的过期键
+        assert_eq!(db.remove_expired_objects(Instant::now()).await, 1);
+        assert_eq!((db.expired_keys_lazy(), db.expired_keys_active()), (2, 1));
+
+        db.reset_stats();
+        assert_eq!((db.expired_keys_lazy(), db.expired_keys_active()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn remove_expired_objects_test() {
+        test_init();
+
+        let db = Db::default();
+
+        db.insert_object(
+            "key_expire".into(),
+            ObjectInner::new_str(
+                "value",
+                Some(Instant::now() + std::time::Duration::from_millis(10)),
+            ),
+        )
+        .await;
+        db.insert_object(
+            "key_never_expire".into(),
+            ObjectInner::new_str("value", None),
+        )
+        .await;
+        assert_eq!(db.entry_expire_records().len(), 1);
+
+        // 过期记录与对象不一致时，不会移除未过期的对象
+        let stale_record = (Instant::now(), Key::from("key_never_expire"));
+        db.entry_expire_records().insert(stale_record);
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let removed = db.remove_expired_objects(Instant::now()).await;
+        assert_eq!(removed, 1);
+        assert!(db.entry_expire_records().is_empty());
+        assert!(!db.contains_object(&"key_expire".into()).await);
+        assert!(db.contains_object(&"key_never_expire".into()).await);
+    }
+
+    #[tokio::test]
+    async fn custom_shards_test() {
+        test_init();
+
+        // 分片数量会被向上取整为2的幂
+        for shards in [1, 3, 64] {
+            let db = Db::new(shards, 16);
+
+            for i in 0..100 {
+                db.insert_object(
+                    Key::from(format!("key{i}")),
+                    ObjectInner::new_str(i.to_string().as_str(), None),
+                )
+                .await;
+            }
+            assert_eq!(db.size(), 100);
+
+            let res = db
+                .get_object_entry(&"key42".into())
+                .await
+                .unwrap()
+                .on_str()
+                .unwrap()
+                .unwrap()
+                .to_bytes();
+            assert_eq!(res, "42".as_bytes());
+        }
+    }
 }
@@ -1,60 +1,107 @@
 use crate::Key;
 use ahash::AHashMap;
 use bytes::Bytes;
+use either::Either;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Hash {
     HashMap(AHashMap<Key, Bytes>),
-    ZipList,
+    // 元素较少且较小时使用紧凑的编码，节省内存
+    ZipList(Vec<(Key, Bytes)>),
 }
 
 impl Hash {
     pub fn len(&self) -> usize {
         match self {
             Hash::HashMap(map) => map.len(),
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => list.len(),
         }
     }
 
     pub fn is_empty(&self) -> bool {
         match self {
             Hash::HashMap(map) => map.is_empty(),
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => list.is_empty(),
         }
     }
 
     pub fn insert(&mut self, field: Key, value: Bytes) -> Option<Bytes> {
         match self {
             Hash::HashMap(map) => map.insert(field, value),
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => {
+                if let Some((_, v)) = list.iter_mut().find(|(f, _)| *f == field) {
+                    Some(std::mem::replace(v, value))
+                } else {
+                    list.push((field, value));
+                    None
+                }
+            }
         }
     }
 
     pub fn remove(&mut self, field: &Key) -> Option<Bytes> {
         match self {
             Hash::HashMap(map) => map.remove(field),
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => {
+                let i = list.iter().position(|(f, _)| f == field)?;
+                Some(list.remove(i).1)
+            }
         }
     }
 
     pub fn get(&self, field: &Key) -> Option<Bytes> {
         match self {
             Hash::HashMap(map) => map.get(field).cloned(),
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => list
+                .iter()
+                .find_map(|(f, v)| (f == field).then(|| v.clone())),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Bytes)> {
+        match self {
+            Hash::HashMap(map) => Either::Left(map.iter()),
+            Hash::ZipList(list) => Either::Right(list.iter().map(|(f, v)| (f, v))),
         }
     }
 
     pub fn contains_key(&self, field: &Key) -> bool {
         match self {
             Hash::HashMap(map) => map.contains_key(field),
-            Hash::ZipList => unimplemented!(),
+            Hash::ZipList(list) => list.iter().any(|(f, _)| f == field),
+        }
+    }
+
+    /// 当元素数量超过max_entries，或者有field或value的长度超过max_value时，将ZipList转换
+    /// 为HashMap。转换是单向的
+    pub fn convert_if_exceed(&mut self, max_entries: usize, max_value: usize) {
+        if let Hash::ZipList(list) = self {
+            if list.len() > max_entries
+                || list
+                    .iter()
+                    .any(|(f, v)| f.len() > max_value || v.len() > max_value)
+            {
+                self.convert_to_hash_map();
+            }
+        }
+    }
+
+    pub fn convert_to_hash_map(&mut self) {
+        if let Hash::ZipList(list) = self {
+            *self = Hash::HashMap(std::mem::take(list).into_iter().collect());
+        }
+    }
+
+    pub fn convert_to_zip_list(&mut self) {
+        if let Hash::HashMap(map) = self {
+            *self = Hash::ZipList(std::mem::take(map).into_iter().collect());
         }
     }
 }
 
 impl Default for Hash {
     fn default() -> Self {
-        Self::HashMap(AHashMap::default())
+        Self::ZipList(Vec::new())
     }
 }
 
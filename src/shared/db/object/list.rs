@@ -95,6 +95,54 @@ impl List {
             List::ZipList => unimplemented!(),
         }
     }
+
+    /// OBJECT ENCODING的返回值。元素数量不超过max_entries且所有元素的长度都小于
+    /// packed_threshold时为listpack，否则为quicklist(过大的元素会单独成为一个节点)
+    pub fn encoding(&self, max_entries: usize, packed_threshold: usize) -> &'static str {
+        match self {
+            List::LinkedList(list) => {
+                if list.len() <= max_entries && list.iter().all(|e| e.len() < packed_threshold) {
+                    "listpack"
+                } else {
+                    "quicklist"
+                }
+            }
+            List::ZipList => unimplemented!(),
+        }
+    }
+
+    /// 使用quicklist编码时的节点数量。每个节点最多包含max_entries个元素且不超过8KB，长度
+    /// 达到packed_threshold的元素单独成为一个节点
+    pub fn ql_nodes(&self, max_entries: usize, packed_threshold: usize) -> usize {
+        const NODE_MAX_BYTES: usize = 8 * 1024;
+
+        match self {
+            List::LinkedList(list) => {
+                let (mut nodes, mut entries, mut bytes) = (0, 0, 0);
+                for elem in list {
+                    if elem.len() >= packed_threshold {
+                        nodes += 1;
+                        // 之后的元素需要放入新的节点
+                        entries = 0;
+                        bytes = 0;
+                        continue;
+                    }
+
+                    if entries == 0 || entries >= max_entries || bytes + elem.len() > NODE_MAX_BYTES
+                    {
+                        nodes += 1;
+                        entries = 0;
+                        bytes = 0;
+                    }
+                    entries += 1;
+                    bytes += elem.len();
+                }
+
+                nodes
+            }
+            List::ZipList => unimplemented!(),
+        }
+    }
 }
 
 impl<'a> Iterator for &'a List {
@@ -2,6 +2,7 @@ mod hash;
 mod list;
 mod set;
 mod str;
+mod stream;
 mod zset;
 
 pub use hash::*;
@@ -9,6 +10,7 @@ pub use list::*;
 pub use set::*;
 use smallvec::SmallVec;
 pub use str::*;
+pub use stream::*;
 pub use zset::*;
 
 use crate::{
@@ -18,7 +20,8 @@ use crate::{
         object_entry::{IntentionLock, ObjectEntryMut},
         Db, DbError,
     },
-    Id, Key,
+    util::get_lru_clock,
+    Id, Int, Key,
 };
 use bytes::Bytes;
 use dashmap::mapref::entry::Entry;
@@ -58,6 +61,12 @@ impl Object {
         self.inner.as_ref()
     }
 
+    /// 对象不为空且未过期。空对象只用于存储事件，对用户而言该键不存在
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.inner.as_ref().is_some_and(|inner| !inner.is_expired())
+    }
+
     #[inline]
     pub fn inner_mut(&mut self) -> Option<&mut ObjectInner> {
         self.inner.as_mut()
@@ -98,6 +107,10 @@ impl Object {
         Object::new(ObjectInner::new_zset(z, expire))
     }
 
+    pub fn new_stream(s: Stream, expire: Option<Instant>) -> Self {
+        Object::new(ObjectInner::new_stream(s, expire))
+    }
+
     pub fn new_null() -> Self {
         Object {
             inner: None,
@@ -190,6 +203,23 @@ impl Object {
         }
     }
 
+    pub fn on_stream(&self) -> Option<Result<&Stream, DbError>> {
+        let inner = if let Some(inner) = &self.inner {
+            inner
+        } else {
+            return None;
+        };
+
+        if let ObjValue::Stream(s) = &inner.value {
+            Some(Ok(s))
+        } else {
+            Some(Err(DbError::TypeErr {
+                expected: "stream",
+                found: inner.type_str(),
+            }))
+        }
+    }
+
     pub fn on_str_mut(&mut self) -> Option<Result<&mut Str, DbError>> {
         let inner = if let Some(inner) = &mut self.inner {
             inner
@@ -280,6 +310,24 @@ impl Object {
         }
     }
 
+    pub fn on_stream_mut(&mut self) -> Option<Result<&mut Stream, DbError>> {
+        let inner = if let Some(inner) = &mut self.inner {
+            inner
+        } else {
+            return None;
+        };
+
+        let typ = inner.type_str();
+        if let ObjValue::Stream(s) = &mut inner.value {
+            Some(Ok(s))
+        } else {
+            Some(Err(DbError::TypeErr {
+                expected: "stream",
+                found: typ,
+            }))
+        }
+    }
+
     #[inline]
     pub fn set_flag(&mut self, flag: u8) {
         self.events.flags |= flag;
@@ -294,6 +342,31 @@ impl Object {
         }
     }
 
+    /// 对象被其它任务加上了意向锁
+    pub(super) fn is_locked_by_others(&self) -> bool {
+        self.events.contains(INTENTION_LOCK_FLAG)
+            && self.events.inner.iter().any(
+                |e| matches!(e, Event::IntentionLock { target_id, .. } if *target_id != ID.get()),
+            )
+    }
+
+    /// 移除没有任务在等待的意向锁事件。有任务在等待时，由最后一个等待的任务负责移除
+    pub(super) fn remove_idle_lock_event(&mut self) {
+        if !self.events.contains(INTENTION_LOCK_FLAG) {
+            return;
+        }
+
+        if let Some(i) = self
+            .events
+            .inner
+            .iter()
+            .position(|e| matches!(e, Event::IntentionLock { count: 0, .. }))
+        {
+            self.remove_event(i, INTENTION_LOCK_FLAG);
+            self.remove_flag(INTENTION_LOCK_FLAG);
+        }
+    }
+
     pub(super) fn add_lock_event(&mut self, target_id: Id) -> IntentionLock {
         let id = target_id;
         if self.events.contains(INTENTION_LOCK_FLAG) {
@@ -332,6 +405,31 @@ impl Object {
         self.events.inner.push(event);
     }
 
+    /// 移除通过sender发送的MayUpdate事件
+    pub(super) fn remove_may_update_event(&mut self, sender: &Sender<Bytes>) {
+        if !self.events.contains(MAY_UPDATE_FLAG) {
+            return;
+        }
+
+        self.events
+            .inner
+            .retain(|e| !matches!(e, Event::MayUpdate(s) if s.same_channel(sender)));
+        if !self
+            .events
+            .inner
+            .iter()
+            .any(|e| matches!(e, Event::MayUpdate(_)))
+        {
+            self.remove_flag(MAY_UPDATE_FLAG);
+        }
+    }
+
+    /// 对象为空且没有任何事件，此时键值对已没有存在的意义
+    #[inline]
+    pub(super) fn is_unused(&self) -> bool {
+        self.inner.is_none() && self.events.flags == 0
+    }
+
     #[inline]
     pub(super) fn add_track_event(&mut self, sender: Sender<Resp3>) {
         let event = Event::Track(sender);
@@ -349,7 +447,7 @@ impl Object {
     }
 
     #[instrument(level = "debug", skip(db))]
-    pub(super) async fn trigger_lock_event(db: &Db, key: Key) -> ObjectEntryMut {
+    pub(super) async fn trigger_lock_event(db: &Db, key: Key) -> ObjectEntryMut<'_> {
         let mut entry = db.entries.entry(key);
 
         match &mut entry {
@@ -360,7 +458,7 @@ impl Object {
                 }
 
                 let events = &mut e.get_mut().events;
-                for (i, e) in events.inner.iter_mut().enumerate() {
+                for e in events.inner.iter_mut() {
                     if let Event::IntentionLock {
                         target_id,
                         intention_lock,
@@ -384,24 +482,16 @@ impl Object {
                             // 重新获取写锁
                             let mut new_entry = db.entries.entry(key.clone());
 
-                            // 如果当前任务是最后一个获取写锁的任务，则由该任务负责移除IntentionLock事件
+                            // 如果当前任务是最后一个获取写锁的任务，则由该任务负责移除IntentionLock事件。
+                            // 等待期间其它事件可能已被移除，因此需要重新查找IntentionLock事件的位置
                             if let Entry::Occupied(e) = &mut new_entry {
                                 let obj = e.get_mut();
 
-                                if let Event::IntentionLock { count, .. } = obj
-                                    .events
-                                    .inner
-                                    .get_mut(i)
-                                    .expect("must exist since no one removed it")
-                                {
-                                    if seq == *count {
-                                        obj.remove_event(i, INTENTION_LOCK_FLAG);
-                                        obj.remove_flag(INTENTION_LOCK_FLAG);
-                                    }
-                                } else {
-                                    unreachable!(
-                                        "get the same result that must be IntentionLock event"
-                                    )
+                                if let Some(i) = obj.events.inner.iter().position(|e| {
+                                    matches!(e, Event::IntentionLock { count, .. } if *count == seq)
+                                }) {
+                                    obj.remove_event(i, INTENTION_LOCK_FLAG);
+                                    obj.remove_flag(INTENTION_LOCK_FLAG);
                                 }
                             }
 
@@ -482,11 +572,14 @@ impl From<ObjectInner> for Object {
     }
 }
 
+// 共享对象的引用计数，与Redis的OBJ_SHARED_REFCOUNT一致
+pub const SHARED_REFCOUNT: Int = i32::MAX as Int;
+
 pub const INTENTION_LOCK_FLAG: u8 = 1 << 0;
 pub const TRACK_FLAG: u8 = 1 << 1;
 pub const MAY_UPDATE_FLAG: u8 = 1 << 2;
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct Events {
     inner: SmallVec<[Event; 8]>,
     // 事件类型标志位，用于快速判断是否包含某种事件
@@ -504,6 +597,12 @@ impl Events {
     }
 }
 
+impl Default for Events {
+    fn default() -> Self {
+        Self::new(SmallVec::new())
+    }
+}
+
 #[derive(EnumDiscriminants, EnumProperty)]
 #[strum_discriminants(vis(pub))]
 #[strum_discriminants(name(EventType))]
@@ -557,19 +656,84 @@ impl Event {
     }
 }
 
+/// 对象的访问信息(access time and count)，用于LRU/LFU淘汰
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Atc {
+    // 最近一次访问时的LRU时钟
+    access_time: u32,
+    // 访问频率
+    access_count: u32,
+}
+
+impl Default for Atc {
+    fn default() -> Self {
+        Atc {
+            access_time: get_lru_clock(),
+            access_count: 0,
+        }
+    }
+}
+
+impl Atc {
+    /// 以空闲时间(秒)构造，访问时间为当前LRU时钟减去空闲时间
+    #[inline]
+    pub fn with_idle_time(idle_secs: u32) -> Self {
+        Atc {
+            access_time: get_lru_clock().saturating_sub(idle_secs),
+            access_count: 0,
+        }
+    }
+
+    /// 以访问频率构造，访问时间为当前LRU时钟
+    #[inline]
+    pub fn with_access_count(access_count: u32) -> Self {
+        Atc {
+            access_time: get_lru_clock(),
+            access_count,
+        }
+    }
+
+    #[inline]
+    pub fn access_time(&self) -> u32 {
+        self.access_time
+    }
+
+    #[inline]
+    pub fn access_count(&self) -> u32 {
+        self.access_count
+    }
+
+    /// 距离最近一次访问的秒数
+    #[inline]
+    pub fn idle_time(&self) -> u32 {
+        get_lru_clock().saturating_sub(self.access_time)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ObjectInner {
     value: ObjValue,
     // TODO: 优化内存占用
     expire: Option<Instant>, // None代表永不过期
+    atc: Atc,
 }
 
 impl ObjectInner {
+    #[inline]
+    pub fn new(value: ObjValue, expire: Option<Instant>) -> Self {
+        ObjectInner {
+            value,
+            expire,
+            atc: Atc::default(),
+        }
+    }
+
     #[inline]
     pub fn new_str(s: impl Into<Str>, expire: Option<Instant>) -> Self {
         ObjectInner {
             value: ObjValue::Str(s.into()),
             expire,
+            atc: Atc::default(),
         }
     }
 
@@ -578,6 +742,7 @@ impl ObjectInner {
         ObjectInner {
             value: ObjValue::List(l.into()),
             expire,
+            atc: Atc::default(),
         }
     }
 
@@ -586,6 +751,7 @@ impl ObjectInner {
         ObjectInner {
             value: ObjValue::Set(s.into()),
             expire,
+            atc: Atc::default(),
         }
     }
 
@@ -594,6 +760,7 @@ impl ObjectInner {
         ObjectInner {
             value: ObjValue::Hash(h.into()),
             expire,
+            atc: Atc::default(),
         }
     }
 
@@ -602,6 +769,16 @@ impl ObjectInner {
         ObjectInner {
             value: ObjValue::ZSet(z.into()),
             expire,
+            atc: Atc::default(),
+        }
+    }
+
+    #[inline]
+    pub fn new_stream(s: impl Into<Stream>, expire: Option<Instant>) -> Self {
+        ObjectInner {
+            value: ObjValue::Stream(s.into()),
+            expire,
+            atc: Atc::default(),
         }
     }
 
@@ -622,16 +799,82 @@ impl ObjectInner {
             ObjValue::Set(_) => ObjValueType::Set,
             ObjValue::Hash(_) => ObjValueType::Hash,
             ObjValue::ZSet(_) => ObjValueType::ZSet,
+            ObjValue::Stream(_) => ObjValueType::Stream,
         }
     }
 
     pub fn type_str(&self) -> &'static str {
+        self.typ().as_str()
+    }
+
+    /// OBJECT ENCODING的返回值
+    pub fn encoding(&self) -> &'static str {
+        match &self.value {
+            ObjValue::Str(s) => s.encoding(),
+            ObjValue::List(List::LinkedList(_)) => "quicklist",
+            ObjValue::List(List::ZipList) => "ziplist",
+            ObjValue::Set(Set::HashSet(_)) => "hashtable",
+            ObjValue::Set(Set::IntSet(_)) => "intset",
+            ObjValue::Hash(Hash::HashMap(_)) => "hashtable",
+            ObjValue::Hash(Hash::ZipList(_)) => "ziplist",
+            ObjValue::ZSet(ZSet::SkipList(_)) => "skiplist",
+            ObjValue::ZSet(ZSet::ZipSet) => "ziplist",
+            ObjValue::Stream(_) => "stream",
+        }
+    }
+
+    /// 强制将对象转换为指定的编码(DEBUG OBJECT ENCODING-FORCE)，使测试可以确定地覆盖同一
+    /// 类型的不同编码。只支持Hash和Set的编码转换，不支持的转换返回false。之后的写操作仍可能
+    /// 再次改变编码
+    pub fn force_encoding(&mut self, encoding: &[u8]) -> bool {
+        match (&mut self.value, encoding) {
+            (ObjValue::Hash(hash), b"hashtable") => hash.convert_to_hash_map(),
+            (ObjValue::Hash(hash), b"ziplist" | b"listpack") => hash.convert_to_zip_list(),
+            (ObjValue::Set(set), b"hashtable") => set.convert_to_hash_set(),
+            (ObjValue::Set(set), b"intset") => return set.convert_to_int_set(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// 释放对象的开销，近似为需要释放的内存块数量
+    pub fn free_effort(&self) -> usize {
+        match &self.value {
+            ObjValue::Str(_) => 1,
+            ObjValue::List(l) => l.len(),
+            // IntSet只包含一块连续的内存
+            ObjValue::Set(Set::IntSet(_)) => 1,
+            ObjValue::Set(s) => s.len(),
+            ObjValue::Hash(h) => h.len(),
+            ObjValue::ZSet(z) => z.len(),
+            ObjValue::Stream(s) => s.len(),
+        }
+    }
+
+    /// 对象值占用的字节数的估计值，只计算元素本身，不包含容器的开销
+    pub fn value_bytes(&self) -> usize {
         match &self.value {
-            ObjValue::Str(_) => "string",
-            ObjValue::List(_) => "list",
-            ObjValue::Set(_) => "set",
-            ObjValue::Hash(_) => "hash",
-            ObjValue::ZSet(_) => "zset",
+            ObjValue::Str(s) => s.len(),
+            ObjValue::List(List::LinkedList(l)) => l.iter().map(Bytes::len).sum(),
+            ObjValue::List(List::ZipList) => unimplemented!(),
+            ObjValue::Set(Set::IntSet(s)) => s.len() * size_of::<i64>(),
+            ObjValue::Set(s) => s.iter().map(|e| e.len()).sum(),
+            ObjValue::Hash(h) => h.iter().map(|(f, v)| f.len() + v.len()).sum(),
+            ObjValue::ZSet(z) => z.iter().map(|e| e.1.len() + size_of::<f64>()).sum(),
+            ObjValue::Stream(s) => s
+                .range(..)
+                .map(|(id, fields)| {
+                    size_of_val(id) + fields.iter().map(|(f, v)| f.len() + v.len()).sum::<usize>()
+                })
+                .sum(),
+        }
+    }
+
+    /// OBJECT REFCOUNT的返回值。共享整数返回SHARED_REFCOUNT，其余对象都由键独占
+    pub fn refcount(&self) -> Int {
+        match &self.value {
+            ObjValue::Str(s) if s.is_shared_int() => SHARED_REFCOUNT,
+            _ => 1,
         }
     }
 
@@ -645,6 +888,16 @@ impl ObjectInner {
         self.expire
     }
 
+    #[inline]
+    pub fn atc(&self) -> Atc {
+        self.atc
+    }
+
+    #[inline]
+    pub fn set_atc(&mut self, atc: Atc) {
+        self.atc = atc;
+    }
+
     pub fn set_expire(&mut self, new_ex: Option<Instant>) -> Result<Option<Instant>, &'static str> {
         if let Some(ex) = new_ex {
             if ex <= Instant::now() {
@@ -709,6 +962,17 @@ impl ObjectInner {
         }
     }
 
+    pub fn on_stream(&self) -> Result<&Stream, DbError> {
+        if let ObjValue::Stream(s) = &self.value {
+            Ok(s)
+        } else {
+            Err(DbError::TypeErr {
+                expected: "stream",
+                found: self.type_str(),
+            })
+        }
+    }
+
     pub fn on_str_mut(&mut self) -> Result<&mut Str, DbError> {
         let typ = self.type_str();
         if let ObjValue::Str(s) = &mut self.value {
@@ -768,6 +1032,18 @@ impl ObjectInner {
             })
         }
     }
+
+    pub fn on_stream_mut(&mut self) -> Result<&mut Stream, DbError> {
+        let typ = self.type_str();
+        if let ObjValue::Stream(s) = &mut self.value {
+            Ok(s)
+        } else {
+            Err(DbError::TypeErr {
+                expected: "stream",
+                found: typ,
+            })
+        }
+    }
 }
 
 impl PartialEq for ObjectInner {
@@ -796,6 +1072,21 @@ pub enum ObjValue {
     Set(Set),
     Hash(Hash),
     ZSet(ZSet),
+    Stream(Stream),
+}
+
+impl ObjValueType {
+    /// TYPE命令的返回值
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjValueType::Str => "string",
+            ObjValueType::List => "list",
+            ObjValueType::Set => "set",
+            ObjValueType::Hash => "hash",
+            ObjValueType::ZSet => "zset",
+            ObjValueType::Stream => "stream",
+        }
+    }
 }
 
 impl From<Str> for ObjValue {
@@ -828,6 +1119,12 @@ impl From<ZSet> for ObjValue {
     }
 }
 
+impl From<Stream> for ObjValue {
+    fn from(s: Stream) -> Self {
+        Self::Stream(s)
+    }
+}
+
 #[cfg(test)]
 mod object_tests {
     use super::*;
@@ -1,52 +1,115 @@
+use crate::util::atoi;
 use ahash::AHashSet;
 use bytes::Bytes;
+use either::Either;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Set {
     HashSet(AHashSet<Bytes>),
-    IntSet,
+    // 元素均为整数且数量较少时使用有序数组存储，节省内存并通过二分查找判断成员
+    IntSet(Vec<i64>),
 }
 
 impl Set {
     pub fn len(&self) -> usize {
         match self {
             Set::HashSet(set) => set.len(),
-            Set::IntSet => unimplemented!(),
+            Set::IntSet(set) => set.len(),
         }
     }
 
     pub fn is_empty(&self) -> bool {
         match self {
             Set::HashSet(set) => set.is_empty(),
-            Set::IntSet => unimplemented!(),
+            Set::IntSet(set) => set.is_empty(),
         }
     }
 
+    /// 插入非整数元素时，IntSet会被转换为HashSet
     pub fn insert(&mut self, elem: Bytes) -> bool {
         match self {
             Set::HashSet(set) => set.insert(elem),
-            Set::IntSet => unimplemented!(),
+            Set::IntSet(set) => match to_int(&elem) {
+                Some(i) => match set.binary_search(&i) {
+                    Ok(_) => false,
+                    Err(pos) => {
+                        set.insert(pos, i);
+                        true
+                    }
+                },
+                None => {
+                    self.convert_to_hash_set();
+                    self.insert(elem)
+                }
+            },
         }
     }
 
     pub fn remove(&mut self, elem: &Bytes) -> bool {
         match self {
             Set::HashSet(set) => set.remove(elem),
-            Set::IntSet => unimplemented!(),
+            Set::IntSet(set) => match to_int(elem).map(|i| set.binary_search(&i)) {
+                Some(Ok(pos)) => {
+                    set.remove(pos);
+                    true
+                }
+                _ => false,
+            },
         }
     }
 
     pub fn contains(&self, elem: &Bytes) -> bool {
         match self {
             Set::HashSet(set) => set.contains(elem),
-            Set::IntSet => unimplemented!(),
+            Set::IntSet(set) => to_int(elem).is_some_and(|i| set.binary_search(&i).is_ok()),
         }
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = Bytes> + '_ {
+        match self {
+            Set::HashSet(set) => Either::Left(set.iter().cloned()),
+            Set::IntSet(set) => Either::Right(
+                set.iter()
+                    .map(|i| Bytes::copy_from_slice(itoa::Buffer::new().format(*i).as_bytes())),
+            ),
+        }
+    }
+
+    /// 当元素数量超过max_entries时，将IntSet转换为HashSet。转换是单向的
+    pub fn convert_if_exceed(&mut self, max_entries: usize) {
+        if matches!(self, Set::IntSet(set) if set.len() > max_entries) {
+            self.convert_to_hash_set();
+        }
+    }
+
+    pub fn convert_to_hash_set(&mut self) {
+        if let Set::IntSet(_) = self {
+            *self = Set::HashSet(self.iter().collect());
+        }
+    }
+
+    /// 只有所有元素都是规范形式的整数时才能转换为IntSet，否则返回false
+    pub fn convert_to_int_set(&mut self) -> bool {
+        if let Set::HashSet(set) = self {
+            let Some(mut ints) = set.iter().map(|e| to_int(e)).collect::<Option<Vec<_>>>() else {
+                return false;
+            };
+            ints.sort_unstable();
+            *self = Set::IntSet(ints);
+        }
+        true
+    }
+}
+
+/// 只有规范形式的整数(如"1"而非"01"或"+1")才能存储在IntSet中，否则无法还原原来的元素
+fn to_int(elem: &[u8]) -> Option<i64> {
+    let i = atoi::<i64>(elem).ok()?;
+    (itoa::Buffer::new().format(i).as_bytes() == elem).then_some(i)
 }
 
 impl Default for Set {
     fn default() -> Self {
-        Self::HashSet(AHashSet::default())
+        Self::IntSet(Vec::new())
     }
 }
 
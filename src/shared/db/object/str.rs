@@ -2,6 +2,13 @@ use crate::{shared::db::DbError, util::to_valid_range, Int};
 use atoi::atoi;
 use bytes::{Bytes, BytesMut};
 
+// 不超过该长度的字符串在OBJECT ENCODING中报告为embstr，与Redis保持一致
+pub const EMBSTR_SIZE_LIMIT: usize = 44;
+
+// [0, SHARED_INTEGERS)范围内的整数在Redis中是共享对象。Str::Int本身内联存储，不需要额外分配
+// 内存，因此这里不真正共享，只在OBJECT REFCOUNT中报告为共享对象
+pub const SHARED_INTEGERS: Int = 10000;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Str {
     Raw(Bytes),
@@ -28,13 +35,22 @@ impl Str {
     }
 
     pub fn set(&mut self, other: Bytes) -> Str {
-        if let Some(i) = atoi::<Int>(&other) {
+        if let Some(i) = parse_int(&other) {
             std::mem::replace(self, Self::Int(i.into()))
         } else {
             std::mem::replace(self, Self::Raw(other))
         }
     }
 
+    /// OBJECT ENCODING的返回值。embstr与raw只是根据长度计算出的属性，实际存储方式相同
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Self::Raw(b) if b.len() <= EMBSTR_SIZE_LIMIT => "embstr",
+            Self::Raw(_) => "raw",
+            Self::Int(_) => "int",
+        }
+    }
+
     pub fn is_raw(&self) -> bool {
         matches!(self, Self::Raw(_))
     }
@@ -43,6 +59,11 @@ impl Str {
         matches!(self, Self::Int(_))
     }
 
+    /// 是否为共享整数，即值在[0, SHARED_INTEGERS)范围内的整数
+    pub fn is_shared_int(&self) -> bool {
+        matches!(self, Self::Int(i) if (0..SHARED_INTEGERS).contains(&i.get()))
+    }
+
     /// 获取字串，首个字符索引为1，末尾字符索引为-1
     pub fn get_range<'a>(&'a self, buffer: &'a mut itoa::Buffer, start: Int, end: Int) -> &'a [u8] {
         if let Some((start_index, end_index)) = to_valid_range(start, end, self.len()) {
@@ -118,12 +139,17 @@ impl Str {
 
     pub fn append(&mut self, other: Bytes) {
         match self {
-            Self::Raw(b) => b.to_vec().extend(other),
+            Self::Raw(b) => {
+                let mut raw = BytesMut::with_capacity(b.len() + other.len());
+                raw.extend_from_slice(b);
+                raw.extend_from_slice(&other);
+                *b = raw.freeze();
+            }
             Self::Int(i) => {
                 let mut raw = BytesMut::from(i.as_bytes(&mut itoa::Buffer::new()));
                 raw.extend(other);
                 // 尝试将新的Str解析为Int，如果解析成功则更新Int的值，否则变为Raw
-                if let Some(new_num) = atoi(&raw) {
+                if let Some(new_num) = parse_int(&raw) {
                     i.set(new_num);
                 } else {
                     *self = Self::Raw(raw.freeze());
@@ -133,9 +159,15 @@ impl Str {
     }
 }
 
+// 只有规范形式的整数(如"10"，而非"010"或"+10")才会被编码为Int，保证读出的字节与写入的一致
+fn parse_int(b: &[u8]) -> Option<Int> {
+    let i = atoi::<Int>(b)?;
+    (itoa::Buffer::new().format(i).as_bytes() == b).then_some(i)
+}
+
 impl From<Bytes> for Str {
     fn from(b: Bytes) -> Self {
-        if let Some(i) = atoi::<Int>(&b) {
+        if let Some(i) = parse_int(&b) {
             return Str::Int(i.into());
         }
         Self::Raw(b)
@@ -144,7 +176,7 @@ impl From<Bytes> for Str {
 
 impl From<&str> for Str {
     fn from(s: &str) -> Self {
-        if let Some(i) = atoi::<Int>(s.as_bytes()) {
+        if let Some(i) = parse_int(s.as_bytes()) {
             return Str::Int(i.into());
         }
         Self::Raw(Bytes::copy_from_slice(s.as_bytes()))
@@ -153,7 +185,7 @@ impl From<&str> for Str {
 
 impl From<&[u8]> for Str {
     fn from(b: &[u8]) -> Self {
-        if let Some(i) = atoi::<Int>(b) {
+        if let Some(i) = parse_int(b) {
             return Str::Int(i.into());
         }
         Self::Raw(Bytes::copy_from_slice(b))
@@ -256,13 +288,7 @@ impl IntType {
     }
 
     pub fn set(&mut self, i: Int) {
-        if i > i8::MIN as Int && i < i8::MAX as Int {
-            *self = Self::Int8(i as i8);
-        } else if i > i16::MIN as Int && i < i16::MAX as Int {
-            *self = Self::Int16(i as i16);
-        } else {
-            *self = Self::Int32(i as i32);
-        }
+        *self = i.into();
     }
 
     pub fn as_bytes<'a>(&self, buffer: &'a mut itoa::Buffer) -> &'a [u8] {
@@ -285,15 +311,7 @@ impl IntType {
 
         // 根据结果更新原值
         if let Some(res) = res {
-            if res < i8::MIN as Int {
-                *self = Self::Int8(res as i8);
-            } else if res < i16::MIN as Int {
-                *self = Self::Int16(res as i16);
-            } else if res < i32::MIN as Int {
-                *self = Self::Int32(res as i32);
-            } else {
-                *self = Self::Int64(res);
-            }
+            self.set(res);
 
             Ok(res)
         } else {
@@ -312,15 +330,7 @@ impl IntType {
 
         // 根据结果更新原值
         if let Some(res) = res {
-            if res > i8::MIN as Int {
-                *self = Self::Int8(res as i8);
-            } else if res > i16::MIN as Int {
-                *self = Self::Int16(res as i16);
-            } else if res > i32::MIN as Int {
-                *self = Self::Int32(res as i32);
-            } else {
-                *self = Self::Int64(res);
-            }
+            self.set(res);
 
             Ok(res)
         } else {
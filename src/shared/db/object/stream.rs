@@ -0,0 +1,325 @@
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    ops::{Bound, RangeBounds},
+};
+
+/// 流中条目的ID，格式为`<ms>-<seq>`，按照(ms, seq)排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    #[inline]
+    pub fn new(ms: u64, seq: u64) -> Self {
+        StreamId { ms, seq }
+    }
+
+    /// 紧随其后的ID，已经是最大ID时返回None
+    pub fn next(&self) -> Option<StreamId> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+
+    /// 紧挨在前的ID，已经是最小ID时返回None
+    pub fn prev(&self) -> Option<StreamId> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(StreamId::new(self.ms, seq)),
+            None => Some(StreamId::new(self.ms.checked_sub(1)?, u64::MAX)),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        self.to_string().into()
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+pub type StreamFields = Vec<(Bytes, Bytes)>;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stream {
+    entries: BTreeMap<StreamId, StreamFields>,
+    // 曾经添加过的最大ID，即使该条目已被删除，新条目的ID也必须大于它
+    last_id: StreamId,
+    groups: BTreeMap<Bytes, StreamGroup>,
+}
+
+impl Stream {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// 只允许增大last_id
+    pub fn set_last_id(&mut self, id: StreamId) {
+        self.last_id = self.last_id.max(id);
+    }
+
+    /// 根据当前时间生成新的ID。时钟回拨或同一毫秒内的ID在last_id的基础上递增序号
+    pub fn next_auto_id(&self, now_ms: u64) -> Option<StreamId> {
+        if now_ms > self.last_id.ms {
+            Some(StreamId::new(now_ms, 0))
+        } else {
+            self.last_id.next()
+        }
+    }
+
+    /// 以ms为毫秒部分生成新的ID(XADD的`<ms>-*`形式)
+    pub fn next_id_with_ms(&self, ms: u64) -> Option<StreamId> {
+        if ms > self.last_id.ms {
+            Some(StreamId::new(ms, 0))
+        } else if ms == self.last_id.ms {
+            self.last_id.next().filter(|id| id.ms == ms)
+        } else {
+            None
+        }
+    }
+
+    /// 添加条目，id必须大于last_id(因此也必然大于0-0)，否则返回false
+    pub fn insert(&mut self, id: StreamId, fields: StreamFields) -> bool {
+        if id <= self.last_id {
+            return false;
+        }
+
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        true
+    }
+
+    pub fn range(
+        &self,
+        range: impl RangeBounds<StreamId>,
+    ) -> impl DoubleEndedIterator<Item = (&StreamId, &StreamFields)> {
+        self.entries.range(range)
+    }
+
+    pub fn get(&self, id: &StreamId) -> Option<&StreamFields> {
+        self.entries.get(id)
+    }
+
+    pub fn first_id(&self) -> Option<StreamId> {
+        self.entries.keys().next().copied()
+    }
+
+    /// 从最旧的条目开始删除，直到长度不超过max_len或者删除数量达到limit。返回删除的数量
+    pub fn trim_max_len(&mut self, max_len: usize, limit: Option<usize>) -> usize {
+        let mut count = self.len().saturating_sub(max_len);
+        if let Some(limit) = limit {
+            count = count.min(limit);
+        }
+
+        for _ in 0..count {
+            self.entries.pop_first();
+        }
+        count
+    }
+
+    /// 从最旧的条目开始删除ID小于min_id的条目，直到删除数量达到limit。返回删除的数量
+    pub fn trim_min_id(&mut self, min_id: StreamId, limit: Option<usize>) -> usize {
+        let mut count = 0;
+        while let Some(entry) = self.entries.first_entry() {
+            if *entry.key() >= min_id || limit.is_some_and(|limit| count >= limit) {
+                break;
+            }
+            entry.remove();
+            count += 1;
+        }
+        count
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&Bytes, &StreamGroup)> {
+        self.groups.iter()
+    }
+
+    pub fn group(&self, name: &[u8]) -> Option<&StreamGroup> {
+        self.groups.get(name)
+    }
+
+    pub fn group_mut(&mut self, name: &[u8]) -> Option<&mut StreamGroup> {
+        self.groups.get_mut(name)
+    }
+
+    /// 创建消费者组，组已存在时返回false
+    pub fn create_group(&mut self, name: Bytes, last_delivered: StreamId) -> bool {
+        if self.groups.contains_key(&name) {
+            return false;
+        }
+
+        self.groups.insert(name, StreamGroup::new(last_delivered));
+        true
+    }
+
+    pub fn destroy_group(&mut self, name: &[u8]) -> bool {
+        self.groups.remove(name).is_some()
+    }
+
+    /// 消费者组是否有尚未投递的新条目
+    pub fn has_new_for(&self, group: &[u8]) -> bool {
+        self.groups.get(group).is_some_and(|group| {
+            self.entries
+                .range((Bound::Excluded(group.last_delivered), Bound::Unbounded))
+                .next()
+                .is_some()
+        })
+    }
+
+    /// 向消费者组投递ID大于last_delivered的新条目，并记录到PEL中(noack为true时不记录)。
+    /// 返回投递的条目
+    pub fn deliver_new(
+        &mut self,
+        group: &[u8],
+        consumer: &Bytes,
+        count: Option<usize>,
+        noack: bool,
+        now_ms: u64,
+    ) -> Option<Vec<(StreamId, StreamFields)>> {
+        let group = self.groups.get_mut(group)?;
+        let entries: Vec<_> = self
+            .entries
+            .range((Bound::Excluded(group.last_delivered), Bound::Unbounded))
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect();
+
+        group.consumer_mut(consumer, now_ms);
+        for (id, _) in &entries {
+            group.last_delivered = *id;
+            if !noack {
+                group.add_pending(*id, consumer.clone(), now_ms, 1);
+            }
+        }
+
+        Some(entries)
+    }
+}
+
+/// 已投递但尚未确认的条目
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: Bytes,
+    // 最近一次投递的时间(Unix毫秒)
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StreamConsumer {
+    // 最近一次活动的时间(Unix毫秒)
+    pub seen_time: u64,
+    // 该消费者的PEL，条目的详细信息记录在组的PEL中
+    pub pending: BTreeSet<StreamId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StreamGroup {
+    pub last_delivered: StreamId,
+    // 组的PEL
+    pending: BTreeMap<StreamId, PendingEntry>,
+    consumers: BTreeMap<Bytes, StreamConsumer>,
+}
+
+impl StreamGroup {
+    pub fn new(last_delivered: StreamId) -> Self {
+        StreamGroup {
+            last_delivered,
+            ..Default::default()
+        }
+    }
+
+    pub fn pending(&self) -> &BTreeMap<StreamId, PendingEntry> {
+        &self.pending
+    }
+
+    pub fn consumers(&self) -> &BTreeMap<Bytes, StreamConsumer> {
+        &self.consumers
+    }
+
+    pub fn consumer(&self, name: &[u8]) -> Option<&StreamConsumer> {
+        self.consumers.get(name)
+    }
+
+    pub fn pending_mut(&mut self, id: &StreamId) -> Option<&mut PendingEntry> {
+        self.pending.get_mut(id)
+    }
+
+    /// 创建消费者，消费者已存在时返回false
+    pub fn create_consumer(&mut self, name: Bytes, now_ms: u64) -> bool {
+        if self.consumers.contains_key(&name) {
+            return false;
+        }
+
+        self.consumers.insert(
+            name,
+            StreamConsumer {
+                seen_time: now_ms,
+                ..Default::default()
+            },
+        );
+        true
+    }
+
+    /// 返回消费者，消费者不存在时创建，并更新其活动时间
+    pub fn consumer_mut(&mut self, name: &Bytes, now_ms: u64) -> &mut StreamConsumer {
+        let consumer = self.consumers.entry(name.clone()).or_default();
+        consumer.seen_time = now_ms;
+        consumer
+    }
+
+    /// 将条目加入PEL，如果条目已经属于其它消费者，则转移给新的消费者
+    pub fn add_pending(&mut self, id: StreamId, consumer: Bytes, now_ms: u64, delivery_count: u64) {
+        if let Some(old) = self.pending.get(&id) {
+            if let Some(c) = self.consumers.get_mut(&old.consumer) {
+                c.pending.remove(&id);
+            }
+        }
+
+        self.consumer_mut(&consumer, now_ms).pending.insert(id);
+        self.pending.insert(
+            id,
+            PendingEntry {
+                consumer,
+                delivery_time: now_ms,
+                delivery_count,
+            },
+        );
+    }
+
+    /// 确认条目，将其从PEL中移除。条目不在PEL中时返回false
+    pub fn ack(&mut self, id: &StreamId) -> bool {
+        let Some(entry) = self.pending.remove(id) else {
+            return false;
+        };
+
+        if let Some(c) = self.consumers.get_mut(&entry.consumer) {
+            c.pending.remove(id);
+        }
+        true
+    }
+}
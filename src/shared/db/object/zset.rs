@@ -35,6 +35,43 @@ impl ZSet {
             ZSet::ZipSet => unimplemented!(),
         }
     }
+
+    /// 按成员移除，由于跳表按分数排序，需要遍历查找成员
+    pub fn remove_member(&mut self, member: &[u8]) -> Option<ZSetElem> {
+        match self {
+            ZSet::SkipList(sl) => {
+                let index = sl.iter().position(|e| e.member() == member)?;
+                Some(sl.remove_index(index))
+            }
+            ZSet::ZipSet => unimplemented!(),
+        }
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        match self {
+            ZSet::SkipList(sl) => sl
+                .iter()
+                .find(|e| e.member() == member)
+                .map(ZSetElem::score),
+            ZSet::ZipSet => unimplemented!(),
+        }
+    }
+
+    /// 按分数从小到大遍历所有元素
+    pub fn iter(&self) -> impl Iterator<Item = &ZSetElem> {
+        match self {
+            ZSet::SkipList(sl) => sl.iter(),
+            ZSet::ZipSet => unimplemented!(),
+        }
+    }
+
+    /// 按分数从小到大遍历所有成员
+    pub fn members(&self) -> impl Iterator<Item = &Bytes> {
+        match self {
+            ZSet::SkipList(sl) => sl.iter().map(ZSetElem::member),
+            ZSet::ZipSet => unimplemented!(),
+        }
+    }
 }
 
 impl Clone for ZSet {
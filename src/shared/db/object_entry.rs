@@ -43,6 +43,14 @@ impl ObjectEntryMut<'_> {
         }
     }
 
+    /// 对象存在且已过期
+    pub fn is_object_expired(&self) -> bool {
+        match &self.entry {
+            Entry::Occupied(e) => e.get().inner().is_some_and(|inner| inner.is_expired()),
+            Entry::Vacant(_) => false,
+        }
+    }
+
     #[inline]
     pub fn key(&self) -> &Key {
         self.entry.key()
@@ -89,9 +97,11 @@ impl ObjectEntryMut<'_> {
                     ObjValueType::Set => Object::new_set(Set::default(), None),
                     ObjValueType::Hash => Object::new_hash(Hash::default(), None),
                     ObjValueType::ZSet => Object::new_zset(ZSet::default(), None),
+                    ObjValueType::Stream => Object::new_stream(Stream::default(), None),
                 };
 
                 let new_entry = e.insert_entry(new_obj);
+                db.incr_object_count();
                 Self {
                     entry: entry::Entry::Occupied(new_entry),
                     db,
@@ -103,86 +113,112 @@ impl ObjectEntryMut<'_> {
 
     /// # Desc:
     ///
-    /// 插入对象。如果存在旧对象，则会触发旧对象中的**MayUpdate**和**Track**事件。
+    /// 插入对象。如果存在旧对象，则会触发旧对象中的**MayUpdate**和**Track**事件。旧对象
+    /// 较大时可能在后台释放
     ///
     /// # Return:
     ///
-    /// 返回一个旧对象和[`ObjectEntryMut`]以便重复操作。
+    /// 返回[`ObjectEntryMut`]以便重复操作。
     #[instrument(level = "debug", skip(self))]
-    pub fn insert_object(mut self, object: ObjectInner) -> (Self, Option<ObjectInner>) {
+    pub fn insert_object(mut self, object: ObjectInner) -> Self {
         let key = self.entry.key().clone();
         let new_ex = object.expire();
 
         let db = self.db;
         match self.entry {
             Entry::Occupied(ref mut e) => {
-                let mut old_obj = e.insert(object.into());
-
-                old_obj.trigger_may_update_event(&key);
-                old_obj.trigger_track_event(&key);
+                let old_obj = e.insert(object.into());
+                db.after_replace_object(&key, old_obj, new_ex);
 
-                if let Some(old_obj_inner) = old_obj.inner() {
-                    // 旧对象为有效对象
-                    db.update_expire_records(&key, new_ex, old_obj_inner.expire());
-                } else {
-                    // 旧对象中为空对象，则old_expire为None
-                    db.update_expire_records(&key, new_ex, None);
-                }
-                (self, old_obj.into_inner())
+                self
             }
             Entry::Vacant(e) => {
                 let new_entry = e.insert_entry(object.into());
 
                 // 不存在旧对象，则old_expire为None
                 db.update_expire_records(&key, new_ex, None);
+                db.incr_object_count();
 
-                (
-                    Self {
-                        entry: entry::Entry::Occupied(new_entry),
-                        db: self.db,
-                        intention_lock: self.intention_lock,
-                    },
-                    None,
-                )
+                Self {
+                    entry: entry::Entry::Occupied(new_entry),
+                    db: self.db,
+                    intention_lock: self.intention_lock,
+                }
             }
         }
     }
 
     /// # Desc:
     ///
-    /// 移除对象。如果存在旧对象，则会触发旧对象中的**Track**事件
+    /// 移除对象。如果存在旧对象，则会触发旧对象中的**MayUpdate**和**Track**事件以及Db的
+    /// expire hook。expire hook在释放锁之后调用
     #[inline]
     #[instrument(level = "debug", skip(self), ret)]
     pub fn remove_object(self) -> Option<(Key, Object)> {
-        match self.entry {
-            Entry::Occupied(e) => {
-                let (key, mut obj) = e.remove_entry();
+        let Self {
+            entry,
+            db,
+            intention_lock,
+        } = self;
 
-                if let Some(obj_inner) = obj.inner() {
-                    self.db
-                        .update_expire_records(&key, None, obj_inner.expire());
-                }
+        let Entry::Occupied(e) = entry else {
+            return None;
+        };
 
-                obj.trigger_track_event(&key);
+        let expired = e.get().inner().is_some_and(|inner| inner.is_expired());
+        // DEL命令需要在释放锁之前传播
+        if expired {
+            db.propagate_expired_del(e.key());
+        }
 
-                Some((key, obj))
-            }
-            Entry::Vacant(_) => None,
+        let (key, mut obj) = e.remove_entry();
+
+        if let Some(obj_inner) = obj.inner() {
+            db.update_expire_records(&key, None, obj_inner.expire());
+            db.decr_object_count();
+
+            let reason = if expired {
+                ExpireReason::Expired
+            } else {
+                ExpireReason::Deleted
+            };
+            db.trigger_expire_hook(&key, reason);
         }
+
+        obj.trigger_may_update_event(&key);
+        obj.trigger_track_event(&key);
+
+        drop(intention_lock);
+        db.deliver_expire_events();
+
+        Some((key, obj))
     }
 
     /// # Desc:
     ///
-    /// 更新对象的过期时间。如果对象不存在，则返回错误。
+    /// 更新对象的过期时间，并更新Db中的过期记录。会触发对象中的**MayUpdate**和**Track**事件。
+    ///
+    /// # Error:
+    ///
+    /// 如果对象不存在，对象为空或者对象已过期则返回CmdError::from(DbError::KeyNotFound)
     #[inline]
     #[instrument(level = "debug", skip(self), err)]
     pub fn update_object_expire(&mut self, new_ex: Option<Instant>) -> CmdResult<Option<Instant>> {
         if let Entry::Occupied(e) = &mut self.entry {
-            if e.get_mut().inner().is_some() {
-                let obj_inner = e.get_mut().inner_mut().unwrap();
+            if let Some(obj_inner) = e.get_mut().inner_mut() {
+                if obj_inner.is_expired() {
+                    return Err(DbError::KeyNotFound.into());
+                }
+
                 let old_ex = obj_inner.set_expire(new_ex)?;
 
-                self.db.update_expire_records(e.key(), new_ex, old_ex);
+                let key = e.key().clone();
+                self.db.update_expire_records(&key, new_ex, old_ex);
+
+                let obj = e.get_mut();
+                obj.trigger_may_update_event(&key);
+                obj.trigger_track_event(&key);
+
                 return Ok(old_ex);
             }
         }
@@ -211,13 +247,21 @@ impl ObjectEntryMut<'_> {
                 }
 
                 let obj_inner = e.get_mut().inner_mut().unwrap();
+                let old_encoding = self
+                    .db
+                    .is_notify_encoding_change()
+                    .then(|| obj_inner.encoding());
                 f(obj_inner)?;
+                let encoding_changed = old_encoding.is_some_and(|old| obj_inner.encoding() != old);
 
                 let key = e.key().clone();
                 let obj = e.get_mut();
 
                 obj.trigger_may_update_event(&key);
                 obj.trigger_track_event(&key);
+                if encoding_changed {
+                    self.db.notify_keyevent("encoding-change", &key);
+                }
 
                 return Ok(());
             }
@@ -245,13 +289,31 @@ impl ObjectEntryMut<'_> {
         match self.entry {
             Entry::Occupied(ref mut e) => match e.get_mut().inner_mut() {
                 Some(obj_inner) => {
+                    // 在执行回调函数前检查类型，避免类型不匹配时已经修改了对象的一部分
+                    if obj_inner.typ() != obj_type {
+                        return Err(DbError::TypeErr {
+                            expected: obj_type.as_str(),
+                            found: obj_inner.type_str(),
+                        }
+                        .into());
+                    }
+
+                    let old_encoding = self
+                        .db
+                        .is_notify_encoding_change()
+                        .then(|| obj_inner.encoding());
                     f(obj_inner)?;
+                    let encoding_changed =
+                        old_encoding.is_some_and(|old| obj_inner.encoding() != old);
 
                     let key = e.key().clone();
                     let obj = e.get_mut();
 
                     obj.trigger_may_update_event(&key);
                     obj.trigger_track_event(&key);
+                    if encoding_changed {
+                        self.db.notify_keyevent("encoding-change", &key);
+                    }
 
                     Ok(self)
                 }
@@ -263,10 +325,12 @@ impl ObjectEntryMut<'_> {
                         ObjValueType::Set => Object::new_set(Set::default(), None),
                         ObjValueType::Hash => Object::new_hash(Hash::default(), None),
                         ObjValueType::ZSet => Object::new_zset(ZSet::default(), None),
+                        ObjValueType::Stream => Object::new_stream(Stream::default(), None),
                     };
                     f(new_obj.inner_mut().unwrap())?;
 
                     let mut old_obj = e.insert(new_obj);
+                    self.db.incr_object_count();
 
                     old_obj.trigger_may_update_event(e.key());
                     old_obj.trigger_track_event(e.key());
@@ -281,10 +345,12 @@ impl ObjectEntryMut<'_> {
                     ObjValueType::Set => Object::new_set(Set::default(), None),
                     ObjValueType::Hash => Object::new_hash(Hash::default(), None),
                     ObjValueType::ZSet => Object::new_zset(ZSet::default(), None),
+                    ObjValueType::Stream => Object::new_stream(Stream::default(), None),
                 };
                 f(new_obj.inner_mut().unwrap())?;
 
                 let new_entry = e.insert_entry(new_obj);
+                self.db.incr_object_count();
                 Ok(Self {
                     entry: entry::Entry::Occupied(new_entry),
                     db: self.db,
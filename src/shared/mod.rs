@@ -1,15 +1,27 @@
 pub mod db;
+pub mod observer;
 pub mod propagator;
 pub mod script;
 
+pub use observer::*;
 pub use script::*;
 
 use crate::{
     conf::Conf,
+    server::RESERVE_MAX_ID,
     shared::{db::Db, propagator::Propagator},
+    Id,
 };
 use async_shutdown::ShutdownManager;
-use std::sync::Arc;
+use crossbeam::atomic::AtomicCell;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::time::Instant;
 
 #[derive(Clone, Default)]
 pub struct Shared {
@@ -18,6 +30,16 @@ pub struct Shared {
     script: Arc<Script>,
     wcmd_propagator: Arc<Propagator>,
     shutdown: ShutdownManager<()>,
+    // 是否关闭定期删除过期键，可通过DEBUG SET-ACTIVE-EXPIRE修改
+    active_expire_disabled: Arc<AtomicBool>,
+    // 已分配的客户端ID数量，用于为新连接分配单调递增的客户端ID
+    client_id_count: Arc<AtomicU64>,
+    // 在该时刻之前暂停执行所有命令，可通过DEBUG SLEEP-BLOCK设置
+    paused_until: Arc<AtomicCell<Option<Instant>>>,
+    // 提前解除暂停时唤醒等待暂停结束的命令
+    unpaused: Arc<tokio::sync::Notify>,
+    // 嵌入rutin的应用注册的观察者
+    observer: Option<Arc<dyn Observer>>,
 }
 
 impl Shared {
@@ -28,6 +50,17 @@ impl Shared {
             conf.aof.is_some(),
             conf.replica.max_replica,
         ));
+        // 根据配置决定哪些被移除的大对象在后台释放
+        let lazy_free = db.lazy_free();
+        lazy_free.set_lazy_user_del(conf.memory.lazyfree_lazy_user_del);
+        lazy_free.set_lazy_eviction(conf.memory.lazyfree_lazy_eviction);
+        lazy_free.set_lazy_expire(conf.memory.lazyfree_lazy_expire);
+        lazy_free.set_lazy_server_del(conf.memory.lazyfree_lazy_server_del);
+        db.set_notify_encoding_change(conf.memory.notify_encoding_change);
+        db.set_replica_mode(conf.replica.replicaof.load().is_some());
+        // replica不会自行删除过期键，因此master删除过期键时需要传播DEL命令
+        db.set_wcmd_propagator(wcmd_propagator.clone());
+
         let script = Arc::new(Script::new());
         Self {
             db,
@@ -35,6 +68,11 @@ impl Shared {
             script,
             wcmd_propagator,
             shutdown,
+            active_expire_disabled: Default::default(),
+            client_id_count: Default::default(),
+            paused_until: Default::default(),
+            unpaused: Default::default(),
+            observer: None,
         }
     }
 
@@ -45,12 +83,20 @@ impl Shared {
         wcmd_propagator: Arc<Propagator>,
         shutdown: ShutdownManager<()>,
     ) -> Self {
+        db.set_replica_mode(conf.replica.replicaof.load().is_some());
+        db.set_wcmd_propagator(wcmd_propagator.clone());
+
         Self {
             db,
             conf,
             script,
             wcmd_propagator,
             shutdown,
+            active_expire_disabled: Default::default(),
+            client_id_count: Default::default(),
+            paused_until: Default::default(),
+            unpaused: Default::default(),
+            observer: None,
         }
     }
 
@@ -73,6 +119,102 @@ impl Shared {
     pub fn shutdown(&self) -> &ShutdownManager<()> {
         &self.shutdown
     }
+
+    #[inline]
+    pub fn observer(&self) -> Option<&Arc<dyn Observer>> {
+        self.observer.as_ref()
+    }
+
+    /// 注册观察者，会覆盖之前注册的观察者，但不影响Db的expire hook。只对之后clone的
+    /// Shared生效，因此应该在服务开始运行之前注册
+    pub fn set_observer(&mut self, observer: impl Observer) {
+        let observer: Arc<dyn Observer> = Arc::new(observer);
+        self.db.set_observer(Some(observer.clone()));
+        self.observer = Some(observer);
+    }
+
+    /// 分配一个新的客户端ID。ID单调递增，且大于[`RESERVE_MAX_ID`]
+    pub fn next_client_id(&self) -> Id {
+        let count = self.client_id_count.fetch_add(1, Ordering::Relaxed) + 1;
+        RESERVE_MAX_ID + count as Id
+    }
+
+    /// 暂停执行所有命令直到deadline。已有更晚的暂停时间时不会缩短暂停时间
+    pub fn pause_until(&self, deadline: Instant) {
+        let _ = self.paused_until.fetch_update(|until| match until {
+            Some(until) if until >= deadline => None,
+            _ => Some(Some(deadline)),
+        });
+    }
+
+    /// 立即解除暂停，唤醒所有等待暂停结束的命令
+    pub fn unpause(&self) {
+        self.paused_until.store(None);
+        self.unpaused.notify_waiters();
+    }
+
+    /// 如果服务器处于暂停状态，则等待暂停结束。等待期间暂停可能被延长或者被提前解除
+    pub async fn wait_unpaused(&self) {
+        loop {
+            // 先注册通知再检查暂停状态，避免错过检查之后的unpause
+            let notified = self.unpaused.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let Some(until) = self.paused_until.load() else {
+                return;
+            };
+            if until <= Instant::now() {
+                return;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(until) => {}
+                _ = notified => {}
+            }
+        }
+    }
+
+    pub fn is_active_expire_enabled(&self) -> bool {
+        !self.active_expire_disabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_active_expire(&self, enable: bool) {
+        self.active_expire_disabled
+            .store(!enable, Ordering::Relaxed);
+    }
+
+    /// 执行一次定期删除，返回移除的过期键数量。关闭定期删除后不会移除任何键，过期键
+    /// 只会在被访问时删除
+    pub async fn active_expire_cycle(&self) -> usize {
+        // replica不会自行删除过期键，而是等待master传播的DEL命令
+        if !self.is_active_expire_enabled() || self.conf.replica.replicaof.load().is_some() {
+            return 0;
+        }
+
+        self.db.remove_expired_objects(Instant::now()).await
+    }
+
+    /// 等待fut完成，直到deadline(None表示永不超时)或者服务器关闭。超时或服务器关闭时
+    /// 返回None
+    pub async fn block_until<T>(
+        &self,
+        deadline: Option<Instant>,
+        fut: impl Future<Output = T>,
+    ) -> Option<T> {
+        let timeout = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            res = fut => Some(res),
+            _ = timeout => None,
+            _ = self.shutdown.wait_shutdown_triggered() => None,
+        }
+    }
 }
 
 impl std::fmt::Debug for Shared {
@@ -82,6 +224,7 @@ impl std::fmt::Debug for Shared {
             .field("script", &self.script)
             .field("conf", &self.conf)
             .field("wcmd_propagator", &self.wcmd_propagator)
+            .field("observer", &self.observer.is_some())
             .finish()
     }
 }
@@ -0,0 +1,132 @@
+use crate::Key;
+use std::time::Duration;
+
+/// 嵌入rutin的应用可以通过实现该trait观察服务器内部的事件，从而接入自己的监控系统。
+/// 通过[`Shared::set_observer`](super::Shared::set_observer)注册，未注册时不会产生
+/// 任何额外开销。回调函数在释放Db的锁之后调用，因此可以访问Db，但不应该阻塞
+pub trait Observer: Send + Sync + 'static {
+    /// 命令执行完毕后调用。name为命令名，dur为命令的执行时间，err表示命令是否返回了错误
+    fn on_command(&self, _name: &str, _dur: Duration, _err: bool) {}
+
+    /// 键因内存不足被淘汰时调用
+    fn on_evict(&self, _key: &Key) {}
+
+    /// 键过期被删除时调用
+    fn on_expire(&self, _key: &Key) {}
+}
+
+#[cfg(test)]
+mod observer_tests {
+    use super::*;
+    use crate::{
+        server::Handler,
+        shared::{
+            db::{Db, ObjectInner},
+            Shared,
+        },
+        util::{cmd, test_init},
+    };
+    use async_shutdown::ShutdownManager;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    };
+    use tokio::time::Instant;
+
+    #[derive(Default)]
+    struct CountObserver {
+        commands: Mutex<Vec<(String, bool)>>,
+        expired: AtomicUsize,
+    }
+
+    impl Observer for Arc<CountObserver> {
+        fn on_command(&self, name: &str, _dur: Duration, err: bool) {
+            self.commands.lock().unwrap().push((name.to_string(), err));
+        }
+
+        fn on_expire(&self, _key: &Key) {
+            self.expired.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_test() {
+        test_init();
+
+        let mut shared = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let observer = Arc::new(CountObserver::default());
+        shared.set_observer(observer.clone());
+        let (mut handler, _) = Handler::with_shared(shared.clone());
+
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+        handler.dispatch(cmd(&["GET", "nil"])).await.unwrap();
+        handler.dispatch(cmd(&["INCR", "k"])).await.unwrap();
+        assert_eq!(
+            *observer.commands.lock().unwrap(),
+            [
+                ("SET".to_string(), false),
+                // 键不存在不算作错误
+                ("GET".to_string(), false),
+                ("INCR".to_string(), true)
+            ]
+        );
+
+        let expire = Instant::now() + Duration::from_millis(10);
+        shared
+            .db()
+            .insert_object("expired".into(), ObjectInner::new_str("v", Some(expire)))
+            .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(shared.db().remove_expired_objects(Instant::now()).await, 1);
+        assert_eq!(observer.expired.load(Ordering::Relaxed), 1);
+
+        // 删除不会触发on_expire
+        handler.dispatch(cmd(&["DEL", "k"])).await.unwrap();
+        assert_eq!(observer.expired.load(Ordering::Relaxed), 1);
+    }
+
+    struct DbObserver(Weak<Db>, flume::Sender<bool>);
+
+    impl Observer for DbObserver {
+        fn on_expire(&self, key: &Key) {
+            // 如果在持有锁时调用，则会死锁
+            let db = self.0.upgrade().unwrap();
+            self.1.send(db.entries().contains_key(key)).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn observer_access_db_test() {
+        test_init();
+
+        let db = Arc::new(Db::default());
+        let mut shared = Shared::new(db.clone(), Default::default(), ShutdownManager::new());
+        let (hook_tx, hook_rx) = flume::unbounded();
+        db.set_expire_hook(move |key, _| hook_tx.send(key.clone()).unwrap());
+        let (tx, rx) = flume::unbounded();
+        shared.set_observer(DbObserver(Arc::downgrade(&db), tx));
+        let (mut handler, _) = Handler::with_shared(shared.clone());
+
+        // 过期键在被访问时删除，观察者与expire hook都收到通知
+        let expire = Instant::now() + Duration::from_millis(10);
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+        db.insert_object("k".into(), ObjectInner::new_str("v", Some(expire)))
+            .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handler.dispatch(cmd(&["GET", "k"])).await.unwrap();
+
+        assert!(!rx.try_recv().unwrap());
+        assert_eq!(hook_rx.try_recv().unwrap(), Key::from("k"));
+
+        // 覆盖已过期的键时同样在释放锁之后通知
+        db.insert_object("k".into(), ObjectInner::new_str("v", Some(Instant::now())))
+            .await;
+        handler.dispatch(cmd(&["SET", "k", "v"])).await.unwrap();
+        assert!(rx.try_recv().unwrap());
+        assert_eq!(hook_rx.try_recv().unwrap(), Key::from("k"));
+    }
+}
@@ -1,12 +1,18 @@
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use kanal::{AsyncReceiver, AsyncSender};
-use std::sync::atomic::{AtomicU8, Ordering};
+use parking_lot::Mutex;
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+use tokio::time::Instant;
 
 use crate::{
     cmd::CmdUnparsed,
     connection::AsyncStream,
     frame::Resp3,
     server::{Handler, ServerError},
+    Key,
 };
 
 #[derive(Debug, Default)]
@@ -14,6 +20,24 @@ pub struct Propagator {
     pub to_aof: Option<(AsyncSender<BytesMut>, AsyncReceiver<BytesMut>)>,
     to_replicas: Box<[(AsyncSender<BytesMut>, AsyncReceiver<BytesMut>)]>,
     existing_replicas: AtomicU8,
+    // 已连接的replica的状态，按连接的先后排列
+    replica_states: Mutex<Vec<ReplicaState>>,
+    // 等待传播的过期键的DEL命令。键过期时，创建该键的写命令可能仍缓存在某个handler的
+    // wcmd_buf中，DEL命令需要等待这些写命令传播后再传播，否则replica会先删除键，之后又
+    // 被写命令重新创建
+    expired_dels: Mutex<BytesMut>,
+    // 正在执行写命令或者缓存了尚未传播的写命令的handler数量
+    holding_handlers: AtomicUsize,
+}
+
+/// 已连接的replica的状态，用于INFO replication以及等待replica确认的命令(WAIT)
+#[derive(Debug, Clone)]
+pub struct ReplicaState {
+    pub addr: SocketAddr,
+    /// replica确认已处理的复制偏移量
+    pub ack_offset: u64,
+    /// 最近一次收到replica确认的时刻
+    pub last_ack: Instant,
 }
 
 impl Propagator {
@@ -23,10 +47,14 @@ impl Propagator {
             to_aof: if aof_enable { Some((tx, rx)) } else { None },
             to_replicas: (0..max_replica).map(|_| kanal::unbounded_async()).collect(),
             existing_replicas: AtomicU8::new(0),
+            replica_states: Mutex::default(),
+            expired_dels: Mutex::default(),
+            holding_handlers: AtomicUsize::new(0),
         }
     }
 
-    pub fn new_receiver(&self) -> Result<AsyncReceiver<BytesMut>, ServerError> {
+    /// 为地址为addr的replica分配一个接收写命令的通道，并开始记录它的状态
+    pub fn new_receiver(&self, addr: SocketAddr) -> Result<AsyncReceiver<BytesMut>, ServerError> {
         let prev_len = self.existing_replicas.fetch_add(1, Ordering::Relaxed) as usize;
 
         if prev_len + 1 > self.to_replicas.len() {
@@ -34,15 +62,101 @@ impl Propagator {
             return Err(ServerError::from("too many replica connections"));
         }
 
+        self.replica_states.lock().push(ReplicaState {
+            addr,
+            ack_offset: 0,
+            last_ack: Instant::now(),
+        });
+
         Ok(self.to_replicas[prev_len].1.clone())
     }
 
-    pub fn delete_receiver(&self) -> Result<usize, ServerError> {
+    pub fn delete_receiver(&self, addr: &SocketAddr) -> Result<usize, ServerError> {
         let curr_len = self.existing_replicas.fetch_sub(1, Ordering::Relaxed);
+        self.replica_states
+            .lock()
+            .retain(|state| state.addr != *addr);
 
         Ok(curr_len as usize)
     }
 
+    /// 记录replica确认的复制偏移量(REPLCONF ACK)
+    pub fn ack_replica(&self, addr: &SocketAddr, offset: u64) {
+        if let Some(state) = self
+            .replica_states
+            .lock()
+            .iter_mut()
+            .find(|state| state.addr == *addr)
+        {
+            state.ack_offset = offset;
+            state.last_ack = Instant::now();
+        }
+    }
+
+    /// 所有已连接的replica的状态
+    pub fn replica_states(&self) -> Vec<ReplicaState> {
+        self.replica_states.lock().clone()
+    }
+
+    /// 传播过期键的删除。replica不会自行删除过期键，而是等待master传播的DEL命令。如果有
+    /// handler缓存了尚未传播的写命令，则DEL命令等待这些写命令传播后再传播。该函数在持有
+    /// 键的锁时被调用，因此不能阻塞也不能访问Db
+    pub fn propagate_expired_del(&self, key: &Key) {
+        if self.existing_replicas.load(Ordering::Relaxed) == 0 && self.to_aof.is_none() {
+            return;
+        }
+
+        let mut dels = self.expired_dels.lock();
+        Resp3::<Bytes, bytestring::ByteString>::new_array(vec![
+            Resp3::new_blob_string(Bytes::from_static(b"DEL")),
+            Resp3::new_blob_string(key.clone()),
+        ])
+        .encode_buf(&mut *dels);
+
+        if self.holding_handlers.load(Ordering::Acquire) == 0 {
+            self.send_expired_dels(&mut dels);
+        }
+    }
+
+    /// 写命令修改数据前调用(held为handler的`wcmd_held`)，直到handler缓存的写命令被传播
+    /// (见[`Propagator::propagate_buffered`])。期间过期的键的DEL命令会等待这些写命令传播后
+    /// 再传播，因此不能在阻塞等待期间持有
+    pub fn hold_wcmd(&self, held: &mut bool) {
+        if *held || (self.existing_replicas.load(Ordering::Relaxed) == 0 && self.to_aof.is_none()) {
+            return;
+        }
+
+        // 与传播DEL命令互斥：已经决定传播的DEL命令一定先于之后执行的写命令传播
+        let _dels = self.expired_dels.lock();
+        self.holding_handlers.fetch_add(1, Ordering::AcqRel);
+        *held = true;
+    }
+
+    /// 释放[`Propagator::hold_wcmd`]。最后一个handler释放时，传播等待中的DEL命令
+    pub fn release_wcmd(&self) {
+        let mut dels = self.expired_dels.lock();
+        if self.holding_handlers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.send_expired_dels(&mut dels);
+        }
+    }
+
+    fn send_expired_dels(&self, dels: &mut BytesMut) {
+        if dels.is_empty() {
+            return;
+        }
+
+        let dels = dels.split();
+        // 通道为无界通道，发送不会阻塞；接收端由Propagator持有，因此发送也不会失败
+        if let Some((tx, _)) = &self.to_aof {
+            tx.as_sync().send(dels.clone()).unwrap();
+        }
+
+        for i in 0..self.existing_replicas.load(Ordering::Relaxed) {
+            let (tx, _) = &self.to_replicas[i as usize];
+            tx.as_sync().send(dels.clone()).unwrap();
+        }
+    }
+
     #[inline]
     pub async fn may_propagate(&self, cmd: CmdUnparsed, handler: &mut Handler<impl AsyncStream>) {
         let existing_replicas = self.existing_replicas.load(Ordering::Relaxed);
@@ -54,20 +168,533 @@ impl Propagator {
             return;
         };
 
-        // 如果有多个未处理的命令，则暂时不进行传播，等待处理完毕
-        if handler.conn.unhandled_count() > 1 {
+        // 如果有多个未处理的命令，则暂时不进行传播，等待处理完毕。但缓存达到上限时立即传播，
+        // 避免批处理中的大量写命令占用过多内存。wcmd_buf中总是完整的命令，因此可以安全地传播
+        if handler.conn.unhandled_count() > 1
+            && handler.context.wcmd_buf.len() < handler.shared.conf().server.max_wcmd_buf
+        {
             return;
         }
 
-        // 传播到aof
-        if let Some((tx, _)) = &self.to_aof {
-            tx.send(handler.context.wcmd_buf.split()).await.unwrap();
+        self.propagate_buffered(handler).await;
+    }
+
+    /// 传播wcmd_buf中缓存的写命令，之后释放[`Propagator::hold_wcmd`]
+    #[inline]
+    pub async fn propagate_buffered(&self, handler: &mut Handler<impl AsyncStream>) {
+        let existing_replicas = self.existing_replicas.load(Ordering::Relaxed);
+
+        // 每个目标都需要收到一份完整的写命令
+        let wcmd = handler.context.wcmd_buf.split();
+
+        if !wcmd.is_empty() {
+            // 传播到aof
+            if let Some((tx, _)) = &self.to_aof {
+                tx.send(wcmd.clone()).await.unwrap();
+            }
+
+            // 传播到replica
+            for i in 0..existing_replicas {
+                let (tx, _) = &self.to_replicas[i as usize];
+                tx.send(wcmd.clone()).await.unwrap();
+            }
         }
 
-        // 传播到replica
-        for i in 0..existing_replicas {
-            let (tx, _) = &self.to_replicas[i as usize];
-            tx.send(handler.context.wcmd_buf.split()).await.unwrap();
+        if std::mem::take(&mut handler.context.wcmd_held) {
+            self.release_wcmd();
+        }
+    }
+}
+
+#[cfg(test)]
+mod propagator_tests {
+    use super::*;
+    use crate::{
+        cmd::dispatch,
+        frame::RESP3Decoder,
+        shared::{
+            db::{Db, ObjectInner},
+            Shared,
+        },
+        util::cmd,
+    };
+    use async_shutdown::ShutdownManager;
+    use std::{sync::Arc, time::Duration};
+    use tokio::time::Instant;
+    use tokio_util::codec::Decoder;
+
+    #[tokio::test]
+    async fn propagate_expired_del_test() {
+        crate::util::test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let replica = Shared::default();
+        let replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+
+        let expire = Instant::now() + Duration::from_millis(10);
+        master
+            .db()
+            .insert_object(Key::from("key1"), ObjectInner::new_str("v1", Some(expire)))
+            .await;
+        // replica中的对象不会自行过期
+        replica
+            .db()
+            .insert_object(Key::from("key1"), ObjectInner::new_str("v1", None))
+            .await;
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(master.db().remove_expired_objects(Instant::now()).await, 1);
+
+        let mut wcmd = replica_rx.recv().await.unwrap();
+        let (mut handler, _) = Handler::with_shared(replica.clone());
+        let mut decoder = RESP3Decoder::default();
+        while let Some(cmd_frame) = decoder.decode(&mut wcmd).unwrap() {
+            dispatch(cmd_frame, &mut handler).await.unwrap();
+        }
+
+        assert!(!replica.db().contains_object(&Key::from("key1")).await);
+    }
+
+    #[tokio::test]
+    async fn expire_hook_with_propagation_test() {
+        crate::util::test_init();
+
+        // new_with创建的Shared同样传播过期键的删除，设置expire hook不影响传播
+        let db = Arc::new(Db::default());
+        let propagator = Arc::new(Propagator::new(false, 1));
+        let master = Shared::new_with(
+            db.clone(),
+            Default::default(),
+            Default::default(),
+            propagator.clone(),
+            ShutdownManager::new(),
+        );
+        let replica_rx = propagator
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+        let (tx, rx) = flume::unbounded();
+        db.set_expire_hook(move |key, _| tx.send(key.clone()).unwrap());
+
+        let expire = Instant::now() + Duration::from_millis(10);
+        master
+            .db()
+            .insert_object(Key::from("key"), ObjectInner::new_str("v", Some(expire)))
+            .await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(master.db().remove_expired_objects(Instant::now()).await, 1);
+
+        assert_eq!(rx.try_recv().unwrap(), Key::from("key"));
+        let mut buf = replica_rx.try_recv().unwrap().unwrap();
+        assert_eq!(
+            RESP3Decoder::default().decode(&mut buf).unwrap().unwrap(),
+            cmd(&["DEL", "key"])
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_del_order_test() {
+        crate::util::test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let propagator = master.wcmd_propagator().clone();
+        let replica_rx = propagator
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+        let (mut handler, _) = Handler::with_shared(master.clone());
+
+        // 模拟批处理中已经执行但尚未传播的写命令
+        propagator.hold_wcmd(&mut handler.context.wcmd_held);
+        let expire = Instant::now() + Duration::from_millis(10);
+        master
+            .db()
+            .insert_object(Key::from("key"), ObjectInner::new_str("v", Some(expire)))
+            .await;
+        cmd(&["SET", "key", "v", "PXAT", "1"]).encode_buf(&mut handler.context.wcmd_buf);
+
+        // 键过期后，DEL命令等待缓存的写命令传播
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(master.db().remove_expired_objects(Instant::now()).await, 1);
+        assert!(replica_rx.try_recv().unwrap().is_none());
+
+        propagator.propagate_buffered(&mut handler).await;
+        assert!(!handler.context.wcmd_held);
+
+        let mut decoder = RESP3Decoder::default();
+        let mut buf = replica_rx.recv().await.unwrap();
+        let frame = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(
+            frame.try_array().unwrap()[0],
+            Resp3::new_blob_string("SET".into())
+        );
+        let mut buf = replica_rx.recv().await.unwrap();
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap().unwrap(),
+            cmd(&["DEL", "key"])
+        );
+
+        // 没有handler缓存写命令时，DEL命令立即传播
+        master
+            .db()
+            .insert_object(Key::from("key"), ObjectInner::new_str("v", Some(expire)))
+            .await;
+        assert_eq!(master.db().remove_expired_objects(Instant::now()).await, 1);
+        let mut buf = replica_rx.try_recv().unwrap().unwrap();
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap().unwrap(),
+            cmd(&["DEL", "key"])
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_del_while_blocking_test() {
+        crate::util::test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+        let (mut handler, _) = Handler::with_shared(master.clone());
+        let (mut other, _) = Handler::with_shared(master.clone());
+        let mut decoder = RESP3Decoder::default();
+
+        // BLPOP阻塞期间过期的键，其DEL命令立即传播，不需要等待BLPOP结束
+        let (res, _) = tokio::join!(handler.dispatch(cmd(&["BLPOP", "list", "0"])), async {
+            let expire = Instant::now() + Duration::from_millis(10);
+            master
+                .db()
+                .insert_object(Key::from("key"), ObjectInner::new_str("v", Some(expire)))
+                .await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert_eq!(master.db().remove_expired_objects(Instant::now()).await, 1);
+
+            let mut buf = tokio::time::timeout(Duration::from_secs(1), replica_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                decoder.decode(&mut buf).unwrap().unwrap(),
+                cmd(&["DEL", "key"])
+            );
+
+            other.dispatch(cmd(&["LPUSH", "list", "v"])).await.unwrap();
+        });
+        assert_eq!(
+            res.unwrap().unwrap(),
+            Resp3::new_array(vec![
+                Resp3::new_blob_string("list".into()),
+                Resp3::new_blob_string("v".into())
+            ])
+        );
+        assert!(!handler.context.wcmd_held);
+
+        // LPUSH与BLPOP依次传播
+        for name in ["LPUSH", "BLPOP"] {
+            let mut buf = replica_rx.recv().await.unwrap();
+            let frame = decoder.decode(&mut buf).unwrap().unwrap();
+            assert_eq!(
+                frame.try_array().unwrap()[0],
+                Resp3::new_blob_string(name.into())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn propagate_rewrite_test() {
+        crate::util::test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let replica = Shared::default();
+        let replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+
+        let (mut master_handler, _) = Handler::with_shared(master.clone());
+        let (mut replica_handler, _) = Handler::with_shared(replica.clone());
+        let mut decoder = RESP3Decoder::default();
+
+        let expire_of = |shared: &Shared| {
+            let db = shared.db().clone();
+            async move {
+                db.get_object_entry(&Key::from("key"))
+                    .await
+                    .unwrap()
+                    .inner_unchecked()
+                    .expire()
+                    .unwrap()
+            }
+        };
+
+        // 相对过期时间被改写为绝对过期时间
+        for (wcmd, expected) in [
+            (cmd(&["SET", "key", "value", "EX", "100"]), "SET"),
+            (cmd(&["SETEX", "key", "100", "value"]), "SET"),
+            (cmd(&["EXPIRE", "key", "200"]), "PEXPIREAT"),
+        ] {
+            master_handler.dispatch(wcmd).await.unwrap();
+
+            let mut buf = replica_rx.recv().await.unwrap();
+            let frame = decoder.decode(&mut buf).unwrap().unwrap();
+            let args = frame.try_array().unwrap();
+            assert_eq!(args[0], Resp3::new_blob_string(expected.into()));
+            assert_eq!(
+                args.last().unwrap(),
+                &Resp3::new_blob_string(crate::util::unix_millis(expire_of(&master).await))
+            );
+
+            replica_handler.dispatch(frame).await.unwrap();
+            let diff = expire_of(&master)
+                .await
+                .duration_since(expire_of(&replica).await);
+            assert!(diff < Duration::from_millis(5));
+        }
+
+        // 已经过去的时间戳会删除键，并以DEL传播
+        master_handler
+            .dispatch(cmd(&["PEXPIREAT", "key", "1"]))
+            .await
+            .unwrap();
+        assert!(!master.db().contains_object(&Key::from("key")).await);
+
+        let mut buf = replica_rx.recv().await.unwrap();
+        let frame = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(frame, cmd(&["DEL", "key"]));
+    }
+
+    #[tokio::test]
+    async fn apply_from_master_test() {
+        crate::util::test_init();
+
+        // replica自身也有下游replica时，来自master的命令也不应被再次传播
+        let replica = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let sub_replica_rx = replica
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+        let (mut handler, _) = Handler::with_shared(replica.clone());
+
+        let wcmd = Resp3::new_array(vec![
+            Resp3::new_blob_string(Bytes::from_static(b"SET")),
+            Resp3::new_blob_string(Bytes::from_static(b"key")),
+            Resp3::new_blob_string(Bytes::from_static(b"value")),
+        ]);
+        let len = wcmd.encode().len() as u64;
+        let offset = replica.conf().replica.offset.load();
+
+        handler.apply_from_master(wcmd).await.unwrap();
+
+        assert!(replica.db().contains_object(&Key::from("key")).await);
+        assert!(handler.context.wcmd_buf.is_empty());
+        assert!(sub_replica_rx.try_recv().unwrap().is_none());
+        assert_eq!(replica.conf().replica.offset.load(), offset + len);
+    }
+
+    #[tokio::test]
+    async fn pipeline_propagate_test() {
+        crate::util::test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let stream = listener.accept().await.unwrap().0;
+        let mut handler = Handler::new(master, stream);
+        tokio::spawn(async move { handler.run().await });
+
+        // 一次性发送多个命令，最后一个写命令执行失败，之后是读命令
+        let mut pipeline = BytesMut::new();
+        for c in [
+            cmd(&["SET", "key", "value"]),
+            cmd(&["INCR", "key"]),
+            cmd(&["GET", "key"]),
+        ] {
+            c.encode_buf(&mut pipeline);
+        }
+        tokio::io::AsyncWriteExt::write_all(&mut client, &pipeline)
+            .await
+            .unwrap();
+
+        let mut conn = crate::connection::Connection::new(client, 0);
+        assert_eq!(
+            conn.read_frame().await.unwrap().unwrap(),
+            Resp3::new_simple_string("OK".into())
+        );
+        assert!(conn.read_frame().await.unwrap().unwrap().is_simple_error());
+        // 读命令的结果不受失败的写命令影响
+        assert_eq!(
+            conn.read_frame().await.unwrap().unwrap(),
+            Resp3::new_blob_string("value".into())
+        );
+
+        // 批处理结束后，缓存的写命令被传播，且不包括执行失败的命令
+        let mut buf = tokio::time::timeout(Duration::from_secs(1), replica_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut decoder = RESP3Decoder::default();
+        assert_eq!(
+            decoder.decode(&mut buf).unwrap().unwrap(),
+            cmd(&["SET", "key", "value"])
+        );
+        assert!(decoder.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn rollback_wcmd_test() {
+        use crate::cmd::{commands::LPush, CmdExecutor};
+
+        crate::util::test_init();
+
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Default::default(),
+            ShutdownManager::new(),
+        );
+        let _replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+        let (mut handler, _) = Handler::with_shared(master.clone());
+
+        master
+            .db()
+            .insert_object(Key::from("key"), ObjectInner::new_str("value", None))
+            .await;
+
+        // 模拟批处理中已经缓存的写命令
+        let cmd: Resp3 = Resp3::new_array(vec![
+            Resp3::new_blob_string(Bytes::from_static(b"SET")),
+            Resp3::new_blob_string(Bytes::from_static(b"other")),
+            Resp3::new_blob_string(Bytes::from_static(b"value")),
+        ]);
+        cmd.encode_buf(&mut handler.context.wcmd_buf);
+        let before = handler.context.wcmd_buf.clone();
+
+        // 执行失败的写命令不会改变wcmd_buf
+        let res = LPush::apply(CmdUnparsed::from(["key", "a", "b"].as_ref()), &mut handler).await;
+        assert!(res.is_err());
+        assert_eq!(handler.context.wcmd_buf, before);
+
+        // 回滚只丢弃mark之后的部分
+        let mark = handler.context.wcmd_buf.len();
+        handler
+            .context
+            .wcmd_buf
+            .extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+        handler.context.rollback_wcmd(mark);
+        assert_eq!(handler.context.wcmd_buf, before);
+    }
+
+    #[tokio::test]
+    async fn max_wcmd_buf_test() {
+        use crate::conf::{Conf, ServerConf};
+
+        crate::util::test_init();
+
+        let max_wcmd_buf = 256;
+        let conf = Conf {
+            server: ServerConf {
+                max_wcmd_buf,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let master = Shared::new(
+            Arc::new(Db::default()),
+            Arc::new(conf),
+            ShutdownManager::new(),
+        );
+        let replica_rx = master
+            .wcmd_propagator()
+            .new_receiver("127.0.0.1:6380".parse().unwrap())
+            .unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let stream = listener.accept().await.unwrap().0;
+        let mut handler = Handler::new(master.clone(), stream);
+        tokio::spawn(async move { handler.run().await });
+
+        // 一次性发送大量写命令，缓存的写命令远超上限
+        let n = 200;
+        let mut pipeline = BytesMut::new();
+        let mut max_cmd_len = 0;
+        for i in 0..n {
+            let cmd: Resp3 = Resp3::new_array(vec![
+                Resp3::new_blob_string(Bytes::from_static(b"SET")),
+                Resp3::new_blob_string(format!("key{i}").into()),
+                Resp3::new_blob_string(Bytes::from_static(b"value")),
+            ]);
+            let len = pipeline.len();
+            cmd.encode_buf(&mut pipeline);
+            max_cmd_len = max_cmd_len.max(pipeline.len() - len);
+        }
+        tokio::io::AsyncWriteExt::write_all(&mut client, &pipeline)
+            .await
+            .unwrap();
+
+        let mut conn = crate::connection::Connection::new(client, 0);
+        for _ in 0..n {
+            assert_eq!(
+                conn.read_frame().await.unwrap().unwrap(),
+                Resp3::new_simple_string("OK".into())
+            );
+        }
+
+        // 每次传播的都是完整的命令，且不超过上限加上一个命令的长度
+        let mut decoder = RESP3Decoder::default();
+        let mut received = 0;
+        while received < n {
+            let mut buf = tokio::time::timeout(Duration::from_secs(1), replica_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(buf.len() < max_wcmd_buf + max_cmd_len);
+
+            while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+                let args = frame.try_array().unwrap();
+                assert_eq!(
+                    args[1],
+                    Resp3::new_blob_string(format!("key{received}").into())
+                );
+                received += 1;
+            }
+            assert!(buf.is_empty());
         }
+        assert!(replica_rx.try_recv().unwrap().is_none());
     }
 }
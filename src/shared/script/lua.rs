@@ -1,5 +1,5 @@
 use crate::{
-    cmd::{CmdError, ServerErrSnafu},
+    cmd::{CmdError, Err, ServerErrSnafu},
     conf::{AccessControl, DEFAULT_USER},
     connection::{AsyncStream, FakeStream},
     frame::Resp3,
@@ -337,9 +337,14 @@ impl LuaScript {
                     // 脚本的用户与客户端的用户一致
                     fake_handler.context.user = user;
 
-                    let mut intention_locks = Vec::with_capacity(keys.len());
-                    // 给需要操作的键加上意向锁
-                    for key in &keys {
+                    // 给需要操作的键加上意向锁。按键的顺序加锁并去重，与EXEC保持一致，
+                    // 避免交叉加锁导致死锁
+                    let mut lock_keys: Vec<&Key> = keys.iter().collect();
+                    lock_keys.sort_unstable();
+                    lock_keys.dedup();
+
+                    let mut intention_locks = Vec::with_capacity(lock_keys.len());
+                    for key in lock_keys {
                         if let Some(notify_unlock) = shared
                             .db()
                             .add_lock_event(key.clone(), fake_handler.context.client_id)
@@ -400,7 +405,7 @@ impl LuaScript {
     ) -> Result<Resp3, CmdError> {
         let chunk = match self.lua_scripts.get(&script_name) {
             Some(script) => script.clone(),
-            None => return Err("script not found".into()),
+            None => return Err(Err::NoScript.into()),
         };
 
         self.eval(handler, chunk, keys, argv)
@@ -423,7 +428,7 @@ impl LuaScript {
                 entry.insert(chunk);
                 Ok(())
             }
-            Entry::Occupied(_) => Err("script already exists".into()),
+            Entry::Occupied(_) => Err("ERR script already exists".into()),
         }
     }
 
@@ -431,7 +436,7 @@ impl LuaScript {
     pub fn remove_script(&self, script_name: Bytes) -> Result<(), CmdError> {
         match self.lua_scripts.remove(&script_name) {
             Some(_) => Ok(()),
-            None => Err("script not found".into()),
+            None => Err(Err::NoScript.into()),
         }
     }
 }
@@ -5,7 +5,11 @@ pub use test::*;
 use crate::Int;
 use anyhow::anyhow;
 use atoi::FromRadix10SignedChecked;
-use std::{num::ParseFloatError, time::SystemTime};
+use rand::Rng;
+use std::{
+    num::ParseFloatError,
+    time::{Duration, SystemTime},
+};
 use tokio::time::Instant;
 
 // 模拟服务端，接收客户端的命令并打印
@@ -79,6 +83,67 @@ pub async fn fake_client() {
     }
 }
 
+/// 全局分配器(mimalloc)提交的内存字节数，返回(当前值, 峰值)
+pub fn allocated_memory() -> (usize, usize) {
+    let mut info = [0usize; 8];
+    let [elapsed, user, system, rss, peak_rss, commit, peak_commit, faults] = &mut info;
+    // SAFETY: 所有指针都指向有效的usize
+    unsafe {
+        libmimalloc_sys::mi_process_info(
+            elapsed,
+            user,
+            system,
+            rss,
+            peak_rss,
+            commit,
+            peak_commit,
+            faults,
+        );
+    }
+
+    // 统计由各线程汇总而来，内存在其它线程释放时当前值可能暂时为负数。mimalloc在Linux上
+    // 以提交的内存作为当前RSS，因此只能以操作系统报告的RSS峰值代替
+    let commit = if (info[5] as isize) <= 0 {
+        info[4]
+    } else {
+        info[5]
+    };
+    // 同理，峰值可能短暂地小于当前值
+    (commit, info[6].max(commit))
+}
+
+/// 从[0, len)中随机选取索引(HRANDFIELD等命令的count参数)。count为正数时选取不重复的
+/// min(count, len)个索引；count为负数时选取|count|个索引，可能重复
+pub fn random_indices(len: usize, count: Int) -> Vec<usize> {
+    if len == 0 {
+        return vec![];
+    }
+
+    let mut rng = rand::thread_rng();
+    if count < 0 {
+        return (0..count.unsigned_abs())
+            .map(|_| rng.gen_range(0..len))
+            .collect();
+    }
+
+    // 只打乱前count个位置的Fisher-Yates洗牌
+    let count = (count as usize).min(len);
+    let mut indices: Vec<usize> = (0..len).collect();
+    for i in 0..count {
+        indices.swap(i, rng.gen_range(i..len));
+    }
+    indices.truncate(count);
+    indices
+}
+
+/// 生成由40个随机的十六进制字符组成的ID，用作run_id以及replid
+pub fn gen_run_id() -> String {
+    let mut rng = rand::thread_rng();
+    (0..40)
+        .map(|_| char::from_digit(rng.gen_range(0..16), 16).unwrap())
+        .collect()
+}
+
 #[inline]
 pub fn epoch() -> Instant {
     Instant::now()
@@ -87,6 +152,31 @@ pub fn epoch() -> Instant {
             .unwrap()
 }
 
+/// 以秒计的LRU时钟，用于记录对象最近一次被访问的时间
+#[inline]
+pub fn get_lru_clock() -> u32 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
+}
+
+/// 将时间点转换为以毫秒计的Unix时间戳，用于将命令改写为确定性的形式后再传播
+pub fn unix_millis(instant: Instant) -> bytes::Bytes {
+    let millis = instant.saturating_duration_since(epoch()).as_millis();
+    itoa::Buffer::new().format(millis).to_owned().into()
+}
+
+/// 将客户端给出的超时时间(秒)转换为截止时间，0表示永不超时
+#[inline]
+pub fn timeout_to_deadline(timeout_secs: u64) -> Option<Instant> {
+    if timeout_secs == 0 {
+        None
+    } else {
+        Some(Instant::now() + Duration::from_secs(timeout_secs))
+    }
+}
+
 pub fn atoi<I: FromRadix10SignedChecked>(text: &[u8]) -> Result<I, String> {
     atoi::atoi(text).ok_or_else(|| {
         format!(
@@ -115,7 +205,7 @@ pub fn uppercase(src: &[u8], buf: &mut [u8]) -> anyhow::Result<usize> {
     Ok(len)
 }
 
-pub fn get_uppercase<'a, 'b>(src: &'a [u8], buf: &'b mut [u8]) -> anyhow::Result<&'b [u8]> {
+pub fn get_uppercase<'b>(src: &[u8], buf: &'b mut [u8]) -> anyhow::Result<&'b [u8]> {
     let len = src.len();
     if len > buf.len() {
         return Err(anyhow!("buffer is too small"));
@@ -127,6 +217,134 @@ pub fn get_uppercase<'a, 'b>(src: &'a [u8], buf: &'b mut [u8]) -> anyhow::Result
     Ok(&buf[..len])
 }
 
+/// 判断string是否匹配glob风格的pattern，行为与Redis的stringmatchlen()一致。支持`*`、
+/// `?`、`[...]`(包括`^`取反与`a-z`范围)以及`\`转义
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let mut skip_longer_matches = false;
+    glob_match_impl(pattern, string, nocase, &mut skip_longer_matches, 0)
+}
+
+fn glob_match_impl(
+    pattern: &[u8],
+    string: &[u8],
+    nocase: bool,
+    skip_longer_matches: &mut bool,
+    nesting: usize,
+) -> bool {
+    // 限制递归深度，防止病态的模式导致栈溢出
+    if nesting > 1000 {
+        return false;
+    }
+
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    let (mut p, mut s) = (0, 0);
+    while p < pattern.len() && s < string.len() {
+        match pattern[p] {
+            b'*' => {
+                while p + 1 < pattern.len() && pattern[p + 1] == b'*' {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                while s < string.len() {
+                    if glob_match_impl(
+                        &pattern[p + 1..],
+                        &string[s..],
+                        nocase,
+                        skip_longer_matches,
+                        nesting + 1,
+                    ) {
+                        return true;
+                    }
+                    // 剩余的模式在更短的字符串上都无法匹配，则更长的字符串也无法匹配
+                    if *skip_longer_matches {
+                        return false;
+                    }
+                    s += 1;
+                }
+                *skip_longer_matches = true;
+                return false;
+            }
+            b'?' => s += 1,
+            b'[' => {
+                p += 1;
+                let not = pattern.get(p) == Some(&b'^');
+                if not {
+                    p += 1;
+                }
+
+                let mut matched = false;
+                loop {
+                    if p >= pattern.len() {
+                        // 缺少`]`时，将模式的最后一个字符视为`]`
+                        p -= 1;
+                        break;
+                    } else if pattern[p] == b'\\' && pattern.len() - p >= 2 {
+                        p += 1;
+                        if pattern[p] == string[s] {
+                            matched = true;
+                        }
+                    } else if pattern[p] == b']' {
+                        break;
+                    } else if pattern.len() - p >= 3 && pattern[p + 1] == b'-' {
+                        let (mut start, mut end, mut c) = (pattern[p], pattern[p + 2], string[s]);
+                        if start > end {
+                            std::mem::swap(&mut start, &mut end);
+                        }
+                        if nocase {
+                            start = start.to_ascii_lowercase();
+                            end = end.to_ascii_lowercase();
+                            c = c.to_ascii_lowercase();
+                        }
+                        p += 2;
+                        if (start..=end).contains(&c) {
+                            matched = true;
+                        }
+                    } else if eq(pattern[p], string[s]) {
+                        matched = true;
+                    }
+                    p += 1;
+                }
+
+                if matched == not {
+                    return false;
+                }
+                s += 1;
+            }
+            c => {
+                let c = if c == b'\\' && pattern.len() - p >= 2 {
+                    p += 1;
+                    pattern[p]
+                } else {
+                    c
+                };
+                if !eq(c, string[s]) {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+
+        p += 1;
+        if s == string.len() {
+            while p < pattern.len() && pattern[p] == b'*' {
+                p += 1;
+            }
+            break;
+        }
+    }
+
+    p == pattern.len() && s == string.len()
+}
+
 pub fn to_valid_range(start: Int, end: Int, len: usize) -> Option<(usize, usize)> {
     if start == 0 || end == 0 {
         return None;
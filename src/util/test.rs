@@ -1,7 +1,22 @@
+#[cfg(test)]
+use crate::frame::Resp3;
+#[cfg(test)]
+use bytes::Bytes;
 use tracing::Level;
 
 pub fn test_init() {
     tracing_subscriber::fmt()
         .with_max_level(Level::DEBUG)
-        .init();
+        .try_init()
+        .ok();
+}
+
+/// 由命令名和参数构造命令帧，例如`cmd(&["SET", "key", "value"])`
+#[cfg(test)]
+pub fn cmd(args: &[&str]) -> Resp3 {
+    Resp3::new_array(
+        args.iter()
+            .map(|s| Resp3::new_blob_string(Bytes::copy_from_slice(s.as_bytes())))
+            .collect::<Vec<_>>(),
+    )
 }